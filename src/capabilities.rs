@@ -0,0 +1,240 @@
+use std::{collections::HashSet, process::Command};
+
+use crate::{containers, logging::LogLaunch, processing, Config};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Features the installed ffmpeg was built with.
+pub struct Capabilities {
+    filters: HashSet<String>,
+    encoders: HashSet<String>,
+}
+
+impl Capabilities {
+    /// Features of the ffmpeg `conf` runs, see `Config::ffmpeg_path`.
+    pub fn detect(conf: &Config) -> Result<Self> {
+        let ffmpeg = processing::ffmpeg_program(conf);
+        Ok(Self::from_listings(
+            &query_ffmpeg(&ffmpeg, "-filters")?,
+            &query_ffmpeg(&ffmpeg, "-encoders")?,
+        ))
+    }
+
+    /// From the output of `ffmpeg -filters` and `ffmpeg -encoders`.
+    pub(crate) fn from_listings(filters: &str, encoders: &str) -> Self {
+        Self {
+            filters: parse_filters(filters),
+            encoders: parse_encoders(encoders),
+        }
+    }
+
+    pub fn has_filter(&self, name: &str) -> bool {
+        self.filters.contains(name)
+    }
+
+    pub fn has_encoder(&self, name: &str) -> bool {
+        self.encoders.contains(name)
+    }
+
+    /// First available encoder of the fallback chain of `codec` a file with
+    /// `extension` can hold, if any.
+    pub fn available_fallback(&self, codec: &str, extension: &str) -> Option<&'static str> {
+        let mut fallback = container_fallback(codec, extension)?;
+        while !self.has_encoder(fallback) {
+            fallback = container_fallback(fallback, extension)?;
+        }
+        Some(fallback)
+    }
+}
+
+/// Encoder used instead of `codec` when ffmpeg is built without it. Fallbacks
+/// are chained: libopus falls back to libvorbis, and then to aac.
+pub fn fallback_encoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "libopus" => Some("libvorbis"),
+        "libvorbis" => Some("aac"),
+        "libfdk_aac" => Some("aac"),
+        "libx265" => Some("libx264"),
+        _ => None,
+    }
+}
+
+/// First encoder of the fallback chain of `codec` a file with `extension` can
+/// hold: mp4 outputs skip libvorbis and fall back from libopus to aac. Empty
+/// extensions and unlisted codecs are not checked.
+pub fn container_fallback(codec: &str, extension: &str) -> Option<&'static str> {
+    let extension = extension.to_lowercase();
+    let mut fallback = fallback_encoder(codec)?;
+    while !extension.is_empty()
+        && containers::codec_containers(fallback).map_or(false, |containers| {
+            !containers.contains(&extension.as_str())
+        })
+    {
+        fallback = fallback_encoder(fallback)?;
+    }
+    Some(fallback)
+}
+
+fn query_ffmpeg(ffmpeg: &str, option: &str) -> Result<String> {
+    let output = Command::new(ffmpeg)
+        .arg("-hide_banner")
+        .arg(option)
+        .logged()
+        .output()
+        .map_err(|e| format!("Failed to query ffmpeg capabilities.\nError: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to query ffmpeg capabilities.\nError output: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// parse the listing printed by `ffmpeg -filters`, lines look like:
+// ` TSC vidstabdetect     V->V       Extract relative transformations...`
+fn parse_filters(listing: &str) -> HashSet<String> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let _flags = columns.next()?;
+            let name = columns.next()?;
+            let io = columns.next()?;
+            if io.contains("->") {
+                Some(name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// parse the listing printed by `ffmpeg -encoders`, encoders follow the legend
+// after a ` ------` line and look like:
+// ` A....D libopus              libopus Opus (codec opus)`
+fn parse_encoders(listing: &str) -> HashSet<String> {
+    listing
+        .lines()
+        .skip_while(|line| line.trim() != "------")
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS_LISTING: &str = "Filters:
+  T.. = Timeline support
+  .S. = Slice threading
+  ..C = Command support
+  A = Audio input/output
+  V = Video input/output
+  N = Dynamic number and/or type of input/output
+  | = Source or sink filter
+ ... abench            A->A       Benchmark part of a filtergraph.
+ T.C highpass          A->A       Apply a high-pass filter with 3dB point frequency.
+ ... vidstabdetect     V->V       Extract relative transformations, pass 1 of 2 for stabilization (see vidstabtransform for pass 2).
+ .S. vidstabtransform  V->V       Transform the frames, pass 2 of 2 for stabilization (see vidstabdetect for pass 1).
+ ... anullsrc          |->A       Null audio source, return empty audio frames.
+";
+
+    #[test]
+    fn parse_filters_listing() {
+        let filters = parse_filters(FILTERS_LISTING);
+        assert_eq!(filters.len(), 5);
+        assert!(filters.contains("highpass"));
+        assert!(filters.contains("vidstabdetect"));
+        assert!(filters.contains("vidstabtransform"));
+        assert!(filters.contains("anullsrc"));
+        assert!(!filters.contains("Timeline"));
+        assert!(!filters.contains("="));
+    }
+
+    const ENCODERS_LISTING: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D png                  PNG (Portable Network Graphics) image
+ A....D aac                  AAC (Advanced Audio Coding)
+ A....D libvorbis            libvorbis (codec vorbis)
+ S..... srt                  SubRip subtitle
+";
+
+    #[test]
+    fn parse_encoders_listing() {
+        let capabilities = Capabilities::from_listings("", ENCODERS_LISTING);
+        assert_eq!(capabilities.encoders.len(), 5);
+        assert!(capabilities.has_encoder("libx264"));
+        assert!(capabilities.has_encoder("srt"));
+        assert!(!capabilities.has_encoder("libopus"));
+        assert!(!capabilities.has_encoder("Video"));
+        assert!(!capabilities.has_encoder("="));
+    }
+
+    #[test]
+    fn encoder_fallbacks() {
+        assert_eq!(fallback_encoder("libopus"), Some("libvorbis"));
+        assert_eq!(fallback_encoder("libvorbis"), Some("aac"));
+        assert_eq!(fallback_encoder("libx265"), Some("libx264"));
+        assert_eq!(fallback_encoder("libfdk_aac"), Some("aac"));
+        assert_eq!(fallback_encoder("aac"), None);
+        assert_eq!(fallback_encoder("copy"), None);
+
+        let capabilities = Capabilities::from_listings("", ENCODERS_LISTING);
+        assert_eq!(
+            capabilities.available_fallback("libopus", "mkv"),
+            Some("libvorbis")
+        );
+        assert_eq!(
+            capabilities.available_fallback("libx265", "mp4"),
+            Some("libx264")
+        );
+        assert_eq!(capabilities.available_fallback("libvpx-vp9", "webm"), None);
+
+        // libvorbis missing too: the chain goes on to aac
+        let capabilities = Capabilities::from_listings("", " ------\n A....D aac  AAC\n");
+        assert_eq!(
+            capabilities.available_fallback("libopus", "mkv"),
+            Some("aac")
+        );
+        let capabilities = Capabilities::from_listings("", " ------\n");
+        assert_eq!(capabilities.available_fallback("libopus", "mkv"), None);
+    }
+
+    #[test]
+    fn container_fallbacks() {
+        // mp4 cannot hold vorbis
+        assert_eq!(container_fallback("libopus", "mp4"), Some("aac"));
+        assert_eq!(container_fallback("libopus", "MKV"), Some("libvorbis"));
+        assert_eq!(container_fallback("libopus", ""), Some("libvorbis"));
+        // nor can an ogg file hold aac
+        assert_eq!(container_fallback("libopus", "ogg"), Some("libvorbis"));
+        assert_eq!(container_fallback("libvorbis", "ogg"), None);
+        assert_eq!(container_fallback("libx265", "mov"), Some("libx264"));
+
+        let capabilities = Capabilities::from_listings("", ENCODERS_LISTING);
+        assert_eq!(
+            capabilities.available_fallback("libopus", "mp4"),
+            Some("aac")
+        );
+        assert_eq!(
+            capabilities.available_fallback("libopus", "ogg"),
+            Some("libvorbis")
+        );
+        let capabilities = Capabilities::from_listings("", " ------\n A....D aac  AAC\n");
+        assert_eq!(capabilities.available_fallback("libopus", "ogg"), None);
+    }
+}
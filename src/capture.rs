@@ -0,0 +1,376 @@
+use std::{
+    env,
+    fs::remove_file,
+    io::Read,
+    process::{Command, Stdio},
+    thread,
+};
+
+use chrono::Duration;
+
+use crate::{
+    duration_to_string,
+    logging::LogLaunch,
+    processing::{self, CancelFlag, CANCELLED_ERROR},
+    Config,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+// how often a recording checks whether it is cancelled
+const CANCEL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// Audio input ffmpeg can record from, e.g. a microphone.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Device {
+    /// Input format given with `-f`: `pulse`, `alsa`, `avfoundation` or `dshow`.
+    pub format: &'static str,
+    /// Input given with `-i`.
+    pub name: String,
+    /// Name shown to the user.
+    pub description: String,
+}
+
+/// Audio inputs of this computer, the default one first when known. They are
+/// listed by the ffmpeg `conf` runs, see `Config::ffmpeg_path`.
+pub fn list_devices(conf: &Config) -> Result<Vec<Device>> {
+    let query_ffmpeg = |args: &[&str]| query_ffmpeg(&processing::ffmpeg_program(conf), args);
+    match env::consts::OS {
+        // avfoundation and dshow list their devices in the log of a failed run
+        "macos" => Ok(parse_avfoundation_devices(&query_ffmpeg(&[
+            "-f",
+            "avfoundation",
+            "-list_devices",
+            "true",
+            "-i",
+            "",
+        ])?)),
+        "windows" => Ok(parse_dshow_devices(&query_ffmpeg(&[
+            "-f",
+            "dshow",
+            "-list_devices",
+            "true",
+            "-i",
+            "dummy",
+        ])?)),
+        // without a PulseAudio server, ALSA is asked
+        _ => match query_ffmpeg(&["-sources", "pulse"]) {
+            Ok(listing) if !parse_sources("pulse", &listing).is_empty() => {
+                Ok(parse_sources("pulse", &listing))
+            }
+            _ => Ok(parse_sources("alsa", &query_ffmpeg(&["-sources", "alsa"])?)),
+        },
+    }
+}
+
+/// Record `duration` of `device` to `output_file`, replacing it, with the
+/// ffmpeg `conf` runs.
+pub fn record(conf: &Config, device: &Device, duration: Duration, output_file: &str) -> Result<()> {
+    record_cancellable(conf, device, duration, output_file, &CancelFlag::new())
+}
+
+/// As `record`, stopped once `cancel` is set: nothing is left in
+/// `output_file` then.
+pub fn record_cancellable(
+    conf: &Config,
+    device: &Device,
+    duration: Duration,
+    output_file: &str,
+    cancel: &CancelFlag,
+) -> Result<()> {
+    let failed = |e: String| {
+        format!(
+            "Failed to record from {}.\nError: {}",
+            device.description, e
+        )
+    };
+    let mut child = Command::new(processing::ffmpeg_program(conf))
+        .args(make_record_args(device, duration, output_file))
+        .logged()
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+
+    // drained on its own thread: ffmpeg blocks once the pipe is full
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut contents = Vec::new();
+        let _ = stderr.read_to_end(&mut contents);
+        contents
+    });
+
+    let status = loop {
+        if cancel.is_cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = remove_file(output_file);
+            return Err(String::from(CANCELLED_ERROR));
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => thread::sleep(CANCEL_POLL_INTERVAL),
+            Err(e) => return Err(failed(e.to_string())),
+        }
+    };
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Failed to record from {}.\nError output: {}",
+            device.description,
+            String::from_utf8_lossy(&stderr)
+        ))
+    }
+}
+
+fn make_record_args(device: &Device, duration: Duration, output_file: &str) -> Vec<String> {
+    vec![
+        String::from("-y"),
+        String::from("-f"),
+        String::from(device.format),
+        String::from("-i"),
+        device.name.clone(),
+        String::from("-t"),
+        duration_to_string(duration),
+        String::from(output_file),
+    ]
+}
+
+// both outputs: `-sources` prints to stdout, device listings are logged
+fn query_ffmpeg(ffmpeg: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(ffmpeg)
+        .arg("-hide_banner")
+        .args(args)
+        .logged()
+        .output()
+        .map_err(|e| format!("Failed to list the audio devices.\nError: {}", e))?;
+
+    Ok(format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+// parse the listing printed by `ffmpeg -sources pulse`, the default source is
+// starred:
+// `* alsa_input.pci-0000_00_1f.3.analog-stereo [Built-in Audio Analog Stereo]`
+fn parse_sources(format: &'static str, listing: &str) -> Vec<Device> {
+    let mut devices: Vec<(bool, Device)> = listing
+        .lines()
+        .filter(|line| line.starts_with(' ') || line.starts_with('*'))
+        .filter_map(|line| {
+            let is_default = line.starts_with('*');
+            let line = line.trim_start_matches('*').trim();
+            let (name, description) = match line.find(" [") {
+                Some(index) if line.ends_with(']') => {
+                    (&line[..index], &line[index + 2..line.len() - 1])
+                }
+                _ => (line, line),
+            };
+            if name.is_empty() {
+                return None;
+            }
+            Some((
+                is_default,
+                Device {
+                    format,
+                    name: String::from(name),
+                    description: String::from(description),
+                },
+            ))
+        })
+        .collect();
+    // stable: the other sources keep their order
+    devices.sort_by_key(|(is_default, _)| !is_default);
+    devices.into_iter().map(|(_, device)| device).collect()
+}
+
+// parse the log of `ffmpeg -f avfoundation -list_devices true -i ""`, audio
+// devices follow the video ones and are chosen by index:
+// `[AVFoundation indev @ 0x7f8b] [0] MacBook Pro Microphone`
+fn parse_avfoundation_devices(log: &str) -> Vec<Device> {
+    log.lines()
+        .skip_while(|line| !line.ends_with("audio devices:"))
+        .skip(1)
+        .filter_map(|line| {
+            let (_, entry) = line.split_once("] ")?;
+            let index_end = entry.find("] ")?;
+            let index = entry.strip_prefix('[')?.get(..index_end - 1)?;
+            index.parse::<u32>().ok()?;
+            Some(Device {
+                format: "avfoundation",
+                name: format!(":{}", index),
+                description: String::from(&entry[index_end + 2..]),
+            })
+        })
+        .collect()
+}
+
+// parse the log of `ffmpeg -f dshow -list_devices true -i dummy`, either with
+// sections (before ffmpeg 4.4):
+// `[dshow @ 0000] DirectShow audio devices`
+// `[dshow @ 0000]  "Microphone (Realtek Audio)"`
+// or with the type after each device:
+// `[dshow @ 0000] "Microphone (Realtek Audio)" (audio)`
+fn parse_dshow_devices(log: &str) -> Vec<Device> {
+    let mut in_audio_section = false;
+    let mut devices = Vec::new();
+    for line in log.lines() {
+        let entry = match line.split_once("] ") {
+            Some((_, entry)) => entry.trim(),
+            None => continue,
+        };
+        if entry.starts_with("DirectShow") {
+            // the video section may mention audio devices too
+            in_audio_section = entry.starts_with("DirectShow audio devices");
+            continue;
+        }
+        // alternative names come below each device
+        if !entry.starts_with('"') {
+            continue;
+        }
+        let name_end = match entry[1..].find('"') {
+            Some(index) => index + 1,
+            None => continue,
+        };
+        let name = &entry[1..name_end];
+        let kind = entry[name_end + 1..].trim();
+        if kind == "(audio)" || (kind.is_empty() && in_audio_section) {
+            devices.push(Device {
+                format: "dshow",
+                name: format!("audio={}", name),
+                description: String::from(name),
+            });
+        }
+    }
+    devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pulse_sources() {
+        let listing = "Auto-detected sources for pulse:
+  alsa_output.pci-0000_00_1f.3.analog-stereo.monitor [Monitor of Built-in Audio Analog Stereo]
+* alsa_input.pci-0000_00_1f.3.analog-stereo [Built-in Audio Analog Stereo]
+  bluez_source.00_1B_66 [Headset]
+";
+        let devices = parse_sources("pulse", listing);
+        assert_eq!(devices.len(), 3);
+        assert_eq!(
+            devices[0],
+            Device {
+                format: "pulse",
+                name: String::from("alsa_input.pci-0000_00_1f.3.analog-stereo"),
+                description: String::from("Built-in Audio Analog Stereo"),
+            }
+        );
+        assert_eq!(
+            devices[1].description,
+            "Monitor of Built-in Audio Analog Stereo"
+        );
+        assert_eq!(devices[2].name, "bluez_source.00_1B_66");
+
+        // the header alone when nothing is found
+        assert!(parse_sources("alsa", "Auto-detected sources for alsa:\n").is_empty());
+        let devices = parse_sources("alsa", "Auto-detected sources for alsa:\n  default\n");
+        assert_eq!(devices[0].name, "default");
+        assert_eq!(devices[0].description, "default");
+    }
+
+    #[test]
+    fn avfoundation_devices() {
+        let log = "[AVFoundation indev @ 0x7f8b4c] AVFoundation video devices:
+[AVFoundation indev @ 0x7f8b4c] [0] FaceTime HD Camera
+[AVFoundation indev @ 0x7f8b4c] [1] Capture screen 0
+[AVFoundation indev @ 0x7f8b4c] AVFoundation audio devices:
+[AVFoundation indev @ 0x7f8b4c] [0] MacBook Pro Microphone
+[AVFoundation indev @ 0x7f8b4c] [1] USB Audio [Mic]
+: Input/output error
+";
+        assert_eq!(
+            parse_avfoundation_devices(log),
+            vec![
+                Device {
+                    format: "avfoundation",
+                    name: String::from(":0"),
+                    description: String::from("MacBook Pro Microphone"),
+                },
+                Device {
+                    format: "avfoundation",
+                    name: String::from(":1"),
+                    description: String::from("USB Audio [Mic]"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dshow_devices() {
+        let sections =
+            "[dshow @ 0000018c] DirectShow video devices (some may be both video and audio devices)
+[dshow @ 0000018c]  \"Integrated Camera\"
+[dshow @ 0000018c]     Alternative name \"@device_pnp_\\\\?\\usb#vid\"
+[dshow @ 0000018c] DirectShow audio devices
+[dshow @ 0000018c]  \"Microphone (Realtek Audio)\"
+[dshow @ 0000018c]     Alternative name \"@device_cm_{33D9A762}\\wave_{A1B2}\"
+dummy: Immediate exit requested
+";
+        let typed = "[dshow @ 0000018c] \"Integrated Camera\" (video)
+[dshow @ 0000018c]   Alternative name \"@device_pnp_\\\\?\\usb#vid\"
+[dshow @ 0000018c] \"Microphone (Realtek Audio)\" (audio)
+[dshow @ 0000018c]   Alternative name \"@device_cm_{33D9A762}\\wave_{A1B2}\"
+";
+        for log in &[sections, typed] {
+            assert_eq!(
+                parse_dshow_devices(log),
+                vec![Device {
+                    format: "dshow",
+                    name: String::from("audio=Microphone (Realtek Audio)"),
+                    description: String::from("Microphone (Realtek Audio)"),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn record_args() {
+        let device = Device {
+            format: "pulse",
+            name: String::from("default"),
+            description: String::from("Default"),
+        };
+        assert_eq!(
+            make_record_args(&device, Duration::seconds(5), "room tone.wav"),
+            vec![
+                "-y",
+                "-f",
+                "pulse",
+                "-i",
+                "default",
+                "-t",
+                "0:0:5.0",
+                "room tone.wav"
+            ]
+        );
+
+        let device = Device {
+            format: "dshow",
+            name: String::from("audio=Microphone (Realtek Audio)"),
+            description: String::from("Microphone (Realtek Audio)"),
+        };
+        let args = make_record_args(&device, Duration::milliseconds(2500), "noise.wav");
+        assert_eq!(
+            &args[1..5],
+            ["-f", "dshow", "-i", "audio=Microphone (Realtek Audio)"]
+        );
+        assert_eq!(args[6], "0:0:2.500");
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    project::{self, Marker},
+    silence::{self, Segment},
+};
+
+/// Named part of the output shown by players, see `Config::write_chapters`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Chapter {
+    #[serde(with = "project::millis")]
+    pub start: Duration,
+    #[serde(with = "project::millis")]
+    pub end: Duration,
+    pub title: String,
+}
+
+/// One chapter per marker, lasting until the next one or `end` for the last.
+pub fn from_markers(markers: &[Marker], end: Duration) -> Vec<Chapter> {
+    let mut markers: Vec<&Marker> = markers.iter().collect();
+    markers.sort_by_key(|marker| marker.time);
+
+    let mut chapters = Vec::with_capacity(markers.len());
+    for (index, marker) in markers.iter().enumerate() {
+        let chapter_end = markers.get(index + 1).map_or(end, |next| next.time);
+        if chapter_end > marker.time {
+            chapters.push(Chapter {
+                start: marker.time,
+                end: chapter_end,
+                title: marker.name.clone(),
+            });
+        }
+    }
+    chapters
+}
+
+/// `chapters` of the input moved to the output timeline: the selection starts
+/// at `from_time` and ends at `to_time`, if any, and `segments` give the speed
+/// of each part of it when silences are sped up. Chapters outside the
+/// selection are left out, those crossing its bounds are shortened.
+pub fn rebase(
+    chapters: &[Chapter],
+    from_time: Duration,
+    to_time: Option<Duration>,
+    segments: &[Segment],
+) -> Vec<Chapter> {
+    let output_time = |time: Duration| {
+        let mut time = (time - from_time).max(Duration::zero());
+        if let Some(to_time) = to_time {
+            time = time.min(to_time - from_time);
+        }
+        if segments.is_empty() {
+            time
+        } else {
+            let secs = silence::output_time(segments, time.num_milliseconds() as f64 / 1000.0);
+            Duration::milliseconds((secs * 1000.0).round() as i64)
+        }
+    };
+
+    chapters
+        .iter()
+        .map(|chapter| Chapter {
+            start: output_time(chapter.start),
+            end: output_time(chapter.end),
+            title: chapter.title.clone(),
+        })
+        .filter(|chapter| chapter.end > chapter.start)
+        .collect()
+}
+
+/// ffmpeg metadata file declaring `chapters`, given to `-map_chapters`.
+pub fn to_ffmetadata(chapters: &[Chapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start.num_milliseconds(),
+            chapter.end.num_milliseconds(),
+            escape_metadata(&chapter.title)
+        ));
+    }
+    metadata
+}
+
+// `=`, `;`, `#`, backslashes and newlines are special in metadata files
+fn escape_metadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if let '=' | ';' | '#' | '\\' | '\n' = c {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(start: i64, end: i64, title: &str) -> Chapter {
+        Chapter {
+            start: Duration::seconds(start),
+            end: Duration::seconds(end),
+            title: String::from(title),
+        }
+    }
+
+    #[test]
+    fn ffmetadata_serialisation() {
+        assert_eq!(to_ffmetadata(&[]), ";FFMETADATA1\n");
+        assert_eq!(
+            to_ffmetadata(&[chapter(0, 5, "Intro"), chapter(5, 65, "Part 1")]),
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=5000\ntitle=Intro\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=5000\nEND=65000\ntitle=Part 1\n"
+        );
+    }
+
+    #[test]
+    fn title_escaping() {
+        assert_eq!(escape_metadata("Q&A"), "Q&A");
+        assert_eq!(escape_metadata("a=b; c"), "a\\=b\\; c");
+        assert_eq!(escape_metadata("#1 \\o/"), "\\#1 \\\\o/");
+        assert_eq!(escape_metadata("two\nlines"), "two\\\nlines");
+        assert!(to_ffmetadata(&[chapter(0, 1, "x=1")]).contains("title=x\\=1\n"));
+    }
+
+    #[test]
+    fn rebasing() {
+        let chapters = [
+            chapter(0, 10, "before"),
+            chapter(10, 30, "crossing start"),
+            chapter(30, 40, "inside"),
+            chapter(40, 70, "crossing end"),
+            chapter(70, 80, "after"),
+        ];
+        assert_eq!(
+            rebase(
+                &chapters,
+                Duration::seconds(20),
+                Some(Duration::seconds(50)),
+                &[]
+            ),
+            vec![
+                chapter(0, 10, "crossing start"),
+                chapter(10, 20, "inside"),
+                chapter(20, 30, "crossing end"),
+            ]
+        );
+
+        // whole file
+        assert_eq!(
+            rebase(&chapters, Duration::zero(), None, &[]),
+            chapters.to_vec()
+        );
+    }
+
+    #[test]
+    fn rebasing_sped_up_silences() {
+        // 10s of speech, 20s of silence played 4 times faster, 10s of speech
+        let segments = [
+            Segment {
+                start: 0.0,
+                end: 10.0,
+                speed: 1.0,
+            },
+            Segment {
+                start: 10.0,
+                end: 30.0,
+                speed: 4.0,
+            },
+            Segment {
+                start: 30.0,
+                end: 40.0,
+                speed: 1.0,
+            },
+        ];
+        let chapters = [chapter(100, 110, "speech"), chapter(110, 140, "the rest")];
+        assert_eq!(
+            rebase(
+                &chapters,
+                Duration::seconds(100),
+                Some(Duration::seconds(140)),
+                &segments
+            ),
+            vec![chapter(0, 10, "speech"), chapter(10, 25, "the rest")]
+        );
+    }
+
+    #[test]
+    fn chapters_from_markers() {
+        let marker = |time: i64, name: &str| Marker {
+            time: Duration::seconds(time),
+            name: String::from(name),
+        };
+        let markers = [marker(60, "Part 2"), marker(0, "Part 1"), marker(90, "End")];
+        assert_eq!(
+            from_markers(&markers, Duration::seconds(90)),
+            vec![chapter(0, 60, "Part 1"), chapter(60, 90, "Part 2")]
+        );
+        assert!(from_markers(&[], Duration::seconds(90)).is_empty());
+    }
+}
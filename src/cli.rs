@@ -0,0 +1,352 @@
+//! Tasks run from the command line without opening the window, e.g.
+//! `media-cutter --input talk.mkv --from 1:00 --to 2:30 --export-cutlist edl`
+//! or `media-cutter --input talk.mkv --output - --format wav | analyzer`.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+use crate::{
+    cutlist::{self, CutListFormat, CutListKind},
+    parse_duration, probe, processing,
+    project::Segment,
+    Config, STDOUT_OUTPUT,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+pub const INPUT_FLAG: &str = "--input";
+/// `-` for the standard output, see `STDOUT_OUTPUT`. Alone, the selection is
+/// cut to it with the default settings.
+pub const OUTPUT_FLAG: &str = "--output";
+/// Container format of the output, needed on the standard output, see
+/// `Config::output_format`.
+pub const FORMAT_FLAG: &str = "--format";
+/// Start of the selection, see `parse_duration`. The input start if unset.
+pub const FROM_FLAG: &str = "--from";
+/// End of the selection, see `parse_duration`. Without it nor `--from`, the
+/// whole input is selected.
+pub const TO_FLAG: &str = "--to";
+/// Video stream kept from inputs with several, counted from 0 among video
+/// streams, see `Config::video_stream_index`.
+pub const VIDEO_STREAM_FLAG: &str = "--video-stream";
+/// Followed by `csv`, `edl` or `concat`, see `CutListKind`.
+pub const EXPORT_CUT_LIST_FLAG: &str = "--export-cutlist";
+/// Followed by the columns and rows of the grid, e.g. `5x6`.
+pub const CONTACT_SHEET_FLAG: &str = "--contact-sheet";
+
+/// What the command line asks for instead of the window.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Task {
+    /// Cut the selection to the output, see `processing::run`.
+    Process,
+    /// Write the selection as a cut list, see `cutlist::export`. The output
+    /// is the standard output if unset.
+    ExportCutList(CutListKind),
+    /// Render a contact sheet of the selection with these columns and rows,
+    /// see `processing::contact_sheet`.
+    ContactSheet((u32, u32)),
+}
+
+/// Task read from the command line, along with its settings.
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    pub task: Task,
+    /// Input, output and selection read from the command line, the machine
+    /// settings from the environment, see `Config::from_env`.
+    pub conf: Config,
+}
+
+/// Invocation `args` ask for, the program name left out. `None` without a
+/// task: the window opens then, and `args` are left to GTK.
+pub fn parse(args: &[String]) -> Result<Option<Invocation>> {
+    let mut task = None;
+    let mut conf = Config::from_env();
+    let mut output = None;
+    let (mut from, mut to) = (None, None);
+    let mut unknown = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Error: {} expects a value.", arg))
+        };
+        match arg.as_str() {
+            INPUT_FLAG => conf.input_file = value()?.clone(),
+            OUTPUT_FLAG => output = Some(value()?.clone()),
+            FORMAT_FLAG => conf.output_format = Some(value()?.clone()),
+            FROM_FLAG => from = Some(parse_duration(value()?)?),
+            TO_FLAG => to = Some(parse_duration(value()?)?),
+            VIDEO_STREAM_FLAG => {
+                let text = value()?;
+                conf.video_stream_index = Some(text.trim().parse().map_err(|_| {
+                    format!("Invalid video stream: \"{}\", expected a number.", text)
+                })?);
+            }
+            EXPORT_CUT_LIST_FLAG => task = Some(Task::ExportCutList(value()?.parse()?)),
+            CONTACT_SHEET_FLAG => task = Some(Task::ContactSheet(parse_grid(value()?)?)),
+            _ => unknown.push(arg.as_str()),
+        }
+    }
+
+    let task = match (task, output.is_some()) {
+        (Some(task), _) => task,
+        (None, true) => Task::Process,
+        (None, false) => return Ok(None),
+    };
+    conf.output_file = output.unwrap_or_else(|| String::from(STDOUT_OUTPUT));
+    if !unknown.is_empty() {
+        return Err(format!("Error: unknown arguments: {}.", unknown.join(" ")));
+    }
+    if conf.input_file.is_empty() {
+        return Err(format!("Error: {} is missing.", INPUT_FLAG));
+    }
+    if let Task::ContactSheet(_) = task {
+        if conf.output_file == STDOUT_OUTPUT {
+            return Err(format!(
+                "Error: the contact sheet is written to a picture file, {} is missing.",
+                OUTPUT_FLAG
+            ));
+        }
+    }
+    match (from, to) {
+        (None, None) => conf.process_whole_file = true,
+        (from, Some(to)) => {
+            conf.from_time = from.unwrap_or_else(chrono::Duration::zero);
+            conf.to_time = to;
+        }
+        (Some(_), None) => {
+            return Err(format!(
+                "Error: {} is missing, the selection needs an end.",
+                TO_FLAG
+            ))
+        }
+    }
+
+    Ok(Some(Invocation { task, conf }))
+}
+
+/// Carry `invocation` out, the warnings are returned.
+pub fn run(invocation: &Invocation) -> Result<Vec<String>> {
+    let conf = &invocation.conf;
+    match invocation.task {
+        // on the standard output, ffmpeg writes straight to ours
+        Task::Process => processing::run(conf).map(|outcome| outcome.warnings),
+        Task::ExportCutList(kind) => export_cut_list(conf, kind).map(|_| Vec::new()),
+        Task::ContactSheet(grid) => processing::contact_sheet(
+            conf,
+            grid,
+            processing::CONTACT_SHEET_THUMBNAIL_WIDTH,
+            &conf.output_file,
+        )
+        .map(|outcome| outcome.warnings),
+    }
+}
+
+// `5x6`: 5 columns, 6 rows
+fn parse_grid(text: &str) -> Result<(u32, u32)> {
+    let invalid = || {
+        format!(
+            "Invalid contact sheet grid: \"{}\", expected columns and rows such as 5x6.",
+            text
+        )
+    };
+    let mut sizes = text.trim().splitn(2, ['x', 'X']);
+    let mut size = || -> Result<u32> {
+        sizes
+            .next()
+            .and_then(|size| size.trim().parse().ok())
+            .filter(|&size| size > 0)
+            .ok_or_else(invalid)
+    };
+    Ok((size()?, size()?))
+}
+
+fn export_cut_list(conf: &Config, kind: CutListKind) -> Result<()> {
+    let info = probe::MediaInfo::probe(&conf.input_file)?;
+    let (from_time, to_time) = if conf.process_whole_file {
+        let duration = info.duration.ok_or_else(|| {
+            String::from("Error: the input duration is unknown, the selection needs an end.")
+        })?;
+        (
+            chrono::Duration::zero(),
+            chrono::Duration::milliseconds((duration * 1000.0).round() as i64),
+        )
+    } else {
+        (conf.from_time, conf.to_time)
+    };
+    let segments = [Segment {
+        from_time,
+        to_time,
+        name: String::from("Selection"),
+    }];
+
+    let format = match kind {
+        CutListKind::Csv => CutListFormat::Csv,
+        CutListKind::Edl => CutListFormat::Edl {
+            fps: info
+                .streams
+                .iter()
+                .find(|stream| stream.kind == "video")
+                .and_then(|stream| stream.frame_rate)
+                .unwrap_or(cutlist::DEFAULT_FPS),
+        },
+        CutListKind::FfmpegConcat => CutListFormat::FfmpegConcat {
+            input_file: conf.input_file.clone(),
+        },
+    };
+
+    if conf.output_file == STDOUT_OUTPUT {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        cutlist::export(&segments, &format, &mut stdout)?;
+        stdout
+            .flush()
+            .map_err(|e| format!("Could not write the cut list.\nError: {}", e))
+    } else {
+        let mut file = File::create(&conf.output_file)
+            .map_err(|e| format!("Could not create the cut list file.\nError: {}", e))?;
+        cutlist::export(&segments, &format, &mut file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn export_cut_list_arguments() {
+        let invocation = parse(&args(&[
+            "--input",
+            "talk.mkv",
+            "--from",
+            "1:00",
+            "--to",
+            "2:30.5",
+            "--export-cutlist",
+            "EDL",
+            "--output",
+            "talk.edl",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(invocation.task, Task::ExportCutList(CutListKind::Edl));
+        assert_eq!(invocation.conf.input_file, "talk.mkv");
+        assert_eq!(invocation.conf.output_file, "talk.edl");
+        assert_eq!(invocation.conf.from_time, Duration::seconds(60));
+        assert_eq!(invocation.conf.to_time, Duration::milliseconds(150_500));
+        assert!(!invocation.conf.process_whole_file);
+
+        // to the standard output, the whole input
+        let invocation = parse(&args(&["--export-cutlist", "csv", "--input", "talk.mkv"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(invocation.conf.output_file, STDOUT_OUTPUT);
+        assert!(invocation.conf.process_whole_file);
+    }
+
+    #[test]
+    fn process_arguments() {
+        let invocation = parse(&args(&[
+            "--input", "talk.mkv", "--to", "30", "--output", "-", "--format", "wav",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(invocation.task, Task::Process);
+        assert_eq!(invocation.conf.output_file, STDOUT_OUTPUT);
+        assert_eq!(invocation.conf.output_format.as_deref(), Some("wav"));
+        assert_eq!(invocation.conf.from_time, Duration::zero());
+        assert_eq!(invocation.conf.to_time, Duration::seconds(30));
+        assert!(invocation.conf.writes_to_stdout());
+
+        // the angle of a multi-angle recording
+        let invocation = parse(&args(&[
+            "--input",
+            "angles.mkv",
+            "--video-stream",
+            "2",
+            "--output",
+            "angle.mkv",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(invocation.conf.video_stream_index, Some(2));
+
+        // the format is checked along with the other settings
+        let invocation = parse(&args(&["--input", "talk.mkv", "--output", "-"]))
+            .unwrap()
+            .unwrap();
+        assert!(invocation.conf.validate().is_err());
+    }
+
+    #[test]
+    fn contact_sheet_arguments() {
+        let invocation = parse(&args(&[
+            "--input",
+            "talk.mkv",
+            "--contact-sheet",
+            "5x6",
+            "--output",
+            "talk.jpg",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(invocation.task, Task::ContactSheet((5, 6)));
+        assert_eq!(invocation.conf.output_file, "talk.jpg");
+
+        assert_eq!(parse_grid(" 4X3 "), Ok((4, 3)));
+        assert!(parse_grid("4").is_err());
+        assert!(parse_grid("0x3").is_err());
+        assert!(parse_grid("4x3x2").is_err());
+        assert!(parse_grid("ax3").is_err());
+
+        // a picture cannot go to the standard output
+        assert!(parse(&args(&["--input", "talk.mkv", "--contact-sheet", "5x6"])).is_err());
+    }
+
+    #[test]
+    fn window_arguments() {
+        // no task: the window opens, GTK gets the arguments
+        assert!(parse(&[]).unwrap().is_none());
+        assert!(parse(&args(&["--input", "talk.mkv", "--gtk-debug=all"]))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let export = |extra: &[&str]| {
+            let mut all = args(&["--input", "talk.mkv", "--export-cutlist", "csv"]);
+            all.extend(args(extra));
+            parse(&all).map(|_| ())
+        };
+        assert_eq!(
+            export(&["--bogus"]),
+            Err(String::from("Error: unknown arguments: --bogus."))
+        );
+        assert_eq!(
+            export(&["--to"]),
+            Err(String::from("Error: --to expects a value."))
+        );
+        assert_eq!(
+            export(&["--from", "1:00"]),
+            Err(String::from(
+                "Error: --to is missing, the selection needs an end."
+            ))
+        );
+        assert!(export(&["--to", "soon"]).is_err());
+        assert!(export(&["--video-stream", "first"]).is_err());
+        assert!(parse(&args(&["--export-cutlist", "xml", "--input", "talk.mkv"])).is_err());
+        assert_eq!(
+            parse(&args(&["--export-cutlist", "csv"])).map(|_| ()),
+            Err(String::from("Error: --input is missing."))
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::path::Path;
+
+/// Video codecs offered for encoding, others can still be set by hand.
+pub const VIDEO_CODECS: [&str; 6] = [
+    "libx264",
+    "libx265",
+    "libvpx-vp9",
+    "libvpx",
+    "libtheora",
+    "mpeg4",
+];
+/// Audio codecs offered for encoding, others can still be set by hand.
+pub const AUDIO_CODECS: [&str; 7] = [
+    "aac",
+    "libmp3lame",
+    "libopus",
+    "libvorbis",
+    "flac",
+    "ac3",
+    "pcm_s16le",
+];
+
+/// Formats of the extracted subtitles: extension, whose muxer ffmpeg picks,
+/// and the codec it takes.
+pub const SUBTITLE_FORMATS: [(&str, &str); 3] = [("srt", "srt"), ("vtt", "webvtt"), ("ass", "ass")];
+
+// muxers writing chapters: MP4 and QuickTime atoms, Matroska, Ogg comments and ID3
+const CHAPTER_CONTAINERS: [&str; 11] = [
+    "mp4", "m4a", "mov", "mkv", "mka", "webm", "ogg", "ogv", "oga", "opus", "mp3",
+];
+
+// muxers writing an attached picture: ID3 APIC frames, MP4 covr atoms and FLAC picture blocks
+const COVER_ART_CONTAINERS: [&str; 3] = ["mp3", "m4a", "flac"];
+
+/// Extensions of the containers able to hold `codec`, the canonical one first.
+/// `None` for stream copies and unlisted codecs, which are not checked.
+pub fn codec_containers(codec: &str) -> Option<&'static [&'static str]> {
+    let containers: &[&str] = match codec {
+        "libx264" | "h264" | "libx265" | "hevc" => &["mp4", "mkv", "mov"],
+        "mpeg4" => &["mp4", "mkv", "mov", "avi"],
+        "libvpx" | "vp8" => &["webm", "mkv"],
+        "libvpx-vp9" | "vp9" => &["webm", "mkv", "mp4"],
+        "libtheora" | "theora" => &["ogv", "ogg", "mkv"],
+        "aac" | "libfdk_aac" => &["m4a", "mp4", "mov", "mkv", "mka"],
+        "libmp3lame" | "mp3" => &["mp3", "mp4", "mov", "mkv", "mka", "avi"],
+        "libopus" | "opus" => &["opus", "ogg", "oga", "ogv", "webm", "mkv", "mka", "mp4"],
+        "libvorbis" | "vorbis" => &["ogg", "oga", "ogv", "webm", "mkv", "mka"],
+        "flac" => &["flac", "ogg", "oga", "mkv", "mka", "mp4"],
+        "ac3" => &["ac3", "mp4", "mov", "mkv", "mka"],
+        codec if codec.starts_with("pcm_") => &["wav", "mov", "mkv", "mka", "avi"],
+        "srt" | "subrip" => &["srt", "mkv"],
+        "webvtt" => &["vtt", "webm", "mkv"],
+        "ass" | "ssa" => &["ass", "mkv"],
+        _ => return None,
+    };
+    Some(containers)
+}
+
+/// Codec of the subtitles saved as `format`, one of `SUBTITLE_FORMATS`.
+pub fn subtitle_codec(format: &str) -> Option<&'static str> {
+    SUBTITLE_FORMATS
+        .iter()
+        .find(|&&(extension, _)| extension == format)
+        .map(|&(_, codec)| codec)
+}
+
+/// Whether a file with `extension` can hold chapters.
+pub fn supports_chapters(extension: &str) -> bool {
+    let extension = extension.to_lowercase();
+    CHAPTER_CONTAINERS.contains(&extension.as_str())
+}
+
+/// Codec the subtitle streams of the input take in a file with `extension`:
+/// Matroska holds them as they are, MP4 and QuickTime as mov_text. `None`
+/// for containers without subtitles.
+pub fn subtitle_stream_codec(extension: &str) -> Option<&'static str> {
+    match extension.to_lowercase().as_str() {
+        "mkv" | "mka" => Some("copy"),
+        "mp4" | "mov" | "m4v" => Some("mov_text"),
+        _ => None,
+    }
+}
+
+/// Whether a file with `extension` can hold attachments, e.g. subtitle fonts.
+pub fn supports_attachments(extension: &str) -> bool {
+    matches!(extension.to_lowercase().as_str(), "mkv" | "mka")
+}
+
+/// Whether a file with `extension` can hold a cover picture next to the audio.
+pub fn supports_cover_art(extension: &str) -> bool {
+    let extension = extension.to_lowercase();
+    COVER_ART_CONTAINERS.contains(&extension.as_str())
+}
+
+/// Whether a file with `extension` can hold the encoded streams, `None` codecs
+/// being streams left out or encoded with the container default.
+pub fn is_compatible(
+    extension: &str,
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+) -> bool {
+    let extension = extension.to_lowercase();
+    [video_codec, audio_codec]
+        .iter()
+        .filter_map(|codec| codec.and_then(codec_containers))
+        .all(|containers| containers.contains(&extension.as_str()))
+}
+
+/// Preferred extension for the encoded streams, video ones decide first.
+/// `None` when no codec constrains the container.
+pub fn canonical_extension(
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+) -> Option<&'static str> {
+    let video_containers = video_codec.and_then(codec_containers);
+    let audio_containers = audio_codec.and_then(codec_containers);
+    match (video_containers, audio_containers) {
+        (Some(video), Some(audio)) => video
+            .iter()
+            .find(|extension| audio.contains(extension))
+            .copied(),
+        (Some(containers), None) | (None, Some(containers)) => Some(containers[0]),
+        (None, None) => None,
+    }
+}
+
+/// `output_file` with the canonical extension of its codecs, `None` when its
+/// extension already fits them.
+pub fn corrected_output_file(
+    output_file: &str,
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+) -> Option<String> {
+    let path = Path::new(output_file);
+    let extension = path.extension().map_or_else(String::new, |extension| {
+        extension.to_string_lossy().into_owned()
+    });
+    if is_compatible(&extension, video_codec, audio_codec) {
+        return None;
+    }
+
+    let canonical = canonical_extension(video_codec, audio_codec)?;
+    Some(
+        path.with_extension(canonical)
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_EXTENSIONS: [&str; 15] = [
+        "mp4", "mkv", "mov", "avi", "webm", "ogv", "ogg", "oga", "m4a", "mka", "mp3", "opus",
+        "flac", "ac3", "wav",
+    ];
+
+    // (codec, extensions able to hold it)
+    const MATRIX: [(&str, &[&str]); 13] = [
+        ("libx264", &["mp4", "mkv", "mov"]),
+        ("libx265", &["mp4", "mkv", "mov"]),
+        ("mpeg4", &["mp4", "mkv", "mov", "avi"]),
+        ("libvpx", &["webm", "mkv"]),
+        ("libvpx-vp9", &["webm", "mkv", "mp4"]),
+        ("libtheora", &["ogv", "ogg", "mkv"]),
+        ("aac", &["m4a", "mp4", "mov", "mkv", "mka"]),
+        ("libmp3lame", &["mp3", "mp4", "mov", "mkv", "mka", "avi"]),
+        (
+            "libopus",
+            &["opus", "ogg", "oga", "ogv", "webm", "mkv", "mka", "mp4"],
+        ),
+        ("libvorbis", &["ogg", "oga", "ogv", "webm", "mkv", "mka"]),
+        ("flac", &["flac", "ogg", "oga", "mkv", "mka", "mp4"]),
+        ("ac3", &["ac3", "mp4", "mov", "mkv", "mka"]),
+        ("pcm_s16le", &["wav", "mov", "mkv", "mka", "avi"]),
+    ];
+
+    #[test]
+    fn codec_container_matrix() {
+        for &(codec, allowed) in &MATRIX {
+            let is_video = VIDEO_CODECS.contains(&codec);
+            assert!(is_video || AUDIO_CODECS.contains(&codec), "{}", codec);
+            for extension in &ALL_EXTENSIONS {
+                let (video, audio) = if is_video {
+                    (Some(codec), None)
+                } else {
+                    (None, Some(codec))
+                };
+                assert_eq!(
+                    is_compatible(extension, video, audio),
+                    allowed.contains(extension),
+                    "{} in .{}",
+                    codec,
+                    extension
+                );
+            }
+        }
+        // every offered codec is in the table
+        assert_eq!(MATRIX.len(), VIDEO_CODECS.len() + AUDIO_CODECS.len());
+    }
+
+    #[test]
+    fn every_pair_has_a_container() {
+        for video in &VIDEO_CODECS {
+            for audio in &AUDIO_CODECS {
+                let extension = canonical_extension(Some(video), Some(audio))
+                    .unwrap_or_else(|| panic!("{} with {}", video, audio));
+                assert!(is_compatible(extension, Some(video), Some(audio)));
+            }
+        }
+        assert_eq!(
+            canonical_extension(Some("libx264"), Some("aac")),
+            Some("mp4")
+        );
+        assert_eq!(
+            canonical_extension(Some("libvpx-vp9"), Some("libopus")),
+            Some("webm")
+        );
+        assert_eq!(
+            canonical_extension(Some("libx264"), Some("libvorbis")),
+            Some("mkv")
+        );
+        assert_eq!(canonical_extension(None, Some("libopus")), Some("opus"));
+        assert_eq!(canonical_extension(None, Some("pcm_s24le")), Some("wav"));
+        assert_eq!(canonical_extension(None, None), None);
+    }
+
+    #[test]
+    fn output_file_correction() {
+        assert_eq!(
+            corrected_output_file("/music/song.mp3", None, Some("libopus")),
+            Some(String::from("/music/song.opus"))
+        );
+        assert_eq!(
+            corrected_output_file("clip.v2.mp4", Some("libvpx"), Some("libvorbis")),
+            Some(String::from("clip.v2.webm"))
+        );
+        assert_eq!(
+            corrected_output_file("clip", Some("libx264"), None),
+            Some(String::from("clip.mp4"))
+        );
+        // fitting extensions are kept, whatever their case
+        assert_eq!(
+            corrected_output_file("clip.MKV", Some("libx264"), Some("flac")),
+            None
+        );
+        assert_eq!(corrected_output_file("clip.avi", Some("copy"), None), None);
+        assert_eq!(corrected_output_file("clip.mp3", None, None), None);
+    }
+
+    #[test]
+    fn subtitle_muxers() {
+        for &(extension, codec) in &SUBTITLE_FORMATS {
+            assert_eq!(subtitle_codec(extension), Some(codec));
+            assert!(codec_containers(codec).unwrap().contains(&extension));
+        }
+        assert_eq!(subtitle_codec("vtt"), Some("webvtt"));
+        assert_eq!(subtitle_codec("webvtt"), None);
+        // web players read WebVTT from WebM, MP4 would need mov_text
+        assert!(!codec_containers("webvtt").unwrap().contains(&"mp4"));
+        assert!(!codec_containers("srt").unwrap().contains(&"vtt"));
+    }
+
+    #[test]
+    fn chapter_support() {
+        for extension in &["mp4", "MKV", "webm", "mp3", "opus"] {
+            assert!(supports_chapters(extension), "{}", extension);
+        }
+        for extension in &["avi", "wav", "flac", "ac3", ""] {
+            assert!(!supports_chapters(extension), "{}", extension);
+        }
+    }
+
+    #[test]
+    fn subtitle_and_attachment_support() {
+        assert_eq!(subtitle_stream_codec("MKV"), Some("copy"));
+        assert_eq!(subtitle_stream_codec("mp4"), Some("mov_text"));
+        assert_eq!(subtitle_stream_codec("webm"), None);
+        assert_eq!(subtitle_stream_codec("mp3"), None);
+        assert!(supports_attachments("mkv"));
+        assert!(!supports_attachments("mp4"));
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use chrono::Duration;
+
+use crate::{
+    output_pattern::{self, TrackTags},
+    Config,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// CD frames in a second, the last field of CUE timestamps.
+const FRAMES_PER_SECOND: i64 = 75;
+
+const FILE_TYPES: [&str; 6] = ["WAVE", "MP3", "AIFF", "BINARY", "MOTOROLA", "FLAC"];
+
+/// Track outputs named after their number and title, next to the input.
+pub const DEFAULT_TRACK_PATTERN: &str = "{parent}/{track:02} - {title}.{ext}";
+
+/// Album of a single audio file, as described by a CUE sheet.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// Audio file of the album, as named in the sheet.
+    pub file: Option<String>,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    /// None when the album performer plays the track.
+    pub performer: Option<String>,
+    /// `INDEX 01`, where the track starts once its pregap is over.
+    pub start: Duration,
+}
+
+/// Read a CUE sheet, with or without a byte order mark, in UTF-8 or, as older
+/// rippers wrote them, Latin-1. Commands other than `FILE`, `TRACK`, `INDEX`,
+/// `TITLE` and `PERFORMER` are skipped.
+pub fn parse<R: Read>(mut reader: R) -> Result<CueSheet> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Could not read the CUE sheet.\nError: {}", e))?;
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&bytes);
+    let contents = match std::str::from_utf8(bytes) {
+        Ok(contents) => String::from(contents),
+        Err(_) => bytes.iter().map(|&byte| char::from(byte)).collect(),
+    };
+
+    let mut sheet = CueSheet::default();
+    // the track being read, whose start is not known until its INDEX 01
+    let mut track: Option<(CueTrack, bool)> = None;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("Error: CUE line {}: {}", number + 1, message);
+        let (command, arguments) = match line.find(char::is_whitespace) {
+            Some(end) => (&line[..end], line[end..].trim_start()),
+            None => (line, ""),
+        };
+
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                if sheet.file.is_some() {
+                    return Err(error(String::from(
+                        "sheets of several files are not supported.",
+                    )));
+                }
+                sheet.file = Some(file_name(arguments));
+            }
+            "TRACK" => {
+                finish_track(&mut sheet, track.take())?;
+                let number = arguments
+                    .split_whitespace()
+                    .next()
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(|| error(format!("invalid track \"{}\".", arguments)))?;
+                track = Some((
+                    CueTrack {
+                        number,
+                        title: None,
+                        performer: None,
+                        start: Duration::zero(),
+                    },
+                    false,
+                ));
+            }
+            "INDEX" => {
+                let mut fields = arguments.split_whitespace();
+                let (index, timestamp) = match (fields.next(), fields.next()) {
+                    (Some(index), Some(timestamp)) => (index, timestamp),
+                    _ => return Err(error(format!("invalid index \"{}\".", arguments))),
+                };
+                // INDEX 00 starts the pregap, played before the track
+                if index.parse() == Ok(1) {
+                    if let Some((ref mut track, ref mut has_start)) = track {
+                        track.start = parse_timestamp(timestamp).map_err(error)?;
+                        *has_start = true;
+                    }
+                }
+            }
+            "TITLE" | "PERFORMER" => {
+                let value = unquote(arguments);
+                let field = match track {
+                    Some((ref mut track, _)) if command.eq_ignore_ascii_case("TITLE") => {
+                        &mut track.title
+                    }
+                    Some((ref mut track, _)) => &mut track.performer,
+                    None if command.eq_ignore_ascii_case("TITLE") => &mut sheet.title,
+                    None => &mut sheet.performer,
+                };
+                *field = Some(value);
+            }
+            // REM, CATALOG, FLAGS, ISRC, POSTGAP, blank lines…
+            _ => {}
+        }
+    }
+    finish_track(&mut sheet, track)?;
+
+    if sheet.tracks.is_empty() {
+        return Err(String::from("Error: the CUE sheet has no track."));
+    }
+    Ok(sheet)
+}
+
+/// `MM:SS:FF` timestamp, the minutes being unbounded and `FF` counting 75th
+/// of a second.
+pub fn parse_timestamp(text: &str) -> Result<Duration> {
+    let invalid = || format!("invalid timestamp \"{}\", expected MM:SS:FF.", text);
+    let fields: Vec<i64> = text
+        .split(':')
+        .map(|field| field.parse().map_err(|_| invalid()))
+        .collect::<Result<_>>()?;
+    match fields[..] {
+        [minutes, seconds, frames]
+            if minutes >= 0
+                && (0..60).contains(&seconds)
+                && (0..FRAMES_PER_SECOND).contains(&frames) =>
+        {
+            Ok(Duration::minutes(minutes)
+                + Duration::seconds(seconds)
+                + Duration::milliseconds(
+                    (frames * 1000 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND,
+                ))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// CUE sheet next to `input`, named either `album.cue` or `album.flac.cue`.
+pub fn sibling_cue(input: &Path) -> Option<PathBuf> {
+    let mut appended = input.as_os_str().to_owned();
+    appended.push(".cue");
+    vec![input.with_extension("cue"), PathBuf::from(appended)]
+        .into_iter()
+        .find(|cue| cue.as_path() != input && cue.is_file())
+}
+
+/// One copy of `conf` per track of `sheet`, cutting it from the input, named
+/// after `pattern` and tagged with its title, artist, album and number. The
+/// last track lasts until `input_duration`.
+pub fn track_jobs(
+    conf: &Config,
+    sheet: &CueSheet,
+    pattern: &str,
+    input_duration: Option<Duration>,
+) -> Result<Vec<Config>> {
+    output_pattern::validate_track_pattern(pattern)?;
+
+    let mut jobs = Vec::with_capacity(sheet.tracks.len());
+    for (index, track) in sheet.tracks.iter().enumerate() {
+        let end = match sheet.tracks.get(index + 1) {
+            Some(next) if next.start > track.start => next.start,
+            Some(_) => return Err(format!("Error: CUE track {} is empty.", track.number)),
+            None => match input_duration {
+                Some(duration) if duration > track.start => duration,
+                Some(_) => {
+                    return Err(format!(
+                        "Error: CUE track {} starts after the end of the input file.",
+                        track.number
+                    ))
+                }
+                None => {
+                    return Err(String::from(
+                        "Error: input file duration is unknown, the last track cannot be cut.",
+                    ))
+                }
+            },
+        };
+
+        let tags = TrackTags {
+            number: track.number,
+            title: track.title.as_deref().unwrap_or_default(),
+            artist: track
+                .performer
+                .as_deref()
+                .or(sheet.performer.as_deref())
+                .unwrap_or_default(),
+            album: sheet.title.as_deref().unwrap_or_default(),
+        };
+        let mut job = conf.clone();
+        job.process_whole_file = false;
+        job.from_time = track.start;
+        job.to_time = end;
+        job.output_pattern = None;
+        job.output_file = output_pattern::expand_track_pattern(
+            pattern,
+            Path::new(&conf.input_file),
+            &tags,
+            conf.filename_charset,
+        )
+        .to_string_lossy()
+        .into_owned();
+        for (key, value) in &[
+            ("title", tags.title),
+            ("artist", tags.artist),
+            ("album", tags.album),
+        ] {
+            if !value.is_empty() {
+                job.metadata
+                    .push((String::from(*key), String::from(*value)));
+            }
+        }
+        job.metadata.push((
+            String::from("track"),
+            format!("{}/{}", track.number, sheet.tracks.len()),
+        ));
+        jobs.push(job);
+    }
+    Ok(jobs)
+}
+
+// tracks without a start cannot be cut
+fn finish_track(sheet: &mut CueSheet, track: Option<(CueTrack, bool)>) -> Result<()> {
+    match track {
+        Some((track, true)) => {
+            if let Some(previous) = sheet.tracks.last() {
+                if track.start < previous.start {
+                    return Err(format!(
+                        "Error: CUE track {} starts before track {}.",
+                        track.number, previous.number
+                    ));
+                }
+            }
+            sheet.tracks.push(track);
+            Ok(())
+        }
+        Some((track, false)) => Err(format!(
+            "Error: CUE track {} has no INDEX 01.",
+            track.number
+        )),
+        None => Ok(()),
+    }
+}
+
+// unquoted values take the whole line, quotes are sometimes left unclosed
+fn unquote(arguments: &str) -> String {
+    match arguments.strip_prefix('"') {
+        Some(quoted) => String::from(quoted.find('"').map_or(quoted, |end| &quoted[..end])),
+        None => String::from(arguments),
+    }
+}
+
+// the file type follows the name: WAVE, MP3, AIFF…
+fn file_name(arguments: &str) -> String {
+    if arguments.starts_with('"') {
+        return unquote(arguments);
+    }
+    match arguments.rfind(char::is_whitespace) {
+        Some(end)
+            if FILE_TYPES.contains(&arguments[end..].trim().to_ascii_uppercase().as_str()) =>
+        {
+            String::from(arguments[..end].trim_end())
+        }
+        _ => String::from(arguments),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // as written by EAC on Windows: BOM, CRLF, remarks and pregaps
+    const EAC_SHEET: &str = "\u{feff}REM GENRE Rock\r\n\
+        REM DATE 1992\r\n\
+        PERFORMER \"4 Non Blondes\"\r\n\
+        TITLE \"Bigger, Better, Faster, More!\"\r\n\
+        FILE \"4 Non Blondes - Bigger, Better, Faster, More!.flac\" WAVE\r\n\
+        \x20 TRACK 01 AUDIO\r\n\
+        \x20   TITLE \"Train\"\r\n\
+        \x20   INDEX 01 00:00:00\r\n\
+        \x20 TRACK 02 AUDIO\r\n\
+        \x20   TITLE \"What's Up\"\r\n\
+        \x20   PERFORMER \"Linda Perry\"\r\n\
+        \x20   INDEX 00 03:20:40\r\n\
+        \x20   INDEX 01 03:22:30\r\n\
+        \x20 TRACK 03 AUDIO\r\n\
+        \x20   FLAGS DCP\r\n\
+        \x20   TITLE \"Pleasantly Blue\r\n\
+        \x20   INDEX 01 78:30:74\r\n";
+
+    fn time(ms: i64) -> Duration {
+        Duration::milliseconds(ms)
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(parse_timestamp("00:00:00"), Ok(Duration::zero()));
+        assert_eq!(parse_timestamp("03:22:30"), Ok(time(202_400)));
+        assert_eq!(parse_timestamp("78:30:74"), Ok(time(4_710_987)));
+        for invalid in &["3:22", "03:60:00", "03:22:75", "-1:00:00", "03:22:3a", ""] {
+            assert!(parse_timestamp(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn messy_sheet() {
+        let sheet = parse(EAC_SHEET.as_bytes()).unwrap();
+        assert_eq!(sheet.performer.as_deref(), Some("4 Non Blondes"));
+        assert_eq!(
+            sheet.title.as_deref(),
+            Some("Bigger, Better, Faster, More!")
+        );
+        assert_eq!(
+            sheet.file.as_deref(),
+            Some("4 Non Blondes - Bigger, Better, Faster, More!.flac")
+        );
+        assert_eq!(
+            sheet.tracks,
+            vec![
+                CueTrack {
+                    number: 1,
+                    title: Some(String::from("Train")),
+                    performer: None,
+                    start: Duration::zero(),
+                },
+                CueTrack {
+                    number: 2,
+                    title: Some(String::from("What's Up")),
+                    performer: Some(String::from("Linda Perry")),
+                    start: time(202_400),
+                },
+                // unclosed quote
+                CueTrack {
+                    number: 3,
+                    title: Some(String::from("Pleasantly Blue")),
+                    performer: None,
+                    start: time(4_710_987),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unquoted_and_latin1_sheet() {
+        let sheet = parse(
+            &b"performer Bj\xf6rk\ntitle Homogenic\nfile homogenic.wav wave\n\
+               track 1 audio\ntitle Hunter Wave\nindex 01 00:00:00\n"[..],
+        )
+        .unwrap();
+        assert_eq!(sheet.performer.as_deref(), Some("Björk"));
+        assert_eq!(sheet.file.as_deref(), Some("homogenic.wav"));
+        assert_eq!(sheet.tracks[0].title.as_deref(), Some("Hunter Wave"));
+    }
+
+    #[test]
+    fn invalid_sheets() {
+        let error = |sheet: &str| parse(sheet.as_bytes()).unwrap_err();
+        assert_eq!(
+            error("FILE a.wav WAVE\nREM no track\n"),
+            "Error: the CUE sheet has no track."
+        );
+        assert_eq!(
+            error("TRACK 01 AUDIO\nINDEX 00 00:00:00\nTRACK 02 AUDIO\nINDEX 01 00:10:00\n"),
+            "Error: CUE track 1 has no INDEX 01."
+        );
+        assert_eq!(
+            error("TRACK 01 AUDIO\nINDEX 01 01:00:00\nTRACK 02 AUDIO\nINDEX 01 00:10:00\n"),
+            "Error: CUE track 2 starts before track 1."
+        );
+        assert_eq!(
+            error("FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\nFILE b.wav WAVE\n"),
+            "Error: CUE line 4: sheets of several files are not supported."
+        );
+        assert!(error("TRACK one AUDIO\n").starts_with("Error: CUE line 1: invalid track"));
+        assert!(error("TRACK 01 AUDIO\nINDEX 01 1:2\n")
+            .starts_with("Error: CUE line 2: invalid timestamp"));
+    }
+
+    #[test]
+    fn jobs_per_track() {
+        let sheet = parse(EAC_SHEET.as_bytes()).unwrap();
+        let mut conf = Config::new();
+        conf.input_file = String::from("/music/album.flac");
+        conf.process_whole_file = true;
+        conf.output_pattern = Some(String::from("{stem}_cut.{ext}"));
+
+        let jobs = track_jobs(&conf, &sheet, DEFAULT_TRACK_PATTERN, Some(time(4_800_000))).unwrap();
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[0].output_file, "/music/01 - Train.flac");
+        assert_eq!(jobs[1].output_file, "/music/02 - What's Up.flac");
+        assert_eq!(
+            (jobs[1].from_time, jobs[1].to_time),
+            (time(202_400), time(4_710_987))
+        );
+        assert_eq!(
+            (jobs[2].from_time, jobs[2].to_time),
+            (time(4_710_987), time(4_800_000))
+        );
+        for job in &jobs {
+            assert!(!job.process_whole_file);
+            assert_eq!(job.output_pattern, None);
+        }
+
+        let tag = |key: &str, value: &str| (String::from(key), String::from(value));
+        assert_eq!(
+            jobs[0].metadata,
+            vec![
+                tag("title", "Train"),
+                tag("artist", "4 Non Blondes"),
+                tag("album", "Bigger, Better, Faster, More!"),
+                tag("track", "1/3"),
+            ]
+        );
+        assert_eq!(jobs[1].metadata[1], tag("artist", "Linda Perry"));
+
+        assert!(track_jobs(&conf, &sheet, DEFAULT_TRACK_PATTERN, None).is_err());
+        assert!(track_jobs(&conf, &sheet, DEFAULT_TRACK_PATTERN, Some(time(4_000_000))).is_err());
+        assert!(track_jobs(&conf, &sheet, "{genre}.flac", Some(time(4_800_000))).is_err());
+    }
+}
@@ -0,0 +1,764 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{
+    edl::{parse_edl, Timecode},
+    parse_duration,
+    project::{Marker, Segment},
+};
+
+lazy_static! {
+    // `0:00 Intro`, `[1:23] - Part 1`, `1:02:03.5 | Q&A`…
+    static ref LEADING_TIMESTAMP_RE: Regex = Regex::new(
+        r#"^[\[(]?(?P<time>\d+(?::\d{1,2}){1,2}(?:\.\d+)?)[\])]?\s*(?:[-–—:|]\s*)?(?P<label>.*)$"#
+    )
+    .unwrap();
+    // …or `Intro - 0:00`, `Part 1 (1:23)`
+    static ref TRAILING_TIMESTAMP_RE: Regex = Regex::new(
+        r#"^(?P<label>.*?)\s*(?:[-–—:|]\s*)?[\[(]?(?P<time>\d+(?::\d{1,2}){1,2}(?:\.\d+)?)[\])]?$"#
+    )
+    .unwrap();
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Frame rate of EDL timecodes for inputs without one, such as audio files.
+pub const DEFAULT_FPS: f64 = 25.0;
+
+/// Files the cut list can be written to, along with what each one needs.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CutListFormat {
+    /// `start,end,label` rows, times in seconds.
+    Csv,
+    /// CMX3600 edit decision list, times as timecodes at `fps`.
+    Edl { fps: f64 },
+    /// Script for ffmpeg's concat demuxer, cutting `input_file` with
+    /// `inpoint` and `outpoint` directives.
+    FfmpegConcat { input_file: String },
+}
+
+impl CutListFormat {
+    /// Usual extension of the written file.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CutListFormat::Csv => "csv",
+            CutListFormat::Edl { .. } => "edl",
+            CutListFormat::FfmpegConcat { .. } => "ffconcat",
+        }
+    }
+}
+
+/// Kind of cut list without its parameters, e.g. as typed on a command line.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CutListKind {
+    Csv,
+    Edl,
+    FfmpegConcat,
+}
+
+pub const CUT_LIST_KINDS: [CutListKind; 3] = [
+    CutListKind::Csv,
+    CutListKind::Edl,
+    CutListKind::FfmpegConcat,
+];
+
+impl CutListKind {
+    /// Usual extension of the written file, see `CutListFormat::extension`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            CutListKind::Csv => "csv",
+            CutListKind::Edl => "edl",
+            CutListKind::FfmpegConcat => "ffconcat",
+        }
+    }
+
+    /// Kind of a file named with `extension`, whatever its case.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        CUT_LIST_KINDS
+            .iter()
+            .find(|kind| kind.extension().eq_ignore_ascii_case(extension))
+            .copied()
+    }
+}
+
+impl fmt::Display for CutListKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CutListKind::Csv => "csv",
+            CutListKind::Edl => "edl",
+            CutListKind::FfmpegConcat => "concat",
+        })
+    }
+}
+
+impl FromStr for CutListKind {
+    type Err = String;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        CUT_LIST_KINDS
+            .iter()
+            .find(|known| known.to_string().eq_ignore_ascii_case(kind.trim()))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Unknown cut list format: {}, expected csv, edl or concat.",
+                    kind
+                )
+            })
+    }
+}
+
+/// Write `segments` to `writer` as a `format` cut list.
+pub fn export<W: Write>(
+    segments: &[Segment],
+    format: &CutListFormat,
+    writer: &mut W,
+) -> Result<()> {
+    let contents = match format {
+        CutListFormat::Csv => to_csv(segments),
+        CutListFormat::Edl { fps } => to_edl(segments, *fps),
+        CutListFormat::FfmpegConcat { input_file } => to_ffmpeg_concat(segments, input_file),
+    };
+    writer
+        .write_all(contents.as_bytes())
+        .map_err(|e| format!("Could not write the cut list.\nError: {}", e))
+}
+
+// seconds with milliseconds, as read back by spreadsheets and ffmpeg alike
+fn seconds(time: Duration) -> String {
+    format!("{:.3}", time.num_milliseconds() as f64 / 1000.0)
+}
+
+fn to_csv(segments: &[Segment]) -> String {
+    let mut csv = String::from("start,end,label\n");
+    for segment in segments {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            seconds(segment.from_time),
+            seconds(segment.to_time),
+            csv_field(&segment.name)
+        ));
+    }
+    csv
+}
+
+// quoted only when needed, inner quotes doubled
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+// segments are laid end to end on the record side
+fn to_edl(segments: &[Segment], fps: f64) -> String {
+    let mut edl = String::from("TITLE: Cut list\nFCM: NON-DROP FRAME\n");
+    let mut record = Duration::zero();
+    for (index, segment) in segments.iter().enumerate() {
+        let record_out = record + (segment.to_time - segment.from_time);
+        edl.push_str(&format!(
+            "\n{:03}  {:<9}{:<6}C        {} {} {} {}\n",
+            index + 1,
+            "AX",
+            "AA/V",
+            Timecode::from_duration(segment.from_time, fps),
+            Timecode::from_duration(segment.to_time, fps),
+            Timecode::from_duration(record, fps),
+            Timecode::from_duration(record_out, fps)
+        ));
+        if !segment.name.is_empty() {
+            edl.push_str(&format!(
+                "* FROM CLIP NAME: {}\n",
+                single_line(&segment.name)
+            ));
+        }
+        record = record_out;
+    }
+    edl
+}
+
+fn to_ffmpeg_concat(segments: &[Segment], input_file: &str) -> String {
+    // single quotes cannot be escaped inside quotes: the quoting is closed around them
+    let quoted_file = format!("'{}'", input_file.replace('\'', "'\\''"));
+    let mut concat = String::from("ffconcat version 1.0\n");
+    for segment in segments {
+        concat.push('\n');
+        if !segment.name.is_empty() {
+            concat.push_str(&format!("# {}\n", single_line(&segment.name)));
+        }
+        concat.push_str(&format!(
+            "file {}\ninpoint {}\noutpoint {}\n",
+            quoted_file,
+            seconds(segment.from_time),
+            seconds(segment.to_time)
+        ));
+    }
+    concat
+}
+
+// labels of line based formats
+fn single_line(value: &str) -> String {
+    value.replace(&['\r', '\n'][..], " ")
+}
+
+/// Cut lists that can be read back, see `parse`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImportFormat {
+    /// `start,end,label` rows, with or without a header, times in seconds or
+    /// `h:mm:ss`. The end may be left empty.
+    Csv,
+    /// CMX3600 edit decision list.
+    Edl,
+    /// One `0:00 Label` line per chapter, as in video descriptions.
+    Timestamps,
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ImportFormat::Csv => "CSV",
+            ImportFormat::Edl => "EDL",
+            ImportFormat::Timestamps => "timestamps",
+        })
+    }
+}
+
+/// Cut or chapter read from a cut list.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub start: Duration,
+    /// None when the entry lasts until the next one, as chapters do.
+    pub end: Option<Duration>,
+    pub label: String,
+}
+
+/// Entries of an imported cut list, along with the lines that were skipped.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParsedCutList {
+    pub format: ImportFormat,
+    pub entries: Vec<Entry>,
+    pub warnings: Vec<String>,
+}
+
+/// Read a cut list from `text`, in the `hint` format or the detected one.
+/// Malformed lines are skipped with a warning rather than failing the whole
+/// list, EDL timecodes are read at `fps`.
+pub fn parse(text: &str, hint: Option<ImportFormat>, fps: f64) -> Result<ParsedCutList> {
+    let format = hint.unwrap_or_else(|| detect_format(text));
+    let mut warnings = Vec::new();
+    let entries = match format {
+        ImportFormat::Csv => parse_csv(text, &mut warnings),
+        ImportFormat::Edl => parse_edl(text)?
+            .iter()
+            .map(|clip| {
+                let (start, end) = clip.source_range(fps);
+                Entry {
+                    start,
+                    end: Some(end),
+                    label: clip.clip_name.clone().unwrap_or_default(),
+                }
+            })
+            .collect(),
+        ImportFormat::Timestamps => parse_timestamps(text, &mut warnings),
+    };
+
+    if entries.is_empty() {
+        return Err(format!("Error: no cut found in the {} cut list.", format));
+    }
+    Ok(ParsedCutList {
+        format,
+        entries,
+        warnings,
+    })
+}
+
+/// `parse` reading from a file or any other reader.
+pub fn parse_reader<R: Read>(
+    reader: &mut R,
+    hint: Option<ImportFormat>,
+    fps: f64,
+) -> Result<ParsedCutList> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .map_err(|e| format!("Could not read the cut list.\nError: {}", e))?;
+    parse(&text, hint, fps)
+}
+
+/// Guess the format of a cut list from its contents.
+pub fn detect_format(text: &str) -> ImportFormat {
+    let is_edl = text.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("TITLE:") || line.starts_with("FCM:")
+    });
+    if is_edl || matches!(parse_edl(text), Ok(clips) if !clips.is_empty()) {
+        return ImportFormat::Edl;
+    }
+
+    if let Some(first_line) = text.lines().find(|line| !line.trim().is_empty()) {
+        let fields = split_csv_record(first_line);
+        let is_header =
+            matches!(fields.first(), Some(field) if field.trim().eq_ignore_ascii_case("start"));
+        let has_times = fields.len() >= 2 && parse_duration(&fields[0]).is_ok();
+        if first_line.contains(',') && (is_header || has_times) {
+            return ImportFormat::Csv;
+        }
+    }
+    ImportFormat::Timestamps
+}
+
+/// Segments covering `entries`: those without an end last until the next
+/// start, or until `file_end` for the last one, which is left out when the
+/// duration of the file is unknown. Empty entries are left out too.
+pub fn to_segments(entries: &[Entry], file_end: Option<Duration>) -> Vec<Segment> {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by_key(|entry| entry.start);
+
+    let mut segments = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let next_start = entries[index + 1..]
+            .iter()
+            .map(|next| next.start)
+            .find(|start| *start > entry.start);
+        let end = match entry.end.or(next_start).or(file_end) {
+            Some(end) if end > entry.start => end,
+            _ => continue,
+        };
+        segments.push(Segment {
+            from_time: entry.start,
+            to_time: end,
+            name: entry.label.clone(),
+        });
+    }
+    segments
+}
+
+/// One marker at the start of each of `entries`.
+pub fn to_markers(entries: &[Entry]) -> Vec<Marker> {
+    entries
+        .iter()
+        .map(|entry| Marker {
+            time: entry.start,
+            name: entry.label.clone(),
+        })
+        .collect()
+}
+
+fn parse_csv(text: &str, warnings: &mut Vec<String>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut columns = (0, 1, 2);
+    for (index, (number, fields)) in csv_records(text).into_iter().enumerate() {
+        if fields.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let field = |column: usize| fields.get(column).map_or("", |field| field.trim());
+
+        if index == 0 && parse_duration(field(0)).is_err() {
+            // header, its column names may be in any order
+            let find = |names: &[&str]| {
+                fields.iter().position(|field| {
+                    names
+                        .iter()
+                        .any(|name| field.trim().eq_ignore_ascii_case(name))
+                })
+            };
+            columns = (
+                find(&["start", "from", "in"]).unwrap_or(0),
+                find(&["end", "to", "out"]).unwrap_or(1),
+                find(&["label", "name", "title"]).unwrap_or(2),
+            );
+            continue;
+        }
+
+        let (start_column, end_column, label_column) = columns;
+        let start = match parse_duration(field(start_column)) {
+            Ok(start) => start,
+            Err(e) => {
+                warnings.push(format!("Line {}: {}", number, e));
+                continue;
+            }
+        };
+        let end = match field(end_column) {
+            "" => None,
+            end => match parse_duration(end) {
+                Ok(end) if end > start => Some(end),
+                Ok(_) => {
+                    warnings.push(format!("Line {}: the cut ends before it starts.", number));
+                    continue;
+                }
+                Err(e) => {
+                    warnings.push(format!("Line {}: {}", number, e));
+                    continue;
+                }
+            },
+        };
+        entries.push(Entry {
+            start,
+            end,
+            label: String::from(field(label_column)),
+        });
+    }
+    entries
+}
+
+// records along with the number of their first line, quoted fields may span lines
+fn csv_records(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = String::new();
+    let mut first_line = 1;
+    for (number, line) in text.lines().enumerate() {
+        if record.is_empty() {
+            first_line = number + 1;
+        } else {
+            record.push('\n');
+        }
+        record.push_str(line);
+        // each quote opens or closes a field
+        let open = record.matches('"').fold(false, |open, _| !open);
+        if !open {
+            records.push((first_line, split_csv_record(&record)));
+            record.clear();
+        }
+    }
+    if !record.is_empty() {
+        records.push((first_line, split_csv_record(&record)));
+    }
+    records
+}
+
+fn split_csv_record(record: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = record.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+fn parse_timestamps(text: &str, warnings: &mut Vec<String>) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let captures = match LEADING_TIMESTAMP_RE
+            .captures(line)
+            .or_else(|| TRAILING_TIMESTAMP_RE.captures(line))
+        {
+            Some(captures) => captures,
+            None => {
+                warnings.push(format!("Line {}: no timestamp found.", number + 1));
+                continue;
+            }
+        };
+        match parse_duration(&captures["time"]) {
+            Ok(start) => entries.push(Entry {
+                start,
+                end: None,
+                label: String::from(captures["label"].trim()),
+            }),
+            Err(e) => warnings.push(format!("Line {}: {}", number + 1, e)),
+        }
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edl::parse_edl;
+
+    fn segment(from_ms: i64, to_ms: i64, name: &str) -> Segment {
+        Segment {
+            from_time: Duration::milliseconds(from_ms),
+            to_time: Duration::milliseconds(to_ms),
+            name: String::from(name),
+        }
+    }
+
+    fn export_string(segments: &[Segment], format: &CutListFormat) -> String {
+        let mut output = Vec::new();
+        export(segments, format, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn csv_export() {
+        let segments = [
+            segment(1500, 4000, "Intro"),
+            segment(62_040, 3_725_500, "Q&A, part \"two\""),
+        ];
+        assert_eq!(
+            export_string(&segments, &CutListFormat::Csv),
+            "start,end,label\n\
+             1.500,4.000,Intro\n\
+             62.040,3725.500,\"Q&A, part \"\"two\"\"\"\n"
+        );
+        assert_eq!(export_string(&[], &CutListFormat::Csv), "start,end,label\n");
+    }
+
+    #[test]
+    fn edl_export() {
+        let segments = [segment(1410, 4000, "Intro"), segment(62_040, 65_000, "")];
+        let edl = export_string(&segments, &CutListFormat::Edl { fps: 25.0 });
+        assert_eq!(
+            edl,
+            "TITLE: Cut list\nFCM: NON-DROP FRAME\n\
+             \n001  AX       AA/V  C        00:00:01:10 00:00:04:00 00:00:00:00 00:00:02:15\n\
+             * FROM CLIP NAME: Intro\n\
+             \n002  AX       AA/V  C        00:01:02:01 00:01:05:00 00:00:02:15 00:00:05:14\n"
+        );
+
+        // read back by the EDL import, snapped to frames unlike CSV seconds
+        let clips = parse_edl(&edl).unwrap();
+        assert_eq!(clips.len(), 2);
+        assert_eq!(clips[0].clip_name.as_deref(), Some("Intro"));
+        assert_eq!(
+            clips[0].source_range(25.0),
+            (Duration::milliseconds(1400), Duration::seconds(4))
+        );
+    }
+
+    #[test]
+    fn ffmpeg_concat_export() {
+        let segments = [
+            segment(1500, 4000, "Intro\nshot 1"),
+            segment(62_040, 65_000, ""),
+        ];
+        let format = CutListFormat::FfmpegConcat {
+            input_file: String::from("/videos/Tom's talk.mp4"),
+        };
+        assert_eq!(
+            export_string(&segments, &format),
+            "ffconcat version 1.0\n\
+             \n# Intro shot 1\nfile '/videos/Tom'\\''s talk.mp4'\ninpoint 1.500\noutpoint 4.000\n\
+             \nfile '/videos/Tom'\\''s talk.mp4'\ninpoint 62.040\noutpoint 65.000\n"
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        for kind in &CUT_LIST_KINDS {
+            assert_eq!(kind.to_string().parse::<CutListKind>(), Ok(*kind));
+        }
+        assert_eq!("EDL".parse::<CutListKind>(), Ok(CutListKind::Edl));
+        assert!("xml".parse::<CutListKind>().is_err());
+        assert_eq!(CutListFormat::Edl { fps: 25.0 }.extension(), "edl");
+        assert_eq!(
+            CutListKind::from_extension("FFCONCAT"),
+            Some(CutListKind::FfmpegConcat)
+        );
+        assert_eq!(CutListKind::from_extension("txt"), None);
+    }
+
+    fn entry(start_ms: i64, end_ms: Option<i64>, label: &str) -> Entry {
+        Entry {
+            start: Duration::milliseconds(start_ms),
+            end: end_ms.map(Duration::milliseconds),
+            label: String::from(label),
+        }
+    }
+
+    #[test]
+    fn format_detection() {
+        assert_eq!(detect_format("start,end,label\n1,2,a\n"), ImportFormat::Csv);
+        assert_eq!(detect_format("\n1.5,4,Intro\n"), ImportFormat::Csv);
+        assert_eq!(
+            detect_format("TITLE: Cut list\n\n001  AX  V  C  00:00:01:00 00:00:02:00 00:00:00:00 00:00:01:00\n"),
+            ImportFormat::Edl
+        );
+        assert_eq!(
+            detect_format("001  AX  V  C  00:00:01:00 00:00:02:00 00:00:00:00 00:00:01:00\n"),
+            ImportFormat::Edl
+        );
+        assert_eq!(
+            detect_format("0:00 Intro\n1:23 Part 1\n"),
+            ImportFormat::Timestamps
+        );
+        assert_eq!(
+            detect_format("0:00 Intro, part 1\n"),
+            ImportFormat::Timestamps
+        );
+        assert_eq!(detect_format(""), ImportFormat::Timestamps);
+    }
+
+    #[test]
+    fn csv_import() {
+        let parsed = parse(
+            "start,end,label\n\
+             1.500,4.000,Intro\n\
+             \n\
+             1:02.04,1:02:05.5,\"Q&A, part \"\"two\"\"\"\n\
+             90,,Outro\n",
+            None,
+            DEFAULT_FPS,
+        )
+        .unwrap();
+        assert_eq!(parsed.format, ImportFormat::Csv);
+        assert_eq!(
+            parsed.entries,
+            vec![
+                entry(1500, Some(4000), "Intro"),
+                entry(62_040, Some(3_725_500), "Q&A, part \"two\""),
+                entry(90_000, None, "Outro"),
+            ]
+        );
+        assert!(parsed.warnings.is_empty());
+
+        // columns are found by name, a missing header keeps the default order
+        let parsed = parse(
+            "title,out,in\nA,5,2\n",
+            Some(ImportFormat::Csv),
+            DEFAULT_FPS,
+        )
+        .unwrap();
+        assert_eq!(parsed.entries, vec![entry(2000, Some(5000), "A")]);
+        let parsed = parse("2,5\n", None, DEFAULT_FPS).unwrap();
+        assert_eq!(parsed.entries, vec![entry(2000, Some(5000), "")]);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let segments = [
+            segment(1500, 4000, "Intro"),
+            segment(62_040, 65_000, "Q&A, \"two\"\nlines"),
+        ];
+        let csv = export_string(&segments, &CutListFormat::Csv);
+        let parsed = parse(&csv, None, DEFAULT_FPS).unwrap();
+        assert_eq!(to_segments(&parsed.entries, None), segments.to_vec());
+    }
+
+    #[test]
+    fn malformed_csv_lines() {
+        let parsed = parse(
+            "start,end,label\n1,2,ok\nsoon,3,bad start\n5,4,backwards\n6,7:99,bad end\n8,9,ok too\n",
+            None,
+            DEFAULT_FPS,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.entries,
+            vec![
+                entry(1000, Some(2000), "ok"),
+                entry(8000, Some(9000), "ok too")
+            ]
+        );
+        assert_eq!(parsed.warnings.len(), 3);
+        assert!(parsed.warnings[0].starts_with("Line 3: Invalid time: \"soon\""));
+        assert_eq!(parsed.warnings[1], "Line 4: the cut ends before it starts.");
+        assert!(parsed.warnings[2].starts_with("Line 5: "));
+
+        assert!(parse("start,end,label\n", None, DEFAULT_FPS).is_err());
+    }
+
+    #[test]
+    fn edl_import() {
+        let edl = export_string(
+            &[segment(1400, 4000, "Intro"), segment(62_040, 65_000, "")],
+            &CutListFormat::Edl { fps: 25.0 },
+        );
+        let parsed = parse(&edl, None, 25.0).unwrap();
+        assert_eq!(parsed.format, ImportFormat::Edl);
+        assert_eq!(
+            parsed.entries,
+            vec![
+                entry(1400, Some(4000), "Intro"),
+                entry(62_040, Some(65_000), "")
+            ]
+        );
+
+        // unlike the other formats, a broken EDL is rejected as a whole
+        assert!(parse("FCM: NON-DROP FRAME\n001  AX  V\n", None, 25.0).is_err());
+    }
+
+    #[test]
+    fn timestamps_import() {
+        let parsed = parse(
+            "Chapters:\n\
+             0:00 Intro\n\
+             [1:23] - Part 1\n\
+             \n\
+             1:02:03.5 | Q&A\n\
+             Bloopers - 1:05:00\n\
+             Credits (1:10:30)\n\
+             1:75 Bad\n",
+            None,
+            DEFAULT_FPS,
+        )
+        .unwrap();
+        assert_eq!(parsed.format, ImportFormat::Timestamps);
+        assert_eq!(
+            parsed.entries,
+            vec![
+                entry(0, None, "Intro"),
+                entry(83_000, None, "Part 1"),
+                entry(3_723_500, None, "Q&A"),
+                entry(3_900_000, None, "Bloopers"),
+                entry(4_230_000, None, "Credits"),
+            ]
+        );
+        assert_eq!(parsed.warnings.len(), 2);
+        assert_eq!(parsed.warnings[0], "Line 1: no timestamp found.");
+        assert!(parsed.warnings[1].starts_with("Line 8: Invalid time"));
+
+        assert!(parse("no times here\n", None, DEFAULT_FPS).is_err());
+    }
+
+    #[test]
+    fn chapters_to_segments() {
+        let entries = [
+            entry(60_000, None, "Part 2"),
+            entry(0, None, "Part 1"),
+            entry(60_000, None, "Duplicate"),
+            entry(90_000, Some(95_000), "Closed"),
+            entry(120_000, None, "Last"),
+        ];
+        assert_eq!(
+            to_segments(&entries, Some(Duration::seconds(150))),
+            vec![
+                segment(0, 60_000, "Part 1"),
+                segment(60_000, 90_000, "Part 2"),
+                segment(60_000, 90_000, "Duplicate"),
+                segment(90_000, 95_000, "Closed"),
+                segment(120_000, 150_000, "Last"),
+            ]
+        );
+        // the end of the last chapter is unknown without the file duration
+        assert_eq!(to_segments(&entries, None).len(), 4);
+        // nor can a chapter start after the end of the file
+        assert_eq!(to_segments(&entries, Some(Duration::seconds(100))).len(), 4);
+
+        assert_eq!(
+            to_markers(&entries[..2]),
+            vec![
+                Marker {
+                    time: Duration::seconds(60),
+                    name: String::from("Part 2"),
+                },
+                Marker {
+                    time: Duration::zero(),
+                    name: String::from("Part 1"),
+                },
+            ]
+        );
+    }
+}
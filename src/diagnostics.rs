@@ -0,0 +1,189 @@
+use std::{env, fmt, fs, process::Command};
+
+use crate::{
+    logging::LogLaunch,
+    probe::FFPROBE_COMMAND,
+    processing::{self, FFPLAY_COMMAND, SOX_COMMAND},
+    Config,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+// distribution name on Linux and the BSDs
+const OS_RELEASE_FILE: &str = "/etc/os-release";
+
+/// Environment Media Cutter runs in, printed for bug reports.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub version: String,
+    /// Operating system and architecture, with the distribution when known.
+    pub os: String,
+    /// Version of the GTK library linked to the interface, unknown to the library.
+    pub gtk_version: Option<String>,
+    /// First line of the version of each external program, or why it failed.
+    pub tools: Vec<(String, Result<String>)>,
+}
+
+impl Report {
+    /// Versions of the installed programs, ffmpeg being the one of
+    /// `MEDIA_CUTTER_FFMPEG_PATH` if set.
+    pub fn collect() -> Self {
+        let ffmpeg = processing::ffmpeg_program(&Config::from_env());
+        Self {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            os: os_description(),
+            gtk_version: None,
+            tools: vec![
+                (String::from("ffmpeg"), tool_version(&ffmpeg, "-version")),
+                (
+                    String::from("ffprobe"),
+                    tool_version(FFPROBE_COMMAND, "-version"),
+                ),
+                (
+                    String::from("ffplay"),
+                    tool_version(FFPLAY_COMMAND, "-version"),
+                ),
+                (String::from("sox"), tool_version(SOX_COMMAND, "--version")),
+            ],
+        }
+    }
+}
+
+// Markdown, as issues are written
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "### Media Cutter")?;
+        writeln!(f, "- version: {}", self.version)?;
+        writeln!(f)?;
+        writeln!(f, "### System")?;
+        writeln!(f, "- os: {}", self.os)?;
+        writeln!(
+            f,
+            "- gtk: {}",
+            self.gtk_version.as_deref().unwrap_or("unknown")
+        )?;
+        writeln!(f)?;
+        writeln!(f, "### Tools")?;
+        for (name, version) in &self.tools {
+            match version {
+                Ok(version) => writeln!(f, "- {}: {}", name, version)?,
+                Err(e) => writeln!(f, "- {}: unavailable ({})", name, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// First line printed by `program` given `version_arg`, e.g. `ffmpeg version 4.1`.
+pub fn tool_version(program: &str, version_arg: &str) -> Result<String> {
+    let output = Command::new(program)
+        .arg(version_arg)
+        .logged()
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    // older builds of some tools print it on stderr
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    first_line(&stdout)
+        .or_else(|| first_line(&stderr))
+        .ok_or_else(|| String::from("no version printed"))
+}
+
+fn first_line(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(String::from)
+}
+
+fn os_description() -> String {
+    let os = format!("{} ({})", env::consts::OS, env::consts::ARCH);
+    match fs::read_to_string(OS_RELEASE_FILE)
+        .ok()
+        .and_then(|os_release| pretty_name(&os_release))
+    {
+        Some(name) => format!("{}, {}", os, name),
+        None => os,
+    }
+}
+
+// `PRETTY_NAME="Debian GNU/Linux 10 (buster)"` line of os-release
+fn pretty_name(os_release: &str) -> Option<String> {
+    os_release
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .filter(|name| !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_sections() {
+        let report = Report {
+            version: String::from("1.0.0"),
+            os: String::from("linux (x86_64), Debian GNU/Linux 10 (buster)"),
+            gtk_version: Some(String::from("3.24.5")),
+            tools: vec![
+                (
+                    String::from("ffmpeg"),
+                    Ok(String::from("ffmpeg version 4.1.4-1~deb10u1")),
+                ),
+                (
+                    String::from("ffprobe"),
+                    Ok(String::from("ffprobe version 4.1.4-1~deb10u1")),
+                ),
+                (
+                    String::from("ffplay"),
+                    Ok(String::from("ffplay version 4.1.4-1~deb10u1")),
+                ),
+                (
+                    String::from("sox"),
+                    Err(String::from("No such file or directory (os error 2)")),
+                ),
+            ],
+        };
+        let text = report.to_string();
+
+        let headers: Vec<&str> = text
+            .lines()
+            .filter(|line| line.starts_with("### "))
+            .collect();
+        assert_eq!(headers, vec!["### Media Cutter", "### System", "### Tools"]);
+        assert!(text.contains("- version: 1.0.0\n"));
+        assert!(text.contains("- gtk: 3.24.5\n"));
+        assert!(text.contains("- ffmpeg: ffmpeg version 4.1.4-1~deb10u1\n"));
+        assert!(text.contains("- ffprobe: ffprobe version 4.1.4-1~deb10u1\n"));
+        assert!(text.contains("- sox: unavailable (No such file or directory (os error 2))\n"));
+
+        let report = Report {
+            gtk_version: None,
+            ..report
+        };
+        assert!(report.to_string().contains("- gtk: unknown\n"));
+    }
+
+    #[test]
+    fn os_release_names() {
+        assert_eq!(
+            pretty_name("NAME=\"Arch Linux\"\nPRETTY_NAME=\"Arch Linux\"\nID=arch\n"),
+            Some(String::from("Arch Linux"))
+        );
+        assert_eq!(pretty_name("NAME=Alpine\nPRETTY_NAME=\n"), None);
+        assert_eq!(pretty_name(""), None);
+    }
+
+    #[test]
+    fn version_lines() {
+        assert_eq!(
+            first_line("\nsox:      SoX v14.4.2\n\n"),
+            Some(String::from("sox:      SoX v14.4.2"))
+        );
+        assert_eq!(first_line(" \n"), None);
+    }
+}
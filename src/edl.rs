@@ -0,0 +1,313 @@
+use std::{fmt, str::FromStr};
+
+use chrono::Duration;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// SMPTE timecode, `HH:MM:SS:FF` or `HH:MM:SS;FF` for drop frame.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub struct Timecode {
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    /// Frame numbers are skipped to keep up with NTSC rates, see `to_duration`.
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    /// Time since `00:00:00:00` at `fps` frames per second, e.g. 25.0 or 29.97.
+    pub fn to_duration(&self, fps: f64) -> Duration {
+        let nominal_fps = u64::from(fps.round() as u32);
+        let total_minutes = u64::from(self.hours * 60 + self.minutes);
+        let mut frames =
+            (total_minutes * 60 + u64::from(self.seconds)) * nominal_fps + u64::from(self.frames);
+
+        if self.drop_frame {
+            // 2 frame numbers dropped at 29.97 (4 at 59.94) each minute, except every tenth
+            let dropped_per_minute = (nominal_fps as f64 / 15.0).round() as u64;
+            frames -= dropped_per_minute * (total_minutes - total_minutes / 10);
+        }
+
+        Duration::milliseconds((frames as f64 * 1000.0 / fps).round() as i64)
+    }
+
+    /// Non drop frame timecode of the frame shown at `time`, the inverse of
+    /// `to_duration`.
+    pub fn from_duration(time: Duration, fps: f64) -> Self {
+        let nominal_fps = u64::from(fps.round() as u32);
+        let frames = (time.num_milliseconds().max(0) as f64 * fps / 1000.0).round() as u64;
+        let seconds = frames / nominal_fps;
+        Self {
+            hours: (seconds / 3600) as u32,
+            minutes: (seconds / 60 % 60) as u32,
+            seconds: (seconds % 60) as u32,
+            frames: (frames % nominal_fps) as u32,
+            drop_frame: false,
+        }
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = String;
+
+    fn from_str(timecode: &str) -> Result<Self> {
+        let invalid = || format!("Invalid timecode: {}", timecode);
+
+        let drop_frame = timecode.contains(';');
+        let parts = timecode
+            .split(&[':', ';', '.'][..])
+            .map(|part| part.parse::<u32>().map_err(|_| invalid()))
+            .collect::<Result<Vec<u32>>>()?;
+        match parts[..] {
+            [hours, minutes, seconds, frames] if minutes < 60 && seconds < 60 => Ok(Self {
+                hours,
+                minutes,
+                seconds,
+                frames,
+                drop_frame,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours,
+            self.minutes,
+            self.seconds,
+            if self.drop_frame { ';' } else { ':' },
+            self.frames
+        )
+    }
+}
+
+/// Event of an edit decision list: `source_in..source_out` of the reel is
+/// placed at `record_in..record_out` of the edited program.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EdlClip {
+    pub event: u32,
+    pub reel: String,
+    /// `V`, `A`, `A2`, `AA/V`…
+    pub track: String,
+    pub source_in: Timecode,
+    pub source_out: Timecode,
+    pub record_in: Timecode,
+    pub record_out: Timecode,
+    /// From the `* FROM CLIP NAME:` comment following the event, if any.
+    pub clip_name: Option<String>,
+}
+
+impl EdlClip {
+    /// Range of the source to keep, as `from_time` and `to_time`.
+    pub fn source_range(&self, fps: f64) -> (Duration, Duration) {
+        (
+            self.source_in.to_duration(fps),
+            self.source_out.to_duration(fps),
+        )
+    }
+}
+
+/// Events of a CMX3600 edit decision list. Titles, comments other than clip
+/// names and motion effects are skipped.
+pub fn parse_edl(contents: &str) -> Result<Vec<EdlClip>> {
+    let mut clips: Vec<EdlClip> = Vec::new();
+    let mut drop_frame = false;
+
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("EDL line {}: {}", number + 1, message);
+
+        if let Some(mode) = line.strip_prefix("FCM:") {
+            drop_frame = mode.trim().eq_ignore_ascii_case("DROP FRAME");
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('*') {
+            let comment = comment.trim();
+            if let Some(name) = comment.strip_prefix("FROM CLIP NAME:") {
+                if let Some(clip) = clips.last_mut() {
+                    clip.clip_name = Some(String::from(name.trim()));
+                }
+            }
+            continue;
+        }
+        if !line.starts_with(|c: char| c.is_ascii_digit()) {
+            // TITLE:, M2 motion effects, blank lines…
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 {
+            return Err(error(String::from("incomplete event.")));
+        }
+        let event = fields[0]
+            .parse()
+            .map_err(|_| error(format!("invalid event number {}.", fields[0])))?;
+
+        // dissolves and wipes carry their duration in frames after the transition
+        let timecodes = &fields[fields.len() - 4..];
+        let mut parsed = Vec::with_capacity(4);
+        for timecode in timecodes {
+            let mut timecode: Timecode = timecode.parse().map_err(error)?;
+            timecode.drop_frame |= drop_frame;
+            parsed.push(timecode);
+        }
+
+        let clip = EdlClip {
+            event,
+            reel: String::from(fields[1]),
+            track: String::from(fields[2]),
+            source_in: parsed[0],
+            source_out: parsed[1],
+            record_in: parsed[2],
+            record_out: parsed[3],
+            clip_name: None,
+        };
+        if clip.source_out < clip.source_in {
+            return Err(error(String::from(
+                "source out point is before the in point.",
+            )));
+        }
+        clips.push(clip);
+    }
+
+    Ok(clips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_EDL: &str = "TITLE: Interview cut
+FCM: NON-DROP FRAME
+
+001  AX       V     C        00:00:03:10 00:00:07:20 01:00:00:00 01:00:04:10
+* FROM CLIP NAME: interview.mov
+
+002  AX       AA/V  C        00:12:00:00 00:12:30:05 01:00:04:10 01:00:34:15
+M2   AX       050.0                      00:12:00:00
+
+003  BL       V     D    030 00:00:00:00 00:00:01:00 01:00:34:15 01:00:35:15
+";
+
+    fn timecode(text: &str) -> Timecode {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn sample_edl() {
+        let clips = parse_edl(SAMPLE_EDL).unwrap();
+        assert_eq!(clips.len(), 3);
+
+        assert_eq!(
+            clips[0],
+            EdlClip {
+                event: 1,
+                reel: String::from("AX"),
+                track: String::from("V"),
+                source_in: timecode("00:00:03:10"),
+                source_out: timecode("00:00:07:20"),
+                record_in: timecode("01:00:00:00"),
+                record_out: timecode("01:00:04:10"),
+                clip_name: Some(String::from("interview.mov")),
+            }
+        );
+        assert_eq!(clips[1].track, "AA/V");
+        assert_eq!(clips[1].clip_name, None);
+        assert_eq!(
+            clips[1].source_range(25.0),
+            (Duration::minutes(12), Duration::milliseconds(750_200))
+        );
+        // the dissolve duration is not a timecode
+        assert_eq!(clips[2].reel, "BL");
+        assert_eq!(clips[2].source_out, timecode("00:00:01:00"));
+    }
+
+    #[test]
+    fn timecode_from_duration() {
+        assert_eq!(
+            Timecode::from_duration(Duration::milliseconds(3400), 25.0),
+            timecode("00:00:03:10")
+        );
+        assert_eq!(
+            Timecode::from_duration(Duration::milliseconds(3_723_500), 24.0),
+            timecode("01:02:03:12")
+        );
+        assert_eq!(
+            Timecode::from_duration(Duration::milliseconds(60_060), 29.97),
+            timecode("00:01:00:00")
+        );
+        // rounded to the nearest frame
+        assert_eq!(
+            Timecode::from_duration(Duration::milliseconds(1019), 25.0),
+            timecode("00:00:01:00")
+        );
+        assert_eq!(
+            Timecode::from_duration(Duration::milliseconds(1021), 25.0),
+            timecode("00:00:01:01")
+        );
+    }
+
+    #[test]
+    fn timecode_to_duration() {
+        assert_eq!(
+            timecode("00:00:03:10").to_duration(25.0),
+            Duration::milliseconds(3400)
+        );
+        assert_eq!(
+            timecode("01:02:03:12").to_duration(24.0),
+            Duration::milliseconds(3_723_500)
+        );
+        // NTSC without drop frame drifts from the wall clock
+        assert_eq!(
+            timecode("00:01:00:00").to_duration(29.97),
+            Duration::milliseconds(60_060)
+        );
+
+        // drop frame: 00:01:00;00 and ;01 do not exist, ;02 follows 00:00:59;29
+        let first_after_drop = timecode("00:01:00;02");
+        assert!(first_after_drop.drop_frame);
+        assert_eq!(
+            first_after_drop.to_duration(29.97),
+            Duration::milliseconds(60_060)
+        );
+        // no drop on tenth minutes: one hour of timecode is an hour of time
+        assert_eq!(
+            timecode("01:00:00;00").to_duration(29.97),
+            Duration::milliseconds(3_600_000)
+        );
+    }
+
+    #[test]
+    fn drop_frame_mode() {
+        let clips = parse_edl(
+            "FCM: DROP FRAME\n001  AX V C 00:10:00:00 00:10:01:00 00:00:00:00 00:00:01:00\n",
+        )
+        .unwrap();
+        assert!(clips[0].source_in.drop_frame);
+        assert_eq!(clips[0].source_in.to_string(), "00:10:00;00");
+        assert_eq!(
+            clips[0].source_in.to_duration(29.97),
+            Duration::milliseconds(600_000)
+        );
+    }
+
+    #[test]
+    fn invalid_edl() {
+        assert!(parse_edl("001  AX V C 00:00:01:00 00:00:02:00\n")
+            .unwrap_err()
+            .contains("line 1"));
+        assert!(
+            parse_edl("TITLE: x\n001  AX V C 00:00:01:00 00:00:0x:00 01:00:00:00 01:00:01:00")
+                .unwrap_err()
+                .contains("line 2")
+        );
+        assert!(parse_edl("001  AX V C 00:00:05:00 00:00:02:00 01:00:00:00 01:00:01:00").is_err());
+        assert!(parse_edl("001  AX V C 00:61:00:00 00:62:00:00 01:00:00:00 01:00:01:00").is_err());
+        assert_eq!(parse_edl("TITLE: empty\n\n"), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+/// Pad of a filtergraph: either an input stream such as `0:a` or an
+/// intermediate result created by `FilterGraph`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Label(String);
+
+impl Label {
+    /// Stream `stream` (`v`, `a`, `v:0`…) of the input file `file`.
+    pub fn input(file: usize, stream: &str) -> Self {
+        Label(format!("{}:{}", file, stream))
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}]", self.0)
+    }
+}
+
+/// Builder for `-filter_complex` graphs: each node consumes labels and hands
+/// back freshly allocated ones, so streams are wired without naming them.
+///
+/// Filter arguments are written as is, values must already be escaped.
+#[derive(Default)]
+pub struct FilterGraph {
+    nodes: Vec<String>,
+    outputs: Vec<Label>,
+    next_label: usize,
+}
+
+impl FilterGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a filter reading `inputs` and producing `output_count` new streams.
+    pub fn node(&mut self, inputs: &[&Label], filter: &str, output_count: usize) -> Vec<Label> {
+        let outputs: Vec<Label> = (0..output_count).map(|_| self.new_label()).collect();
+        self.push_node(inputs, filter, &outputs);
+        outputs
+    }
+
+    /// Add a linear chain of one-input one-output filters, e.g. `highpass=f=200`
+    /// then `volume=3dB`. An empty chain passes the stream through.
+    pub fn chain<S: AsRef<str>>(&mut self, input: &Label, filters: &[S]) -> Label {
+        let filters: Vec<&str> = filters.iter().map(AsRef::as_ref).collect();
+        let filter = if filters.is_empty() {
+            String::from("null")
+        } else {
+            filters.join(",")
+        };
+        self.node(&[input], &filter, 1).remove(0)
+    }
+
+    /// Sum audio `inputs`, each at its gain in dB, as they are: `amix` would
+    /// otherwise scale them down by their count. A single input at 0 dB is
+    /// handed back untouched.
+    pub fn mix(&mut self, inputs: &[(Label, f64)]) -> Label {
+        if let [(input, gain_db)] = inputs {
+            if *gain_db == 0.0 {
+                return input.clone();
+            }
+        }
+        let mut gained: Vec<Label> = inputs
+            .iter()
+            .map(|(input, gain_db)| self.chain(input, &[format!("volume={}dB", gain_db)]))
+            .collect();
+        if gained.len() == 1 {
+            return gained.remove(0);
+        }
+        let gained: Vec<&Label> = gained.iter().collect();
+        self.node(
+            &gained,
+            &format!("amix=inputs={}:normalize=0", inputs.len()),
+            1,
+        )
+        .remove(0)
+    }
+
+    /// Mark `label` as an output stream of the graph, mapped in order.
+    pub fn output(&mut self, label: Label) {
+        self.outputs.push(label);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Graph description suitable for `-filter_complex`.
+    pub fn to_filter_complex(&self) -> String {
+        self.nodes.join(";")
+    }
+
+    /// `-map` arguments selecting the output streams.
+    pub fn map_args(&self) -> Vec<String> {
+        let mut args = Vec::with_capacity(self.outputs.len() * 2);
+        for output in &self.outputs {
+            args.push(String::from("-map"));
+            args.push(output.to_string());
+        }
+        args
+    }
+
+    /// `-filter_complex` followed by the `-map` arguments.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![String::from("-filter_complex"), self.to_filter_complex()];
+        args.extend(self.map_args());
+        args
+    }
+
+    fn new_label(&mut self) -> Label {
+        let label = Label(format!("s{}", self.next_label));
+        self.next_label += 1;
+        label
+    }
+
+    fn push_node(&mut self, inputs: &[&Label], filter: &str, outputs: &[Label]) {
+        let mut node = String::new();
+        for input in inputs {
+            node.push_str(&input.to_string());
+        }
+        node.push_str(filter);
+        for output in outputs {
+            node.push_str(&output.to_string());
+        }
+        self.nodes.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_process_concat() {
+        let mut graph = FilterGraph::new();
+
+        let video = Label::input(0, "v");
+        let audio = Label::input(0, "a");
+        let copies = graph.node(&[&video], "split=2", 2);
+        let reversed = graph.chain(&copies[1], &["reverse"]);
+        let concat = graph.node(&[&copies[0], &reversed], "concat=n=2:v=1:a=0", 1);
+        let cleaned = graph.chain(&audio, &["highpass=f=200", "volume=3dB"]);
+        graph.output(concat[0].clone());
+        graph.output(cleaned);
+
+        assert_eq!(
+            graph.to_filter_complex(),
+            "[0:v]split=2[s0][s1];[s1]reverse[s2];[s0][s2]concat=n=2:v=1:a=0[s3];\
+             [0:a]highpass=f=200,volume=3dB[s4]"
+        );
+        assert_eq!(graph.map_args(), vec!["-map", "[s3]", "-map", "[s4]"]);
+        assert_eq!(graph.args()[0], "-filter_complex");
+        assert_eq!(graph.args().len(), 6);
+    }
+
+    #[test]
+    fn empty_chain_passes_through() {
+        let mut graph = FilterGraph::new();
+        assert!(graph.is_empty());
+
+        let output = graph.chain::<&str>(&Label::input(1, "a:0"), &[]);
+        graph.output(output);
+
+        assert!(!graph.is_empty());
+        assert_eq!(graph.to_filter_complex(), "[1:a:0]null[s0]");
+        assert_eq!(graph.map_args(), vec!["-map", "[s0]"]);
+    }
+
+    #[test]
+    fn mixed_tracks() {
+        let mut graph = FilterGraph::new();
+        let track = |index: usize| Label::input(0, &format!("a:{}", index));
+
+        assert_eq!(graph.mix(&[(track(1), 0.0)]), track(1));
+        assert!(graph.is_empty());
+
+        let mix = graph.mix(&[(track(0), 0.0), (track(1), -6.0)]);
+        graph.output(mix);
+        assert_eq!(
+            graph.to_filter_complex(),
+            "[0:a:0]volume=0dB[s0];[0:a:1]volume=-6dB[s1];[s0][s1]amix=inputs=2:normalize=0[s2]"
+        );
+    }
+}
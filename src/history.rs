@@ -0,0 +1,195 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::Config;
+
+/// Number of changes that can be undone, older ones are forgotten.
+pub const HISTORY_CAPACITY: usize = 100;
+/// Changes of the same settings closer than this make a single history entry.
+pub const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Previous states to undo changes and the undone ones to redo them, e.g.
+/// `Config` snapshots. A change replaces the undone states: they cannot be
+/// redone anymore.
+#[derive(Clone, Debug)]
+pub struct History<T> {
+    capacity: usize,
+    coalesce_window: Duration,
+    undo: VecDeque<T>,
+    redo: Vec<T>,
+    // settings the last recorded change touched and when, continuing it adds no entry
+    last_change: Option<(Vec<String>, Instant)>,
+}
+
+impl<T> History<T> {
+    pub fn new(capacity: usize, coalesce_window: Duration) -> Self {
+        Self {
+            capacity,
+            coalesce_window,
+            undo: VecDeque::with_capacity(capacity),
+            redo: Vec::new(),
+            last_change: None,
+        }
+    }
+
+    /// Record a change of the `changed` settings made at `now`, `before`
+    /// being the state it started from. The changes of the same settings
+    /// following each other within the coalesce window, such as those of a
+    /// slider drag, are undone at once.
+    pub fn record(&mut self, before: T, changed: Vec<String>, now: Instant) {
+        if changed.is_empty() {
+            return;
+        }
+        self.redo.clear();
+
+        let continued = match self.last_change {
+            Some((ref last_changed, at)) => {
+                *last_changed == changed && now.duration_since(at) < self.coalesce_window
+            }
+            None => false,
+        };
+        if !continued {
+            if self.undo.len() == self.capacity {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(before);
+        }
+        self.last_change = Some((changed, now));
+    }
+
+    /// State before the last change, `current` becoming the one to redo.
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.last_change = None;
+        Some(previous)
+    }
+
+    /// State before the last undo, `current` becoming the one to undo.
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push_back(current);
+        self.last_change = None;
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+/// Names of the settings that differ between `before` and `after`, as saved
+/// in projects. Settings that are not saved are not compared.
+pub fn changed_settings(before: &Config, after: &Config) -> Vec<String> {
+    let (before, after) = match (serde_json::to_value(before), serde_json::to_value(after)) {
+        (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) => {
+            (before, after)
+        }
+        _ => return Vec::new(),
+    };
+    after
+        .iter()
+        .filter(|&(name, value)| before.get(name) != Some(value))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration as TimeDuration;
+
+    fn changed(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| String::from(name)).collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let now = Instant::now();
+        let mut history = History::new(HISTORY_CAPACITY, COALESCE_WINDOW);
+        assert!(!history.can_undo());
+        assert_eq!(history.undo(0), None);
+
+        history.record(0, changed(&["from_time"]), now);
+        history.record(1, changed(&["to_time"]), now);
+        assert_eq!(history.undo(2), Some(1));
+        assert_eq!(history.undo(1), Some(0));
+        assert_eq!(history.undo(0), None);
+        assert!(history.can_redo());
+        assert_eq!(history.redo(0), Some(1));
+        assert_eq!(history.redo(1), Some(2));
+        assert_eq!(history.redo(2), None);
+
+        // a new change cannot be followed by the undone ones
+        assert_eq!(history.undo(2), Some(1));
+        history.record(1, changed(&["volume_change"]), now);
+        assert!(!history.can_redo());
+        assert_eq!(history.undo(3), Some(1));
+        assert_eq!(history.undo(1), Some(0));
+    }
+
+    #[test]
+    fn coalesced_changes() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut history = History::new(HISTORY_CAPACITY, COALESCE_WINDOW);
+
+        // a slider drag, each step within the window of the previous one
+        for (step, millis) in [0, 400, 800, 1200, 1600].iter().enumerate() {
+            history.record(step, changed(&["from_time"]), at(*millis));
+        }
+        // another setting, or the same one later on, starts another entry
+        history.record(5, changed(&["to_time"]), at(1700));
+        history.record(6, changed(&["to_time"]), at(3000));
+
+        assert_eq!(history.undo(7), Some(6));
+        assert_eq!(history.undo(6), Some(5));
+        assert_eq!(history.undo(5), Some(0));
+        assert!(!history.can_undo());
+
+        // an undo ends the drag
+        let mut history = History::new(HISTORY_CAPACITY, COALESCE_WINDOW);
+        history.record(0, changed(&["from_time"]), at(0));
+        assert_eq!(history.undo(1), Some(0));
+        history.record(0, changed(&["from_time"]), at(100));
+        assert_eq!(history.undo(2), Some(0));
+    }
+
+    #[test]
+    fn bounded_history() {
+        let now = Instant::now();
+        let mut history = History::new(3, COALESCE_WINDOW);
+        for state in 0..5 {
+            let setting = format!("setting_{}", state);
+            history.record(state, vec![setting], now);
+        }
+        assert_eq!(history.undo(5), Some(4));
+        assert_eq!(history.undo(4), Some(3));
+        assert_eq!(history.undo(3), Some(2));
+        assert_eq!(history.undo(2), None);
+
+        // nothing is recorded without a change
+        history.record(2, Vec::new(), now);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn config_changes() {
+        let before = Config::new();
+        assert!(changed_settings(&before, &before).is_empty());
+
+        let mut after = before.clone();
+        after.from_time = TimeDuration::seconds(12);
+        after.volume_change = -3.0;
+        after.preview = true;
+        let mut names = changed_settings(&before, &after);
+        names.sort();
+        assert_eq!(names, changed(&["from_time", "preview", "volume_change"]));
+    }
+}
@@ -0,0 +1,342 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+type Result<T> = std::result::Result<T, String>;
+
+lazy_static! {
+    // the number of an ffmpeg image2 pattern: `%d` or zero padded `%05d`
+    static ref NUMBER_RE: Regex = Regex::new(r"%(0[1-9][0-9]*)?d").unwrap();
+}
+
+/// Pattern naming every picture of a sequence, inferred from one of them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SequencePattern {
+    /// printf-style path given to ffmpeg, e.g. `frames/frame_%05d.png`.
+    pub pattern: String,
+    /// Number of the first picture of the run holding the chosen one.
+    pub start_number: u32,
+    /// Pictures of that run, ffmpeg stops reading at the first missing number.
+    pub frame_count: usize,
+    /// Whether other pictures follow the pattern outside of the run, left out
+    /// by ffmpeg.
+    pub has_gaps: bool,
+}
+
+// file name split around its number: `frame_`, 5 digits (0 when unpadded), `.png`
+#[derive(Clone, PartialEq, Debug)]
+struct NameTemplate {
+    prefix: String,
+    width: usize,
+    suffix: String,
+}
+
+impl NameTemplate {
+    fn from_pattern(file_name: &str) -> Option<Self> {
+        let number = NUMBER_RE.find(file_name)?;
+        let width = number.as_str()[1..number.as_str().len() - 1]
+            .parse()
+            .unwrap_or(0);
+        Some(Self {
+            prefix: file_name[..number.start()].replace("%%", "%"),
+            width,
+            suffix: file_name[number.end()..].replace("%%", "%"),
+        })
+    }
+
+    // number of `file_name` if this template could have produced it
+    fn number_of(&self, file_name: &str) -> Option<u32> {
+        let digits = file_name
+            .strip_prefix(self.prefix.as_str())?
+            .strip_suffix(self.suffix.as_str())?;
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let number = digits.parse::<u32>().ok()?;
+        // padded as ffmpeg writes it: `frame_007` is not `%d`, nor `frame_7` `%03d`
+        if format!("{:0width$}", number, width = self.width) == digits {
+            Some(number)
+        } else {
+            None
+        }
+    }
+
+    fn file_name(&self, number: u32) -> String {
+        format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            number,
+            self.suffix,
+            width = self.width
+        )
+    }
+
+    fn to_pattern(&self) -> String {
+        let number = if self.width == 0 {
+            String::from("%d")
+        } else {
+            format!("%0{}d", self.width)
+        };
+        format!(
+            "{}{}{}",
+            self.prefix.replace('%', "%%"),
+            number,
+            self.suffix.replace('%', "%%")
+        )
+    }
+}
+
+/// Whether `input` names pictures with a number such as `%05d` rather than
+/// a single file.
+pub fn is_pattern(input: &str) -> bool {
+    matches!(
+        Path::new(input).file_name(),
+        Some(name) if NUMBER_RE.is_match(&name.to_string_lossy())
+    )
+}
+
+/// Pattern of the sequence `file` belongs to, from the numbering of the
+/// pictures next to it.
+pub fn infer_pattern(file: &Path) -> Result<SequencePattern> {
+    let file_name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("Error: {} is not a file.", file.display()))?;
+    let dir = file.parent().unwrap_or_else(|| Path::new(""));
+    let siblings = list_dir(dir)?;
+
+    let (template, start_number, frame_count, has_gaps) = infer_from_names(&file_name, &siblings)
+        .ok_or_else(|| {
+        format!(
+            "Error: {} is not numbered as part of a sequence.",
+            file_name
+        )
+    })?;
+    Ok(SequencePattern {
+        pattern: dir
+            .join(template.to_pattern())
+            .to_string_lossy()
+            .into_owned(),
+        start_number,
+        frame_count,
+        has_gaps,
+    })
+}
+
+/// Lowest number of the existing pictures matching `pattern`, if any.
+pub fn first_number(pattern: &str) -> Option<u32> {
+    let path = Path::new(pattern);
+    let template = NameTemplate::from_pattern(&path.file_name()?.to_string_lossy())?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    list_dir(dir)
+        .ok()?
+        .iter()
+        .filter_map(|name| template.number_of(name))
+        .min()
+}
+
+/// Files named by `pattern` from `start_number` up to the first missing one,
+/// e.g. the chunks written by ffmpeg's segment muxer.
+pub fn numbered_files(pattern: &str, start_number: u32) -> Vec<String> {
+    let path = Path::new(pattern);
+    let template = match path
+        .file_name()
+        .and_then(|name| NameTemplate::from_pattern(&name.to_string_lossy()))
+    {
+        Some(template) => template,
+        None => return Vec::new(),
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    (start_number..)
+        .map(|number| dir.join(template.file_name(number)))
+        .take_while(|file| file.is_file())
+        .map(|file| file.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<String>> {
+    let dir = if dir == Path::new("") {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Could not list {}.\nError: {}", dir.display(), e))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect())
+}
+
+// template, first number and length of the run holding `file_name`, and
+// whether numbered siblings lie outside of it
+fn infer_from_names(
+    file_name: &str,
+    siblings: &[String],
+) -> Option<(NameTemplate, u32, usize, bool)> {
+    // the last number of the name, `take2_frame_0042.png` counts frames
+    let stem_end = file_name.rfind('.').unwrap_or(file_name.len());
+    let digits_end = file_name[..stem_end].rfind(|c: char| c.is_ascii_digit())? + 1;
+    let digits_start = file_name[..digits_end]
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+    let (prefix, digits, suffix) = (
+        &file_name[..digits_start],
+        &file_name[digits_start..digits_end],
+        &file_name[digits_end..],
+    );
+
+    // zero padding shows on small numbers only: `frame_1000` may belong to
+    // `frame_%04d` as well as to `frame_%d`
+    let padded_width = |name: &str| {
+        let digits = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+        if digits.len() > 1
+            && digits.starts_with('0')
+            && digits.bytes().all(|byte| byte.is_ascii_digit())
+        {
+            Some(digits.len())
+        } else {
+            None
+        }
+    };
+    let width = match padded_width(file_name) {
+        Some(width) => width,
+        None => siblings
+            .iter()
+            .filter_map(|name| padded_width(name))
+            .min()
+            .filter(|&width| width <= digits.len())
+            .unwrap_or(0),
+    };
+    let template = NameTemplate {
+        prefix: String::from(prefix),
+        width,
+        suffix: String::from(suffix),
+    };
+
+    let selected = template.number_of(file_name)?;
+    let numbers: HashSet<u32> = siblings
+        .iter()
+        .filter_map(|name| template.number_of(name))
+        .chain(std::iter::once(selected))
+        .collect();
+    let mut start_number = selected;
+    while start_number > 0 && numbers.contains(&(start_number - 1)) {
+        start_number -= 1;
+    }
+    let mut end_number = selected;
+    while numbers.contains(&(end_number + 1)) {
+        end_number += 1;
+    }
+    let frame_count = (end_number - start_number) as usize + 1;
+    let has_gaps = numbers.len() > frame_count;
+    Some((template, start_number, frame_count, has_gaps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn pattern_detection() {
+        assert!(is_pattern("frames/frame_%05d.png"));
+        assert!(is_pattern("shot%d.jpg"));
+        assert!(!is_pattern("frames/frame_00001.png"));
+        assert!(!is_pattern("100%.png"));
+        // the directory is not part of the numbering
+        assert!(!is_pattern("renders_%d/clip.mp4"));
+    }
+
+    #[test]
+    fn padded_numbering() {
+        let siblings = names(&[
+            "frame_00001.png",
+            "frame_00002.png",
+            "frame_00003.png",
+            "frame_00004.png",
+            "notes.txt",
+            "frame_00002.jpg",
+        ]);
+        let (template, start, count, gaps) =
+            infer_from_names("frame_00003.png", &siblings).unwrap();
+        assert_eq!(template.to_pattern(), "frame_%05d.png");
+        assert_eq!((start, count, gaps), (1, 4, false));
+
+        // numbers past the padding still follow it
+        let siblings = names(&["f_0998.png", "f_0999.png", "f_1000.png", "f_1001.png"]);
+        let (template, start, count, _) = infer_from_names("f_1000.png", &siblings).unwrap();
+        assert_eq!(template.to_pattern(), "f_%04d.png");
+        assert_eq!((start, count), (998, 4));
+    }
+
+    #[test]
+    fn unpadded_numbering() {
+        let siblings = names(&[
+            "shot1.jpg",
+            "shot2.jpg",
+            "shot9.jpg",
+            "shot10.jpg",
+            "shot11.jpg",
+        ]);
+        let (template, start, count, gaps) = infer_from_names("shot10.jpg", &siblings).unwrap();
+        assert_eq!(template.to_pattern(), "shot%d.jpg");
+        assert_eq!((start, count, gaps), (9, 3, true));
+
+        // padded files of another export are left out
+        let siblings = names(&["shot1.jpg", "shot2.jpg", "shot3.jpg", "shot001.jpg"]);
+        let (template, start, count, gaps) = infer_from_names("shot2.jpg", &siblings).unwrap();
+        assert_eq!(template.to_pattern(), "shot%d.jpg");
+        assert_eq!((start, count, gaps), (1, 3, false));
+    }
+
+    #[test]
+    fn numbering_gaps() {
+        let siblings = names(&[
+            "take2_0000.tif",
+            "take2_0001.tif",
+            "take2_0003.tif",
+            "take2_0004.tif",
+            "take2_0005.tif",
+        ]);
+        // the run of the chosen picture, the number of the take is kept
+        let (template, start, count, gaps) = infer_from_names("take2_0004.tif", &siblings).unwrap();
+        assert_eq!(template.to_pattern(), "take2_%04d.tif");
+        assert_eq!((start, count, gaps), (3, 3, true));
+        let (_, start, count, gaps) = infer_from_names("take2_0000.tif", &siblings).unwrap();
+        assert_eq!((start, count, gaps), (0, 2, true));
+    }
+
+    #[test]
+    fn numbered_chunks() {
+        let mut dir = std::env::temp_dir();
+        dir.push("media_cutter_numbered_files_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // a chunk left by an older run, after the gap
+        for name in &["out_000.ts", "out_001.ts", "out_002.ts", "out_004.ts"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let pattern = dir.join("out_%03d.ts").to_string_lossy().into_owned();
+        let files = numbered_files(&pattern, 0);
+        assert_eq!(files.len(), 3);
+        assert!(files[2].ends_with("out_002.ts"));
+        assert!(numbered_files(&pattern, 3).is_empty());
+        assert!(numbered_files(&dir.join("out.ts").to_string_lossy(), 0).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unnumbered_names() {
+        assert_eq!(infer_from_names("cover.png", &names(&["cover.png"])), None);
+        // percent signs are escaped for ffmpeg
+        let (template, ..) = infer_from_names("100%_01.png", &names(&["100%_02.png"])).unwrap();
+        assert_eq!(template.to_pattern(), "100%%_%02d.png");
+        assert_eq!(NameTemplate::from_pattern("100%%_%02d.png"), Some(template));
+    }
+}
@@ -1,6 +1,72 @@
+use std::{
+    env, fmt, fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
 use chrono::Duration;
+use serde::{Deserialize, Serialize};
 
+pub mod capabilities;
+pub mod capture;
+pub mod chapters;
+pub mod cli;
+pub mod containers;
+pub mod cue;
+pub mod cutlist;
+pub mod diagnostics;
+pub mod edl;
+pub mod filtergraph;
+pub mod history;
+pub mod image_sequence;
+pub mod logging;
+pub mod output_pattern;
+pub mod presets;
+pub mod probe;
 pub mod processing;
+pub mod progress;
+pub mod project;
+pub mod queue;
+pub mod recovery;
+pub mod silence;
+pub mod smartcut;
+pub mod validation;
+pub mod waveform;
+
+// overshoots of the input end below this are left to probe imprecision
+const END_OVERSHOOT_TOLERANCE_MS: i64 = 100;
+
+/// Speed and size tradeoffs of the x264 and x265 encoders, fastest first.
+pub const ENCODER_PRESETS: [&str; 10] = [
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+    "placebo",
+];
+
+/// Verbosity levels of ffmpeg, quietest first.
+pub const LOG_LEVELS: [&str; 9] = [
+    "quiet", "panic", "fatal", "error", "warning", "info", "verbose", "debug", "trace",
+];
+
+/// Protocols of the network inputs, read by ffmpeg and ffprobe themselves.
+pub const URL_SCHEMES: [&str; 9] = [
+    "http", "https", "rtmp", "rtmps", "rtsp", "rtsps", "srt", "udp", "ftp",
+];
+
+/// Output path standing for the standard output, to pipe into other tools.
+pub const STDOUT_OUTPUT: &str = "-";
+
+/// Environment variables read by `Config::from_env`.
+pub const FFMPEG_PATH_VAR: &str = "MEDIA_CUTTER_FFMPEG_PATH";
+pub const TEMP_DIR_VAR: &str = "MEDIA_CUTTER_TEMP_DIR";
+pub const LOGLEVEL_VAR: &str = "MEDIA_CUTTER_LOGLEVEL";
 
 #[macro_export]
 macro_rules! message_dialog {
@@ -48,6 +114,19 @@ macro_rules! clone {
             move |$(clone!(@param $p),)+| $body
         }
     );
+    // typed parameters, with the return type when the closure gives one
+    ($($n:ident),+ => move |$($p:ident: $t:ty),+| -> $r:ty { $($body:tt)* }) => (
+        {
+            $( let $n = $n.clone(); )+
+            move |$($p: $t),+| -> $r { $($body)* }
+        }
+    );
+    ($($n:ident),+ => move |$($p:ident: $t:ty),+| $body:expr) => (
+        {
+            $( let $n = $n.clone(); )+
+            move |$($p: $t),+| $body
+        }
+    );
 }
 
 #[macro_export]
@@ -59,6 +138,49 @@ macro_rules! get_widget {
     };
 }
 
+/// Scheme of `input` when it is a URL, such as `https` for
+/// `https://example.com/talk.mp4`.
+pub fn url_scheme(input: &str) -> Option<&str> {
+    let (scheme, _) = input.split_once("://")?;
+    let mut chars = scheme.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {}
+        _ => return None,
+    }
+    if chars.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.') {
+        Some(scheme)
+    } else {
+        None
+    }
+}
+
+/// Whether `input` is a URL rather than a local file path.
+pub fn is_url(input: &str) -> bool {
+    url_scheme(input).is_some()
+}
+
+/// Kind of stream and index among the streams of that kind of an ffmpeg
+/// stream specifier such as `s` or `a:1`, for the kinds ffprobe reports.
+pub(crate) fn parse_stream_specifier(spec: &str) -> Option<(&'static str, Option<u32>)> {
+    let (kind, index) = match spec.split_once(':') {
+        Some((kind, index)) => (kind, Some(index)),
+        None => (spec, None),
+    };
+    let kind = match kind {
+        "v" => "video",
+        "a" => "audio",
+        "s" => "subtitle",
+        "d" => "data",
+        "t" => "attachment",
+        _ => return None,
+    };
+    match index {
+        Some(index) if !index.bytes().all(|byte| byte.is_ascii_digit()) => None,
+        Some(index) => index.parse().ok().map(|index| (kind, Some(index))),
+        None => Some((kind, None)),
+    }
+}
+
 fn duration_to_string(time: Duration) -> String {
     format!(
         "{}:{}:{}.{}",
@@ -69,6 +191,73 @@ fn duration_to_string(time: Duration) -> String {
     )
 }
 
+fn seconds_to_string(time: Duration) -> String {
+    format!("{:.3}s", time.num_milliseconds() as f64 / 1000.0)
+}
+
+// tried in order: `..` before `-` and `to` so that neither splits it
+const RANGE_SEPARATORS: [&str; 5] = ["..", "–", "-", " to ", ","];
+
+/// Time typed as seconds, `M:SS` or `H:MM:SS`, seconds possibly with a
+/// fraction, e.g. `83`, `1:23.5` or `1:02:03`.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let invalid = || {
+        format!(
+            "Invalid time: \"{}\", expected e.g. 1:23 or 1:02:03.5.",
+            text
+        )
+    };
+
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    let (seconds, units) = parts.split_last().ok_or_else(invalid)?;
+    let seconds: f64 = match seconds.parse() {
+        Ok(seconds) if seconds >= 0.0 && seconds < f64::from(u32::MAX) => seconds,
+        _ => return Err(invalid()),
+    };
+    // the largest unit is unbounded, the others must stay below 60
+    if !units.is_empty() && seconds >= 60.0 {
+        return Err(invalid());
+    }
+
+    let mut minutes = 0;
+    for (position, unit) in units.iter().enumerate() {
+        if unit.is_empty() || !unit.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let value: i64 = unit.parse().map_err(|_| invalid())?;
+        if position > 0 && value >= 60 {
+            return Err(invalid());
+        }
+        minutes = minutes * 60 + value;
+    }
+
+    Ok(Duration::minutes(minutes) + Duration::milliseconds((seconds * 1000.0).round() as i64))
+}
+
+/// Start and end times typed at once, e.g. `1:23-2:45`, `83..165` or
+/// `1:23 to 2:45`.
+pub fn parse_range(text: &str) -> Result<(Duration, Duration), String> {
+    let (start, end) = RANGE_SEPARATORS
+        .iter()
+        .find_map(|separator| text.split_once(separator))
+        .ok_or_else(|| {
+            format!(
+                "Invalid range: \"{}\", expected a start and an end time such as 1:23-2:45.",
+                text.trim()
+            )
+        })?;
+
+    let (start, end) = (parse_duration(start)?, parse_duration(end)?);
+    if start >= end {
+        return Err(String::from("Range start must be before its end."));
+    }
+    Ok((start, end))
+}
+
 fn build_args_string<I, S>(args: I) -> String
 where
     I: IntoIterator<Item = S>,
@@ -80,11 +269,486 @@ where
         .join(" ")
 }
 
+// ffmpeg names hardware encoders after the API they use, e.g. `h264_nvenc`
+fn is_hardware_encoder(codec: &str) -> bool {
+    const HARDWARE_SUFFIXES: [&str; 7] = [
+        "_nvenc",
+        "_vaapi",
+        "_qsv",
+        "_videotoolbox",
+        "_amf",
+        "_v4l2m2m",
+        "_omx",
+    ];
+    HARDWARE_SUFFIXES
+        .iter()
+        .any(|suffix| codec.ends_with(suffix))
+}
+
+/// Amplitude ratio of a gain in decibels: 0 dB is 1.0, -6 dB about 0.5.
+pub fn db_to_ratio(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+/// Gain in decibels of an amplitude ratio, the inverse of `db_to_ratio`.
+pub fn ratio_to_db(ratio: f64) -> f64 {
+    20.0 * ratio.log10()
+}
+
+/// Gain as a whole percentage, the way it is displayed.
+pub fn db_to_percent(db: f64) -> f64 {
+    (db_to_ratio(db) * 100.0).round()
+}
+
+// paths of the same file: links are followed when both exist, a missing file
+// can only be the other one if it is written the same way
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Both representations of a gain, e.g. `-6.0 dB (50 %)`.
+pub fn volume_description(db: f64) -> String {
+    format!("{:.1} dB ({} %)", db, db_to_percent(db))
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StabilizeSettings {
+    /// How shaky the footage is, from 1 (little) to 10 (a lot).
+    pub shakiness: u8,
+    /// Frames before and after each one the camera motion is averaged over,
+    /// up to 1000. 0 simulates a static camera.
+    pub smoothing: u16,
+}
+
+impl Default for StabilizeSettings {
+    fn default() -> Self {
+        // vidstab's own defaults
+        Self {
+            shakiness: 5,
+            smoothing: 10,
+        }
+    }
+}
+
+/// Silent parts played faster instead of being cut, see `silence` module.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SilenceSpeedup {
+    /// Speed of the silent parts, e.g. 4 for four times faster.
+    pub factor: f64,
+    /// Level in dB under which the audio counts as silent.
+    pub noise_db: f64,
+    /// Shortest silence sped up, in seconds: short pauses between words are kept.
+    pub min_silence: f64,
+}
+
+impl Default for SilenceSpeedup {
+    fn default() -> Self {
+        Self {
+            factor: 4.0,
+            noise_db: -35.0,
+            min_silence: 0.5,
+        }
+    }
+}
+
+/// Audio only output written along with the main one, from the same decoding
+/// and filtering, see `Config::additional_outputs`.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputSpec {
+    pub path: String,
+    /// Container format given with `-f`, guessed from the extension if unset.
+    pub format: Option<String>,
+    /// Audio encoder, the container default if unset.
+    pub audio_codec: Option<String>,
+    /// Audio bitrate in kbps, the encoder default if unset.
+    pub bitrate: Option<u32>,
+}
+
+impl OutputSpec {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: String::from(path),
+            ..Self::default()
+        }
+    }
+}
+
+/// Numbered pictures read as a video, the input file being their pattern
+/// such as `frame_%05d.png`, see `image_sequence`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ImageSequence {
+    /// Frames per second, pictures carry no timing.
+    pub framerate: f64,
+    /// Number of the first picture, ffmpeg looks for one from 0 to 4 if unset.
+    pub start_number: Option<u32>,
+}
+
+impl ImageSequence {
+    /// Input options of ffmpeg and ffprobe, given before the pattern.
+    pub fn input_args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("-f"),
+            String::from("image2"),
+            String::from("-framerate"),
+            self.framerate.to_string(),
+        ];
+        if let Some(start_number) = self.start_number {
+            args.push(String::from("-start_number"));
+            args.push(start_number.to_string());
+        }
+        args
+    }
+}
+
+/// Audio track of the input written to the output, see `Config::audio_tracks`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TrackSpec {
+    /// Index counted among audio streams, e.g. 1 for the second track.
+    pub stream_index: u32,
+    pub filters: FilterChain,
+    /// Written to the output, the tracks left out are dropped.
+    pub keep: bool,
+}
+
+/// Processing of one audio track of `Config::audio_tracks`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FilterChain {
+    /// The audio filters of the settings, e.g. the high-pass of a microphone.
+    Configured,
+    /// Encoded as decoded, e.g. the game sound next to the microphone.
+    Unfiltered,
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum VideoDenoise {
+    /// Fast 3D denoiser, spatial strengths for luma and chroma planes.
+    Hqdn3d { luma: f64, chroma: f64 },
+    /// Non-local means denoiser, much better but also much slower.
+    Nlmeans { strength: f64 },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ColorAdjust {
+    pub brightness: f64,
+    pub contrast: f64,
+    pub saturation: f64,
+    pub gamma: f64,
+}
+
+impl ColorAdjust {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // ranges accepted by ffmpeg's eq filter
+    pub fn clamped(&self) -> Self {
+        Self {
+            brightness: self.brightness.clamp(-1.0, 1.0),
+            contrast: self.contrast.clamp(-1000.0, 1000.0),
+            saturation: self.saturation.clamp(0.0, 3.0),
+            gamma: self.gamma.clamp(0.1, 10.0),
+        }
+    }
+}
+
+impl Default for ColorAdjust {
+    fn default() -> Self {
+        // no change
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Mp3Quality {
+    /// LAME variable bitrate level, from 0 (best, about 245 kbps) to 9
+    /// (smallest, about 65 kbps).
+    Vbr(u8),
+    /// Constant bitrate in kbps.
+    Cbr(u32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Mp3Settings {
+    pub quality: Mp3Quality,
+    /// Encode the channels as sum and difference when it saves bits, LAME's
+    /// default. Turn it off for stereo sources with unrelated channels.
+    pub joint_stereo: bool,
+}
+
+impl Default for Mp3Settings {
+    fn default() -> Self {
+        // LAME's recommended transparent setting
+        Self {
+            quality: Mp3Quality::Vbr(2),
+            joint_stereo: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AacEncoder {
+    /// ffmpeg's own encoder, always built in.
+    Native,
+    /// Fraunhofer FDK AAC, better sounding but only in ffmpeg builds enabling
+    /// it.
+    Fdk,
+}
+
+impl AacEncoder {
+    pub fn codec(self) -> &'static str {
+        match self {
+            AacEncoder::Native => "aac",
+            AacEncoder::Fdk => "libfdk_aac",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AacProfile {
+    /// Low complexity, the usual AAC.
+    Lc,
+    /// High efficiency, adding spectral band replication for low bitrates.
+    HeV1,
+    /// High efficiency with parametric stereo, for stereo at the lowest bitrates.
+    HeV2,
+}
+
+impl AacProfile {
+    /// Name given to `-profile:a`.
+    pub fn ffmpeg_name(self) -> &'static str {
+        match self {
+            AacProfile::Lc => "aac_low",
+            AacProfile::HeV1 => "aac_he",
+            AacProfile::HeV2 => "aac_he_v2",
+        }
+    }
+
+    /// Bitrates in kbps the profile can encode a stereo output at: band
+    /// replication needs some room and is pointless at high bitrates.
+    pub fn bitrate_range(self) -> RangeInclusive<u32> {
+        match self {
+            AacProfile::Lc => 8..=320,
+            AacProfile::HeV1 => 24..=128,
+            AacProfile::HeV2 => 16..=64,
+        }
+    }
+}
+
+impl fmt::Display for AacProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AacProfile::Lc => "AAC-LC",
+            AacProfile::HeV1 => "HE-AAC",
+            AacProfile::HeV2 => "HE-AAC v2",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AacOptions {
+    pub encoder: AacEncoder,
+    pub profile: AacProfile,
+    /// libfdk_aac variable bitrate mode, from 1 (smallest) to 5 (best).
+    /// `bitrate` is used when unset.
+    pub vbr_mode: Option<u8>,
+    /// Constant bitrate in kbps.
+    pub bitrate: u32,
+}
+
+impl Default for AacOptions {
+    fn default() -> Self {
+        Self {
+            encoder: AacEncoder::Native,
+            profile: AacProfile::Lc,
+            vbr_mode: None,
+            bitrate: 128,
+        }
+    }
+}
+
+/// Rework of the two channels of a stereo input, e.g. to fix a microphone
+/// recorded on a single side.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ChannelOp {
+    /// Exchange the left and right channels.
+    SwapLR,
+    /// Attenuate one side, from -1 (left only) to 1 (right only).
+    Balance(f64),
+    /// Mono output made of the left channel.
+    ExtractLeft,
+    /// Mono output made of the right channel.
+    ExtractRight,
+    /// Stereo output with the left channel on both sides.
+    MonoFromLeft,
+    /// Stereo output with the right channel on both sides.
+    MonoFromRight,
+}
+
+/// Echoes added by the `aecho` filter, from preset rooms or set by hand.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ReverbSettings {
+    SmallRoom,
+    Hall,
+    Custom {
+        /// Level of the input signal, from 0 to 1.
+        in_gain: f64,
+        /// Level of the output signal, from 0 to 1.
+        out_gain: f64,
+        /// Delay of each echo in milliseconds.
+        delays: Vec<f64>,
+        /// Level of each echo, from 0 to 1, one per delay.
+        decays: Vec<f64>,
+    },
+}
+
+// (in gain, out gain, delays, decays) of the presets: a few close and quick
+// reflections for a room, later and longer ones for a hall
+const SMALL_ROOM_REVERB: (f64, f64, &[f64], &[f64]) =
+    (0.8, 0.9, &[20.0, 35.0, 50.0], &[0.4, 0.3, 0.2]);
+const HALL_REVERB: (f64, f64, &[f64], &[f64]) = (
+    0.8,
+    0.8,
+    &[60.0, 120.0, 180.0, 250.0],
+    &[0.5, 0.4, 0.3, 0.2],
+);
+
+impl ReverbSettings {
+    /// Input gain, output gain, delays and decays, as given to `aecho`.
+    pub fn parameters(&self) -> (f64, f64, &[f64], &[f64]) {
+        match self {
+            ReverbSettings::SmallRoom => SMALL_ROOM_REVERB,
+            ReverbSettings::Hall => HALL_REVERB,
+            ReverbSettings::Custom {
+                in_gain,
+                out_gain,
+                delays,
+                decays,
+            } => (*in_gain, *out_gain, delays, decays),
+        }
+    }
+}
+
+impl fmt::Display for ChannelOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ChannelOp::SwapLR => "channel swap",
+            ChannelOp::Balance(_) => "balance",
+            ChannelOp::ExtractLeft => "left channel extraction",
+            ChannelOp::ExtractRight => "right channel extraction",
+            ChannelOp::MonoFromLeft => "left channel on both sides",
+            ChannelOp::MonoFromRight => "right channel on both sides",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AspectPolicy {
+    /// Leave the aspect ratio untouched.
+    Keep,
+    /// Override the display aspect ratio, e.g. `Force(16, 9)`.
+    Force(u32, u32),
+    /// Rescale so that pixels are square, fixing the display aspect ratio for good.
+    SquarePixels,
+}
+
+/// Corner, or bottom centre, of the picture the timecode is drawn in.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TimecodePosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+/// Time shown by the burned timecode.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum TimecodeSource {
+    /// Starts at 0 with the selection.
+    SelectionRelative,
+    /// Time in the input file, to find a moment back in the recording.
+    SourceAbsolute,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TimecodeStyle {
+    pub position: TimecodePosition,
+    /// Height of the text in pixels.
+    pub font_size: u32,
+    pub source: TimecodeSource,
+}
+
+impl Default for TimecodeStyle {
+    fn default() -> Self {
+        Self {
+            position: TimecodePosition::BottomCenter,
+            font_size: 32,
+            source: TimecodeSource::SourceAbsolute,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Operation {
+    /// Trim and filter the input.
+    Process,
+    /// Rewrite the video rotation tag without re-encoding, `None` removes the tag.
+    FixRotation { set: Option<u16> },
+    /// Produce analysis images of the trimmed range instead of a media file.
+    Analyze,
+    /// Trim re-encoding only the video around the cut points, see `smartcut`.
+    SmartCut,
+    /// Encode the processed audio with a picture of it, drawn at `resolution`
+    /// in the `colors` RGB colour, ffmpeg's default ones if unset.
+    Visualize {
+        style: VisualStyle,
+        resolution: (u32, u32),
+        colors: Option<[u8; 3]>,
+    },
+}
+
+/// Picture drawn from the audio by `Operation::Visualize`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum VisualStyle {
+    /// Waveform, `showwaves`.
+    Waves,
+    /// Scrolling spectrum, `showspectrum`.
+    Spectrum,
+    /// Musical notes spectrum, `showcqt`.
+    Cqt,
+}
+
+impl VisualStyle {
+    /// Name of the ffmpeg filter drawing the picture.
+    pub fn filter_name(self) -> &'static str {
+        match self {
+            VisualStyle::Waves => "showwaves",
+            VisualStyle::Spectrum => "showspectrum",
+            VisualStyle::Cqt => "showcqt",
+        }
+    }
+}
+
+// missing fields take their default value: projects saved by older versions still load
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub operation: Operation,
     pub preview: bool,
     pub input_file: String,
     pub output_file: String,
+    #[serde(with = "project::millis")]
     pub from_time: Duration,
+    #[serde(with = "project::millis")]
     pub to_time: Duration,
     pub high_pass_filter: Option<u32>,
     pub low_pass_filter: Option<u32>,
@@ -95,51 +759,3708 @@ pub struct Config {
     pub volume_change: f64,
     pub noise_profile_file: Option<String>,
     pub noise_reduction_amount: Option<f64>,
+    pub stabilize: Option<StabilizeSettings>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub demux_streams: bool,
+    pub video_denoise: Option<VideoDenoise>,
+    pub max_retries: u32,
+    pub color: Option<ColorAdjust>,
+    #[serde(with = "project::optional_range_millis")]
+    pub normalization_measure_range: Option<(Duration, Duration)>,
+    pub lut: Option<PathBuf>,
+    pub aspect_ratio: Option<AspectPolicy>,
+    /// Burn the running time into the picture, for dailies and review copies.
+    /// Sped up silences are counted at their output speed.
+    pub burn_timecode: Option<TimecodeStyle>,
+    /// Font of the burned timecode, the default font of fontconfig if unset.
+    pub font_path: Option<String>,
+    /// Hard limit in bytes: encoding stops when reached, it does not lower the quality to fit.
+    pub max_output_size: Option<u64>,
+    /// Size in bytes the output aims for, e.g. the upload limit of a chat
+    /// service: the video bitrate is worked out from the output duration and
+    /// the audio bitrate. Unlike `max_output_size`, the whole selection is
+    /// kept and the quality lowered instead.
+    pub target_size: Option<u64>,
+    /// Pictures taller than this are scaled down to it, the aspect ratio kept.
+    pub max_height: Option<u32>,
+    /// Width and height of the spectrogram image rendered in analyze mode.
+    pub spectrogram: Option<(u32, u32)>,
+    /// Turn warnings about the selection into errors, and refuse output
+    /// extensions that do not fit the codecs instead of correcting them.
+    pub strict: bool,
+    /// Ignore `from_time` and `to_time`: the input is neither seeked nor cut.
+    pub process_whole_file: bool,
+    /// Context kept before `from_time`.
+    #[serde(with = "project::optional_millis")]
+    pub pad_before: Option<Duration>,
+    /// Context kept after `to_time`.
+    #[serde(with = "project::optional_millis")]
+    pub pad_after: Option<Duration>,
+    /// Cut lossless audio-only outputs on exact samples instead of timestamps.
+    pub sample_accurate: bool,
+    /// Create the missing parent directories of the output file.
+    pub create_output_dirs: bool,
+    /// Reset the rotation tag of re-encoded video, whose frames ffmpeg already
+    /// rotated, so that players do not rotate them a second time.
+    pub strip_rotation_metadata: bool,
+    /// Name the output after the input instead of using `output_file`,
+    /// see `output_pattern::expand_pattern`.
+    pub output_pattern: Option<String>,
+    /// One of `ENCODER_PRESETS`, for the x264 and x265 software encoders.
+    pub encoder_preset: Option<String>,
+    /// Threads of each encoder, ffmpeg picks them by default. Lower it when
+    /// several jobs run at once, see `queue::JobQueue::set_concurrency`.
+    pub threads: Option<u32>,
+    /// Subtitle stream, counted among subtitle streams only, saved trimmed
+    /// next to the output as an SRT file, see `subtitle_format`.
+    pub extract_subtitles: Option<u32>,
+    /// Format of the extracted subtitles instead of SRT, one of
+    /// `containers::SUBTITLE_FORMATS`, e.g. `vtt` for web players.
+    pub subtitle_format: Option<String>,
+    /// Start every command at a lower CPU and disk priority, so that long
+    /// encodes do not slow the desktop down.
+    pub low_priority: bool,
+    /// Adjustment `nice -n` adds to the niceness media-cutter runs at when
+    /// starting ffmpeg and the other commands, from -20 to 19, instead of the
+    /// 10 of `low_priority`. The result is capped at 19, negative levels need
+    /// the rights to raise the priority. Unix only, ignored on Windows.
+    pub nice_level: Option<i32>,
+    /// Make the presentation timestamps again from the decoding ones and
+    /// start the output at zero. Helps with captured streams, e.g. from a
+    /// TV tuner or a crashed recorder, whose timestamps jump back or are
+    /// missing, making the cut start late or the output stutter.
+    pub fix_timestamps: bool,
+    /// Read damaged inputs as far as possible: decoding errors and corrupt
+    /// packets are skipped and the input is probed longer. Without it, a
+    /// run failing on such errors is retried once with it, unless `strict`.
+    pub tolerant_input: bool,
+    /// Play the preview again and again until its window is closed.
+    pub preview_loop: bool,
+    /// Fail when ffmpeg lacks the requested encoder instead of using a
+    /// fallback one the output container can hold, e.g. libvorbis for libopus.
+    pub strict_codecs: bool,
+    /// Container format given to ffmpeg with `-f`, e.g. `matroska`, instead of
+    /// guessing it from the output extension which is then left as is.
+    pub output_format: Option<String>,
+    /// Cut the output into files of this length with ffmpeg's segment muxer,
+    /// e.g. to prepare a stream, `output_file` numbering them like
+    /// `out_%03d.ts`. Unlike the segments of a project, the whole selection
+    /// is written in a single run and the cuts land on keyframes.
+    #[serde(with = "project::optional_millis")]
+    pub segment_time: Option<Duration>,
+    /// Audio of the selection also written to these files, e.g. an Opus file
+    /// for the web next to a WAV file for an editor.
+    pub additional_outputs: Vec<OutputSpec>,
+    /// Read `input_file` as the pattern of numbered pictures, always whole:
+    /// pictures carry no times to trim them by.
+    pub image_sequence: Option<ImageSequence>,
+    /// Arguments handed to ffmpeg as they are, before the main output of the
+    /// process step, e.g. the `-map` options of `raw_filter_complex`.
+    pub extra_ffmpeg_args: Vec<String>,
+    /// Filtergraph given with `-filter_complex` instead of the one built from
+    /// the filter settings, which must then be left unset. Its outputs are
+    /// chosen with `-map` in `extra_ffmpeg_args`.
+    pub raw_filter_complex: Option<String>,
+    /// Volume curve applied on top of `volume_change`: (input time, dB)
+    /// points, linearly interpolated in between and held before the first
+    /// and after the last one.
+    #[serde(with = "project::envelope_millis")]
+    pub volume_envelope: Vec<(Duration, f64)>,
+    /// Quality of libmp3lame outputs, ffmpeg's 128 kbps CBR if unset.
+    pub mp3: Option<Mp3Settings>,
+    /// Write every audio track of the input, e.g. all the languages of a
+    /// movie, instead of the single one ffmpeg picks by default.
+    pub keep_all_audio: bool,
+    /// Audio tracks written to the output in this order, each filtered or
+    /// not, instead of the single one ffmpeg picks by default. Stream
+    /// metadata such as the language is kept.
+    pub audio_tracks: Vec<TrackSpec>,
+    /// Audio tracks mixed into a single one, each with its gain in dB, e.g.
+    /// a microphone and the game sound. The tracks are summed as they are,
+    /// not scaled down, and the audio filters then apply to the mix.
+    pub downmix_tracks: Option<Vec<(usize, f64)>>,
+    /// Picture shown for the whole audio of the input, e.g. the cover of a
+    /// music track, making a video of them.
+    pub still_image: Option<PathBuf>,
+    /// Companion video and time of the frame embedded as the cover picture
+    /// of the audio output, e.g. the recording of a podcast episode. The
+    /// frame is taken and attached by the same ffmpeg run, as a JPEG
+    /// picture unless the video codec is png. Only MP3, M4A and FLAC outputs
+    /// hold cover pictures.
+    #[serde(with = "project::optional_frame_millis")]
+    pub thumbnail_from_video: Option<(String, Duration)>,
+    /// Copy every stream of the input as it is, subtitles and attachments
+    /// such as the fonts of Matroska files included, instead of the single
+    /// video and audio ones ffmpeg picks. The chapters of the input are moved
+    /// along with the selection.
+    pub stream_copy: bool,
+    /// Streams of the input left out of `stream_copy`, as ffmpeg stream
+    /// specifiers such as `d` or `s:1`, e.g. the data streams an output
+    /// container cannot hold.
+    pub stream_copy_exclude: Vec<String>,
+    /// FLAC encoder effort from 0 (fastest) to 12 (smallest), ffmpeg's 5 if
+    /// unset. Only applies to FLAC outputs.
+    pub flac_compression: Option<u8>,
+    /// Compare the MD5 of the decoded output audio with the one of the input
+    /// selection, proving that a FLAC trim lost nothing.
+    pub verify_lossless: bool,
+    /// Encoder and profile of AAC outputs, ffmpeg's native AAC-LC encoder if
+    /// unset.
+    pub aac_options: Option<AacOptions>,
+    /// Bitrate in kbps of the audio output, the encoder default if unset. AAC
+    /// and MP3 outputs take theirs from `aac_options` and `mp3`.
+    pub audio_bitrate: Option<u32>,
+    /// Directory relative paths are resolved from, by ffmpeg, ffplay and sox
+    /// as well as by the checks made before starting them. The current
+    /// directory if unset.
+    pub working_dir: Option<PathBuf>,
+    /// Columns and rows of the contact sheet rendered in analyze mode: frames
+    /// evenly spaced across the selection, tiled in a single picture.
+    pub contact_sheet: Option<(u32, u32)>,
+    /// Width in pixels of each contact sheet frame, 320 if unset.
+    pub contact_sheet_tile_width: Option<u32>,
+    /// Burn the input time of each contact sheet frame into its corner.
+    pub contact_sheet_timestamps: bool,
+    /// Video stream to keep, counted among video streams only, for inputs
+    /// with several such as multi-angle recordings. ffmpeg picks one itself
+    /// if unset.
+    pub video_stream_index: Option<u32>,
+    /// Swap, balance or extract the channels of a stereo input.
+    pub channel_ops: Option<ChannelOp>,
+    /// Write the input file and the selection in the comment tag of the
+    /// output, to remember where a clip comes from.
+    pub embed_source_info: bool,
+    /// Strength from 0 to 1 of the vocal removal: what is common to both
+    /// channels, usually the lead voice, is cancelled out. Stereo inputs only.
+    pub vocal_removal: Option<f64>,
+    /// Keep the channel count of the input audio, probed before processing,
+    /// whatever the filters make of it: a mono input does not turn stereo nor
+    /// the other way around. Left to the settings reworking the channels,
+    /// e.g. a channel operation, a track mix or `-ac` in the extra arguments.
+    /// Only mono and stereo inputs are kept as they are, ffmpeg decides how
+    /// surround ones fit the encoder.
+    pub preserve_channels: bool,
+    /// Copy the input audio as is instead of encoding it again, when no audio
+    /// filter is enabled and the output container can hold its codec.
+    pub copy_audio_when_possible: bool,
+    /// Echoes added at the end of the audio filters.
+    pub reverb: Option<ReverbSettings>,
+    /// Integrated loudness in LUFS the audio is brought to by `loudnorm`
+    /// after the other filters, e.g. -16 for podcasts.
+    pub loudness_target: Option<f64>,
+    /// Ceiling of the true peak in dBTP kept by `loudness_target`.
+    pub loudness_true_peak: f64,
+    /// Loudness range in LU `loudness_target` aims for, from 1 to 50.
+    pub loudness_range: f64,
+    /// Measure the loudness of the selection in a first pass, so that the
+    /// second one normalizes it linearly and hits `loudness_target` closely.
+    /// Previews are normalized in a single pass.
+    pub loudnorm_two_pass: bool,
+    /// Play the silent parts of the selection faster, keeping audio and video
+    /// in sync. Ignored by previews.
+    pub silence_speedup: Option<SilenceSpeedup>,
+    /// Chapters embedded in the output, timed on the input: they are moved to
+    /// the output timeline and left out when outside the selection.
+    pub write_chapters: Option<Vec<chapters::Chapter>>,
+    /// Preview the filters on the whole input instead of the selection, so
+    /// that any part can be heard by seeking.
+    pub preview_full: bool,
+    /// Write MP4 and QuickTime outputs as fragments starting on keyframes,
+    /// without a final index, as DASH and HLS packagers expect. Other outputs
+    /// are written as usual. Such files cannot be moved to fast start: a
+    /// `-movflags` in `extra_ffmpeg_args`, e.g. `+faststart`, is rejected.
+    pub fragmented_mp4: bool,
+    /// Characters allowed in the output names derived from a pattern or
+    /// from `output_file`, the others being replaced by `_`.
+    pub filename_charset: output_pattern::FilenameCharset,
+    /// Tags written to the output, e.g. `title` or `track`, replacing those
+    /// of the input.
+    pub metadata: Vec<(String, String)>,
+    /// Move the start of the selection to its first sound, a short lead-in
+    /// kept, when it opens with silence. See `silence::first_sound`.
+    pub trim_to_first_sound: bool,
+    /// ffmpeg program run instead of the one found in `PATH`, for waveforms,
+    /// recordings and encoder queries too. ffprobe, ffplay and sox are still
+    /// looked up in `PATH`. Like the two settings below, it belongs to the
+    /// machine and is not saved in projects, see `from_env`.
+    #[serde(skip)]
+    pub ffmpeg_path: Option<PathBuf>,
+    /// Directory of the intermediate files instead of the system one.
+    #[serde(skip)]
+    pub temp_dir: Option<PathBuf>,
+    /// Verbosity of the processing ffmpeg, one of `LOG_LEVELS`. Analysis
+    /// steps keep the default one, whose messages they read.
+    #[serde(skip)]
+    pub loglevel: Option<String>,
 }
 
 impl Config {
     pub fn new() -> Self {
         Self::default()
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            preview: false,
-            input_file: String::from(""),
-            output_file: String::from(""),
-            from_time: Duration::seconds(0),
-            to_time: Duration::seconds(0),
-            high_pass_filter: None,
-            low_pass_filter: None,
-            allow_overidde: false,
-            ignore_video: false,
-            ignore_audio: false,
-            peak_normalization: false,
-            volume_change: 0.0,
-            noise_profile_file: None,
-            noise_reduction_amount: None,
-        }
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_input_url()?;
+        self.validate_output_file()?;
+        self.validate_times()?;
+        self.validate_high_pass()?;
+        self.validate_low_pass()?;
+        self.validate_noise_profile()?;
+        self.validate_settings()?;
+        // the file is read last, once the settings are known to be consistent
+        self.validate_lut()
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Rules of `validate` but those of a single setting, which the window
+    /// shows next to its widget, see `validation::Field`.
+    pub(crate) fn validate_settings(&self) -> Result<(), String> {
+        self.validate_stdout_output()?;
+        self.validate_additional_outputs()?;
+        self.validate_raw_filter_complex()?;
+        self.validate_visualize()?;
+        self.validate_image_sequence()?;
+        self.validate_audio_tracks()?;
+        self.validate_downmix_tracks()?;
+        self.validate_still_image()?;
+        self.validate_thumbnail_from_video()?;
+        self.validate_stream_copy()?;
+        self.validate_segment_time()?;
 
-    #[test]
-    fn convert_duration_to_string() {
-        assert_eq!(duration_to_string(Duration::milliseconds(1002)), "0:0:1.2");
-        assert_eq!(
-            duration_to_string(Duration::milliseconds(65125)),
-            "0:1:5.125"
-        );
-        assert_eq!(
-            duration_to_string(Duration::milliseconds(6065125)),
-            "1:41:5.125"
-        );
-        assert_eq!(duration_to_string(Duration::seconds(128)), "0:2:8.0");
+        if self.ignore_audio && self.ignore_video {
+            return Err(String::from("Error: cannot ignore both audio and video."));
+        }
+
+        if self.ignore_video && self.video_stream_index.is_some() {
+            return Err(String::from(
+                "Error: video is ignored but a video stream is selected.",
+            ));
+        }
+
+        if self.keep_all_audio {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: cannot keep all audio tracks while ignoring audio.",
+                ));
+            }
+            if self.noise_profile_file.is_some() && self.noise_reduction_amount.is_some() {
+                return Err(String::from(
+                    "Error: noise reduction only cleans a single audio track, it cannot keep them all.",
+                ));
+            }
+        }
+
+        if self.ignore_video {
+            let video_filters = self.video_filter_names();
+            if !video_filters.is_empty() {
+                return Err(format!(
+                    "Error: video is ignored but the following video filters are enabled: {}.",
+                    video_filters.join(", ")
+                ));
+            }
+        }
+
+        if let Some((from, to)) = self.normalization_measure_range {
+            if from >= to {
+                return Err(String::from(
+                    "Error: normalization measure range ends before it starts.",
+                ));
+            }
+            if !self.process_whole_file && (from < self.from_time || to > self.to_time) {
+                return Err(String::from(
+                    "Error: normalization measure range must be within the trimmed range.",
+                ));
+            }
+        }
+
+        if let Operation::FixRotation { set: Some(angle) } = self.operation {
+            if angle % 90 != 0 || angle >= 360 {
+                return Err(String::from(
+                    "Error: rotation must be one of 0, 90, 180 or 270 degrees.",
+                ));
+            }
+            if angle != 0 && self.strip_rotation_metadata {
+                return Err(String::from(
+                    "Error: rotation metadata is stripped but a rotation is requested.",
+                ));
+            }
+        }
+
+        // ffmpeg keeps the last -movflags only, dropping the other flags
+        if self.fragmented_mp4 && self.extra_ffmpeg_args.iter().any(|arg| arg == "-movflags") {
+            return Err(String::from(
+                "Error: fragmented MP4 cannot be combined with -movflags in the extra arguments, such as +faststart.",
+            ));
+        }
+
+        if let Some((width, height)) = self.spectrogram {
+            if width == 0 || height == 0 {
+                return Err(String::from(
+                    "Error: spectrogram size must be made of positive numbers.",
+                ));
+            }
+        }
+
+        if let Some((columns, rows)) = self.contact_sheet {
+            if columns == 0 || rows == 0 {
+                return Err(String::from(
+                    "Error: contact sheet grid must be made of positive numbers.",
+                ));
+            }
+        }
+        if self.contact_sheet_tile_width == Some(0) {
+            return Err(String::from(
+                "Error: contact sheet frames must be wider than 0 pixels.",
+            ));
+        }
+
+        if self.operation == Operation::Analyze {
+            if self.spectrogram.is_none() && self.contact_sheet.is_none() {
+                return Err(String::from("Error: no analysis requested."));
+            }
+            if self.ignore_audio && self.spectrogram.is_some() {
+                return Err(String::from(
+                    "Error: audio is ignored but a spectrogram is requested.",
+                ));
+            }
+            if self.ignore_video && self.contact_sheet.is_some() {
+                return Err(String::from(
+                    "Error: video is ignored but a contact sheet is requested.",
+                ));
+            }
+        }
+
+        if self.operation == Operation::SmartCut {
+            if self.process_whole_file {
+                return Err(String::from(
+                    "Error: smart cut needs a selection, there is nothing to cut in the whole file.",
+                ));
+            }
+            if self.ignore_video {
+                return Err(String::from(
+                    "Error: video is ignored, smart cut only helps with video.",
+                ));
+            }
+            if self.video_codec.is_some() {
+                return Err(String::from(
+                    "Error: smart cut keeps the video codec of the input, none can be chosen.",
+                ));
+            }
+            // the cut follows the keyframes of the selection as it is
+            if self.trim_to_first_sound {
+                return Err(String::from(
+                    "Error: smart cut cannot trim the selection to its first sound.",
+                ));
+            }
+            let mut filters = self.video_filter_names();
+            if self.burn_timecode.is_some() {
+                filters.push("timecode");
+            }
+            filters.extend(self.audio_filter_names());
+            if self.silence_speedup.is_some() {
+                filters.push("silence speedup");
+            }
+            if !filters.is_empty() {
+                return Err(format!(
+                    "Error: smart cut copies most of the input but the following filters are enabled: {}.",
+                    filters.join(", ")
+                ));
+            }
+        }
+
+        if let Some(AspectPolicy::Force(num, den)) = self.aspect_ratio {
+            if num == 0 || den == 0 {
+                return Err(String::from(
+                    "Error: forced aspect ratio must be made of positive numbers.",
+                ));
+            }
+        }
+
+        if let Some(ref preset) = self.encoder_preset {
+            if !ENCODER_PRESETS.contains(&preset.as_str()) {
+                return Err(format!(
+                    "Error: unknown encoder preset {}, expected one of {}.",
+                    preset,
+                    ENCODER_PRESETS.join(", ")
+                ));
+            }
+            if self.ignore_video {
+                return Err(String::from(
+                    "Error: video is ignored but an encoder preset is requested.",
+                ));
+            }
+            match self.video_codec.as_deref() {
+                Some("copy") => {
+                    return Err(String::from(
+                        "Error: video is copied but an encoder preset is requested.",
+                    ))
+                }
+                Some(codec) if is_hardware_encoder(codec) => {
+                    return Err(format!(
+                        "Error: encoder presets only apply to x264 and x265, not to the {} hardware encoder.",
+                        codec
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(mp3) = self.mp3 {
+            if self.audio_codec.as_deref() != Some("libmp3lame") {
+                return Err(String::from(
+                    "Error: MP3 quality settings need the libmp3lame audio codec.",
+                ));
+            }
+            match mp3.quality {
+                Mp3Quality::Vbr(level) if level > 9 => {
+                    return Err(format!(
+                        "Error: MP3 VBR quality must be between 0 and 9, got {}.",
+                        level
+                    ));
+                }
+                Mp3Quality::Cbr(bitrate) if !(8..=320).contains(&bitrate) => {
+                    return Err(format!(
+                        "Error: MP3 bitrate must be between 8 and 320 kbps, got {}.",
+                        bitrate
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(aac) = self.aac_options {
+            if !matches!(
+                self.audio_codec.as_deref(),
+                Some("aac") | Some("libfdk_aac")
+            ) {
+                return Err(String::from(
+                    "Error: AAC settings need the aac audio codec.",
+                ));
+            }
+            if aac.encoder == AacEncoder::Native {
+                if aac.profile != AacProfile::Lc {
+                    return Err(format!(
+                        "Error: {} needs the libfdk_aac encoder.",
+                        aac.profile
+                    ));
+                }
+                if aac.vbr_mode.is_some() {
+                    return Err(String::from(
+                        "Error: AAC VBR modes need the libfdk_aac encoder.",
+                    ));
+                }
+            }
+            match aac.vbr_mode {
+                Some(mode) if !(1..=5).contains(&mode) => {
+                    return Err(format!(
+                        "Error: AAC VBR mode must be between 1 and 5, got {}.",
+                        mode
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    let range = aac.profile.bitrate_range();
+                    if !range.contains(&aac.bitrate) {
+                        return Err(format!(
+                            "Error: {} bitrate must be between {} and {} kbps, got {}.",
+                            aac.profile,
+                            range.start(),
+                            range.end(),
+                            aac.bitrate
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(bitrate) = self.audio_bitrate {
+            if !(6..=512).contains(&bitrate) {
+                return Err(format!(
+                    "Error: audio bitrate must be between 6 and 512 kbps, got {}.",
+                    bitrate
+                ));
+            }
+            if self.aac_options.is_some() || self.mp3.is_some() {
+                return Err(String::from(
+                    "Error: the audio bitrate of AAC and MP3 outputs is part of their settings.",
+                ));
+            }
+        }
+
+        if let Some(ref format) = self.subtitle_format {
+            if containers::subtitle_codec(format).is_none() {
+                let formats: Vec<&str> = containers::SUBTITLE_FORMATS
+                    .iter()
+                    .map(|&(extension, _)| extension)
+                    .collect();
+                return Err(format!(
+                    "Error: unknown subtitle format {}, expected one of {}.",
+                    format,
+                    formats.join(", ")
+                ));
+            }
+            if self.extract_subtitles.is_none() {
+                return Err(String::from(
+                    "Error: a subtitle format is set but no subtitle stream is extracted.",
+                ));
+            }
+        }
+
+        if let Some(level) = self.flac_compression {
+            if level > 12 {
+                return Err(format!(
+                    "Error: FLAC compression level must be between 0 and 12, got {}.",
+                    level
+                ));
+            }
+        }
+
+        if self.verify_lossless {
+            if self.ignore_audio || !self.is_flac_output() {
+                return Err(String::from(
+                    "Error: lossless verification needs a FLAC audio output.",
+                ));
+            }
+            let audio_filters = self.audio_filter_names();
+            if !audio_filters.is_empty() {
+                return Err(format!(
+                    "Error: lossless verification needs the audio untouched but the following audio filters are enabled: {}.",
+                    audio_filters.join(", ")
+                ));
+            }
+        }
+
+        if let Some(op) = self.channel_ops {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: audio is ignored but a channel operation is set.",
+                ));
+            }
+            if let ChannelOp::Balance(balance) = op {
+                if !(-1.0..=1.0).contains(&balance) {
+                    return Err(format!(
+                        "Error: balance must be between -1 (left) and 1 (right), got {}.",
+                        balance
+                    ));
+                }
+            }
+        }
+
+        if let Some(strength) = self.vocal_removal {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: audio is ignored but vocal removal is enabled.",
+                ));
+            }
+            if !(strength > 0.0 && strength <= 1.0) {
+                return Err(format!(
+                    "Error: vocal removal strength must be above 0 and at most 1, got {}.",
+                    strength
+                ));
+            }
+        }
+
+        if let Some(ref reverb) = self.reverb {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: audio is ignored but reverb is enabled.",
+                ));
+            }
+            let (in_gain, out_gain, delays, decays) = reverb.parameters();
+            let is_gain = |gain: f64| gain > 0.0 && gain <= 1.0;
+            if !is_gain(in_gain) || !is_gain(out_gain) {
+                return Err(String::from(
+                    "Error: reverb gains must be above 0 and at most 1.",
+                ));
+            }
+            if delays.is_empty() || delays.len() != decays.len() {
+                return Err(format!(
+                    "Error: reverb needs one decay per delay, got {} delays and {} decays.",
+                    delays.len(),
+                    decays.len()
+                ));
+            }
+            // aecho limits
+            if !delays.iter().all(|&delay| delay > 0.0 && delay <= 90_000.0) {
+                return Err(String::from(
+                    "Error: reverb delays must be above 0 and at most 90000 ms.",
+                ));
+            }
+            if !decays.iter().all(|&decay| decay > 0.0 && decay <= 1.0) {
+                return Err(String::from(
+                    "Error: reverb decays must be above 0 and at most 1.",
+                ));
+            }
+        }
+
+        if self.loudnorm_two_pass && self.loudness_target.is_none() {
+            return Err(String::from(
+                "Error: two-pass loudness normalization needs a loudness target.",
+            ));
+        }
+        if let Some(target) = self.loudness_target {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: audio is ignored but loudness normalization is enabled.",
+                ));
+            }
+            if self.peak_normalization {
+                return Err(String::from(
+                    "Error: peak and loudness normalization cannot be combined.",
+                ));
+            }
+            // loudnorm limits
+            if !(-70.0..=-5.0).contains(&target) {
+                return Err(format!(
+                    "Error: loudness target must be between -70 and -5 LUFS, got {}.",
+                    target
+                ));
+            }
+            if !(-9.0..=0.0).contains(&self.loudness_true_peak) {
+                return Err(format!(
+                    "Error: true peak ceiling must be between -9 and 0 dBTP, got {}.",
+                    self.loudness_true_peak
+                ));
+            }
+            if !(1.0..=50.0).contains(&self.loudness_range) {
+                return Err(format!(
+                    "Error: loudness range must be between 1 and 50 LU, got {}.",
+                    self.loudness_range
+                ));
+            }
+        }
+
+        if let Some(ref speedup) = self.silence_speedup {
+            self.validate_silence_speedup(speedup)?;
+        }
+
+        if self.trim_to_first_sound {
+            if self.ignore_audio {
+                return Err(String::from(
+                    "Error: audio is ignored, the first sound cannot be found.",
+                ));
+            }
+            if self.process_whole_file {
+                return Err(String::from(
+                    "Error: trimming to the first sound needs a selection, the whole file is not cut.",
+                ));
+            }
+        }
+
+        if let Some(ref chapters) = self.write_chapters {
+            if self.demux_streams && !chapters.is_empty() {
+                return Err(String::from(
+                    "Error: chapters cannot be written to demuxed streams.",
+                ));
+            }
+            if let Some(chapter) = chapters.iter().find(|chapter| chapter.end <= chapter.start) {
+                return Err(format!(
+                    "Error: chapter \"{}\" must end after it starts.",
+                    chapter.title
+                ));
+            }
+        }
+
+        if self.threads == Some(0) {
+            return Err(String::from("Error: encoder threads must be positive."));
+        }
+
+        if let Some(level) = self.nice_level {
+            if !(-20..=19).contains(&level) {
+                return Err(format!(
+                    "Error: nice level {} is outside of -20 to 19.",
+                    level
+                ));
+            }
+        }
+
+        if self
+            .volume_envelope
+            .windows(2)
+            .any(|points| points[1].0 < points[0].0)
+        {
+            return Err(String::from(
+                "Error: volume envelope points must be in time order.",
+            ));
+        }
+
+        if self.max_output_size == Some(0) {
+            return Err(String::from("Error: output size limit must be positive."));
+        }
+        self.validate_target_size()?;
+
+        if let Some(ref stabilize) = self.stabilize {
+            if !(1..=10).contains(&stabilize.shakiness) {
+                return Err(format!(
+                    "Error: stabilization shakiness must be between 1 and 10, got {}.",
+                    stabilize.shakiness
+                ));
+            }
+            if stabilize.smoothing > 1000 {
+                return Err(format!(
+                    "Error: stabilization smoothing must be between 0 and 1000 frames, got {}.",
+                    stabilize.smoothing
+                ));
+            }
+        }
+        if let Some(height) = self.max_height {
+            if height == 0 || height % 2 != 0 {
+                return Err(format!(
+                    "Error: maximum height must be a positive even number of pixels, got {}.",
+                    height
+                ));
+            }
+        }
+
+        if let Some(ref pattern) = self.output_pattern {
+            output_pattern::validate_pattern(pattern)?;
+        }
+
+        if let Some(ref level) = self.loglevel {
+            if !LOG_LEVELS.contains(&level.as_str()) {
+                return Err(format!(
+                    "Error: unknown log level {}, expected one of {}.",
+                    level,
+                    LOG_LEVELS.join(", ")
+                ));
+            }
+        }
+
+        if let Some(timecode) = self.burn_timecode {
+            if timecode.font_size == 0 {
+                return Err(String::from("Error: timecode font size must be positive."));
+            }
+            if let Some(ref font_path) = self.font_path {
+                if !self.resolve_path(font_path).is_file() {
+                    return Err(format!("Error: font file not found: {}", font_path));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_target_size(&self) -> Result<(), String> {
+        match self.target_size {
+            Some(0) => return Err(String::from("Error: target size must be positive.")),
+            Some(_) => {}
+            None => return Ok(()),
+        }
+
+        // the size is reached through the video bitrate
+        let mut conflicts = Vec::new();
+        if self.ignore_video {
+            conflicts.push("ignoring video");
+        }
+        if self.video_codec.as_deref() == Some("copy") || self.stream_copy {
+            conflicts.push("video copy");
+        }
+        if self.operation == Operation::SmartCut {
+            conflicts.push("smart cut");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: target size cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    // each segment is trimmed and retimed, which rules out copying streams and
+    // the features working on the whole selection or several outputs
+    fn validate_silence_speedup(&self, speedup: &SilenceSpeedup) -> Result<(), String> {
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: audio is ignored but silences are sped up, they are found in the audio.",
+            ));
+        }
+        if speedup.factor <= 1.0 || speedup.factor > 100.0 {
+            return Err(format!(
+                "Error: silence speed factor must be above 1 and at most 100, got {}.",
+                speedup.factor
+            ));
+        }
+        if speedup.noise_db >= 0.0 {
+            return Err(format!(
+                "Error: silence level must be below 0 dB, got {} dB.",
+                speedup.noise_db
+            ));
+        }
+        if speedup.min_silence <= 0.0 {
+            return Err(String::from(
+                "Error: shortest sped up silence must last more than 0 seconds.",
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        // a single track is sped up, maybe a mix
+        if self.audio_tracks.iter().filter(|track| track.keep).count() > 1 {
+            conflicts.push("several audio tracks");
+        }
+        if self.stabilize.is_some() {
+            conflicts.push("stabilization");
+        }
+        if !self.volume_envelope.is_empty() {
+            conflicts.push("volume envelope");
+        }
+        if self.video_codec.as_deref() == Some("copy")
+            || self.audio_codec.as_deref() == Some("copy")
+        {
+            conflicts.push("stream copy");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: speeding through silences cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    // network inputs go to ffmpeg untouched, but sox only reads local files
+    pub(crate) fn validate_input_url(&self) -> Result<(), String> {
+        let scheme = match url_scheme(&self.input_file) {
+            Some(scheme) => scheme.to_ascii_lowercase(),
+            None => return Ok(()),
+        };
+        if !URL_SCHEMES.contains(&scheme.as_str()) {
+            return Err(format!(
+                "Error: unsupported input protocol {}://, expected one of {}.",
+                scheme,
+                URL_SCHEMES.join(", ")
+            ));
+        }
+        if self.noise_profile_file.is_some() && self.noise_reduction_amount.is_some() {
+            return Err(String::from(
+                "Error: noise reduction runs sox, which cannot read network inputs: download the input first.",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn validate_times(&self) -> Result<(), String> {
+        // the whole file is remuxed, see `Operation::FixRotation`
+        let selected =
+            !self.process_whole_file && !matches!(self.operation, Operation::FixRotation { .. });
+        if selected && self.from_time >= self.to_time {
+            return Err(String::from(
+                "Error: start time must be before the end time.",
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_high_pass(&self) -> Result<(), String> {
+        if self.high_pass_filter == Some(0) {
+            return Err(String::from("Error: high-pass frequency must be positive."));
+        }
+        if let (Some(high), Some(low)) = (self.high_pass_filter, self.low_pass_filter) {
+            if high >= low {
+                return Err(String::from(
+                    "Error: high-pass frequency must be below the low-pass frequency: nothing would be left.",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn validate_low_pass(&self) -> Result<(), String> {
+        if self.low_pass_filter == Some(0) {
+            return Err(String::from("Error: low-pass frequency must be positive."));
+        }
+        Ok(())
+    }
+
+    // whether the file exists is left to sox, like the input to ffmpeg
+    pub(crate) fn validate_noise_profile(&self) -> Result<(), String> {
+        match self.noise_profile_file {
+            Some(ref noise_profile_file) if noise_profile_file.is_empty() => {
+                Err(String::from("Error: no noise profile file specified."))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The output must not overwrite the input, however both are written,
+    /// e.g. `./talk.mp4` and `talk.mp4` or through a link. A pattern is
+    /// checked as named for the first input of a batch.
+    pub(crate) fn validate_output_file(&self) -> Result<(), String> {
+        if self.preview || self.writes_to_stdout() || self.input_file.is_empty() {
+            return Ok(());
+        }
+        let named = self.with_output_pattern(1);
+        let mut outputs = vec![named.output_file.as_str()];
+        outputs.extend(
+            self.additional_outputs
+                .iter()
+                .map(|output| output.path.as_str()),
+        );
+
+        let input = self.resolve_path(&self.input_file);
+        for output in outputs {
+            if same_file(&input, &self.resolve_path(output)) {
+                return Err(format!(
+                    "Error: {} is the input file, writing it would destroy it.",
+                    output
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // nothing but the single output may be written to the standard output
+    pub(crate) fn validate_stdout_output(&self) -> Result<(), String> {
+        if !self.writes_to_stdout() {
+            return Ok(());
+        }
+        if self.output_format.is_none() {
+            return Err(String::from(
+                "Error: writing to the standard output needs an explicit output format.",
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if self.extract_subtitles.is_some() {
+            conflicts.push("subtitle extraction");
+        }
+        if self.verify_lossless {
+            conflicts.push("lossless verification");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: writing to the standard output cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_additional_outputs(&self) -> Result<(), String> {
+        if self.additional_outputs.is_empty() {
+            return Ok(());
+        }
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: additional outputs hold the audio but audio is ignored.",
+            ));
+        }
+
+        let mut written = vec![self.output_file.as_str()];
+        for output in &self.additional_outputs {
+            if output.path.is_empty() {
+                return Err(String::from("Error: an additional output has no path."));
+            }
+            if output.path == self.input_file {
+                return Err(format!(
+                    "Error: additional output {} is the input file.",
+                    output.path
+                ));
+            }
+            if written.contains(&output.path.as_str()) {
+                return Err(format!("Error: {} is written more than once.", output.path));
+            }
+            if output.bitrate == Some(0) {
+                return Err(format!(
+                    "Error: bitrate of {} must be positive.",
+                    output.path
+                ));
+            }
+            written.push(&output.path);
+        }
+
+        let mut conflicts = Vec::new();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.output_pattern.is_some() {
+            conflicts.push("output patterns");
+        }
+        if self.writes_to_stdout() {
+            conflicts.push("writing to the standard output");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.silence_speedup.is_some() {
+            conflicts.push("speeding through silences");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: additional outputs cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    pub(crate) fn validate_lut(&self) -> Result<(), String> {
+        if let Some(ref lut) = self.lut {
+            let extension = lut
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            match extension.as_deref() {
+                Some("cube") | Some("3dl") => {}
+                _ => {
+                    return Err(String::from(
+                        "Error: LUT file must be a .cube or .3dl file.",
+                    ));
+                }
+            }
+            if !self.resolve_path(lut).is_file() {
+                return Err(format!("Error: LUT file not found: {}", lut.display()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the selection against the input file duration, `None` when unknown.
+    /// Returns the warnings to report.
+    pub fn check_duration(&self, duration: Option<Duration>) -> Result<Vec<String>, String> {
+        if self.process_whole_file {
+            return Ok(Vec::new());
+        }
+
+        let duration = match duration {
+            Some(duration) => duration,
+            None => {
+                return Ok(vec![String::from(
+                    "Input file duration is unknown: the selection was not checked against it.",
+                )]);
+            }
+        };
+
+        if self.from_time >= duration {
+            return Err(format!(
+                "Error: selection starts at {} but the input file lasts {}.",
+                seconds_to_string(self.from_time),
+                seconds_to_string(duration)
+            ));
+        }
+
+        if self.to_time > duration + Duration::milliseconds(END_OVERSHOOT_TOLERANCE_MS) {
+            let message = format!(
+                "Selection ends at {}, past the end of the input file ({}): the output is truncated to {}.",
+                seconds_to_string(self.to_time),
+                seconds_to_string(duration),
+                seconds_to_string(duration - self.from_time)
+            );
+            if self.strict {
+                return Err(format!("Error: {}", message));
+            }
+            return Ok(vec![message]);
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Copy whose `output_file` is named after `output_pattern`, if any, for the
+    /// `index`-th input of a batch.
+    pub fn with_output_pattern(&self, index: usize) -> Self {
+        let mut named = self.clone();
+        if let Some(ref pattern) = self.output_pattern {
+            named.output_file = output_pattern::expand_pattern(
+                pattern,
+                std::path::Path::new(&self.input_file),
+                index,
+                self.filename_charset,
+            )
+            .to_string_lossy()
+            .into_owned();
+        }
+        named
+    }
+
+    /// Default settings, with the machine ones set from the environment for
+    /// containers and CI: `MEDIA_CUTTER_FFMPEG_PATH`, `MEDIA_CUTTER_TEMP_DIR`
+    /// and `MEDIA_CUTTER_LOGLEVEL`.
+    pub fn from_env() -> Self {
+        Self::default().with_env()
+    }
+
+    /// Copy with the machine settings the environment sets, see `from_env`.
+    /// Variables set to an empty value are ignored.
+    pub fn with_env(&self) -> Self {
+        let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+        let mut conf = self.clone();
+        if let Some(path) = var(FFMPEG_PATH_VAR) {
+            conf.ffmpeg_path = Some(PathBuf::from(path));
+        }
+        if let Some(dir) = var(TEMP_DIR_VAR) {
+            conf.temp_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(level) = var(LOGLEVEL_VAR) {
+            conf.loglevel = Some(level.to_string_lossy().trim().to_lowercase());
+        }
+        conf
+    }
+
+    /// Copy with the selection expanded by `pad_before` and `pad_after`.
+    /// Padding never goes below zero nor past `duration` when it is known.
+    pub fn padded(&self, duration: Option<Duration>) -> Self {
+        let mut padded = self.clone();
+
+        if let Some(pad) = self.pad_before {
+            padded.from_time = (self.from_time - pad)
+                .max(Duration::zero())
+                .min(self.from_time);
+        }
+
+        if let Some(pad) = self.pad_after {
+            let to_time = self.to_time + pad;
+            padded.to_time = match duration {
+                // a selection already past the end is left to check_duration
+                Some(duration) => to_time.min(duration).max(self.to_time),
+                None => to_time,
+            };
+        }
+
+        padded
+    }
+
+    fn validate_raw_filter_complex(&self) -> Result<(), String> {
+        let graph = match self.raw_filter_complex {
+            Some(ref graph) => graph,
+            None => return Ok(()),
+        };
+        if graph.trim().is_empty() {
+            return Err(String::from("Error: the raw filtergraph is empty."));
+        }
+        if !self.extra_ffmpeg_args.iter().any(|arg| arg == "-map") {
+            return Err(String::from(
+                "Error: the outputs of the raw filtergraph must be chosen with -map in the extra ffmpeg arguments.",
+            ));
+        }
+
+        let mut filters = self.video_filter_names();
+        filters.extend(self.audio_filter_names());
+        if self.burn_timecode.is_some() {
+            filters.push("timecode");
+        }
+        if !filters.is_empty() {
+            return Err(format!(
+                "Error: the raw filtergraph replaces the filters, remove: {}.",
+                filters.join(", ")
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.video_stream_index.is_some() {
+            conflicts.push("video stream selection");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: a raw filtergraph cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_image_sequence(&self) -> Result<(), String> {
+        let sequence = match self.image_sequence {
+            Some(sequence) => sequence,
+            None => return Ok(()),
+        };
+        if !image_sequence::is_pattern(&self.input_file) {
+            return Err(format!(
+                "Error: {} is not an image sequence pattern such as frame_%05d.png.",
+                self.input_file
+            ));
+        }
+        if !(sequence.framerate > 0.0 && sequence.framerate.is_finite()) {
+            return Err(String::from(
+                "Error: the image sequence needs a positive frame rate.",
+            ));
+        }
+        if self.ignore_video {
+            return Err(String::from(
+                "Error: an image sequence only holds video, it cannot be ignored.",
+            ));
+        }
+        if !self.process_whole_file {
+            return Err(String::from(
+                "Error: an image sequence is read whole, it cannot be trimmed.",
+            ));
+        }
+
+        // pictures have no audio nor subtitles
+        let mut conflicts = self.audio_filter_names();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.extract_subtitles.is_some() {
+            conflicts.push("subtitle extraction");
+        }
+        if self.trim_to_first_sound {
+            conflicts.push("trimming to the first sound");
+        }
+        if self.verify_lossless {
+            conflicts.push("lossless verification");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: an image sequence input cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_audio_tracks(&self) -> Result<(), String> {
+        if self.audio_tracks.is_empty() {
+            return Ok(());
+        }
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: audio is ignored but audio tracks are selected.",
+            ));
+        }
+        if !self.audio_tracks.iter().any(|track| track.keep) {
+            return Err(String::from(
+                "Error: none of the selected audio tracks is kept.",
+            ));
+        }
+        for (position, track) in self.audio_tracks.iter().enumerate() {
+            if self.audio_tracks[..position]
+                .iter()
+                .any(|other| other.stream_index == track.stream_index)
+            {
+                return Err(format!(
+                    "Error: audio track {} is selected twice.",
+                    track.stream_index
+                ));
+            }
+        }
+
+        // sox cleans a single track, the others go through ffmpeg only
+        let mut conflicts = Vec::new();
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.noise_profile_file.is_some() && self.noise_reduction_amount.is_some() {
+            conflicts.push("noise reduction");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if self.verify_lossless {
+            conflicts.push("lossless verification");
+        }
+        // the measurements read the default track, not the ones kept
+        if self.peak_normalization {
+            conflicts.push("peak normalization");
+        }
+        if self.loudness_target.is_some() && self.loudnorm_two_pass {
+            conflicts.push("two-pass loudness normalization");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: audio track selection cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_downmix_tracks(&self) -> Result<(), String> {
+        let tracks = match self.downmix_tracks {
+            Some(ref tracks) => tracks,
+            None => return Ok(()),
+        };
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: audio is ignored but audio tracks are mixed.",
+            ));
+        }
+        if tracks.len() < 2 {
+            return Err(String::from(
+                "Error: mixing audio tracks needs at least two of them.",
+            ));
+        }
+        for (position, &(stream_index, gain_db)) in tracks.iter().enumerate() {
+            if tracks[..position]
+                .iter()
+                .any(|&(other, _)| other == stream_index)
+            {
+                return Err(format!(
+                    "Error: audio track {} is mixed twice.",
+                    stream_index
+                ));
+            }
+            if !gain_db.is_finite() {
+                return Err(format!(
+                    "Error: gain of audio track {} must be a number, got {}.",
+                    stream_index, gain_db
+                ));
+            }
+        }
+
+        // the audio leaves ffmpeg as a single track
+        let mut conflicts = Vec::new();
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.noise_profile_file.is_some() && self.noise_reduction_amount.is_some() {
+            conflicts.push("noise reduction");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if self.verify_lossless {
+            conflicts.push("lossless verification");
+        }
+        // the measurements read the default track, not the mix
+        if self.peak_normalization {
+            conflicts.push("peak normalization");
+        }
+        if self.loudness_target.is_some() && self.loudnorm_two_pass {
+            conflicts.push("two-pass loudness normalization");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: mixing audio tracks cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_still_image(&self) -> Result<(), String> {
+        if self.still_image.is_none() {
+            return Ok(());
+        }
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: the picture is shown for the audio but audio is ignored.",
+            ));
+        }
+
+        // the picture is encoded as it is, the input video left out
+        let mut conflicts = self.video_filter_names();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.image_sequence.is_some() {
+            conflicts.push("image sequence input");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.silence_speedup.is_some() {
+            conflicts.push("speeding through silences");
+        }
+        if self.raw_filter_complex.is_some() {
+            conflicts.push("raw filtergraph");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: a still image video cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_thumbnail_from_video(&self) -> Result<(), String> {
+        let (video_file, time) = match self.thumbnail_from_video {
+            Some((ref video_file, time)) => (video_file, time),
+            None => return Ok(()),
+        };
+        if video_file.is_empty() {
+            return Err(String::from(
+                "Error: the video of the thumbnail is not set.",
+            ));
+        }
+        if time < Duration::zero() {
+            return Err(String::from(
+                "Error: the time of the thumbnail is negative.",
+            ));
+        }
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: the thumbnail is embedded in the audio but audio is ignored.",
+            ));
+        }
+        if self.output_format.is_none() && !self.writes_to_stdout() {
+            let extension = Path::new(&self.output_file)
+                .extension()
+                .map_or_else(String::new, |extension| {
+                    extension.to_string_lossy().into_owned()
+                });
+            if !containers::supports_cover_art(&extension) {
+                return Err(format!(
+                    "Error: {} cannot hold a cover picture, use the mp3, m4a or flac extension.",
+                    self.output_file
+                ));
+            }
+        }
+
+        // only the audio of the input is written, next to the picture
+        let mut conflicts = self.video_filter_names();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.image_sequence.is_some() {
+            conflicts.push("image sequence input");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.silence_speedup.is_some() {
+            conflicts.push("speeding through silences");
+        }
+        if self.raw_filter_complex.is_some() {
+            conflicts.push("raw filtergraph");
+        }
+        if self.still_image.is_some() {
+            conflicts.push("still image video");
+        }
+        if self.stream_copy {
+            conflicts.push("copying every stream");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: a thumbnail from a video cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_segment_time(&self) -> Result<(), String> {
+        let segment_time = match self.segment_time {
+            Some(segment_time) => segment_time,
+            None => return Ok(()),
+        };
+        if segment_time <= Duration::zero() {
+            return Err(String::from(
+                "Error: the length of the output segments must be positive.",
+            ));
+        }
+        if !image_sequence::is_pattern(&self.output_file) {
+            return Err(format!(
+                "Error: {} has no number for the segments, use a pattern such as out_%03d.ts.",
+                self.output_file
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if self.preview {
+            conflicts.push("previews");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.extract_subtitles.is_some() {
+            conflicts.push("subtitle extraction");
+        }
+        if self.verify_lossless {
+            conflicts.push("lossless verification");
+        }
+        if self.fragmented_mp4 {
+            conflicts.push("fragmented MP4");
+        }
+        if self.thumbnail_from_video.is_some() {
+            conflicts.push("cover from a video frame");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: segmented output cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_stream_copy(&self) -> Result<(), String> {
+        if !self.stream_copy {
+            if !self.stream_copy_exclude.is_empty() {
+                return Err(String::from(
+                    "Error: streams are excluded but the streams are not copied.",
+                ));
+            }
+            return Ok(());
+        }
+        for spec in &self.stream_copy_exclude {
+            if parse_stream_specifier(spec).is_none() {
+                return Err(format!(
+                    "Error: invalid stream specifier {}, expected e.g. s or a:1.",
+                    spec
+                ));
+            }
+        }
+
+        let mut filters = self.video_filter_names();
+        filters.extend(self.audio_filter_names());
+        if self.burn_timecode.is_some() {
+            filters.push("timecode");
+        }
+        if !filters.is_empty() {
+            return Err(format!(
+                "Error: streams are copied as they are but the following filters are enabled: {}.",
+                filters.join(", ")
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.operation != Operation::Process {
+            conflicts.push("operations other than processing");
+        }
+        if matches!(self.video_codec.as_deref(), Some(codec) if codec != "copy")
+            || matches!(self.audio_codec.as_deref(), Some(codec) if codec != "copy")
+        {
+            conflicts.push("encoders");
+        }
+        if self.sample_accurate {
+            conflicts.push("sample-accurate cuts");
+        }
+        if self.video_stream_index.is_some() {
+            conflicts.push("video stream selection");
+        }
+        if self.image_sequence.is_some() {
+            conflicts.push("image sequence input");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.still_image.is_some() {
+            conflicts.push("still image video");
+        }
+        if self.raw_filter_complex.is_some() {
+            conflicts.push("raw filtergraph");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: copying every stream cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    fn validate_visualize(&self) -> Result<(), String> {
+        let (width, height) = match self.operation {
+            Operation::Visualize { resolution, .. } => resolution,
+            _ => return Ok(()),
+        };
+        if width == 0 || height == 0 {
+            return Err(String::from(
+                "Error: the visualisation size must be positive.",
+            ));
+        }
+        if self.ignore_audio {
+            return Err(String::from(
+                "Error: the visualisation is drawn from the audio but audio is ignored.",
+            ));
+        }
+
+        let mut conflicts = Vec::new();
+        if self.preview {
+            conflicts.push("previews");
+        }
+        if !self.video_filter_names().is_empty() || self.burn_timecode.is_some() {
+            conflicts.push("video filters");
+        }
+        if self.demux_streams {
+            conflicts.push("stream demuxing");
+        }
+        if !self.additional_outputs.is_empty() {
+            conflicts.push("additional outputs");
+        }
+        if self.keep_all_audio {
+            conflicts.push("keeping all audio tracks");
+        }
+        if !self.audio_tracks.is_empty() {
+            conflicts.push("audio track selection");
+        }
+        if self.downmix_tracks.is_some() {
+            conflicts.push("audio track mix");
+        }
+        if self.silence_speedup.is_some() {
+            conflicts.push("speeding through silences");
+        }
+        if self.raw_filter_complex.is_some() {
+            conflicts.push("raw filtergraphs");
+        }
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Error: the audio visualisation cannot be combined with: {}.",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    // user facing names of the enabled video filters
+    fn video_filter_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.video_denoise.is_some() {
+            names.push("denoising");
+        }
+        if self.stabilize.is_some() {
+            names.push("stabilization");
+        }
+        if matches!(self.color, Some(color) if !color.is_default()) {
+            names.push("colour adjustments");
+        }
+        if self.lut.is_some() {
+            names.push("3D LUT");
+        }
+        match self.aspect_ratio {
+            Some(AspectPolicy::Force(..)) | Some(AspectPolicy::SquarePixels) => {
+                names.push("aspect ratio")
+            }
+            Some(AspectPolicy::Keep) | None => {}
+        }
+        if self.max_height.is_some() {
+            names.push("maximum height");
+        }
+        names
+    }
+
+    pub(crate) fn audio_filter_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.vocal_removal.is_some() {
+            names.push("vocal removal");
+        }
+        if self.channel_ops.is_some() {
+            names.push("channel operation");
+        }
+        if self.high_pass_filter.is_some() {
+            names.push("high-pass");
+        }
+        if self.low_pass_filter.is_some() {
+            names.push("low-pass");
+        }
+        if self.noise_profile_file.is_some() && self.noise_reduction_amount.is_some() {
+            names.push("noise reduction");
+        }
+        if self.peak_normalization {
+            names.push("peak normalization");
+        }
+        if self.volume_change != 0.0 {
+            names.push("volume change");
+        }
+        if !self.volume_envelope.is_empty() {
+            names.push("volume envelope");
+        }
+        if self.reverb.is_some() {
+            names.push("reverb");
+        }
+        if self.loudness_target.is_some() {
+            names.push("loudness normalization");
+        }
+        if self.silence_speedup.is_some() {
+            names.push("silence speedup");
+        }
+        names
+    }
+
+    /// Whether the input is cut to the selection, not so for whole file runs
+    /// and full previews.
+    pub(crate) fn trims_input(&self) -> bool {
+        let full_preview = self.preview && self.preview_full;
+        !self.process_whole_file && !full_preview
+    }
+
+    /// Whether the audio is encoded to FLAC, chosen or implied by the extension.
+    pub(crate) fn is_flac_output(&self) -> bool {
+        match self.audio_codec.as_deref() {
+            Some(codec) => codec == "flac",
+            None => match Path::new(&self.output_file).extension() {
+                Some(extension) => extension.eq_ignore_ascii_case("flac"),
+                None => false,
+            },
+        }
+    }
+
+    /// `path` as seen from `working_dir`. Network inputs are left as they are.
+    pub(crate) fn resolve_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        match self.working_dir {
+            Some(ref working_dir) if !is_url(&path.to_string_lossy()) => working_dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// The output goes to the standard output instead of a file, see `STDOUT_OUTPUT`.
+    pub fn writes_to_stdout(&self) -> bool {
+        self.output_pattern.is_none() && self.output_file == STDOUT_OUTPUT
+    }
+
+    /// Audio encoder given to ffmpeg: the AAC settings pick one of their own.
+    pub(crate) fn audio_encoder(&self) -> Option<&str> {
+        match self.aac_options {
+            Some(aac) => Some(aac.encoder.codec()),
+            None => self.audio_codec.as_deref(),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            operation: Operation::Process,
+            preview: false,
+            input_file: String::from(""),
+            output_file: String::from(""),
+            from_time: Duration::seconds(0),
+            to_time: Duration::seconds(0),
+            high_pass_filter: None,
+            low_pass_filter: None,
+            allow_overidde: false,
+            ignore_video: false,
+            ignore_audio: false,
+            peak_normalization: false,
+            volume_change: 0.0,
+            noise_profile_file: None,
+            noise_reduction_amount: None,
+            stabilize: None,
+            video_codec: None,
+            audio_codec: None,
+            demux_streams: false,
+            video_denoise: None,
+            max_retries: 0,
+            color: None,
+            normalization_measure_range: None,
+            lut: None,
+            aspect_ratio: None,
+            burn_timecode: None,
+            font_path: None,
+            max_output_size: None,
+            target_size: None,
+            max_height: None,
+            spectrogram: None,
+            strict: false,
+            process_whole_file: false,
+            pad_before: None,
+            pad_after: None,
+            sample_accurate: false,
+            create_output_dirs: false,
+            strip_rotation_metadata: false,
+            output_pattern: None,
+            encoder_preset: None,
+            threads: None,
+            extract_subtitles: None,
+            subtitle_format: None,
+            low_priority: false,
+            nice_level: None,
+            fix_timestamps: false,
+            tolerant_input: false,
+            preview_loop: false,
+            strict_codecs: false,
+            output_format: None,
+            segment_time: None,
+            additional_outputs: Vec::new(),
+            image_sequence: None,
+            extra_ffmpeg_args: Vec::new(),
+            raw_filter_complex: None,
+            volume_envelope: Vec::new(),
+            mp3: None,
+            keep_all_audio: false,
+            audio_tracks: Vec::new(),
+            downmix_tracks: None,
+            still_image: None,
+            thumbnail_from_video: None,
+            stream_copy: false,
+            stream_copy_exclude: Vec::new(),
+            flac_compression: None,
+            verify_lossless: false,
+            aac_options: None,
+            audio_bitrate: None,
+            working_dir: None,
+            contact_sheet: None,
+            contact_sheet_tile_width: None,
+            contact_sheet_timestamps: false,
+            video_stream_index: None,
+            channel_ops: None,
+            embed_source_info: false,
+            vocal_removal: None,
+            preserve_channels: true,
+            copy_audio_when_possible: true,
+            reverb: None,
+            loudness_target: None,
+            loudness_true_peak: -1.5,
+            loudness_range: 11.0,
+            loudnorm_two_pass: false,
+            silence_speedup: None,
+            write_chapters: None,
+            preview_full: false,
+            fragmented_mp4: false,
+            filename_charset: output_pattern::FilenameCharset::Portable,
+            metadata: Vec::new(),
+            trim_to_first_sound: false,
+            ffmpeg_path: None,
+            temp_dir: None,
+            loglevel: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // tests run on several threads, those setting the environment take turns
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    // most settings need a selection to be valid
+    fn selection_config() -> Config {
+        let mut conf = Config::new();
+        conf.to_time = Duration::seconds(10);
+        conf
+    }
+
+    #[test]
+    fn convert_duration_to_string() {
+        assert_eq!(duration_to_string(Duration::milliseconds(1002)), "0:0:1.2");
+        assert_eq!(
+            duration_to_string(Duration::milliseconds(65125)),
+            "0:1:5.125"
+        );
+        assert_eq!(
+            duration_to_string(Duration::milliseconds(6065125)),
+            "1:41:5.125"
+        );
+        assert_eq!(duration_to_string(Duration::seconds(128)), "0:2:8.0");
+    }
+
+    #[test]
+    fn time_parsing() {
+        for &(text, millis) in &[
+            ("83", 83_000),
+            ("0", 0),
+            ("12.5", 12_500),
+            ("1:23", 83_000),
+            (" 1:23.25 ", 83_250),
+            ("01:02:03", 3_723_000),
+            ("1:02:03.5", 3_723_500),
+            // the largest unit may exceed 60
+            ("90:00", 5_400_000),
+            ("0:00.001", 1),
+        ] {
+            assert_eq!(
+                parse_duration(text),
+                Ok(Duration::milliseconds(millis)),
+                "{}",
+                text
+            );
+        }
+        for text in &[
+            "", "abc", "1:60", "1:75:00", "1:2:3:4", ":30", "1::30", "-5", "1:-5", "+1:30",
+            "1:30s", "NaN", "inf",
+        ] {
+            assert!(parse_duration(text).is_err(), "{}", text);
+        }
+        assert_eq!(
+            parse_duration("1h"),
+            Err(String::from(
+                "Invalid time: \"1h\", expected e.g. 1:23 or 1:02:03.5."
+            ))
+        );
+    }
+
+    #[test]
+    fn range_parsing() {
+        let expected = Ok((Duration::seconds(83), Duration::seconds(165)));
+        for text in &[
+            "1:23-2:45",
+            "1:23 - 2:45",
+            "1:23–2:45",
+            "83..165",
+            "1:23 to 2:45",
+            "83, 165",
+            "0:01:23.000-0:02:45",
+        ] {
+            assert_eq!(parse_range(text), expected, "{}", text);
+        }
+
+        for text in &[
+            "1:23",
+            "",
+            "1:23-",
+            "-2:45",
+            "1:23-2:45-3:00",
+            "a-b",
+            "1:23 2:45",
+        ] {
+            assert!(parse_range(text).is_err(), "{}", text);
+        }
+        assert_eq!(
+            parse_range("1:23"),
+            Err(String::from(
+                "Invalid range: \"1:23\", expected a start and an end time such as 1:23-2:45."
+            ))
+        );
+        assert_eq!(
+            parse_range("2:45-1:23"),
+            Err(String::from("Range start must be before its end."))
+        );
+        assert!(parse_range("1:23-1:23").is_err());
+    }
+
+    #[test]
+    fn volume_conversions() {
+        let close = |a: f64, b: f64, epsilon: f64| (a - b).abs() < epsilon;
+
+        assert!(close(db_to_ratio(0.0), 1.0, 1e-3));
+        assert!(close(db_to_ratio(-6.0), 0.501, 1e-3));
+        assert!(close(db_to_ratio(6.0), 1.995, 1e-3));
+        assert!(close(ratio_to_db(1.0), 0.0, 1e-2));
+        assert!(close(ratio_to_db(0.501), -6.0, 1e-2));
+        assert!(close(ratio_to_db(1.995), 6.0, 1e-2));
+
+        assert_eq!(db_to_percent(0.0), 100.0);
+        assert_eq!(db_to_percent(-6.0), 50.0);
+        assert_eq!(db_to_percent(6.0), 200.0);
+        assert_eq!(volume_description(-6.0), "-6.0 dB (50 %)");
+    }
+
+    #[test]
+    fn volume_round_trip() {
+        for tenth_db in -400..=200 {
+            let db = f64::from(tenth_db) / 10.0;
+            assert!((ratio_to_db(db_to_ratio(db)) - db).abs() < 1e-9);
+        }
+
+        // whole percentages survive the conversion to dB and back
+        for percent in 1..=1000 {
+            let db = ratio_to_db(f64::from(percent) / 100.0);
+            assert_eq!(db_to_percent(db), f64::from(percent));
+        }
+    }
+
+    #[test]
+    fn clamp_color_adjustments() {
+        let color = ColorAdjust {
+            brightness: -3.0,
+            contrast: 2000.0,
+            saturation: 3.5,
+            gamma: 0.0,
+        };
+        assert_eq!(
+            color.clamped(),
+            ColorAdjust {
+                brightness: -1.0,
+                contrast: 1000.0,
+                saturation: 3.0,
+                gamma: 0.1,
+            }
+        );
+        assert!(ColorAdjust::default().is_default());
+        assert!(!color.is_default());
+    }
+
+    #[test]
+    fn validate_normalization_measure_range() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(10);
+        conf.to_time = Duration::seconds(60);
+        assert!(conf.validate().is_ok());
+
+        conf.normalization_measure_range = Some((Duration::seconds(20), Duration::seconds(30)));
+        assert!(conf.validate().is_ok());
+
+        conf.normalization_measure_range = Some((Duration::seconds(10), Duration::seconds(60)));
+        assert!(conf.validate().is_ok());
+
+        conf.normalization_measure_range = Some((Duration::seconds(5), Duration::seconds(30)));
+        assert!(conf.validate().is_err());
+
+        conf.normalization_measure_range = Some((Duration::seconds(20), Duration::seconds(61)));
+        assert!(conf.validate().is_err());
+
+        conf.normalization_measure_range = Some((Duration::seconds(30), Duration::seconds(20)));
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn url_schemes() {
+        assert_eq!(url_scheme("https://example.com/in.mp4"), Some("https"));
+        assert_eq!(url_scheme("rtmp://live.example.com/app/key"), Some("rtmp"));
+        assert_eq!(url_scheme("srt+tls://host:9000"), Some("srt+tls"));
+        assert_eq!(url_scheme("/home/me/in.mp4"), None);
+        assert_eq!(url_scheme(r"C:\Videos\in.mp4"), None);
+        // no scheme, or not one
+        assert_eq!(url_scheme("://example.com"), None);
+        assert_eq!(url_scheme("1http://example.com"), None);
+        assert_eq!(url_scheme("/videos/a://b.mp4"), None);
+        assert!(is_url("HTTPS://example.com/in.mp4"));
+        assert!(!is_url("in.mp4"));
+    }
+
+    #[test]
+    fn validate_input_url() {
+        let mut conf = selection_config();
+        for url in &[
+            "https://example.com/in.mp4",
+            "HTTP://example.com/in.mp4",
+            "rtmp://live.example.com/app/key",
+            "rtsp://camera.local:554/stream",
+        ] {
+            conf.input_file = String::from(*url);
+            assert_eq!(conf.validate(), Ok(()), "{}", url);
+        }
+
+        conf.input_file = String::from("gopher://example.com/in.mp4");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: unsupported input protocol gopher://, expected one of http, https, rtmp, rtmps, rtsp, rtsps, srt, udp, ftp."
+            ))
+        );
+        conf.input_file = String::from("file:///home/me/in.mp4");
+        assert!(conf.validate().is_err());
+
+        // sox reads the input itself
+        conf.input_file = String::from("https://example.com/in.mp4");
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.2);
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: noise reduction runs sox, which cannot read network inputs: download the input first."
+            ))
+        );
+        conf.input_file = String::from("in.mp4");
+        assert!(conf.validate().is_ok());
+
+        // the rest still applies
+        conf.noise_profile_file = None;
+        conf.input_file = String::from("https://example.com/in.mp4");
+        conf.process_whole_file = true;
+        conf.operation = Operation::SmartCut;
+        assert!(conf.validate().is_err());
+
+        // paths are not resolved against the working directory
+        conf.working_dir = Some(PathBuf::from("/videos"));
+        assert_eq!(
+            conf.resolve_path(&conf.input_file),
+            PathBuf::from("https://example.com/in.mp4")
+        );
+    }
+
+    #[test]
+    fn validate_subtitle_format() {
+        let mut conf = selection_config();
+        conf.subtitle_format = Some(String::from("vtt"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: a subtitle format is set but no subtitle stream is extracted."
+            ))
+        );
+        conf.extract_subtitles = Some(0);
+        assert_eq!(conf.validate(), Ok(()));
+
+        // the muxer is named after the extension, not the codec
+        conf.subtitle_format = Some(String::from("webvtt"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: unknown subtitle format webvtt, expected one of srt, vtt, ass."
+            ))
+        );
+        conf.subtitle_format = Some(String::from("ass"));
+        assert_eq!(conf.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_additional_outputs() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("in.mp4");
+        conf.output_file = String::from("out.mp4");
+        conf.additional_outputs = vec![OutputSpec::new("out.opus"), OutputSpec::new("out.wav")];
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.additional_outputs[1].path = String::from("out.mp4");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from("Error: out.mp4 is written more than once."))
+        );
+        conf.additional_outputs[1].path = String::from("out.opus");
+        assert!(conf.validate().is_err());
+        conf.additional_outputs[1].path = String::new();
+        assert!(conf.validate().is_err());
+        conf.additional_outputs[1] = OutputSpec {
+            bitrate: Some(0),
+            ..OutputSpec::new("out.wav")
+        };
+        assert!(conf.validate().is_err());
+        conf.additional_outputs[1].bitrate = None;
+
+        conf.ignore_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: additional outputs hold the audio but audio is ignored."
+            ))
+        );
+        conf.ignore_audio = false;
+        conf.demux_streams = true;
+        conf.silence_speedup = Some(SilenceSpeedup::default());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: additional outputs cannot be combined with: stream demuxing, speeding through silences."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_raw_filter_complex() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("in.mp4");
+        conf.output_file = String::from("out.mp4");
+        conf.raw_filter_complex = Some(String::from("[0:v]hflip[v]"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: the outputs of the raw filtergraph must be chosen with -map in the extra ffmpeg arguments."
+            ))
+        );
+        conf.extra_ffmpeg_args = vec![String::from("-map"), String::from("[v]")];
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.high_pass_filter = Some(100);
+        conf.burn_timecode = Some(TimecodeStyle::default());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: the raw filtergraph replaces the filters, remove: high-pass, timecode."
+            ))
+        );
+        conf.high_pass_filter = None;
+        conf.burn_timecode = None;
+
+        conf.keep_all_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: a raw filtergraph cannot be combined with: keeping all audio tracks."
+            ))
+        );
+        conf.keep_all_audio = false;
+        conf.raw_filter_complex = Some(String::from(" "));
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn image_sequence_input_args() {
+        let sequence = ImageSequence {
+            framerate: 12.5,
+            start_number: None,
+        };
+        assert_eq!(
+            sequence.input_args(),
+            vec!["-f", "image2", "-framerate", "12.5"]
+        );
+        let sequence = ImageSequence {
+            framerate: 30.0,
+            start_number: Some(0),
+        };
+        assert_eq!(
+            sequence.input_args(),
+            vec!["-f", "image2", "-framerate", "30", "-start_number", "0"]
+        );
+    }
+
+    #[test]
+    fn validate_image_sequence() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("frames/frame_%05d.png");
+        conf.output_file = String::from("clip.mp4");
+        conf.process_whole_file = true;
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 24.0,
+            start_number: Some(1),
+        });
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.process_whole_file = false;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: an image sequence is read whole, it cannot be trimmed."
+            ))
+        );
+        conf.process_whole_file = true;
+
+        conf.input_file = String::from("frames/frame_00001.png");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: frames/frame_00001.png is not an image sequence pattern such as frame_%05d.png."
+            ))
+        );
+        conf.input_file = String::from("frames/frame_%05d.png");
+
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 0.0,
+            start_number: None,
+        });
+        assert!(conf.validate().is_err());
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 29.97,
+            start_number: None,
+        });
+        conf.high_pass_filter = Some(100);
+        conf.extract_subtitles = Some(0);
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: an image sequence input cannot be combined with: high-pass, subtitle extraction."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_audio_tracks() {
+        let track = |stream_index, keep| TrackSpec {
+            stream_index,
+            filters: FilterChain::Configured,
+            keep,
+        };
+        let mut conf = selection_config();
+        conf.audio_tracks = vec![track(0, true), track(1, false)];
+        assert!(conf.validate().is_ok());
+
+        conf.audio_tracks = vec![track(0, false), track(1, false)];
+        assert!(conf.validate().is_err());
+        conf.audio_tracks = vec![track(1, true), track(1, false)];
+        assert!(conf.validate().is_err());
+
+        conf.audio_tracks = vec![track(0, true)];
+        conf.keep_all_audio = true;
+        assert!(conf.validate().is_err());
+        conf.keep_all_audio = false;
+        conf.noise_profile_file = Some(String::from("noise.prof"));
+        conf.noise_reduction_amount = Some(0.2);
+        assert!(conf.validate().is_err());
+        conf.noise_reduction_amount = None;
+        conf.peak_normalization = true;
+        conf.loudness_target = Some(-16.0);
+        conf.loudnorm_two_pass = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: audio track selection cannot be combined with: peak normalization, two-pass loudness normalization."
+            ))
+        );
+        conf.peak_normalization = false;
+        conf.loudnorm_two_pass = false;
+        assert!(conf.validate().is_ok());
+        conf.loudness_target = None;
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_downmix_tracks() {
+        let mut conf = selection_config();
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, -6.0)]);
+        assert!(conf.validate().is_ok());
+
+        conf.downmix_tracks = Some(vec![(0, 0.0)]);
+        assert!(conf.validate().is_err());
+        conf.downmix_tracks = Some(vec![(1, 0.0), (1, -6.0)]);
+        assert!(conf.validate().is_err());
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, f64::NAN)]);
+        assert!(conf.validate().is_err());
+
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, -6.0)]);
+        conf.keep_all_audio = true;
+        conf.verify_lossless = true;
+        conf.output_file = String::from("out.flac");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: mixing audio tracks cannot be combined with: keeping all audio tracks, lossless verification."
+            ))
+        );
+
+        conf.keep_all_audio = false;
+        conf.verify_lossless = false;
+        conf.peak_normalization = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: mixing audio tracks cannot be combined with: peak normalization."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_still_image() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("track.flac");
+        conf.output_file = String::from("track.mp4");
+        conf.still_image = Some(PathBuf::from("cover.jpg"));
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+        conf.ignore_audio = false;
+
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        conf.keep_all_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: a still image video cannot be combined with: aspect ratio, keeping all audio tracks."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_thumbnail_from_video() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("episode.wav");
+        conf.output_file = String::from("episode.mp3");
+        conf.thumbnail_from_video = Some((String::from("episode.mkv"), Duration::seconds(95)));
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.output_file = String::from("episode.opus");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: episode.opus cannot hold a cover picture, use the mp3, m4a or flac extension."
+            ))
+        );
+        conf.output_file = String::from("episode.M4A");
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.thumbnail_from_video = Some((String::from("episode.mkv"), Duration::seconds(-1)));
+        assert!(conf.validate().is_err());
+        conf.thumbnail_from_video = Some((String::new(), Duration::zero()));
+        assert!(conf.validate().is_err());
+        conf.thumbnail_from_video = Some((String::from("episode.mkv"), Duration::zero()));
+
+        conf.keep_all_audio = true;
+        conf.stream_copy = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: a thumbnail from a video cannot be combined with: keeping all audio tracks, copying every stream."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_fragmented_mp4() {
+        let mut conf = selection_config();
+        conf.fragmented_mp4 = true;
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.extra_ffmpeg_args = vec![String::from("-movflags"), String::from("+faststart")];
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: fragmented MP4 cannot be combined with -movflags in the extra arguments, such as +faststart."
+            ))
+        );
+        conf.fragmented_mp4 = false;
+        assert_eq!(conf.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_stream_copy() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("movie.mkv");
+        conf.output_file = String::from("clip.mkv");
+        conf.stream_copy = true;
+        conf.stream_copy_exclude = vec![String::from("d"), String::from("s:1")];
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.stream_copy_exclude.push(String::from("x:1"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: invalid stream specifier x:1, expected e.g. s or a:1."
+            ))
+        );
+        conf.stream_copy_exclude.pop();
+
+        conf.volume_change = 3.0;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: streams are copied as they are but the following filters are enabled: volume change."
+            ))
+        );
+        conf.volume_change = 0.0;
+
+        conf.audio_codec = Some(String::from("libopus"));
+        conf.keep_all_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: copying every stream cannot be combined with: encoders, keeping all audio tracks."
+            ))
+        );
+        conf.audio_codec = Some(String::from("copy"));
+        conf.keep_all_audio = false;
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.stream_copy = false;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_segment_time() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("talk.mkv");
+        conf.output_file = String::from("chunks/talk_%03d.ts");
+        conf.segment_time = Some(Duration::seconds(6));
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.output_file = String::from("chunks_%d/talk.ts");
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: chunks_%d/talk.ts has no number for the segments, use a pattern such as out_%03d.ts."
+            ))
+        );
+        conf.output_file = String::from("talk_%d.ts");
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.segment_time = Some(Duration::zero());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: the length of the output segments must be positive."
+            ))
+        );
+        conf.segment_time = Some(Duration::seconds(6));
+
+        conf.demux_streams = true;
+        conf.verify_lossless = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: segmented output cannot be combined with: stream demuxing, lossless verification."
+            ))
+        );
+    }
+
+    #[test]
+    fn stream_specifiers() {
+        assert_eq!(parse_stream_specifier("d"), Some(("data", None)));
+        assert_eq!(parse_stream_specifier("t"), Some(("attachment", None)));
+        assert_eq!(parse_stream_specifier("a:1"), Some(("audio", Some(1))));
+        assert_eq!(parse_stream_specifier("s:+1"), None);
+        assert_eq!(parse_stream_specifier("a:"), None);
+        assert_eq!(parse_stream_specifier("V"), None);
+        assert_eq!(parse_stream_specifier("0:s"), None);
+    }
+
+    #[test]
+    fn validate_visualize() {
+        let mut conf = selection_config();
+        conf.input_file = String::from("in.mp3");
+        conf.output_file = String::from("out.mp4");
+        conf.operation = Operation::Visualize {
+            style: VisualStyle::Waves,
+            resolution: (1280, 720),
+            colors: None,
+        };
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        conf.keep_all_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: the audio visualisation cannot be combined with: video filters, keeping all audio tracks."
+            ))
+        );
+        conf.aspect_ratio = None;
+        conf.keep_all_audio = false;
+
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+        conf.ignore_audio = false;
+        conf.operation = Operation::Visualize {
+            style: VisualStyle::Cqt,
+            resolution: (0, 720),
+            colors: None,
+        };
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_stdout_output() {
+        let mut conf = selection_config();
+        conf.output_file = String::from(STDOUT_OUTPUT);
+        assert!(conf.writes_to_stdout());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: writing to the standard output needs an explicit output format."
+            ))
+        );
+        conf.output_format = Some(String::from("wav"));
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.demux_streams = true;
+        conf.operation = Operation::Analyze;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: writing to the standard output cannot be combined with: operations other than processing, stream demuxing."
+            ))
+        );
+
+        // a pattern names the outputs, `-` is then just a file name
+        conf.output_pattern = Some(String::from("{stem}-{index}.wav"));
+        assert!(!conf.writes_to_stdout());
+    }
+
+    #[test]
+    fn validate_font_file() {
+        let mut conf = selection_config();
+        conf.font_path = Some(String::from("/nonexistent/DejaVuSans.ttf"));
+        // only used by the timecode
+        assert!(conf.validate().is_ok());
+        conf.burn_timecode = Some(TimecodeStyle::default());
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: font file not found: /nonexistent/DejaVuSans.ttf"
+        );
+        conf.font_path = None;
+        assert!(conf.validate().is_ok());
+        conf.burn_timecode = Some(TimecodeStyle {
+            font_size: 0,
+            ..TimecodeStyle::default()
+        });
+        assert!(conf.validate().is_err());
+        conf.burn_timecode = Some(TimecodeStyle::default());
+
+        // the picture is left alone
+        conf.ignore_video = true;
+        assert!(conf.validate().is_ok());
+        conf.ignore_video = false;
+        conf.operation = Operation::SmartCut;
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: smart cut copies most of the input but the following filters are enabled: timecode."
+        );
+    }
+
+    #[test]
+    fn validate_lut_file() {
+        let mut conf = selection_config();
+        conf.lut = Some(PathBuf::from("/nonexistent/grade.png"));
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: LUT file must be a .cube or .3dl file."
+        );
+
+        conf.lut = Some(PathBuf::from("/nonexistent/grade.CUBE"));
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: LUT file not found: /nonexistent/grade.CUBE"
+        );
+
+        let mut lut = std::env::temp_dir();
+        lut.push("media_cutter_validate_lut.3dl");
+        std::fs::write(&lut, "").unwrap();
+        conf.lut = Some(lut.clone());
+        assert!(conf.validate().is_ok());
+
+        // relative to the working directory
+        conf.lut = Some(PathBuf::from("media_cutter_validate_lut.3dl"));
+        assert!(conf.validate().is_err());
+        conf.working_dir = Some(std::env::temp_dir());
+        assert!(conf.validate().is_ok());
+        std::fs::remove_file(lut).unwrap();
+    }
+
+    #[test]
+    fn validate_ignore_both_streams() {
+        let mut conf = selection_config();
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_ok());
+        conf.ignore_video = true;
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: cannot ignore both audio and video."
+        );
+
+        conf.ignore_audio = false;
+        conf.video_stream_index = Some(1);
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: video is ignored but a video stream is selected."
+        );
+    }
+
+    #[test]
+    fn validate_flac_settings() {
+        let mut conf = selection_config();
+        conf.output_file = String::from("archive.flac");
+        conf.flac_compression = Some(12);
+        assert!(conf.validate().is_ok());
+        conf.flac_compression = Some(13);
+        assert!(conf.validate().is_err());
+
+        conf.flac_compression = None;
+        conf.verify_lossless = true;
+        assert!(conf.validate().is_ok());
+        conf.volume_change = 2.0;
+        conf.high_pass_filter = Some(80);
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: lossless verification needs the audio untouched but the following audio filters are enabled: high-pass, volume change."
+        );
+
+        conf.volume_change = 0.0;
+        conf.high_pass_filter = None;
+        conf.output_file = String::from("archive.mka");
+        assert!(conf.validate().is_err());
+        conf.audio_codec = Some(String::from("flac"));
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_keep_all_audio() {
+        let mut conf = selection_config();
+        conf.keep_all_audio = true;
+        assert!(conf.validate().is_ok());
+        conf.ignore_video = true;
+        assert!(conf.validate().is_ok());
+
+        conf.ignore_video = false;
+        conf.ignore_audio = true;
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: cannot keep all audio tracks while ignoring audio."
+        );
+
+        conf.ignore_audio = false;
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.2);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_ignore_video_with_video_filters() {
+        let mut conf = selection_config();
+        conf.ignore_video = true;
+        conf.color = Some(ColorAdjust::default());
+        assert!(conf.validate().is_ok());
+
+        conf.stabilize = Some(StabilizeSettings::default());
+        conf.lut = Some(PathBuf::from("grade.cube"));
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: video is ignored but the following video filters are enabled: stabilization, 3D LUT."
+        );
+
+        conf.ignore_video = false;
+        conf.stabilize = None;
+        conf.lut = None;
+        conf.video_denoise = Some(VideoDenoise::Nlmeans { strength: 1.0 });
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_forced_aspect_ratio() {
+        let mut conf = selection_config();
+        conf.aspect_ratio = Some(AspectPolicy::Force(16, 9));
+        assert!(conf.validate().is_ok());
+        conf.aspect_ratio = Some(AspectPolicy::Force(4, 0));
+        assert!(conf.validate().is_err());
+
+        conf.aspect_ratio = Some(AspectPolicy::Keep);
+        conf.ignore_video = true;
+        assert!(conf.validate().is_ok());
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_output_size_limit() {
+        let mut conf = selection_config();
+        conf.max_output_size = Some(1);
+        assert!(conf.validate().is_ok());
+        conf.max_output_size = Some(0);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_output_file() {
+        let mut conf = selection_config();
+        conf.input_file = String::from(file!());
+        conf.output_file = format!("./{}", file!());
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            format!(
+                "Error: ./{} is the input file, writing it would destroy it.",
+                file!()
+            )
+        );
+        conf.preview = true;
+        assert!(conf.validate().is_ok());
+
+        let mut conf = selection_config();
+        conf.input_file = String::from(file!());
+        conf.output_pattern = Some(String::from("{parent}/{stem}.{ext}"));
+        assert!(conf.validate().is_err());
+        conf.output_pattern = Some(String::from("{parent}/{stem}_cut.{ext}"));
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_selection_and_filters() {
+        let mut conf = selection_config();
+        conf.from_time = conf.to_time;
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: start time must be before the end time."
+        );
+        // the times are ignored then
+        conf.process_whole_file = true;
+        assert!(conf.validate().is_ok());
+        conf.process_whole_file = false;
+        conf.operation = Operation::FixRotation { set: None };
+        assert!(conf.validate().is_ok());
+
+        let mut conf = selection_config();
+        conf.high_pass_filter = Some(0);
+        assert!(conf.validate().is_err());
+        conf.high_pass_filter = Some(5000);
+        conf.low_pass_filter = Some(200);
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: high-pass frequency must be below the low-pass frequency: nothing would be left."
+        );
+        conf.high_pass_filter = None;
+        conf.low_pass_filter = Some(0);
+        assert!(conf.validate().is_err());
+
+        let mut conf = selection_config();
+        conf.noise_profile_file = Some(String::new());
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: no noise profile file specified."
+        );
+    }
+
+    #[test]
+    fn validate_stabilize_settings() {
+        let mut conf = selection_config();
+        conf.stabilize = Some(StabilizeSettings {
+            shakiness: 10,
+            smoothing: 1000,
+        });
+        assert!(conf.validate().is_ok());
+
+        conf.stabilize = Some(StabilizeSettings {
+            shakiness: 0,
+            smoothing: 10,
+        });
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: stabilization shakiness must be between 1 and 10, got 0."
+        );
+        conf.stabilize = Some(StabilizeSettings {
+            shakiness: 5,
+            smoothing: 1001,
+        });
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_target_size() {
+        let mut conf = selection_config();
+        conf.target_size = Some(8_000_000);
+        assert!(conf.validate().is_ok());
+        conf.target_size = Some(0);
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: target size must be positive."
+        );
+
+        conf.target_size = Some(8_000_000);
+        conf.ignore_video = true;
+        conf.video_codec = Some(String::from("copy"));
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: target size cannot be combined with: ignoring video, video copy."
+        );
+    }
+
+    #[test]
+    fn validate_max_height_and_audio_bitrate() {
+        let mut conf = selection_config();
+        conf.max_height = Some(720);
+        conf.audio_bitrate = Some(48);
+        assert!(conf.validate().is_ok());
+        conf.max_height = Some(721);
+        assert!(conf.validate().is_err());
+
+        conf.max_height = None;
+        conf.audio_bitrate = Some(1000);
+        assert!(conf.validate().is_err());
+        conf.audio_bitrate = Some(96);
+        conf.audio_codec = Some(String::from("libmp3lame"));
+        conf.mp3 = Some(Mp3Settings::default());
+        assert_eq!(
+            conf.validate().unwrap_err(),
+            "Error: the audio bitrate of AAC and MP3 outputs is part of their settings."
+        );
+    }
+
+    #[test]
+    fn validate_mp3_settings() {
+        let mut conf = selection_config();
+        conf.mp3 = Some(Mp3Settings::default());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: MP3 quality settings need the libmp3lame audio codec."
+            ))
+        );
+        conf.audio_codec = Some(String::from("libopus"));
+        assert!(conf.validate().is_err());
+
+        conf.audio_codec = Some(String::from("libmp3lame"));
+        assert!(conf.validate().is_ok());
+        for &(quality, valid) in &[
+            (Mp3Quality::Vbr(0), true),
+            (Mp3Quality::Vbr(9), true),
+            (Mp3Quality::Vbr(10), false),
+            (Mp3Quality::Cbr(8), true),
+            (Mp3Quality::Cbr(320), true),
+            (Mp3Quality::Cbr(0), false),
+            (Mp3Quality::Cbr(384), false),
+        ] {
+            conf.mp3 = Some(Mp3Settings {
+                quality,
+                joint_stereo: false,
+            });
+            assert_eq!(conf.validate().is_ok(), valid, "{:?}", quality);
+        }
+    }
+
+    #[test]
+    fn validate_aac_options() {
+        let mut conf = selection_config();
+        conf.aac_options = Some(AacOptions::default());
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: AAC settings need the aac audio codec."
+            ))
+        );
+
+        conf.audio_codec = Some(String::from("aac"));
+        assert!(conf.validate().is_ok());
+        let fdk = |profile, vbr_mode, bitrate| AacOptions {
+            encoder: AacEncoder::Fdk,
+            profile,
+            vbr_mode,
+            bitrate,
+        };
+        for &(aac, valid) in &[
+            (fdk(AacProfile::Lc, None, 320), true),
+            (fdk(AacProfile::Lc, None, 384), false),
+            (fdk(AacProfile::HeV1, None, 24), true),
+            (fdk(AacProfile::HeV1, None, 16), false),
+            (fdk(AacProfile::HeV1, None, 160), false),
+            (fdk(AacProfile::HeV2, None, 16), true),
+            (fdk(AacProfile::HeV2, None, 8), false),
+            (fdk(AacProfile::HeV2, None, 96), false),
+            // bitrates do not matter in VBR
+            (fdk(AacProfile::HeV2, Some(1), 8), true),
+            (fdk(AacProfile::Lc, Some(5), 128), true),
+            (fdk(AacProfile::Lc, Some(0), 128), false),
+            (fdk(AacProfile::Lc, Some(6), 128), false),
+        ] {
+            conf.aac_options = Some(aac);
+            assert_eq!(conf.validate().is_ok(), valid, "{:?}", aac);
+        }
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: AAC VBR mode must be between 1 and 5, got 6."
+            ))
+        );
+        conf.aac_options = Some(fdk(AacProfile::HeV1, None, 16));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: HE-AAC bitrate must be between 24 and 128 kbps, got 16."
+            ))
+        );
+
+        // the native encoder only does AAC-LC at a constant bitrate
+        conf.aac_options = Some(AacOptions {
+            encoder: AacEncoder::Native,
+            ..fdk(AacProfile::HeV2, None, 32)
+        });
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: HE-AAC v2 needs the libfdk_aac encoder."
+            ))
+        );
+        conf.aac_options = Some(AacOptions {
+            encoder: AacEncoder::Native,
+            ..fdk(AacProfile::Lc, Some(4), 128)
+        });
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_channel_ops() {
+        let mut conf = selection_config();
+        for &balance in &[-1.0, -0.25, 0.0, 1.0] {
+            conf.channel_ops = Some(ChannelOp::Balance(balance));
+            assert!(conf.validate().is_ok(), "{}", balance);
+        }
+        conf.channel_ops = Some(ChannelOp::Balance(1.5));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: balance must be between -1 (left) and 1 (right), got 1.5."
+            ))
+        );
+        conf.channel_ops = Some(ChannelOp::Balance(f64::NAN));
+        assert!(conf.validate().is_err());
+
+        conf.channel_ops = Some(ChannelOp::MonoFromLeft);
+        conf.ignore_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: audio is ignored but a channel operation is set."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_vocal_removal() {
+        let mut conf = selection_config();
+        for &strength in &[0.1, 0.5, 1.0] {
+            conf.vocal_removal = Some(strength);
+            assert!(conf.validate().is_ok(), "{}", strength);
+        }
+        for &strength in &[0.0, -0.5, 1.5, f64::NAN] {
+            conf.vocal_removal = Some(strength);
+            assert!(conf.validate().is_err(), "{}", strength);
+        }
+        conf.vocal_removal = Some(2.0);
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: vocal removal strength must be above 0 and at most 1, got 2."
+            ))
+        );
+
+        conf.vocal_removal = Some(1.0);
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_loudness() {
+        let mut conf = selection_config();
+        conf.loudness_target = Some(-16.0);
+        assert_eq!(conf.validate(), Ok(()));
+        conf.loudness_true_peak = 0.0;
+        conf.loudness_range = 7.5;
+        assert_eq!(conf.validate(), Ok(()));
+
+        conf.loudness_true_peak = 0.5;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: true peak ceiling must be between -9 and 0 dBTP, got 0.5."
+            ))
+        );
+        conf.loudness_true_peak = -1.0;
+        conf.loudness_range = 0.0;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: loudness range must be between 1 and 50 LU, got 0."
+            ))
+        );
+        conf.loudness_range = 0.5;
+        assert!(conf.validate().is_err());
+        conf.loudness_range = 1.0;
+        assert_eq!(conf.validate(), Ok(()));
+        conf.loudness_range = f64::NAN;
+        assert!(conf.validate().is_err());
+        conf.loudness_range = 11.0;
+
+        conf.loudness_target = Some(0.0);
+        assert!(conf.validate().is_err());
+        conf.loudness_target = None;
+        conf.loudnorm_two_pass = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: two-pass loudness normalization needs a loudness target."
+            ))
+        );
+        conf.loudnorm_two_pass = false;
+        conf.loudness_target = Some(-23.0);
+        conf.peak_normalization = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: peak and loudness normalization cannot be combined."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_reverb() {
+        let mut conf = selection_config();
+        for reverb in &[ReverbSettings::SmallRoom, ReverbSettings::Hall] {
+            conf.reverb = Some(reverb.clone());
+            assert!(conf.validate().is_ok());
+        }
+
+        let custom = |delays: Vec<f64>, decays: Vec<f64>| ReverbSettings::Custom {
+            in_gain: 0.6,
+            out_gain: 0.3,
+            delays,
+            decays,
+        };
+        conf.reverb = Some(custom(vec![1000.0, 1800.0], vec![0.3, 0.25]));
+        assert!(conf.validate().is_ok());
+        conf.reverb = Some(custom(vec![1000.0, 1800.0], vec![0.3]));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: reverb needs one decay per delay, got 2 delays and 1 decays."
+            ))
+        );
+        conf.reverb = Some(custom(Vec::new(), Vec::new()));
+        assert!(conf.validate().is_err());
+        conf.reverb = Some(custom(vec![0.0], vec![0.3]));
+        assert!(conf.validate().is_err());
+        conf.reverb = Some(custom(vec![100_000.0], vec![0.3]));
+        assert!(conf.validate().is_err());
+        conf.reverb = Some(custom(vec![100.0], vec![1.5]));
+        assert!(conf.validate().is_err());
+        // unreadable numbers typed in
+        conf.reverb = Some(custom(vec![f64::NAN], vec![0.3]));
+        assert!(conf.validate().is_err());
+        conf.reverb = Some(ReverbSettings::Custom {
+            in_gain: 0.0,
+            out_gain: 0.3,
+            delays: vec![100.0],
+            decays: vec![0.3],
+        });
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: reverb gains must be above 0 and at most 1."
+            ))
+        );
+
+        conf.reverb = Some(ReverbSettings::Hall);
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_chapters() {
+        let chapter = |start: i64, end: i64| chapters::Chapter {
+            start: Duration::seconds(start),
+            end: Duration::seconds(end),
+            title: String::from("Part"),
+        };
+        let mut conf = selection_config();
+        conf.write_chapters = Some(vec![chapter(0, 5), chapter(5, 8)]);
+        assert!(conf.validate().is_ok());
+
+        conf.write_chapters = Some(vec![chapter(5, 5)]);
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: chapter \"Part\" must end after it starts."
+            ))
+        );
+
+        conf.write_chapters = Some(vec![chapter(0, 5)]);
+        conf.demux_streams = true;
+        assert!(conf.validate().is_err());
+        conf.write_chapters = Some(Vec::new());
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_first_sound() {
+        let mut conf = selection_config();
+        conf.trim_to_first_sound = true;
+        assert!(conf.validate().is_ok());
+        conf.ignore_audio = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: audio is ignored, the first sound cannot be found."
+            ))
+        );
+        conf.ignore_audio = false;
+        conf.process_whole_file = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_silence_speedup() {
+        let mut conf = selection_config();
+        conf.silence_speedup = Some(SilenceSpeedup::default());
+        assert!(conf.validate().is_ok());
+
+        for &(factor, noise_db, min_silence) in &[
+            (1.0, -35.0, 0.5),
+            (150.0, -35.0, 0.5),
+            (4.0, 0.0, 0.5),
+            (4.0, -35.0, 0.0),
+        ] {
+            conf.silence_speedup = Some(SilenceSpeedup {
+                factor,
+                noise_db,
+                min_silence,
+            });
+            assert!(conf.validate().is_err());
+        }
+
+        conf.silence_speedup = Some(SilenceSpeedup::default());
+        conf.demux_streams = true;
+        conf.video_codec = Some(String::from("copy"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: speeding through silences cannot be combined with: stream demuxing, stream copy."
+            ))
+        );
+
+        conf.demux_streams = false;
+        conf.video_codec = None;
+        // the mix is sped up as a whole, a single kept track as well
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, -6.0)]);
+        assert!(conf.validate().is_ok());
+        conf.downmix_tracks = None;
+        let track = |stream_index| TrackSpec {
+            stream_index,
+            filters: FilterChain::Configured,
+            keep: true,
+        };
+        conf.audio_tracks = vec![track(1)];
+        assert!(conf.validate().is_ok());
+        conf.audio_tracks.push(track(2));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: speeding through silences cannot be combined with: several audio tracks."
+            ))
+        );
+        conf.audio_tracks.clear();
+
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn settings_from_env() {
+        let _guard = ENV_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for var in &[FFMPEG_PATH_VAR, TEMP_DIR_VAR, LOGLEVEL_VAR] {
+            env::remove_var(var);
+        }
+        assert_eq!(Config::from_env(), Config::default());
+
+        env::set_var(FFMPEG_PATH_VAR, "/opt/ffmpeg/bin/ffmpeg");
+        env::set_var(TEMP_DIR_VAR, "/scratch");
+        env::set_var(LOGLEVEL_VAR, " Warning ");
+        let conf = Config::from_env();
+        assert_eq!(
+            conf.ffmpeg_path,
+            Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg"))
+        );
+        assert_eq!(conf.temp_dir, Some(PathBuf::from("/scratch")));
+        assert_eq!(conf.loglevel.as_deref(), Some("warning"));
+        assert!(Config {
+            process_whole_file: true,
+            ..conf
+        }
+        .validate()
+        .is_ok());
+
+        // layered over other settings, empty values are ignored
+        env::set_var(TEMP_DIR_VAR, "");
+        let mut project = selection_config();
+        project.output_file = String::from("talk_cut.mp4");
+        project.temp_dir = Some(PathBuf::from("/var/tmp"));
+        let conf = project.with_env();
+        assert_eq!(conf.output_file, "talk_cut.mp4");
+        assert_eq!(conf.temp_dir, Some(PathBuf::from("/var/tmp")));
+        assert_eq!(
+            conf.ffmpeg_path,
+            Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg"))
+        );
+
+        env::set_var(LOGLEVEL_VAR, "chatty");
+        assert!(Config::from_env().validate().is_err());
+
+        for var in &[FFMPEG_PATH_VAR, TEMP_DIR_VAR, LOGLEVEL_VAR] {
+            env::remove_var(var);
+        }
+    }
+
+    #[test]
+    fn machine_settings_are_not_saved() {
+        let mut conf = selection_config();
+        conf.ffmpeg_path = Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg"));
+        conf.loglevel = Some(String::from("error"));
+        let json = serde_json::to_string(&conf).unwrap();
+        assert!(!json.contains("ffmpeg_path"));
+        let loaded: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.ffmpeg_path, None);
+        assert_eq!(loaded.loglevel, None);
+    }
+
+    #[test]
+    fn validate_encoder_preset() {
+        let mut conf = selection_config();
+        for preset in &ENCODER_PRESETS {
+            conf.encoder_preset = Some(String::from(*preset));
+            assert!(conf.validate().is_ok());
+        }
+        conf.encoder_preset = Some(String::from("Slow"));
+        assert!(conf.validate().is_err());
+        conf.encoder_preset = Some(String::from("p7"));
+        assert!(conf.validate().is_err());
+
+        conf.encoder_preset = Some(String::from("veryslow"));
+        conf.video_codec = Some(String::from("libx265"));
+        assert!(conf.validate().is_ok());
+        for codec in &["h264_nvenc", "hevc_vaapi", "h264_qsv", "copy"] {
+            conf.video_codec = Some(String::from(*codec));
+            assert!(conf.validate().is_err(), "{}", codec);
+        }
+
+        conf.video_codec = None;
+        conf.ignore_video = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_threads() {
+        let mut conf = selection_config();
+        conf.threads = Some(4);
+        assert!(conf.validate().is_ok());
+        conf.threads = Some(0);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_nice_level() {
+        let mut conf = selection_config();
+        conf.nice_level = Some(19);
+        assert!(conf.validate().is_ok());
+        conf.nice_level = Some(-20);
+        assert!(conf.validate().is_ok());
+        conf.nice_level = Some(20);
+        assert!(conf.validate().is_err());
+        conf.nice_level = Some(-21);
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_volume_envelope() {
+        let mut conf = selection_config();
+        conf.volume_envelope = vec![
+            (Duration::seconds(1), 0.0),
+            (Duration::seconds(2), -12.0),
+            // a sudden change
+            (Duration::seconds(2), 0.0),
+        ];
+        assert!(conf.validate().is_ok());
+        conf.volume_envelope.push((Duration::seconds(1), 3.0));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: volume envelope points must be in time order."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_rotation_angle() {
+        let mut conf = selection_config();
+        for &angle in &[0, 90, 180, 270] {
+            conf.operation = Operation::FixRotation { set: Some(angle) };
+            assert!(conf.validate().is_ok());
+        }
+        conf.operation = Operation::FixRotation { set: None };
+        assert!(conf.validate().is_ok());
+        conf.operation = Operation::FixRotation { set: Some(45) };
+        assert!(conf.validate().is_err());
+        conf.operation = Operation::FixRotation { set: Some(360) };
+        assert!(conf.validate().is_err());
+
+        // stripping agrees with removing or zeroing the tag only
+        conf.strip_rotation_metadata = true;
+        conf.operation = Operation::FixRotation { set: Some(90) };
+        assert!(conf.validate().is_err());
+        conf.operation = Operation::FixRotation { set: Some(0) };
+        assert!(conf.validate().is_ok());
+        conf.operation = Operation::FixRotation { set: None };
+        assert!(conf.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_spectrogram() {
+        let mut conf = selection_config();
+        conf.operation = Operation::Analyze;
+        assert!(conf.validate().is_err());
+        conf.spectrogram = Some((1024, 512));
+        assert!(conf.validate().is_ok());
+        conf.spectrogram = Some((0, 512));
+        assert!(conf.validate().is_err());
+        conf.spectrogram = Some((1024, 512));
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_err());
+    }
+
+    #[test]
+    fn validate_smart_cut() {
+        let mut conf = selection_config();
+        conf.operation = Operation::SmartCut;
+        assert!(conf.validate().is_ok());
+
+        conf.volume_change = 3.0;
+        conf.lut = Some(PathBuf::from("film.cube"));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: smart cut copies most of the input but the following filters are enabled: 3D LUT, volume change."
+            ))
+        );
+        conf.volume_change = 0.0;
+        conf.lut = None;
+
+        conf.video_codec = Some(String::from("libx264"));
+        assert!(conf.validate().is_err());
+        conf.video_codec = None;
+        conf.process_whole_file = true;
+        assert!(conf.validate().is_err());
+        conf.process_whole_file = false;
+        conf.ignore_video = true;
+        assert!(conf.validate().is_err());
+        conf.ignore_video = false;
+        conf.trim_to_first_sound = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: smart cut cannot trim the selection to its first sound."
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_contact_sheet() {
+        let mut conf = selection_config();
+        conf.operation = Operation::Analyze;
+        conf.contact_sheet = Some((4, 3));
+        assert!(conf.validate().is_ok());
+        conf.contact_sheet = Some((4, 0));
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: contact sheet grid must be made of positive numbers."
+            ))
+        );
+        conf.contact_sheet = Some((4, 3));
+        conf.contact_sheet_tile_width = Some(0);
+        assert!(conf.validate().is_err());
+        conf.contact_sheet_tile_width = Some(160);
+        assert!(conf.validate().is_ok());
+
+        // only the analyses needing it care for the audio
+        conf.contact_sheet = Some((4, 3));
+        conf.ignore_audio = true;
+        assert!(conf.validate().is_ok());
+        conf.ignore_audio = false;
+        conf.ignore_video = true;
+        assert_eq!(
+            conf.validate(),
+            Err(String::from(
+                "Error: video is ignored but a contact sheet is requested."
+            ))
+        );
+    }
+
+    #[test]
+    fn check_duration_boundaries() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(1);
+        conf.to_time = Duration::seconds(10);
+
+        // selection ends right at the end of the file
+        assert_eq!(
+            conf.check_duration(Some(Duration::seconds(10))),
+            Ok(Vec::new())
+        );
+        assert_eq!(
+            conf.check_duration(Some(Duration::seconds(60))),
+            Ok(Vec::new())
+        );
+
+        // within the probe imprecision
+        assert_eq!(
+            conf.check_duration(Some(Duration::milliseconds(9_900))),
+            Ok(Vec::new())
+        );
+
+        let warnings = conf
+            .check_duration(Some(Duration::milliseconds(9_899)))
+            .unwrap();
+        assert_eq!(
+            warnings,
+            vec!["Selection ends at 10.000s, past the end of the input file (9.899s): the output is truncated to 8.899s."]
+        );
+
+        // start still inside the file
+        assert_eq!(
+            conf.check_duration(Some(Duration::milliseconds(1_001)))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            conf.check_duration(Some(Duration::seconds(1))),
+            Err(String::from(
+                "Error: selection starts at 1.000s but the input file lasts 1.000s."
+            ))
+        );
+        assert!(conf.check_duration(Some(Duration::zero())).is_err());
+    }
+
+    #[test]
+    fn whole_file_ignores_selection() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(10);
+        conf.to_time = Duration::seconds(20);
+        conf.normalization_measure_range = Some((Duration::seconds(30), Duration::seconds(40)));
+        conf.process_whole_file = true;
+
+        assert!(conf.validate().is_ok());
+        assert_eq!(
+            conf.check_duration(Some(Duration::seconds(5))),
+            Ok(Vec::new())
+        );
+        assert_eq!(conf.check_duration(None), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn padding() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(5);
+        conf.to_time = Duration::seconds(10);
+
+        let padded = conf.padded(Some(Duration::seconds(60)));
+        assert_eq!(padded.from_time, Duration::seconds(5));
+        assert_eq!(padded.to_time, Duration::seconds(10));
+
+        conf.pad_before = Some(Duration::milliseconds(500));
+        conf.pad_after = Some(Duration::milliseconds(250));
+        let padded = conf.padded(Some(Duration::seconds(60)));
+        assert_eq!(padded.from_time, Duration::milliseconds(4_500));
+        assert_eq!(padded.to_time, Duration::milliseconds(10_250));
+
+        // unknown duration: only the start is clamped
+        let padded = conf.padded(None);
+        assert_eq!(padded.to_time, Duration::milliseconds(10_250));
+    }
+
+    #[test]
+    fn padding_clamping() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::milliseconds(300);
+        conf.to_time = Duration::seconds(10);
+        conf.pad_before = Some(Duration::seconds(1));
+        conf.pad_after = Some(Duration::seconds(1));
+
+        let padded = conf.padded(Some(Duration::milliseconds(10_400)));
+        assert_eq!(padded.from_time, Duration::zero());
+        assert_eq!(padded.to_time, Duration::milliseconds(10_400));
+
+        // padding never shrinks a selection already past the end
+        let padded = conf.padded(Some(Duration::seconds(8)));
+        assert_eq!(padded.to_time, Duration::seconds(10));
+
+        // negative padding is ignored
+        conf.pad_before = Some(Duration::seconds(-1));
+        assert_eq!(conf.padded(None).from_time, Duration::milliseconds(300));
+    }
+
+    #[test]
+    fn check_duration_far_past_end() {
+        // a two hour clip requested from a five minute file
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(60);
+        conf.to_time = Duration::hours(2);
+
+        let warnings = conf.check_duration(Some(Duration::minutes(5))).unwrap();
+        assert_eq!(
+            warnings,
+            vec!["Selection ends at 7200.000s, past the end of the input file (300.000s): the output is truncated to 240.000s."]
+        );
+
+        conf.strict = true;
+        assert_eq!(
+            conf.check_duration(Some(Duration::minutes(5))),
+            Err(format!("Error: {}", warnings[0]))
+        );
+    }
+
+    #[test]
+    fn check_duration_strict() {
+        let mut conf = selection_config();
+        conf.from_time = Duration::seconds(1);
+        conf.to_time = Duration::seconds(10);
+        conf.strict = true;
+
+        assert!(conf.check_duration(Some(Duration::seconds(10))).is_ok());
+        assert!(conf.check_duration(Some(Duration::seconds(5))).is_err());
+    }
+
+    #[test]
+    fn check_duration_unknown() {
+        let conf = selection_config();
+        let warnings = conf.check_duration(None).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unknown"));
     }
 
     #[test]
@@ -0,0 +1,134 @@
+use std::{env, fs, io, path::Path, process::Command};
+
+use log::{debug, LevelFilter};
+
+/// Variable holding `env_logger` filters such as `media_cutter::processing=trace`,
+/// which then take over the verbosity flags.
+pub const LOG_FILTER_VAR: &str = "RUST_LOG";
+/// File of the state directory present while verbose logs are chosen in the
+/// interface, see `recovery::state_dir`.
+pub const VERBOSE_LOG_FILE: &str = "verbose-log";
+/// Long form of `-v`, each occurrence raising the verbosity once.
+pub const VERBOSE_FLAG: &str = "--verbose";
+
+/// Verbosity `arg` asks for: 1 for `-v` and `--verbose`, 2 for `-vv` and so
+/// on. `None` for any other argument.
+pub fn verbosity_flag(arg: &str) -> Option<usize> {
+    if arg == VERBOSE_FLAG {
+        return Some(1);
+    }
+    let vs = arg.strip_prefix('-')?;
+    if !vs.is_empty() && vs.bytes().all(|byte| byte == b'v') {
+        Some(vs.len())
+    } else {
+        None
+    }
+}
+
+/// Events shown at a verbosity: warnings and errors by default, the
+/// durations and exit codes with `-v`, the launched commands with `-vv`.
+pub fn level(verbosity: usize) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Log to stderr from now on, up to `level` unless `RUST_LOG` sets filters.
+pub fn init(level: LevelFilter) {
+    let mut builder = env_logger::Builder::new();
+    match env_filters() {
+        Some(filters) => {
+            builder.parse_filters(&filters).init();
+        }
+        None => {
+            // everything reaches the logger, `set_level` picks what it prints
+            builder.filter_level(LevelFilter::Trace).init();
+            set_level(level);
+        }
+    }
+}
+
+/// Filters `RUST_LOG` sets, `set_level` must not override them.
+pub fn env_filters() -> Option<String> {
+    env::var(LOG_FILTER_VAR).ok()
+}
+
+/// Change the level of the events logged, e.g. from the interface.
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+/// Whether verbose logs were chosen in the interface, see `save_verbose`.
+pub fn verbose_chosen(state_dir: &Path) -> bool {
+    state_dir.join(VERBOSE_LOG_FILE).exists()
+}
+
+/// Remember in `state_dir` whether verbose logs are chosen.
+pub fn save_verbose(state_dir: &Path, verbose: bool) -> Result<(), String> {
+    let verbose_log_file = state_dir.join(VERBOSE_LOG_FILE);
+    let saved = if verbose {
+        fs::create_dir_all(state_dir).and_then(|_| fs::write(&verbose_log_file, ""))
+    } else {
+        match fs::remove_file(&verbose_log_file) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            removed => removed,
+        }
+    };
+    saved.map_err(|e| {
+        format!(
+            "Could not write {}.\nError: {}",
+            verbose_log_file.display(),
+            e
+        )
+    })
+}
+
+/// Commands logged at debug level as they are about to be launched.
+pub trait LogLaunch {
+    /// Log the program and its arguments, quoted, then hand the command back.
+    fn logged(&mut self) -> &mut Self;
+}
+
+impl LogLaunch for Command {
+    fn logged(&mut self) -> &mut Self {
+        debug!("Running {:?}", self);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verbosity_flags() {
+        assert_eq!(verbosity_flag("-v"), Some(1));
+        assert_eq!(verbosity_flag("-vv"), Some(2));
+        assert_eq!(verbosity_flag("--verbose"), Some(1));
+        assert_eq!(verbosity_flag("-"), None);
+        assert_eq!(verbosity_flag("-vx"), None);
+        assert_eq!(verbosity_flag("--resume"), None);
+        assert_eq!(verbosity_flag("v"), None);
+
+        assert_eq!(level(0), LevelFilter::Warn);
+        assert_eq!(level(2), LevelFilter::Debug);
+        assert_eq!(level(5), LevelFilter::Trace);
+    }
+    #[test]
+    fn verbose_preference() {
+        let state_dir = env::temp_dir().join("media_cutter_logging_test_verbose");
+        let _ = fs::remove_dir_all(&state_dir);
+        assert!(!verbose_chosen(&state_dir));
+        // nothing to forget yet
+        assert_eq!(save_verbose(&state_dir, false), Ok(()));
+
+        assert_eq!(save_verbose(&state_dir, true), Ok(()));
+        assert!(verbose_chosen(&state_dir));
+        assert_eq!(save_verbose(&state_dir, false), Ok(()));
+        assert!(!verbose_chosen(&state_dir));
+        let _ = fs::remove_dir_all(&state_dir);
+    }
+}
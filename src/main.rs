@@ -1,33 +1,152 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::env::args;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
 use chrono::Duration;
 use gdk_pixbuf::Pixbuf;
 use gio::prelude::*;
 use gtk::prelude::*;
 use gtk::{MenuItemExt, MessageDialog};
+use log::{info, warn, LevelFilter};
 
 use media_cutter::*;
 
+mod playback;
+mod waveform_view;
+
+use crate::playback::Player;
+use crate::waveform_view::WaveformView;
+
+// delay between two audio snippets while dragging a cut point
+const SCRUB_INTERVAL_MS: u32 = 150;
+const SCRUB_SNIPPET_MS: i64 = 300;
+
+// resolution of the extracted peaks, zooming further shows the same buckets wider
+const PEAK_BUCKETS_PER_SECOND: f64 = 200.0;
+const MIN_PEAK_BUCKETS: usize = 2000;
+
+// encoder preset combo entry leaving the encoder default
+const DEFAULT_PRESET_ID: &str = "default";
+// codec combo entry letting ffmpeg pick the codec from the output extension
+const DEFAULT_CODEC_ID: &str = "default";
+// MP3 mode combo entries, see ui/main.glade
+const MP3_VBR_ID: &str = "vbr";
+const MP3_CBR_ID: &str = "cbr";
+// AAC combo entries, see ui/main.glade
+const AAC_FDK_ID: &str = "fdk";
+const AAC_NATIVE_ID: &str = "native";
+const AAC_VBR_ID: &str = "vbr";
+const AAC_CBR_ID: &str = "cbr";
+// preset combo title and section header, built-in presets follow with their index
+const NO_PRESET_ID: &str = "none";
+const BUILTIN_HEADER_ID: &str = "builtin";
+const BUILTIN_PRESET_PREFIX: &str = "builtin_";
+// channel combo entries with a meaning of their own, see ui/main.glade
+const NO_CHANNEL_OP_ID: &str = "none";
+const BALANCE_ID: &str = "balance";
+// reverb combo entries, see ui/main.glade
+const NO_REVERB_ID: &str = "none";
+const REVERB_SMALL_ROOM_ID: &str = "small_room";
+const REVERB_HALL_ID: &str = "hall";
+const REVERB_CUSTOM_ID: &str = "custom";
+
+// shown in the input entry for network inputs, and to stop probing them
+const NETWORK_ICON: &str = "network-server-symbolic";
+const STOP_PROBE_ICON: &str = "process-stop-symbolic";
+
+// range of the gain sliders of mixed audio tracks
+const MIN_TRACK_GAIN_DB: f64 = -30.0;
+const MAX_TRACK_GAIN_DB: f64 = 30.0;
+
+// skip the jobs an interrupted batch completed, see `queue::Manifest`
+const RESUME_FLAG: &str = "--resume";
+// print the environment for bug reports instead of starting
+const DIAGNOSTICS_FLAG: &str = "--diagnostics";
+// how often the autosave looks for changes to write, see `recovery::Debouncer`
+const AUTOSAVE_POLL_MS: u32 = 500;
+
 fn main() {
+    if args().any(|arg| arg == DIAGNOSTICS_FLAG) {
+        // the report itself, not a log event
+        print!("{}", diagnostics());
+        return;
+    }
+
+    // ours, GTK rejects the options it does not know
+    let mut resume = false;
+    let mut verbosity = 0;
+    let mut gtk_args = Vec::new();
+    for arg in args() {
+        if arg == RESUME_FLAG {
+            resume = true;
+        } else if let Some(count) = logging::verbosity_flag(&arg) {
+            verbosity += count;
+        } else {
+            gtk_args.push(arg);
+        }
+    }
+    let log_level = logging::level(verbosity);
+    logging::init(log_level);
+
+    // a task on the command line runs without the window
+    match cli::parse(gtk_args.get(1..).unwrap_or(&[])) {
+        Ok(None) => {}
+        Ok(Some(invocation)) => {
+            match cli::run(&invocation) {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        eprintln!("{}", warning);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    }
+
     let application = gtk::Application::new(
         "eu.fried-world.media_cutter",
         gio::ApplicationFlags::empty(),
     )
     .expect("Initialization failed...");
 
-    application.connect_startup(|app| {
-        build_ui(app);
+    application.connect_startup(move |app| {
+        build_ui(app, resume, log_level);
     });
     application.connect_activate(|_| {});
 
-    application.run(&args().collect::<Vec<_>>());
+    application.run(&gtk_args);
 }
 
-pub fn build_ui(application: &gtk::Application) {
-    println!(
-        "=> Gtk {}.{} detected",
+// what to paste into bug reports, see `diagnostics::Report`
+fn diagnostics() -> String {
+    let mut report = diagnostics::Report::collect();
+    report.gtk_version = Some(format!(
+        "{}.{}.{}",
+        gtk::get_major_version(),
+        gtk::get_minor_version(),
+        gtk::get_micro_version()
+    ));
+    report.to_string()
+}
+
+// `log_level` is the one of the command line, see `logging::level`
+pub fn build_ui(application: &gtk::Application, resume: bool, log_level: LevelFilter) {
+    info!(
+        "Gtk {}.{} detected",
         gtk::get_major_version(),
         gtk::get_minor_version()
     );
@@ -35,7 +154,7 @@ pub fn build_ui(application: &gtk::Application) {
     let glade_src = include_str!("../ui/main.glade");
     let builder = gtk::Builder::new_from_string(glade_src);
 
-    let conf = Rc::new(RefCell::new(Config::default()));
+    let conf = Rc::new(RefCell::new(Config::from_env()));
 
     let window: gtk::ApplicationWindow = get_widget!(builder, "main_window");
     window.set_application(application);
@@ -46,56 +165,350 @@ pub fn build_ui(application: &gtk::Application) {
     dialog.set_logo(Some(&logo_buf));
 
     let quit_menu_item: gtk::MenuItem = get_widget!(builder, "quit_menu_item");
+    let undo_menu_item: gtk::MenuItem = get_widget!(builder, "undo_menu_item");
+    let redo_menu_item: gtk::MenuItem = get_widget!(builder, "redo_menu_item");
     let about_menu_item: gtk::MenuItem = get_widget!(builder, "about_menu_item");
+    let verbose_log_menu_item: gtk::CheckMenuItem = get_widget!(builder, "verbose_log_menu_item");
+    let smart_cut_menu_item: gtk::MenuItem = get_widget!(builder, "smart_cut_menu_item");
+    let fix_rotation_menu_item: gtk::MenuItem = get_widget!(builder, "fix_rotation_menu_item");
+    let spectrogram_menu_item: gtk::MenuItem = get_widget!(builder, "spectrogram_menu_item");
+    let contact_sheet_menu_item: gtk::MenuItem = get_widget!(builder, "contact_sheet_menu_item");
+    let visualize_menu_item: gtk::MenuItem = get_widget!(builder, "visualize_menu_item");
+    let still_image_menu_item: gtk::MenuItem = get_widget!(builder, "still_image_menu_item");
+    let thumbnail_menu_item: gtk::MenuItem = get_widget!(builder, "thumbnail_menu_item");
+    let file_info_menu_item: gtk::MenuItem = get_widget!(builder, "file_info_menu_item");
+    let export_cut_list_menu_item: gtk::MenuItem =
+        get_widget!(builder, "export_cut_list_menu_item");
+    let import_cut_list_menu_item: gtk::MenuItem =
+        get_widget!(builder, "import_cut_list_menu_item");
+    let open_project_menu_item: gtk::MenuItem = get_widget!(builder, "open_project_menu_item");
+    let save_project_menu_item: gtk::MenuItem = get_widget!(builder, "save_project_menu_item");
+    let save_project_as_menu_item: gtk::MenuItem =
+        get_widget!(builder, "save_project_as_menu_item");
+    let scrub_menu_item: gtk::CheckMenuItem = get_widget!(builder, "scrub_menu_item");
+    let preview_loop_menu_item: gtk::CheckMenuItem = get_widget!(builder, "preview_loop_menu_item");
+    let preview_full_menu_item: gtk::CheckMenuItem = get_widget!(builder, "preview_full_menu_item");
+    let source_info_menu_item: gtk::CheckMenuItem = get_widget!(builder, "source_info_menu_item");
+    let fragmented_mp4_menu_item: gtk::CheckMenuItem =
+        get_widget!(builder, "fragmented_mp4_menu_item");
+    let stream_copy_menu_item: gtk::CheckMenuItem = get_widget!(builder, "stream_copy_menu_item");
+    let fix_timestamps_menu_item: gtk::CheckMenuItem =
+        get_widget!(builder, "fix_timestamps_menu_item");
+    let tolerant_input_menu_item: gtk::CheckMenuItem =
+        get_widget!(builder, "tolerant_input_menu_item");
+    let preserve_channels_menu_item: gtk::CheckMenuItem =
+        get_widget!(builder, "preserve_channels_menu_item");
+    let portable_names_menu_item: gtk::CheckMenuItem =
+        get_widget!(builder, "portable_names_menu_item");
+    let copy_audio_menu_item: gtk::CheckMenuItem = get_widget!(builder, "copy_audio_menu_item");
 
     let select_input_button: gtk::Button = get_widget!(builder, "select_input_button");
+    let cue_split_button: gtk::Button = get_widget!(builder, "cue_split_button");
     let input_file_entry: gtk::Entry = get_widget!(builder, "input_file_entry");
     let select_output_button: gtk::Button = get_widget!(builder, "select_output_button");
     let output_file_entry: gtk::Entry = get_widget!(builder, "output_file_entry");
+    let additional_outputs_entry: gtk::Entry = get_widget!(builder, "additional_outputs_entry");
+    let image_sequence_check: gtk::CheckButton = get_widget!(builder, "image_sequence_check");
+    let sequence_fps_spin: gtk::SpinButton = get_widget!(builder, "sequence_fps_spin");
+    let sequence_fps_adj: gtk::Adjustment = get_widget!(builder, "sequence_fps_adj");
+    let audio_tracks_box: gtk::Box = get_widget!(builder, "audio_tracks_box");
+    // audio tracks listed for inputs holding several
+    let mix_tracks_check: gtk::CheckButton = get_widget!(builder, "mix_tracks_check");
+    let audio_track_rows: Rc<RefCell<Vec<AudioTrackRow>>> = Rc::new(RefCell::new(Vec::new()));
+    // shown for inputs holding several video streams, see fill_video_streams
+    let video_stream_combo: gtk::ComboBoxText = get_widget!(builder, "video_stream_combo");
 
     let ignore_video_check: gtk::CheckButton = get_widget!(builder, "ignore_video_check");
     let ignore_audio_check: gtk::CheckButton = get_widget!(builder, "ignore_audio_check");
     let peak_normalization_check: gtk::CheckButton =
         get_widget!(builder, "peak_normalization_check");
     let overidde_existing_check: gtk::CheckButton = get_widget!(builder, "overidde_check");
+    let sample_accurate_check: gtk::CheckButton = get_widget!(builder, "sample_accurate_check");
+    let create_output_dirs_check: gtk::CheckButton =
+        get_widget!(builder, "create_output_dirs_check");
+    let strip_rotation_check: gtk::CheckButton = get_widget!(builder, "strip_rotation_check");
+    let low_priority_check: gtk::CheckButton = get_widget!(builder, "low_priority_check");
+    let preset_combo: gtk::ComboBoxText = get_widget!(builder, "preset_combo");
+    preset_combo.append(NO_PRESET_ID, "Presets");
+    preset_combo.append(BUILTIN_HEADER_ID, "— Built-in —");
+    for (index, preset) in presets::Preset::builtin().iter().enumerate() {
+        preset_combo.append(
+            format!("{}{}", BUILTIN_PRESET_PREFIX, index).as_str(),
+            preset.name,
+        );
+    }
+    preset_combo.set_active_id(NO_PRESET_ID);
+    let video_codec_combo: gtk::ComboBoxText = get_widget!(builder, "video_codec_combo");
+    fill_codec_combo(
+        &video_codec_combo,
+        &containers::VIDEO_CODECS,
+        "Default video codec",
+    );
+    let audio_codec_combo: gtk::ComboBoxText = get_widget!(builder, "audio_codec_combo");
+    fill_codec_combo(
+        &audio_codec_combo,
+        &containers::AUDIO_CODECS,
+        "Default audio codec",
+    );
+    let mp3_quality_box: gtk::Box = get_widget!(builder, "mp3_quality_box");
+    let mp3_mode_combo: gtk::ComboBoxText = get_widget!(builder, "mp3_mode_combo");
+    let mp3_quality_adj: gtk::Adjustment = get_widget!(builder, "mp3_quality_adj");
+    let mp3_joint_stereo_check: gtk::CheckButton = get_widget!(builder, "mp3_joint_stereo_check");
+    let aac_options_box: gtk::Box = get_widget!(builder, "aac_options_box");
+    let aac_encoder_combo: gtk::ComboBoxText = get_widget!(builder, "aac_encoder_combo");
+    let aac_profile_combo: gtk::ComboBoxText = get_widget!(builder, "aac_profile_combo");
+    let aac_mode_combo: gtk::ComboBoxText = get_widget!(builder, "aac_mode_combo");
+    let aac_quality_adj: gtk::Adjustment = get_widget!(builder, "aac_quality_adj");
+    // not fatal: without a listing, FDK is offered and ffmpeg falls back by itself
+    let has_fdk_aac = capabilities::Capabilities::detect(&conf.borrow())
+        .map(|capabilities| capabilities.has_encoder(AacEncoder::Fdk.codec()))
+        .unwrap_or(true);
+    if !has_fdk_aac {
+        aac_encoder_combo.set_sensitive(false);
+        aac_encoder_combo.set_tooltip_text("Installed ffmpeg is built without libfdk_aac");
+    }
+    let encoder_preset_combo: gtk::ComboBoxText = get_widget!(builder, "encoder_preset_combo");
+    encoder_preset_combo.append(DEFAULT_PRESET_ID, "Default preset");
+    for preset in &ENCODER_PRESETS {
+        encoder_preset_combo.append(*preset, preset);
+    }
+    encoder_preset_combo.set_active_id(DEFAULT_PRESET_ID);
     let high_pass_check: gtk::CheckButton = get_widget!(builder, "high_pass_check");
     let low_pass_check: gtk::CheckButton = get_widget!(builder, "low_pass_check");
+    let channel_ops_combo: gtk::ComboBoxText = get_widget!(builder, "channel_ops_combo");
+    let balance_spin: gtk::SpinButton = get_widget!(builder, "balance_spin");
+    let vocal_removal_check: gtk::CheckButton = get_widget!(builder, "vocal_removal_check");
+    let vocal_removal_scale: gtk::Scale = get_widget!(builder, "vocal_removal_scale");
+    let reverb_combo: gtk::ComboBoxText = get_widget!(builder, "reverb_combo");
+    let reverb_expander: gtk::Expander = get_widget!(builder, "reverb_expander");
+    let reverb_delays_entry: gtk::Entry = get_widget!(builder, "reverb_delays_entry");
+    let reverb_decays_entry: gtk::Entry = get_widget!(builder, "reverb_decays_entry");
     let noise_reduc_check: gtk::CheckButton = get_widget!(builder, "noise_reduc_check");
+    let silence_speedup_check: gtk::CheckButton = get_widget!(builder, "silence_speedup_check");
+    let silence_speedup_spin: gtk::SpinButton = get_widget!(builder, "silence_speedup_spin");
 
     let noise_file_entry: gtk::Entry = get_widget!(builder, "noise_file_entry");
     let select_noise_button: gtk::Button = get_widget!(builder, "select_noise_button");
+    let record_noise_button: gtk::Button = get_widget!(builder, "record_noise_button");
 
     let high_pass_freq_adj: gtk::Adjustment = get_widget!(builder, "high_pass_freq_adj");
     let low_pass_freq_adj: gtk::Adjustment = get_widget!(builder, "low_pass_freq_adj");
+    let balance_adj: gtk::Adjustment = get_widget!(builder, "balance_adj");
+    let vocal_removal_adj: gtk::Adjustment = get_widget!(builder, "vocal_removal_adj");
+    let reverb_in_gain_adj: gtk::Adjustment = get_widget!(builder, "reverb_in_gain_adj");
+    let reverb_out_gain_adj: gtk::Adjustment = get_widget!(builder, "reverb_out_gain_adj");
+    let silence_speedup_adj: gtk::Adjustment = get_widget!(builder, "silence_speedup_adj");
     let start_secs_adj: gtk::Adjustment = get_widget!(builder, "start_secs_adj");
     let end_secs_adj: gtk::Adjustment = get_widget!(builder, "end_secs_adj");
+    let pad_before_adj: gtk::Adjustment = get_widget!(builder, "pad_before_adj");
+    let pad_after_adj: gtk::Adjustment = get_widget!(builder, "pad_after_adj");
     let volume_adj: gtk::Adjustment = get_widget!(builder, "volume_adj");
+    let volume_percent_adj: gtk::Adjustment = get_widget!(builder, "volume_percent_adj");
     let sox_amount_adj: gtk::Adjustment = get_widget!(builder, "sox_amount_adj");
+    let brightness_adj: gtk::Adjustment = get_widget!(builder, "brightness_adj");
+    let contrast_adj: gtk::Adjustment = get_widget!(builder, "contrast_adj");
+    let saturation_adj: gtk::Adjustment = get_widget!(builder, "saturation_adj");
+    let gamma_adj: gtk::Adjustment = get_widget!(builder, "gamma_adj");
+    let color_reset_button: gtk::Button = get_widget!(builder, "color_reset_button");
+    let lut_check: gtk::CheckButton = get_widget!(builder, "lut_check");
+    let timecode_check: gtk::CheckButton = get_widget!(builder, "timecode_check");
+    let timecode_source_combo: gtk::ComboBoxText = get_widget!(builder, "timecode_source_combo");
+    let lut_file_entry: gtk::Entry = get_widget!(builder, "lut_file_entry");
+    let select_lut_button: gtk::Button = get_widget!(builder, "select_lut_button");
 
     let process_button: gtk::Button = get_widget!(builder, "process_button");
     let preview_button: gtk::Button = get_widget!(builder, "preview_button");
+    let busy_spinner: gtk::Spinner = get_widget!(builder, "busy_spinner");
+    let process_progress_bar: gtk::ProgressBar = get_widget!(builder, "process_progress_bar");
+    let queue_stop_button: gtk::Button = get_widget!(builder, "queue_stop_button");
+
+    let files_grid: gtk::Grid = get_widget!(builder, "files_grid");
+    let stream_options_box: gtk::Box = get_widget!(builder, "stream_options_box");
+    let volume_box: gtk::Box = get_widget!(builder, "volume_box");
+    let volume_label: gtk::Label = get_widget!(builder, "volume_label");
+    let volume_db_spin: gtk::SpinButton = get_widget!(builder, "volume_db_spin");
+    let volume_percent_spin: gtk::SpinButton = get_widget!(builder, "volume_percent_spin");
+    let volume_percent_toggle: gtk::ToggleButton = get_widget!(builder, "volume_percent_toggle");
+    let pass_filters_box: gtk::Box = get_widget!(builder, "pass_filters_box");
+    let channel_ops_box: gtk::Box = get_widget!(builder, "channel_ops_box");
+    let reverb_box: gtk::Box = get_widget!(builder, "reverb_box");
+    let noise_reduction_box: gtk::Box = get_widget!(builder, "noise_reduction_box");
+    let silence_speedup_box: gtk::Box = get_widget!(builder, "silence_speedup_box");
+    let video_filters_expander: gtk::Expander = get_widget!(builder, "video_filters_expander");
+    let waveform_box: gtk::Box = get_widget!(builder, "waveform_box");
+    let time_box: gtk::ButtonBox = get_widget!(builder, "time_box");
+    let time_warning_label: gtk::Label = get_widget!(builder, "time_warning_label");
+    let start_time_box: gtk::Box = get_widget!(builder, "start_time_box");
+    let end_time_box: gtk::Box = get_widget!(builder, "end_time_box");
+    let padding_box: gtk::Box = get_widget!(builder, "padding_box");
+    let quick_trim_box: gtk::Box = get_widget!(builder, "quick_trim_box");
+    let quick_trim_entry: gtk::Entry = get_widget!(builder, "quick_trim_entry");
+    let whole_file_check: gtk::CheckButton = get_widget!(builder, "whole_file_check");
+    let first_sound_check: gtk::CheckButton = get_widget!(builder, "first_sound_check");
+    let start_secs_spin: gtk::SpinButton = get_widget!(builder, "start_secs_spin");
+    let end_secs_spin: gtk::SpinButton = get_widget!(builder, "end_secs_spin");
+    let high_pass_spin: gtk::SpinButton = get_widget!(builder, "high_pass_spin");
+    let low_pass_spin: gtk::SpinButton = get_widget!(builder, "low_pass_spin");
 
     let update_conf = Rc::new(clone!(conf,
                              input_file_entry,
                              output_file_entry,
+                             additional_outputs_entry,
                              start_secs_adj,
                              end_secs_adj,
+                             pad_before_adj,
+                             pad_after_adj,
+                             whole_file_check,
+                             first_sound_check,
                              ignore_audio_check,
                              ignore_video_check,
+                             video_stream_combo,
                              overidde_existing_check,
+                             sample_accurate_check,
+                             create_output_dirs_check,
+                             strip_rotation_check,
+                             video_codec_combo,
+                             audio_codec_combo,
+                             mp3_mode_combo,
+                             mp3_quality_adj,
+                             mp3_joint_stereo_check,
+                             aac_encoder_combo,
+                             aac_profile_combo,
+                             aac_mode_combo,
+                             aac_quality_adj,
+                             encoder_preset_combo,
+                             low_priority_check,
+                             preview_loop_menu_item,
+                             preview_full_menu_item,
+                             source_info_menu_item,
+                             fragmented_mp4_menu_item,
+                             stream_copy_menu_item,
+                             fix_timestamps_menu_item,
+                             tolerant_input_menu_item,
+                             preserve_channels_menu_item,
+                             portable_names_menu_item,
+                             copy_audio_menu_item,
                              high_pass_check,
                              low_pass_check,
                              high_pass_freq_adj,
                              low_pass_freq_adj,
+                             channel_ops_combo,
+                             balance_adj,
+                             vocal_removal_check,
+                             vocal_removal_adj,
+                             reverb_combo,
+                             reverb_in_gain_adj,
+                             reverb_out_gain_adj,
+                             reverb_delays_entry,
+                             reverb_decays_entry,
+                             silence_speedup_check,
+                             silence_speedup_adj,
                              peak_normalization_check,
-                             noise_file_entry => move || {
+                             noise_file_entry,
+                             brightness_adj,
+                             contrast_adj,
+                             saturation_adj,
+                             gamma_adj,
+                             lut_check,
+                             lut_file_entry,
+                             timecode_check,
+                             timecode_source_combo,
+                             image_sequence_check,
+                             sequence_fps_adj,
+                             audio_track_rows,
+                             mix_tracks_check => move || {
+        let previous_input = conf.borrow().input_file.clone();
         conf.borrow_mut().input_file = input_file_entry.get_text().unwrap();
         conf.borrow_mut().output_file = output_file_entry.get_text().unwrap();
+        let additional_outputs = parse_output_list(
+            &additional_outputs_entry.get_text().unwrap(),
+            &conf.borrow().additional_outputs,
+        );
+        conf.borrow_mut().additional_outputs = additional_outputs;
         conf.borrow_mut().from_time = Duration::milliseconds((start_secs_adj.get_value() * 1000.0) as i64);
         conf.borrow_mut().to_time = Duration::milliseconds((end_secs_adj.get_value() * 1000.0) as i64);
+        conf.borrow_mut().process_whole_file = whole_file_check.get_active();
+        conf.borrow_mut().trim_to_first_sound = first_sound_check.get_active();
+
+        let padding = |adj: &gtk::Adjustment| {
+            if adj.get_value() > 0.0 {
+                Some(Duration::milliseconds((adj.get_value() * 1000.0) as i64))
+            } else {
+                None
+            }
+        };
+        conf.borrow_mut().pad_before = padding(&pad_before_adj);
+        conf.borrow_mut().pad_after = padding(&pad_after_adj);
         conf.borrow_mut().ignore_video = ignore_video_check.get_active();
         conf.borrow_mut().ignore_audio = ignore_audio_check.get_active();
+        conf.borrow_mut().video_stream_index =
+            if video_stream_combo.get_visible() && !ignore_video_check.get_active() {
+                video_stream_combo.get_active_id().and_then(|id| id.parse().ok())
+            } else {
+                None
+            };
         conf.borrow_mut().allow_overidde = overidde_existing_check.get_active();
+        conf.borrow_mut().sample_accurate = sample_accurate_check.get_active();
+        conf.borrow_mut().create_output_dirs = create_output_dirs_check.get_active();
+        conf.borrow_mut().strip_rotation_metadata = strip_rotation_check.get_active();
+        conf.borrow_mut().video_codec = active_codec(&video_codec_combo);
+        conf.borrow_mut().audio_codec = active_codec(&audio_codec_combo);
+        let mp3 = if active_codec(&audio_codec_combo).as_deref() == Some("libmp3lame") {
+            let quality = if mp3_mode_combo.get_active_id().as_deref() == Some(MP3_CBR_ID) {
+                Mp3Quality::Cbr(mp3_quality_adj.get_value() as u32)
+            } else {
+                Mp3Quality::Vbr(mp3_quality_adj.get_value() as u8)
+            };
+            Some(Mp3Settings {
+                quality,
+                joint_stereo: mp3_joint_stereo_check.get_active(),
+            })
+        } else {
+            None
+        };
+        conf.borrow_mut().mp3 = mp3;
+        let aac_options = if active_codec(&audio_codec_combo).as_deref() == Some("aac") {
+            let encoder = if aac_encoder_combo.get_active_id().as_deref() == Some(AAC_FDK_ID) {
+                AacEncoder::Fdk
+            } else {
+                AacEncoder::Native
+            };
+            let vbr_mode = if aac_mode_combo.get_active_id().as_deref() == Some(AAC_VBR_ID) {
+                Some(aac_quality_adj.get_value() as u8)
+            } else {
+                None
+            };
+            Some(AacOptions {
+                encoder,
+                profile: aac_profile_from_id(aac_profile_combo.get_active_id().as_deref()),
+                vbr_mode,
+                bitrate: match vbr_mode {
+                    Some(_) => AacOptions::default().bitrate,
+                    None => aac_quality_adj.get_value() as u32,
+                },
+            })
+        } else {
+            None
+        };
+        conf.borrow_mut().aac_options = aac_options;
+        conf.borrow_mut().encoder_preset = encoder_preset_combo
+            .get_active_id()
+            .filter(|preset| preset != DEFAULT_PRESET_ID);
+        conf.borrow_mut().low_priority = low_priority_check.get_active();
+        conf.borrow_mut().preview_loop = preview_loop_menu_item.get_active();
+        conf.borrow_mut().preview_full = preview_full_menu_item.get_active();
+        conf.borrow_mut().embed_source_info = source_info_menu_item.get_active();
+        conf.borrow_mut().fragmented_mp4 = fragmented_mp4_menu_item.get_active();
+        conf.borrow_mut().stream_copy = stream_copy_menu_item.get_active();
+        conf.borrow_mut().fix_timestamps = fix_timestamps_menu_item.get_active();
+        conf.borrow_mut().tolerant_input = tolerant_input_menu_item.get_active();
+        conf.borrow_mut().preserve_channels = preserve_channels_menu_item.get_active();
+        conf.borrow_mut().filename_charset = if portable_names_menu_item.get_active() {
+            output_pattern::FilenameCharset::Portable
+        } else {
+            output_pattern::FilenameCharset::Unix
+        };
+        conf.borrow_mut().copy_audio_when_possible = copy_audio_menu_item.get_active();
         conf.borrow_mut().peak_normalization = peak_normalization_check.get_active();
         conf.borrow_mut().volume_change = volume_adj.get_value();
 
@@ -111,6 +524,38 @@ pub fn build_ui(application: &gtk::Application) {
             None
         };
 
+        conf.borrow_mut().channel_ops = channel_op_from_id(
+            channel_ops_combo.get_active_id().as_deref(),
+            balance_adj.get_value(),
+        );
+        conf.borrow_mut().vocal_removal = if vocal_removal_check.get_active() {
+            Some(vocal_removal_adj.get_value())
+        } else {
+            None
+        };
+        conf.borrow_mut().reverb = match reverb_combo.get_active_id().as_deref() {
+            Some(REVERB_SMALL_ROOM_ID) => Some(ReverbSettings::SmallRoom),
+            Some(REVERB_HALL_ID) => Some(ReverbSettings::Hall),
+            Some(REVERB_CUSTOM_ID) => Some(ReverbSettings::Custom {
+                in_gain: reverb_in_gain_adj.get_value(),
+                out_gain: reverb_out_gain_adj.get_value(),
+                delays: parse_number_list(&reverb_delays_entry.get_text().unwrap()),
+                decays: parse_number_list(&reverb_decays_entry.get_text().unwrap()),
+            }),
+            _ => None,
+        };
+        // detection settings without a control are kept as loaded
+        let silence_speedup = if silence_speedup_check.get_active() {
+            let current = conf.borrow().silence_speedup.clone().unwrap_or_default();
+            Some(SilenceSpeedup {
+                factor: silence_speedup_adj.get_value(),
+                ..current
+            })
+        } else {
+            None
+        };
+        conf.borrow_mut().silence_speedup = silence_speedup;
+
         if noise_reduc_check.get_active() {
             conf.borrow_mut().noise_profile_file = Some(noise_file_entry.get_text().unwrap());
             conf.borrow_mut().noise_reduction_amount = Some(sox_amount_adj.get_value());
@@ -118,22 +563,434 @@ pub fn build_ui(application: &gtk::Application) {
             conf.borrow_mut().noise_profile_file = None;
             conf.borrow_mut().noise_reduction_amount = None;
         }
+
+        let color = ColorAdjust {
+            brightness: brightness_adj.get_value(),
+            contrast: contrast_adj.get_value(),
+            saturation: saturation_adj.get_value(),
+            gamma: gamma_adj.get_value(),
+        };
+        conf.borrow_mut().color = if color.is_default() {
+            None
+        } else {
+            Some(color)
+        };
+
+        conf.borrow_mut().lut = if lut_check.get_active() {
+            Some(PathBuf::from(lut_file_entry.get_text().unwrap()))
+        } else {
+            None
+        };
+
+        // position and size have no widget, those of a project are kept
+        let timecode = if timecode_check.get_active() {
+            let source = match timecode_source_combo.get_active_id().as_deref() {
+                Some("selection") => TimecodeSource::SelectionRelative,
+                _ => TimecodeSource::SourceAbsolute,
+            };
+            Some(TimecodeStyle {
+                source,
+                ..conf.borrow().burn_timecode.unwrap_or_default()
+            })
+        } else {
+            None
+        };
+        conf.borrow_mut().burn_timecode = timecode;
+
+        let image_sequence = if image_sequence_check.get_active() {
+            // the number inferred along with the pattern, unless another one was typed
+            let input = conf.borrow().input_file.clone();
+            let start_number = match conf.borrow().image_sequence {
+                Some(sequence) if input == previous_input => sequence.start_number,
+                _ => image_sequence::first_number(&input),
+            };
+            Some(ImageSequence {
+                framerate: sequence_fps_adj.get_value(),
+                start_number,
+            })
+        } else {
+            None
+        };
+        conf.borrow_mut().image_sequence = image_sequence;
+
+        let rows = audio_track_rows.borrow();
+        if mix_tracks_check.get_active() && !rows.is_empty() {
+            conf.borrow_mut().audio_tracks = Vec::new();
+            conf.borrow_mut().downmix_tracks = Some(downmix_tracks_from_rows(&rows));
+        } else {
+            conf.borrow_mut().audio_tracks = audio_tracks_from_rows(&rows);
+            conf.borrow_mut().downmix_tracks = None;
+        }
     }));
 
-    let window_weak = window.downgrade();
-    let input_file_entry_weak = input_file_entry.downgrade();
-    select_input_button.connect_clicked(move |_| {
-        let window = upgrade_weak!(window_weak);
-        let input_file_entry = upgrade_weak!(input_file_entry_weak);
-        handle_select_file(&window, &input_file_entry, gtk::FileChooserAction::Open);
+    color_reset_button.connect_clicked(
+        clone!(brightness_adj, contrast_adj, saturation_adj, gamma_adj => move |_| {
+            let neutral = ColorAdjust::default();
+            brightness_adj.set_value(neutral.brightness);
+            contrast_adj.set_value(neutral.contrast);
+            saturation_adj.set_value(neutral.saturation);
+            gamma_adj.set_value(neutral.gamma);
+        }),
+    );
+
+    // the configuration always stores decibels, the percent spin button is a view on it
+    let syncing_volume = Rc::new(Cell::new(false));
+    let refresh_volume_tooltips = clone!(volume_db_spin, volume_percent_spin => move |db: f64| {
+        let description = volume_description(db);
+        volume_db_spin.set_tooltip_text(Some(description.as_str()));
+        volume_percent_spin.set_tooltip_text(Some(description.as_str()));
+    });
+    refresh_volume_tooltips(volume_adj.get_value());
+
+    volume_adj.connect_value_changed(
+        clone!(volume_percent_adj, syncing_volume, refresh_volume_tooltips => move |adj| {
+            refresh_volume_tooltips(adj.get_value());
+            if !syncing_volume.get() {
+                syncing_volume.set(true);
+                volume_percent_adj.set_value(db_to_percent(adj.get_value()));
+                syncing_volume.set(false);
+            }
+        }),
+    );
+    volume_percent_adj.connect_value_changed(clone!(volume_adj, syncing_volume => move |adj| {
+        if !syncing_volume.get() {
+            syncing_volume.set(true);
+            volume_adj.set_value(ratio_to_db(adj.get_value() / 100.0));
+            syncing_volume.set(false);
+        }
+    }));
+
+    volume_percent_toggle.connect_toggled(
+        clone!(volume_label, volume_db_spin, volume_percent_spin => move |toggle| {
+            let percent = toggle.get_active();
+            volume_db_spin.set_visible(!percent);
+            volume_percent_spin.set_visible(percent);
+            volume_label.set_text(if percent {
+                "Volume amplification/attenuation (%):"
+            } else {
+                "Volume amplification/attenuation (dB):"
+            });
+        }),
+    );
+
+    // None until an existing input file is probed, then its duration if known
+    let input_duration: Rc<Cell<Option<Option<Duration>>>> = Rc::new(Cell::new(None));
+
+    let refresh_time_warning = Rc::new(
+        clone!(start_secs_adj, end_secs_adj, whole_file_check, time_warning_label, input_duration => move || {
+            let duration = match input_duration.get() {
+                Some(duration) => duration,
+                None => {
+                    time_warning_label.hide();
+                    return;
+                }
+            };
+
+            let selection = Config {
+                from_time: Duration::milliseconds((start_secs_adj.get_value() * 1000.0) as i64),
+                to_time: Duration::milliseconds((end_secs_adj.get_value() * 1000.0) as i64),
+                process_whole_file: whole_file_check.get_active(),
+                ..Config::default()
+            };
+            let message = match selection.check_duration(duration) {
+                Ok(warnings) => warnings.join("\n"),
+                Err(e) => e,
+            };
+            time_warning_label.set_text(&message);
+            time_warning_label.set_visible(!message.is_empty());
+        }),
+    );
+
+    let waveform_view = WaveformView::new();
+    waveform_box.pack_start(waveform_view.widget(), true, true, 0);
+    waveform_view.widget().show();
+    waveform_view.set_selection(start_secs_adj.get_value(), end_secs_adj.get_value());
+    // last dragged position not played yet, snippets are throttled
+    let pending_scrub: Rc<Cell<Option<f64>>> = Rc::new(Cell::new(None));
+    waveform_view.connect_selection_changed(
+        clone!(start_secs_adj, end_secs_adj, scrub_menu_item, input_file_entry, pending_scrub => move |start, end| {
+            let moved = if start != start_secs_adj.get_value() { start } else { end };
+            start_secs_adj.set_value(start);
+            end_secs_adj.set_value(end);
+
+            if !scrub_menu_item.get_active() || pending_scrub.replace(Some(moved)).is_some() {
+                return;
+            }
+            gtk::timeout_add(
+                SCRUB_INTERVAL_MS,
+                clone!(input_file_entry, pending_scrub => move || {
+                    if let Some(at) = pending_scrub.take() {
+                        let input_file = input_file_entry.get_text().unwrap_or_default();
+                        let at = Duration::milliseconds((at * 1000.0).round() as i64);
+                        // a missed snippet is not worth interrupting the drag
+                        let _ = processing::play_snippet(
+                            &input_file,
+                            at,
+                            Duration::milliseconds(SCRUB_SNIPPET_MS),
+                        );
+                    }
+                    Continue(false)
+                }),
+            );
+        }),
+    );
+
+    // previews fall back to ffplay when GStreamer or its gtksink is missing
+    let player = Rc::new(Player::new().ok());
+    if let Some(ref player) = *player {
+        waveform_box.pack_start(player.widget(), false, true, 0);
+        waveform_box.reorder_child(player.widget(), 0);
+        player.set_selection(start_secs_adj.get_value(), end_secs_adj.get_value());
+    }
+
+    start_secs_adj.connect_value_changed(
+        clone!(end_secs_adj, waveform_view, player, refresh_time_warning => move |adj| {
+            waveform_view.set_selection(adj.get_value(), end_secs_adj.get_value());
+            if let Some(ref player) = *player {
+                player.set_selection(adj.get_value(), end_secs_adj.get_value());
+            }
+            refresh_time_warning();
+        }),
+    );
+    end_secs_adj.connect_value_changed(
+        clone!(start_secs_adj, waveform_view, player, refresh_time_warning => move |adj| {
+            waveform_view.set_selection(start_secs_adj.get_value(), adj.get_value());
+            if let Some(ref player) = *player {
+                player.set_selection(start_secs_adj.get_value(), adj.get_value());
+            }
+            refresh_time_warning();
+        }),
+    );
+
+    // the selection values are kept, only greyed out
+    whole_file_check.connect_toggled(
+        clone!(start_time_box, end_time_box, padding_box, quick_trim_box, first_sound_check, refresh_time_warning => move |check| {
+            start_time_box.set_sensitive(!check.get_active());
+            end_time_box.set_sensitive(!check.get_active());
+            padding_box.set_sensitive(!check.get_active());
+            quick_trim_box.set_sensitive(!check.get_active());
+            first_sound_check.set_sensitive(!check.get_active());
+            refresh_time_warning();
+        }),
+    );
+
+    // both times typed at once, the entry empties once they are set
+    let quick_trim_tooltip = quick_trim_entry.get_tooltip_text();
+    quick_trim_entry.connect_activate(clone!(start_secs_adj, end_secs_adj => move |entry| {
+        match parse_range(&entry.get_text().unwrap()) {
+            Ok((start, end)) => {
+                start_secs_adj.set_value(start.num_milliseconds() as f64 / 1000.0);
+                end_secs_adj.set_value(end.num_milliseconds() as f64 / 1000.0);
+                entry.set_text("");
+            }
+            Err(e) => {
+                entry.get_style_context().add_class("error");
+                entry.set_tooltip_text(Some(e.as_str()));
+            }
+        }
+    }));
+    quick_trim_entry.connect_changed(move |entry| {
+        entry.get_style_context().remove_class("error");
+        entry.set_tooltip_text(quick_trim_tooltip.as_deref());
     });
 
+    let default_start_upper = start_secs_adj.get_upper();
+    let default_end_upper = end_secs_adj.get_upper();
+    // last probed input and its information, saved along with projects
+    let input_info: Rc<RefCell<Option<(String, probe::MediaInfo)>>> = Rc::new(RefCell::new(None));
+    // duration, waveform and player of a probed input, `None` when probing failed
+    let apply_input_info = {
+        let (start_secs_adj, end_secs_adj, time_warning_label) = (
+            start_secs_adj.clone(),
+            end_secs_adj.clone(),
+            time_warning_label.clone(),
+        );
+        let (input_duration, refresh_time_warning, waveform_view, player) = (
+            input_duration.clone(),
+            refresh_time_warning.clone(),
+            waveform_view.clone(),
+            player.clone(),
+        );
+        let (audio_tracks_box, audio_track_rows, mix_tracks_check, video_stream_combo, conf) = (
+            audio_tracks_box.clone(),
+            audio_track_rows.clone(),
+            mix_tracks_check.clone(),
+            video_stream_combo.clone(),
+            conf.clone(),
+        );
+        let input_info = input_info.clone();
+        Rc::new(move |input_file: String, info: Option<probe::MediaInfo>| {
+            *input_info.borrow_mut() = info.clone().map(|info| (input_file.clone(), info));
+            fill_audio_tracks(
+                &audio_tracks_box,
+                &audio_track_rows,
+                &mix_tracks_check,
+                info.as_ref(),
+            );
+            fill_video_streams(&video_stream_combo, info.as_ref());
+            let secs = info.as_ref().and_then(|info| info.duration);
+            let sample_rate = info.as_ref().and_then(|info| {
+                info.streams
+                    .iter()
+                    .find(|stream| stream.kind == "audio")
+                    .and_then(|stream| stream.sample_rate)
+            });
+            input_duration.set(Some(
+                secs.map(|secs| Duration::milliseconds((secs * 1000.0).round() as i64)),
+            ));
+            if let Some(ref player) = *player {
+                let has_video = info.as_ref().map_or(false, |info| {
+                    info.streams.iter().any(|stream| stream.kind == "video")
+                });
+                player.load(&input_file, has_video);
+            }
+
+            match secs {
+                Some(secs) => {
+                    start_secs_adj.set_upper(secs);
+                    end_secs_adj.set_upper(secs);
+                    // the peaks of a network input would mean downloading it all
+                    if sample_rate.is_some() && !is_url(&input_file) {
+                        load_waveform(
+                            &waveform_view,
+                            conf.borrow().clone(),
+                            input_file,
+                            secs,
+                            sample_rate,
+                        );
+                    } else {
+                        waveform_view.clear();
+                    }
+                    let clamped = end_secs_adj.get_value() > secs;
+                    if clamped {
+                        end_secs_adj.set_value(secs);
+                    }
+                    refresh_time_warning();
+                    if clamped && !time_warning_label.get_visible() {
+                        time_warning_label.set_text(&format!(
+                            "End time clamped to the input file duration ({:.1}s).",
+                            secs
+                        ));
+                        time_warning_label.show();
+                    }
+                }
+                None => {
+                    waveform_view.clear();
+                    start_secs_adj.set_upper(default_start_upper);
+                    end_secs_adj.set_upper(default_end_upper);
+                    refresh_time_warning();
+                }
+            }
+        })
+    };
+
+    // set while the input is probed, cancelled when it changes
+    let input_probe: Rc<RefCell<Option<processing::CancelFlag>>> = Rc::new(RefCell::new(None));
+    input_file_entry.connect_changed(
+        clone!(start_secs_adj, end_secs_adj, input_duration, refresh_time_warning, waveform_view, player, cue_split_button, apply_input_info, input_probe, audio_tracks_box, audio_track_rows, mix_tracks_check, video_stream_combo => move |entry| {
+            if let Some(cancel) = input_probe.borrow_mut().take() {
+                cancel.cancel();
+            }
+            let input_file = entry.get_text().unwrap();
+            let url = is_url(&input_file);
+            entry.set_icon_from_icon_name(
+                gtk::EntryIconPosition::Primary,
+                if url { Some(NETWORK_ICON) } else { None },
+            );
+            entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, None);
+            cue_split_button.set_visible(
+                Path::new(&input_file).is_file() && cue::sibling_cue(Path::new(&input_file)).is_some(),
+            );
+
+            // nothing is known of the input until it is probed, the previous
+            // one is not played nor bounds the selection meanwhile
+            fill_audio_tracks(&audio_tracks_box, &audio_track_rows, &mix_tracks_check, None);
+            fill_video_streams(&video_stream_combo, None);
+            waveform_view.clear();
+            if let Some(ref player) = *player {
+                player.unload();
+            }
+            input_duration.set(None);
+            start_secs_adj.set_upper(default_start_upper);
+            end_secs_adj.set_upper(default_end_upper);
+            refresh_time_warning();
+
+            if url {
+                // ffprobe may wait on the network for a while, the window keeps responding
+                let cancel = processing::CancelFlag::new();
+                *input_probe.borrow_mut() = Some(cancel.clone());
+                entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, Some(STOP_PROBE_ICON));
+                entry.set_icon_tooltip_text(
+                    gtk::EntryIconPosition::Secondary,
+                    Some("Stop probing the network input"),
+                );
+                probe_in_background(
+                    input_file.clone(),
+                    cancel,
+                    clone!(entry, apply_input_info, input_probe => move |info| {
+                        input_probe.borrow_mut().take();
+                        entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, None);
+                        apply_input_info(input_file.clone(), info.ok());
+                    }),
+                );
+                return;
+            }
+            if !Path::new(&input_file).is_file() {
+                return;
+            }
+
+            // probed off the main loop as well, a newer input cancels it
+            let cancel = processing::CancelFlag::new();
+            *input_probe.borrow_mut() = Some(cancel.clone());
+            probe_in_background(
+                input_file.clone(),
+                cancel,
+                clone!(apply_input_info, input_probe => move |info| {
+                    input_probe.borrow_mut().take();
+                    apply_input_info(input_file.clone(), info.ok());
+                }),
+            );
+        }),
+    );
+    input_file_entry.connect_icon_press(clone!(input_probe => move |entry, position, _| {
+        if position != gtk::EntryIconPosition::Secondary {
+            return;
+        }
+        // the selection is then left unbounded, as for an input without duration
+        if let Some(cancel) = input_probe.borrow_mut().take() {
+            cancel.cancel();
+        }
+        entry.set_icon_from_icon_name(gtk::EntryIconPosition::Secondary, None);
+    }));
+
+    mix_tracks_check.connect_toggled(clone!(audio_track_rows => move |check| {
+        for row in audio_track_rows.borrow().iter() {
+            row.refresh_sensitivity(check.get_active());
+        }
+    }));
+
+    // pictures are read whole
+    image_sequence_check.connect_toggled(
+        clone!(sequence_fps_spin, whole_file_check => move |check| {
+            sequence_fps_spin.set_sensitive(check.get_active());
+            if check.get_active() {
+                whole_file_check.set_active(true);
+            }
+        }),
+    );
+
     let window_weak = window.downgrade();
     let output_file_entry_weak = output_file_entry.downgrade();
     select_output_button.connect_clicked(move |_| {
         let window = upgrade_weak!(window_weak);
         let output_file_entry = upgrade_weak!(output_file_entry_weak);
-        handle_select_file(&window, &output_file_entry, gtk::FileChooserAction::Save);
+        handle_select_file(
+            &window.clone(),
+            gtk::FileChooserAction::Save,
+            None,
+            false,
+            move |paths| set_chosen_path(&window, &output_file_entry, &paths),
+        );
     });
 
     let window_weak = window.downgrade();
@@ -141,88 +998,3127 @@ pub fn build_ui(application: &gtk::Application) {
     select_noise_button.connect_clicked(move |_| {
         let window = upgrade_weak!(window_weak);
         let noise_file_entry = upgrade_weak!(noise_file_entry_weak);
-        handle_select_file(&window, &noise_file_entry, gtk::FileChooserAction::Open);
+        handle_select_file(
+            &window.clone(),
+            gtk::FileChooserAction::Open,
+            None,
+            false,
+            move |paths| set_chosen_path(&window, &noise_file_entry, &paths),
+        );
+    });
+
+    record_noise_button.connect_clicked(
+        clone!(window, conf, input_file_entry, noise_file_entry, noise_reduc_check => move |button| {
+            // ffmpeg may take a moment to list them, the window keeps responding
+            button.set_sensitive(false);
+            list_devices_in_background(
+                conf.borrow().clone(),
+                clone!(window, conf, button, input_file_entry, noise_file_entry, noise_reduc_check => move |devices| {
+                    button.set_sensitive(true);
+                    let devices = match devices {
+                        Ok(ref devices) if devices.is_empty() => {
+                            message_dialog!(window, gtk::MessageType::Error, "No audio input found.");
+                            return;
+                        }
+                        Ok(devices) => devices,
+                        Err(e) => {
+                            message_dialog!(window, gtk::MessageType::Error, &e);
+                            return;
+                        }
+                    };
+                    let (device, duration) = match ask_noise_recording(&window, &devices) {
+                        Some(settings) => settings,
+                        None => return,
+                    };
+
+                    // kept next to the input, projects refer to it
+                    let input_file = input_file_entry.get_text().unwrap();
+                    let stem = Path::new(&input_file)
+                        .file_stem()
+                        .map_or_else(|| String::from("room"), |stem| stem.to_string_lossy().into_owned());
+                    let output_file = Path::new(&input_file)
+                        .with_file_name(format!("{}_noise.wav", stem))
+                        .to_string_lossy()
+                        .into_owned();
+
+                    record_noise_sample(
+                        &window,
+                        conf.borrow().clone(),
+                        device,
+                        duration,
+                        output_file,
+                        clone!(noise_file_entry, noise_reduc_check => move |output_file| {
+                            noise_file_entry.set_text(&output_file);
+                            noise_reduc_check.set_active(true);
+                        }),
+                    );
+                }),
+            );
+        }),
+    );
+
+    let window_weak = window.downgrade();
+    let lut_file_entry_weak = lut_file_entry.downgrade();
+    select_lut_button.connect_clicked(move |_| {
+        let window = upgrade_weak!(window_weak);
+        let lut_file_entry = upgrade_weak!(lut_file_entry_weak);
+
+        let lut_filter = gtk::FileFilter::new();
+        lut_filter.set_name("3D LUT files (*.cube, *.3dl)");
+        lut_filter.add_pattern("*.cube");
+        lut_filter.add_pattern("*.3dl");
+
+        handle_select_file(
+            &window.clone(),
+            gtk::FileChooserAction::Open,
+            Some(&lut_filter),
+            false,
+            move |paths| set_chosen_path(&window, &lut_file_entry, &paths),
+        );
     });
 
+    // whether the settings allow to process and to preview, see refresh_validation
+    let runnable = Rc::new(Cell::new((false, false)));
+    let window_busy = Rc::new(Cell::new(false));
+
+    let set_window_busy = Rc::new(clone!(files_grid,
+                                         stream_options_box,
+                                         volume_box,
+                                         pass_filters_box,
+                                         channel_ops_box,
+                                         reverb_box,
+                                         noise_reduction_box,
+                                         silence_speedup_box,
+                                         video_filters_expander,
+                                         time_box,
+                                         preview_button,
+                                         process_button,
+                                         runnable,
+                                         window_busy,
+                                         busy_spinner => move |busy| {
+        set_busy(
+            &[
+                files_grid.upcast_ref(),
+                stream_options_box.upcast_ref(),
+                volume_box.upcast_ref(),
+                pass_filters_box.upcast_ref(),
+                channel_ops_box.upcast_ref(),
+                reverb_box.upcast_ref(),
+                noise_reduction_box.upcast_ref(),
+                silence_speedup_box.upcast_ref(),
+                video_filters_expander.upcast_ref(),
+                time_box.upcast_ref(),
+            ],
+            busy,
+        );
+        window_busy.set(busy);
+        let (can_process, can_preview) = runnable.get();
+        process_button.set_sensitive(!busy && can_process);
+        preview_button.set_sensitive(!busy && can_preview);
+
+        if busy {
+            busy_spinner.start();
+        } else {
+            busy_spinner.stop();
+        }
+    }));
+
+    // invalid settings are shown next to their widget, processing waits for them to be fixed
+    let validated_widgets: Vec<(validation::Field, gtk::Widget)> = vec![
+        (
+            validation::Field::InputFile,
+            input_file_entry.clone().upcast(),
+        ),
+        (
+            validation::Field::OutputFile,
+            output_file_entry.clone().upcast(),
+        ),
+        (validation::Field::Times, start_secs_spin.upcast()),
+        (validation::Field::Times, end_secs_spin.upcast()),
+        (validation::Field::HighPass, high_pass_spin.upcast()),
+        (validation::Field::LowPass, low_pass_spin.upcast()),
+        (
+            validation::Field::NoiseProfile,
+            noise_file_entry.clone().upcast(),
+        ),
+        (validation::Field::Lut, lut_file_entry.clone().upcast()),
+    ];
+    let validated_widgets: Vec<(validation::Field, gtk::Widget, Option<String>)> =
+        validated_widgets
+            .into_iter()
+            .map(|(field, widget)| {
+                let tooltip = widget.get_tooltip_text();
+                (field, widget, tooltip)
+            })
+            .collect();
+
+    let refresh_validation: Rc<dyn Fn()> = Rc::new(
+        clone!(conf, update_conf, runnable, window_busy, process_button, preview_button => move || {
+            update_conf();
+            let state = validation::ValidationState::new(&conf.borrow());
+
+            for (field, widget, tooltip) in &validated_widgets {
+                let style_context = widget.get_style_context();
+                match state.error(*field) {
+                    Some(error) => {
+                        style_context.add_class("error");
+                        widget.set_tooltip_text(error);
+                    }
+                    None => {
+                        style_context.remove_class("error");
+                        widget.set_tooltip_text(tooltip.as_deref());
+                    }
+                }
+            }
+
+            runnable.set((state.can_process(), state.can_preview()));
+            process_button.set_sensitive(!window_busy.get() && state.can_process());
+            preview_button.set_sensitive(!window_busy.get() && state.can_preview());
+            let summary = state.summary();
+            process_button.set_tooltip_text(if summary.is_empty() {
+                None
+            } else {
+                Some(summary.as_str())
+            });
+        }),
+    );
+
     process_button.connect_clicked(
-        clone!(input_file_entry, output_file_entry, window, conf, update_conf => move |_| {
-            let mut errors: Vec<&str> = Vec::new();
-            if input_file_entry.get_text().unwrap() == "" {
-                errors.push("No input file specified");
+        clone!(window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            update_conf();
+            conf.borrow_mut().preview = false;
+            let warnings = processing::setting_warnings(&conf.borrow());
+            if !warnings.is_empty() && !confirm_warnings(&window, &warnings) {
+                return;
+            }
+
+            set_window_busy(true);
+            run_in_background(
+                conf.borrow().clone(),
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
+
+    select_input_button.connect_clicked(
+        clone!(window, input_file_entry, image_sequence_check, sequence_fps_adj, conf, update_conf, set_window_busy, process_progress_bar, queue_stop_button => move |_| {
+            handle_select_file(
+                &window,
+                gtk::FileChooserAction::Open,
+                None,
+                true,
+                clone!(window, input_file_entry, image_sequence_check, sequence_fps_adj, conf, update_conf, set_window_busy, process_progress_bar, queue_stop_button => move |paths| {
+                    let inputs = match path_texts(&paths) {
+                        Ok(inputs) => inputs,
+                        Err(e) => {
+                            message_dialog!(window, gtk::MessageType::Error, &e);
+                            return;
+                        }
+                    };
+
+                    // several inputs are processed one after the other with the current
+                    // settings, several pictures are read as a sequence
+                    if inputs.len() > 1 && !image_sequence_check.get_active() {
+                        update_conf();
+                        if !needs_output_folder(&conf.borrow(), &inputs) {
+                            let jobs = batch_jobs(&conf.borrow(), &inputs, None);
+                            start_batch(&window, &process_progress_bar, &queue_stop_button, jobs, resume, &set_window_busy);
+                            return;
+                        }
+                        // sandboxed, the outputs go to a folder granted by the portal
+                        handle_select_file(
+                            &window,
+                            gtk::FileChooserAction::SelectFolder,
+                            None,
+                            false,
+                            clone!(window, conf, set_window_busy, process_progress_bar, queue_stop_button => move |folders| {
+                                let folder = match path_texts(&folders) {
+                                    Ok(folders) => folders.into_iter().next(),
+                                    Err(e) => {
+                                        message_dialog!(window, gtk::MessageType::Error, &e);
+                                        return;
+                                    }
+                                };
+                                let jobs = batch_jobs(&conf.borrow(), &inputs, folder.as_deref());
+                                start_batch(&window, &process_progress_bar, &queue_stop_button, jobs, resume, &set_window_busy);
+                            }),
+                        );
+                        return;
+                    }
+                    let input = match inputs.into_iter().next() {
+                        Some(input) => input,
+                        None => return,
+                    };
+                    input_file_entry.set_text(&input);
+
+                    // one of the pictures stands for all of them
+                    if !image_sequence_check.get_active() || image_sequence::is_pattern(&input) {
+                        return;
+                    }
+                    match image_sequence::infer_pattern(Path::new(&input)) {
+                        Ok(sequence) => {
+                            input_file_entry.set_text(&sequence.pattern);
+                            // the run holding the chosen picture, not the first one found
+                            conf.borrow_mut().input_file = sequence.pattern.clone();
+                            conf.borrow_mut().image_sequence = Some(ImageSequence {
+                                framerate: sequence_fps_adj.get_value(),
+                                start_number: Some(sequence.start_number),
+                            });
+                            if sequence.has_gaps {
+                                let message = format!(
+                                    "The numbering of the pictures has gaps: only the {} pictures from number {} are read.",
+                                    sequence.frame_count, sequence.start_number
+                                );
+                                message_dialog!(window, gtk::MessageType::Warning, &message);
+                            }
+                        }
+                        Err(e) => {
+                            message_dialog!(window, gtk::MessageType::Error, &e);
+                        }
+                    }
+                }),
+            );
+        }),
+    );
+
+    cue_split_button.connect_clicked(
+        clone!(window, conf, update_conf, set_window_busy, process_progress_bar, queue_stop_button, input_duration => move |_| {
+            update_conf();
+            let mut split_conf = conf.borrow().clone();
+            split_conf.preview = false;
+
+            let sheet = match cue::sibling_cue(Path::new(&split_conf.input_file)) {
+                Some(cue_file) => File::open(&cue_file)
+                    .map_err(|e| format!("Could not open the CUE sheet.\nError: {}", e))
+                    .and_then(cue::parse),
+                None => return,
+            };
+            let sheet = match sheet {
+                Ok(sheet) => sheet,
+                Err(e) => {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                    return;
+                }
+            };
+            let pattern = match ask_track_pattern(&window, &sheet) {
+                Some(pattern) => pattern,
+                None => return,
+            };
+            let jobs = match cue::track_jobs(&split_conf, &sheet, &pattern, input_duration.get().flatten()) {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                    return;
+                }
+            };
+
+            let mut job_queue = queue::JobQueue::new();
+            for job in jobs {
+                job_queue.push(job);
             }
-            if output_file_entry.get_text().unwrap() == "" {
-                errors.push("No output file specified");
+            if let Some(manifest_file) = job_queue.default_manifest_file() {
+                job_queue.set_manifest_file(manifest_file);
+                job_queue.set_resume(resume);
             }
+            set_window_busy(true);
+            run_queue_in_background(
+                job_queue,
+                &process_progress_bar,
+                &queue_stop_button,
+                clone!(window, set_window_busy => move |results| {
+                    set_window_busy(false);
+                    let (message_type, message) =
+                        batch_result_message(&results, "Track", "tracks");
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
+
+    file_info_menu_item.connect_activate(clone!(input_file_entry, window => move |_| {
+        let errors = missing_files_errors(&input_file_entry, None);
+        if !errors.is_empty() {
+            message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+            return;
+        }
+
+        // ffprobe may take a while on large files and network inputs
+        let input_file = input_file_entry.get_text().unwrap();
+        probe_in_background(
+            input_file.clone(),
+            processing::CancelFlag::new(),
+            clone!(input_file_entry, window => move |info| {
+                // the input changed meanwhile, its information is not asked for
+                if input_file_entry.get_text().as_deref() != Some(input_file.as_str()) {
+                    return;
+                }
+                match info {
+                    Ok(info) => show_media_info(&window, &info),
+                    Err(e) => message_dialog!(window, gtk::MessageType::Error, &e),
+                }
+            }),
+        );
+    }));
 
+    smart_cut_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
             if !errors.is_empty() {
                 message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
                 return;
             }
 
             update_conf();
-            conf.borrow_mut().preview = false;
+            let mut smart_cut_conf = conf.borrow().clone();
+            smart_cut_conf.preview = false;
+            smart_cut_conf.operation = Operation::SmartCut;
+
+            set_window_busy(true);
+            run_in_background(
+                smart_cut_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
 
-            match processing::run(&conf.borrow()) {
-                Ok(_) => message_dialog!(window, gtk::MessageType::Info, "Operation suceeded!"),
-                Err(e) => message_dialog!(window, gtk::MessageType::Error, &e),
+    fix_rotation_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
             }
+
+            let rotation = match ask_rotation(&window) {
+                Some(rotation) => rotation,
+                None => return,
+            };
+
+            update_conf();
+            let mut fix_rotation_conf = conf.borrow().clone();
+            fix_rotation_conf.preview = false;
+            fix_rotation_conf.operation = Operation::FixRotation { set: rotation };
+
+            set_window_busy(true);
+            run_in_background(
+                fix_rotation_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
         }),
     );
 
-    preview_button.connect_clicked(clone!(input_file_entry, window, conf => move |_| {
-        let mut errors: Vec<&str> = Vec::new();
-        if input_file_entry.get_text().unwrap() == "" {
-            errors.push("No input file specified");
-        }
+    spectrogram_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
+            }
 
-        if !errors.is_empty() {
-            message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
-            return;
-        }
+            let size = match ask_spectrogram_size(&window) {
+                Some(size) => size,
+                None => return,
+            };
 
-        update_conf();
-        conf.borrow_mut().preview = true;
+            update_conf();
+            let mut analyze_conf = conf.borrow().clone();
+            analyze_conf.preview = false;
+            analyze_conf.operation = Operation::Analyze;
+            analyze_conf.spectrogram = Some(size);
 
-        match processing::run(&conf.borrow()) {
-            Ok(_) => (),
-            Err(e) => message_dialog!(window, gtk::MessageType::Error, &e),
-        }
-    }));
+            set_window_busy(true);
+            run_in_background(
+                analyze_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
 
-    quit_menu_item.connect_activate(clone!(window => move |_| {
-        window.close();
-    }));
+    contact_sheet_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
+            }
 
-    about_menu_item.connect_activate(move |_| {
-        dialog.run();
-        dialog.hide();
-    });
+            let (grid, tile_width, timestamps) = match ask_contact_sheet_settings(&window) {
+                Some(settings) => settings,
+                None => return,
+            };
 
-    window.connect_delete_event(|win, _| {
-        win.destroy();
-        Inhibit(false)
-    });
+            update_conf();
+            let mut analyze_conf = conf.borrow().clone();
+            analyze_conf.preview = false;
+            analyze_conf.operation = Operation::Analyze;
+            analyze_conf.contact_sheet = Some(grid);
+            analyze_conf.contact_sheet_tile_width = Some(tile_width);
+            analyze_conf.contact_sheet_timestamps = timestamps;
 
-    window.show_all();
-}
+            set_window_busy(true);
+            run_in_background(
+                analyze_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
 
-fn handle_select_file(
-    window: &gtk::ApplicationWindow,
-    entry: &gtk::Entry,
-    dialog_action: gtk::FileChooserAction,
-) {
-    let file_chooser =
-        gtk::FileChooserDialog::new(Some("Select File"), Some(window), dialog_action);
+    visualize_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
+            }
 
-    file_chooser.add_buttons(&[
-        ("Select", gtk::ResponseType::Ok.into()),
-        ("Cancel", gtk::ResponseType::Cancel.into()),
-    ]);
+            let (style, resolution) = match ask_visualize_settings(&window) {
+                Some(settings) => settings,
+                None => return,
+            };
 
-    if file_chooser.run() == gtk::ResponseType::Ok.into() {
-        let filename = file_chooser.get_filename().expect("couldn't get filename");
-        entry.set_text(&filename.to_string_lossy());
-    }
+            update_conf();
+            let mut visualize_conf = conf.borrow().clone();
+            visualize_conf.preview = false;
+            visualize_conf.operation = Operation::Visualize {
+                style,
+                resolution,
+                colors: None,
+            };
+            // the output may be an audio file, the video goes next to it
+            visualize_conf.output_file = Path::new(&visualize_conf.output_file)
+                .with_extension("mp4")
+                .to_string_lossy()
+                .into_owned();
 
-    file_chooser.destroy();
+            set_window_busy(true);
+            run_in_background(
+                visualize_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
+
+    still_image_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
+            }
+
+            let image = match ask_still_image(&window) {
+                Some(image) => image,
+                None => return,
+            };
+
+            update_conf();
+            let mut still_image_conf = conf.borrow().clone();
+            still_image_conf.preview = false;
+            still_image_conf.still_image = Some(image);
+            still_image_conf.output_file = processing::still_image_output_file(&still_image_conf);
+
+            set_window_busy(true);
+            run_in_background(
+                still_image_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
+
+    thumbnail_menu_item.connect_activate(
+        clone!(input_file_entry, output_file_entry, window, conf, update_conf, set_window_busy, process_progress_bar => move |_| {
+            let errors = missing_files_errors(&input_file_entry, Some(&output_file_entry));
+            if !errors.is_empty() {
+                message_dialog!(window, gtk::MessageType::Error, &errors.join("\n"));
+                return;
+            }
+
+            let (video_file, time) = match ask_thumbnail_frame(&window) {
+                Some(thumbnail) => thumbnail,
+                None => return,
+            };
+            let video_file = match path_texts(&[video_file]) {
+                Ok(mut texts) => texts.remove(0),
+                Err(e) => {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                    return;
+                }
+            };
+
+            update_conf();
+            let mut thumbnail_conf = conf.borrow().clone();
+            thumbnail_conf.preview = false;
+            thumbnail_conf.thumbnail_from_video = Some((video_file, time));
+
+            set_window_busy(true);
+            run_in_background(
+                thumbnail_conf,
+                &process_progress_bar,
+                clone!(window, set_window_busy => move |result| {
+                    set_window_busy(false);
+                    let (message_type, message) = run_result_message(result);
+                    message_dialog!(window, message_type, &message);
+                }),
+            );
+        }),
+    );
+
+    preview_button.connect_clicked(clone!(window, conf, update_conf, player, set_window_busy, process_progress_bar => move |_| {
+        update_conf();
+        conf.borrow_mut().preview = true;
+
+        if let Some(ref player) = *player {
+            player.set_audio_filter(processing::make_gst_audio_filter_description(&conf.borrow()));
+            player.play_selection();
+            return;
+        }
+
+        set_window_busy(true);
+        run_in_background(
+            conf.borrow().clone(),
+            &process_progress_bar,
+            clone!(window, set_window_busy => move |result| {
+                set_window_busy(false);
+                if let Err(e) = result {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                }
+            }),
+        );
+    }));
+
+    // settings as of the last recorded change, the history holds the previous ones
+    update_conf();
+    let recorded_conf = Rc::new(RefCell::new(session_config(&conf.borrow())));
+    let history = Rc::new(RefCell::new(history::History::new(
+        history::HISTORY_CAPACITY,
+        history::COALESCE_WINDOW,
+    )));
+    // set while apply_conf replaces the settings, recorded at once afterwards
+    let applying_conf = Rc::new(Cell::new(false));
+
+    let refresh_history_items =
+        Rc::new(clone!(history, undo_menu_item, redo_menu_item => move || {
+            let history = history.borrow();
+            undo_menu_item.set_sensitive(history.can_undo());
+            redo_menu_item.set_sensitive(history.can_redo());
+        }));
+
+    let record_change = Rc::new(
+        clone!(conf, update_conf, recorded_conf, history, applying_conf, refresh_history_items => move || {
+            if applying_conf.get() {
+                return;
+            }
+            update_conf();
+            let current = session_config(&conf.borrow());
+            let changed = history::changed_settings(&recorded_conf.borrow(), &current);
+            if changed.is_empty() {
+                return;
+            }
+            let before = recorded_conf.replace(current);
+            history.borrow_mut().record(before, changed, Instant::now());
+            refresh_history_items();
+        }),
+    );
+
+    // inverse of update_conf, settings without widgets are kept as they are
+    let apply_conf = Rc::new(clone!(conf,
+                            applying_conf,
+                            record_change,
+                            input_file_entry,
+                            output_file_entry,
+                            additional_outputs_entry,
+                            start_secs_adj,
+                            end_secs_adj,
+                            pad_before_adj,
+                            pad_after_adj,
+                            whole_file_check,
+                            first_sound_check,
+                            ignore_audio_check,
+                            ignore_video_check,
+                            overidde_existing_check,
+                            sample_accurate_check,
+                            create_output_dirs_check,
+                            strip_rotation_check,
+                            video_codec_combo,
+                            audio_codec_combo,
+                            mp3_mode_combo,
+                            mp3_quality_adj,
+                            mp3_joint_stereo_check,
+                            aac_encoder_combo,
+                            aac_profile_combo,
+                            aac_mode_combo,
+                            aac_quality_adj,
+                            encoder_preset_combo,
+                            low_priority_check,
+                            preview_loop_menu_item,
+                            preview_full_menu_item,
+                            source_info_menu_item,
+                            fragmented_mp4_menu_item,
+                            stream_copy_menu_item,
+                            fix_timestamps_menu_item,
+                            tolerant_input_menu_item,
+                            preserve_channels_menu_item,
+                            portable_names_menu_item,
+                            copy_audio_menu_item,
+                            high_pass_check,
+                            low_pass_check,
+                            high_pass_freq_adj,
+                            low_pass_freq_adj,
+                            channel_ops_combo,
+                            balance_adj,
+                            vocal_removal_check,
+                            vocal_removal_adj,
+                            reverb_combo,
+                            reverb_in_gain_adj,
+                            reverb_out_gain_adj,
+                            reverb_delays_entry,
+                            reverb_decays_entry,
+                            silence_speedup_check,
+                            silence_speedup_adj,
+                            peak_normalization_check,
+                            volume_adj,
+                            noise_reduc_check,
+                            noise_file_entry,
+                            sox_amount_adj,
+                            brightness_adj,
+                            contrast_adj,
+                            saturation_adj,
+                            gamma_adj,
+                            lut_check,
+                            lut_file_entry,
+                            timecode_check,
+                            timecode_source_combo,
+                            image_sequence_check,
+                            sequence_fps_adj,
+                            audio_track_rows,
+                            mix_tracks_check => move |new_conf: &Config| {
+        applying_conf.set(true);
+        // projects and presets leave the machine settings out
+        *conf.borrow_mut() = new_conf.with_env();
+
+        // the input first: it resets the time limits
+        input_file_entry.set_text(&new_conf.input_file);
+        output_file_entry.set_text(&new_conf.output_file);
+        additional_outputs_entry.set_text(&format_output_list(&new_conf.additional_outputs));
+        start_secs_adj.set_value(new_conf.from_time.num_milliseconds() as f64 / 1000.0);
+        end_secs_adj.set_value(new_conf.to_time.num_milliseconds() as f64 / 1000.0);
+        whole_file_check.set_active(new_conf.process_whole_file);
+        first_sound_check.set_active(new_conf.trim_to_first_sound);
+
+        let padding = |pad: Option<Duration>| pad.map_or(0.0, |pad| pad.num_milliseconds() as f64 / 1000.0);
+        pad_before_adj.set_value(padding(new_conf.pad_before));
+        pad_after_adj.set_value(padding(new_conf.pad_after));
+        ignore_video_check.set_active(new_conf.ignore_video);
+        ignore_audio_check.set_active(new_conf.ignore_audio);
+        overidde_existing_check.set_active(new_conf.allow_overidde);
+        sample_accurate_check.set_active(new_conf.sample_accurate);
+        create_output_dirs_check.set_active(new_conf.create_output_dirs);
+        strip_rotation_check.set_active(new_conf.strip_rotation_metadata);
+        set_active_codec(&video_codec_combo, new_conf.video_codec.as_deref());
+        set_active_codec(&audio_codec_combo, new_conf.audio_codec.as_deref());
+        if let Some(mp3) = new_conf.mp3 {
+            // the mode first: it resets the quality range
+            match mp3.quality {
+                Mp3Quality::Vbr(level) => {
+                    mp3_mode_combo.set_active_id(MP3_VBR_ID);
+                    mp3_quality_adj.set_value(f64::from(level));
+                }
+                Mp3Quality::Cbr(bitrate) => {
+                    mp3_mode_combo.set_active_id(MP3_CBR_ID);
+                    mp3_quality_adj.set_value(f64::from(bitrate));
+                }
+            }
+            mp3_joint_stereo_check.set_active(mp3.joint_stereo);
+        }
+        if let Some(aac) = new_conf.aac_options {
+            aac_encoder_combo.set_active_id(match aac.encoder {
+                AacEncoder::Native => AAC_NATIVE_ID,
+                AacEncoder::Fdk => AAC_FDK_ID,
+            });
+            aac_profile_combo.set_active_id(aac_profile_id(aac.profile));
+            // the mode first: it resets the quality range
+            match aac.vbr_mode {
+                Some(mode) => {
+                    aac_mode_combo.set_active_id(AAC_VBR_ID);
+                    aac_quality_adj.set_value(f64::from(mode));
+                }
+                None => {
+                    aac_mode_combo.set_active_id(AAC_CBR_ID);
+                    aac_quality_adj.set_value(f64::from(aac.bitrate));
+                }
+            }
+        }
+        encoder_preset_combo
+            .set_active_id(new_conf.encoder_preset.as_deref().unwrap_or(DEFAULT_PRESET_ID));
+        low_priority_check.set_active(new_conf.low_priority);
+        preview_loop_menu_item.set_active(new_conf.preview_loop);
+        preview_full_menu_item.set_active(new_conf.preview_full);
+        source_info_menu_item.set_active(new_conf.embed_source_info);
+        fragmented_mp4_menu_item.set_active(new_conf.fragmented_mp4);
+        stream_copy_menu_item.set_active(new_conf.stream_copy);
+        fix_timestamps_menu_item.set_active(new_conf.fix_timestamps);
+        tolerant_input_menu_item.set_active(new_conf.tolerant_input);
+        preserve_channels_menu_item.set_active(new_conf.preserve_channels);
+        portable_names_menu_item
+            .set_active(new_conf.filename_charset == output_pattern::FilenameCharset::Portable);
+        copy_audio_menu_item.set_active(new_conf.copy_audio_when_possible);
+        peak_normalization_check.set_active(new_conf.peak_normalization);
+        volume_adj.set_value(new_conf.volume_change);
+
+        low_pass_check.set_active(new_conf.low_pass_filter.is_some());
+        if let Some(low) = new_conf.low_pass_filter {
+            low_pass_freq_adj.set_value(f64::from(low));
+        }
+        high_pass_check.set_active(new_conf.high_pass_filter.is_some());
+        if let Some(high) = new_conf.high_pass_filter {
+            high_pass_freq_adj.set_value(f64::from(high));
+        }
+
+        channel_ops_combo.set_active_id(channel_op_id(new_conf.channel_ops));
+        if let Some(ChannelOp::Balance(balance)) = new_conf.channel_ops {
+            balance_adj.set_value(balance);
+        }
+        vocal_removal_check.set_active(new_conf.vocal_removal.is_some());
+        if let Some(strength) = new_conf.vocal_removal {
+            vocal_removal_adj.set_value(strength);
+        }
+        reverb_combo.set_active_id(match new_conf.reverb {
+            None => NO_REVERB_ID,
+            Some(ReverbSettings::SmallRoom) => REVERB_SMALL_ROOM_ID,
+            Some(ReverbSettings::Hall) => REVERB_HALL_ID,
+            Some(ReverbSettings::Custom { .. }) => REVERB_CUSTOM_ID,
+        });
+        if let Some(ReverbSettings::Custom {
+            in_gain,
+            out_gain,
+            ref delays,
+            ref decays,
+        }) = new_conf.reverb
+        {
+            reverb_in_gain_adj.set_value(in_gain);
+            reverb_out_gain_adj.set_value(out_gain);
+            reverb_delays_entry.set_text(&format_number_list(delays));
+            reverb_decays_entry.set_text(&format_number_list(decays));
+        }
+        silence_speedup_check.set_active(new_conf.silence_speedup.is_some());
+        if let Some(ref speedup) = new_conf.silence_speedup {
+            silence_speedup_adj.set_value(speedup.factor);
+        }
+
+        noise_reduc_check.set_active(new_conf.noise_profile_file.is_some());
+        if let Some(ref noise_profile_file) = new_conf.noise_profile_file {
+            noise_file_entry.set_text(noise_profile_file);
+        }
+        if let Some(amount) = new_conf.noise_reduction_amount {
+            sox_amount_adj.set_value(amount);
+        }
+
+        let color = new_conf.color.unwrap_or_default();
+        brightness_adj.set_value(color.brightness);
+        contrast_adj.set_value(color.contrast);
+        saturation_adj.set_value(color.saturation);
+        gamma_adj.set_value(color.gamma);
+
+        lut_check.set_active(new_conf.lut.is_some());
+        if let Some(ref lut) = new_conf.lut {
+            lut_file_entry.set_text(&lut.to_string_lossy());
+        }
+
+        timecode_check.set_active(new_conf.burn_timecode.is_some());
+        if let Some(timecode) = new_conf.burn_timecode {
+            timecode_source_combo.set_active_id(match timecode.source {
+                TimecodeSource::SourceAbsolute => "source",
+                TimecodeSource::SelectionRelative => "selection",
+            });
+        }
+
+        image_sequence_check.set_active(new_conf.image_sequence.is_some());
+        if let Some(sequence) = new_conf.image_sequence {
+            sequence_fps_adj.set_value(sequence.framerate);
+        }
+
+        // the tracks are listed once the input is probed
+        mix_tracks_check.set_active(new_conf.downmix_tracks.is_some());
+        set_audio_track_rows(
+            &audio_track_rows.borrow(),
+            &new_conf.audio_tracks,
+            new_conf.downmix_tracks.as_deref(),
+        );
+
+        // a single change to undo
+        applying_conf.set(false);
+        record_change();
+    }));
+
+    // the restored settings are the recorded ones, applying them adds no change
+    let restore_history = Rc::new(
+        clone!(conf, update_conf, apply_conf, recorded_conf, history, refresh_history_items => move |redo: bool| {
+            update_conf();
+            let current = session_config(&conf.borrow());
+            let restored = if redo {
+                history.borrow_mut().redo(current)
+            } else {
+                history.borrow_mut().undo(current)
+            };
+            if let Some(restored) = restored {
+                *recorded_conf.borrow_mut() = restored.clone();
+                apply_conf(&restored);
+            }
+            refresh_history_items();
+        }),
+    );
+
+    let accel_group = gtk::AccelGroup::new();
+    window.add_accel_group(&accel_group);
+    undo_menu_item.add_accelerator(
+        "activate",
+        &accel_group,
+        gdk::enums::key::z,
+        gdk::ModifierType::CONTROL_MASK,
+        gtk::AccelFlags::VISIBLE,
+    );
+    redo_menu_item.add_accelerator(
+        "activate",
+        &accel_group,
+        gdk::enums::key::z,
+        gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK,
+        gtk::AccelFlags::VISIBLE,
+    );
+    undo_menu_item.connect_activate(clone!(restore_history => move |_| {
+        restore_history(false);
+    }));
+    redo_menu_item.connect_activate(clone!(restore_history => move |_| {
+        restore_history(true);
+    }));
+
+    // a preset replaces the settings at once, the combo then shows its title again
+    preset_combo.connect_changed(clone!(conf, update_conf, apply_conf => move |combo| {
+        let id = match combo.get_active_id() {
+            Some(ref id) if id != NO_PRESET_ID => id.clone(),
+            _ => return,
+        };
+        if let Some(preset) = builtin_preset(&id) {
+            update_conf();
+            let applied = preset.apply_to(&conf.borrow());
+            apply_conf(&applied);
+        }
+        combo.set_active_id(NO_PRESET_ID);
+    }));
+
+    // the output extension follows the codecs, unless the container is explicit
+    let follow_codecs = Rc::new(clone!(conf,
+                            output_file_entry,
+                            video_codec_combo,
+                            audio_codec_combo,
+                            ignore_video_check,
+                            ignore_audio_check => move || {
+        let output_file = output_file_entry.get_text().unwrap();
+        if output_file.is_empty() || conf.borrow().output_format.is_some() {
+            return;
+        }
+        let video_codec =
+            active_codec(&video_codec_combo).filter(|_| !ignore_video_check.get_active());
+        let audio_codec =
+            active_codec(&audio_codec_combo).filter(|_| !ignore_audio_check.get_active());
+        if let Some(corrected) = containers::corrected_output_file(
+            &output_file,
+            video_codec.as_deref(),
+            audio_codec.as_deref(),
+        ) {
+            output_file_entry.set_text(&corrected);
+        }
+    }));
+    video_codec_combo.connect_changed(clone!(follow_codecs => move |_| follow_codecs()));
+    audio_codec_combo.connect_changed(clone!(follow_codecs => move |_| follow_codecs()));
+
+    // MP3 quality settings only show for the MP3 encoder
+    audio_codec_combo.connect_changed(clone!(mp3_quality_box => move |combo| {
+        mp3_quality_box.set_visible(active_codec(combo).as_deref() == Some("libmp3lame"));
+    }));
+    // a VBR level and a CBR bitrate have ranges of their own
+    mp3_mode_combo.connect_changed(clone!(mp3_quality_adj => move |combo| {
+        if combo.get_active_id().as_deref() == Some(MP3_CBR_ID) {
+            mp3_quality_adj.configure(192.0, 8.0, 320.0, 8.0, 32.0, 0.0);
+        } else {
+            mp3_quality_adj.configure(2.0, 0.0, 9.0, 1.0, 1.0, 0.0);
+        }
+    }));
+
+    // AAC settings only show for the AAC encoders
+    audio_codec_combo.connect_changed(clone!(aac_options_box => move |combo| {
+        aac_options_box.set_visible(active_codec(combo).as_deref() == Some("aac"));
+    }));
+    // HE profiles and VBR modes are FDK only
+    aac_encoder_combo.connect_changed(clone!(aac_profile_combo, aac_mode_combo => move |combo| {
+        let fdk = combo.get_active_id().as_deref() == Some(AAC_FDK_ID);
+        if !fdk {
+            aac_profile_combo.set_active_id(aac_profile_id(AacProfile::Lc));
+            aac_mode_combo.set_active_id(AAC_CBR_ID);
+        }
+        aac_profile_combo.set_sensitive(fdk);
+        aac_mode_combo.set_sensitive(fdk);
+    }));
+    aac_mode_combo.connect_changed(clone!(aac_quality_adj => move |combo| {
+        if combo.get_active_id().as_deref() == Some(AAC_VBR_ID) {
+            aac_quality_adj.configure(4.0, 1.0, 5.0, 1.0, 1.0, 0.0);
+        } else {
+            aac_quality_adj.configure(128.0, 8.0, 320.0, 8.0, 32.0, 0.0);
+        }
+    }));
+    channel_ops_combo.connect_changed(clone!(balance_spin => move |combo| {
+        balance_spin.set_visible(combo.get_active_id().as_deref() == Some(BALANCE_ID));
+    }));
+    vocal_removal_check.connect_toggled(clone!(vocal_removal_scale => move |check| {
+        vocal_removal_scale.set_sensitive(check.get_active());
+    }));
+    silence_speedup_check.connect_toggled(clone!(silence_speedup_spin => move |check| {
+        silence_speedup_spin.set_sensitive(check.get_active());
+    }));
+    // presets have fixed parameters
+    reverb_combo.connect_changed(clone!(reverb_expander => move |combo| {
+        let custom = combo.get_active_id().as_deref() == Some(REVERB_CUSTOM_ID);
+        reverb_expander.set_sensitive(custom);
+        reverb_expander.set_expanded(custom);
+    }));
+
+    // last saved or opened session, the title shows an asterisk when the settings differ
+    update_conf();
+    let saved_project = Rc::new(RefCell::new(project::Project::new(session_config(
+        &conf.borrow(),
+    ))));
+    let project_file: Rc<RefCell<Option<PathBuf>>> = Rc::new(RefCell::new(None));
+
+    let has_unsaved_changes = Rc::new(clone!(conf, update_conf, saved_project => move || {
+        update_conf();
+        session_config(&conf.borrow()) != saved_project.borrow().config
+    }));
+
+    let refresh_title: Rc<dyn Fn()> = Rc::new(
+        clone!(window, project_file, has_unsaved_changes => move || {
+            let name = project_file.borrow().as_ref().map_or_else(
+                || String::from("Untitled"),
+                |path| path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            );
+            let marker = if has_unsaved_changes() { "*" } else { "" };
+            window.set_title(&format!("{}{} - Media Cutter", marker, name));
+        }),
+    );
+    refresh_title();
+    refresh_validation();
+
+    // the session is autosaved shortly after each change and offered back
+    // after a crash, the one left by the last run being read first
+    let session_recovery = recovery::Recovery::from_env();
+    let crashed_session = session_recovery
+        .as_ref()
+        .and_then(recovery::Recovery::pending);
+    let autosaver = Rc::new(RefCell::new(
+        session_recovery.clone().map(recovery::Autosaver::new),
+    ));
+    let autosave_debouncer = Rc::new(RefCell::new(recovery::Debouncer::new(
+        recovery::AUTOSAVE_DELAY,
+        recovery::AUTOSAVE_MAX_DELAY,
+    )));
+
+    let on_settings_changed: Rc<dyn Fn()> = Rc::new(
+        clone!(refresh_title, refresh_validation, record_change, autosave_debouncer => move || {
+            refresh_title();
+            refresh_validation();
+            record_change();
+            autosave_debouncer.borrow_mut().touch(Instant::now());
+        }),
+    );
+    if let Some(child) = window.get_child() {
+        connect_changes(&child, &on_settings_changed);
+    }
+
+    // returns whether the project was saved
+    let save_project = Rc::new(
+        clone!(window, conf, update_conf, saved_project, project_file, refresh_title, input_info => move |ask_file: bool| -> bool {
+            let current_file = project_file.borrow().clone();
+            let path = match current_file {
+                Some(ref path) if !ask_file => path.clone(),
+                _ => match ask_project_file(&window, gtk::FileChooserAction::Save, current_file.as_ref()) {
+                    Some(path) => path,
+                    None => return false,
+                },
+            };
+
+            update_conf();
+            let mut project = saved_project.borrow().clone();
+            let config = session_config(&conf.borrow());
+            // probed when the input was chosen, ffprobe is not run again
+            project.media_info = match *input_info.borrow() {
+                Some((ref input_file, ref info)) if *input_file == config.input_file => Some(info.clone()),
+                // still probing, the saved information is kept for the same input
+                _ if project.config.input_file == config.input_file => project.media_info.clone(),
+                _ => None,
+            };
+            project.config = config;
+            if let Err(e) = project::save(&path, &project) {
+                message_dialog!(window, gtk::MessageType::Error, &e);
+                return false;
+            }
+
+            *saved_project.borrow_mut() = project;
+            *project_file.borrow_mut() = Some(path);
+            refresh_title();
+            true
+        }),
+    );
+
+    // returns false when the user cancelled
+    let confirm_discard = Rc::new(
+        clone!(window, has_unsaved_changes, save_project => move || -> bool {
+            if !has_unsaved_changes() {
+                return true;
+            }
+            match ask_save_changes(&window) {
+                Some(true) => save_project(false),
+                Some(false) => true,
+                None => false,
+            }
+        }),
+    );
+
+    // written off the main loop by the autosaver
+    gtk::timeout_add(
+        AUTOSAVE_POLL_MS,
+        clone!(conf, update_conf, saved_project, project_file, autosaver, autosave_debouncer => move || {
+            if !autosave_debouncer.borrow_mut().poll(Instant::now()) {
+                return Continue(true);
+            }
+            if let Some(ref autosaver) = *autosaver.borrow() {
+                update_conf();
+                let mut project = saved_project.borrow().clone();
+                project.config = session_config(&conf.borrow());
+                autosaver.save(recovery::Session {
+                    project_file: project_file.borrow().clone(),
+                    project,
+                });
+            }
+            Continue(true)
+        }),
+    );
+
+    save_project_menu_item.connect_activate(clone!(save_project => move |_| {
+        save_project(false);
+    }));
+    save_project_as_menu_item.connect_activate(clone!(save_project => move |_| {
+        save_project(true);
+    }));
+
+    open_project_menu_item.connect_activate(
+        clone!(window, apply_conf, saved_project, project_file, refresh_title, confirm_discard => move |_| {
+            if !confirm_discard() {
+                return;
+            }
+            let path = match ask_project_file(&window, gtk::FileChooserAction::Open, None) {
+                Some(path) => path,
+                None => return,
+            };
+            let project = match project::load(&path) {
+                Ok(project) => project,
+                Err(e) => {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                    return;
+                }
+            };
+
+            // a relocated source is a change to save: the saved settings keep the old path
+            let open = clone!(window, apply_conf, saved_project, project_file, refresh_title => move |project: &project::Project, path: &Path, moved_source: Option<(&Path, Option<probe::MediaInfo>)>| {
+                let mut relocated = project.clone();
+                if let Some((input, info)) = moved_source {
+                    let warnings = relocated.relocate(input, info);
+                    if !warnings.is_empty() {
+                        message_dialog!(window, gtk::MessageType::Warning, &warnings.join("\n"));
+                    }
+                }
+
+                *saved_project.borrow_mut() = project.clone();
+                *project_file.borrow_mut() = Some(path.to_path_buf());
+                apply_conf(&relocated.config);
+                refresh_title();
+            });
+            if !project.source_exists() {
+                if let Some(input) = ask_moved_source(&window, &project, &path) {
+                    // the new source is probed off the main loop, it may be large
+                    probe_in_background(
+                        input.to_string_lossy().into_owned(),
+                        processing::CancelFlag::new(),
+                        move |info| open(&project, &path, Some((input.as_path(), info.ok()))),
+                    );
+                    return;
+                }
+            }
+            open(&project, &path, None);
+        }),
+    );
+
+    export_cut_list_menu_item.connect_activate(
+        clone!(window, conf, update_conf, saved_project => move |_| {
+            let (path, kind) = match ask_cut_list_file(&window) {
+                Some(choice) => choice,
+                None => return,
+            };
+
+            update_conf();
+            let conf = conf.borrow();
+            // without segments, the selection is the cut
+            let mut segments = saved_project.borrow().segments.clone();
+            if segments.is_empty() {
+                segments.push(project::Segment {
+                    from_time: conf.from_time,
+                    to_time: conf.to_time,
+                    name: String::from("Selection"),
+                });
+            }
+            let write = clone!(window => move |format| {
+                let path = if path.extension().is_none() {
+                    path.with_extension(cutlist::CutListFormat::extension(&format))
+                } else {
+                    path.clone()
+                };
+
+                let result = File::create(&path)
+                    .map_err(|e| format!("Could not create the cut list file.\nError: {}", e))
+                    .and_then(|mut file| cutlist::export(&segments, &format, &mut file));
+                if let Err(e) = result {
+                    message_dialog!(window, gtk::MessageType::Error, &e);
+                }
+            });
+            match kind {
+                cutlist::CutListKind::Csv => write(cutlist::CutListFormat::Csv),
+                cutlist::CutListKind::Edl => {
+                    probe_frame_rate(conf.input_file.clone(), move |fps| {
+                        write(cutlist::CutListFormat::Edl { fps })
+                    });
+                }
+                cutlist::CutListKind::FfmpegConcat => write(cutlist::CutListFormat::FfmpegConcat {
+                    input_file: conf.input_file.clone(),
+                }),
+            }
+        }),
+    );
+
+    import_cut_list_menu_item.connect_activate(
+        clone!(window, conf, update_conf, saved_project, start_secs_adj, end_secs_adj, input_duration => move |_| {
+            update_conf();
+            // the frame rate reads EDL timecodes
+            probe_frame_rate(
+                conf.borrow().input_file.clone(),
+                clone!(window, saved_project, start_secs_adj, end_secs_adj, input_duration => move |fps| {
+                    let parsed = match ask_cut_list_import(&window, fps) {
+                        Some(parsed) => parsed,
+                        None => return,
+                    };
+
+                    // chapters run until the next one, the last one until the end of the file
+                    let segments = cutlist::to_segments(&parsed.entries, input_duration.get().flatten());
+                    let first = match segments.first() {
+                        Some(first) => first.clone(),
+                        None => {
+                            message_dialog!(
+                                window,
+                                gtk::MessageType::Error,
+                                "Error: no segment could be made from the cut list, the duration of the input file is unknown."
+                            );
+                            return;
+                        }
+                    };
+                    {
+                        let mut project = saved_project.borrow_mut();
+                        project.segments = segments;
+                        if parsed.format == cutlist::ImportFormat::Timestamps {
+                            project.markers = cutlist::to_markers(&parsed.entries);
+                        }
+                    }
+                    start_secs_adj.set_value(first.from_time.num_milliseconds() as f64 / 1000.0);
+                    end_secs_adj.set_value(first.to_time.num_milliseconds() as f64 / 1000.0);
+                }),
+            );
+        }),
+    );
+
+    quit_menu_item.connect_activate(clone!(window => move |_| {
+        window.close();
+    }));
+
+    about_menu_item.connect_activate(move |_| {
+        dialog.run();
+        dialog.hide();
+    });
+
+    // the launched commands, on top of what the command line asked for,
+    // unless RUST_LOG filters decide what is logged
+    let verbose_level = move |verbose| {
+        if verbose {
+            log_level.max(LevelFilter::Debug)
+        } else {
+            log_level
+        }
+    };
+    if logging::env_filters().is_some() {
+        verbose_log_menu_item.set_sensitive(false);
+    } else {
+        let verbose = log_level >= LevelFilter::Debug
+            || recovery::state_dir().map_or(false, |dir| logging::verbose_chosen(&dir));
+        logging::set_level(verbose_level(verbose));
+        verbose_log_menu_item.set_active(verbose);
+        verbose_log_menu_item.connect_toggled(move |item| {
+            logging::set_level(verbose_level(item.get_active()));
+            if let Some(dir) = recovery::state_dir() {
+                if let Err(e) = logging::save_verbose(&dir, item.get_active()) {
+                    warn!("{}", e);
+                }
+            }
+        });
+    }
+
+    window.connect_delete_event(clone!(confirm_discard, autosaver => move |win, _| {
+        if !confirm_discard() {
+            return Inhibit(true);
+        }
+        if let Some(autosaver) = autosaver.borrow_mut().take() {
+            autosaver.shutdown();
+        }
+        win.destroy();
+        Inhibit(false)
+    }));
+
+    window.show_all();
+
+    if let (Some(session_recovery), Some(session)) = (session_recovery, crashed_session) {
+        if ask_restore_session(&window, &session) {
+            // the settings differ from the saved project, if any, as they did before the crash
+            if let Some(ref path) = session.project_file {
+                if let Ok(project) = project::load(path) {
+                    *saved_project.borrow_mut() = project;
+                    *project_file.borrow_mut() = Some(path.clone());
+                }
+            }
+            {
+                let mut project = saved_project.borrow_mut();
+                project.markers = session.project.markers;
+                project.segments = session.project.segments;
+            }
+            apply_conf(&session.project.config);
+            refresh_title();
+        } else {
+            session_recovery.discard();
+        }
+    }
+}
+
+// processing happens on a worker thread so that the window stays responsive,
+// `progress_bar` shows the encoding progress and `on_done` is then called back
+// from the main loop.
+fn run_in_background<F>(conf: Config, progress_bar: &gtk::ProgressBar, on_done: F)
+where
+    F: Fn(Result<processing::RunOutcome, String>) + 'static,
+{
+    enum Message {
+        Progress(Option<f64>, String),
+        Done(Result<processing::RunOutcome, String>),
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let progress_sender = sender.clone();
+        let result = processing::run_with_progress(&conf, |tracker| {
+            let _ = progress_sender.send(Message::Progress(tracker.fraction(), tracker.summary()));
+        });
+        // receiver is only dropped once a result has been received
+        let _ = sender.send(Message::Done(result));
+    });
+
+    let progress_bar = progress_bar.clone();
+    progress_bar.set_fraction(0.0);
+    progress_bar.set_text(None);
+    gtk::timeout_add(100, move || loop {
+        match receiver.try_recv() {
+            Ok(Message::Progress(fraction, summary)) => {
+                match fraction {
+                    Some(fraction) => progress_bar.set_fraction(fraction),
+                    // whole files are not always probed
+                    None => progress_bar.pulse(),
+                }
+                progress_bar.set_text(Some(summary.as_str()));
+                progress_bar.show();
+            }
+            Ok(Message::Done(result)) => {
+                progress_bar.hide();
+                on_done(result);
+                return Continue(false);
+            }
+            Err(mpsc::TryRecvError::Empty) => return Continue(true),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                progress_bar.hide();
+                on_done(Err(String::from(
+                    "Unexpected error: processing thread panicked.",
+                )));
+                return Continue(false);
+            }
+        }
+    });
+}
+
+// as `run_in_background`, for the jobs of `job_queue` run one after the other
+// `stop_button` is shown while the queue runs, it stops the running job and
+// skips the following ones
+fn run_queue_in_background<F>(
+    job_queue: queue::JobQueue,
+    progress_bar: &gtk::ProgressBar,
+    stop_button: &gtk::Button,
+    on_done: F,
+) where
+    F: Fn(Vec<Result<processing::RunOutcome, String>>) + 'static,
+{
+    enum Message {
+        Progress(usize, Option<f64>, String),
+        Done(Vec<Result<processing::RunOutcome, String>>),
+    }
+
+    let jobs = job_queue.len();
+    let cancel = processing::CancelFlag::new();
+    let stop_handler = stop_button.connect_clicked(clone!(cancel => move |button| {
+        cancel.cancel();
+        button.set_sensitive(false);
+    }));
+    stop_button.set_sensitive(true);
+    stop_button.show();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let progress_sender = sender.clone();
+        let results = job_queue.run(&cancel, |event| {
+            if let queue::JobEvent::Progress(index, tracker) = event {
+                let _ = progress_sender.send(Message::Progress(
+                    index,
+                    tracker.fraction(),
+                    tracker.summary(),
+                ));
+            }
+        });
+        // receiver is only dropped once the results have been received
+        let _ = sender.send(Message::Done(results));
+    });
+
+    let progress_bar = progress_bar.clone();
+    progress_bar.set_fraction(0.0);
+    progress_bar.set_text(None);
+    // the handler is dropped with the run, the next one connects its own
+    let stop_button = stop_button.clone();
+    let stop_handler = Cell::new(Some(stop_handler));
+    let finish = clone!(progress_bar => move || {
+        progress_bar.hide();
+        stop_button.hide();
+        if let Some(handler) = stop_handler.take() {
+            stop_button.disconnect(handler);
+        }
+    });
+    gtk::timeout_add(100, move || loop {
+        match receiver.try_recv() {
+            Ok(Message::Progress(index, fraction, summary)) => {
+                match fraction {
+                    Some(fraction) => {
+                        progress_bar.set_fraction((index as f64 + fraction) / jobs as f64)
+                    }
+                    None => progress_bar.pulse(),
+                }
+                progress_bar.set_text(Some(
+                    format!("{}/{}: {}", index + 1, jobs, summary).as_str(),
+                ));
+                progress_bar.show();
+            }
+            Ok(Message::Done(results)) => {
+                finish();
+                on_done(results);
+                return Continue(false);
+            }
+            Err(mpsc::TryRecvError::Empty) => return Continue(true),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                finish();
+                on_done(vec![Err(String::from(
+                    "Unexpected error: processing thread panicked.",
+                ))]);
+                return Continue(false);
+            }
+        }
+    });
+}
+
+// probes `input_file` on a worker thread, `on_done` is not called once `cancel` is set
+fn probe_in_background<F>(input_file: String, cancel: processing::CancelFlag, on_done: F)
+where
+    F: Fn(Result<probe::MediaInfo, String>) + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let worker_cancel = cancel.clone();
+    thread::spawn(move || {
+        let result = probe::MediaInfo::probe_cancellable(&input_file, &worker_cancel);
+        let _ = sender.send(result);
+    });
+
+    gtk::timeout_add(100, move || match receiver.try_recv() {
+        Err(mpsc::TryRecvError::Empty) if !cancel.is_cancelled() => Continue(true),
+        _ if cancel.is_cancelled() => Continue(false),
+        Ok(result) => {
+            on_done(result);
+            Continue(false)
+        }
+        Err(_) => {
+            on_done(Err(String::from("Error: ffprobe stopped unexpectedly.")));
+            Continue(false)
+        }
+    });
+}
+
+// frame rate of the first video stream of `input_file`, probed on a worker
+// thread. `cutlist::DEFAULT_FPS` when unknown.
+fn probe_frame_rate<F>(input_file: String, on_done: F)
+where
+    F: Fn(f64) + 'static,
+{
+    probe_in_background(input_file, processing::CancelFlag::new(), move |info| {
+        let fps = info
+            .ok()
+            .and_then(|info| {
+                info.streams
+                    .iter()
+                    .find(|stream| stream.kind == "video")
+                    .and_then(|stream| stream.frame_rate)
+            })
+            .unwrap_or(cutlist::DEFAULT_FPS);
+        on_done(fps);
+    });
+}
+
+// peaks are extracted on a worker thread, the view shows the progress meanwhile
+fn load_waveform(
+    view: &WaveformView,
+    conf: Config,
+    input_file: String,
+    duration: f64,
+    sample_rate: Option<u32>,
+) {
+    enum Message {
+        Progress(f64),
+        Done(Result<Vec<(f32, f32)>, String>),
+    }
+
+    let buckets = ((duration * PEAK_BUCKETS_PER_SECOND) as usize).max(MIN_PEAK_BUCKETS);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let progress_sender = sender.clone();
+        let result =
+            waveform::extract_peaks_with_progress(&conf, &input_file, buckets, |progress| {
+                let _ = progress_sender.send(Message::Progress(progress));
+            });
+        let _ = sender.send(Message::Done(result));
+    });
+
+    let load_id = view.begin_load();
+    let view = view.clone();
+    gtk::timeout_add(100, move || loop {
+        if !view.is_current_load(load_id) {
+            // another file was selected meanwhile
+            return Continue(false);
+        }
+
+        match receiver.try_recv() {
+            Ok(Message::Progress(progress)) => view.set_progress(progress),
+            Ok(Message::Done(Ok(peaks))) => {
+                view.set_peaks(peaks, duration, sample_rate);
+                return Continue(false);
+            }
+            // the waveform is a convenience, failing to show it is not worth a dialog
+            Ok(Message::Done(Err(_))) | Err(mpsc::TryRecvError::Disconnected) => {
+                view.clear();
+                return Continue(false);
+            }
+            Err(mpsc::TryRecvError::Empty) => return Continue(true),
+        }
+    });
+}
+
+// widgets of an audio track listed by `fill_audio_tracks`
+struct AudioTrackRow {
+    keep_check: gtk::CheckButton,
+    filter_check: gtk::CheckButton,
+    gain_scale: gtk::Scale,
+}
+
+impl AudioTrackRow {
+    // the filters apply to each track, or to the mix of the tracks at their gain
+    fn refresh_sensitivity(&self, mixing: bool) {
+        let keep = self.keep_check.get_active();
+        self.filter_check.set_sensitive(keep && !mixing);
+        self.gain_scale.set_sensitive(keep && mixing);
+    }
+}
+
+// a row per audio track of `info`, shown when there are several. The first
+// track is kept, as ffmpeg would.
+// one entry per video stream of the input, the first chosen. Hidden unless
+// there are several to choose from.
+fn fill_video_streams(combo: &gtk::ComboBoxText, info: Option<&probe::MediaInfo>) {
+    combo.remove_all();
+    let streams: Vec<&probe::StreamInfo> = info.map_or_else(Vec::new, |info| {
+        info.streams
+            .iter()
+            .filter(|stream| stream.kind == "video")
+            .collect()
+    });
+    combo.set_visible(streams.len() > 1);
+    if streams.len() < 2 {
+        return;
+    }
+
+    for (position, stream) in streams.iter().enumerate() {
+        let mut label = format!("Video {}", position + 1);
+        if let (Some(width), Some(height)) = (stream.width, stream.height) {
+            label.push_str(&format!(" ({}x{})", width, height));
+        }
+        combo.append(&position.to_string(), &label);
+    }
+    combo.set_active_id("0");
+}
+
+fn fill_audio_tracks(
+    tracks_box: &gtk::Box,
+    rows: &RefCell<Vec<AudioTrackRow>>,
+    mix_check: &gtk::CheckButton,
+    info: Option<&probe::MediaInfo>,
+) {
+    for row in rows.borrow_mut().drain(..) {
+        if let Some(row_box) = row.keep_check.get_parent() {
+            tracks_box.remove(&row_box);
+        }
+    }
+
+    let streams: Vec<&probe::StreamInfo> = info.map_or_else(Vec::new, |info| {
+        info.streams
+            .iter()
+            .filter(|stream| stream.kind == "audio")
+            .collect()
+    });
+    tracks_box.set_visible(streams.len() > 1);
+    if streams.len() < 2 {
+        return;
+    }
+
+    for (position, stream) in streams.iter().enumerate() {
+        let mut label = format!("Track {}", position + 1);
+        if let Some(ref language) = stream.language {
+            label.push_str(&format!(" ({})", language));
+        }
+        let row = AudioTrackRow {
+            keep_check: gtk::CheckButton::new_with_label(&label),
+            filter_check: gtk::CheckButton::new_with_label("apply filters"),
+            gain_scale: gtk::Scale::new_with_range(
+                gtk::Orientation::Horizontal,
+                MIN_TRACK_GAIN_DB,
+                MAX_TRACK_GAIN_DB,
+                0.5,
+            ),
+        };
+        row.keep_check.set_active(position == 0);
+        row.filter_check.set_active(true);
+        row.gain_scale.set_value(0.0);
+        row.gain_scale.set_size_request(120, -1);
+        row.gain_scale
+            .set_tooltip_text(Some("Gain of the track in the mix, in dB"));
+        row.refresh_sensitivity(mix_check.get_active());
+        let (filter_check, gain_scale) = (row.filter_check.clone(), row.gain_scale.clone());
+        row.keep_check
+            .connect_toggled(clone!(mix_check => move |check| {
+                let keep = check.get_active();
+                filter_check.set_sensitive(keep && !mix_check.get_active());
+                gain_scale.set_sensitive(keep && mix_check.get_active());
+            }));
+
+        let row_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+        row_box.add(&row.keep_check);
+        row_box.add(&row.filter_check);
+        row_box.add(&row.gain_scale);
+        tracks_box.add(&row_box);
+        row_box.show_all();
+        rows.borrow_mut().push(row);
+    }
+}
+
+// none when only the first track is kept with the filters: ffmpeg then picks
+// the track itself and the audio may be copied
+fn audio_tracks_from_rows(rows: &[AudioTrackRow]) -> Vec<TrackSpec> {
+    let tracks: Vec<TrackSpec> = rows
+        .iter()
+        .enumerate()
+        .map(|(position, row)| TrackSpec {
+            stream_index: position as u32,
+            filters: if row.filter_check.get_active() {
+                FilterChain::Configured
+            } else {
+                FilterChain::Unfiltered
+            },
+            keep: row.keep_check.get_active(),
+        })
+        .collect();
+    let is_default = tracks.iter().all(|track| {
+        track.keep == (track.stream_index == 0) && track.filters == FilterChain::Configured
+    });
+    if is_default {
+        Vec::new()
+    } else {
+        tracks
+    }
+}
+
+// the kept tracks at their gain
+fn downmix_tracks_from_rows(rows: &[AudioTrackRow]) -> Vec<(usize, f64)> {
+    rows.iter()
+        .enumerate()
+        .filter(|(_, row)| row.keep_check.get_active())
+        .map(|(position, row)| (position, row.gain_scale.get_value()))
+        .collect()
+}
+
+fn set_audio_track_rows(
+    rows: &[AudioTrackRow],
+    tracks: &[TrackSpec],
+    downmix_tracks: Option<&[(usize, f64)]>,
+) {
+    for (position, row) in rows.iter().enumerate() {
+        let track = tracks
+            .iter()
+            .find(|track| track.stream_index == position as u32);
+        let mixed = downmix_tracks.and_then(|mixed| {
+            mixed
+                .iter()
+                .find(|&&(stream_index, _)| stream_index == position)
+        });
+        match (track, mixed) {
+            (_, Some(&(_, gain_db))) => {
+                row.keep_check.set_active(true);
+                row.filter_check.set_active(true);
+                row.gain_scale.set_value(gain_db);
+            }
+            (Some(track), None) => {
+                row.keep_check.set_active(track.keep);
+                row.filter_check
+                    .set_active(track.filters == FilterChain::Configured);
+                row.gain_scale.set_value(0.0);
+            }
+            (None, None) => {
+                let is_default = tracks.is_empty() && downmix_tracks.is_none();
+                row.keep_check.set_active(is_default && position == 0);
+                row.filter_check.set_active(true);
+                row.gain_scale.set_value(0.0);
+            }
+        }
+    }
+}
+
+fn missing_files_errors(
+    input_file_entry: &gtk::Entry,
+    output_file_entry: Option<&gtk::Entry>,
+) -> Vec<&'static str> {
+    let mut errors = Vec::new();
+    if input_file_entry.get_text().unwrap() == "" {
+        errors.push("No input file specified");
+    }
+    if let Some(output_file_entry) = output_file_entry {
+        if output_file_entry.get_text().unwrap() == "" {
+            errors.push("No output file specified");
+        }
+    }
+    errors
+}
+
+fn fill_codec_combo(combo: &gtk::ComboBoxText, codecs: &[&str], default_label: &str) {
+    combo.append(DEFAULT_CODEC_ID, default_label);
+    for codec in codecs {
+        combo.append(*codec, codec);
+    }
+    combo.append("copy", "Copy as is");
+    combo.set_active_id(DEFAULT_CODEC_ID);
+}
+
+fn builtin_preset(id: &str) -> Option<presets::Preset> {
+    let index = id.strip_prefix(BUILTIN_PRESET_PREFIX)?.parse().ok()?;
+    presets::Preset::builtin().into_iter().nth(index)
+}
+
+fn aac_profile_id(profile: AacProfile) -> &'static str {
+    match profile {
+        AacProfile::Lc => "lc",
+        AacProfile::HeV1 => "he",
+        AacProfile::HeV2 => "he_v2",
+    }
+}
+
+fn aac_profile_from_id(id: Option<&str>) -> AacProfile {
+    match id {
+        Some("he") => AacProfile::HeV1,
+        Some("he_v2") => AacProfile::HeV2,
+        _ => AacProfile::Lc,
+    }
+}
+
+fn channel_op_id(op: Option<ChannelOp>) -> &'static str {
+    match op {
+        None => NO_CHANNEL_OP_ID,
+        Some(ChannelOp::SwapLR) => "swap",
+        Some(ChannelOp::Balance(_)) => BALANCE_ID,
+        Some(ChannelOp::ExtractLeft) => "extract_left",
+        Some(ChannelOp::ExtractRight) => "extract_right",
+        Some(ChannelOp::MonoFromLeft) => "mono_from_left",
+        Some(ChannelOp::MonoFromRight) => "mono_from_right",
+    }
+}
+
+fn channel_op_from_id(id: Option<&str>, balance: f64) -> Option<ChannelOp> {
+    match id? {
+        "swap" => Some(ChannelOp::SwapLR),
+        BALANCE_ID => Some(ChannelOp::Balance(balance)),
+        "extract_left" => Some(ChannelOp::ExtractLeft),
+        "extract_right" => Some(ChannelOp::ExtractRight),
+        "mono_from_left" => Some(ChannelOp::MonoFromLeft),
+        "mono_from_right" => Some(ChannelOp::MonoFromRight),
+        _ => None,
+    }
+}
+
+// numbers separated by spaces, commas or pipes, those not understood are kept
+// as NaN for validation to report
+fn parse_number_list(text: &str) -> Vec<f64> {
+    text.split(|c: char| c.is_whitespace() || c == ',' || c == '|')
+        .filter(|number| !number.is_empty())
+        .map(|number| number.parse().unwrap_or(f64::NAN))
+        .collect()
+}
+
+fn format_number_list(numbers: &[f64]) -> String {
+    numbers
+        .iter()
+        .map(f64::to_string)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// paths separated by semicolons, outputs already listed keep their settings
+fn parse_output_list(text: &str, outputs: &[OutputSpec]) -> Vec<OutputSpec> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            outputs
+                .iter()
+                .find(|output| output.path == path)
+                .cloned()
+                .unwrap_or_else(|| OutputSpec::new(path))
+        })
+        .collect()
+}
+
+fn format_output_list(outputs: &[OutputSpec]) -> String {
+    outputs
+        .iter()
+        .map(|output| output.path.as_str())
+        .collect::<Vec<&str>>()
+        .join("; ")
+}
+
+fn active_codec(combo: &gtk::ComboBoxText) -> Option<String> {
+    combo
+        .get_active_id()
+        .filter(|codec| codec != DEFAULT_CODEC_ID)
+}
+
+// codecs set by hand in a project are added to the choices
+fn set_active_codec(combo: &gtk::ComboBoxText, codec: Option<&str>) {
+    let id = codec.unwrap_or(DEFAULT_CODEC_ID);
+    if !combo.set_active_id(id) {
+        combo.append(id, id);
+        combo.set_active_id(id);
+    }
+}
+
+fn run_result_message(
+    result: Result<processing::RunOutcome, String>,
+) -> (gtk::MessageType, String) {
+    match result {
+        Ok(ref outcome) if !outcome.warnings.is_empty() => (
+            gtk::MessageType::Warning,
+            format!(
+                "Operation suceeded with warnings:\n\n{}{}{}",
+                outcome.warnings.join("\n"),
+                written_files_message(outcome),
+                copied_streams_message(outcome)
+            ),
+        ),
+        Ok(ref outcome) => (
+            gtk::MessageType::Info,
+            format!(
+                "Operation suceeded!{}{}",
+                written_files_message(outcome),
+                copied_streams_message(outcome)
+            ),
+        ),
+        Err(e) => (gtk::MessageType::Error, e),
+    }
+}
+
+// a single output is the one the user just picked, no need to repeat it
+fn written_files_message(outcome: &processing::RunOutcome) -> String {
+    if outcome.output_files.len() > 1 {
+        format!("\n\nWritten files:\n{}", outcome.output_files.join("\n"))
+    } else {
+        String::new()
+    }
+}
+
+fn copied_streams_message(outcome: &processing::RunOutcome) -> String {
+    if outcome.copied_streams.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nCopied streams:\n{}", outcome.copied_streams.join("\n"))
+    }
+}
+
+// the settings leave something out or take long, processing starts once confirmed
+fn confirm_warnings(window: &gtk::ApplicationWindow, warnings: &[String]) -> bool {
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &format!("{}\n\nProcess anyway?", warnings.join("\n")),
+    );
+    let response = dialog.run();
+    dialog.destroy();
+    response == gtk::ResponseType::Ok.into()
+}
+
+// failures and warnings are listed by job, in batch order, `item` naming one
+// of them and `items` several
+fn batch_result_message(
+    results: &[Result<processing::RunOutcome, String>],
+    item: &str,
+    items: &str,
+) -> (gtk::MessageType, String) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for (index, result) in results.iter().enumerate() {
+        match result {
+            Ok(outcome) => {
+                for warning in &outcome.warnings {
+                    warnings.push(format!("{} {}: {}", item, index + 1, warning));
+                }
+            }
+            Err(e) => errors.push(format!("{} {}: {}", item, index + 1, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        (
+            gtk::MessageType::Error,
+            format!(
+                "{} of {} {} failed:\n\n{}",
+                errors.len(),
+                results.len(),
+                items,
+                errors.join("\n")
+            ),
+        )
+    } else if !warnings.is_empty() {
+        (
+            gtk::MessageType::Warning,
+            format!(
+                "{} {} written with warnings:\n\n{}",
+                results.len(),
+                items,
+                warnings.join("\n")
+            ),
+        )
+    } else {
+        (
+            gtk::MessageType::Info,
+            format!("{} {} written!", results.len(), items),
+        )
+    }
+}
+
+// returns None when cancelled, Some(None) to remove the rotation tag
+fn ask_rotation(window: &gtk::ApplicationWindow) -> Option<Option<u16>> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Fix rotation metadata"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Apply", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let rotation_combo = gtk::ComboBoxText::new();
+    rotation_combo.append("remove", "Remove rotation tag");
+    for angle in &["0", "90", "180", "270"] {
+        rotation_combo.append(*angle, &format!("Set rotation to {}°", angle));
+    }
+    rotation_combo.set_active(0);
+    rotation_combo.set_margin_top(5);
+    rotation_combo.set_margin_bottom(5);
+    dialog.get_content_area().add(&rotation_combo);
+    rotation_combo.show();
+
+    let response = dialog.run();
+    let choice = rotation_combo.get_active_id();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Ok.into() {
+        return None;
+    }
+
+    match choice.as_deref() {
+        Some("remove") | None => Some(None),
+        Some(angle) => angle.parse().ok().map(Some),
+    }
+}
+
+// the tracks are listed above the pattern naming their outputs
+fn ask_track_pattern(window: &gtk::ApplicationWindow, sheet: &cue::CueSheet) -> Option<String> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Split tracks"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Split", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let album = match (&sheet.performer, &sheet.title) {
+        (Some(performer), Some(title)) => format!("{} – {}", performer, title),
+        (None, Some(title)) => title.clone(),
+        (Some(performer), None) => performer.clone(),
+        (None, None) => String::from("Untitled album"),
+    };
+    let mut summary = format!("{}, {} tracks:", album, sheet.tracks.len());
+    for track in &sheet.tracks {
+        summary.push_str(&format!(
+            "\n{:02}. {}",
+            track.number,
+            track.title.as_deref().unwrap_or("Untitled")
+        ));
+    }
+    let summary_label = gtk::Label::new(Some(summary.as_str()));
+    summary_label.set_halign(gtk::Align::Start);
+    summary_label.set_margin_top(5);
+
+    let pattern_entry = gtk::Entry::new();
+    pattern_entry.set_text(cue::DEFAULT_TRACK_PATTERN);
+    pattern_entry.set_tooltip_text(Some(
+        "{track}, {track:0N}, {title}, {artist} and {album} name each track, along with {stem}, {ext} and {parent} of the input",
+    ));
+    pattern_entry.set_activates_default(true);
+    pattern_entry.set_margin_top(5);
+    pattern_entry.set_margin_bottom(5);
+    dialog.set_default_response(gtk::ResponseType::Ok.into());
+
+    let content_area = dialog.get_content_area();
+    content_area.add(&summary_label);
+    content_area.add(&pattern_entry);
+    content_area.show_all();
+
+    let response = dialog.run();
+    let pattern = pattern_entry.get_text();
+    dialog.destroy();
+
+    if response != gtk::ResponseType::Ok.into() {
+        return None;
+    }
+    pattern
+}
+
+fn show_media_info(window: &gtk::ApplicationWindow, info: &probe::MediaInfo) {
+    const COPY_RESPONSE: i32 = 1;
+
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Input file info"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Copy as text", COPY_RESPONSE),
+            ("Close", gtk::ResponseType::Close.into()),
+        ],
+    );
+
+    let summary_grid = gtk::Grid::new();
+    summary_grid.set_row_spacing(5);
+    summary_grid.set_column_spacing(10);
+    summary_grid.set_margin_top(5);
+    summary_grid.set_margin_bottom(5);
+    for (row, (label, value)) in info.summary_rows().iter().enumerate() {
+        let label = gtk::Label::new(Some(*label));
+        label.set_halign(gtk::Align::Start);
+        let value = gtk::Label::new(Some(value.as_str()));
+        value.set_halign(gtk::Align::Start);
+        value.set_selectable(true);
+        summary_grid.attach(&label, 0, row as i32, 1, 1);
+        summary_grid.attach(&value, 1, row as i32, 1, 1);
+    }
+
+    let streams_store = gtk::ListStore::new(&[String::static_type(); 9]);
+    let columns: Vec<u32> = (0..probe::STREAM_COLUMNS.len() as u32).collect();
+    for row in info.stream_rows() {
+        let values: Vec<&dyn ToValue> = row.iter().map(|value| value as &dyn ToValue).collect();
+        streams_store.insert_with_values(None, &columns, &values);
+    }
+
+    let streams_view = gtk::TreeView::new_with_model(&streams_store);
+    for (i, title) in probe::STREAM_COLUMNS.iter().enumerate() {
+        let cell = gtk::CellRendererText::new();
+        let column = gtk::TreeViewColumn::new();
+        column.set_title(title);
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", i as i32);
+        streams_view.append_column(&column);
+    }
+
+    let content_area = dialog.get_content_area();
+    content_area.add(&summary_grid);
+    content_area.add(&streams_view);
+    content_area.show_all();
+
+    let report = info.to_text();
+    while dialog.run() == COPY_RESPONSE {
+        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&report);
+    }
+    dialog.destroy();
+}
+
+// the picture is written next to the output file, with a png extension
+fn ask_spectrogram_size(window: &gtk::ApplicationWindow) -> Option<(u32, u32)> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Audio spectrogram"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Save", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let size_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let width_spin = gtk::SpinButton::new_with_range(64.0, 8192.0, 64.0);
+    width_spin.set_value(1024.0);
+    let height_spin = gtk::SpinButton::new_with_range(64.0, 8192.0, 64.0);
+    height_spin.set_value(512.0);
+    size_box.pack_start(&gtk::Label::new("Size:"), false, true, 0);
+    size_box.pack_start(&width_spin, false, true, 0);
+    size_box.pack_start(&gtk::Label::new("x"), false, true, 0);
+    size_box.pack_start(&height_spin, false, true, 0);
+    size_box.set_margin_top(5);
+    size_box.set_margin_bottom(5);
+    dialog.get_content_area().add(&size_box);
+    size_box.show_all();
+
+    let response = dialog.run();
+    let size = (
+        width_spin.get_value_as_int() as u32,
+        height_spin.get_value_as_int() as u32,
+    );
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok.into() {
+        Some(size)
+    } else {
+        None
+    }
+}
+
+// the picture is written next to the output file, with a jpg extension
+// grid, frame width and whether times are shown
+fn ask_contact_sheet_settings(window: &gtk::ApplicationWindow) -> Option<((u32, u32), u32, bool)> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Contact sheet"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Save", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let grid_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let columns_spin = gtk::SpinButton::new_with_range(1.0, 20.0, 1.0);
+    columns_spin.set_value(4.0);
+    let rows_spin = gtk::SpinButton::new_with_range(1.0, 20.0, 1.0);
+    rows_spin.set_value(4.0);
+    grid_box.pack_start(&gtk::Label::new("Columns:"), false, true, 0);
+    grid_box.pack_start(&columns_spin, false, true, 0);
+    grid_box.pack_start(&gtk::Label::new("Rows:"), false, true, 0);
+    grid_box.pack_start(&rows_spin, false, true, 0);
+    let tile_width_spin = gtk::SpinButton::new_with_range(64.0, 1920.0, 16.0);
+    tile_width_spin.set_value(320.0);
+    grid_box.pack_start(&gtk::Label::new("Frame width:"), false, true, 0);
+    grid_box.pack_start(&tile_width_spin, false, true, 0);
+    let timestamps_check = gtk::CheckButton::new_with_label("Show times");
+    timestamps_check.set_tooltip_text(Some("Write the time of each frame in its corner"));
+    grid_box.pack_start(&timestamps_check, false, true, 0);
+    grid_box.set_margin_top(5);
+    grid_box.set_margin_bottom(5);
+    dialog.get_content_area().add(&grid_box);
+    grid_box.show_all();
+
+    let response = dialog.run();
+    let settings = (
+        (
+            columns_spin.get_value_as_int() as u32,
+            rows_spin.get_value_as_int() as u32,
+        ),
+        tile_width_spin.get_value_as_int() as u32,
+        timestamps_check.get_active(),
+    );
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok.into() {
+        Some(settings)
+    } else {
+        None
+    }
+}
+
+// device and seconds to record
+fn ask_noise_recording(
+    window: &gtk::ApplicationWindow,
+    devices: &[capture::Device],
+) -> Option<(capture::Device, i64)> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Record noise sample"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Record", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let settings_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let device_combo = gtk::ComboBoxText::new();
+    for (index, device) in devices.iter().enumerate() {
+        device_combo.append(index.to_string().as_str(), &device.description);
+    }
+    device_combo.set_active(0);
+    let seconds_spin = gtk::SpinButton::new_with_range(1.0, 60.0, 1.0);
+    seconds_spin.set_value(5.0);
+    settings_box.pack_start(&device_combo, false, true, 0);
+    settings_box.pack_start(&gtk::Label::new("Seconds:"), false, true, 0);
+    settings_box.pack_start(&seconds_spin, false, true, 0);
+    settings_box.set_margin_top(5);
+    settings_box.set_margin_bottom(5);
+    dialog.get_content_area().add(&settings_box);
+    settings_box.show_all();
+
+    let response = dialog.run();
+    let device = device_combo
+        .get_active_id()
+        .and_then(|id| id.parse::<usize>().ok())
+        .and_then(|index| devices.get(index).cloned());
+    let seconds = i64::from(seconds_spin.get_value_as_int());
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok.into() {
+        device.map(|device| (device, seconds))
+    } else {
+        None
+    }
+}
+
+// lists the audio inputs on a worker thread
+fn list_devices_in_background<F>(conf: Config, on_done: F)
+where
+    F: Fn(Result<Vec<capture::Device>, String>) + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(capture::list_devices(&conf));
+    });
+
+    gtk::timeout_add(100, move || match receiver.try_recv() {
+        Err(mpsc::TryRecvError::Empty) => Continue(true),
+        Ok(result) => {
+            on_done(result);
+            Continue(false)
+        }
+        Err(mpsc::TryRecvError::Disconnected) => {
+            on_done(Err(String::from(
+                "Unexpected error: device listing thread panicked.",
+            )));
+            Continue(false)
+        }
+    });
+}
+
+// records in the background, counting the seconds down in a dialog whose
+// button stops the recording
+fn record_noise_sample<F>(
+    window: &gtk::ApplicationWindow,
+    conf: Config,
+    device: capture::Device,
+    seconds: i64,
+    output_file: String,
+    on_recorded: F,
+) where
+    F: Fn(String) + 'static,
+{
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Recording noise sample"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[("Cancel", gtk::ResponseType::Cancel.into())],
+    );
+    let cancel = processing::CancelFlag::new();
+    // closing the dialog stops the recording as well
+    dialog.connect_response(clone!(cancel => move |_, _| {
+        cancel.cancel();
+    }));
+    let countdown_label = gtk::Label::new(format!("Keep quiet… {} s left", seconds).as_str());
+    countdown_label.set_margin_top(10);
+    countdown_label.set_margin_bottom(10);
+    countdown_label.set_margin_start(10);
+    countdown_label.set_margin_end(10);
+    dialog.get_content_area().add(&countdown_label);
+    dialog.show_all();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(clone!(output_file, cancel => move || {
+        let _ = sender.send(capture::record_cancellable(
+            &conf,
+            &device,
+            Duration::seconds(seconds),
+            &output_file,
+            &cancel,
+        ));
+    }));
+
+    let window = window.clone();
+    let started = std::time::Instant::now();
+    gtk::timeout_add(100, move || {
+        let left = seconds - started.elapsed().as_secs() as i64;
+        countdown_label.set_text(&format!("Keep quiet… {} s left", left.max(0)));
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return Continue(true),
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err(String::from("Unexpected error: recording thread panicked."))
+            }
+        };
+        dialog.destroy();
+        match result {
+            Ok(()) => on_recorded(output_file.clone()),
+            Err(_) if cancel.is_cancelled() => {}
+            Err(e) => message_dialog!(window, gtk::MessageType::Error, &e),
+        }
+        Continue(false)
+    });
+}
+
+// the picture shown for the whole audio, `None` when cancelled
+fn ask_still_image(window: &gtk::ApplicationWindow) -> Option<PathBuf> {
+    let file_chooser = gtk::FileChooserNative::new(
+        Some("Select the picture"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        Some("Select"),
+        Some("Cancel"),
+    );
+    let filter = gtk::FileFilter::new();
+    filter.set_name("Pictures");
+    filter.add_mime_type("image/*");
+    file_chooser.add_filter(&filter);
+
+    let mut path = None;
+    if file_chooser.run() == gtk::ResponseType::Accept.into() {
+        path = file_chooser.get_filename();
+    }
+    file_chooser.destroy();
+    path
+}
+
+// the companion video and the time of its frame, in seconds from its start
+fn ask_thumbnail_frame(window: &gtk::ApplicationWindow) -> Option<(PathBuf, Duration)> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Cover from a video frame"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Embed", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let settings_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let video_button =
+        gtk::FileChooserButton::new("Select the video", gtk::FileChooserAction::Open);
+    let filter = gtk::FileFilter::new();
+    filter.set_name("Videos");
+    filter.add_mime_type("video/*");
+    video_button.add_filter(&filter);
+    let seconds_spin = gtk::SpinButton::new_with_range(0.0, 86_400.0, 0.1);
+    seconds_spin.set_digits(1);
+    settings_box.pack_start(&video_button, true, true, 0);
+    settings_box.pack_start(&gtk::Label::new("Seconds:"), false, true, 0);
+    settings_box.pack_start(&seconds_spin, false, true, 0);
+    settings_box.set_margin_top(5);
+    settings_box.set_margin_bottom(5);
+    dialog.get_content_area().add(&settings_box);
+    settings_box.show_all();
+
+    let response = dialog.run();
+    let video_file = video_button.get_filename();
+    let time = Duration::milliseconds((seconds_spin.get_value() * 1000.0).round() as i64);
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok.into() {
+        video_file.map(|video_file| (video_file, time))
+    } else {
+        None
+    }
+}
+
+// the video is written next to the output file, with an mp4 extension
+fn ask_visualize_settings(window: &gtk::ApplicationWindow) -> Option<(VisualStyle, (u32, u32))> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Audio visualisation"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Save", gtk::ResponseType::Ok.into()),
+        ],
+    );
+
+    let settings_box = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    let style_combo = gtk::ComboBoxText::new();
+    style_combo.append("waves", "Waves");
+    style_combo.append("spectrum", "Spectrum");
+    style_combo.append("cqt", "Notes spectrum");
+    style_combo.set_active(0);
+    let width_spin = gtk::SpinButton::new_with_range(64.0, 3840.0, 16.0);
+    width_spin.set_value(1280.0);
+    let height_spin = gtk::SpinButton::new_with_range(64.0, 2160.0, 16.0);
+    height_spin.set_value(720.0);
+    settings_box.pack_start(&style_combo, false, true, 0);
+    settings_box.pack_start(&gtk::Label::new("Size:"), false, true, 0);
+    settings_box.pack_start(&width_spin, false, true, 0);
+    settings_box.pack_start(&gtk::Label::new("x"), false, true, 0);
+    settings_box.pack_start(&height_spin, false, true, 0);
+    settings_box.set_margin_top(5);
+    settings_box.set_margin_bottom(5);
+    dialog.get_content_area().add(&settings_box);
+    settings_box.show_all();
+
+    let response = dialog.run();
+    let style = match style_combo.get_active_id().as_deref() {
+        Some("spectrum") => VisualStyle::Spectrum,
+        Some("cqt") => VisualStyle::Cqt,
+        _ => VisualStyle::Waves,
+    };
+    let resolution = (
+        width_spin.get_value_as_int() as u32,
+        height_spin.get_value_as_int() as u32,
+    );
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Ok.into() {
+        Some((style, resolution))
+    } else {
+        None
+    }
+}
+
+fn set_busy(widgets: &[&gtk::Widget], busy: bool) {
+    for widget in widgets {
+        widget.set_sensitive(!busy);
+    }
+}
+
+// settings that make up a session, the kind of run and the machine settings
+// are not part of them
+fn session_config(conf: &Config) -> Config {
+    Config {
+        operation: Operation::Process,
+        preview: false,
+        ffmpeg_path: None,
+        temp_dir: None,
+        loglevel: None,
+        ..conf.clone()
+    }
+}
+
+// calls `on_change` whenever a setting is edited in `widget` or its children
+fn connect_changes(widget: &gtk::Widget, on_change: &Rc<dyn Fn()>) {
+    if let Some(button) = widget.downcast_ref::<gtk::ToggleButton>() {
+        let on_change = on_change.clone();
+        button.connect_toggled(move |_| on_change());
+    } else if let Some(entry) = widget.downcast_ref::<gtk::Entry>() {
+        // spin buttons included
+        let on_change = on_change.clone();
+        entry.connect_changed(move |_| on_change());
+    } else if let Some(combo) = widget.downcast_ref::<gtk::ComboBox>() {
+        let on_change = on_change.clone();
+        combo.connect_changed(move |_| on_change());
+    } else if let Some(range) = widget.downcast_ref::<gtk::Range>() {
+        let on_change = on_change.clone();
+        range.connect_value_changed(move |_| on_change());
+    } else if let Some(container) = widget.downcast_ref::<gtk::Container>() {
+        for child in container.get_children() {
+            connect_changes(&child, on_change);
+        }
+    }
+}
+
+// returns None when cancelled, Some(true) to save first
+fn ask_save_changes(window: &gtk::ApplicationWindow) -> Option<bool> {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        "The project has unsaved changes. Save them first?",
+    );
+    dialog.add_buttons(&[
+        ("Discard", gtk::ResponseType::No.into()),
+        ("Cancel", gtk::ResponseType::Cancel.into()),
+        ("Save", gtk::ResponseType::Yes.into()),
+    ]);
+    let response = dialog.run();
+    dialog.destroy();
+
+    if response == gtk::ResponseType::Yes.into() {
+        Some(true)
+    } else if response == gtk::ResponseType::No.into() {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn ask_restore_session(window: &gtk::ApplicationWindow, session: &recovery::Session) -> bool {
+    let name = session.project_file.as_ref().map_or_else(
+        || String::from("the untitled project"),
+        |path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        },
+    );
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        &format!(
+            "Media Cutter did not close properly. Restore the unsaved settings of {}?",
+            name
+        ),
+    );
+    dialog.add_buttons(&[
+        ("Discard", gtk::ResponseType::No.into()),
+        ("Restore", gtk::ResponseType::Yes.into()),
+    ]);
+    let response = dialog.run();
+    dialog.destroy();
+
+    response == gtk::ResponseType::Yes.into()
+}
+
+fn ask_project_file(
+    window: &gtk::ApplicationWindow,
+    action: gtk::FileChooserAction,
+    current: Option<&PathBuf>,
+) -> Option<PathBuf> {
+    let title = if action == gtk::FileChooserAction::Save {
+        "Save project"
+    } else {
+        "Open project"
+    };
+    let file_chooser = gtk::FileChooserNative::new(
+        Some(title),
+        Some(window),
+        action,
+        Some("Select"),
+        Some("Cancel"),
+    );
+
+    let filter = gtk::FileFilter::new();
+    filter.set_name(format!("Media Cutter projects (*.{})", project::PROJECT_EXTENSION).as_str());
+    filter.add_pattern(&format!("*.{}", project::PROJECT_EXTENSION));
+    file_chooser.add_filter(&filter);
+    file_chooser.set_do_overwrite_confirmation(true);
+    if let Some(current) = current {
+        file_chooser.set_filename(current);
+    }
+
+    let mut path = None;
+    if file_chooser.run() == gtk::ResponseType::Accept.into() {
+        path = file_chooser.get_filename();
+    }
+    file_chooser.destroy();
+
+    path.map(|path| {
+        if action == gtk::FileChooserAction::Save && path.extension().is_none() {
+            path.with_extension(project::PROJECT_EXTENSION)
+        } else {
+            path
+        }
+    })
+}
+
+// native choosers take no extra widget: the format is the one of the extension
+// typed, or else of the filter shown
+fn ask_cut_list_file(window: &gtk::ApplicationWindow) -> Option<(PathBuf, cutlist::CutListKind)> {
+    let file_chooser = gtk::FileChooserNative::new(
+        Some("Export cut list"),
+        Some(window),
+        gtk::FileChooserAction::Save,
+        Some("Export"),
+        Some("Cancel"),
+    );
+    file_chooser.set_do_overwrite_confirmation(true);
+
+    let filters: Vec<_> = [
+        (
+            cutlist::CutListKind::Csv,
+            "CSV (start, end, label in seconds)",
+        ),
+        (cutlist::CutListKind::Edl, "EDL (CMX3600 timecodes)"),
+        (cutlist::CutListKind::FfmpegConcat, "ffmpeg concat script"),
+    ]
+    .iter()
+    .map(|&(kind, name)| {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(name);
+        filter.add_pattern(&format!("*.{}", kind.extension()));
+        file_chooser.add_filter(&filter);
+        (kind, filter)
+    })
+    .collect();
+
+    let mut choice = None;
+    if file_chooser.run() == gtk::ResponseType::Accept.into() {
+        if let Some(path) = file_chooser.get_filename() {
+            let kind = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .and_then(cutlist::CutListKind::from_extension)
+                .or_else(|| {
+                    let shown = file_chooser.get_filter()?;
+                    filters
+                        .iter()
+                        .find(|(_, filter)| *filter == shown)
+                        .map(|&(kind, _)| kind)
+                })
+                .unwrap_or(cutlist::CutListKind::Csv);
+            choice = Some((path, kind));
+        }
+    }
+    file_chooser.destroy();
+    choice
+}
+
+// the list is pasted or loaded from a file, and parsed again on each change
+// to preview its entries
+fn ask_cut_list_import(
+    window: &gtk::ApplicationWindow,
+    fps: f64,
+) -> Option<cutlist::ParsedCutList> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("Import cut list"),
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Import", gtk::ResponseType::Ok.into()),
+        ],
+    );
+    dialog.set_default_size(500, 450);
+    dialog.set_response_sensitive(gtk::ResponseType::Ok.into(), false);
+
+    let text_view = gtk::TextView::new();
+    let buffer = text_view.get_buffer().expect("text view without buffer");
+    let text_window = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    text_window.set_min_content_height(150);
+    text_window.add(&text_view);
+
+    let load_button = gtk::Button::new_with_label("Load file…");
+    load_button.set_halign(gtk::Align::Start);
+    load_button.set_margin_top(5);
+
+    let preview_store = gtk::ListStore::new(&[String::static_type(); 3]);
+    let preview_view = gtk::TreeView::new_with_model(&preview_store);
+    for (i, title) in ["Start", "End", "Label"].iter().enumerate() {
+        let cell = gtk::CellRendererText::new();
+        let column = gtk::TreeViewColumn::new();
+        column.set_title(title);
+        column.pack_start(&cell, true);
+        column.add_attribute(&cell, "text", i as i32);
+        preview_view.append_column(&column);
+    }
+    let preview_window =
+        gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    preview_window.set_min_content_height(150);
+    preview_window.set_vexpand(true);
+    preview_window.set_margin_top(5);
+    preview_window.add(&preview_view);
+
+    let status_label = gtk::Label::new(None);
+    status_label.set_halign(gtk::Align::Start);
+    status_label.set_line_wrap(true);
+    status_label.set_selectable(true);
+    status_label.set_margin_top(5);
+    status_label.set_margin_bottom(5);
+
+    let parsed: Rc<RefCell<Option<cutlist::ParsedCutList>>> = Rc::new(RefCell::new(None));
+    buffer.connect_changed(
+        clone!(dialog, preview_store, status_label, parsed => move |buffer| {
+            let text = buffer
+                .get_text(&buffer.get_start_iter(), &buffer.get_end_iter(), false)
+                .unwrap_or_default();
+            let seconds = |time: Duration| format!("{:.3}", time.num_milliseconds() as f64 / 1000.0);
+
+            preview_store.clear();
+            let result = cutlist::parse(&text, None, fps);
+            match result {
+                Ok(ref list) => {
+                    for entry in &list.entries {
+                        let end = entry.end.map_or_else(|| String::from("next"), &seconds);
+                        preview_store.insert_with_values(
+                            None,
+                            &[0, 1, 2],
+                            &[&seconds(entry.start) as &dyn ToValue, &end, &entry.label],
+                        );
+                    }
+                    let mut status = format!("{} entries read as {}.", list.entries.len(), list.format);
+                    for warning in &list.warnings {
+                        status.push_str(&format!("\nSkipped: {}", warning));
+                    }
+                    status_label.set_text(&status);
+                }
+                Err(ref e) if !text.trim().is_empty() => status_label.set_text(e),
+                Err(_) => status_label.set_text(""),
+            }
+            dialog.set_response_sensitive(gtk::ResponseType::Ok.into(), result.is_ok());
+            *parsed.borrow_mut() = result.ok();
+        }),
+    );
+
+    load_button.connect_clicked(clone!(dialog, buffer => move |_| {
+        let file_chooser = gtk::FileChooserNative::new(
+            Some("Load cut list"),
+            Some(&dialog),
+            gtk::FileChooserAction::Open,
+            Some("Load"),
+            Some("Cancel"),
+        );
+        let path = if file_chooser.run() == gtk::ResponseType::Accept.into() {
+            file_chooser.get_filename()
+        } else {
+            None
+        };
+        file_chooser.destroy();
+
+        if let Some(path) = path {
+            match fs::read_to_string(&path) {
+                Ok(text) => buffer.set_text(&text),
+                Err(e) => {
+                    let message = format!("Could not read the cut list.\nError: {}", e);
+                    message_dialog!(dialog, gtk::MessageType::Error, &message);
+                }
+            }
+        }
+    }));
+
+    let content_area = dialog.get_content_area();
+    content_area.add(&text_window);
+    content_area.add(&load_button);
+    content_area.add(&preview_window);
+    content_area.add(&status_label);
+    content_area.show_all();
+
+    let response = dialog.run();
+    dialog.destroy();
+    if response != gtk::ResponseType::Ok.into() {
+        return None;
+    }
+    parsed.replace(None)
+}
+
+// returns None to keep the missing source
+fn ask_moved_source(
+    window: &gtk::ApplicationWindow,
+    project: &project::Project,
+    project_path: &Path,
+) -> Option<PathBuf> {
+    let dialog = gtk::MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Warning,
+        gtk::ButtonsType::None,
+        &format!(
+            "The source file of the project was not found:\n{}\n\nLocate it?",
+            project.config.input_file
+        ),
+    );
+    dialog.add_buttons(&[
+        ("Keep missing", gtk::ResponseType::Cancel.into()),
+        ("Locate…", gtk::ResponseType::Ok.into()),
+    ]);
+    let response = dialog.run();
+    dialog.destroy();
+    if response != gtk::ResponseType::Ok.into() {
+        return None;
+    }
+
+    let file_chooser = gtk::FileChooserNative::new(
+        Some("Locate the source file"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        Some("Select"),
+        Some("Cancel"),
+    );
+    match project.find_moved_source(project_path) {
+        Some(candidate) => {
+            file_chooser.set_filename(&candidate);
+        }
+        None => {
+            if let Some(directory) = project_path.parent() {
+                file_chooser.set_current_folder(directory);
+            }
+        }
+    }
+    let mut path = None;
+    if file_chooser.run() == gtk::ResponseType::Accept.into() {
+        path = file_chooser.get_filename();
+    }
+    file_chooser.destroy();
+    path
+}
+
+// `on_selected` gets the paths chosen by the user, several with `select_multiple` only,
+// and is not called when cancelled. The native chooser goes through the desktop portal
+// inside a sandbox: its paths may then be documents under /run/user/<uid>/doc, which
+// are regular files for ffmpeg and must be kept as they are.
+fn handle_select_file<F>(
+    window: &gtk::ApplicationWindow,
+    dialog_action: gtk::FileChooserAction,
+    filter: Option<&gtk::FileFilter>,
+    select_multiple: bool,
+    on_selected: F,
+) where
+    F: Fn(Vec<PathBuf>) + 'static,
+{
+    let file_chooser = gtk::FileChooserNative::new(
+        Some("Select File"),
+        Some(window),
+        dialog_action,
+        Some("Select"),
+        Some("Cancel"),
+    );
+    file_chooser.set_modal(true);
+
+    if let Some(filter) = filter {
+        file_chooser.add_filter(filter);
+    }
+    file_chooser.set_select_multiple(select_multiple);
+
+    // nothing else holds the chooser while it is shown
+    let keep_alive = RefCell::new(Some(file_chooser.clone()));
+    file_chooser.connect_response(move |file_chooser, response| {
+        let paths = if response == gtk::ResponseType::Accept.into() {
+            file_chooser.get_filenames()
+        } else {
+            Vec::new()
+        };
+        file_chooser.destroy();
+        if !paths.is_empty() {
+            on_selected(paths);
+        }
+        keep_alive.borrow_mut().take();
+    });
+    file_chooser.show();
+}
+
+// entries and settings hold text: a lossy conversion would name another file
+fn path_texts(paths: &[PathBuf]) -> Result<Vec<String>, String> {
+    paths
+        .iter()
+        .map(|path| {
+            path.to_str().map(String::from).ok_or_else(|| {
+                format!(
+                    "Error: the name of {} is not valid UTF-8, rename the file to use it.",
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+// the first chosen file goes in `entry`
+fn set_chosen_path(window: &gtk::ApplicationWindow, entry: &gtk::Entry, paths: &[PathBuf]) {
+    match path_texts(paths) {
+        Ok(texts) => {
+            if let Some(text) = texts.first() {
+                entry.set_text(text);
+            }
+        }
+        Err(e) => {
+            let window = window.clone();
+            message_dialog!(window, gtk::MessageType::Error, &e);
+        }
+    }
+}
+
+// files handed over by the desktop portal, under /run/user/<uid>/doc/<id>: their
+// folder only holds the chosen files, nothing else can be written there
+fn is_portal_document(path: &Path) -> bool {
+    path.starts_with("/run/user")
+        && path
+            .components()
+            .nth(4)
+            .map_or(false, |component| component.as_os_str() == "doc")
+}
+
+// the outputs would go next to portal documents, see `batch_jobs`
+fn needs_output_folder(conf: &Config, inputs: &[String]) -> bool {
+    conf.output_pattern.is_none()
+        && inputs
+            .iter()
+            .any(|input| is_portal_document(Path::new(input)))
+}
+
+// each whole input with the current settings, outputs named by the output pattern or
+// else written in `output_folder`, next to their input by default, with the
+// extension of the output file
+fn batch_jobs(conf: &Config, inputs: &[String], output_folder: Option<&str>) -> Vec<Config> {
+    let pattern = conf.output_pattern.clone().unwrap_or_else(|| {
+        let folder = output_folder.unwrap_or("{parent}");
+        match Path::new(&conf.output_file).extension() {
+            Some(extension) => format!("{}/{{stem}}_cut.{}", folder, extension.to_string_lossy()),
+            None => format!("{}/{{stem}}_cut.{{ext}}", folder),
+        }
+    });
+    inputs
+        .iter()
+        .map(|input| Config {
+            preview: false,
+            process_whole_file: true,
+            input_file: input.clone(),
+            output_pattern: Some(pattern.clone()),
+            ..conf.clone()
+        })
+        .collect()
+}
+
+// the jobs are confirmed then run one after the other
+fn start_batch<F>(
+    window: &gtk::ApplicationWindow,
+    progress_bar: &gtk::ProgressBar,
+    stop_button: &gtk::Button,
+    jobs: Vec<Config>,
+    resume: bool,
+    set_window_busy: &Rc<F>,
+) where
+    F: Fn(bool) + 'static,
+{
+    if !confirm_batch(window, &jobs) {
+        return;
+    }
+    let mut job_queue = queue::JobQueue::new();
+    for job in jobs {
+        job_queue.push(job);
+    }
+    if let Some(manifest_file) = job_queue.default_manifest_file() {
+        job_queue.set_manifest_file(manifest_file);
+        job_queue.set_resume(resume);
+    }
+    set_window_busy(true);
+    run_queue_in_background(
+        job_queue,
+        progress_bar,
+        stop_button,
+        clone!(window, set_window_busy => move |results| {
+            set_window_busy(false);
+            let (message_type, message) =
+                batch_result_message(&results, "File", "files");
+            message_dialog!(window, message_type, &message);
+        }),
+    );
+}
+
+fn confirm_batch(window: &gtk::ApplicationWindow, jobs: &[Config]) -> bool {
+    let mut message = format!("Process {} files with the current settings?\n", jobs.len());
+    for (index, job) in jobs.iter().enumerate() {
+        message.push_str(&format!(
+            "\n{} → {}",
+            job.input_file,
+            job.with_output_pattern(index + 1).output_file
+        ));
+    }
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::OkCancel,
+        &message,
+    );
+    let response = dialog.run();
+    dialog.destroy();
+    response == gtk::ResponseType::Ok.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_toggles_sensitivity() {
+        if gtk::init().is_err() {
+            // no display available
+            return;
+        }
+
+        let entry = gtk::Entry::new();
+        let button = gtk::Button::new();
+        let widgets: [&gtk::Widget; 2] = [entry.upcast_ref(), button.upcast_ref()];
+
+        set_busy(&widgets, true);
+        assert!(!entry.get_sensitive());
+        assert!(!button.get_sensitive());
+
+        set_busy(&widgets, false);
+        assert!(entry.get_sensitive());
+        assert!(button.get_sensitive());
+    }
+
+    #[test]
+    fn chosen_path_texts() {
+        assert_eq!(path_texts(&[]), Ok(Vec::new()));
+        assert_eq!(
+            path_texts(&[
+                PathBuf::from("/media/talk.mp4"),
+                PathBuf::from("/media/été.wav")
+            ]),
+            Ok(vec![
+                String::from("/media/talk.mp4"),
+                String::from("/media/été.wav")
+            ])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_path_texts() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let latin1 = PathBuf::from(OsStr::from_bytes(b"/media/\xe9t\xe9.wav"));
+        let error = path_texts(&[PathBuf::from("/media/talk.mp4"), latin1]).unwrap_err();
+        assert!(error.contains("not valid UTF-8"));
+    }
+
+    #[test]
+    fn batch_job_outputs() {
+        let mut conf = Config::new();
+        conf.input_file = String::from("/media/first.mp4");
+        conf.output_file = String::from("/exports/first.webm");
+        conf.preview = true;
+        let inputs = [
+            String::from("/media/talk.mp4"),
+            String::from("/media/q&a.mp4"),
+        ];
+
+        let jobs = batch_jobs(&conf, &inputs, None);
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[1].input_file, "/media/q&a.mp4");
+        assert!(!jobs[1].preview);
+        // the selection was made on another file
+        assert!(jobs[1].process_whole_file);
+        assert_eq!(
+            jobs[0].with_output_pattern(1).output_file,
+            "/media/talk_cut.webm"
+        );
+
+        // the output pattern of the settings wins
+        conf.output_pattern = Some(String::from("/exports/{index:02}.{ext}"));
+        let jobs = batch_jobs(&conf, &inputs, None);
+        assert_eq!(
+            jobs[1].with_output_pattern(2).output_file,
+            "/exports/02.mp4"
+        );
+    }
+
+    #[test]
+    fn portal_document_paths() {
+        // what the chooser returns inside a sandbox
+        let document = "/run/user/1000/doc/1a2b3c4d/talk.mp4";
+        let inputs = path_texts(&[PathBuf::from(document)]).unwrap();
+        assert_eq!(inputs, vec![String::from(document)]);
+
+        assert!(is_portal_document(Path::new(document)));
+        assert!(!is_portal_document(Path::new("/run/user/1000/talk.mp4")));
+        assert!(!is_portal_document(Path::new("/media/doc/talk.mp4")));
+
+        // nothing can be written next to the document, another folder is asked for
+        let mut conf = Config::new();
+        conf.output_file = String::from("/exports/talk.webm");
+        assert!(needs_output_folder(&conf, &inputs));
+        let jobs = batch_jobs(&conf, &inputs, Some("/home/me/Videos"));
+        assert_eq!(jobs[0].input_file, document);
+        assert_eq!(
+            jobs[0].with_output_pattern(1).output_file,
+            "/home/me/Videos/talk_cut.webm"
+        );
+
+        conf.output_pattern = Some(String::from("/exports/{index:02}.{ext}"));
+        assert!(!needs_output_folder(&conf, &inputs));
+    }
 }
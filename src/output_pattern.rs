@@ -0,0 +1,468 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+type Result<T> = std::result::Result<T, String>;
+
+// device names Windows reserves in every directory, whatever the extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters allowed in derived file names, see `sanitize_filename`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FilenameCharset {
+    /// Names valid on Windows, macOS and Linux alike, for outputs copied
+    /// across systems or written to FAT and NTFS drives.
+    Portable,
+    /// Anything but the path separator, as Linux and macOS allow.
+    Unix,
+}
+
+impl FilenameCharset {
+    /// `name` with the characters this charset forbids replaced by `_`.
+    pub fn sanitize(self, name: &str) -> String {
+        let forbidden = |c: char| match self {
+            FilenameCharset::Portable => {
+                c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+            }
+            FilenameCharset::Unix => c == '/' || c == '\0',
+        };
+        let mut sanitized: String = name
+            .chars()
+            .map(|c| if forbidden(c) { '_' } else { c })
+            .collect();
+
+        if self == FilenameCharset::Portable {
+            // Windows drops trailing dots and spaces, leading ones hide or
+            // misalign the file elsewhere
+            sanitized = sanitized
+                .trim_start_matches(' ')
+                .trim_end_matches(&['.', ' '][..])
+                .to_string();
+            if sanitized.starts_with('.') {
+                sanitized.replace_range(..1, "_");
+            }
+            let base = sanitized.split('.').next().unwrap_or_default();
+            if RESERVED_NAMES
+                .iter()
+                .any(|reserved| base.trim_end().eq_ignore_ascii_case(reserved))
+            {
+                sanitized.insert(0, '_');
+            }
+        }
+
+        match sanitized.as_str() {
+            "" | "." | ".." => String::from("_"),
+            _ => sanitized,
+        }
+    }
+}
+
+/// `name` made valid as a file name on every system, characters such as the
+/// `:` of timecodes being replaced by `_`.
+pub fn sanitize_filename(name: &str) -> String {
+    FilenameCharset::Portable.sanitize(name)
+}
+
+/// `path` with its file name sanitized as `charset` requires, its directories
+/// being left as they are.
+pub fn with_sanitized_file_name(path: &Path, charset: FilenameCharset) -> PathBuf {
+    match path.file_name() {
+        Some(name) => path.with_file_name(charset.sanitize(&name.to_string_lossy())),
+        None => path.to_path_buf(),
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    /// Input file name without its extension.
+    Stem,
+    /// Input file extension, without the dot.
+    Ext,
+    /// Directory of the input file.
+    Parent,
+    /// Position of the input in the batch, zero padded to `width` digits.
+    Index {
+        width: usize,
+    },
+    /// Track number, zero padded to `width` digits.
+    Track {
+        width: usize,
+    },
+    Title,
+    Artist,
+    Album,
+}
+
+/// Album track an output is cut for, see `expand_track_pattern`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TrackTags<'a> {
+    pub number: u32,
+    pub title: &'a str,
+    pub artist: &'a str,
+    pub album: &'a str,
+}
+
+/// Check that `pattern` only uses known tokens: `{stem}`, `{ext}`, `{parent}`,
+/// `{index}` and `{index:0N}`, `N` being the number of digits.
+pub fn validate_pattern(pattern: &str) -> Result<()> {
+    if pattern.is_empty() {
+        return Err(String::from("Error: output pattern is empty."));
+    }
+    parse(pattern, false).map(|_| ())
+}
+
+/// Check that `pattern` only uses the tokens of `validate_pattern` and those
+/// of album tracks: `{track}`, `{track:0N}`, `{title}`, `{artist}` and `{album}`.
+pub fn validate_track_pattern(pattern: &str) -> Result<()> {
+    if pattern.is_empty() {
+        return Err(String::from("Error: output pattern is empty."));
+    }
+    parse(pattern, true).map(|_| ())
+}
+
+/// Output path of the `index`-th input of a batch, `input`, named after `pattern`.
+/// Invalid patterns are used as is: check them first with `validate_pattern`.
+/// The file name is sanitized as `charset` requires.
+pub fn expand_pattern(
+    pattern: &str,
+    input: &Path,
+    index: usize,
+    charset: FilenameCharset,
+) -> PathBuf {
+    match parse(pattern, false) {
+        Ok(tokens) => expand(&tokens, input, index, None, charset),
+        Err(_) => PathBuf::from(pattern),
+    }
+}
+
+/// Output path of `track`, cut from `input`, named after `pattern`. Invalid
+/// patterns are used as is: check them first with `validate_track_pattern`.
+/// Tags are sanitized as `charset` requires, as is the file name.
+pub fn expand_track_pattern(
+    pattern: &str,
+    input: &Path,
+    track: &TrackTags,
+    charset: FilenameCharset,
+) -> PathBuf {
+    match parse(pattern, true) {
+        Ok(tokens) => expand(&tokens, input, track.number as usize, Some(track), charset),
+        Err(_) => PathBuf::from(pattern),
+    }
+}
+
+fn expand(
+    tokens: &[Token],
+    input: &Path,
+    index: usize,
+    track: Option<&TrackTags>,
+    charset: FilenameCharset,
+) -> PathBuf {
+    let lossy = |part: Option<&std::ffi::OsStr>| {
+        part.map_or_else(String::new, |part| part.to_string_lossy().into_owned())
+    };
+    // tags may hold path separators, they are sanitized on their own
+    let tag = |value: Option<&str>| match value {
+        Some(value) if !value.is_empty() => charset.sanitize(value),
+        _ => String::new(),
+    };
+    let mut expanded = String::new();
+    for token in tokens {
+        match *token {
+            Token::Text(text) => expanded.push_str(text),
+            Token::Stem => expanded.push_str(&lossy(input.file_stem())),
+            Token::Ext => expanded.push_str(&lossy(input.extension())),
+            Token::Parent => {
+                expanded.push_str(&lossy(input.parent().map(|parent| parent.as_os_str())))
+            }
+            Token::Index { width } => expanded.push_str(&format!("{:01$}", index, width)),
+            Token::Track { width } => {
+                let number = track.map_or(0, |track| track.number);
+                expanded.push_str(&format!("{:01$}", number, width))
+            }
+            Token::Title => expanded.push_str(&tag(track.map(|track| track.title))),
+            Token::Artist => expanded.push_str(&tag(track.map(|track| track.artist))),
+            Token::Album => expanded.push_str(&tag(track.map(|track| track.album))),
+        }
+    }
+    with_sanitized_file_name(Path::new(&expanded), charset)
+}
+
+// album track tokens are only known with `track_tokens`
+fn parse(pattern: &str, track_tokens: bool) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = pattern;
+
+    while !rest.is_empty() {
+        match (rest.find('{'), rest.find('}')) {
+            (None, None) => {
+                tokens.push(Token::Text(rest));
+                break;
+            }
+            (Some(_), None) => {
+                return Err(format!(
+                    "Error: unclosed brace in output pattern \"{}\".",
+                    pattern
+                ))
+            }
+            (Some(open), Some(close)) if open < close => {
+                if open > 0 {
+                    tokens.push(Token::Text(&rest[..open]));
+                }
+                tokens.push(parse_token(&rest[open + 1..close], pattern, track_tokens)?);
+                rest = &rest[close + 1..];
+            }
+            _ => {
+                return Err(format!(
+                    "Error: unexpected closing brace in output pattern \"{}\".",
+                    pattern
+                ))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_token<'a>(token: &str, pattern: &str, track_tokens: bool) -> Result<Token<'a>> {
+    let unknown = || {
+        let track_choices = if track_tokens {
+            "{track}, {track:0N}, {title}, {artist}, {album}, "
+        } else {
+            ""
+        };
+        format!(
+            "Error: unknown token {{{}}} in output pattern \"{}\", expected one of {}{{stem}}, {{ext}}, {{parent}}, {{index}} or {{index:0N}}.",
+            token, pattern, track_choices
+        )
+    };
+
+    match token {
+        "stem" => Ok(Token::Stem),
+        "ext" => Ok(Token::Ext),
+        "parent" => Ok(Token::Parent),
+        "index" => Ok(Token::Index { width: 0 }),
+        "track" if track_tokens => Ok(Token::Track { width: 0 }),
+        "title" if track_tokens => Ok(Token::Title),
+        "artist" if track_tokens => Ok(Token::Artist),
+        "album" if track_tokens => Ok(Token::Album),
+        _ => match (padded_width(token, "index"), padded_width(token, "track")) {
+            (Some(width), _) => Ok(Token::Index { width }),
+            (_, Some(width)) if track_tokens => Ok(Token::Track { width }),
+            _ => Err(unknown()),
+        },
+    }
+}
+
+// `{name:0N}`, N being the number of digits
+fn padded_width(token: &str, name: &str) -> Option<usize> {
+    token
+        .strip_prefix(name)
+        .and_then(|width| width.strip_prefix(":0"))
+        .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(pattern: &str, input: &str, index: usize) -> String {
+        assert_eq!(validate_pattern(pattern), Ok(()));
+        expand_pattern(pattern, Path::new(input), index, FilenameCharset::Portable)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            expand("{stem}_cut.{ext}", "/media/talk.wav", 1),
+            "talk_cut.wav"
+        );
+        assert_eq!(
+            expand("{parent}/cut/{stem}.{ext}", "/media/talk.wav", 1),
+            "/media/cut/talk.wav"
+        );
+        assert_eq!(
+            expand("{stem}_{index}.flac", "talk.wav", 12),
+            "talk_12.flac"
+        );
+        // repeated tokens and plain text only
+        assert_eq!(expand("{stem}-{stem}", "a.b.mp4", 0), "a.b-a.b");
+        assert_eq!(expand("out.mp4", "talk.wav", 3), "out.mp4");
+    }
+
+    #[test]
+    fn missing_parts_expand_to_nothing() {
+        // but for the dot, dropped as Windows would
+        assert_eq!(expand("{stem}.{ext}", "README", 1), "README");
+        assert_eq!(expand("{parent}{stem}", "talk.wav", 1), "talk");
+    }
+
+    #[test]
+    fn sanitized_names() {
+        assert_eq!(
+            expand("{stem} 1:02:03.mp4", "talk.wav", 1),
+            "talk 1_02_03.mp4"
+        );
+        // directories are kept, only the file name is sanitized
+        assert_eq!(
+            expand("{parent}/cut/{stem}?.{ext}", "/media/talk.wav", 1),
+            "/media/cut/talk_.wav"
+        );
+        assert_eq!(
+            expand_pattern(
+                "{stem} 1:02:03.mp4",
+                Path::new("talk.wav"),
+                1,
+                FilenameCharset::Unix
+            ),
+            PathBuf::from("talk 1:02:03.mp4")
+        );
+    }
+
+    #[test]
+    fn windows_reserved_characters() {
+        assert_eq!(
+            sanitize_filename("a<b>c:d\"e|f?g*h.mp4"),
+            "a_b_c_d_e_f_g_h.mp4"
+        );
+        assert_eq!(
+            sanitize_filename("back\\slash/slash.mp4"),
+            "back_slash_slash.mp4"
+        );
+        assert_eq!(sanitize_filename("tab\there\u{7}.mp4"), "tab_here_.mp4");
+        assert_eq!(
+            sanitize_filename("Q&A – été, 10%.mp4"),
+            "Q&A – été, 10%.mp4"
+        );
+
+        assert_eq!(
+            FilenameCharset::Unix.sanitize("a<b>c:d|e?.mp4"),
+            "a<b>c:d|e?.mp4"
+        );
+        assert_eq!(FilenameCharset::Unix.sanitize("a/b\0c"), "a_b_c");
+    }
+
+    #[test]
+    fn leading_and_trailing_dots_and_spaces() {
+        assert_eq!(sanitize_filename("  talk.mp4  "), "talk.mp4");
+        assert_eq!(sanitize_filename("talk. . ."), "talk");
+        assert_eq!(sanitize_filename(".hidden.mp4"), "_hidden.mp4");
+        assert_eq!(sanitize_filename(" .mp4"), "_mp4");
+        for empty in &["", " ", ".", "..", "..."] {
+            assert_eq!(sanitize_filename(empty), "_", "{:?}", empty);
+        }
+
+        // allowed on Unix, but for the current and parent directories
+        assert_eq!(FilenameCharset::Unix.sanitize(" .talk. "), " .talk. ");
+        assert_eq!(FilenameCharset::Unix.sanitize(".."), "_");
+    }
+
+    #[test]
+    fn reserved_device_names() {
+        for name in &[
+            "CON",
+            "con",
+            "Prn.mp4",
+            "AUX.tar.gz",
+            "NUL ",
+            "COM1.wav",
+            "lpt9",
+        ] {
+            assert_eq!(
+                sanitize_filename(name),
+                format!("_{}", name.trim()),
+                "{}",
+                name
+            );
+        }
+        for name in &["CONSOLE.mp4", "COM10.mp4", "icon.png", "my CON.mp4"] {
+            assert_eq!(sanitize_filename(name), *name);
+        }
+        assert_eq!(FilenameCharset::Unix.sanitize("CON.mp4"), "CON.mp4");
+    }
+
+    #[test]
+    fn track_tokens() {
+        let track = TrackTags {
+            number: 3,
+            title: "What's Up? / Live",
+            artist: "4 Non Blondes",
+            album: "",
+        };
+        let expand = |pattern: &str| {
+            assert_eq!(validate_track_pattern(pattern), Ok(()));
+            expand_track_pattern(
+                pattern,
+                Path::new("/music/rip.flac"),
+                &track,
+                FilenameCharset::Portable,
+            )
+            .to_string_lossy()
+            .into_owned()
+        };
+        assert_eq!(
+            expand("{track:02} - {title}.flac"),
+            "03 - What's Up_ _ Live.flac"
+        );
+        assert_eq!(
+            expand("{parent}/{artist}/{album}{track}.{ext}"),
+            "/music/4 Non Blondes/3.flac"
+        );
+        assert_eq!(expand("{stem}_{index:03}.{ext}"), "rip_003.flac");
+
+        // only known when splitting an album
+        assert!(validate_pattern("{track:02} - {title}.flac").is_err());
+        assert!(validate_track_pattern("{track:2}.flac").is_err());
+        let error = validate_track_pattern("{genre}.flac").unwrap_err();
+        assert!(error.contains("{title}"), "{}", error);
+    }
+
+    #[test]
+    fn index_padding() {
+        assert_eq!(expand("{stem}_{index:03}.{ext}", "a.mp4", 7), "a_007.mp4");
+        assert_eq!(expand("{index:03}", "a.mp4", 42), "042");
+        // wider indices are never truncated
+        assert_eq!(expand("{index:03}", "a.mp4", 12345), "12345");
+        assert_eq!(expand("{index:01}", "a.mp4", 0), "0");
+        assert_eq!(expand("{index:010}", "a.mp4", 5), "0000000005");
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in &[
+            "",
+            "{name}.mp4",
+            "{stem",
+            "stem}",
+            "{}",
+            "{{stem}}",
+            "{index:3}",
+            "{index:0}",
+            "{index:0x}",
+            "{index:}",
+            "{Stem}",
+        ] {
+            assert!(validate_pattern(pattern).is_err(), "{}", pattern);
+        }
+
+        let error = validate_pattern("{stem}_{date}.mp4").unwrap_err();
+        assert!(error.contains("{date}"), "{}", error);
+
+        // used as is
+        assert_eq!(
+            expand_pattern(
+                "{name}.mp4",
+                Path::new("a.mp4"),
+                1,
+                FilenameCharset::Portable
+            ),
+            PathBuf::from("{name}.mp4")
+        );
+    }
+}
@@ -0,0 +1,301 @@
+#[cfg(feature = "embedded-playback")]
+pub use self::embedded::Player;
+
+/// Built without GStreamer: `new` always fails and previews go through ffplay.
+#[cfg(not(feature = "embedded-playback"))]
+pub enum Player {}
+
+#[cfg(not(feature = "embedded-playback"))]
+impl Player {
+    pub fn new() -> Result<Self, String> {
+        Err(String::from(
+            "Embedded playback is not enabled in this build.",
+        ))
+    }
+
+    pub fn widget(&self) -> &gtk::Box {
+        match *self {}
+    }
+
+    pub fn load(&self, _path: &str, _has_video: bool) {
+        match *self {}
+    }
+
+    pub fn unload(&self) {
+        match *self {}
+    }
+
+    pub fn set_selection(&self, _start: f64, _end: f64) {
+        match *self {}
+    }
+
+    pub fn set_audio_filter(&self, _description: Option<String>) {
+        match *self {}
+    }
+
+    pub fn play_selection(&self) {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "embedded-playback")]
+mod embedded {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use gstreamer as gst;
+    use gstreamer::prelude::*;
+    use gtk::prelude::*;
+
+    // how often the position is checked against the selection end
+    const POSITION_POLL_MS: u32 = 50;
+
+    struct PlayerState {
+        /// Start and end of the selection, in seconds.
+        selection: (f64, f64),
+        /// GStreamer approximation of the output filters, heard when enabled.
+        audio_filter: Option<String>,
+        loaded: bool,
+        /// Position to go back to once the pipeline is prerolled again.
+        pending_seek: Option<f64>,
+    }
+
+    /// GStreamer `playbin` rendered in the window, looping over the selection.
+    /// The filters toggle compares the input (A) with the filtered sound (B).
+    #[derive(Clone)]
+    pub struct Player {
+        container: gtk::Box,
+        video: gtk::Widget,
+        position_scale: gtk::Scale,
+        play_button: gtk::ToggleButton,
+        filters_button: gtk::ToggleButton,
+        playbin: gst::Element,
+        state: Rc<RefCell<PlayerState>>,
+    }
+
+    impl Player {
+        pub fn new() -> Result<Self, String> {
+            gst::init().map_err(|e| format!("Could not initialize GStreamer.\nError: {}", e))?;
+
+            let playbin = gst::ElementFactory::make("playbin", None)
+                .ok_or_else(|| String::from("GStreamer playbin element is missing."))?;
+            let sink = gst::ElementFactory::make("gtksink", None)
+                .ok_or_else(|| String::from("GStreamer gtksink element is missing."))?;
+            let video = sink
+                .get_property("widget")
+                .ok()
+                .and_then(|widget| widget.get::<gtk::Widget>())
+                .ok_or_else(|| String::from("Could not get the gtksink widget."))?;
+            playbin
+                .set_property("video-sink", &sink)
+                .map_err(|e| format!("Could not set the video sink.\nError: {}", e))?;
+
+            let container = gtk::Box::new(gtk::Orientation::Vertical, 5);
+            video.set_size_request(-1, 180);
+            video.set_no_show_all(true);
+            container.pack_start(&video, true, true, 0);
+
+            let controls = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            let play_button = gtk::ToggleButton::new_with_label("Play");
+            let position_scale =
+                gtk::Scale::new_with_range(gtk::Orientation::Horizontal, 0.0, 1.0, 0.1);
+            position_scale.set_draw_value(false);
+            let filters_button = gtk::ToggleButton::new_with_label("Filters");
+            filters_button.set_active(true);
+            filters_button.set_tooltip_text(
+                "Hear the pass filters and volume change (B) or the input as is (A)",
+            );
+            controls.pack_start(&play_button, false, false, 0);
+            controls.pack_start(&position_scale, true, true, 0);
+            controls.pack_start(&filters_button, false, false, 0);
+            container.pack_start(&controls, false, false, 0);
+            container.show_all();
+
+            let player = Self {
+                container,
+                video,
+                position_scale,
+                play_button,
+                filters_button,
+                playbin,
+                state: Rc::new(RefCell::new(PlayerState {
+                    selection: (0.0, 0.0),
+                    audio_filter: None,
+                    loaded: false,
+                    pending_seek: None,
+                })),
+            };
+            player.set_sensitive(false);
+            player.connect_events();
+            Ok(player)
+        }
+
+        pub fn widget(&self) -> &gtk::Box {
+            &self.container
+        }
+
+        pub fn load(&self, path: &str, has_video: bool) {
+            self.unload();
+            // playbin reads network inputs by itself
+            let uri = if media_cutter::is_url(path) {
+                String::from(path)
+            } else {
+                match glib::filename_to_uri(path, None) {
+                    Ok(uri) => uri,
+                    Err(_) => return,
+                }
+            };
+            if self.playbin.set_property("uri", &uri).is_err() {
+                return;
+            }
+
+            self.state.borrow_mut().loaded = true;
+            self.apply_audio_filter();
+            // prerolled so that the first frame shows and seeking works right away
+            let _ = self.playbin.set_state(gst::State::Paused);
+            self.video.set_visible(has_video);
+            self.set_sensitive(true);
+        }
+
+        pub fn unload(&self) {
+            self.play_button.set_active(false);
+            let _ = self.playbin.set_state(gst::State::Null);
+            self.state.borrow_mut().loaded = false;
+            self.state.borrow_mut().pending_seek = None;
+            self.video.hide();
+            self.set_sensitive(false);
+        }
+
+        pub fn set_selection(&self, start: f64, end: f64) {
+            self.state.borrow_mut().selection = (start, end);
+            self.position_scale.set_range(start, end.max(start + 0.001));
+        }
+
+        /// Filter heard when the filters toggle is active, see
+        /// `processing::make_gst_audio_filter_description`.
+        pub fn set_audio_filter(&self, description: Option<String>) {
+            if self.state.borrow().audio_filter == description {
+                return;
+            }
+            self.state.borrow_mut().audio_filter = description;
+            self.apply_audio_filter();
+        }
+
+        pub fn play_selection(&self) {
+            let start = self.state.borrow().selection.0;
+            self.seek(start);
+            self.play_button.set_active(true);
+        }
+
+        fn set_sensitive(&self, sensitive: bool) {
+            self.play_button.set_sensitive(sensitive);
+            self.position_scale.set_sensitive(sensitive);
+            self.filters_button.set_sensitive(sensitive);
+        }
+
+        fn position(&self) -> Option<f64> {
+            self.playbin
+                .query_position::<gst::ClockTime>()
+                .and_then(|position| position.nseconds())
+                .map(|nanos| nanos as f64 / 1e9)
+        }
+
+        // clamped to the selection, replaces a pending seek
+        fn seek(&self, time: f64) {
+            self.state.borrow_mut().pending_seek = None;
+            let (start, end) = self.state.borrow().selection;
+            let time = time.max(start).min(end);
+            let _ = self.playbin.seek_simple(
+                gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                gst::ClockTime::from_nseconds((time * 1e9) as u64),
+            );
+        }
+
+        // the audio filter of a playbin can only be replaced when stopped
+        fn apply_audio_filter(&self) {
+            if !self.state.borrow().loaded {
+                return;
+            }
+
+            let position = self.position();
+            let _ = self.playbin.set_state(gst::State::Ready);
+
+            let description = if self.filters_button.get_active() {
+                self.state.borrow().audio_filter.clone()
+            } else {
+                None
+            };
+            let filter = description
+                .and_then(|description| gst::parse_bin_from_description(&description, true).ok());
+            let _ = self.playbin.set_property("audio-filter", &filter);
+
+            let target = if self.play_button.get_active() {
+                gst::State::Playing
+            } else {
+                gst::State::Paused
+            };
+            let _ = self.playbin.set_state(target);
+            // seeking needs the pipeline prerolled, see `connect_events`
+            self.state.borrow_mut().pending_seek = position;
+        }
+
+        // whether the pipeline reached its target state, without waiting for it
+        fn is_prerolled(&self) -> bool {
+            let (result, _, _) = self.playbin.get_state(gst::ClockTime::from_nseconds(0));
+            result == gst::StateChangeReturn::Success
+        }
+
+        fn connect_events(&self) {
+            let player = self.clone();
+            self.play_button.connect_toggled(move |button| {
+                if button.get_active() {
+                    let (start, end) = player.state.borrow().selection;
+                    match player.position() {
+                        Some(position) if position >= start && position < end => {}
+                        _ => player.seek(start),
+                    }
+                    let _ = player.playbin.set_state(gst::State::Playing);
+                    button.set_label("Pause");
+                } else {
+                    let _ = player.playbin.set_state(gst::State::Paused);
+                    button.set_label("Play");
+                }
+            });
+
+            let player = self.clone();
+            self.filters_button.connect_toggled(move |_| {
+                player.apply_audio_filter();
+            });
+
+            let player = self.clone();
+            self.position_scale
+                .connect_change_value(move |_, _, value| {
+                    player.seek(value);
+                    Inhibit(false)
+                });
+
+            // loop over the selection, end of stream included, and seek back
+            // once the pipeline is prerolled
+            let player = self.clone();
+            gtk::timeout_add(POSITION_POLL_MS, move || {
+                let pending_seek = player.state.borrow().pending_seek;
+                if let Some(position) = pending_seek {
+                    if !player.is_prerolled() {
+                        return Continue(true);
+                    }
+                    player.seek(position);
+                }
+                if !player.play_button.get_active() {
+                    return Continue(true);
+                }
+                let (start, end) = player.state.borrow().selection;
+                match player.position() {
+                    Some(position) if position >= end => player.seek(start),
+                    Some(position) => player.position_scale.set_value(position),
+                    None => {}
+                }
+                Continue(true)
+            });
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{containers, AacOptions, Config};
+
+// chat services refuse uploads above 8 MB
+const CHAT_CLIP_SIZE: u64 = 8 * 1000 * 1000;
+// speech loudness of podcasts and voice messages
+const VOICE_MEMO_LOUDNESS: f64 = -16.0;
+
+/// Named settings template, for those who would rather pick a use than
+/// settings.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Preset {
+    pub name: &'static str,
+    /// Settings of the preset, without input, output or selection.
+    pub config: Config,
+}
+
+impl Preset {
+    /// Presets shipped with the application. They cannot be changed or deleted.
+    pub fn builtin() -> Vec<Self> {
+        vec![
+            Self {
+                name: "Chat clip ≤ 8 MB",
+                config: Config {
+                    video_codec: Some(String::from("libx264")),
+                    audio_codec: Some(String::from("aac")),
+                    aac_options: Some(AacOptions {
+                        bitrate: 96,
+                        ..AacOptions::default()
+                    }),
+                    encoder_preset: Some(String::from("veryfast")),
+                    target_size: Some(CHAT_CLIP_SIZE),
+                    max_height: Some(720),
+                    ..Config::default()
+                },
+            },
+            Self {
+                name: "Voice memo",
+                config: Config {
+                    ignore_video: true,
+                    audio_codec: Some(String::from("libopus")),
+                    audio_bitrate: Some(48),
+                    high_pass_filter: Some(80),
+                    loudness_target: Some(VOICE_MEMO_LOUDNESS),
+                    // a single voice needs a single channel
+                    extra_ffmpeg_args: vec![String::from("-ac"), String::from("1")],
+                    ..Config::default()
+                },
+            },
+            Self {
+                name: "Archive (FLAC)",
+                config: Config {
+                    ignore_video: true,
+                    audio_codec: Some(String::from("flac")),
+                    flac_compression: Some(12),
+                    verify_lossless: true,
+                    ..Config::default()
+                },
+            },
+        ]
+    }
+
+    /// The preset settings for the input, output and selection of `conf`. The
+    /// output extension is corrected to fit the codecs of the preset.
+    pub fn apply_to(&self, conf: &Config) -> Config {
+        let mut applied = Config {
+            input_file: conf.input_file.clone(),
+            output_file: conf.output_file.clone(),
+            from_time: conf.from_time,
+            to_time: conf.to_time,
+            process_whole_file: conf.process_whole_file,
+            pad_before: conf.pad_before,
+            pad_after: conf.pad_after,
+            output_pattern: conf.output_pattern.clone(),
+            working_dir: conf.working_dir.clone(),
+            ..self.config.clone()
+        };
+
+        let video_codec = applied
+            .video_codec
+            .as_deref()
+            .filter(|_| !applied.ignore_video);
+        let audio_codec = applied
+            .audio_codec
+            .as_deref()
+            .filter(|_| !applied.ignore_audio);
+        if let Some(corrected) =
+            containers::corrected_output_file(&applied.output_file, video_codec, audio_codec)
+        {
+            applied.output_file = corrected;
+        }
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::{self, StepKind};
+    use chrono::Duration;
+
+    fn current_config() -> Config {
+        let mut conf = Config::new();
+        conf.input_file = String::from("talk.mp4");
+        conf.output_file = String::from("talk_cut.mkv");
+        conf.from_time = Duration::seconds(10);
+        conf.to_time = Duration::seconds(70);
+        conf.volume_change = 6.0;
+        conf
+    }
+
+    fn preset(name: &str) -> Preset {
+        Preset::builtin()
+            .into_iter()
+            .find(|preset| preset.name == name)
+            .unwrap()
+    }
+
+    fn has_args(args: &[String], expected: &[&str]) -> bool {
+        args.windows(expected.len())
+            .any(|window| window == expected)
+    }
+
+    #[test]
+    fn builtin_presets_validate() {
+        for preset in Preset::builtin() {
+            let conf = preset.apply_to(&current_config());
+            assert_eq!(conf.validate(), Ok(()), "{}", preset.name);
+            assert!(processing::plan(&conf).is_ok(), "{}", preset.name);
+        }
+    }
+
+    #[test]
+    fn application() {
+        let conf = preset("Voice memo").apply_to(&current_config());
+        assert_eq!(conf.input_file, "talk.mp4");
+        assert_eq!(conf.from_time, Duration::seconds(10));
+        assert_eq!(conf.to_time, Duration::seconds(70));
+        // the other settings come from the preset
+        assert_eq!(conf.volume_change, 0.0);
+        // Matroska holds Opus
+        assert_eq!(conf.output_file, "talk_cut.mkv");
+
+        let conf = preset("Chat clip ≤ 8 MB").apply_to(&current_config());
+        assert_eq!(conf.output_file, "talk_cut.mkv");
+        let mut current = current_config();
+        current.output_file = String::from("talk_cut.wav");
+        let conf = preset("Chat clip ≤ 8 MB").apply_to(&current);
+        assert_eq!(conf.output_file, "talk_cut.mp4");
+    }
+
+    #[test]
+    fn chat_clip_args() {
+        let conf = preset("Chat clip ≤ 8 MB").apply_to(&current_config());
+        let plan = processing::plan(&conf).unwrap();
+        assert_eq!(plan.commands.len(), 1);
+        let args = &plan.commands[0].args;
+        assert!(has_args(args, &["-c:v", "libx264"]));
+        assert!(has_args(args, &["-preset", "veryfast"]));
+        assert!(has_args(
+            args,
+            &["-c:a", "aac", "-profile:a", "aac_low", "-b:a", "96k"]
+        ));
+        // 60 seconds in 8 MB, less the audio
+        assert!(has_args(
+            args,
+            &["-b:v", "917k", "-maxrate", "917k", "-bufsize", "1834k"]
+        ));
+        assert!(args.contains(&String::from("scale=-2:'min(ih,720)'")));
+        assert!(!args.contains(&String::from("-fs")));
+    }
+
+    #[test]
+    fn voice_memo_args() {
+        let conf = preset("Voice memo").apply_to(&current_config());
+        let plan = processing::plan(&conf).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(kinds, vec![StepKind::Process]);
+        let args = &plan.commands[0].args;
+        assert!(has_args(args, &["-vn"]));
+        assert!(has_args(args, &["-c:a", "libopus", "-b:a", "48k"]));
+        assert!(has_args(args, &["-ac", "1"]));
+        assert!(args.iter().any(|arg| arg.contains("highpass=f=80")));
+        assert!(args.iter().any(|arg| arg.contains("loudnorm=I=-16")));
+    }
+
+    #[test]
+    fn archive_args() {
+        let conf = preset("Archive (FLAC)").apply_to(&current_config());
+        assert_eq!(conf.output_file, "talk_cut.mkv");
+        let plan = processing::plan(&conf).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::Process,
+                StepKind::LosslessCheck,
+                StepKind::LosslessCheck
+            ]
+        );
+        let args = &plan.commands[0].args;
+        assert!(has_args(
+            args,
+            &["-c:a", "flac", "-compression_level", "12"]
+        ));
+        assert!(has_args(args, &["-vn"]));
+    }
+}
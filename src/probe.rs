@@ -0,0 +1,686 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    process::{Command, Stdio},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chapters::Chapter,
+    logging::LogLaunch,
+    processing::{CancelFlag, CANCELLED_ERROR},
+    ImageSequence,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+pub(crate) const FFPROBE_COMMAND: &str = "ffprobe";
+
+// how often a running probe checks whether it is cancelled
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn has_video_stream(input_file: &str) -> Result<bool> {
+    let output = Command::new(FFPROBE_COMMAND)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v",
+            "-show_entries",
+            "stream=index",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(input_file)
+        .logged()
+        .output()
+        .map_err(|e| format!("Failed to start {}.\nError: {}", FFPROBE_COMMAND, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Could not probe {}.\nError output: {}",
+            input_file,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    // one stream index per line
+    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
+
+/// Times in seconds of the keyframes of the first video stream, read between
+/// `from` and `to` seconds. Packets are not decoded, which keeps it fast.
+pub fn keyframes(input_file: &str, from: f64, to: f64) -> Result<Vec<f64>> {
+    let output = Command::new(FFPROBE_COMMAND)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "packet=pts_time,flags",
+            "-of",
+            "csv=p=0",
+            "-read_intervals",
+        ])
+        .arg(format!("{:.3}%{:.3}", from, to))
+        .arg(input_file)
+        .logged()
+        .output()
+        .map_err(|e| format!("Failed to start {}.\nError: {}", FFPROBE_COMMAND, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Could not probe the keyframes of {}.\nError output: {}",
+            input_file,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(parse_keyframes(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// `<pts_time>,<flags>` lines in decoding order, keyframes flagged with `K`
+fn parse_keyframes(csv: &str) -> Vec<f64> {
+    let mut keyframes: Vec<f64> = csv
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim().split(',');
+            let time = fields.next()?.parse::<f64>().ok()?;
+            match fields.next() {
+                Some(flags) if flags.starts_with('K') => Some(time),
+                _ => None,
+            }
+        })
+        .collect();
+    keyframes.sort_by(|a, b| a.total_cmp(b));
+    keyframes.dedup();
+    keyframes
+}
+
+/// Chapters of the input, timed on it, untitled ones named by their number.
+pub fn chapters(input_file: &str) -> Result<Vec<Chapter>> {
+    let output = Command::new(FFPROBE_COMMAND)
+        .args(["-v", "error", "-print_format", "json", "-show_chapters"])
+        .arg(input_file)
+        .logged()
+        .output()
+        .map_err(|e| format!("Failed to start {}.\nError: {}", FFPROBE_COMMAND, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "Could not probe the chapters of {}.\nError output: {}",
+            input_file,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    parse_chapters(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[derive(Deserialize)]
+struct ProbeChapters {
+    #[serde(default)]
+    chapters: Vec<ProbeChapter>,
+}
+
+#[derive(Deserialize)]
+struct ProbeChapter {
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+fn parse_chapters(json: &str) -> Result<Vec<Chapter>> {
+    let probed: ProbeChapters = serde_json::from_str(json)
+        .map_err(|e| format!("Could not read ffprobe output.\nError: {}", e))?;
+
+    let to_duration = |secs: &str| {
+        secs.parse::<f64>()
+            .map(|secs| chrono::Duration::milliseconds((secs * 1000.0).round() as i64))
+            .map_err(|_| format!("Invalid chapter time in ffprobe output: {}", secs))
+    };
+    probed
+        .chapters
+        .into_iter()
+        .enumerate()
+        .map(|(index, chapter)| {
+            Ok(Chapter {
+                start: to_duration(&chapter.start_time)?,
+                end: to_duration(&chapter.end_time)?,
+                title: chapter
+                    .tags
+                    .get("title")
+                    .cloned()
+                    .unwrap_or_else(|| format!("Chapter {}", index + 1)),
+            })
+        })
+        .collect()
+}
+
+/// Container and streams of a media file, as reported by ffprobe.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub container: String,
+    pub duration: Option<f64>,
+    pub bit_rate: Option<u64>,
+    pub streams: Vec<StreamInfo>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, `data`…
+    pub kind: String,
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub sample_aspect_ratio: Option<String>,
+    pub display_aspect_ratio: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub language: Option<String>,
+    pub bit_rate: Option<u64>,
+    /// Frames per second.
+    pub frame_rate: Option<f64>,
+    /// Pixel format of video streams, `yuv420p` for instance.
+    pub pix_fmt: Option<String>,
+    /// As ffprobe names it, `High` or `Main 10` for instance.
+    pub profile: Option<String>,
+    /// Codec level, 41 for H.264 level 4.1.
+    pub level: Option<i32>,
+    /// Such as `1/12800`.
+    pub time_base: Option<String>,
+}
+
+/// Titles of the columns returned by `MediaInfo::stream_rows`.
+pub const STREAM_COLUMNS: [&str; 9] = [
+    "#",
+    "Type",
+    "Codec",
+    "Format",
+    "Aspect",
+    "Channels",
+    "Language",
+    "Bitrate",
+    "Frame rate",
+];
+
+// shown for values the file does not provide
+const UNKNOWN: &str = "-";
+
+// ffprobe prints most numbers as strings
+#[derive(Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: ProbeFormat,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    format_name: String,
+    format_long_name: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    index: u32,
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    sample_aspect_ratio: Option<String>,
+    display_aspect_ratio: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    bit_rate: Option<String>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    pix_fmt: Option<String>,
+    profile: Option<String>,
+    level: Option<i32>,
+    time_base: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+impl MediaInfo {
+    pub fn probe(input_file: &str) -> Result<Self> {
+        Self::probe_cancellable(input_file, &CancelFlag::new())
+    }
+
+    /// As `probe`, given up once `cancel` is set: network inputs may take a
+    /// while to answer.
+    pub fn probe_cancellable(input_file: &str, cancel: &CancelFlag) -> Result<Self> {
+        Self::probe_input(&[], input_file, cancel)
+    }
+
+    /// As `probe` for the pictures named by `pattern`: ffprobe reads them
+    /// with the input options ffmpeg is given.
+    pub fn probe_image_sequence(pattern: &str, sequence: &ImageSequence) -> Result<Self> {
+        Self::probe_input(&sequence.input_args(), pattern, &CancelFlag::new())
+    }
+
+    fn probe_input(input_args: &[String], input_file: &str, cancel: &CancelFlag) -> Result<Self> {
+        let mut child = Command::new(FFPROBE_COMMAND)
+            .args([
+                "-v",
+                "error",
+                "-print_format",
+                "json",
+                "-show_format",
+                "-show_streams",
+            ])
+            .args(input_args)
+            .arg(input_file)
+            .logged()
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}.\nError: {}", FFPROBE_COMMAND, e))?;
+
+        // drained on their own threads: ffprobe blocks once a pipe is full
+        let mut stdout = child.stdout.take().unwrap();
+        let stdout_reader = thread::spawn(move || {
+            let mut contents = Vec::new();
+            let _ = stdout.read_to_end(&mut contents);
+            contents
+        });
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr_reader = thread::spawn(move || {
+            let mut contents = Vec::new();
+            let _ = stderr.read_to_end(&mut contents);
+            contents
+        });
+
+        let status = loop {
+            if cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(String::from(CANCELLED_ERROR));
+            }
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) => thread::sleep(CANCEL_POLL_INTERVAL),
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to wait for {}.\nError: {}",
+                        FFPROBE_COMMAND, e
+                    ))
+                }
+            }
+        };
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        if !status.success() {
+            return Err(format!(
+                "Could not probe {}.\nError output: {}",
+                input_file,
+                String::from_utf8_lossy(&stderr)
+            ));
+        }
+
+        Self::from_json(&String::from_utf8_lossy(&stdout))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let probed: ProbeOutput = serde_json::from_str(json)
+            .map_err(|e| format!("Could not read ffprobe output.\nError: {}", e))?;
+
+        Ok(Self {
+            container: probed
+                .format
+                .format_long_name
+                .unwrap_or(probed.format.format_name),
+            duration: parse_number(probed.format.duration),
+            bit_rate: parse_number(probed.format.bit_rate),
+            streams: probed.streams.into_iter().map(StreamInfo::from).collect(),
+        })
+    }
+
+    /// Label and value of the container-wide properties.
+    pub fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Container", self.container.clone()),
+            (
+                "Duration",
+                self.duration
+                    .map_or_else(|| String::from(UNKNOWN), format_seconds),
+            ),
+            (
+                "Bitrate",
+                self.bit_rate
+                    .map_or_else(|| String::from(UNKNOWN), format_bit_rate),
+            ),
+        ]
+    }
+
+    /// One row per stream, matching `STREAM_COLUMNS`.
+    pub fn stream_rows(&self) -> Vec<[String; 9]> {
+        self.streams.iter().map(StreamInfo::row).collect()
+    }
+
+    /// Plain-text report, one line per property or stream.
+    pub fn to_text(&self) -> String {
+        let mut report = String::new();
+
+        for (label, value) in self.summary_rows() {
+            report.push_str(&format!("{}: {}\n", label, value));
+        }
+
+        for row in self.stream_rows() {
+            report.push_str(&format!("\nStream #{}\n", row[0]));
+            for (title, value) in STREAM_COLUMNS.iter().zip(row.iter()).skip(1) {
+                if value != UNKNOWN {
+                    report.push_str(&format!("  {}: {}\n", title, value));
+                }
+            }
+        }
+
+        report
+    }
+}
+
+impl From<ProbeStream> for StreamInfo {
+    fn from(stream: ProbeStream) -> Self {
+        let frame_rate = parse_frame_rate(stream.avg_frame_rate.as_deref())
+            .or_else(|| parse_frame_rate(stream.r_frame_rate.as_deref()));
+
+        Self {
+            index: stream.index,
+            kind: stream.codec_type.unwrap_or_else(|| String::from("unknown")),
+            codec: stream.codec_name,
+            width: stream.width,
+            height: stream.height,
+            sample_aspect_ratio: stream.sample_aspect_ratio,
+            display_aspect_ratio: stream.display_aspect_ratio,
+            sample_rate: parse_number(stream.sample_rate),
+            channels: stream.channels,
+            language: stream.tags.get("language").cloned(),
+            bit_rate: parse_number(stream.bit_rate),
+            frame_rate,
+            pix_fmt: stream.pix_fmt,
+            profile: stream.profile,
+            // -99 when unknown
+            level: stream.level.filter(|&level| level > 0),
+            time_base: stream.time_base,
+        }
+    }
+}
+
+impl StreamInfo {
+    fn row(&self) -> [String; 9] {
+        let format = match (self.width, self.height, self.sample_rate) {
+            (Some(width), Some(height), _) => Some(format!("{}x{}", width, height)),
+            (_, _, Some(sample_rate)) => Some(format!("{} Hz", sample_rate)),
+            _ => None,
+        };
+
+        let aspect = match (&self.sample_aspect_ratio, &self.display_aspect_ratio) {
+            (Some(sar), Some(dar)) => Some(format!("SAR {}, DAR {}", sar, dar)),
+            (None, Some(dar)) => Some(format!("DAR {}", dar)),
+            (Some(sar), None) => Some(format!("SAR {}", sar)),
+            (None, None) => None,
+        };
+
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| String::from(UNKNOWN));
+
+        [
+            self.index.to_string(),
+            self.kind.clone(),
+            or_unknown(self.codec.clone()),
+            or_unknown(format),
+            or_unknown(aspect),
+            or_unknown(self.channels.map(|channels| channels.to_string())),
+            or_unknown(self.language.clone()),
+            or_unknown(self.bit_rate.map(format_bit_rate)),
+            or_unknown(self.frame_rate.map(|rate| format!("{:.3} fps", rate))),
+        ]
+    }
+}
+
+fn parse_number<T: FromStr>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| value.parse().ok())
+}
+
+// rates look like `30000/1001`, `0/0` when unknown
+fn parse_frame_rate(rate: Option<&str>) -> Option<f64> {
+    let mut parts = rate?.splitn(2, '/');
+    let num: f64 = parts.next()?.parse().ok()?;
+    let den: f64 = parts.next().unwrap_or("1").parse().ok()?;
+    if num > 0.0 && den > 0.0 {
+        Some(num / den)
+    } else {
+        None
+    }
+}
+
+fn format_seconds(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn format_bit_rate(bit_rate: u64) -> String {
+    format!("{} kb/s", bit_rate / 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MKV_PROBE: &str = r#"{
+    "streams": [
+        {
+            "index": 0,
+            "codec_name": "h264",
+            "codec_type": "video",
+            "width": 1920,
+            "height": 1080,
+            "sample_aspect_ratio": "1:1",
+            "display_aspect_ratio": "16:9",
+            "pix_fmt": "yuv420p",
+            "profile": "High",
+            "level": 41,
+            "time_base": "1/1000",
+            "r_frame_rate": "24000/1001",
+            "avg_frame_rate": "24000/1001",
+            "tags": { "language": "eng", "DURATION": "00:42:13.512000000" }
+        },
+        {
+            "index": 1,
+            "codec_name": "aac",
+            "codec_type": "audio",
+            "profile": "LC",
+            "level": -99,
+            "sample_rate": "48000",
+            "channels": 6,
+            "bit_rate": "384000",
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0",
+            "tags": { "language": "eng" }
+        },
+        {
+            "index": 2,
+            "codec_name": "opus",
+            "codec_type": "audio",
+            "sample_rate": "48000",
+            "channels": 2,
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0",
+            "tags": { "language": "fre" }
+        },
+        {
+            "index": 3,
+            "codec_name": "subrip",
+            "codec_type": "subtitle",
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0",
+            "tags": { "language": "eng" }
+        },
+        {
+            "index": 4,
+            "codec_name": "ass",
+            "codec_type": "subtitle",
+            "r_frame_rate": "0/0",
+            "avg_frame_rate": "0/0"
+        }
+    ],
+    "format": {
+        "filename": "movie.mkv",
+        "nb_streams": 5,
+        "format_name": "matroska,webm",
+        "format_long_name": "Matroska / WebM",
+        "start_time": "0.000000",
+        "duration": "2533.512000",
+        "size": "1234567890",
+        "bit_rate": "3898312"
+    }
+}"#;
+
+    #[test]
+    fn parse_mkv_probe() {
+        let info = MediaInfo::from_json(MKV_PROBE).unwrap();
+        assert_eq!(info.container, "Matroska / WebM");
+        assert_eq!(info.duration, Some(2533.512));
+        assert_eq!(info.bit_rate, Some(3_898_312));
+        assert_eq!(info.streams.len(), 5);
+        assert_eq!(info.streams[1].sample_rate, Some(48000));
+        assert_eq!(info.streams[1].frame_rate, None);
+        assert_eq!(info.streams[4].language, None);
+        assert_eq!(info.streams[0].pix_fmt.as_deref(), Some("yuv420p"));
+        assert_eq!(info.streams[0].level, Some(41));
+        assert_eq!(info.streams[0].time_base.as_deref(), Some("1/1000"));
+        assert_eq!(info.streams[1].level, None);
+    }
+
+    #[test]
+    fn mkv_report_rows() {
+        let info = MediaInfo::from_json(MKV_PROBE).unwrap();
+
+        assert_eq!(
+            info.summary_rows(),
+            vec![
+                ("Container", String::from("Matroska / WebM")),
+                ("Duration", String::from("0:42:13.512")),
+                ("Bitrate", String::from("3898 kb/s")),
+            ]
+        );
+
+        let rows = info.stream_rows();
+        assert_eq!(
+            rows[0],
+            [
+                "0",
+                "video",
+                "h264",
+                "1920x1080",
+                "SAR 1:1, DAR 16:9",
+                "-",
+                "eng",
+                "-",
+                "23.976 fps"
+            ]
+        );
+        assert_eq!(
+            rows[1],
+            ["1", "audio", "aac", "48000 Hz", "-", "6", "eng", "384 kb/s", "-"]
+        );
+        assert_eq!(
+            rows[2],
+            ["2", "audio", "opus", "48000 Hz", "-", "2", "fre", "-", "-"]
+        );
+        assert_eq!(
+            rows[3],
+            ["3", "subtitle", "subrip", "-", "-", "-", "eng", "-", "-"]
+        );
+        assert_eq!(
+            rows[4],
+            ["4", "subtitle", "ass", "-", "-", "-", "-", "-", "-"]
+        );
+    }
+
+    #[test]
+    fn mkv_text_report() {
+        let info = MediaInfo::from_json(MKV_PROBE).unwrap();
+        let report = info.to_text();
+
+        assert!(report.starts_with(
+            "Container: Matroska / WebM\nDuration: 0:42:13.512\nBitrate: 3898 kb/s\n"
+        ));
+        assert!(report.contains(
+            "\nStream #2\n  Type: audio\n  Codec: opus\n  Format: 48000 Hz\n  Channels: 2\n  Language: fre\n"
+        ));
+        assert!(report.ends_with("\nStream #4\n  Type: subtitle\n  Codec: ass\n"));
+    }
+
+    #[test]
+    fn keyframe_packets() {
+        let csv = "10.010000,K_\n10.093000,__\n10.051000,__\nN/A,K_\n12.012000,K_\n\n11.011000,KD\n12.012000,K_\n";
+        assert_eq!(parse_keyframes(csv), vec![10.01, 11.011, 12.012]);
+        assert!(parse_keyframes("").is_empty());
+    }
+
+    #[test]
+    fn chapters_probe() {
+        let json = r#"{
+    "chapters": [
+        {
+            "id": 0,
+            "time_base": "1/1000000000",
+            "start": 0,
+            "start_time": "0.000000",
+            "end": 95500000000,
+            "end_time": "95.500000",
+            "tags": { "title": "Opening" }
+        },
+        {
+            "id": 1,
+            "time_base": "1/1000000000",
+            "start": 95500000000,
+            "start_time": "95.500000",
+            "end": 600000000000,
+            "end_time": "600.000000"
+        }
+    ]
+}"#;
+        assert_eq!(
+            parse_chapters(json).unwrap(),
+            vec![
+                Chapter {
+                    start: chrono::Duration::zero(),
+                    end: chrono::Duration::milliseconds(95_500),
+                    title: String::from("Opening"),
+                },
+                Chapter {
+                    start: chrono::Duration::milliseconds(95_500),
+                    end: chrono::Duration::seconds(600),
+                    title: String::from("Chapter 2"),
+                },
+            ]
+        );
+        assert_eq!(parse_chapters("{}").unwrap(), vec![]);
+        assert!(parse_chapters("").is_err());
+    }
+
+    #[test]
+    fn invalid_probe_output() {
+        assert!(MediaInfo::from_json("").is_err());
+        assert!(MediaInfo::from_json("{\"streams\": []}").is_err());
+    }
+}
@@ -1,294 +1,9014 @@
 use std::{
+    collections::HashMap,
     env,
-    fs::{create_dir_all, remove_file},
-    path::PathBuf,
-    process::{Command, Output, Stdio},
+    fs::{self, create_dir_all, remove_file, write, OpenOptions},
+    io::{BufRead, BufReader, ErrorKind, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
     str::from_utf8,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use lazy_static::lazy_static;
+use log::{error, info, warn};
 use regex::Regex;
+use serde::Deserialize;
 
-use crate::{build_args_string, duration_to_string, Config};
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+use crate::{
+    build_args_string,
+    capabilities::{self, Capabilities},
+    chapters, containers, db_to_ratio, duration_to_string,
+    filtergraph::{FilterGraph, Label},
+    image_sequence,
+    logging::LogLaunch,
+    output_pattern, parse_stream_specifier, probe,
+    progress::ProgressTracker,
+    silence::{self, Segment},
+    smartcut::{self, EncoderSettings, Piece, PieceKind},
+    AacEncoder, AacOptions, AacProfile, AspectPolicy, ChannelOp, ColorAdjust, Config, FilterChain,
+    Mp3Quality, Operation, ReverbSettings, TimecodePosition, TimecodeSource, TimecodeStyle,
+    VideoDenoise, VisualStyle,
+};
 
 type Result<T> = std::result::Result<T, String>;
 
 lazy_static! {
     static ref MAX_VOLUME_RE: Regex =
         Regex::new(r#"max_volume:\s*(?P<max>-?[0-9\.]+)\s*dB"#).unwrap();
+    static ref TRANSIENT_ERROR_RE: Regex = Regex::new(
+        r#"(?i)(connection (refused|reset|timed out)|operation timed out|network is unreachable|temporary failure in name resolution|server returned 5(xx|[0-9]{2})|broken pipe)"#
+    )
+    .unwrap();
+    // a stream cut short, a truncated local file is not worth retrying
+    static ref END_OF_FILE_RE: Regex = Regex::new(r#"(?i)end of file"#).unwrap();
+    static ref PERMANENT_ERROR_RE: Regex = Regex::new(
+        r#"(?i)(unrecognized option|option not found|invalid argument|error parsing|no such filter|unknown encoder|no such file or directory|no space left on device|server returned 4(xx|[0-9]{2}))"#
+    )
+    .unwrap();
+    // ENOSPC, and its Windows counterpart ERROR_DISK_FULL
+    static ref DISK_FULL_RE: Regex =
+        Regex::new(r#"(?i)(no space left on device|not enough space on the disk)"#).unwrap();
+    static ref MD5_RE: Regex = Regex::new(r#"MD5=(?P<hash>[0-9a-f]{32})"#).unwrap();
+    static ref UNKNOWN_ENCODER_RE: Regex =
+        Regex::new(r#"Unknown encoder '(?P<name>[^']+)'"#).unwrap();
+    // what ffmpeg reports when reading a truncated or damaged input
+    static ref DAMAGED_INPUT_RE: Regex = Regex::new(
+        r#"(?i)(invalid data found when processing input|error while decoding stream|corrupt decoded frame|corrupt input packet|header missing|invalid nal unit size|packet mismatch|error reading header)"#
+    )
+    .unwrap();
+    static ref SNIPPET_PLAYER: Mutex<SnippetPlayer> = Mutex::new(SnippetPlayer::default());
+    static ref LOW_PRIORITY_WRAPPER: Vec<String> =
+        make_low_priority_wrapper(env::consts::OS, is_in_path);
 }
 
-const FFMPEG_COMMAND: &str = "ffmpeg";
-const FFPLAY_COMMAND: &str = "ffplay";
-const SOX_COMMAND: &str = "sox";
+pub(crate) const FFMPEG_COMMAND: &str = "ffmpeg";
+pub(crate) const FFPLAY_COMMAND: &str = "ffplay";
+pub(crate) const SOX_COMMAND: &str = "sox";
 const TMP_DIRECTORY: &str = "media_cutter_tmp";
+const VIDSTAB_TRANSFORMS_FILE: &str = "transforms.trf";
+// named after the process and the run: concurrent jobs share the temporary directory
+const CHAPTERS_FILE_PREFIX: &str = "chapters_";
+const SMART_CUT_LIST_FILE: &str = "smartcut.txt";
+const DISK_FULL_ERROR: &str = "Ran out of disk space while writing";
+const OUTPUT_DIR_PERMISSION_ERROR: &str = "Cannot write to output directory: permission denied";
+// created then deleted to check that the output directory is writable
+const WRITE_TEST_FILE_PREFIX: &str = ".media_cutter_write_test_";
+// ffmpeg output standing for its stdout, see `Config::writes_to_stdout`
+const STDOUT_PIPE: &str = "pipe:1";
+// last lines of a failed command error output worth logging, ffmpeg ends with the reason
+const STDERR_EXCERPT_LINES: usize = 5;
+// runs of this process which planned a chapters file, see `CHAPTERS_FILE_PREFIX`
+static CHAPTERS_FILES: AtomicUsize = AtomicUsize::new(0);
+// checks of this process, queued jobs check the same directory at once
+static WRITE_TEST_FILES: AtomicUsize = AtomicUsize::new(0);
+// probing limits of damaged inputs, 100 seconds and 100 MB instead of 5 of each
+const TOLERANT_ANALYZE_DURATION: &str = "100M";
+const TOLERANT_PROBE_SIZE: &str = "100M";
+// extensions of the outputs written by the muxers below
+const MP4_EXTENSIONS: [&str; 4] = ["mp4", "m4a", "m4v", "mov"];
+// muxers taking movflags, as named to `-f`: `m4v` is raw MPEG-4 video
+const MP4_MUXERS: [&str; 7] = ["mp4", "mov", "ipod", "ismv", "3gp", "3g2", "f4v"];
+// frames are scaled down to this width before being tiled
+pub(crate) const CONTACT_SHEET_THUMBNAIL_WIDTH: u32 = 320;
+// shortest slice of the selection a contact sheet picks a frame in, the filter
+// divides by it: selections shorter than the tiles then show consecutive frames
+const CONTACT_SHEET_MIN_INTERVAL_MS: i64 = 1;
+// rate loudness normalized audio is brought back to when the input one is unknown
+const LOUDNESS_DEFAULT_SAMPLE_RATE: u32 = 48000;
+// encoders of audio visualisations and still image videos when none is chosen
+const VISUALIZE_VIDEO_CODEC: &str = "libx264";
+const VISUALIZE_AUDIO_CODEC: &str = "aac";
+// encoder of the cover pictures taken from a video, unless png is chosen
+const THUMBNAIL_CODEC: &str = "mjpeg";
+// picture based subtitles, SRT only holds text
+const BITMAP_SUBTITLE_CODECS: [&str; 4] =
+    ["dvd_subtitle", "dvb_subtitle", "hdmv_pgs_subtitle", "xsub"];
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+// seconds an output may last more or less than the selection without a warning
+const OUTPUT_DURATION_TOLERANCE_SECS: f64 = 0.5;
+#[cfg(windows)]
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+pub(crate) const CANCELLED_ERROR: &str = "Processing was cancelled.";
+
+/// Input loudness measured by the first loudnorm pass, see
+/// `Config::loudnorm_two_pass`.
+#[derive(Clone, PartialEq, Debug)]
+struct LoudnessMeasurement {
+    /// Integrated loudness in LUFS.
+    integrated: f64,
+    /// True peak in dBTP.
+    true_peak: f64,
+    /// Loudness range in LU.
+    range: f64,
+    /// Gating threshold in LUFS.
+    threshold: f64,
+    /// Gain in LU loudnorm applies after its limiter.
+    offset: f64,
+}
+
+// fields of the loudnorm JSON statistics, its numbers are strings
+#[derive(Deserialize)]
+struct LoudnormOutput {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
 
 struct State {
     max_volume_db: Option<f64>,
+    /// Set once `LoudnessMeasure` ran.
+    loudness_measurement: Option<LoudnessMeasurement>,
     sox_output_file: Option<String>,
     already_trimed: bool,
+    vidstab_transforms_file: Option<String>,
+    sample_rate: Option<u32>,
+    /// Probed channel count of the input audio, if known.
+    audio_channels: Option<u32>,
+    /// Probed codec of the input audio, if known.
+    audio_codec: Option<String>,
+    /// Audio stream ffmpeg would keep by default, counted among audio
+    /// streams, `None` without audio.
+    audio_stream: Option<usize>,
+    /// Whether the streams of the input are known from probing.
+    probed_streams: bool,
+    /// Whether probing found a video stream in the input.
+    has_video: bool,
+    /// Speech and silences of the selection, known once `SilenceDetect` ran.
+    silence_segments: Option<Vec<Segment>>,
+    /// ffmetadata file holding the chapters, written just before `Process`.
+    chapters_file: Option<String>,
+    /// Probed duration of the input, if known.
+    input_duration: Option<chrono::Duration>,
+    /// Keyframes and encoder of the input, probed for a smart cut.
+    smart_cut: Option<smartcut::Source>,
+    /// Concat list joining the pieces of a smart cut and its content, written
+    /// just before `Process`.
+    smart_cut_list: Option<(String, String)>,
+    /// Where intermediate files are written.
+    tmp_dir: PathBuf,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             max_volume_db: None,
+            loudness_measurement: None,
             sox_output_file: None,
             already_trimed: false,
+            vidstab_transforms_file: None,
+            sample_rate: None,
+            audio_channels: None,
+            audio_codec: None,
+            audio_stream: None,
+            probed_streams: false,
+            has_video: false,
+            silence_segments: None,
+            chapters_file: None,
+            input_duration: None,
+            smart_cut: None,
+            smart_cut_list: None,
+            tmp_dir: tmp_dir_path(&Config::default()),
         }
     }
 }
 
-pub fn run(conf: &Config) -> Result<()> {
-    let mut state = State::default();
+/// Report of a successful run.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RunOutcome {
+    pub warnings: Vec<String>,
+    /// Encoders replaced because the installed ffmpeg lacks them.
+    pub substitutions: Vec<CodecSubstitution>,
+    /// Time taken by each step, in run order.
+    pub timings: Vec<(StepKind, Duration)>,
+    pub lossless_verification: Option<LosslessVerification>,
+    /// Files written by the process step, additional outputs included.
+    pub output_files: Vec<String>,
+    /// Streams of the input written to the output, as `#3 subtitle
+    /// (subrip)`, see `Config::stream_copy`.
+    pub copied_streams: Vec<String>,
+    /// Error output line of the failed run retried with
+    /// `Config::tolerant_input`, if any.
+    pub tolerant_retry: Option<String>,
+}
 
-    if conf.noise_profile_file.is_some() && conf.noise_reduction_amount.is_some() {
-        let mut tmp_dir = env::temp_dir();
-        tmp_dir.push(TMP_DIRECTORY);
-        create_dir_all(&tmp_dir)
-            .map_err(|e| format!("Could not create temporary directory.\nError: {}", e))?;
-
-        let input_file_pathbuf = PathBuf::from(conf.input_file.clone());
-        match input_file_pathbuf.file_name() {
-            Some(filename) => tmp_dir.push(filename),
-            None => {
-                return Err(String::from("Error: no input file provided."));
-            }
-        }
-        let sox_output_file = tmp_dir.to_string_lossy().into_owned();
+/// Hashes of the decoded audio samples, equal when the output lost nothing.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LosslessVerification {
+    pub input_md5: String,
+    pub output_md5: String,
+}
 
-        let sox_noise_profile_args = make_sox_generate_noiseprof_args(conf)?;
-        let sox_clean_noise_args = make_sox_clean_noise_args(conf, &sox_output_file)?;
+impl LosslessVerification {
+    pub fn is_lossless(&self) -> bool {
+        self.input_md5 == self.output_md5
+    }
+}
 
-        let child = command_map_error(
-            Command::new(SOX_COMMAND)
-                .args(&sox_noise_profile_args[..])
-                .stdout(Stdio::piped())
-                .spawn(),
-            SOX_COMMAND,
-            &sox_noise_profile_args,
-        )?;
+/// Encoder used instead of the requested one, see `Config::strict_codecs`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CodecSubstitution {
+    pub requested: String,
+    pub used: String,
+}
 
-        let sox_output = command_map_error(
-            Command::new(SOX_COMMAND)
-                .args(&sox_clean_noise_args[..])
-                .stdin(child.stdout.unwrap())
-                .output(),
-            SOX_COMMAND,
-            &sox_clean_noise_args,
-        )?;
-        output_map_error(&sox_output, SOX_COMMAND, &sox_clean_noise_args)?;
+/// Shared between the threads running jobs and the one cancelling them.
+#[derive(Clone, Debug, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
 
-        state.sox_output_file = Some(sox_output_file);
-        state.already_trimed = true;
+impl CancelFlag {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    if conf.peak_normalization {
-        let args = make_ffmpeg_detect_max_volume_args(conf);
-        let output = run_command_and_get_output(FFMPEG_COMMAND, &args)?;
-        output_map_error(&output, FFMPEG_COMMAND, &args)?;
-        if let Some(caps) = MAX_VOLUME_RE.captures(from_utf8(&output.stderr).unwrap()) {
-            // pattern matched by the regex should be parsable into f64, hence unwrap.
-            state.max_volume_db = Some(caps["max"].parse::<f64>().unwrap());
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// What a planned command is for.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StepKind {
+    /// sox noise profile, piped into the following `NoiseReduction` step.
+    NoiseProfile,
+    NoiseReduction,
+    /// First vidstab pass, writing the transforms used by `Process`.
+    StabilizationDetect,
+    /// ffmpeg volumedetect, its output sets the gain of the `Process` step.
+    PeakMeasure,
+    /// First loudnorm pass, its statistics are fed to the second one run by
+    /// the `Process` step, see `Config::loudnorm_two_pass`.
+    LoudnessMeasure,
+    /// ffmpeg silencedetect, its output cuts the `Process` step into segments
+    /// played at different speeds, see `Config::silence_speedup`.
+    SilenceDetect,
+    /// ffmpeg silencedetect, its output moves the start of the selection to
+    /// its first sound, see `Config::trim_to_first_sound`. The steps after it
+    /// are planned again from there.
+    FirstSoundDetect,
+    /// The command producing the output, or playing it for a preview.
+    Process,
+    /// Subtitles saved next to the output, see `Config::extract_subtitles`.
+    SubtitleExtract,
+    /// MD5 of the decoded audio, of the input selection then of the output,
+    /// see `Config::verify_lossless`.
+    LosslessCheck,
+    /// Piece of a smart cut, re-encoded or copied, joined by `Process`.
+    SmartCutPiece,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlannedCommand {
+    pub kind: StepKind,
+    pub program: String,
+    pub args: Vec<String>,
+    /// Started through `nice` and `ionice`, see `Config::low_priority`.
+    pub low_priority: bool,
+    /// Niceness adjustment given to `nice` on Unix, see `Config::nice_level`.
+    pub nice_level: Option<i32>,
+    /// Directory the program is started in, see `Config::working_dir`.
+    pub working_dir: Option<PathBuf>,
+}
+
+impl PlannedCommand {
+    /// Program and arguments as started, low priority wrapper included. On
+    /// Windows, the priority is lowered by a process creation flag instead.
+    pub fn command_line(&self) -> Vec<String> {
+        let mut command_line = self.wrapper();
+        command_line.push(self.program.clone());
+        command_line.extend_from_slice(&self.args);
+        command_line
+    }
+
+    fn wrapper(&self) -> Vec<String> {
+        let wrapper = if self.low_priority {
+            LOW_PRIORITY_WRAPPER.clone()
+        } else {
+            Vec::new()
+        };
+        match self.nice_level {
+            Some(level) => with_nice_level(wrapper, env::consts::OS, level, is_in_path),
+            None => wrapper,
         }
     }
 
-    let command_name = if conf.preview {
-        FFPLAY_COMMAND
-    } else {
-        FFMPEG_COMMAND
-    };
-    let args = make_ffmpeg_processing_args(conf, &state);
-    let output = run_command_and_get_output(command_name, &args)?;
-    output_map_error(&output, command_name, &args)?;
+    // `args` replace the planned ones, e.g. to report the progress
+    fn to_process(&self, args: &[String]) -> Command {
+        let mut process = match self.wrapper().split_first() {
+            Some((wrapper, wrapper_args)) => {
+                let mut process = Command::new(wrapper);
+                process.args(wrapper_args).arg(&self.program);
+                process
+            }
+            None => Command::new(&self.program),
+        };
+        process.args(args);
+        if let Some(ref working_dir) = self.working_dir {
+            process.current_dir(working_dir);
+        }
 
-    if let Some(sox_output_file) = state.sox_output_file {
-        // clean temporary file
-        remove_file(sox_output_file)
-            .map_err(|e| format!("Could not delete temporary file.\nError: {}", e))?;
+        #[cfg(windows)]
+        {
+            if self.low_priority {
+                process.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+            }
+        }
+
+        process.logged();
+        process
     }
+}
 
-    Ok(())
+/// Commands `run` executes, in order.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ExecutionPlan {
+    pub commands: Vec<PlannedCommand>,
+    /// Intermediate files deleted once done.
+    pub temporary_files: Vec<String>,
 }
 
-fn command_map_error<T, E>(
-    result: std::result::Result<T, E>,
-    command_name: &str,
-    args: &[String],
-) -> Result<T>
+/// Commands `run` would execute for `conf`, without running anything.
+///
+/// The input is not probed: padding is not clamped to the input duration,
+/// sample-accurate trimming is not planned and the audio is encoded even
+/// where `run` would copy it, see `plan_with_info`. With peak
+/// normalization, the gain of the `Process` step is only known once
+/// `PeakMeasure` has run, as are the measured values of a two-pass loudness
+/// normalization once `LoudnessMeasure` has. The contact sheet of a whole
+/// file cannot be planned. Neither can a smart cut, which depends on the
+/// keyframes of the input.
+pub fn plan(conf: &Config) -> Result<ExecutionPlan> {
+    plan_for_streams(conf, None)
+}
+
+/// Same as `plan`, with the streams of the input already probed as `info`,
+/// which decide as in `run` whether the audio is copied.
+pub fn plan_with_info(conf: &Config, info: &probe::MediaInfo) -> Result<ExecutionPlan> {
+    plan_for_streams(conf, Some(info))
+}
+
+fn plan_for_streams(conf: &Config, info: Option<&probe::MediaInfo>) -> Result<ExecutionPlan> {
+    conf.validate()?;
+    let mut conf = conf.with_output_pattern(1);
+    if conf.operation == Operation::Process {
+        check_output_extension(&mut conf, &mut RunOutcome::default())?;
+    }
+    let mut state = State::default();
+    if let Some(info) = info {
+        if !matches!(conf.operation, Operation::FixRotation { .. }) {
+            set_probed_streams(&mut state, info);
+        }
+    }
+    make_plan(&conf, &mut state)
+}
+
+pub fn run(conf: &Config) -> Result<RunOutcome> {
+    run_with_progress(conf, |_| {})
+}
+
+/// Same as `run`, `on_progress` is called while ffmpeg encodes the output.
+pub fn run_with_progress<F>(conf: &Config, on_progress: F) -> Result<RunOutcome>
 where
-    E: std::fmt::Display,
+    F: FnMut(&ProgressTracker),
 {
-    result.map_err(|err| {
-        format!(
-            "Failed to start: {}\nCommand was: {} {}",
-            err,
-            command_name,
-            build_args_string(&args[..])
-        )
-    })
+    // a single input is the first of its batch
+    run_job(
+        conf,
+        1,
+        &tmp_dir_path(conf),
+        &CancelFlag::new(),
+        on_progress,
+    )
 }
 
-fn output_map_error(output: &Output, command_name: &str, args: &[String]) -> Result<()> {
-    if output.status.success() {
-        Ok(())
+/// Same as `run_with_progress` for the `index`-th input of a batch, starting
+/// at 1. Intermediate files are written to `tmp_dir`, and the run stops with
+/// an error once `cancel` is set.
+pub fn run_job<F>(
+    conf: &Config,
+    index: usize,
+    tmp_dir: &Path,
+    cancel: &CancelFlag,
+    mut on_progress: F,
+) -> Result<RunOutcome>
+where
+    F: FnMut(&ProgressTracker),
+{
+    let mut state = State {
+        tmp_dir: tmp_dir.to_path_buf(),
+        ..State::default()
+    };
+    let mut outcome = RunOutcome::default();
+
+    conf.validate()?;
+
+    let named_conf = conf.with_output_pattern(index);
+    let conf = &named_conf;
+    // the pattern was only checked for the first input
+    conf.validate_output_file()?;
+
+    if !conf.preview && !conf.writes_to_stdout() {
+        prepare_output_dir(conf)?;
+    }
+
+    let mut duration = None;
+    if let Operation::FixRotation { .. } = conf.operation {
+        // the whole file is remuxed, the selection does not matter
+        if !probe::has_video_stream(&conf.resolve_path(&conf.input_file).to_string_lossy())? {
+            return Err(String::from("Error: input file has no video stream."));
+        }
     } else {
-        match output.status.code() {
-            Some(code) => Err(format!(
-                "⚠ {} exited with non-zero status code: {}\n\nArguments were: {}\n\nError output: {}",
-                command_name,
-                code,
-                build_args_string(args),
-                String::from_utf8_lossy(&output.stderr)
+        // ffprobe failing is not fatal: ffmpeg reports unreadable inputs by itself
+        match probe_input(conf) {
+            Ok(info) => {
+                duration = info
+                    .duration
+                    .map(|secs| chrono::Duration::milliseconds((secs * 1000.0).round() as i64));
+                outcome.warnings.extend(conf.check_duration(duration)?);
+                check_subtitle_stream(conf, &info)?;
+                check_audio_track_count(conf, &info)?;
+                check_audio_channels(conf, &info)?;
+                check_video_streams(conf, &info)?;
+                set_probed_streams(&mut state, &info);
+                if conf.stream_copy {
+                    outcome.copied_streams = copied_streams(conf, &info);
+                }
+            }
+            Err(e) => outcome.warnings.push(format!(
+                "Could not check the selection against the input file duration.\n{}",
+                e
             )),
-            None => Err(format!("⚠ {} terminated by signal", command_name)),
         }
     }
-}
 
-fn run_command_and_get_output(command_name: &str, args: &[String]) -> Result<Output> {
-    command_map_error(
-        Command::new(command_name).args(&args[..]).output(),
-        command_name,
-        args,
-    )
-}
+    state.input_duration = duration;
 
-fn make_sox_generate_noiseprof_args(conf: &Config) -> Result<Vec<String>> {
-    let mut args = Vec::with_capacity(3);
-    match conf.noise_profile_file {
-        Some(ref filename) => {
-            if filename == "" {
-                return Err(String::from("Error: no noise file provided."));
-            } else {
-                args.push(filename.clone()); // input noise file
+    // every following stage works on the padded selection
+    let mut conf = conf.padded(duration);
+
+    // copied chapters would keep the input times, they are moved like chosen ones
+    if conf.stream_copy && conf.write_chapters.is_none() && conf.trims_input() && !conf.preview {
+        match probe::chapters(&conf.resolve_path(&conf.input_file).to_string_lossy()) {
+            Ok(chapters) => conf.write_chapters = Some(chapters),
+            Err(e) => outcome.warnings.push(format!(
+                "The chapters of the input could not be moved to the selection.\n{}",
+                e
+            )),
+        }
+    }
+
+    if let Operation::Visualize { .. } = conf.operation {
+        check_required_filters(&conf)?;
+    }
+    if conf.operation == Operation::Process {
+        check_output_extension(&mut conf, &mut outcome)?;
+        check_required_filters(&conf)?;
+        if !conf.strict_codecs && !requested_encoders(&conf).is_empty() {
+            // not fatal: ffmpeg reports missing encoders by itself, see below
+            if let Ok(capabilities) = Capabilities::detect(&conf) {
+                substitute_missing_encoders(&mut conf, &capabilities, &mut outcome);
             }
         }
-        None => {
-            return Err(String::from(
-                "Unexpected error: could not build noise profile generation command.",
-            ));
+        push_process_warnings(&conf, &state, &mut outcome);
+    }
+
+    if conf.operation == Operation::SmartCut {
+        let input_file = conf
+            .resolve_path(&conf.input_file)
+            .to_string_lossy()
+            .into_owned();
+        let encoder = EncoderSettings::matching(&probe::MediaInfo::probe(&input_file)?)?;
+        let keyframes = probe::keyframes(
+            &input_file,
+            conf.from_time.num_milliseconds() as f64 / 1000.0,
+            conf.to_time.num_milliseconds() as f64 / 1000.0,
+        )?;
+        state.smart_cut = Some(smartcut::Source { encoder, keyframes });
+        outcome
+            .warnings
+            .push(String::from(smartcut::DISCONTINUITY_WARNING));
+    }
+
+    let mut plan = make_plan(&conf, &mut state)?;
+
+    if !plan.temporary_files.is_empty() {
+        create_tmp_dir(&state.tmp_dir)?;
+    }
+
+    let total = progress_total(&conf, duration);
+    let existing_files = existing_output_files(&conf);
+    let result = run_plan(
+        &mut plan,
+        &mut conf,
+        &mut state,
+        total,
+        cancel,
+        &mut on_progress,
+        &mut outcome,
+    );
+    if let Err(ref e) = result {
+        if e.starts_with(DISK_FULL_ERROR) {
+            remove_partial_files(&conf, &plan, &existing_files);
+        } else {
+            // e.g. the stabilization transforms of a cancelled run
+            remove_temporary_files(&plan);
         }
     }
-    args.push(String::from("-n"));
-    args.push(String::from("noiseprof"));
-    Ok(args)
+    result?;
+
+    // a piped output is gone once written, nothing to probe
+    if matches!(
+        conf.operation,
+        Operation::Process | Operation::SmartCut | Operation::Visualize { .. }
+    ) && !conf.preview
+        && !conf.writes_to_stdout()
+    {
+        // the selection may have moved, see `StepKind::FirstSoundDetect`
+        let expected = expected_output_duration(&conf, &state);
+        // each chunk lasts a segment, not the selection
+        if let (Some(expected), None) = (expected, conf.segment_time) {
+            outcome
+                .warnings
+                .extend(check_output_durations(&conf, expected));
+        }
+        outcome.output_files = match conf.segment_time {
+            // the chunks written, the output file is their pattern
+            Some(_) => image_sequence::numbered_files(
+                &conf.resolve_path(&conf.output_file).to_string_lossy(),
+                0,
+            ),
+            None => output_files(&conf),
+        };
+    }
+
+    for temporary_file in &plan.temporary_files {
+        // clean temporary file
+        remove_file(temporary_file)
+            .map_err(|e| format!("Could not delete temporary file.\nError: {}", e))?;
+    }
+
+    Ok(outcome)
 }
 
-fn make_sox_clean_noise_args(conf: &Config, sox_output_file: &str) -> Result<Vec<String>> {
-    let mut args = Vec::with_capacity(5);
+// runs the planned commands in order, recording how long each one took. `total`
+// is the expected output duration, see `progress_total`.
+fn run_plan<F>(
+    plan: &mut ExecutionPlan,
+    conf: &mut Config,
+    state: &mut State,
+    mut total: Option<f64>,
+    cancel: &CancelFlag,
+    on_progress: &mut F,
+    outcome: &mut RunOutcome,
+) -> Result<()>
+where
+    F: FnMut(&ProgressTracker),
+{
+    // input selection then output, see `StepKind::LosslessCheck`
+    let mut hashes = Vec::new();
+    let mut index = 0;
+    while index < plan.commands.len() {
+        if cancel.is_cancelled() {
+            return Err(String::from(CANCELLED_ERROR));
+        }
 
-    args.push(conf.input_file.clone()); // input file
-    args.push(sox_output_file.to_string()); // output file
+        let started = Instant::now();
+        let command = plan.commands[index].clone();
+        match command.kind {
+            StepKind::NoiseProfile => {
+                // the profile goes straight into the following reduction step
+                let reduction = &plan.commands[index + 1];
+                let profile_time = run_piped_commands(&command, reduction)?;
+                record_timing(outcome, StepKind::NoiseProfile, profile_time);
+                record_timing(
+                    outcome,
+                    reduction.kind,
+                    started.elapsed().saturating_sub(profile_time),
+                );
+                index += 2;
+                continue;
+            }
+            StepKind::PeakMeasure => {
+                let output =
+                    run_measurement(conf, outcome, &command, make_ffmpeg_detect_max_volume_args)?;
+                if let Some(caps) = MAX_VOLUME_RE.captures(from_utf8(&output.stderr).unwrap()) {
+                    // pattern matched by the regex should be parsable into f64, hence unwrap.
+                    state.max_volume_db = Some(caps["max"].parse::<f64>().unwrap());
+                    for planned in plan.commands.iter_mut() {
+                        if planned.kind == StepKind::Process {
+                            planned.args = make_ffmpeg_processing_args(conf, state);
+                        }
+                    }
+                }
+            }
+            StepKind::LoudnessMeasure => {
+                // only planned with a target, see `make_plan`
+                let target = conf.loudness_target.unwrap_or_default();
+                let output = run_measurement(conf, outcome, &command, |conf| {
+                    make_ffmpeg_loudness_measure_args(conf, state, target)
+                })?;
+                state.loudness_measurement =
+                    parse_loudness_measurement(&String::from_utf8_lossy(&output.stderr))?;
+                if state.loudness_measurement.is_none() {
+                    outcome.warnings.push(String::from(
+                        "The selection is silent, its loudness was normalized in a single pass.",
+                    ));
+                }
+                for planned in plan.commands.iter_mut() {
+                    if planned.kind == StepKind::Process {
+                        planned.args = make_ffmpeg_processing_args(conf, state);
+                    }
+                }
+            }
+            StepKind::SilenceDetect => {
+                // the output of the process step is shorter once the silences are sped up
+                let duration =
+                    match total {
+                        Some(duration) => duration,
+                        None => return Err(String::from(
+                            "Error: input file duration is unknown, silences cannot be sped up.",
+                        )),
+                    };
+                // only planned with a speedup, see `make_plan`
+                let speedup = conf.silence_speedup.clone().unwrap_or_default();
+                let output = run_measurement(conf, outcome, &command, |conf| {
+                    make_ffmpeg_detect_silence_args(
+                        conf,
+                        state,
+                        speedup.noise_db,
+                        speedup.min_silence,
+                    )
+                })?;
+                let silences =
+                    silence::parse_silences(&String::from_utf8_lossy(&output.stderr), duration);
+                let segments = silence::plan_segments(&silences, duration, speedup.factor);
+                total = Some(silence::output_duration(&segments));
+                state.silence_segments = Some(segments);
+                for planned in plan.commands.iter_mut() {
+                    if planned.kind == StepKind::Process {
+                        planned.args = make_ffmpeg_processing_args(conf, state);
+                    }
+                }
+            }
+            StepKind::FirstSoundDetect => {
+                let output = run_planned_command(&command, conf.max_retries)?;
+                let duration = (conf.to_time - conf.from_time).num_milliseconds() as f64 / 1000.0;
+                let silences =
+                    silence::parse_silences(&String::from_utf8_lossy(&output.stderr), duration);
+                match silence::first_sound(&silences, duration) {
+                    Some(first_sound) => {
+                        let start = silence::lead_in_start(first_sound);
+                        conf.from_time +=
+                            chrono::Duration::milliseconds((start * 1000.0).round() as i64);
+                        total = progress_total(conf, state.input_duration);
+                    }
+                    None => outcome.warnings.push(String::from(
+                        "The selection is silent all along, it was not trimmed to its first sound.",
+                    )),
+                }
 
-    // trim the file with sox for increased noise reduction process speed
-    let duration = conf.to_time - conf.from_time;
-    args.push(String::from("trim"));
-    args.push(duration_to_string(conf.from_time));
-    args.push(duration_to_string(duration));
+                // everything else works on the new selection
+                conf.trim_to_first_sound = false;
+                let replanned = make_plan(conf, state)?;
+                plan.commands.truncate(index + 1);
+                plan.commands.extend(replanned.commands);
+                plan.temporary_files = replanned.temporary_files;
+            }
+            StepKind::Process if command.program == ffmpeg_program(conf) => {
+                // rebased on the output timeline, which sped up silences change
+                if let Some(ref chapters_file) = state.chapters_file {
+                    write_chapters_file(conf, state, chapters_file)?;
+                }
+                if let Some((ref list_file, ref list)) = state.smart_cut_list {
+                    write(list_file, list).map_err(|e| {
+                        format!("Could not write the smart cut list.\nError: {}", e)
+                    })?;
+                }
+                // a retry would pipe a second output after the first one
+                let max_retries = if conf.writes_to_stdout() {
+                    0
+                } else {
+                    conf.max_retries
+                };
+                let state = &*state;
+                run_with_tolerant_retry(conf, outcome, |conf, outcome| {
+                    run_with_encoder_fallback(conf, outcome, |conf| {
+                        // rebuilt to use the fallback encoder or the tolerant
+                        // input options when retrying
+                        let command = match conf.operation {
+                            Operation::Process | Operation::Visualize { .. } => PlannedCommand {
+                                args: make_ffmpeg_processing_args(conf, state),
+                                ..command.clone()
+                            },
+                            _ => command.clone(),
+                        };
+                        let network = reads_network(&command.args);
+                        let output = retry(max_retries, RETRY_BASE_DELAY, network, || {
+                            let mut tracker = ProgressTracker::new(total);
+                            run_command_with_progress(&command, &mut tracker, cancel, on_progress)
+                        })?;
+                        output_map_error(&output, &command.program, &command.args)
+                    })
+                })?;
+            }
+            StepKind::LosslessCheck => {
+                let output = run_planned_command(&command, conf.max_retries)?;
+                match MD5_RE.captures(&String::from_utf8_lossy(&output.stdout)) {
+                    Some(caps) => hashes.push(String::from(&caps["hash"])),
+                    None => {
+                        return Err(String::from(
+                            "Error: could not read the audio hash for the lossless verification.",
+                        ))
+                    }
+                }
+            }
+            StepKind::StabilizationDetect => {
+                // it reads the whole selection, which takes about as long as processing it
+                let analyzed = progress_total(conf, state.input_duration);
+                let output = retry(
+                    conf.max_retries,
+                    RETRY_BASE_DELAY,
+                    reads_network(&command.args),
+                    || {
+                        let mut tracker = ProgressTracker::new(analyzed);
+                        run_command_with_progress(&command, &mut tracker, cancel, on_progress)
+                    },
+                )?;
+                output_map_error(&output, &command.program, &command.args)?;
+            }
+            StepKind::NoiseReduction
+            | StepKind::Process
+            | StepKind::SubtitleExtract
+            | StepKind::SmartCutPiece => {
+                run_planned_command(&command, conf.max_retries)?;
+            }
+        }
+        record_timing(outcome, command.kind, started.elapsed());
+        index += 1;
+    }
 
-    args.push(String::from("noisered"));
-    args.push(String::from("-")); // take noise profile from stdin
-    match conf.noise_reduction_amount {
-        Some(amount) => args.push(amount.to_string()),
-        None => {
-            return Err(String::from(
-                "Unexpected error: could not build noise cleaning command.",
+    if let [input_md5, output_md5] = &hashes[..] {
+        let verification = LosslessVerification {
+            input_md5: input_md5.clone(),
+            output_md5: output_md5.clone(),
+        };
+        if !verification.is_lossless() {
+            outcome.warnings.push(String::from(
+                "Lossless verification failed: the output audio differs from the input selection.",
             ));
         }
+        outcome.lossless_verification = Some(verification);
     }
 
-    Ok(args)
+    Ok(())
 }
 
-fn make_ffmpeg_detect_max_volume_args(conf: &Config) -> Vec<String> {
-    let mut args = Vec::with_capacity(15);
-
-    args.push(String::from("-nostdin"));
+fn record_timing(outcome: &mut RunOutcome, kind: StepKind, time: Duration) {
+    info!("{:?} step took {:.2?}", kind, time);
+    outcome.timings.push((kind, time));
+}
 
-    args.push(String::from("-i"));
-    args.push(conf.input_file.clone());
+fn make_plan(conf: &Config, state: &mut State) -> Result<ExecutionPlan> {
+    let mut plan = ExecutionPlan::default();
+    let ffmpeg = |kind, args| PlannedCommand {
+        kind,
+        program: ffmpeg_program(conf),
+        args,
+        low_priority: conf.low_priority,
+        nice_level: conf.nice_level,
+        working_dir: conf.working_dir.clone(),
+    };
 
-    args.push(String::from("-vn"));
+    match conf.operation {
+        Operation::FixRotation { set } => {
+            plan.commands.push(ffmpeg(
+                StepKind::Process,
+                make_ffmpeg_fix_rotation_args(conf, set),
+            ));
+            return Ok(plan);
+        }
+        Operation::Analyze => {
+            if let Some((width, height)) = conf.spectrogram {
+                plan.commands.push(ffmpeg(
+                    StepKind::Process,
+                    make_ffmpeg_spectrogram_args(conf, width, height),
+                ));
+            }
+            if let Some((columns, rows)) = conf.contact_sheet {
+                plan.commands.push(ffmpeg(
+                    StepKind::Process,
+                    make_ffmpeg_contact_sheet_args(conf, state, columns, rows)?,
+                ));
+            }
+            return Ok(plan);
+        }
+        Operation::SmartCut => {
+            let source = state.smart_cut.clone().ok_or_else(|| {
+                String::from("Error: input keyframes are unknown, the smart cut cannot be planned.")
+            })?;
+            let pieces = smartcut::plan_pieces(
+                conf.from_time.num_milliseconds() as f64 / 1000.0,
+                conf.to_time.num_milliseconds() as f64 / 1000.0,
+                &source.keyframes,
+            );
 
-    let duration = conf.to_time - conf.from_time;
-    args.push(String::from("-ss"));
-    args.push(duration_to_string(conf.from_time));
-    args.push(String::from("-t"));
-    args.push(duration_to_string(duration));
+            let mut piece_files = Vec::with_capacity(pieces.len());
+            for (index, piece) in pieces.iter().enumerate() {
+                // matroska takes any codec and keeps the timestamps of copied frames
+                let mut piece_file = state.tmp_dir.clone();
+                piece_file.push(format!("smartcut_{}.mkv", index));
+                let piece_file = piece_file.to_string_lossy().into_owned();
 
-    args.push(String::from("-filter:a"));
-    args.push(String::from("volumedetect"));
+                plan.commands.push(ffmpeg(
+                    StepKind::SmartCutPiece,
+                    make_ffmpeg_smart_cut_piece_args(conf, piece, &source.encoder, &piece_file),
+                ));
+                plan.temporary_files.push(piece_file.clone());
+                piece_files.push(piece_file);
+            }
 
-    // no output file
-    args.push(String::from("-f"));
-    args.push(String::from("null"));
-    args.push(String::from("-"));
+            let mut list_file = state.tmp_dir.clone();
+            list_file.push(SMART_CUT_LIST_FILE);
+            let list_file = list_file.to_string_lossy().into_owned();
+            plan.commands.push(ffmpeg(
+                StepKind::Process,
+                make_ffmpeg_smart_cut_join_args(conf, &list_file),
+            ));
+            plan.temporary_files.push(list_file.clone());
 
-    args
-}
+            state.smart_cut_list = Some((list_file, smartcut::concat_list(&piece_files)));
+            return Ok(plan);
+        }
+        // the processed audio is drawn by the process step
+        Operation::Process | Operation::Visualize { .. } => {}
+    }
 
-fn make_ffmpeg_processing_args(conf: &Config, state: &State) -> Vec<String> {
-    let mut args = Vec::with_capacity(15);
+    // a preview plays the selection as is
+    if conf.trim_to_first_sound && !conf.preview {
+        plan.commands.push(ffmpeg(
+            StepKind::FirstSoundDetect,
+            make_ffmpeg_detect_silence_args(
+                conf,
+                state,
+                silence::FIRST_SOUND_NOISE_DB,
+                silence::FIRST_SOUND_MIN_SILENCE,
+            ),
+        ));
+    }
 
-    if !conf.preview {
-        if conf.allow_overidde {
-            args.push(String::from("-y"));
-        } else {
-            args.push(String::from("-nostdin"));
+    if conf.noise_profile_file.is_some() && conf.noise_reduction_amount.is_some() {
+        let mut sox_output_file = state.tmp_dir.clone();
+        match Path::new(&conf.input_file).file_name() {
+            Some(filename) => sox_output_file.push(filename),
+            None => {
+                return Err(String::from("Error: no input file provided."));
+            }
         }
+        let sox_output_file = sox_output_file.to_string_lossy().into_owned();
+
+        plan.commands.push(PlannedCommand {
+            kind: StepKind::NoiseProfile,
+            program: String::from(SOX_COMMAND),
+            args: make_sox_generate_noiseprof_args(conf)?,
+            low_priority: conf.low_priority,
+            nice_level: conf.nice_level,
+            working_dir: conf.working_dir.clone(),
+        });
+        plan.commands.push(PlannedCommand {
+            kind: StepKind::NoiseReduction,
+            program: String::from(SOX_COMMAND),
+            args: make_sox_clean_noise_args(conf, &sox_output_file)?,
+            low_priority: conf.low_priority,
+            nice_level: conf.nice_level,
+            working_dir: conf.working_dir.clone(),
+        });
+        plan.temporary_files.push(sox_output_file.clone());
+
+        state.sox_output_file = Some(sox_output_file);
+        state.already_trimed = true;
     }
 
-    args.push(String::from("-i"));
-    let output_file = if let Some(ref sox_output_file) = state.sox_output_file {
-        sox_output_file.clone() // use sox output file if applicable
-    } else {
-        conf.input_file.clone()
-    };
-    args.push(output_file);
+    if conf.stabilize.is_some() {
+        // first pass: analyze the shakes and store the transforms for the second pass
+        let mut transforms_file = state.tmp_dir.clone();
+        transforms_file.push(VIDSTAB_TRANSFORMS_FILE);
+        let transforms_file = transforms_file.to_string_lossy().into_owned();
 
-    if conf.ignore_video {
-        args.push(String::from("-vn"));
+        plan.commands.push(ffmpeg(
+            StepKind::StabilizationDetect,
+            make_ffmpeg_vidstab_detect_args(conf, state, &transforms_file),
+        ));
+        plan.temporary_files.push(transforms_file.clone());
+
+        state.vidstab_transforms_file = Some(transforms_file);
     }
 
-    if conf.ignore_audio {
-        args.push(String::from("-an"));
+    if let (Some(ref chapters), false) = (&conf.write_chapters, conf.preview) {
+        if !chapters.is_empty() && containers::supports_chapters(&output_extension(conf)) {
+            let mut chapters_file = state.tmp_dir.clone();
+            chapters_file.push(format!(
+                "{}{}_{}.txt",
+                CHAPTERS_FILE_PREFIX,
+                std::process::id(),
+                CHAPTERS_FILES.fetch_add(1, Ordering::Relaxed)
+            ));
+            let chapters_file = chapters_file.to_string_lossy().into_owned();
+            plan.temporary_files.push(chapters_file.clone());
+            state.chapters_file = Some(chapters_file);
+        }
     }
 
-    if !state.already_trimed {
-        // input file may already be trimed by sox
-        let duration = conf.to_time - conf.from_time;
-        args.push(String::from("-ss"));
-        args.push(duration_to_string(conf.from_time));
-        args.push(String::from("-t"));
-        args.push(duration_to_string(duration));
+    if conf.peak_normalization {
+        plan.commands.push(ffmpeg(
+            StepKind::PeakMeasure,
+            make_ffmpeg_detect_max_volume_args(conf),
+        ));
     }
 
-    // == filters
-    args.push(String::from("-af")); // alias of -filter:a with ffmpeg but not with ffplay.
+    // a preview is normalized in a single pass
+    if let (Some(target), true, false) =
+        (conf.loudness_target, conf.loudnorm_two_pass, conf.preview)
+    {
+        plan.commands.push(ffmpeg(
+            StepKind::LoudnessMeasure,
+            make_ffmpeg_loudness_measure_args(conf, state, target),
+        ));
+    }
 
-    let mut filters = Vec::with_capacity(3);
-    if let Some(high) = conf.high_pass_filter {
-        filters.push(format!("highpass=f={}", high));
+    // a preview plays the selection as is
+    if let (Some(ref speedup), false) = (&conf.silence_speedup, conf.preview) {
+        plan.commands.push(ffmpeg(
+            StepKind::SilenceDetect,
+            make_ffmpeg_detect_silence_args(conf, state, speedup.noise_db, speedup.min_silence),
+        ));
     }
-    if let Some(low) = conf.low_pass_filter {
-        filters.push(format!("lowpass=f={}", low));
+
+    plan.commands.push(PlannedCommand {
+        kind: StepKind::Process,
+        program: if conf.preview {
+            String::from(FFPLAY_COMMAND)
+        } else {
+            ffmpeg_program(conf)
+        },
+        args: make_ffmpeg_processing_args(conf, state),
+        low_priority: conf.low_priority,
+        nice_level: conf.nice_level,
+        working_dir: conf.working_dir.clone(),
+    });
+
+    if let (Some(stream), false) = (conf.extract_subtitles, conf.preview) {
+        plan.commands.push(ffmpeg(
+            StepKind::SubtitleExtract,
+            make_ffmpeg_subtitle_args(conf, stream),
+        ));
     }
 
-    let volume_filter = if let Some(max_volume_db) = state.max_volume_db {
-        // peak normalization
-        conf.volume_change - max_volume_db
+    if conf.verify_lossless && !conf.preview {
+        plan.commands.push(ffmpeg(
+            StepKind::LosslessCheck,
+            make_ffmpeg_audio_md5_args(conf, state, true),
+        ));
+        plan.commands.push(ffmpeg(
+            StepKind::LosslessCheck,
+            make_ffmpeg_audio_md5_args(conf, state, false),
+        ));
+    }
+
+    Ok(plan)
+}
+
+fn write_chapters_file(conf: &Config, state: &State, chapters_file: &str) -> Result<()> {
+    let (from_time, to_time) = if conf.process_whole_file {
+        (chrono::Duration::zero(), None)
     } else {
-        conf.volume_change
+        (conf.from_time, Some(conf.to_time))
     };
-    filters.push(format!("volume={}dB", volume_filter));
+    let rebased = chapters::rebase(
+        conf.write_chapters.as_deref().unwrap_or(&[]),
+        from_time,
+        to_time,
+        state.silence_segments.as_deref().unwrap_or(&[]),
+    );
+    write(chapters_file, chapters::to_ffmetadata(&rebased))
+        .map_err(|e| format!("Could not write the chapters file.\nError: {}", e))
+}
 
-    args.push(filters.join(","));
-    // == end filters
+// files written by the process step, subtitles and the like left out
+fn output_files(conf: &Config) -> Vec<String> {
+    if conf.demux_streams {
+        let (video_output_file, audio_output_file) = demux_output_files(conf);
+        let mut files = Vec::with_capacity(2);
+        if !conf.ignore_video {
+            files.push(video_output_file);
+        }
+        if !conf.ignore_audio {
+            files.push(audio_output_file);
+        }
+        files
+    } else {
+        let mut files = vec![conf.output_file.clone()];
+        files.extend(
+            conf.additional_outputs
+                .iter()
+                .map(|output| output.path.clone()),
+        );
+        files
+    }
+}
+
+// outputs written by a run, those of a preview or an analysis excepted
+fn run_output_files(conf: &Config) -> Vec<PathBuf> {
+    if conf.operation == Operation::Analyze || conf.preview || conf.writes_to_stdout() {
+        return Vec::new();
+    }
+    output_files(conf)
+        .iter()
+        .map(|output_file| conf.resolve_path(output_file))
+        .collect()
+}
+
+// modification time and size of a file, which change once ffmpeg opens it
+type FileStamp = (Option<SystemTime>, u64);
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.modified().ok(), metadata.len()))
+}
+
+// outputs there before the run: the user's files, which a failed run must leave
+// alone, e.g. those ffmpeg refused to overwrite, unless it overwrote them
+fn existing_output_files(conf: &Config) -> HashMap<PathBuf, FileStamp> {
+    run_output_files(conf)
+        .into_iter()
+        .filter_map(|file| file_stamp(&file).map(|stamp| (file, stamp)))
+        .collect()
+}
+
+// truncated outputs are of no use and temporary files only take more room, only
+// the files the run wrote are removed, see `existing_output_files`
+fn remove_partial_files(
+    conf: &Config,
+    plan: &ExecutionPlan,
+    existing: &HashMap<PathBuf, FileStamp>,
+) {
+    remove_temporary_files(plan);
+    remove_created_outputs(conf, existing);
+}
+
+fn remove_temporary_files(plan: &ExecutionPlan) {
+    for temporary_file in &plan.temporary_files {
+        // some were never written
+        let _ = remove_file(temporary_file);
+    }
+}
+
+fn remove_created_outputs(conf: &Config, existing: &HashMap<PathBuf, FileStamp>) {
+    for output_file in run_output_files(conf) {
+        // an output overwritten with `allow_overidde` is not the user's anymore
+        if existing.get(&output_file) != file_stamp(&output_file).as_ref() {
+            // some were never written
+            let _ = remove_file(output_file);
+        }
+    }
+}
+
+// trimming silently snaps to keyframes or stops early on truncated inputs: the
+// outputs are probed and compared to the `expected` seconds
+fn check_output_durations(conf: &Config, expected: f64) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for output_file in output_files(conf) {
+        match probe::MediaInfo::probe(&conf.resolve_path(&output_file).to_string_lossy()) {
+            Ok(info) => {
+                if let Some(measured) = info.duration {
+                    warnings.extend(output_duration_warning(&output_file, expected, measured));
+                }
+            }
+            Err(e) => warnings.push(format!(
+                "Could not check the duration of {} against the selection.\n{}",
+                output_file, e
+            )),
+        }
+    }
+    warnings
+}
+
+fn output_duration_warning(output_file: &str, expected: f64, measured: f64) -> Option<String> {
+    if (measured - expected).abs() <= OUTPUT_DURATION_TOLERANCE_SECS {
+        return None;
+    }
+    Some(format!(
+        "{} lasts {:.3}s instead of {:.3}s: the cut may have snapped to keyframes or the input may be truncated.",
+        output_file, measured, expected
+    ))
+}
+
+// tracks are counted among audio streams only
+fn check_audio_track_count(conf: &Config, info: &probe::MediaInfo) -> Result<()> {
+    let mut selected: Vec<usize> = conf
+        .audio_tracks
+        .iter()
+        .map(|track| track.stream_index as usize)
+        .collect();
+    if let Some(ref tracks) = conf.downmix_tracks {
+        selected.extend(tracks.iter().map(|&(stream_index, _)| stream_index));
+    }
+
+    let count = info
+        .streams
+        .iter()
+        .filter(|stream| stream.kind == "audio")
+        .count();
+    match selected
+        .into_iter()
+        .find(|&stream_index| stream_index >= count)
+    {
+        Some(stream_index) => Err(format!(
+            "Error: audio track {} does not exist, the input file has {} audio tracks (from 0).",
+            stream_index, count
+        )),
+        None => Ok(()),
+    }
+}
+
+// the requested subtitle stream must exist and be convertible to text
+fn check_subtitle_stream(conf: &Config, info: &probe::MediaInfo) -> Result<()> {
+    let stream = match conf.extract_subtitles {
+        Some(stream) if conf.operation == Operation::Process && !conf.preview => stream,
+        _ => return Ok(()),
+    };
+
+    let subtitles: Vec<&probe::StreamInfo> = info
+        .streams
+        .iter()
+        .filter(|stream| stream.kind == "subtitle")
+        .collect();
+    match subtitles.get(stream as usize) {
+        None if subtitles.is_empty() => Err(String::from(
+            "Error: subtitles are extracted but the input file has none.",
+        )),
+        None => Err(format!(
+            "Error: subtitle stream {} does not exist, the input file has {} subtitle streams (from 0).",
+            stream,
+            subtitles.len()
+        )),
+        Some(subtitle) => match subtitle.codec.as_deref() {
+            Some(codec) if BITMAP_SUBTITLE_CODECS.contains(&codec) => Err(format!(
+                "Error: subtitle stream {} is made of pictures ({}), it cannot be saved as text.",
+                stream, codec
+            )),
+            _ => Ok(()),
+        },
+    }
+}
+
+// channel operations and vocal removal work on a left and a right channel
+fn check_audio_channels(conf: &Config, info: &probe::MediaInfo) -> Result<()> {
+    let feature = match (conf.vocal_removal, conf.channel_ops) {
+        _ if conf.ignore_audio => return Ok(()),
+        (Some(_), _) => String::from("vocal removal"),
+        (None, Some(op)) => op.to_string(),
+        (None, None) => return Ok(()),
+    };
+
+    let channels = info
+        .streams
+        .iter()
+        .find(|stream| stream.kind == "audio")
+        .and_then(|stream| stream.channels);
+    match channels {
+        Some(1) => Err(format!(
+            "Error: {} needs a stereo input but the input audio is mono.",
+            feature
+        )),
+        _ => Ok(()),
+    }
+}
+
+// with several video streams, the one kept must be chosen rather than guessed
+fn check_video_streams(conf: &Config, info: &probe::MediaInfo) -> Result<()> {
+    // a stream copy keeps them all
+    if conf.ignore_video || conf.stream_copy {
+        return Ok(());
+    }
+
+    let video_streams = info
+        .streams
+        .iter()
+        .filter(|stream| stream.kind == "video")
+        .count();
+    match conf.video_stream_index {
+        Some(stream) if stream as usize >= video_streams => Err(format!(
+            "Error: video stream {} does not exist, the input file has {} video streams (from 0).",
+            stream, video_streams
+        )),
+        None if video_streams > 1 => Err(format!(
+            "Error: the input file has {} video streams, choose the one to keep.",
+            video_streams
+        )),
+        _ => Ok(()),
+    }
+}
+
+// the audio stream ffmpeg keeps by default: the one with the most channels,
+// the first of them. Counted among audio streams only.
+fn default_audio_stream(info: &probe::MediaInfo) -> Option<usize> {
+    info.streams
+        .iter()
+        .filter(|stream| stream.kind == "audio")
+        .enumerate()
+        .fold(None, |best: Option<(usize, u32)>, (index, stream)| {
+            let channels = stream.channels.unwrap_or(0);
+            match best {
+                Some((_, most)) if most >= channels => best,
+                _ => Some((index, channels)),
+            }
+        })
+        .map(|(index, _)| index)
+}
+
+/// What the processing of `conf` will leave out or take long for, known from
+/// the settings alone: the window asks for a confirmation before starting.
+/// The warnings are also part of the outcome of `run`.
+pub fn setting_warnings(conf: &Config) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if conf.operation != Operation::Process || conf.preview {
+        return warnings;
+    }
+
+    if conf.max_output_size.is_some() {
+        warnings.push(String::from(
+            "Output size is limited: encoding stops once the limit is reached, the end of the selection may be missing.",
+        ));
+    }
+
+    if let Some(VideoDenoise::Nlmeans { .. }) = conf.video_denoise {
+        if !conf.ignore_video {
+            warnings.push(String::from(
+                "Non-local means denoising is very slow: expect the encoding to take many times the clip duration.",
+            ));
+        }
+    }
+
+    if conf.encoder_preset.is_some() {
+        if let Some(ref codec) = conf.video_codec {
+            if !codec.contains("264") && !codec.contains("265") && !codec.contains("hevc") {
+                warnings.push(format!(
+                    "Encoder preset only applies to x264 and x265: {} ignores it.",
+                    codec
+                ));
+            }
+        }
+    }
+
+    if let Some(ref chapters) = conf.write_chapters {
+        let extension = output_extension(conf);
+        if !chapters.is_empty() && !containers::supports_chapters(&extension) {
+            warnings.push(format!(
+                "Chapters are left out: {} files cannot hold them.",
+                if extension.is_empty() {
+                    "extensionless"
+                } else {
+                    &extension
+                }
+            ));
+        }
+    }
+
+    if conf.fragmented_mp4 && !conf.demux_streams && !is_mp4_output(conf, &conf.output_file) {
+        warnings.push(String::from(
+            "Fragmented MP4 only applies to MP4 and QuickTime outputs: the output is written as usual.",
+        ));
+    }
+    warnings
+}
+
+fn push_process_warnings(conf: &Config, state: &State, outcome: &mut RunOutcome) {
+    outcome.warnings.extend(setting_warnings(conf));
+
+    if conf.target_size.is_some() && progress_total(conf, state.input_duration).is_none() {
+        outcome.warnings.push(String::from(
+            "The input duration is unknown: the target size could not be aimed for, the encoder picked the video bitrate.",
+        ));
+    }
+
+    if conf.sample_accurate && !conf.preview && !conf.process_whole_file {
+        if !is_lossless_audio_output(conf) || conf.demux_streams {
+            outcome.warnings.push(String::from(
+                "Sample-accurate trimming only applies to a single lossless audio output (FLAC or WAV): timestamps were used instead.",
+            ));
+        } else if state.sample_rate.is_none() {
+            outcome.warnings.push(String::from(
+                "Input sample rate is unknown: timestamps were used for trimming instead of samples.",
+            ));
+        }
+    }
+}
+
+// encoders ffmpeg is asked for, stream copies aside
+fn requested_encoders(conf: &Config) -> Vec<String> {
+    let mut encoders = Vec::new();
+    if let (Some(codec), false) = (&conf.video_codec, conf.ignore_video) {
+        encoders.push(codec.clone());
+    }
+    if let (Some(codec), false) = (conf.audio_encoder(), conf.ignore_audio) {
+        encoders.push(String::from(codec));
+    }
+    encoders.retain(|codec| codec != "copy");
+    encoders
+}
+
+fn substitute_missing_encoders(
+    conf: &mut Config,
+    capabilities: &Capabilities,
+    outcome: &mut RunOutcome,
+) {
+    let extension = output_extension(conf);
+    for codec in requested_encoders(conf) {
+        if capabilities.has_encoder(&codec) {
+            continue;
+        }
+        if let Some(fallback) = capabilities.available_fallback(&codec, &extension) {
+            substitute_encoder(conf, &codec, fallback, outcome);
+        }
+    }
+}
+
+// returns false when `conf` does not use `requested`
+fn substitute_encoder(
+    conf: &mut Config,
+    requested: &str,
+    used: &str,
+    outcome: &mut RunOutcome,
+) -> bool {
+    let mut substituted = false;
+    for codec in &mut [&mut conf.video_codec, &mut conf.audio_codec] {
+        if codec.as_deref() == Some(requested) {
+            **codec = Some(String::from(used));
+            substituted = true;
+        }
+    }
+    if let Some(aac) = conf.aac_options {
+        if aac.encoder.codec() == requested && used == AacEncoder::Native.codec() {
+            // the native encoder only does AAC-LC at a constant bitrate
+            conf.aac_options = Some(AacOptions {
+                encoder: AacEncoder::Native,
+                profile: AacProfile::Lc,
+                vbr_mode: None,
+                ..aac
+            });
+            substituted = true;
+        }
+    }
+
+    if substituted {
+        outcome.warnings.push(format!(
+            "Installed ffmpeg lacks the {} encoder: {} was used instead.",
+            requested, used
+        ));
+        outcome.substitutions.push(CodecSubstitution {
+            requested: String::from(requested),
+            used: String::from(used),
+        });
+    }
+    substituted
+}
+
+// name of the encoder ffmpeg failed to find, from its error output
+fn unknown_encoder(stderr: &str) -> Option<&str> {
+    UNKNOWN_ENCODER_RE
+        .captures(stderr)
+        .and_then(|caps| caps.name("name"))
+        .map(|name| name.as_str())
+}
+
+// missing encoders are usually replaced before planning, this catches those
+// the capability listing did not tell about: the encoding is run once more
+// with the fallback encoder
+fn run_with_encoder_fallback<F>(
+    conf: &mut Config,
+    outcome: &mut RunOutcome,
+    mut run_encoding: F,
+) -> Result<()>
+where
+    F: FnMut(&Config) -> Result<()>,
+{
+    let error = match run_encoding(conf) {
+        Err(e) if !conf.strict_codecs && conf.operation == Operation::Process => e,
+        result => return result,
+    };
+
+    let extension = output_extension(conf);
+    let fallback = unknown_encoder(&error).and_then(|requested| {
+        capabilities::container_fallback(requested, &extension)
+            .map(|used| (String::from(requested), used))
+    });
+    match fallback {
+        Some((requested, used)) if substitute_encoder(conf, &requested, used, outcome) => {
+            run_encoding(conf)
+        }
+        _ => Err(error),
+    }
+}
+
+// the line of an ffmpeg error output telling the input is damaged
+fn damaged_input(stderr: &str) -> Option<&str> {
+    let found = DAMAGED_INPUT_RE.find(stderr)?;
+    let start = stderr[..found.start()].rfind('\n').map_or(0, |i| i + 1);
+    let end = stderr[found.end()..]
+        .find('\n')
+        .map_or(stderr.len(), |i| found.end() + i);
+    Some(stderr[start..end].trim())
+}
+
+// a run failing on a damaged input is run once more skipping the errors,
+// unless `strict` asks to fail on those, see `Config::tolerant_input`; not
+// when writing to stdout, a retry would pipe a second output after the first.
+// Without `allow_overidde` ffmpeg would refuse the output left by the first
+// attempt, it is removed before the retry.
+fn run_with_tolerant_retry<T, F>(
+    conf: &mut Config,
+    outcome: &mut RunOutcome,
+    mut run_encoding: F,
+) -> Result<T>
+where
+    F: FnMut(&mut Config, &mut RunOutcome) -> Result<T>,
+{
+    let existing_files = existing_output_files(conf);
+    let error = match run_encoding(conf, outcome) {
+        Err(e)
+            if !conf.tolerant_input
+                && !conf.strict
+                && !conf.writes_to_stdout()
+                && conf.operation == Operation::Process =>
+        {
+            e
+        }
+        result => return result,
+    };
+
+    match damaged_input(&error) {
+        Some(reason) => {
+            outcome.warnings.push(format!(
+                "The input looks damaged ({}): it was read again skipping the errors, parts of it may be missing.",
+                reason
+            ));
+            outcome.tolerant_retry = Some(String::from(reason));
+            remove_created_outputs(conf, &existing_files);
+            conf.tolerant_input = true;
+            run_encoding(conf, outcome)
+        }
+        None => Err(error),
+    }
+}
+
+// a measurement step, made again by `make_args` with the tolerant input
+// options when retried, see `run_with_tolerant_retry`
+fn run_measurement<A>(
+    conf: &mut Config,
+    outcome: &mut RunOutcome,
+    command: &PlannedCommand,
+    make_args: A,
+) -> Result<Output>
+where
+    A: Fn(&Config) -> Vec<String>,
+{
+    let planned_tolerant = conf.tolerant_input;
+    run_with_tolerant_retry(conf, outcome, |conf, _| {
+        if conf.tolerant_input == planned_tolerant {
+            run_planned_command(command, conf.max_retries)
+        } else {
+            let command = PlannedCommand {
+                args: make_args(conf),
+                ..command.clone()
+            };
+            run_planned_command(&command, conf.max_retries)
+        }
+    })
+}
+
+fn run_planned_command(command: &PlannedCommand, max_retries: u32) -> Result<Output> {
+    let output = run_command_with_retries(command, max_retries)?;
+    output_map_error(&output, &command.program, &command.args)?;
+    Ok(output)
+}
+
+// `producer` stdout is fed to `consumer` stdin, no retry
+// returns the time taken by `producer`, `consumer` runs alongside and ends later
+fn run_piped_commands(producer: &PlannedCommand, consumer: &PlannedCommand) -> Result<Duration> {
+    let started = Instant::now();
+    let mut child = command_map_error(
+        producer
+            .to_process(&producer.args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn(),
+        &producer.program,
+        &producer.args,
+    )?;
+    // drained on its own thread: the producer blocks once the pipe is full
+    let mut producer_stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut contents = Vec::new();
+        let _ = producer_stderr.read_to_end(&mut contents);
+        contents
+    });
+
+    let consumer_child = command_map_error(
+        consumer
+            .to_process(&consumer.args)
+            .stdin(child.stdout.take().unwrap())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn(),
+        &consumer.program,
+        &consumer.args,
+    )?;
+    let status = command_map_error(child.wait(), &producer.program, &producer.args)?;
+    let producer_time = started.elapsed();
+    let producer_output = Output {
+        status,
+        stdout: Vec::new(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    };
+
+    let output = command_map_error(
+        consumer_child.wait_with_output(),
+        &consumer.program,
+        &consumer.args,
+    )?;
+    // the consumer only fails on a truncated input once the producer did
+    output_map_error(&producer_output, &producer.program, &producer.args)?;
+    output_map_error(&output, &consumer.program, &consumer.args)?;
+    Ok(producer_time)
+}
+
+// ffmpeg writes its `-progress` blocks to stdout, read as they come while
+// stderr is kept for the error message. Cancelling kills ffmpeg at the next block.
+// An output written to stdout goes straight to ours, the blocks then come
+// along with the messages on stderr, see `ProgressStdio`.
+fn run_command_with_progress<F>(
+    command: &PlannedCommand,
+    tracker: &mut ProgressTracker,
+    cancel: &CancelFlag,
+    on_progress: &mut F,
+) -> Result<Output>
+where
+    F: FnMut(&ProgressTracker),
+{
+    let stdio = ProgressStdio::of(&command.args);
+    let args = make_ffmpeg_progress_args(&command.args, stdio.progress_pipe);
+    let stdout = if stdio.inherit_stdout {
+        Stdio::inherit()
+    } else {
+        Stdio::piped()
+    };
+    let mut child = command_map_error(
+        command
+            .to_process(&args)
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .spawn(),
+        &command.program,
+        &args,
+    )?;
+
+    let (progress, stderr_reader): (Box<dyn Read>, _) = if stdio.inherit_stdout {
+        (Box::new(child.stderr.take().unwrap()), None)
+    } else {
+        // drained on its own thread: ffmpeg blocks once a pipe is full
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr_reader = thread::spawn(move || {
+            let mut contents = Vec::new();
+            let _ = stderr.read_to_end(&mut contents);
+            contents
+        });
+        (Box::new(child.stdout.take().unwrap()), Some(stderr_reader))
+    };
+
+    // messages read along with the blocks, for the error message
+    let mut messages = Vec::new();
+    let mut progress = BufReader::new(progress);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        // not `lines()`: a message that is not UTF-8 must not stop the draining
+        match progress.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if stderr_reader.is_none() {
+                    messages.extend_from_slice(&buf);
+                }
+                let line = String::from_utf8_lossy(&buf);
+                if tracker.feed_line(&line) {
+                    if cancel.is_cancelled() {
+                        let _ = child.kill();
+                        break;
+                    }
+                    on_progress(tracker);
+                }
+            }
+        }
+    }
+
+    let status = command_map_error(child.wait(), &command.program, &args)?;
+    if cancel.is_cancelled() {
+        return Err(String::from(CANCELLED_ERROR));
+    }
+    Ok(Output {
+        status,
+        stdout: Vec::new(),
+        stderr: match stderr_reader {
+            Some(stderr_reader) => stderr_reader.join().unwrap_or_default(),
+            None => messages,
+        },
+    })
+}
+
+// how the process step is connected, see `run_command_with_progress`
+#[derive(PartialEq, Debug)]
+struct ProgressStdio {
+    progress_pipe: &'static str,
+    inherit_stdout: bool,
+}
+
+impl ProgressStdio {
+    // the output file is always the last argument
+    fn of(args: &[String]) -> Self {
+        if args.last().map(String::as_str) == Some(STDOUT_PIPE) {
+            Self {
+                progress_pipe: "pipe:2",
+                inherit_stdout: true,
+            }
+        } else {
+            Self {
+                progress_pipe: "pipe:1",
+                inherit_stdout: false,
+            }
+        }
+    }
+}
+
+fn make_ffmpeg_progress_args(args: &[String], progress_pipe: &str) -> Vec<String> {
+    let mut progress_args = vec![
+        String::from("-progress"),
+        String::from(progress_pipe),
+        String::from("-nostats"),
+    ];
+    progress_args.extend_from_slice(args);
+    progress_args
+}
+
+// seconds of output expected from the process step, for its progress
+fn progress_total(conf: &Config, input_duration: Option<chrono::Duration>) -> Option<f64> {
+    let duration = match conf.operation {
+        Operation::Process | Operation::Visualize { .. } if !conf.process_whole_file => {
+            conf.to_time - conf.from_time
+        }
+        Operation::SmartCut => conf.to_time - conf.from_time,
+        Operation::Process | Operation::Visualize { .. } | Operation::FixRotation { .. } => {
+            input_duration?
+        }
+        // a single picture written at the very end
+        Operation::Analyze => return None,
+    };
+    Some(duration.num_milliseconds() as f64 / 1000.0)
+}
+
+/// Render a `grid` (columns, rows) contact sheet of the selection of `conf`
+/// to `output_file`, whose extension is replaced by `jpg`.
+pub fn contact_sheet(
+    conf: &Config,
+    grid: (u32, u32),
+    tile_width: u32,
+    output_file: &str,
+) -> Result<RunOutcome> {
+    let mut conf = conf.clone();
+    conf.preview = false;
+    conf.operation = Operation::Analyze;
+    conf.spectrogram = None;
+    conf.contact_sheet = Some(grid);
+    conf.contact_sheet_tile_width = Some(tile_width);
+    conf.output_file = String::from(output_file);
+    run(&conf)
+}
+
+/// Play `len` of `input` from `at`, without display, to hear what is around
+/// a cut point. A snippet still playing is stopped first.
+pub fn play_snippet(input: &str, at: chrono::Duration, len: chrono::Duration) -> Result<()> {
+    SNIPPET_PLAYER
+        .lock()
+        .map_err(|_| String::from("Snippet player is unavailable."))?
+        .play(FFPLAY_COMMAND, &make_ffplay_snippet_args(input, at, len))
+}
+
+// at most one snippet is heard at a time, otherwise dragging piles them up
+#[derive(Default)]
+struct SnippetPlayer {
+    child: Option<Child>,
+}
+
+impl SnippetPlayer {
+    fn play(&mut self, program: &str, args: &[String]) -> Result<()> {
+        self.stop();
+        let child = command_map_error(
+            Command::new(program)
+                .args(args)
+                .logged()
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn(),
+            program,
+            args,
+        )?;
+        self.child = Some(child);
+        Ok(())
+    }
+
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            // fails when the snippet already ended, nothing to do then
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for SnippetPlayer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn required_filters(conf: &Config) -> Vec<&'static str> {
+    let mut filters = Vec::new();
+    // the input video is left out, its filters with it
+    if let Operation::Visualize { style, .. } = conf.operation {
+        filters.push(style.filter_name());
+        return filters;
+    }
+    if conf.ignore_video {
+        return filters;
+    }
+
+    match conf.video_denoise {
+        Some(VideoDenoise::Hqdn3d { .. }) => filters.push("hqdn3d"),
+        Some(VideoDenoise::Nlmeans { .. }) => filters.push("nlmeans"),
+        None => {}
+    }
+    if conf.stabilize.is_some() {
+        filters.push("vidstabdetect");
+        filters.push("vidstabtransform");
+    }
+    if conf.lut.is_some() {
+        filters.push("lut3d");
+    }
+    if conf.burn_timecode.is_some() {
+        filters.push("drawtext");
+    }
+
+    filters
+}
+
+fn check_required_filters(conf: &Config) -> Result<()> {
+    let required = required_filters(conf);
+    if required.is_empty() {
+        return Ok(());
+    }
+
+    let capabilities = Capabilities::detect(conf)?;
+    let missing: Vec<&str> = required
+        .into_iter()
+        .filter(|filter| !capabilities.has_filter(filter))
+        .collect();
+
+    if missing.iter().any(|filter| filter.starts_with("vidstab")) {
+        Err(String::from(
+            "Error: video stabilization requires ffmpeg to be built with libvidstab (--enable-libvidstab).",
+        ))
+    } else if !missing.is_empty() {
+        Err(format!(
+            "Error: installed ffmpeg lacks the following filters: {}",
+            missing.join(", ")
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn prepare_output_dir(conf: &Config) -> Result<()> {
+    let output_file = conf.resolve_path(&conf.output_file);
+    let output_dir = match output_file.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+        // a bare file name goes to the working directory
+        _ => PathBuf::from("."),
+    };
+
+    if !output_dir.is_dir() {
+        if !conf.create_output_dirs {
+            return Err(format!(
+                "Output directory does not exist: {}",
+                output_dir.display()
+            ));
+        }
+        create_dir_all(&output_dir).map_err(|e| {
+            format!(
+                "Could not create output directory {}.\nError: {}",
+                output_dir.display(),
+                e
+            )
+        })?;
+    }
+
+    check_output_dir_writable(&output_dir)
+}
+
+// ffmpeg only fails to open its output once the steps before it ran
+fn check_output_dir_writable(output_dir: &Path) -> Result<()> {
+    let test_file = output_dir.join(format!(
+        "{}{}_{}",
+        WRITE_TEST_FILE_PREFIX,
+        std::process::id(),
+        WRITE_TEST_FILES.fetch_add(1, Ordering::Relaxed)
+    ));
+    match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&test_file)
+    {
+        Ok(_) => remove_file(&test_file).map_err(|e| {
+            format!(
+                "Could not delete {} from the output directory.\nError: {}",
+                test_file.display(),
+                e
+            )
+        }),
+        Err(ref e) if e.kind() == ErrorKind::PermissionDenied => {
+            Err(String::from(OUTPUT_DIR_PERMISSION_ERROR))
+        }
+        Err(e) => Err(format!(
+            "Cannot write to output directory {}.\nError: {}",
+            output_dir.display(),
+            e
+        )),
+    }
+}
+
+pub(crate) fn tmp_dir_path(conf: &Config) -> PathBuf {
+    let mut tmp_dir = conf.temp_dir.clone().unwrap_or_else(env::temp_dir);
+    tmp_dir.push(TMP_DIRECTORY);
+    tmp_dir
+}
+
+pub(crate) fn ffmpeg_program(conf: &Config) -> String {
+    conf.ffmpeg_path.as_ref().map_or_else(
+        || String::from(FFMPEG_COMMAND),
+        |path| path.to_string_lossy().into_owned(),
+    )
+}
+
+fn create_tmp_dir(tmp_dir: &Path) -> Result<()> {
+    create_dir_all(tmp_dir)
+        .map_err(|e| format!("Could not create temporary directory.\nError: {}", e))
+}
+
+// escape a value to be used as a filter option inside a filtergraph.
+// Two levels are required: one for the option parser (`:` separates options)
+// and one for the filtergraph parser (`,` `;` `[` `]` separate filters and pads).
+// See https://ffmpeg.org/ffmpeg-filters.html#Notes-on-filtergraph-escaping
+fn escape_filter_option(value: &str) -> String {
+    fn escape(value: &str, special: &[char]) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if c == '\\' || c == '\'' || special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
+    escape(&escape(value, &[':']), &[',', ';', '[', ']'])
+}
+
+fn command_map_error<T, E>(
+    result: std::result::Result<T, E>,
+    command_name: &str,
+    args: &[String],
+) -> Result<T>
+where
+    E: std::fmt::Display,
+{
+    result.map_err(|err| {
+        format!(
+            "Failed to start: {}\nCommand was: {} {}",
+            err,
+            command_name,
+            build_args_string(&args[..])
+        )
+    })
+}
+
+fn output_map_error(output: &Output, command_name: &str, args: &[String]) -> Result<()> {
+    if output.status.success() {
+        info!("{} finished ({})", command_name, output.status);
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        error!(
+            "{} failed ({}):\n{}",
+            command_name,
+            output.status,
+            stderr_excerpt(&stderr)
+        );
+        match output.status.code() {
+            Some(_) if DISK_FULL_RE.is_match(&stderr) => {
+                Err(disk_full_error(command_name, args))
+            }
+            Some(code) => Err(format!(
+                "⚠ {} exited with non-zero status code: {}\n\nArguments were: {}\n\nError output: {}",
+                command_name,
+                code,
+                build_args_string(args),
+                stderr
+            )),
+            None => Err(format!("⚠ {} terminated by signal", command_name)),
+        }
+    }
+}
+
+fn stderr_excerpt(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    lines[lines.len().saturating_sub(STDERR_EXCERPT_LINES)..].join("\n")
+}
+
+// sox writes its second argument, ffmpeg its last one
+fn disk_full_error(command_name: &str, args: &[String]) -> String {
+    let output_file = if command_name == SOX_COMMAND {
+        args.get(1)
+    } else {
+        args.last()
+    };
+    format!(
+        "{} {}.",
+        DISK_FULL_ERROR,
+        output_file.map_or(command_name, String::as_str)
+    )
+}
+
+fn run_command_and_get_output(command: &PlannedCommand) -> Result<Output> {
+    command_map_error(
+        command.to_process(&command.args).output(),
+        &command.program,
+        &command.args,
+    )
+}
+
+// network inputs may fail for reasons that go away by themselves
+fn is_transient_error(stderr: &str, network: bool) -> bool {
+    (TRANSIENT_ERROR_RE.is_match(stderr) || (network && END_OF_FILE_RE.is_match(stderr)))
+        && !PERMANENT_ERROR_RE.is_match(stderr)
+}
+
+// some input of the command is a URL
+fn reads_network(args: &[String]) -> bool {
+    args.windows(2)
+        .any(|pair| pair[0] == "-i" && crate::is_url(&pair[1]))
+}
+
+fn run_command_with_retries(command: &PlannedCommand, max_retries: u32) -> Result<Output> {
+    retry_command(command, max_retries, RETRY_BASE_DELAY)
+}
+
+fn retry_command(
+    command: &PlannedCommand,
+    max_retries: u32,
+    base_delay: Duration,
+) -> Result<Output> {
+    retry(
+        max_retries,
+        base_delay,
+        reads_network(&command.args),
+        || run_command_and_get_output(command),
+    )
+}
+
+// `nice` lowers the CPU priority, `ionice` the disk one on Linux. Missing tools
+// are skipped: the command then runs at a higher priority than asked, not at all.
+fn make_low_priority_wrapper<F>(os: &str, is_available: F) -> Vec<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut wrapper = Vec::new();
+    if os == "windows" {
+        // BELOW_NORMAL_PRIORITY_CLASS is set when creating the process
+        return wrapper;
+    }
+
+    if is_available("nice") {
+        wrapper.extend(["nice", "-n", "10"].iter().map(|arg| String::from(*arg)));
+    }
+    if os == "linux" && is_available("ionice") {
+        // idle class: disk access only when no other process needs it
+        wrapper.extend(["ionice", "-c", "3"].iter().map(|arg| String::from(*arg)));
+    }
+    wrapper
+}
+
+// `nice -n <level>` in front of `wrapper`, the adjustment of the low priority
+// wrapper is replaced. A missing `nice` is skipped as by
+// `make_low_priority_wrapper`.
+fn with_nice_level<F>(
+    mut wrapper: Vec<String>,
+    os: &str,
+    level: i32,
+    is_available: F,
+) -> Vec<String>
+where
+    F: Fn(&str) -> bool,
+{
+    if os == "windows" {
+        return wrapper;
+    }
+
+    if wrapper.first().map(String::as_str) == Some("nice") {
+        wrapper[2] = level.to_string();
+    } else if is_available("nice") {
+        let nice = vec![String::from("nice"), String::from("-n"), level.to_string()];
+        wrapper.splice(0..0, nice);
+    }
+    wrapper
+}
+
+fn is_in_path(program: &str) -> bool {
+    match env::var_os("PATH") {
+        Some(path) => env::split_paths(&path).any(|dir| dir.join(program).is_file()),
+        None => false,
+    }
+}
+
+// retry with exponential backoff, up to `RETRY_MAX_DELAY` between attempts.
+// The last output is returned either way.
+fn retry<F>(
+    max_retries: u32,
+    base_delay: Duration,
+    network: bool,
+    mut run_command: F,
+) -> Result<Output>
+where
+    F: FnMut() -> Result<Output>,
+{
+    let mut attempt = 0;
+    loop {
+        let output = run_command()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if output.status.success()
+            || attempt >= max_retries
+            || !is_transient_error(&stderr, network)
+        {
+            return Ok(output);
+        }
+
+        let delay = retry_delay(base_delay, attempt);
+        warn!(
+            "Transient failure, retrying in {:.1?}:\n{}",
+            delay,
+            stderr_excerpt(&stderr)
+        );
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+fn retry_delay(base_delay: Duration, attempt: u32) -> Duration {
+    2u32.checked_pow(attempt)
+        .and_then(|factor| base_delay.checked_mul(factor))
+        .map_or(RETRY_MAX_DELAY, |delay| delay.min(RETRY_MAX_DELAY))
+}
+
+fn make_sox_generate_noiseprof_args(conf: &Config) -> Result<Vec<String>> {
+    let mut args = Vec::with_capacity(3);
+    match conf.noise_profile_file {
+        Some(ref filename) => {
+            if filename == "" {
+                return Err(String::from("Error: no noise file provided."));
+            } else {
+                args.push(filename.clone()); // input noise file
+            }
+        }
+        None => {
+            return Err(String::from(
+                "Unexpected error: could not build noise profile generation command.",
+            ));
+        }
+    }
+    args.push(String::from("-n"));
+    args.push(String::from("noiseprof"));
+    Ok(args)
+}
+
+fn make_sox_clean_noise_args(conf: &Config, sox_output_file: &str) -> Result<Vec<String>> {
+    let mut args = Vec::with_capacity(5);
+
+    args.push(conf.input_file.clone()); // input file
+    args.push(sox_output_file.to_string()); // output file
+
+    if conf.trims_input() {
+        // trim the file with sox for increased noise reduction process speed
+        let duration = conf.to_time - conf.from_time;
+        args.push(String::from("trim"));
+        args.push(duration_to_string(conf.from_time));
+        args.push(duration_to_string(duration));
+    }
+
+    args.push(String::from("noisered"));
+    args.push(String::from("-")); // take noise profile from stdin
+    match conf.noise_reduction_amount {
+        Some(amount) => args.push(amount.to_string()),
+        None => {
+            return Err(String::from(
+                "Unexpected error: could not build noise cleaning command.",
+            ));
+        }
+    }
+
+    Ok(args)
+}
+
+// the audio filters of the process step, measured by loudnorm instead of
+// being normalized
+fn make_ffmpeg_loudness_measure_args(conf: &Config, state: &State, target: f64) -> Vec<String> {
+    let mut args = Vec::with_capacity(15);
+
+    args.push(String::from("-nostdin"));
+
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+
+    args.push(String::from("-vn"));
+
+    if sample_accurate_range(conf, state).is_none() {
+        push_trim_args(conf, state, &mut args);
+    }
+
+    let mut filters = make_audio_filters_before_loudness(conf, state);
+    filters.push(format!(
+        "{}:print_format=json",
+        make_loudness_filter(target, conf.loudness_true_peak, conf.loudness_range, None)
+    ));
+    args.push(String::from("-filter:a"));
+    args.push(filters.join(","));
+
+    // no output file
+    args.push(String::from("-f"));
+    args.push(String::from("null"));
+    args.push(String::from("-"));
+
+    args
+}
+
+fn make_ffmpeg_detect_max_volume_args(conf: &Config) -> Vec<String> {
+    let mut args = Vec::with_capacity(15);
+
+    args.push(String::from("-nostdin"));
+
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    args.push(String::from("-vn"));
+
+    // the peak may be measured on a representative section only
+    let measured_range = match conf.normalization_measure_range {
+        Some(range) => Some(range),
+        None if conf.process_whole_file => None,
+        None => Some((conf.from_time, conf.to_time)),
+    };
+    if let Some((from_time, to_time)) = measured_range {
+        args.push(String::from("-ss"));
+        args.push(duration_to_string(from_time));
+        args.push(String::from("-t"));
+        args.push(duration_to_string(to_time - from_time));
+    }
+
+    args.push(String::from("-filter:a"));
+    args.push(String::from("volumedetect"));
+
+    // no output file
+    args.push(String::from("-f"));
+    args.push(String::from("null"));
+    args.push(String::from("-"));
+
+    args
+}
+
+// trimmed on the input side so that times start at the selection, as in `Process`
+// silences longer than `min_silence` seconds and quieter than `noise_db`
+fn make_ffmpeg_detect_silence_args(
+    conf: &Config,
+    state: &State,
+    noise_db: f64,
+    min_silence: f64,
+) -> Vec<String> {
+    let mut args = Vec::with_capacity(14);
+
+    args.push(String::from("-nostdin"));
+
+    push_trim_args(conf, state, &mut args);
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+
+    args.push(String::from("-vn"));
+
+    // the silences of the audio heard, sox hands back a single track
+    let detection = format!("silencedetect=noise={}dB:d={}", noise_db, min_silence);
+    let tracks = heard_audio_tracks(conf);
+    if tracks == [(0, 0.0)] || state.sox_output_file.is_some() {
+        args.push(String::from("-af"));
+        args.push(detection);
+    } else {
+        let mut graph = FilterGraph::new();
+        let mix = graph.mix(&audio_track_labels(&tracks));
+        let output = graph.chain(&mix, &[detection]);
+        graph.output(output);
+        args.extend(graph.args());
+    }
+
+    // no output file
+    args.push(String::from("-f"));
+    args.push(String::from("null"));
+    args.push(String::from("-"));
+
+    args
+}
+
+fn make_ffmpeg_vidstab_detect_args(
+    conf: &Config,
+    state: &State,
+    transforms_file: &str,
+) -> Vec<String> {
+    let mut args = Vec::with_capacity(12);
+
+    args.push(String::from("-nostdin"));
+
+    if let Some(sequence) = conf.image_sequence {
+        args.extend(sequence.input_args());
+    }
+    args.push(String::from("-i"));
+    let input_file = if let Some(ref sox_output_file) = state.sox_output_file {
+        sox_output_file.clone()
+    } else {
+        conf.input_file.clone()
+    };
+    args.push(input_file);
+
+    args.push(String::from("-an"));
+    if conf.video_stream_index.is_some() {
+        args.push(String::from("-map"));
+        args.push(video_stream_spec(conf));
+    }
+
+    // analyze the same frames as the processing pass
+    push_trim_args(conf, state, &mut args);
+
+    let shakiness = conf.stabilize.as_ref().map_or(5, |s| s.shakiness);
+    args.push(String::from("-vf"));
+    args.push(format!(
+        "vidstabdetect=shakiness={}:result={}",
+        shakiness,
+        escape_filter_option(transforms_file)
+    ));
+
+    // no output file
+    args.push(String::from("-f"));
+    args.push(String::from("null"));
+    args.push(String::from("-"));
+
+    args
+}
+
+fn make_ffplay_snippet_args(
+    input: &str,
+    at: chrono::Duration,
+    len: chrono::Duration,
+) -> Vec<String> {
+    vec![
+        String::from("-nodisp"),
+        String::from("-autoexit"),
+        String::from("-loglevel"),
+        String::from("quiet"),
+        String::from("-ss"),
+        format!("{:.3}", at.num_milliseconds() as f64 / 1000.0),
+        String::from("-t"),
+        format!("{:.3}", len.num_milliseconds() as f64 / 1000.0),
+        String::from(input),
+    ]
+}
+
+// metadata only remux: streams are copied as is, whole file
+fn make_ffmpeg_fix_rotation_args(conf: &Config, rotation: Option<u16>) -> Vec<String> {
+    let mut args = Vec::with_capacity(11);
+
+    if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    args.push(String::from("-map"));
+    args.push(String::from("0"));
+    args.push(String::from("-c"));
+    args.push(String::from("copy"));
+
+    args.push(String::from("-metadata:s:v"));
+    match rotation {
+        Some(angle) => args.push(format!("rotate={}", angle)),
+        None => args.push(String::from("rotate=")), // an empty value removes the tag
+    }
+
+    args.push(conf.output_file.clone());
+
+    args
+}
+
+// video only, audio is cut in one go by the join
+fn make_ffmpeg_smart_cut_piece_args(
+    conf: &Config,
+    piece: &Piece,
+    encoder: &EncoderSettings,
+    piece_file: &str,
+) -> Vec<String> {
+    let mut args = Vec::with_capacity(20);
+
+    // the piece files are ours
+    args.push(String::from("-y"));
+    args.push(String::from("-nostdin"));
+
+    // seeking the input: a copy starts on the keyframe, an encoding on the exact frame
+    args.push(String::from("-ss"));
+    args.push(format!("{:.6}", piece.start));
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+    args.push(String::from("-t"));
+    args.push(format!("{:.6}", piece.duration()));
+
+    args.push(String::from("-map"));
+    args.push(match conf.video_stream_index {
+        Some(stream) => format!("0:v:{}", stream),
+        None => String::from("0:v:0"),
+    });
+    args.push(String::from("-an"));
+    args.push(String::from("-sn"));
+    args.push(String::from("-dn"));
+
+    match piece.kind {
+        PieceKind::Encode => {
+            args.extend(encoder.args());
+            push_threads_args(conf, &mut args);
+        }
+        PieceKind::Copy => {
+            args.push(String::from("-c:v"));
+            args.push(String::from("copy"));
+        }
+    }
+
+    args.push(piece_file.to_owned());
+
+    args
+}
+
+// joined video pieces, then the audio of the selection cut from the input
+fn make_ffmpeg_smart_cut_join_args(conf: &Config, list_file: &str) -> Vec<String> {
+    let mut args = Vec::with_capacity(30);
+
+    if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+    push_loglevel_args(conf, &mut args);
+
+    args.push(String::from("-f"));
+    args.push(String::from("concat"));
+    // absolute paths in the temporary directory
+    args.push(String::from("-safe"));
+    args.push(String::from("0"));
+    args.push(String::from("-i"));
+    args.push(list_file.to_owned());
+
+    args.push(String::from("-ss"));
+    args.push(duration_to_string(conf.from_time));
+    args.push(String::from("-t"));
+    args.push(duration_to_string(conf.to_time - conf.from_time));
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    args.push(String::from("-map"));
+    args.push(String::from("0:v"));
+    args.push(String::from("-c:v"));
+    args.push(String::from("copy"));
+    if !conf.ignore_audio {
+        args.push(String::from("-map"));
+        args.push(String::from("1:a?"));
+        push_audio_codec_args(conf, &mut args);
+    }
+
+    push_source_info_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_output_size_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    args.push(conf.output_file.clone());
+
+    args
+}
+
+// `stream`-th subtitle stream of the trimmed range, written next to the output file
+fn make_ffmpeg_subtitle_args(conf: &Config, stream: u32) -> Vec<String> {
+    let mut args = Vec::with_capacity(13);
+
+    if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    push_trim_args(conf, &State::default(), &mut args);
+
+    // the muxer follows the extension, the codec must be one it takes
+    let format = conf.subtitle_format.as_deref().unwrap_or("srt");
+    args.push(String::from("-map"));
+    args.push(format!("0:s:{}", stream));
+    args.push(String::from("-c:s"));
+    args.push(String::from(
+        containers::subtitle_codec(format).unwrap_or("srt"),
+    ));
+    args.push(output_file_with_extension(conf, format));
+
+    args
+}
+
+// hash of the decoded audio, of the input trimmed as for the output or of the
+// output itself. Samples are widened to 32 bits, which keeps them all as they are.
+fn make_ffmpeg_audio_md5_args(conf: &Config, state: &State, input_selection: bool) -> Vec<String> {
+    let mut args = Vec::with_capacity(16);
+    args.push(String::from("-nostdin"));
+    args.push(String::from("-i"));
+
+    if input_selection {
+        args.push(conf.input_file.clone());
+        match sample_accurate_range(conf, state) {
+            Some((start_sample, end_sample)) => {
+                args.push(String::from("-af"));
+                args.push(format!(
+                    "atrim=start_sample={}:end_sample={}",
+                    start_sample, end_sample
+                ));
+            }
+            None => push_trim_args(conf, state, &mut args),
+        }
+    } else {
+        args.push(conf.output_file.clone());
+    }
+
+    // same stream selection as the output
+    args.push(String::from("-vn"));
+    if conf.keep_all_audio {
+        args.push(String::from("-map"));
+        args.push(String::from("0:a"));
+    }
+    args.push(String::from("-c:a"));
+    args.push(String::from("pcm_s32le"));
+    args.push(String::from("-f"));
+    args.push(String::from("md5"));
+    args.push(String::from("-"));
+
+    args
+}
+
+// single picture of the whole trimmed range, written next to the output file
+fn make_ffmpeg_spectrogram_args(conf: &Config, width: u32, height: u32) -> Vec<String> {
+    let mut args = Vec::with_capacity(15);
+
+    if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    push_trim_args(conf, &State::default(), &mut args);
+
+    args.push(String::from("-lavfi"));
+    args.push(format!("showspectrumpic=s={}x{}", width, height));
+    args.push(String::from("-frames:v"));
+    args.push(String::from("1"));
+
+    args.push(output_file_with_extension(conf, "png"));
+
+    args
+}
+
+// the picture is a JPEG so that it does not overwrite a spectrogram
+fn make_ffmpeg_contact_sheet_args(
+    conf: &Config,
+    state: &State,
+    columns: u32,
+    rows: u32,
+) -> Result<Vec<String>> {
+    let selection = if conf.process_whole_file {
+        state.input_duration.ok_or_else(|| {
+            String::from("Error: the input duration is unknown, a contact sheet needs it.")
+        })?
+    } else {
+        conf.to_time - conf.from_time
+    };
+    let interval = std::cmp::max(
+        selection / (columns * rows) as i32,
+        chrono::Duration::milliseconds(CONTACT_SHEET_MIN_INTERVAL_MS),
+    );
+
+    let mut args = Vec::with_capacity(15);
+
+    if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+
+    // seeking the input: frame times then start at the selection start
+    if !conf.process_whole_file {
+        args.push(String::from("-ss"));
+        args.push(duration_to_string(conf.from_time));
+        args.push(String::from("-t"));
+        args.push(duration_to_string(selection));
+    }
+    args.push(String::from("-i"));
+    args.push(conf.input_file.clone());
+
+    // frame times start at 0 once the input is seeked
+    let timestamps_offset = if conf.process_whole_file {
+        chrono::Duration::zero()
+    } else {
+        conf.from_time
+    };
+    args.push(String::from("-vf"));
+    args.push(make_contact_sheet_filter(
+        columns,
+        rows,
+        interval,
+        conf.contact_sheet_tile_width
+            .unwrap_or(CONTACT_SHEET_THUMBNAIL_WIDTH),
+        Some(timestamps_offset).filter(|_| conf.contact_sheet_timestamps),
+    ));
+    args.push(String::from("-frames:v"));
+    args.push(String::from("1"));
+
+    args.push(output_file_with_extension(conf, "jpg"));
+
+    Ok(args)
+}
+
+// the first frame of each `interval` long slice of the selection: times are
+// not accumulated, rounding cannot push the last frames past the end.
+// `timestamps` is the input time of the first frame, when times are burned in.
+fn make_contact_sheet_filter(
+    columns: u32,
+    rows: u32,
+    interval: chrono::Duration,
+    tile_width: u32,
+    timestamps: Option<chrono::Duration>,
+) -> String {
+    let interval = interval.num_milliseconds() as f64 / 1000.0;
+    let mut filters = vec![
+        format!(
+            "select='isnan(prev_selected_t)+gt(floor(t/{interval}),floor(prev_selected_t/{interval}))'",
+            interval = interval
+        ),
+        format!("scale={}:-2", tile_width),
+    ];
+    if let Some(offset) = timestamps {
+        // quoted for the graph, the colons of the text escaped for its options
+        filters.push(format!(
+            "drawtext=text='%{{pts\\:hms\\:{}}}':x=5:y=h-th-5:fontcolor=white:box=1:boxcolor=black@0.5",
+            offset.num_milliseconds() as f64 / 1000.0
+        ));
+    }
+    filters.push(format!("tile={}x{}", columns, rows));
+    filters.join(",")
+}
+
+fn make_ffmpeg_processing_args(conf: &Config, state: &State) -> Vec<String> {
+    let mut args = Vec::with_capacity(15);
+
+    if conf.preview {
+        if conf.preview_loop {
+            // ffplay seeks back to the start of the selection each time
+            args.push(String::from("-loop"));
+            args.push(String::from("0"));
+        }
+    } else if conf.allow_overidde {
+        args.push(String::from("-y"));
+    } else {
+        args.push(String::from("-nostdin"));
+    }
+    push_loglevel_args(conf, &mut args);
+
+    if let Some(ref segments) = state.silence_segments {
+        if segments.iter().any(Segment::is_silent) {
+            return make_ffmpeg_speedup_args(conf, state, segments, args);
+        }
+    }
+
+    if !conf.additional_outputs.is_empty() && !conf.preview {
+        return make_ffmpeg_multiple_outputs_args(conf, state, args);
+    }
+
+    if let Operation::Visualize {
+        style,
+        resolution,
+        colors,
+    } = conf.operation
+    {
+        return make_ffmpeg_visualize_args(conf, state, style, resolution, colors, args);
+    }
+
+    if let (Some(ref image), false) = (&conf.still_image, conf.preview) {
+        return make_ffmpeg_still_image_args(conf, state, image, args);
+    }
+
+    if let (Some((ref video_file, time)), false) = (&conf.thumbnail_from_video, conf.preview) {
+        return make_ffmpeg_thumbnail_args(conf, state, video_file, *time, args);
+    }
+
+    if conf.stream_copy && !conf.preview {
+        return make_ffmpeg_stream_copy_args(conf, state, args);
+    }
+
+    if let Some(sequence) = conf.image_sequence {
+        args.extend(sequence.input_args());
+    }
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    push_chapters_args(state, 1, &mut args);
+
+    if conf.demux_streams && !conf.preview {
+        // one output per stream type, output options apply to the following output only
+        let (video_output_file, audio_output_file) = demux_output_files(conf);
+
+        if !conf.ignore_video {
+            args.push(String::from("-map"));
+            args.push(video_stream_spec(conf));
+            push_trim_args(conf, state, &mut args);
+            push_video_filter_args(conf, state, &mut args);
+            push_video_codec_args(conf, &mut args);
+            push_target_size_args(conf, state, &mut args);
+            push_threads_args(conf, &mut args);
+            push_rotation_metadata_args(conf, &mut args);
+            push_source_info_args(conf, &mut args);
+            push_metadata_args(conf, &mut args);
+            push_output_size_args(conf, &mut args);
+            push_movflags_args(conf, &video_output_file, &mut args);
+            push_fix_timestamps_output_args(conf, &mut args);
+            args.push(video_output_file);
+        }
+
+        if !conf.ignore_audio {
+            args.push(String::from("-map"));
+            args.push(String::from("0:a"));
+            push_trim_args(conf, state, &mut args);
+            push_audio_filter_args(conf, state, &mut args);
+            push_audio_codec_args(conf, &mut args);
+            push_preserved_channels_args(conf, state, &mut args);
+            push_threads_args(conf, &mut args);
+            push_source_info_args(conf, &mut args);
+            push_metadata_args(conf, &mut args);
+            push_output_size_args(conf, &mut args);
+            push_movflags_args(conf, &audio_output_file, &mut args);
+            push_fix_timestamps_output_args(conf, &mut args);
+            args.push(audio_output_file);
+        }
+
+        return args;
+    }
+
+    if conf.keep_all_audio && !conf.preview {
+        // without maps ffmpeg keeps a single stream of each type, `?` lets
+        // audio only inputs through
+        if !conf.ignore_video {
+            args.push(String::from("-map"));
+            args.push(match conf.video_stream_index {
+                Some(_) => video_stream_spec(conf),
+                None => String::from("0:v?"),
+            });
+        }
+        args.push(String::from("-map"));
+        args.push(String::from("0:a"));
+        push_kept_stream_maps(conf, &mut args);
+    } else if (!conf.audio_tracks.is_empty() || conf.downmix_tracks.is_some()) && !conf.preview {
+        // the audio maps follow the filters
+        if !conf.ignore_video {
+            args.push(String::from("-map"));
+            args.push(match conf.video_stream_index {
+                Some(_) => video_stream_spec(conf),
+                None => String::from("0:v?"),
+            });
+        }
+    } else if let (Some(stream), false) = (conf.video_stream_index, conf.ignore_video) {
+        if conf.preview {
+            args.push(String::from("-vst"));
+            args.push(format!("v:{}", stream));
+        } else {
+            // a map drops the streams not mapped: the audio one ffmpeg
+            // would have kept is mapped as well
+            args.push(String::from("-map"));
+            args.push(video_stream_spec(conf));
+            if let Some(audio) = default_audio_map(conf, state) {
+                args.push(String::from("-map"));
+                args.push(audio);
+            }
+            push_kept_stream_maps(conf, &mut args);
+        }
+    }
+
+    if conf.ignore_video {
+        args.push(String::from("-vn"));
+    }
+
+    // pictures have no audio to encode
+    if conf.ignore_audio || conf.image_sequence.is_some() {
+        args.push(String::from("-an"));
+    }
+
+    if sample_accurate_range(conf, state).is_none() {
+        push_trim_args(conf, state, &mut args);
+    }
+
+    let copy_audio = can_copy_audio(conf, state, &conf.output_file);
+
+    // == filters
+    match conf.raw_filter_complex {
+        // ffplay has no -filter_complex, previews play unfiltered
+        Some(ref graph) if !conf.preview => {
+            args.push(String::from("-filter_complex"));
+            args.push(graph.clone());
+        }
+        _ => {
+            if !conf.ignore_video {
+                push_video_filter_args(conf, state, &mut args);
+            }
+            match conf.downmix_tracks {
+                Some(ref tracks) if !conf.preview => {
+                    push_downmix_args(conf, state, tracks, &mut args);
+                    push_kept_stream_maps(conf, &mut args);
+                }
+                _ if !conf.audio_tracks.is_empty() && !conf.preview => {
+                    push_audio_tracks_args(conf, state, &mut args);
+                    push_kept_stream_maps(conf, &mut args);
+                }
+                _ if !copy_audio => push_audio_filter_args(conf, state, &mut args),
+                _ => (),
+            }
+        }
+    }
+    // == end filters
 
     if !conf.preview {
-        args.push(conf.output_file.clone());
+        push_output_args(conf, state, copy_audio, &mut args);
+    }
+
+    args
+}
+
+// codecs and tags of the main output, then the output itself
+fn push_output_args(conf: &Config, state: &State, copy_audio: bool, args: &mut Vec<String>) {
+    push_video_codec_args(conf, args);
+    push_target_size_args(conf, state, args);
+    push_segment_keyframes_args(conf, args);
+    if copy_audio {
+        args.push(String::from("-c:a"));
+        args.push(String::from("copy"));
+    } else {
+        push_audio_codec_args(conf, args);
+        push_preserved_channels_args(conf, state, args);
+    }
+    push_threads_args(conf, args);
+    if !conf.ignore_video {
+        push_rotation_metadata_args(conf, args);
+    }
+    push_source_info_args(conf, args);
+    push_metadata_args(conf, args);
+    push_output_size_args(conf, args);
+    push_movflags_args(conf, &conf.output_file, args);
+    push_fix_timestamps_output_args(conf, args);
+    args.extend(conf.extra_ffmpeg_args.iter().cloned());
+    push_format_args(conf, args);
+    args.push(output_target(conf));
+}
+
+// the audio is decoded and filtered once, `asplit` hands a copy to each
+// output encoding it. Output options apply to the following output only.
+fn make_ffmpeg_multiple_outputs_args(
+    conf: &Config,
+    state: &State,
+    mut args: Vec<String>,
+) -> Vec<String> {
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    push_chapters_args(state, 1, &mut args);
+
+    let copy_audio = can_copy_audio(conf, state, &conf.output_file);
+    let filtered_outputs = conf.additional_outputs.len() + usize::from(!copy_audio);
+    let mut filters = make_audio_filters(conf, state);
+    if filtered_outputs > 1 {
+        filters.push(format!("asplit={}", filtered_outputs));
+    }
+    let mut graph = FilterGraph::new();
+    let mut audio = graph
+        .node(
+            &[&Label::input(0, "a:0")],
+            &filters.join(","),
+            filtered_outputs,
+        )
+        .into_iter();
+    args.push(String::from("-filter_complex"));
+    args.push(graph.to_filter_complex());
+
+    // maps replace the default stream selection
+    if !conf.ignore_video {
+        args.push(String::from("-map"));
+        args.push(match conf.video_stream_index {
+            Some(_) => video_stream_spec(conf),
+            None => String::from("0:v?"),
+        });
+    }
+    args.push(String::from("-map"));
+    args.push(if copy_audio {
+        String::from("0:a:0")
+    } else {
+        audio.next().unwrap().to_string()
+    });
+    push_kept_stream_maps(conf, &mut args);
+    let trim_outputs = sample_accurate_range(conf, state).is_none();
+    if trim_outputs {
+        push_trim_args(conf, state, &mut args);
+    }
+    if !conf.ignore_video {
+        push_video_filter_args(conf, state, &mut args);
+    }
+    push_output_args(conf, state, copy_audio, &mut args);
+
+    for (output, label) in conf.additional_outputs.iter().zip(audio) {
+        args.push(String::from("-map"));
+        args.push(label.to_string());
+        if trim_outputs {
+            push_trim_args(conf, state, &mut args);
+        }
+        if let Some(ref codec) = output.audio_codec {
+            args.push(String::from("-c:a"));
+            args.push(codec.clone());
+        }
+        if let Some(bitrate) = output.bitrate {
+            args.push(String::from("-b:a"));
+            args.push(format!("{}k", bitrate));
+        }
+        push_threads_args(conf, &mut args);
+        push_metadata_args(conf, &mut args);
+        push_fix_timestamps_output_args(conf, &mut args);
+        if let Some(ref format) = output.format {
+            args.push(String::from("-f"));
+            args.push(format.clone());
+        }
+        args.push(output.path.clone());
+    }
+
+    args
+}
+
+// the processed audio is split: one copy is drawn, the other is encoded
+// along with the picture
+fn make_ffmpeg_visualize_args(
+    conf: &Config,
+    state: &State,
+    style: VisualStyle,
+    resolution: (u32, u32),
+    colors: Option<[u8; 3]>,
+    mut args: Vec<String>,
+) -> Vec<String> {
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    push_chapters_args(state, 1, &mut args);
+
+    let mut filters = make_audio_filters(conf, state);
+    filters.push(String::from("asplit=2"));
+    let mut graph = FilterGraph::new();
+    let mut audio = graph.node(&[&Label::input(0, "a:0")], &filters.join(","), 2);
+    let video = graph.chain(
+        &audio[1],
+        &make_visualizer_filters(style, resolution, colors),
+    );
+    graph.output(video);
+    graph.output(audio.remove(0));
+    args.extend(graph.args());
+
+    if sample_accurate_range(conf, state).is_none() {
+        push_trim_args(conf, state, &mut args);
+    }
+
+    // h264 and aac unless chosen otherwise, played everywhere
+    args.push(String::from("-c:v"));
+    args.push(String::from(
+        conf.video_codec.as_deref().unwrap_or(VISUALIZE_VIDEO_CODEC),
+    ));
+    if let Some(ref preset) = conf.encoder_preset {
+        args.push(String::from("-preset"));
+        args.push(preset.clone());
+    }
+    args.push(String::from("-pix_fmt"));
+    args.push(String::from("yuv420p"));
+    if conf.audio_encoder().is_none() {
+        args.push(String::from("-c:a"));
+        args.push(String::from(VISUALIZE_AUDIO_CODEC));
+    }
+    push_audio_codec_args(conf, &mut args);
+    push_preserved_channels_args(conf, state, &mut args);
+    push_threads_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    push_fix_timestamps_output_args(conf, &mut args);
+    push_format_args(conf, &mut args);
+    args.push(output_target(conf));
+
+    args
+}
+
+/// Output file of a video made from `conf.still_image`: the output file when
+/// its container holds the video, else an mp4 file next to it, e.g. for an
+/// audio output.
+pub fn still_image_output_file(conf: &Config) -> String {
+    let video_codec = conf.video_codec.as_deref().unwrap_or(VISUALIZE_VIDEO_CODEC);
+    if containers::is_compatible(&output_extension(conf), Some(video_codec), None) {
+        conf.output_file.clone()
+    } else {
+        output_file_with_extension(conf, "mp4")
+    }
+}
+
+// the picture repeats until the audio ends, both trimmed on the output side
+fn make_ffmpeg_still_image_args(
+    conf: &Config,
+    state: &State,
+    image: &Path,
+    mut args: Vec<String>,
+) -> Vec<String> {
+    args.push(String::from("-loop"));
+    args.push(String::from("1"));
+    args.push(String::from("-i"));
+    args.push(image.to_string_lossy().into_owned());
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    // third input, after the picture and the audio
+    if let Some(ref chapters_file) = state.chapters_file {
+        args.push(String::from("-i"));
+        args.push(chapters_file.clone());
+        args.push(String::from("-map_chapters"));
+        args.push(String::from("2"));
+    }
+
+    args.push(String::from("-map"));
+    args.push(String::from("0:v"));
+    args.push(String::from("-map"));
+    args.push(String::from("1:a:0"));
+    if sample_accurate_range(conf, state).is_none() {
+        push_trim_args(conf, state, &mut args);
+    }
+    // 4:2:0 pictures need even sizes
+    args.push(String::from("-vf"));
+    args.push(String::from("scale=trunc(iw/2)*2:trunc(ih/2)*2"));
+    push_audio_filter_args(conf, state, &mut args);
+
+    match conf.video_codec.as_deref() {
+        Some(codec) => {
+            args.push(String::from("-c:v"));
+            args.push(String::from(codec));
+        }
+        None => {
+            args.push(String::from("-c:v"));
+            args.push(String::from(VISUALIZE_VIDEO_CODEC));
+            args.push(String::from("-tune"));
+            args.push(String::from("stillimage"));
+        }
+    }
+    if let Some(ref preset) = conf.encoder_preset {
+        args.push(String::from("-preset"));
+        args.push(preset.clone());
+    }
+    args.push(String::from("-pix_fmt"));
+    args.push(String::from("yuv420p"));
+    push_segment_keyframes_args(conf, &mut args);
+    if conf.audio_encoder().is_none() {
+        args.push(String::from("-c:a"));
+        args.push(String::from(VISUALIZE_AUDIO_CODEC));
+    }
+    push_audio_codec_args(conf, &mut args);
+    push_preserved_channels_args(conf, state, &mut args);
+    push_threads_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    push_fix_timestamps_output_args(conf, &mut args);
+    // the looped picture never ends
+    args.push(String::from("-shortest"));
+    push_format_args(conf, &mut args);
+    args.push(output_target(conf));
+
+    args
+}
+
+// the audio is trimmed on the input side: as output options, the cut would
+// also drop the single frame of the picture, which starts at zero
+fn make_ffmpeg_thumbnail_args(
+    conf: &Config,
+    state: &State,
+    video_file: &str,
+    time: chrono::Duration,
+    mut args: Vec<String>,
+) -> Vec<String> {
+    if sample_accurate_range(conf, state).is_none() {
+        push_trim_args(conf, state, &mut args);
+    }
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    args.push(String::from("-ss"));
+    args.push(duration_to_string(time));
+    args.push(String::from("-i"));
+    args.push(String::from(video_file));
+    // third input, after the audio and the video
+    if let Some(ref chapters_file) = state.chapters_file {
+        args.push(String::from("-i"));
+        args.push(chapters_file.clone());
+        args.push(String::from("-map_chapters"));
+        args.push(String::from("2"));
+    }
+
+    args.push(String::from("-map"));
+    args.push(String::from("0:a:0"));
+    args.push(String::from("-map"));
+    args.push(String::from("1:v:0"));
+    push_audio_filter_args(conf, state, &mut args);
+
+    // a picture whatever the video codec, audio containers hold no other
+    args.push(String::from("-c:v"));
+    args.push(String::from(match conf.video_codec.as_deref() {
+        Some("png") => "png",
+        _ => THUMBNAIL_CODEC,
+    }));
+    args.push(String::from("-frames:v"));
+    args.push(String::from("1"));
+    args.push(String::from("-disposition:v:0"));
+    args.push(String::from("attached_pic"));
+    push_audio_codec_args(conf, &mut args);
+    push_preserved_channels_args(conf, state, &mut args);
+    push_threads_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    push_fix_timestamps_output_args(conf, &mut args);
+    push_format_args(conf, &mut args);
+    args.push(output_target(conf));
+
+    args
+}
+
+// every stream of the input as it is, those excluded removed by negative
+// maps. Unknown streams, which ffmpeg drops by default, are kept too.
+fn make_ffmpeg_stream_copy_args(
+    conf: &Config,
+    state: &State,
+    mut args: Vec<String>,
+) -> Vec<String> {
+    push_input_recovery_args(conf, &mut args);
+    args.push(String::from("-i"));
+    args.push(processing_input_file(conf, state));
+    push_chapters_args(state, 1, &mut args);
+
+    args.push(String::from("-map"));
+    args.push(String::from("0"));
+    for spec in stream_copy_exclusions(conf) {
+        args.push(String::from("-map"));
+        args.push(format!("-0:{}", spec));
+    }
+    push_trim_args(conf, state, &mut args);
+    args.push(String::from("-c"));
+    args.push(String::from("copy"));
+    args.push(String::from("-copy_unknown"));
+
+    push_source_info_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_output_size_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    push_fix_timestamps_output_args(conf, &mut args);
+    args.extend(conf.extra_ffmpeg_args.iter().cloned());
+    push_format_args(conf, &mut args);
+    args.push(output_target(conf));
+
+    args
+}
+
+// stream specifiers left out of a stream copy, ignored kinds included
+fn stream_copy_exclusions(conf: &Config) -> Vec<String> {
+    let mut exclusions = Vec::new();
+    if conf.ignore_video {
+        exclusions.push(String::from("v"));
+    }
+    if conf.ignore_audio {
+        exclusions.push(String::from("a"));
+    }
+    exclusions.extend(conf.stream_copy_exclude.iter().cloned());
+    exclusions
+}
+
+// streams of `info` a stream copy writes, in input order
+fn copied_streams(conf: &Config, info: &probe::MediaInfo) -> Vec<String> {
+    let exclusions: Vec<(&str, Option<u32>)> = stream_copy_exclusions(conf)
+        .iter()
+        .filter_map(|spec| parse_stream_specifier(spec))
+        .collect();
+
+    // specifier indices count the streams of a kind only
+    let mut kind_counts: HashMap<&str, u32> = HashMap::new();
+    let mut copied = Vec::new();
+    for stream in &info.streams {
+        let kind_count = kind_counts.entry(stream.kind.as_str()).or_insert(0);
+        let kind_index = *kind_count;
+        *kind_count += 1;
+
+        let excluded = exclusions.iter().any(|&(kind, index)| {
+            kind == stream.kind && index.map_or(true, |index| index == kind_index)
+        });
+        if !excluded {
+            copied.push(match stream.codec {
+                Some(ref codec) => format!("#{} {} ({})", stream.index, stream.kind, codec),
+                None => format!("#{} {}", stream.index, stream.kind),
+            });
+        }
+    }
+    copied
+}
+
+// the spectrums are drawn in shades of grey then tinted, waves take the colour as is
+fn make_visualizer_filters(
+    style: VisualStyle,
+    (width, height): (u32, u32),
+    colors: Option<[u8; 3]>,
+) -> Vec<String> {
+    let size = format!("s={}x{}", width, height);
+    let mut filters = vec![match style {
+        VisualStyle::Waves => format!("showwaves={}:mode=cline", size),
+        VisualStyle::Spectrum => format!("showspectrum={}:slide=scroll", size),
+        VisualStyle::Cqt => format!("showcqt={}", size),
+    }];
+    match (style, colors) {
+        (VisualStyle::Waves, Some([red, green, blue])) => {
+            filters[0].push_str(&format!(":colors=0x{:02x}{:02x}{:02x}", red, green, blue));
+        }
+        (_, Some([red, green, blue])) => {
+            filters.push(String::from("hue=s=0"));
+            filters.push(format!(
+                "colorchannelmixer=rr={:.3}:gg={:.3}:bb={:.3}",
+                f64::from(red) / 255.0,
+                f64::from(green) / 255.0,
+                f64::from(blue) / 255.0
+            ));
+        }
+        (_, None) => {}
+    }
+    filters
+}
+
+// input `input_index`, the one after the source, its chapters replace those
+// of the source
+fn push_chapters_args(state: &State, input_index: usize, args: &mut Vec<String>) {
+    if let Some(ref chapters_file) = state.chapters_file {
+        args.push(String::from("-i"));
+        args.push(chapters_file.clone());
+        args.push(String::from("-map_chapters"));
+        args.push(input_index.to_string());
+    }
+}
+
+// lowercase extension of the output file, empty without one
+fn output_extension(conf: &Config) -> String {
+    Path::new(&conf.output_file)
+        .extension()
+        .map_or_else(String::new, |extension| {
+            extension.to_string_lossy().to_lowercase()
+        })
+}
+
+// use sox output file if applicable
+fn processing_input_file(conf: &Config, state: &State) -> String {
+    match state.sox_output_file {
+        Some(ref sox_output_file) => sox_output_file.clone(),
+        None => conf.input_file.clone(),
+    }
+}
+
+// each segment is cut from the selection on the input side and retimed, then
+// joined in a single graph, the usual filters apply to the joined result.
+// `args` holds the leading options.
+fn make_ffmpeg_speedup_args(
+    conf: &Config,
+    state: &State,
+    segments: &[Segment],
+    mut args: Vec<String>,
+) -> Vec<String> {
+    // segment times start at the selection
+    let trimmed = !state.already_trimed && conf.trims_input();
+    let offset = if trimmed {
+        conf.from_time
+    } else {
+        chrono::Duration::zero()
+    };
+    let input_file = processing_input_file(conf, state);
+    for (index, segment) in segments.iter().enumerate() {
+        let start =
+            offset + chrono::Duration::milliseconds((segment.start * 1000.0).round() as i64);
+        // the last one runs to the end, whatever the rounding of the duration
+        let end = if index + 1 < segments.len() {
+            Some(offset + chrono::Duration::milliseconds((segment.end * 1000.0).round() as i64))
+        } else if trimmed {
+            Some(conf.to_time)
+        } else {
+            None
+        };
+        args.push(String::from("-ss"));
+        args.push(duration_to_string(start));
+        if let Some(end) = end {
+            args.push(String::from("-t"));
+            args.push(duration_to_string(end - start));
+        }
+        push_input_recovery_args(conf, &mut args);
+        args.push(String::from("-i"));
+        args.push(input_file.clone());
+    }
+    push_chapters_args(state, segments.len(), &mut args);
+
+    // sox only hands back the audio
+    let with_video = !conf.ignore_video && state.has_video && state.sox_output_file.is_none();
+    let video = if with_video {
+        Some(format!("v:{}", conf.video_stream_index.unwrap_or(0)))
+    } else {
+        None
+    };
+    let video_filters = if with_video {
+        make_video_filters(conf, state)
+    } else {
+        Vec::new()
+    };
+    // the kept track may be asked as it is
+    let audio_filters = if conf
+        .audio_tracks
+        .iter()
+        .any(|track| track.keep && track.filters == FilterChain::Unfiltered)
+    {
+        Vec::new()
+    } else {
+        make_audio_filters(conf, state)
+    };
+    let graph = silence::speedup_graph(
+        segments,
+        video.as_deref(),
+        &heard_audio_tracks(conf),
+        &video_filters,
+        &audio_filters,
+    );
+    args.extend(graph.args());
+
+    push_video_codec_args(conf, &mut args);
+    push_target_size_args(conf, state, &mut args);
+    push_segment_keyframes_args(conf, &mut args);
+    push_audio_codec_args(conf, &mut args);
+    push_preserved_channels_args(conf, state, &mut args);
+    push_threads_args(conf, &mut args);
+    if with_video {
+        push_rotation_metadata_args(conf, &mut args);
+    }
+    push_source_info_args(conf, &mut args);
+    push_metadata_args(conf, &mut args);
+    push_output_size_args(conf, &mut args);
+    push_movflags_args(conf, &conf.output_file, &mut args);
+    push_fix_timestamps_output_args(conf, &mut args);
+    args.extend(conf.extra_ffmpeg_args.iter().cloned());
+    push_format_args(conf, &mut args);
+    args.push(output_target(conf));
+
+    args
+}
+
+fn output_target(conf: &Config) -> String {
+    if conf.writes_to_stdout() {
+        String::from(STDOUT_PIPE)
+    } else {
+        conf.output_file.clone()
+    }
+}
+
+fn probe_input(conf: &Config) -> Result<probe::MediaInfo> {
+    let input_file = conf.resolve_path(&conf.input_file);
+    match conf.image_sequence {
+        Some(ref sequence) => {
+            probe::MediaInfo::probe_image_sequence(&input_file.to_string_lossy(), sequence)
+        }
+        None => probe::MediaInfo::probe(&input_file.to_string_lossy()),
+    }
+}
+
+// what the arguments depend on in the input streams, e.g. `can_copy_audio`
+fn set_probed_streams(state: &mut State, info: &probe::MediaInfo) {
+    state.audio_stream = default_audio_stream(info);
+    state.probed_streams = true;
+    let audio_stream = info.streams.iter().find(|stream| stream.kind == "audio");
+    state.sample_rate = audio_stream.and_then(|stream| stream.sample_rate);
+    state.audio_channels = audio_stream.and_then(|stream| stream.channels);
+    state.audio_codec = audio_stream.and_then(|stream| stream.codec.clone());
+    state.has_video = info.streams.iter().any(|stream| stream.kind == "video");
+}
+
+// encoding again is lossy and slow: the input audio is copied when nothing
+// would change it and `output_file` can hold it
+fn can_copy_audio(conf: &Config, state: &State, output_file: &str) -> bool {
+    if !conf.copy_audio_when_possible
+        || conf.ignore_audio
+        || conf.preview
+        || conf.keep_all_audio
+        || !conf.audio_tracks.is_empty()
+        || conf.downmix_tracks.is_some()
+        || conf.output_format.is_some()
+        || conf.raw_filter_complex.is_some()
+        || state.sox_output_file.is_some()
+        || !conf.audio_filter_names().is_empty()
+        || sample_accurate_range(conf, state).is_some()
+    {
+        return false;
+    }
+
+    let source_codec = match state.audio_codec {
+        Some(ref codec) => codec.as_str(),
+        None => return false,
+    };
+    // a chosen encoder is kept unless it makes the same codec without settings
+    let same_codec = match conf.audio_codec.as_deref() {
+        None => true,
+        Some(codec) => {
+            codec == source_codec
+                && conf.aac_options.is_none()
+                && conf.mp3.is_none()
+                && conf.flac_compression.is_none()
+        }
+    };
+    let extension = Path::new(output_file)
+        .extension()
+        .map_or_else(String::new, |extension| {
+            extension.to_string_lossy().to_lowercase()
+        });
+    match containers::codec_containers(source_codec) {
+        Some(containers) => same_codec && containers.contains(&extension.as_str()),
+        None => false,
+    }
+}
+
+fn push_trim_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    if !state.already_trimed && conf.trims_input() {
+        // input file may already be trimed by sox
+        let duration = conf.to_time - conf.from_time;
+        args.push(String::from("-ss"));
+        args.push(duration_to_string(conf.from_time));
+        args.push(String::from("-t"));
+        args.push(duration_to_string(duration));
+    }
+}
+
+fn push_video_filter_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    let filters = make_video_filters(conf, state);
+    if !filters.is_empty() {
+        args.push(String::from("-vf"));
+        args.push(filters.join(","));
+    }
+}
+
+fn make_video_filters(conf: &Config, state: &State) -> Vec<String> {
+    let mut filters = Vec::with_capacity(2);
+
+    // denoise first so that later filters work on a clean picture
+    match conf.video_denoise {
+        Some(VideoDenoise::Hqdn3d { luma, chroma }) => filters.push(format!(
+            "hqdn3d=luma_spatial={}:chroma_spatial={}",
+            luma, chroma
+        )),
+        Some(VideoDenoise::Nlmeans { strength }) => filters.push(format!("nlmeans=s={}", strength)),
+        None => {}
+    }
+
+    if let (Some(stabilize), Some(transforms_file)) =
+        (&conf.stabilize, &state.vidstab_transforms_file)
+    {
+        filters.push(format!(
+            "vidstabtransform=input={}:smoothing={}",
+            escape_filter_option(transforms_file),
+            stabilize.smoothing
+        ));
+    }
+
+    if let Some(color) = conf.color {
+        if let Some(filter) = make_color_filter(&color) {
+            filters.push(filter);
+        }
+    }
+
+    // a LUT expects its input to be already colour corrected
+    if let Some(ref lut) = conf.lut {
+        filters.push(format!(
+            "lut3d=file={}",
+            escape_filter_option(&lut.to_string_lossy())
+        ));
+    }
+
+    // the width follows, kept even for encoders
+    if let Some(height) = conf.max_height {
+        filters.push(format!("scale=-2:'min(ih,{})'", height));
+    }
+
+    // last, so that any scaling done above is taken into account
+    if let Some(AspectPolicy::SquarePixels) = conf.aspect_ratio {
+        // stretch width according to the sample aspect ratio, keeping it even for encoders
+        filters.push(String::from("scale=trunc(iw*sar/2)*2:ih"));
+        filters.push(String::from("setsar=1"));
+    }
+
+    // drawn last, over the final picture: nothing is stretched or covers it
+    if let Some(ref timecode) = conf.burn_timecode {
+        filters.push(make_timecode_filter(
+            timecode,
+            conf.font_path.as_deref(),
+            timecode_offset(conf, state, timecode.source),
+        ));
+    }
+
+    filters
+}
+
+// added to the frame times to show the wanted one. Trimming the output keeps
+// the input times, seeking the input before speeding up silences restarts them.
+fn timecode_offset(conf: &Config, state: &State, source: TimecodeSource) -> chrono::Duration {
+    if !conf.trims_input() {
+        return chrono::Duration::zero();
+    }
+    let input_seeked = matches!(
+        state.silence_segments,
+        Some(ref segments) if segments.iter().any(Segment::is_silent)
+    );
+    match (source, input_seeked) {
+        (TimecodeSource::SelectionRelative, false) => -conf.from_time,
+        (TimecodeSource::SourceAbsolute, true) => conf.from_time,
+        _ => chrono::Duration::zero(),
+    }
+}
+
+fn make_timecode_filter(
+    style: &TimecodeStyle,
+    font_path: Option<&str>,
+    offset: chrono::Duration,
+) -> String {
+    let mut filter = String::from("drawtext=");
+    if let Some(font_path) = font_path {
+        filter.push_str(&format!("fontfile={}:", escape_filter_option(font_path)));
+    }
+    let text = if offset.is_zero() {
+        String::from("%{pts:hms}")
+    } else {
+        format!("%{{pts:hms:{}}}", offset.num_milliseconds() as f64 / 1000.0)
+    };
+    let (x, y) = match style.position {
+        TimecodePosition::TopLeft => ("20", "20"),
+        TimecodePosition::TopRight => ("w-tw-20", "20"),
+        TimecodePosition::BottomLeft => ("20", "h-th-20"),
+        TimecodePosition::BottomCenter => ("(w-tw)/2", "h-th-20"),
+        TimecodePosition::BottomRight => ("w-tw-20", "h-th-20"),
+    };
+    filter.push_str(&format!(
+        "text={}:x={}:y={}:fontsize={}:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8",
+        escape_filter_option(&text),
+        x,
+        y,
+        style.font_size
+    ));
+    filter
+}
+
+// only the values departing from "no change" are given to the eq filter
+fn make_color_filter(color: &ColorAdjust) -> Option<String> {
+    let color = color.clamped();
+    let neutral = ColorAdjust::default();
+
+    let mut options = Vec::with_capacity(4);
+    if color.brightness != neutral.brightness {
+        options.push(format!("brightness={}", color.brightness));
+    }
+    if color.contrast != neutral.contrast {
+        options.push(format!("contrast={}", color.contrast));
+    }
+    if color.saturation != neutral.saturation {
+        options.push(format!("saturation={}", color.saturation));
+    }
+    if color.gamma != neutral.gamma {
+        options.push(format!("gamma={}", color.gamma));
+    }
+
+    if options.is_empty() {
+        None
+    } else {
+        Some(format!("eq={}", options.join(":")))
+    }
+}
+
+fn push_audio_filter_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    args.push(String::from("-af")); // alias of -filter:a with ffmpeg but not with ffplay.
+    args.push(make_audio_filters(conf, state).join(","));
+}
+
+// one chain per filtered track, the others are mapped as they are. The maps
+// keep the order of the tracks, the metadata filtered ones lose is copied back.
+fn push_audio_tracks_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    let mut graph = FilterGraph::new();
+    let mut maps = Vec::new();
+    let mut filtered = Vec::new();
+    let kept_tracks = conf.audio_tracks.iter().filter(|track| track.keep);
+    for (output_index, track) in kept_tracks.enumerate() {
+        match track.filters {
+            FilterChain::Configured => {
+                let input = Label::input(0, &format!("a:{}", track.stream_index));
+                let output = graph.chain(&input, &make_audio_filters(conf, state));
+                maps.push(output.to_string());
+                filtered.push((output_index, track.stream_index));
+            }
+            FilterChain::Unfiltered => maps.push(format!("0:a:{}", track.stream_index)),
+        }
+    }
+
+    if !graph.is_empty() {
+        args.push(String::from("-filter_complex"));
+        args.push(graph.to_filter_complex());
+    }
+    for map in maps {
+        args.push(String::from("-map"));
+        args.push(map);
+    }
+    for (output_index, stream_index) in filtered {
+        args.push(format!("-map_metadata:s:a:{}", output_index));
+        args.push(format!("0:s:a:{}", stream_index));
+    }
+}
+
+// each track at its gain, summed as it is: amix would otherwise scale them
+// down by their count. The audio filters then apply to the mix.
+fn push_downmix_args(
+    conf: &Config,
+    state: &State,
+    tracks: &[(usize, f64)],
+    args: &mut Vec<String>,
+) {
+    let mut graph = FilterGraph::new();
+    let mix = graph.mix(&audio_track_labels(tracks));
+    let output = graph.chain(&mix, &make_audio_filters(conf, state));
+    graph.output(output);
+    args.extend(graph.args());
+}
+
+// streams of the first input for `FilterGraph::mix`
+fn audio_track_labels(tracks: &[(usize, f64)]) -> Vec<(Label, f64)> {
+    tracks
+        .iter()
+        .map(|&(stream_index, gain_db)| (Label::input(0, &format!("a:{}", stream_index)), gain_db))
+        .collect()
+}
+
+// the audio sped up and searched for silences, as stream indices with their
+// gain in dB: the mixed tracks, the single kept track or else the first one
+fn heard_audio_tracks(conf: &Config) -> Vec<(usize, f64)> {
+    if let Some(ref tracks) = conf.downmix_tracks {
+        return tracks.clone();
+    }
+    match conf.audio_tracks.iter().find(|track| track.keep) {
+        Some(track) => vec![(track.stream_index as usize, 0.0)],
+        None => vec![(0, 0.0)],
+    }
+}
+
+fn make_audio_filters(conf: &Config, state: &State) -> Vec<String> {
+    let mut filters = make_audio_filters_before_loudness(conf, state);
+    if let Some(target) = conf.loudness_target {
+        filters.push(make_loudness_filter(
+            target,
+            conf.loudness_true_peak,
+            conf.loudness_range,
+            state.loudness_measurement.as_ref(),
+        ));
+        // loudnorm upsamples to 192 kHz
+        filters.push(format!(
+            "aresample={}",
+            state.sample_rate.unwrap_or(LOUDNESS_DEFAULT_SAMPLE_RATE)
+        ));
+    }
+
+    filters
+}
+
+fn make_audio_filters_before_loudness(conf: &Config, state: &State) -> Vec<String> {
+    let mut filters = Vec::with_capacity(5);
+    if let Some((start_sample, end_sample)) = sample_accurate_range(conf, state) {
+        filters.push(format!(
+            "atrim=start_sample={}:end_sample={}",
+            start_sample, end_sample
+        ));
+        filters.push(String::from("asetpts=PTS-STARTPTS"));
+    }
+    if let Some(strength) = conf.vocal_removal {
+        filters.push(make_vocal_removal_filter(strength));
+    }
+    if let Some(op) = conf.channel_ops {
+        filters.push(make_channel_op_filter(op));
+    }
+    if let Some(high) = conf.high_pass_filter {
+        filters.push(format!("highpass=f={}", high));
+    }
+    if let Some(low) = conf.low_pass_filter {
+        filters.push(format!("lowpass=f={}", low));
+    }
+
+    let volume_filter = if let Some(max_volume_db) = state.max_volume_db {
+        // peak normalization
+        conf.volume_change - max_volume_db
+    } else {
+        conf.volume_change
+    };
+    filters.push(format!("volume={}dB", volume_filter));
+
+    // sox and atrim restart timestamps at the selection, ffmpeg trimming keeps them
+    let offset = if (state.already_trimed && conf.trims_input())
+        || sample_accurate_range(conf, state).is_some()
+    {
+        conf.from_time
+    } else {
+        chrono::Duration::zero()
+    };
+    if let Some(envelope) = make_volume_envelope_filter(&conf.volume_envelope, offset) {
+        filters.push(envelope);
+    }
+    if let Some(ref reverb) = conf.reverb {
+        filters.push(make_reverb_filter(reverb));
+    }
+
+    filters
+}
+
+// with a measurement, the second pass of a two-pass normalization
+fn make_loudness_filter(
+    target: f64,
+    true_peak: f64,
+    range: f64,
+    measured: Option<&LoudnessMeasurement>,
+) -> String {
+    let mut filter = format!("loudnorm=I={}:TP={}:LRA={}", target, true_peak, range);
+    if let Some(measured) = measured {
+        filter.push_str(&format!(
+            ":measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            measured.integrated,
+            measured.true_peak,
+            measured.range,
+            measured.threshold,
+            measured.offset
+        ));
+    }
+    filter
+}
+
+// parse the statistics printed by the first loudnorm pass at the end of the log:
+// `[Parsed_loudnorm_1 @ 0x55d5] ` then a JSON object with string values.
+// Silence measures `-inf`, which loudnorm cannot be given back: `None` then,
+// the second pass normalizes on its own.
+fn parse_loudness_measurement(log: &str) -> Result<Option<LoudnessMeasurement>> {
+    let json = log
+        .rfind("[Parsed_loudnorm")
+        .map(|start| &log[start..])
+        .and_then(|tail| Some(&tail[tail.find('{')?..=tail.rfind('}')?]))
+        .ok_or_else(|| {
+            String::from("Error: the loudness measurement is missing from the ffmpeg output.")
+        })?;
+    let printed: LoudnormOutput = serde_json::from_str(json)
+        .map_err(|e| format!("Could not read the loudness measurement.\nError: {}", e))?;
+
+    let parse = |value: &str| {
+        value.trim().parse::<f64>().map_err(|_| {
+            format!(
+                "Error: could not read the loudness measurement value {}.",
+                value
+            )
+        })
+    };
+    let measured = LoudnessMeasurement {
+        integrated: parse(&printed.input_i)?,
+        true_peak: parse(&printed.input_tp)?,
+        range: parse(&printed.input_lra)?,
+        threshold: parse(&printed.input_thresh)?,
+        offset: parse(&printed.target_offset)?,
+    };
+    if measured.integrated.is_finite() && measured.true_peak.is_finite() {
+        Ok(Some(measured))
+    } else {
+        Ok(None)
+    }
+}
+
+// mid/side cancellation: each side loses part of the other, at full strength
+// only the side signal is left, panned voices and bass included
+fn make_vocal_removal_filter(strength: f64) -> String {
+    format!("pan=stereo|c0=c0-{0}*c1|c1=c1-{0}*c0", strength)
+}
+
+// lists are separated by pipes, commas would end the filter
+fn make_reverb_filter(reverb: &ReverbSettings) -> String {
+    let (in_gain, out_gain, delays, decays) = reverb.parameters();
+    let join = |values: &[f64]| {
+        values
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<String>>()
+            .join("|")
+    };
+    format!(
+        "aecho=in_gain={}:out_gain={}:delays={}:decays={}",
+        in_gain,
+        out_gain,
+        join(delays),
+        join(decays)
+    )
+}
+
+// pan gains are plain factors, `c0` and `c1` being the input left and right
+fn make_channel_op_filter(op: ChannelOp) -> String {
+    match op {
+        ChannelOp::SwapLR => String::from("channelmap=map=FL-FR|FR-FL:channel_layout=stereo"),
+        ChannelOp::Balance(balance) => {
+            // the side balanced towards keeps its level, the other one fades
+            let left = 1.0 - balance.max(0.0);
+            let right = 1.0 + balance.min(0.0);
+            format!("pan=stereo|c0={}*c0|c1={}*c1", left, right)
+        }
+        ChannelOp::ExtractLeft => String::from("pan=mono|c0=FL"),
+        ChannelOp::ExtractRight => String::from("pan=mono|c0=FR"),
+        ChannelOp::MonoFromLeft => String::from("pan=stereo|c0=FL|c1=FL"),
+        ChannelOp::MonoFromRight => String::from("pan=stereo|c0=FR|c1=FR"),
+    }
+}
+
+// `volume` filter evaluated on each frame, interpolating the envelope points
+// in dB. `offset` is the input time of the timestamps origin.
+fn make_volume_envelope_filter(
+    envelope: &[(chrono::Duration, f64)],
+    offset: chrono::Duration,
+) -> Option<String> {
+    let seconds = |time: chrono::Duration| (time - offset).num_milliseconds() as f64 / 1000.0;
+    let &(first_time, first_db) = envelope.first()?;
+    let &(_, last_db) = envelope.last()?;
+
+    // built from the end: after the last point, its level holds
+    let mut expression = last_db.to_string();
+    for points in envelope.windows(2).rev() {
+        let ((from, from_db), (to, to_db)) = (points[0], points[1]);
+        // points closer than a millisecond are as close as the same time
+        let ramp = seconds(to) - seconds(from);
+        if ramp <= 0.0 {
+            // a sudden change, the next segment starts at once
+            continue;
+        }
+        let slope = (to_db - from_db) / ramp;
+        expression = format!(
+            "if(lt(t,{}),{}+({})*(t-{}),{})",
+            seconds(to),
+            from_db,
+            slope,
+            seconds(from),
+            expression
+        );
+    }
+    expression = format!(
+        "if(lt(t,{}),{},{})",
+        seconds(first_time),
+        first_db,
+        expression
+    );
+
+    Some(format!("volume='pow(10,({})/20)':eval=frame", expression))
+}
+
+/// GStreamer approximation of the audio filters for the embedded player, as a
+/// bin description. Peak normalization needs a measure pass and is left out.
+pub fn make_gst_audio_filter_description(conf: &Config) -> Option<String> {
+    let mut elements = Vec::with_capacity(3);
+    if let Some(high) = conf.high_pass_filter {
+        elements.push(format!("audiocheblimit mode=high-pass cutoff={}", high));
+    }
+    if let Some(low) = conf.low_pass_filter {
+        elements.push(format!("audiocheblimit mode=low-pass cutoff={}", low));
+    }
+    if conf.volume_change != 0.0 {
+        elements.push(format!("volume volume={}", db_to_ratio(conf.volume_change)));
+    }
+
+    if elements.is_empty() {
+        None
+    } else {
+        // the filters only take raw float samples
+        Some(format!(
+            "audioconvert ! {} ! audioconvert",
+            elements.join(" ! ")
+        ))
+    }
+}
+
+// trim points in samples when atrim replaces seeking, see `Config::sample_accurate`
+fn sample_accurate_range(conf: &Config, state: &State) -> Option<(u64, u64)> {
+    if !conf.sample_accurate
+        || conf.preview
+        || conf.raw_filter_complex.is_some()
+        || !conf.audio_tracks.is_empty()
+        || conf.downmix_tracks.is_some()
+        || conf.demux_streams
+        || conf.process_whole_file
+        || conf.silence_speedup.is_some()
+        || state.already_trimed
+        || !is_lossless_audio_output(conf)
+    {
+        return None;
+    }
+
+    let sample_rate = state.sample_rate?;
+    Some((
+        time_to_sample(conf.from_time, sample_rate),
+        time_to_sample(conf.to_time, sample_rate),
+    ))
+}
+
+// index of the sample starting at `time`, rounded to the nearest one
+fn time_to_sample(time: chrono::Duration, sample_rate: u32) -> u64 {
+    let micros = time
+        .num_microseconds()
+        .unwrap_or_else(|| time.num_milliseconds() * 1000)
+        .max(0) as u64;
+    (micros * u64::from(sample_rate) + 500_000) / 1_000_000
+}
+
+fn is_lossless_audio_output(conf: &Config) -> bool {
+    let extension = Path::new(&conf.output_file)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let audio_container = matches!(extension.as_deref(), Some("flac") | Some("wav"));
+    let lossless_codec = match conf.audio_codec.as_deref() {
+        Some(codec) => codec == "flac" || codec.starts_with("pcm_"),
+        None => audio_container,
+    };
+    lossless_codec && (conf.ignore_video || audio_container)
+}
+
+fn copies_video(conf: &Config) -> bool {
+    conf.stream_copy || conf.video_codec.as_deref() == Some("copy")
+}
+
+fn push_video_codec_args(conf: &Config, args: &mut Vec<String>) {
+    if let Some(ref video_codec) = conf.video_codec {
+        args.push(String::from("-c:v"));
+        args.push(video_codec.clone());
+    }
+    if let Some(ref preset) = conf.encoder_preset {
+        args.push(String::from("-preset"));
+        args.push(preset.clone());
+    }
+
+    if let Some(AspectPolicy::Force(num, den)) = conf.aspect_ratio {
+        args.push(String::from("-aspect"));
+        args.push(format!("{}:{}", num, den));
+    }
+}
+
+// channel count the output keeps, that of the input unless something else
+// sets it, see `Config::preserve_channels`. Only mono and stereo are pinned:
+// encoders such as libmp3lame take no more, ffmpeg downmixes the others.
+fn preserved_channels(conf: &Config, state: &State) -> Option<u32> {
+    let channels = state.audio_channels.filter(|&channels| channels <= 2)?;
+    if !conf.preserve_channels
+        || conf.ignore_audio
+        || conf.audio_encoder() == Some("copy")
+        || conf.channel_ops.is_some()
+        || conf.downmix_tracks.is_some()
+        || conf.keep_all_audio
+        || !conf.audio_tracks.is_empty()
+        || conf.raw_filter_complex.is_some()
+        || conf.extra_ffmpeg_args.iter().any(|arg| arg == "-ac")
+    {
+        return None;
+    }
+    Some(channels)
+}
+
+fn push_preserved_channels_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    if let Some(channels) = preserved_channels(conf, state) {
+        args.push(String::from("-ac"));
+        args.push(channels.to_string());
+    }
+}
+
+fn push_audio_codec_args(conf: &Config, args: &mut Vec<String>) {
+    if let Some(audio_encoder) = conf.audio_encoder() {
+        args.push(String::from("-c:a"));
+        args.push(String::from(audio_encoder));
+    }
+
+    if let Some(aac) = conf.aac_options {
+        args.push(String::from("-profile:a"));
+        args.push(String::from(aac.profile.ffmpeg_name()));
+        match aac.vbr_mode {
+            Some(mode) => {
+                args.push(String::from("-vbr"));
+                args.push(mode.to_string());
+            }
+            None => {
+                args.push(String::from("-b:a"));
+                args.push(format!("{}k", aac.bitrate));
+            }
+        }
+    }
+
+    if let Some(bitrate) = conf.audio_bitrate {
+        args.push(String::from("-b:a"));
+        args.push(format!("{}k", bitrate));
+    }
+
+    if let (Some(level), true) = (conf.flac_compression, conf.is_flac_output()) {
+        args.push(String::from("-compression_level"));
+        args.push(level.to_string());
+    }
+
+    if let Some(mp3) = conf.mp3 {
+        match mp3.quality {
+            Mp3Quality::Vbr(level) => {
+                args.push(String::from("-q:a"));
+                args.push(level.to_string());
+            }
+            Mp3Quality::Cbr(bitrate) => {
+                args.push(String::from("-b:a"));
+                args.push(format!("{}k", bitrate));
+            }
+        }
+        if !mp3.joint_stereo {
+            args.push(String::from("-joint_stereo"));
+            args.push(String::from("0"));
+        }
+    }
+}
+
+// ffmpeg rotates the decoded frames but some versions keep the tag on the output.
+// Copied frames are not rotated, the tag is then what turns them upright.
+fn push_rotation_metadata_args(conf: &Config, args: &mut Vec<String>) {
+    if conf.strip_rotation_metadata && !copies_video(conf) {
+        args.push(String::from("-metadata:s:v:0"));
+        args.push(String::from("rotate=0"));
+    }
+}
+
+// a single argument, so that paths need no quoting
+fn push_source_info_args(conf: &Config, args: &mut Vec<String>) {
+    if !conf.embed_source_info {
+        return;
+    }
+
+    let range = if conf.process_whole_file {
+        String::new()
+    } else {
+        format!(
+            " [{}-{}]",
+            duration_to_string(conf.from_time),
+            duration_to_string(conf.to_time)
+        )
+    };
+    args.push(String::from("-metadata"));
+    args.push(format!(
+        "comment=cut from {}{} by media-cutter",
+        conf.input_file, range
+    ));
+}
+
+fn push_loglevel_args(conf: &Config, args: &mut Vec<String>) {
+    if let Some(ref level) = conf.loglevel {
+        args.push(String::from("-loglevel"));
+        args.push(level.clone());
+    }
+}
+
+fn push_metadata_args(conf: &Config, args: &mut Vec<String>) {
+    for (key, value) in &conf.metadata {
+        args.push(String::from("-metadata"));
+        args.push(format!("{}={}", key, value));
+    }
+}
+
+// as an output option, it limits the encoders and not the decoder
+fn push_threads_args(conf: &Config, args: &mut Vec<String>) {
+    if let Some(threads) = conf.threads {
+        args.push(String::from("-threads"));
+        args.push(threads.to_string());
+    }
+}
+
+fn push_output_size_args(conf: &Config, args: &mut Vec<String>) {
+    if let Some(max_output_size) = conf.max_output_size {
+        args.push(String::from("-fs"));
+        args.push(max_output_size.to_string());
+    }
+}
+
+// seconds of output the process step writes, the silences sped up
+fn expected_output_duration(conf: &Config, state: &State) -> Option<f64> {
+    match state.silence_segments {
+        Some(ref segments) => Some(silence::output_duration(segments)),
+        None => progress_total(conf, state.input_duration),
+    }
+}
+
+// ffmpeg's AAC and the other usual encoders default to 128 kbps
+const DEFAULT_AUDIO_BITRATE: u32 = 128;
+// share of `target_size` left to the container and the encoder overshoot
+const TARGET_SIZE_MARGIN: f64 = 0.05;
+// below, the picture is unwatchable and the target is missed anyway
+const MIN_TARGET_VIDEO_BITRATE: f64 = 100.0;
+
+// kbps of the video making the output about `Config::target_size`
+fn target_video_bitrate(conf: &Config, state: &State) -> Option<u32> {
+    let target_size = conf.target_size?;
+    let duration = expected_output_duration(conf, state).filter(|&duration| duration > 0.0)?;
+    let audio_bitrate = if conf.ignore_audio {
+        0
+    } else {
+        match (
+            conf.audio_bitrate,
+            conf.aac_options,
+            conf.mp3.map(|mp3| mp3.quality),
+        ) {
+            (Some(bitrate), _, _) => bitrate,
+            (None, Some(aac), _) if aac.vbr_mode.is_none() => aac.bitrate,
+            (None, _, Some(Mp3Quality::Cbr(bitrate))) => bitrate,
+            _ => DEFAULT_AUDIO_BITRATE,
+        }
+    };
+    let total = target_size as f64 * 8.0 / 1000.0 * (1.0 - TARGET_SIZE_MARGIN) / duration;
+    Some((total - f64::from(audio_bitrate)).max(MIN_TARGET_VIDEO_BITRATE) as u32)
+}
+
+// the rate is also capped, single pass encoders overshoot the average otherwise
+fn push_target_size_args(conf: &Config, state: &State, args: &mut Vec<String>) {
+    if conf.ignore_video {
+        return;
+    }
+    if let Some(bitrate) = target_video_bitrate(conf, state) {
+        args.push(String::from("-b:v"));
+        args.push(format!("{}k", bitrate));
+        args.push(String::from("-maxrate"));
+        args.push(format!("{}k", bitrate));
+        args.push(String::from("-bufsize"));
+        args.push(format!("{}k", 2 * bitrate));
+    }
+}
+
+fn push_movflags_args(conf: &Config, output_file: &str, args: &mut Vec<String>) {
+    if conf.fragmented_mp4 && is_mp4_output(conf, output_file) {
+        args.push(String::from("-movflags"));
+        args.push(String::from("+frag_keyframe+empty_moov+default_base_moof"));
+    }
+}
+
+// presentation timestamps made again from the decoding ones, see
+// `Config::fix_timestamps`, and errors skipped, see `Config::tolerant_input`
+fn push_input_recovery_args(conf: &Config, args: &mut Vec<String>) {
+    if conf.tolerant_input {
+        args.push(String::from("-err_detect"));
+        args.push(String::from("ignore_err"));
+        args.push(String::from("-analyzeduration"));
+        args.push(String::from(TOLERANT_ANALYZE_DURATION));
+        args.push(String::from("-probesize"));
+        args.push(String::from(TOLERANT_PROBE_SIZE));
+    }
+    // a second -fflags would replace the first one
+    let fflags = match (conf.fix_timestamps, conf.tolerant_input) {
+        (_, true) => "+genpts+discardcorrupt",
+        (true, false) => "+genpts",
+        (false, false) => return,
+    };
+    args.push(String::from("-fflags"));
+    args.push(String::from(fflags));
+}
+
+// the output starts at zero whatever the first timestamps left by the cut
+fn push_fix_timestamps_output_args(conf: &Config, args: &mut Vec<String>) {
+    if conf.fix_timestamps {
+        args.push(String::from("-avoid_negative_ts"));
+        args.push(String::from("make_zero"));
+    }
+}
+
+// the container format, or the segment muxer writing the chunks, see
+// `Config::segment_time`. The chosen format is then the one of the chunks.
+fn push_format_args(conf: &Config, args: &mut Vec<String>) {
+    let segment_time = match conf.segment_time {
+        Some(segment_time) => segment_time,
+        None => {
+            if let Some(ref format) = conf.output_format {
+                args.push(String::from("-f"));
+                args.push(format.clone());
+            }
+            return;
+        }
+    };
+
+    args.push(String::from("-f"));
+    args.push(String::from("segment"));
+    args.push(String::from("-segment_time"));
+    args.push(duration_to_string(segment_time));
+    if let Some(ref format) = conf.output_format {
+        args.push(String::from("-segment_format"));
+        args.push(format.clone());
+    }
+}
+
+// chunks start on keyframes: encoded video gets one at each cut so that they
+// all last as long, see `push_format_args`
+fn push_segment_keyframes_args(conf: &Config, args: &mut Vec<String>) {
+    if let (Some(segment_time), false, false) =
+        (conf.segment_time, conf.ignore_video, copies_video(conf))
+    {
+        args.push(String::from("-force_key_frames"));
+        args.push(format!(
+            "expr:gte(t,n_forced*{})",
+            segment_time.num_milliseconds() as f64 / 1000.0
+        ));
+    }
+}
+
+// movflags only mean something to the MP4 and QuickTime muxers
+fn is_mp4_output(conf: &Config, output_file: &str) -> bool {
+    match conf.output_format.as_deref() {
+        Some(format) => MP4_MUXERS.contains(&format),
+        None => match Path::new(output_file).extension() {
+            Some(extension) => MP4_EXTENSIONS
+                .iter()
+                .any(|format| extension.eq_ignore_ascii_case(format)),
+            None => false,
+        },
+    }
+}
+
+// map of the audio stream ffmpeg would pick by itself, the probed default
+// one, `None` when audio is ignored. Unprobed, e.g. in a dry-run plan, the
+// first one stands for it.
+fn default_audio_map(conf: &Config, state: &State) -> Option<String> {
+    if conf.ignore_audio {
+        None
+    } else if !state.probed_streams {
+        Some(String::from("0:a:0?"))
+    } else {
+        state.audio_stream.map(|stream| format!("0:a:{}", stream))
+    }
+}
+
+// `0:v:<index>` for the chosen video stream, every video stream otherwise
+fn video_stream_spec(conf: &Config) -> String {
+    match conf.video_stream_index {
+        Some(stream) => format!("0:v:{}", stream),
+        None => String::from("0:v"),
+    }
+}
+
+// maps drop the streams ffmpeg would have kept by itself: the subtitles, and
+// the attachments such as their fonts, are mapped when the output holds them
+fn push_kept_stream_maps(conf: &Config, args: &mut Vec<String>) {
+    let extension = output_extension(conf);
+    if let Some(codec) = containers::subtitle_stream_codec(&extension) {
+        args.push(String::from("-map"));
+        args.push(String::from("0:s?"));
+        args.push(String::from("-c:s"));
+        args.push(String::from(codec));
+    }
+    if containers::supports_attachments(&extension) {
+        args.push(String::from("-map"));
+        args.push(String::from("0:t?"));
+    }
+}
+
+// `<output_stem>.<vext>` and `<output_stem>.<aext>`, extensions derived from the codecs
+fn demux_output_files(conf: &Config) -> (String, String) {
+    (
+        output_file_with_extension(conf, video_codec_extension(conf.video_codec.as_deref())),
+        output_file_with_extension(conf, audio_codec_extension(conf.audio_codec.as_deref())),
+    )
+}
+
+// replace only the last extension: `clip.v2.mp4` gives `clip.v2.<extension>`
+fn output_file_with_extension(conf: &Config, extension: &str) -> String {
+    let output_file = Path::new(&conf.output_file);
+    let stem = output_file
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    output_pattern::with_sanitized_file_name(
+        &output_file.with_file_name(format!("{}.{}", stem, extension)),
+        conf.filename_charset,
+    )
+    .to_string_lossy()
+    .into_owned()
+}
+
+fn video_codec_extension(codec: Option<&str>) -> &'static str {
+    // matroska can hold pretty much anything, including streams copied as is
+    containers::canonical_extension(codec, None).unwrap_or("mkv")
+}
+
+fn audio_codec_extension(codec: Option<&str>) -> &'static str {
+    // matroska audio can hold pretty much anything, including streams copied as is
+    containers::canonical_extension(None, codec).unwrap_or("mka")
+}
+
+// the output extension must fit the codecs: it is corrected, or refused when
+// strict. An explicit container format is trusted as is.
+fn check_output_extension(conf: &mut Config, outcome: &mut RunOutcome) -> Result<()> {
+    if conf.preview || conf.demux_streams || conf.output_format.is_some() {
+        return Ok(());
+    }
+
+    let video_codec = conf.video_codec.as_deref().filter(|_| !conf.ignore_video);
+    let audio_codec = conf.audio_codec.as_deref().filter(|_| !conf.ignore_audio);
+    let corrected =
+        match containers::corrected_output_file(&conf.output_file, video_codec, audio_codec) {
+            Some(corrected) => corrected,
+            None => return Ok(()),
+        };
+
+    let codecs: Vec<&str> = [video_codec, audio_codec]
+        .iter()
+        .flatten()
+        .copied()
+        .collect();
+    if conf.strict {
+        return Err(format!(
+            "Error: {} cannot hold {}: use the {} extension or set the container format.",
+            conf.output_file,
+            codecs.join(" and "),
+            Path::new(&corrected).extension().unwrap().to_string_lossy()
+        ));
+    }
+    outcome.warnings.push(format!(
+        "{} cannot hold {}: the output was written to {} instead.",
+        conf.output_file,
+        codecs.join(" and "),
+        corrected
+    ));
+    conf.output_file = corrected;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ImageSequence, OutputSpec, SilenceSpeedup, StabilizeSettings, TrackSpec, STDOUT_OUTPUT,
+    };
+    use chrono::Duration;
+    use std::path::PathBuf;
+
+    fn base_config() -> Config {
+        let mut conf = Config::new();
+        conf.input_file = String::from("in.mp4");
+        conf.output_file = String::from("out.mp4");
+        conf.from_time = Duration::seconds(1);
+        conf.to_time = Duration::seconds(5);
+        conf
+    }
+
+    #[test]
+    fn transient_error_classification() {
+        assert!(is_transient_error(
+            "[tcp @ 0x55d0] Connection to tcp://example.com:443 failed: Connection timed out",
+            true
+        ));
+        assert!(is_transient_error(
+            "[https @ 0x55d0] HTTP error 503 Service Unavailable\nServer returned 5XX Server Error reply",
+            true
+        ));
+        assert!(is_transient_error("Connection reset by peer", false));
+        assert!(!is_transient_error(
+            "Unrecognized option 'foo'.\nError splitting the argument list: Option not found",
+            true
+        ));
+        assert!(!is_transient_error(
+            "https://example.com/a.mp4: Server returned 404 Not Found",
+            true
+        ));
+        assert!(!is_transient_error(
+            "in.mp4: No such file or directory",
+            false
+        ));
+        assert!(!is_transient_error("", true));
+
+        // a stream cut short may come back, a truncated file does not
+        let truncated = "[mov,mp4,m4a,3gp,3g2,mj2 @ 0x55d0] stream 0, offset 0x2c3f: partial file\nin.mp4: End of file";
+        assert!(is_transient_error(truncated, true));
+        assert!(!is_transient_error(truncated, false));
+
+        let args = |input: &str| vec![String::from("-i"), String::from(input)];
+        assert!(reads_network(&args("https://example.com/a.mp4")));
+        assert!(!reads_network(&args("in.mp4")));
+        assert!(!reads_network(&[String::from(
+            "https://example.com/out.mp4"
+        )]));
+    }
+
+    #[test]
+    fn retry_delays() {
+        let base = std::time::Duration::from_millis(500);
+        assert_eq!(retry_delay(base, 0), base);
+        assert_eq!(retry_delay(base, 3), std::time::Duration::from_secs(4));
+        assert_eq!(retry_delay(base, 10), RETRY_MAX_DELAY);
+        // 2^32 does not fit
+        assert_eq!(retry_delay(base, 32), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(base, u32::MAX), RETRY_MAX_DELAY);
+    }
+
+    #[cfg(unix)]
+    fn count_attempts(stderr: &str, max_retries: u32) -> usize {
+        let mut counter_file = env::temp_dir();
+        counter_file.push(format!("media_cutter_retry_test_{}", max_retries));
+        let _ = remove_file(&counter_file);
+        let script = format!(
+            "echo attempt >> '{}'; echo '{}' >&2; exit 1",
+            counter_file.display(),
+            stderr
+        );
+
+        let command = PlannedCommand {
+            kind: StepKind::Process,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), script],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let output =
+            retry_command(&command, max_retries, std::time::Duration::from_millis(1)).unwrap();
+        assert!(!output.status.success());
+
+        let attempts = std::fs::read_to_string(&counter_file)
+            .unwrap()
+            .lines()
+            .count();
+        remove_file(&counter_file).unwrap();
+        attempts
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn progress_past_invalid_utf8() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // stands in for ffmpeg, whatever the arguments
+        let mut script = env::temp_dir();
+        script.push("media_cutter_progress_test.sh");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\n\
+             printf 'out_time_us=1000000\\nprogress=continue\\n'\n\
+             printf 'title=\\377\\376\\n'\n\
+             printf 'out_time_us=2000000\\nprogress=end\\n'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let command = PlannedCommand {
+            kind: StepKind::Process,
+            program: script.to_string_lossy().into_owned(),
+            args: vec![String::from("out.mp4")],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut tracker = ProgressTracker::new(Some(2.0));
+        let mut blocks = 0;
+        let output =
+            run_command_with_progress(&command, &mut tracker, &CancelFlag::new(), &mut |_| {
+                blocks += 1
+            })
+            .unwrap();
+        remove_file(&script).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(blocks, 2);
+        assert!(tracker.is_finished());
+        assert_eq!(tracker.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn snippet_args() {
+        let args = make_ffplay_snippet_args(
+            "in.mp4",
+            Duration::milliseconds(61_005),
+            Duration::milliseconds(300),
+        );
+        assert_eq!(
+            args,
+            vec![
+                "-nodisp",
+                "-autoexit",
+                "-loglevel",
+                "quiet",
+                "-ss",
+                "61.005",
+                "-t",
+                "0.300",
+                "in.mp4"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    fn is_alive(pid: u32) -> bool {
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn one_snippet_at_a_time() {
+        let sleep_args = [String::from("5")];
+        let mut player = SnippetPlayer::default();
+
+        player.play("sleep", &sleep_args).unwrap();
+        let first = player.child.as_ref().unwrap().id();
+        assert!(is_alive(first));
+
+        player.play("sleep", &sleep_args).unwrap();
+        let second = player.child.as_ref().unwrap().id();
+        assert!(!is_alive(first));
+        assert!(is_alive(second));
+
+        // a snippet that already ended is not an error
+        player.play("true", &[]).unwrap();
+        assert!(!is_alive(second));
+        player.play("sleep", &sleep_args).unwrap();
+        let last = player.child.as_ref().unwrap().id();
+
+        drop(player);
+        assert!(!is_alive(last));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn retries_honor_count() {
+        assert_eq!(count_attempts("Connection refused", 0), 1);
+        assert_eq!(count_attempts("Connection refused", 3), 4);
+        assert_eq!(count_attempts("Invalid argument", 2), 1);
+    }
+
+    #[test]
+    fn step_timings() {
+        let sh = |kind, script: &str| PlannedCommand {
+            kind,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), String::from(script)],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut plan = ExecutionPlan {
+            commands: vec![
+                sh(StepKind::NoiseProfile, "sleep 0.02; echo profile"),
+                sh(StepKind::NoiseReduction, "cat > /dev/null; sleep 0.01"),
+                sh(StepKind::PeakMeasure, "true"),
+                sh(StepKind::Process, "sleep 0.02"),
+            ],
+            temporary_files: Vec::new(),
+        };
+
+        let mut outcome = RunOutcome::default();
+        run_plan(
+            &mut plan,
+            &mut base_config(),
+            &mut State::default(),
+            None,
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut outcome,
+        )
+        .unwrap();
+
+        let kinds: Vec<StepKind> = outcome.timings.iter().map(|&(kind, _)| kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::NoiseProfile,
+                StepKind::NoiseReduction,
+                StepKind::PeakMeasure,
+                StepKind::Process
+            ]
+        );
+        assert!(outcome.timings[0].1 >= std::time::Duration::from_millis(20));
+        assert!(outcome.timings[1].1 >= std::time::Duration::from_millis(10));
+        assert!(outcome.timings[3].1 >= std::time::Duration::from_millis(20));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn piped_commands() {
+        let sh = |kind, script: &str| PlannedCommand {
+            kind,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), String::from(script)],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let consumer = sh(StepKind::NoiseReduction, "cat > /dev/null");
+
+        // more than a pipe holds, which would block an undrained producer
+        let chatty = sh(
+            StepKind::NoiseProfile,
+            "head -c 200000 /dev/zero >&2; echo profile",
+        );
+        assert!(run_piped_commands(&chatty, &consumer).is_ok());
+
+        let failing = sh(StepKind::NoiseProfile, "echo 'no such file' >&2; exit 2");
+        let error = run_piped_commands(&failing, &consumer).unwrap_err();
+        assert!(error.contains("non-zero status code: 2"), "{}", error);
+        assert!(error.contains("no such file"), "{}", error);
+    }
+
+    // keeps every event, those of the tests running alongside included
+    struct CapturingLogger {
+        events: Mutex<Vec<(log::Level, String)>>,
+    }
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.events
+                .lock()
+                .unwrap()
+                .push((record.level(), record.args().to_string()));
+        }
+
+        fn flush(&self) {}
+    }
+
+    lazy_static! {
+        static ref CAPTURING_LOGGER: CapturingLogger = CapturingLogger {
+            events: Mutex::new(Vec::new()),
+        };
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_events_are_logged() {
+        let _ = log::set_logger(&*CAPTURING_LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let sh = |kind, script: &str| PlannedCommand {
+            kind,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), String::from(script)],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut plan = ExecutionPlan {
+            commands: vec![
+                sh(StepKind::PeakMeasure, "true logged_measure"),
+                sh(StepKind::Process, "echo logged_failure >&2; exit 3"),
+            ],
+            temporary_files: Vec::new(),
+        };
+        let result = run_plan(
+            &mut plan,
+            &mut base_config(),
+            &mut State::default(),
+            None,
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut RunOutcome::default(),
+        );
+        assert!(result.is_err());
+
+        let events = CAPTURING_LOGGER.events.lock().unwrap();
+        let has_event = |level, starts_with: &str, contains: &str| {
+            events.iter().any(|(l, message)| {
+                *l == level && message.starts_with(starts_with) && message.contains(contains)
+            })
+        };
+        assert!(has_event(log::Level::Debug, "Running", "logged_measure"));
+        assert!(has_event(log::Level::Debug, "Running", "logged_failure"));
+        assert!(has_event(log::Level::Info, "sh finished", "exit status: 0"));
+        assert!(has_event(log::Level::Info, "PeakMeasure step took", ""));
+        assert!(has_event(log::Level::Error, "sh failed", "logged_failure"));
+    }
+
+    #[test]
+    fn first_sound_plan() {
+        let mut conf = base_config();
+        conf.trim_to_first_sound = true;
+        let planned = plan(&conf).unwrap();
+        assert_eq!(planned.commands.len(), 2);
+        assert_eq!(planned.commands[0].kind, StepKind::FirstSoundDetect);
+        assert_eq!(
+            planned.commands[0].args,
+            vec![
+                "-nostdin",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-i",
+                "in.mp4",
+                "-vn",
+                "-af",
+                "silencedetect=noise=-40dB:d=0.1",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+
+        // a preview plays the selection as is
+        conf.preview = true;
+        let planned = plan(&conf).unwrap();
+        assert_eq!(planned.commands.len(), 1);
+        assert_eq!(planned.commands[0].kind, StepKind::Process);
+    }
+
+    #[test]
+    fn first_sound_moves_the_selection() {
+        let sh = |script: &str| PlannedCommand {
+            kind: StepKind::FirstSoundDetect,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), String::from(script)],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut conf = base_config();
+        conf.trim_to_first_sound = true;
+        // the replanned process step does nothing
+        conf.ffmpeg_path = Some(PathBuf::from("true"));
+
+        let mut plan = ExecutionPlan {
+            commands: vec![sh(
+                "echo 'silence_start: 0' >&2; echo 'silence_end: 1.75 | silence_duration: 1.75' >&2",
+            )],
+            temporary_files: Vec::new(),
+        };
+        let mut outcome = RunOutcome::default();
+        run_plan(
+            &mut plan,
+            &mut conf,
+            &mut State::default(),
+            Some(4.0),
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut outcome,
+        )
+        .unwrap();
+
+        // the lead-in is kept before the first sound
+        assert_eq!(conf.from_time, Duration::milliseconds(2500));
+        assert!(!conf.trim_to_first_sound);
+        assert_eq!(plan.commands.len(), 2);
+        assert_eq!(plan.commands[1].kind, StepKind::Process);
+        assert_eq!(
+            &plan.commands[1].args[3..7],
+            &["-ss", "0:0:2.500", "-t", "0:0:2.500"]
+        );
+        assert!(outcome.warnings.is_empty());
+
+        // silent all along: the selection stays
+        let mut conf = base_config();
+        conf.trim_to_first_sound = true;
+        conf.ffmpeg_path = Some(PathBuf::from("true"));
+        let mut plan = ExecutionPlan {
+            commands: vec![sh("echo 'silence_start: 0' >&2")],
+            temporary_files: Vec::new(),
+        };
+        let mut outcome = RunOutcome::default();
+        run_plan(
+            &mut plan,
+            &mut conf,
+            &mut State::default(),
+            Some(4.0),
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut outcome,
+        )
+        .unwrap();
+        assert_eq!(conf.from_time, Duration::seconds(1));
+        assert_eq!(
+            outcome.warnings,
+            vec!["The selection is silent all along, it was not trimmed to its first sound."]
+        );
+    }
+
+    #[test]
+    fn unknown_encoder_detection() {
+        assert_eq!(
+            unknown_encoder("[out#0/webm] Unknown encoder 'libopus'\nError opening output files: Encoder not found"),
+            Some("libopus")
+        );
+        assert_eq!(unknown_encoder("Unknown decoder 'libdav1d'"), None);
+        assert_eq!(unknown_encoder("No such filter: 'vidstabdetect'"), None);
+    }
+
+    // what ffmpeg prints when built without the encoder, see `output_map_error`
+    fn unknown_encoder_error(codec: &str) -> String {
+        format!(
+            "⚠ ffmpeg exited with non-zero status code: 1\n\nError output: Unknown encoder '{}'",
+            codec
+        )
+    }
+
+    // ffmpeg lacking `missing`, returns the result and the number of attempts
+    fn run_mocked(
+        conf: &mut Config,
+        outcome: &mut RunOutcome,
+        missing: &str,
+    ) -> (Result<()>, usize) {
+        let mut attempts = 0;
+        let result = run_with_encoder_fallback(conf, outcome, |conf| {
+            attempts += 1;
+            if [&conf.video_codec, &conf.audio_codec]
+                .iter()
+                .any(|codec| codec.as_deref() == Some(missing))
+            {
+                Err(unknown_encoder_error(missing))
+            } else {
+                Ok(())
+            }
+        });
+        (result, attempts)
+    }
+
+    #[test]
+    fn encoder_fallback_retry() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.mkv");
+        conf.video_codec = Some(String::from("libx265"));
+        conf.audio_codec = Some(String::from("libopus"));
+
+        let mut outcome = RunOutcome::default();
+        let (result, attempts) = run_mocked(&mut conf, &mut outcome, "libopus");
+        assert_eq!(result, Ok(()));
+        assert_eq!(attempts, 2);
+        assert_eq!(conf.audio_codec.as_deref(), Some("libvorbis"));
+        assert_eq!(
+            outcome.substitutions,
+            vec![CodecSubstitution {
+                requested: String::from("libopus"),
+                used: String::from("libvorbis"),
+            }]
+        );
+        assert_eq!(outcome.warnings.len(), 1);
+
+        // mp4 cannot hold vorbis
+        let mut conf = base_config();
+        conf.audio_codec = Some(String::from("libopus"));
+        let mut outcome = RunOutcome::default();
+        let (result, _) = run_mocked(&mut conf, &mut outcome, "libopus");
+        assert_eq!(result, Ok(()));
+        assert_eq!(conf.audio_codec.as_deref(), Some("aac"));
+
+        // retried only once, even when the fallback is missing too
+        let mut conf = base_config();
+        conf.audio_codec = Some(String::from("libvorbis"));
+        let mut outcome = RunOutcome::default();
+        let mut attempts = 0;
+        let result = run_with_encoder_fallback(&mut conf, &mut outcome, |conf| {
+            attempts += 1;
+            Err(unknown_encoder_error(conf.audio_codec.as_deref().unwrap()))
+        });
+        assert_eq!(result, Err(unknown_encoder_error("aac")));
+        assert_eq!(attempts, 2);
+
+        // no fallback for this one
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libvpx-vp9"));
+        let mut outcome = RunOutcome::default();
+        let (result, attempts) = run_mocked(&mut conf, &mut outcome, "libvpx-vp9");
+        assert_eq!(result, Err(unknown_encoder_error("libvpx-vp9")));
+        assert_eq!(attempts, 1);
+        assert_eq!(outcome, RunOutcome::default());
+
+        // other failures are not retried
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libx265"));
+        let mut attempts = 0;
+        let result = run_with_encoder_fallback(&mut conf, &mut outcome, |_| {
+            attempts += 1;
+            Err(String::from("Invalid argument"))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn strict_codecs() {
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libx265"));
+        conf.strict_codecs = true;
+        let mut outcome = RunOutcome::default();
+        let (result, attempts) = run_mocked(&mut conf, &mut outcome, "libx265");
+        assert_eq!(result, Err(unknown_encoder_error("libx265")));
+        assert_eq!(attempts, 1);
+        assert_eq!(conf.video_codec.as_deref(), Some("libx265"));
+        assert_eq!(outcome, RunOutcome::default());
+    }
+
+    // error outputs of ffmpeg 4.1 reading a truncated MP3 and a damaged MPEG-TS
+    const TRUNCATED_MP3_ERROR: &str = "⚠ ffmpeg exited with non-zero status code: 1\n\nError output: [mp3 @ 0x5581c8a0] Header missing\nError while decoding stream #0:0: Invalid data found when processing input\n";
+    const DAMAGED_TS_ERROR: &str = "⚠ ffmpeg exited with non-zero status code: 1\n\nError output: [mpegts @ 0x55d3f2c0] Packet corrupt (stream = 0, dts = 1800)\nin.ts: corrupt input packet in stream 0\n[h264 @ 0x55d3f980] Invalid NAL unit size (1210 > 582).\n";
+
+    // ffmpeg failing on the damaged input unless told to skip the errors,
+    // returns the result and the number of attempts
+    fn run_damaged_mocked(
+        conf: &mut Config,
+        outcome: &mut RunOutcome,
+        error: &str,
+    ) -> (Result<()>, usize) {
+        let mut attempts = 0;
+        let result = run_with_tolerant_retry(conf, outcome, |conf, _| {
+            attempts += 1;
+            let args = make_ffmpeg_processing_args(conf, &State::default());
+            if args.contains(&String::from("+genpts+discardcorrupt")) {
+                Ok(())
+            } else {
+                Err(String::from(error))
+            }
+        });
+        (result, attempts)
+    }
+
+    #[test]
+    fn damaged_input_errors() {
+        assert_eq!(
+            damaged_input(TRUNCATED_MP3_ERROR),
+            Some("Error output: [mp3 @ 0x5581c8a0] Header missing")
+        );
+        assert_eq!(
+            damaged_input(DAMAGED_TS_ERROR),
+            Some("in.ts: corrupt input packet in stream 0")
+        );
+        assert_eq!(damaged_input(&unknown_encoder_error("libopus")), None);
+        assert_eq!(damaged_input("in.mp4: No such file or directory\n"), None);
+    }
+
+    #[test]
+    fn tolerant_input_retry() {
+        let mut conf = base_config();
+        let mut outcome = RunOutcome::default();
+        let (result, attempts) = run_damaged_mocked(&mut conf, &mut outcome, DAMAGED_TS_ERROR);
+        assert_eq!(result, Ok(()));
+        assert_eq!(attempts, 2);
+        assert!(conf.tolerant_input);
+        assert_eq!(
+            outcome.tolerant_retry.as_deref(),
+            Some("in.ts: corrupt input packet in stream 0")
+        );
+        assert_eq!(outcome.warnings.len(), 1);
+
+        // retried only once, even when it fails again
+        let mut conf = base_config();
+        let mut outcome = RunOutcome::default();
+        let mut attempts = 0;
+        let result: Result<()> = run_with_tolerant_retry(&mut conf, &mut outcome, |_, _| {
+            attempts += 1;
+            Err(String::from(TRUNCATED_MP3_ERROR))
+        });
+        assert_eq!(result, Err(String::from(TRUNCATED_MP3_ERROR)));
+        assert_eq!(attempts, 2);
+
+        // other failures are not retried
+        let mut conf = base_config();
+        let mut outcome = RunOutcome::default();
+        let (result, attempts) =
+            run_damaged_mocked(&mut conf, &mut outcome, &unknown_encoder_error("libopus"));
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert_eq!(outcome, RunOutcome::default());
+
+        // nor when the errors are asked for, or already skipped
+        let mut conf = base_config();
+        conf.strict = true;
+        let (result, attempts) = run_damaged_mocked(&mut conf, &mut outcome, DAMAGED_TS_ERROR);
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        let mut conf = base_config();
+        conf.tolerant_input = true;
+        let mut attempts = 0;
+        let result: Result<()> = run_with_tolerant_retry(&mut conf, &mut outcome, |_, _| {
+            attempts += 1;
+            Err(String::from(DAMAGED_TS_ERROR))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        assert_eq!(outcome, RunOutcome::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tolerant_retry_cleanup() {
+        let mut output_file = env::temp_dir();
+        output_file.push("media_cutter_tolerant_retry_test.mp4");
+        let _ = remove_file(&output_file);
+
+        // the partial output of the first attempt would stop ffmpeg without -y
+        let mut conf = base_config();
+        conf.output_file = output_file.to_string_lossy().into_owned();
+        let mut outcome = RunOutcome::default();
+        let mut attempts = 0;
+        let result = run_with_tolerant_retry(&mut conf, &mut outcome, |conf, _| {
+            attempts += 1;
+            if conf.tolerant_input {
+                assert!(!output_file.exists());
+                Ok(())
+            } else {
+                write(&output_file, "partial").unwrap();
+                Err(String::from(DAMAGED_TS_ERROR))
+            }
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(attempts, 2);
+
+        // the user's file is left alone
+        write(&output_file, "kept").unwrap();
+        let result: Result<()> =
+            run_with_tolerant_retry(&mut base_config(), &mut outcome, |_, _| {
+                Err(String::from(DAMAGED_TS_ERROR))
+            });
+        assert!(result.is_err());
+        remove_file(&output_file).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tolerant_measurement_retry() {
+        // escaped not to be found in the arguments of the error message
+        let command = PlannedCommand {
+            kind: StepKind::PeakMeasure,
+            program: String::from("sh"),
+            args: vec![
+                String::from("-c"),
+                String::from(
+                    "printf '[mpegts] Packet corrupt\\nin.ts: \\143orrupt input packet\\n' >&2; exit 1",
+                ),
+            ],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut conf = base_config();
+        let mut outcome = RunOutcome::default();
+        let output = run_measurement(&mut conf, &mut outcome, &command, |conf| {
+            assert!(conf.tolerant_input);
+            vec![
+                String::from("-c"),
+                String::from("echo 'max_volume: -3.0 dB' >&2"),
+            ]
+        })
+        .unwrap();
+        assert!(String::from_utf8_lossy(&output.stderr).contains("max_volume"));
+        assert_eq!(
+            outcome.tolerant_retry.as_deref(),
+            Some("in.ts: corrupt input packet")
+        );
+    }
+
+    #[test]
+    fn missing_encoders_are_substituted() {
+        let capabilities = Capabilities::from_listings(
+            "",
+            " ------\n V....D libx264  libx264 H.264\n A....D aac  AAC\n",
+        );
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libx265"));
+        conf.audio_codec = Some(String::from("libopus"));
+        let mut outcome = RunOutcome::default();
+        substitute_missing_encoders(&mut conf, &capabilities, &mut outcome);
+        assert_eq!(conf.video_codec.as_deref(), Some("libx264"));
+        assert_eq!(conf.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(outcome.substitutions.len(), 2);
+        assert_eq!(outcome.warnings.len(), 2);
+
+        // copies and ignored streams need no encoder
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("copy"));
+        conf.audio_codec = Some(String::from("libopus"));
+        conf.ignore_audio = true;
+        assert!(requested_encoders(&conf).is_empty());
+    }
+
+    #[test]
+    fn detect_max_volume_range() {
+        let mut conf = base_config();
+        let args = make_ffmpeg_detect_max_volume_args(&conf);
+        assert_eq!(&args[4..8], &["-ss", "0:0:1.0", "-t", "0:0:4.0"]);
+
+        conf.normalization_measure_range =
+            Some((Duration::milliseconds(2500), Duration::seconds(3)));
+        let args = make_ffmpeg_detect_max_volume_args(&conf);
+        assert_eq!(&args[4..8], &["-ss", "0:0:2.500", "-t", "0:0:0.500"]);
+
+        // the encode pass still processes the whole trim
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from("0:0:4.0")));
+    }
+
+    #[test]
+    fn fix_rotation_args() {
+        fn expected(rotate: &str) -> Vec<&str> {
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-map",
+                "0",
+                "-c",
+                "copy",
+                "-metadata:s:v",
+                rotate,
+                "out.mp4",
+            ]
+        }
+
+        let conf = base_config();
+
+        assert_eq!(
+            make_ffmpeg_fix_rotation_args(&conf, Some(0)),
+            expected("rotate=0")
+        );
+        assert_eq!(
+            make_ffmpeg_fix_rotation_args(&conf, Some(90)),
+            expected("rotate=90")
+        );
+        assert_eq!(
+            make_ffmpeg_fix_rotation_args(&conf, None),
+            expected("rotate=")
+        );
+    }
+
+    #[test]
+    fn spectrogram_args() {
+        let mut conf = base_config();
+        conf.operation = Operation::Analyze;
+        conf.output_file = String::from("clip.v2.mp4");
+
+        let args = make_ffmpeg_spectrogram_args(&conf, 1024, 512);
+        assert_eq!(
+            args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-lavfi",
+                "showspectrumpic=s=1024x512",
+                "-frames:v",
+                "1",
+                "clip.v2.png",
+            ]
+        );
+
+        let args = make_ffmpeg_spectrogram_args(&conf, 640, 480);
+        assert!(args.contains(&String::from("showspectrumpic=s=640x480")));
+
+        // derived names are sanitized, unlike the output typed in
+        conf.output_file = String::from("/clips/take 0:01:02.mp4");
+        let args = make_ffmpeg_spectrogram_args(&conf, 1024, 512);
+        assert_eq!(args.last().unwrap(), "/clips/take 0_01_02.png");
+        conf.filename_charset = output_pattern::FilenameCharset::Unix;
+        let args = make_ffmpeg_spectrogram_args(&conf, 1024, 512);
+        assert_eq!(args.last().unwrap(), "/clips/take 0:01:02.png");
+    }
+
+    #[test]
+    fn contact_sheet_filter() {
+        assert_eq!(
+            make_contact_sheet_filter(4, 3, Duration::milliseconds(2500), 320, None),
+            "select='isnan(prev_selected_t)+gt(floor(t/2.5),floor(prev_selected_t/2.5))',scale=320:-2,tile=4x3"
+        );
+        assert_eq!(
+            make_contact_sheet_filter(1, 1, Duration::seconds(60), 160, None),
+            "select='isnan(prev_selected_t)+gt(floor(t/60),floor(prev_selected_t/60))',scale=160:-2,tile=1x1"
+        );
+        assert_eq!(
+            make_contact_sheet_filter(5, 6, Duration::seconds(2), 240, Some(Duration::milliseconds(90500))),
+            "select='isnan(prev_selected_t)+gt(floor(t/2),floor(prev_selected_t/2))',scale=240:-2,\
+             drawtext=text='%{pts\\:hms\\:90.5}':x=5:y=h-th-5:fontcolor=white:box=1:boxcolor=black@0.5,\
+             tile=5x6"
+        );
+    }
+
+    #[test]
+    fn contact_sheet_args() {
+        let mut conf = base_config();
+        conf.operation = Operation::Analyze;
+        conf.output_file = String::from("clip.v2.mp4");
+        conf.contact_sheet = Some((3, 4));
+
+        // 12 frames over the 4 s selection
+        let commands = plan(&conf).unwrap().commands;
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0].args,
+            vec![
+                "-nostdin",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-i",
+                "in.mp4",
+                "-vf",
+                "select='isnan(prev_selected_t)+gt(floor(t/0.333),floor(prev_selected_t/0.333))',scale=320:-2,tile=3x4",
+                "-frames:v",
+                "1",
+                "clip.v2.jpg",
+            ]
+        );
+
+        // times of the input, the selection starting at 1 s
+        conf.contact_sheet_timestamps = true;
+        conf.contact_sheet_tile_width = Some(200);
+        let args = &plan(&conf).unwrap().commands[0].args;
+        assert!(args[8].contains(",scale=200:-2,drawtext=text='%{pts\\:hms\\:1}'"));
+        conf.contact_sheet_timestamps = false;
+        conf.contact_sheet_tile_width = None;
+
+        // with a spectrogram, each has its own picture
+        conf.spectrogram = Some((1024, 512));
+        let commands = plan(&conf).unwrap().commands;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].args.last().unwrap(), "clip.v2.png");
+        assert_eq!(commands[1].args.last().unwrap(), "clip.v2.jpg");
+
+        // the whole file is spread according to its probed duration
+        conf.process_whole_file = true;
+        let mut state = State::default();
+        assert!(make_ffmpeg_contact_sheet_args(&conf, &state, 3, 4).is_err());
+        state.input_duration = Some(Duration::minutes(2));
+        let args = make_ffmpeg_contact_sheet_args(&conf, &state, 3, 4).unwrap();
+        assert_eq!(&args[..3], &["-nostdin", "-i", "in.mp4"]);
+        assert!(args[4].contains("floor(t/10)"));
+
+        // more tiles than milliseconds: not a division by zero
+        conf.process_whole_file = false;
+        conf.to_time = conf.from_time + Duration::milliseconds(5);
+        conf.contact_sheet = Some((10, 10));
+        let args = &plan(&conf).unwrap().commands[1].args;
+        assert!(args[8].contains("floor(t/0.001)"), "{}", args[8]);
+    }
+
+    #[test]
+    fn whole_file_never_seeks() {
+        let mut conf = base_config();
+        conf.process_whole_file = true;
+        conf.peak_normalization = true;
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.2);
+        conf.stabilize = Some(StabilizeSettings::default());
+
+        let has_trim = |args: &[String]| {
+            args.iter()
+                .any(|arg| arg == "-ss" || arg == "-t" || arg == "-to" || arg == "trim")
+        };
+
+        assert!(!has_trim(
+            &make_sox_clean_noise_args(&conf, "tmp.mp4").unwrap()
+        ));
+        assert!(!has_trim(&make_ffmpeg_detect_max_volume_args(&conf)));
+        assert!(!has_trim(&make_ffmpeg_vidstab_detect_args(
+            &conf,
+            &State::default(),
+            "transforms.trf"
+        )));
+        assert!(!has_trim(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+        assert!(!has_trim(&make_ffmpeg_spectrogram_args(&conf, 1024, 512)));
+
+        conf.demux_streams = true;
+        assert!(!has_trim(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+
+        conf.preview = true;
+        assert!(!has_trim(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+
+        // an explicit measure range is still honoured
+        conf.normalization_measure_range = Some((Duration::seconds(2), Duration::seconds(3)));
+        assert!(has_trim(&make_ffmpeg_detect_max_volume_args(&conf)));
+    }
+
+    #[test]
+    fn preview_loop() {
+        let has_loop = |args: &[String]| args.windows(2).any(|pair| pair == ["-loop", "0"]);
+
+        let mut conf = base_config();
+        conf.preview = true;
+        assert!(!has_loop(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+
+        conf.preview_loop = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(has_loop(&args));
+        // the selection is still what loops
+        assert!(args.contains(&String::from("-ss")));
+
+        // only ffplay loops
+        conf.preview = false;
+        assert!(!has_loop(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+    }
+
+    #[test]
+    fn preview_full() {
+        let mut conf = base_config();
+        conf.preview = true;
+        conf.preview_full = true;
+        conf.high_pass_filter = Some(200);
+        conf.volume_change = 3.0;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            args,
+            vec!["-i", "in.mp4", "-af", "highpass=f=200,volume=3dB"]
+        );
+        assert!(!args.contains(&String::from("-ss")));
+        assert!(!args.contains(&String::from("-t")));
+
+        // sox cleans the whole file too
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.2);
+        let sox_args = make_sox_clean_noise_args(&conf, "clean.wav").unwrap();
+        assert!(!sox_args.contains(&String::from("trim")));
+
+        // ignored when exporting
+        conf.preview = false;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from("-ss")));
+        assert!(make_sox_clean_noise_args(&conf, "clean.wav")
+            .unwrap()
+            .contains(&String::from("trim")));
+    }
+
+    #[test]
+    fn sample_indices() {
+        assert_eq!(time_to_sample(Duration::zero(), 44_100), 0);
+        assert_eq!(time_to_sample(Duration::seconds(1), 44_100), 44_100);
+        assert_eq!(
+            time_to_sample(Duration::milliseconds(1_500), 48_000),
+            72_000
+        );
+        assert_eq!(time_to_sample(Duration::milliseconds(10), 44_100), 441);
+        // 1µs at 44.1kHz is 0.0441 sample, 12µs is 0.5292
+        assert_eq!(time_to_sample(Duration::microseconds(1), 44_100), 0);
+        assert_eq!(time_to_sample(Duration::microseconds(12), 44_100), 1);
+        assert_eq!(time_to_sample(Duration::seconds(-1), 44_100), 0);
+    }
+
+    #[test]
+    fn sample_accurate_trimming() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.flac");
+        conf.sample_accurate = true;
+        let mut state = State {
+            sample_rate: Some(44_100),
+            ..State::default()
+        };
+
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(!args.contains(&String::from("-ss")));
+        assert!(!args.contains(&String::from("-t")));
+        assert!(args.contains(&String::from(
+            "atrim=start_sample=44100:end_sample=220500,asetpts=PTS-STARTPTS,volume=0dB"
+        )));
+
+        // pcm codec in a video container is not audio only
+        conf.output_file = String::from("out.mkv");
+        conf.audio_codec = Some(String::from("pcm_s16le"));
+        assert_eq!(sample_accurate_range(&conf, &state), None);
+        conf.ignore_video = true;
+        assert_eq!(
+            sample_accurate_range(&conf, &state),
+            Some((44_100, 220_500))
+        );
+
+        // lossy output falls back to seeking
+        conf.output_file = String::from("out.mp3");
+        conf.audio_codec = None;
+        assert_eq!(sample_accurate_range(&conf, &state), None);
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(args.contains(&String::from("-ss")));
+
+        // unknown sample rate falls back to seeking
+        conf.output_file = String::from("out.wav");
+        state.sample_rate = None;
+        assert_eq!(sample_accurate_range(&conf, &state), None);
+    }
+
+    #[test]
+    fn volume_envelope_expression() {
+        assert_eq!(make_volume_envelope_filter(&[], Duration::zero()), None);
+        assert_eq!(
+            make_volume_envelope_filter(&[(Duration::seconds(3), -6.0)], Duration::zero()),
+            Some(String::from(
+                "volume='pow(10,(if(lt(t,3),-6,-6))/20)':eval=frame"
+            ))
+        );
+
+        // duck the middle, boost the end
+        let envelope = [
+            (Duration::seconds(2), 0.0),
+            (Duration::seconds(4), -12.0),
+            (Duration::seconds(6), -12.0),
+            (Duration::seconds(6), 0.0),
+            (Duration::seconds(8), 4.0),
+        ];
+        assert_eq!(
+            make_volume_envelope_filter(&envelope, Duration::zero()),
+            Some(String::from(
+                "volume='pow(10,(if(lt(t,2),0,if(lt(t,4),0+(-6)*(t-2),if(lt(t,6),-12+(0)*(t-4),if(lt(t,8),0+(2)*(t-6),4)))))/20)':eval=frame"
+            ))
+        );
+
+        // timestamps starting at the selection shift the points
+        assert_eq!(
+            make_volume_envelope_filter(&envelope[..2], Duration::seconds(1)),
+            Some(String::from(
+                "volume='pow(10,(if(lt(t,1),0,if(lt(t,3),0+(-6)*(t-1),-12)))/20)':eval=frame"
+            ))
+        );
+
+        // less than a millisecond apart: no ramp to divide by
+        let envelope = [
+            (Duration::seconds(2), 0.0),
+            (Duration::seconds(2) + Duration::microseconds(300), -6.0),
+        ];
+        assert_eq!(
+            make_volume_envelope_filter(&envelope, Duration::zero()),
+            Some(String::from(
+                "volume='pow(10,(if(lt(t,2),0,-6))/20)':eval=frame"
+            ))
+        );
+    }
+
+    #[test]
+    fn volume_envelope_filter_chain() {
+        let mut conf = base_config();
+        conf.volume_change = 3.0;
+        conf.volume_envelope = vec![(Duration::seconds(2), -6.0), (Duration::seconds(3), 0.0)];
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let af = args.iter().position(|arg| arg == "-af").unwrap();
+        // ffmpeg trimming keeps the input timestamps
+        assert_eq!(
+            args[af + 1],
+            "volume=3dB,volume='pow(10,(if(lt(t,2),-6,if(lt(t,3),-6+(6)*(t-2),0)))/20)':eval=frame"
+        );
+
+        // sox trimmed the input already
+        let state = State {
+            already_trimed: true,
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(args.contains(&String::from(
+            "volume=3dB,volume='pow(10,(if(lt(t,1),-6,if(lt(t,2),-6+(6)*(t-1),0)))/20)':eval=frame"
+        )));
+    }
+
+    #[test]
+    fn audio_copy_decision() {
+        let probed = |codec: &str| State {
+            audio_codec: Some(String::from(codec)),
+            ..State::default()
+        };
+        let conf = base_config();
+
+        // (probed codec, output file, copied)
+        for &(codec, output_file, copied) in &[
+            ("aac", "out.mp4", true),
+            ("aac", "out.MKV", true),
+            ("aac", "out.webm", false),
+            ("opus", "out.webm", true),
+            ("opus", "out.mp4", true),
+            ("vorbis", "out.mp4", false),
+            ("mp3", "out.avi", true),
+            ("pcm_s24le", "out.mov", true),
+            ("pcm_s24le", "out.mp4", false),
+            ("flac", "out.flac", true),
+            // unlisted codecs are not trusted
+            ("wmav2", "out.mkv", false),
+        ] {
+            assert_eq!(
+                can_copy_audio(&conf, &probed(codec), output_file),
+                copied,
+                "{} in {}",
+                codec,
+                output_file
+            );
+        }
+        assert!(!can_copy_audio(&conf, &State::default(), "out.mp4"));
+        // as `plan_with_info` and `run` learn it from the input
+        let probed_info = info(vec![stream("video", "h264"), stream("audio", "aac")]);
+        let mut state = State::default();
+        set_probed_streams(&mut state, &probed_info);
+        assert!(can_copy_audio(&conf, &state, "out.mp4"));
+        let args = &plan_with_info(&conf, &probed_info).unwrap().commands[0].args;
+        assert_eq!(option_values(args, "-c:a"), vec!["copy"]);
+        let args = &plan(&conf).unwrap().commands[0].args;
+        assert!(!option_values(args, "-c:a").contains(&String::from("copy")));
+
+        let state = probed("aac");
+        let check = |edit: &dyn Fn(&mut Config)| {
+            let mut conf = base_config();
+            edit(&mut conf);
+            can_copy_audio(&conf, &state, "out.mp4")
+        };
+        // audio filters need decoded samples
+        assert!(!check(&|conf| conf.volume_change = 3.0));
+        assert!(!check(&|conf| conf.high_pass_filter = Some(80)));
+        assert!(!check(&|conf| conf.peak_normalization = true));
+        assert!(!check(&|conf| conf.channel_ops = Some(ChannelOp::SwapLR)));
+        // chosen encoders
+        assert!(check(&|conf| conf.audio_codec = Some(String::from("aac"))));
+        assert!(!check(
+            &|conf| conf.audio_codec = Some(String::from("libopus"))
+        ));
+        assert!(!check(&|conf| {
+            conf.audio_codec = Some(String::from("aac"));
+            conf.aac_options = Some(AacOptions::default());
+        }));
+        // video settings do not matter
+        assert!(check(
+            &|conf| conf.video_codec = Some(String::from("libx265"))
+        ));
+        assert!(!check(&|conf| conf.copy_audio_when_possible = false));
+        assert!(!check(&|conf| conf.preview = true));
+        assert!(!check(&|conf| conf.keep_all_audio = true));
+    }
+
+    #[test]
+    fn audio_copy_args() {
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libx264"));
+        let state = State {
+            audio_codec: Some(String::from("aac")),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(!args.contains(&String::from("-af")));
+        assert_eq!(
+            &args[args.len() - 5..],
+            &["-c:v", "libx264", "-c:a", "copy", "out.mp4"]
+        );
+
+        conf.volume_change = -2.0;
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(args.contains(&String::from("volume=-2dB")));
+        assert!(!args.contains(&String::from("copy")));
+    }
+
+    fn speedup_config() -> Config {
+        let mut conf = base_config();
+        conf.silence_speedup = Some(SilenceSpeedup::default());
+        conf
+    }
+
+    fn chapters_config() -> Config {
+        let mut conf = base_config();
+        conf.write_chapters = Some(vec![
+            chapters::Chapter {
+                start: Duration::zero(),
+                end: Duration::seconds(3),
+                title: String::from("Intro"),
+            },
+            chapters::Chapter {
+                start: Duration::seconds(3),
+                end: Duration::seconds(10),
+                title: String::from("Talk"),
+            },
+        ]);
+        conf
+    }
+
+    #[test]
+    fn chapters_input() {
+        let conf = chapters_config();
+        let mut state = State::default();
+        let plan = make_plan(&conf, &mut state).unwrap();
+        let chapters_file = state.chapters_file.clone().unwrap();
+        let file_name = Path::new(&chapters_file).file_name().unwrap();
+        assert!(file_name.to_string_lossy().starts_with(&format!(
+            "{}{}_",
+            CHAPTERS_FILE_PREFIX,
+            std::process::id()
+        )));
+        assert_eq!(plan.temporary_files, vec![chapters_file.clone()]);
+        // another run of the process does not overwrite it
+        let mut other_state = State::default();
+        make_plan(&conf, &mut other_state).unwrap();
+        assert_ne!(other_state.chapters_file, state.chapters_file);
+        assert_eq!(
+            &plan.commands[0].args[..6],
+            &[
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-i",
+                &chapters_file,
+                "-map_chapters"
+            ]
+        );
+        assert_eq!(plan.commands[0].args[6], "1");
+
+        // after the inputs of the segments, one each
+        state.has_video = true;
+        state.silence_segments = Some(silence::plan_segments(&[(1.0, 2.0)], 4.0, 4.0));
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        let input = args.iter().rposition(|arg| arg == "in.mp4").unwrap();
+        assert_eq!(
+            &args[input + 1..input + 5],
+            &["-i", chapters_file.as_str(), "-map_chapters", "3"]
+        );
+
+        let mut preview = chapters_config();
+        preview.preview = true;
+        let mut state = State::default();
+        make_plan(&preview, &mut state).unwrap();
+        assert_eq!(state.chapters_file, None);
+    }
+
+    #[test]
+    fn chapters_unsupported_container() {
+        let mut conf = chapters_config();
+        conf.output_file = String::from("out.avi");
+        let mut state = State::default();
+        let plan = make_plan(&conf, &mut state).unwrap();
+        assert!(plan.temporary_files.is_empty());
+        assert!(!plan.commands[0]
+            .args
+            .contains(&String::from("-map_chapters")));
+
+        let mut outcome = RunOutcome::default();
+        push_process_warnings(&conf, &state, &mut outcome);
+        assert_eq!(
+            outcome.warnings,
+            vec!["Chapters are left out: avi files cannot hold them."]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn disk_full_classification() {
+        let failure = |stderr: &str| {
+            Command::new("sh")
+                .args(["-c", "echo \"$0\" >&2; exit 1", stderr])
+                .output()
+                .unwrap()
+        };
+        let args = make_ffmpeg_processing_args(&base_config(), &State::default());
+
+        let output = failure(
+            "[mp4 @ 0x55d0c8a3e2c0] Error writing trailer of out.mp4: No space left on device\n\
+             av_interleaved_write_frame(): No space left on device\n\
+             Conversion failed!",
+        );
+        assert_eq!(
+            output_map_error(&output, "ffmpeg", &args),
+            Err(String::from("Ran out of disk space while writing out.mp4."))
+        );
+        // not worth retrying
+        assert!(!is_transient_error(
+            &String::from_utf8_lossy(&output.stderr),
+            true
+        ));
+
+        let sox_args = vec![
+            String::from("in.mp4"),
+            String::from("/tmp/media_cutter_tmp/in.wav"),
+            String::from("noisered"),
+        ];
+        let output = failure(
+            "sox FAIL sox: `/tmp/media_cutter_tmp/in.wav' There is not enough space on the disk.",
+        );
+        assert_eq!(
+            output_map_error(&output, SOX_COMMAND, &sox_args),
+            Err(String::from(
+                "Ran out of disk space while writing /tmp/media_cutter_tmp/in.wav."
+            ))
+        );
+
+        let output = failure("in.mp4: Invalid data found when processing input");
+        assert!(output_map_error(&output, "ffmpeg", &args)
+            .unwrap_err()
+            .starts_with("⚠ ffmpeg exited with non-zero status code: 1"));
+    }
+
+    #[test]
+    fn partial_files_removal() {
+        let mut dir = env::temp_dir();
+        dir.push("media_cutter_partial_test");
+        create_dir_all(&dir).unwrap();
+        let mut conf = base_config();
+        conf.working_dir = Some(dir.clone());
+        conf.additional_outputs = vec![OutputSpec::new("out.opus")];
+        let temporary_file = dir.join("in.wav").to_string_lossy().into_owned();
+        let plan = ExecutionPlan {
+            temporary_files: vec![temporary_file.clone()],
+            ..ExecutionPlan::default()
+        };
+        // a file of the user's, there before the run
+        write(dir.join("out.mp4"), "previous").unwrap();
+        let existing = existing_output_files(&conf);
+        for file in &["out.opus", "in.wav", "in.mp4"] {
+            write(dir.join(file), "partial").unwrap();
+        }
+
+        remove_partial_files(&conf, &plan, &existing);
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        // the input and the previous output are left alone
+        assert_eq!(left, vec!["in.mp4", "out.mp4"]);
+
+        // unless the run overwrote the previous output
+        create_dir_all(&dir).unwrap();
+        write(dir.join("out.mp4"), "previous").unwrap();
+        let existing = existing_output_files(&conf);
+        write(dir.join("out.mp4"), "truncated output").unwrap();
+        remove_partial_files(&conf, &plan, &existing);
+        assert!(!dir.join("out.mp4").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chapters_file_contents() {
+        let mut chapters_file = env::temp_dir();
+        chapters_file.push("media_cutter_chapters_test.txt");
+        let chapters_file = chapters_file.to_string_lossy().into_owned();
+
+        // the selection starts at 1s and ends at 5s
+        write_chapters_file(&chapters_config(), &State::default(), &chapters_file).unwrap();
+        let contents = std::fs::read_to_string(&chapters_file).unwrap();
+        remove_file(&chapters_file).unwrap();
+        assert_eq!(
+            contents,
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=2000\ntitle=Intro\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=2000\nEND=4000\ntitle=Talk\n"
+        );
+    }
+
+    #[test]
+    fn silence_speedup_plan() {
+        let conf = speedup_config();
+        let plan = make_plan(&conf, &mut State::default()).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(kinds, vec![StepKind::SilenceDetect, StepKind::Process]);
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-nostdin",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-i",
+                "in.mp4",
+                "-vn",
+                "-af",
+                "silencedetect=noise=-35dB:d=0.5",
+                "-f",
+                "null",
+                "-"
+            ]
+        );
+        // segments are unknown until the detection ran
+        assert!(!plan.commands[1]
+            .args
+            .contains(&String::from("-filter_complex")));
+
+        let mut preview = speedup_config();
+        preview.preview = true;
+        let plan = make_plan(&preview, &mut State::default()).unwrap();
+        assert_eq!(plan.commands.len(), 1);
+        assert_eq!(plan.commands[0].program, FFPLAY_COMMAND);
+    }
+
+    #[test]
+    fn silence_speedup_args() {
+        let conf = speedup_config();
+        let segments = vec![
+            Segment {
+                start: 0.0,
+                end: 1.0,
+                speed: 1.0,
+            },
+            Segment {
+                start: 1.0,
+                end: 4.0,
+                speed: 4.0,
+            },
+        ];
+        let state = State {
+            has_video: true,
+            silence_segments: Some(segments.clone()),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(
+            args,
+            vec![
+                "-nostdin",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:1.0",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:2.0",
+                "-t",
+                "0:0:3.0",
+                "-i",
+                "in.mp4",
+                "-filter_complex",
+                "[0:v:0]setpts=PTS-STARTPTS[s0];[0:a:0]asetpts=PTS-STARTPTS[s1];\
+                 [1:v:0]setpts=(PTS-STARTPTS)/4[s2];\
+                 [1:a:0]asetpts=PTS-STARTPTS,atempo=2,atempo=2[s3];\
+                 [s0][s1][s2][s3]concat=n=2:v=1:a=1[s4][s5];\
+                 [s5]volume=0dB[s6]",
+                "-map",
+                "[s4]",
+                "-map",
+                "[s6]",
+                "out.mp4"
+            ]
+        );
+
+        // audio only input, second video stream ignored
+        let mut audio_only = speedup_config();
+        audio_only.video_stream_index = Some(1);
+        let state = State {
+            silence_segments: Some(segments),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&audio_only, &state);
+        let graph = &args[args
+            .iter()
+            .position(|arg| arg == "-filter_complex")
+            .unwrap()
+            + 1];
+        assert!(graph.starts_with("[0:a:0]asetpts"));
+        assert!(graph.contains("concat=n=2:v=0:a=1"));
+
+        // the mixed tracks are sped up, and searched for silences
+        audio_only.downmix_tracks = Some(vec![(0, 0.0), (2, -3.0)]);
+        let args = make_ffmpeg_processing_args(&audio_only, &state);
+        assert!(option_values(&args, "-filter_complex")[0].starts_with(
+            "[0:a:0]volume=0dB[s0];[0:a:2]volume=-3dB[s1];[s0][s1]amix=inputs=2:normalize=0[s2];"
+        ));
+        let args = make_ffmpeg_detect_silence_args(&audio_only, &state, -50.0, 1.0);
+        assert_eq!(
+            option_values(&args, "-filter_complex"),
+            vec!["[0:a:0]volume=0dB[s0];[0:a:2]volume=-3dB[s1];[s0][s1]amix=inputs=2:normalize=0[s2];\
+                  [s2]silencedetect=noise=-50dB:d=1[s3]"]
+        );
+        audio_only.downmix_tracks = None;
+
+        // the whole file: the last segment runs to its end
+        audio_only.process_whole_file = true;
+        let args = make_ffmpeg_processing_args(&audio_only, &state);
+        assert_eq!(option_values(&args, "-ss"), vec!["0:0:0.0", "0:0:1.0"]);
+        assert_eq!(option_values(&args, "-t"), vec!["0:0:1.0"]);
+
+        // nothing to speed up: the selection is processed as usual
+        let state = State {
+            has_video: true,
+            silence_segments: Some(vec![Segment {
+                start: 0.0,
+                end: 4.0,
+                speed: 1.0,
+            }]),
+            ..State::default()
+        };
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &state),
+            make_ffmpeg_processing_args(&conf, &State::default())
+        );
+    }
+
+    #[test]
+    fn silence_detection_step() {
+        let mut plan = ExecutionPlan {
+            commands: vec![PlannedCommand {
+                kind: StepKind::SilenceDetect,
+                program: String::from("sh"),
+                args: vec![
+                    String::from("-c"),
+                    String::from("echo 'silence_start: 1' >&2; echo 'silence_end: 2.5' >&2"),
+                ],
+                low_priority: false,
+                nice_level: None,
+                working_dir: None,
+            }],
+            temporary_files: Vec::new(),
+        };
+
+        let mut state = State::default();
+        run_plan(
+            &mut plan,
+            &mut speedup_config(),
+            &mut state,
+            Some(4.0),
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut RunOutcome::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            state.silence_segments,
+            Some(silence::plan_segments(&[(1.0, 2.5)], 4.0, 4.0))
+        );
+
+        // without duration the segments cannot be planned
+        let error = run_plan(
+            &mut plan,
+            &mut speedup_config(),
+            &mut State::default(),
+            None,
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut RunOutcome::default(),
+        );
+        assert!(error.unwrap_err().contains("duration is unknown"));
+    }
+
+    #[test]
+    fn channel_op_filters() {
+        for &(op, filter) in &[
+            (
+                ChannelOp::SwapLR,
+                "channelmap=map=FL-FR|FR-FL:channel_layout=stereo",
+            ),
+            (ChannelOp::Balance(0.0), "pan=stereo|c0=1*c0|c1=1*c1"),
+            (ChannelOp::Balance(-0.25), "pan=stereo|c0=1*c0|c1=0.75*c1"),
+            (ChannelOp::Balance(1.0), "pan=stereo|c0=0*c0|c1=1*c1"),
+            (ChannelOp::ExtractLeft, "pan=mono|c0=FL"),
+            (ChannelOp::ExtractRight, "pan=mono|c0=FR"),
+            (ChannelOp::MonoFromLeft, "pan=stereo|c0=FL|c1=FL"),
+            (ChannelOp::MonoFromRight, "pan=stereo|c0=FR|c1=FR"),
+        ] {
+            assert_eq!(make_channel_op_filter(op), filter, "{:?}", op);
+        }
+
+        // channels are reworked before the other filters
+        let mut conf = base_config();
+        conf.channel_ops = Some(ChannelOp::MonoFromLeft);
+        conf.high_pass_filter = Some(80);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from(
+            "pan=stereo|c0=FL|c1=FL,highpass=f=80,volume=0dB"
+        )));
+    }
+
+    #[test]
+    fn reverb_filters() {
+        assert_eq!(
+            make_reverb_filter(&ReverbSettings::SmallRoom),
+            "aecho=in_gain=0.8:out_gain=0.9:delays=20|35|50:decays=0.4|0.3|0.2"
+        );
+        assert_eq!(
+            make_reverb_filter(&ReverbSettings::Hall),
+            "aecho=in_gain=0.8:out_gain=0.8:delays=60|120|180|250:decays=0.5|0.4|0.3|0.2"
+        );
+        assert_eq!(
+            make_reverb_filter(&ReverbSettings::Custom {
+                in_gain: 0.6,
+                out_gain: 0.3,
+                delays: vec![1000.0],
+                decays: vec![0.5],
+            }),
+            "aecho=in_gain=0.6:out_gain=0.3:delays=1000:decays=0.5"
+        );
+        assert_eq!(
+            make_reverb_filter(&ReverbSettings::Custom {
+                in_gain: 1.0,
+                out_gain: 0.45,
+                delays: vec![12.5, 1500.0],
+                decays: vec![0.25, 0.125],
+            }),
+            "aecho=in_gain=1:out_gain=0.45:delays=12.5|1500:decays=0.25|0.125"
+        );
+
+        // last in the chain, after the volume, and joined by a single comma
+        let mut conf = base_config();
+        conf.high_pass_filter = Some(80);
+        conf.reverb = Some(ReverbSettings::SmallRoom);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from(
+            "highpass=f=80,volume=0dB,aecho=in_gain=0.8:out_gain=0.9:delays=20|35|50:decays=0.4|0.3|0.2"
+        )));
+    }
+
+    #[test]
+    fn loudness_filters() {
+        assert_eq!(
+            make_loudness_filter(-16.0, -1.5, 11.0, None),
+            "loudnorm=I=-16:TP=-1.5:LRA=11"
+        );
+        assert_eq!(
+            make_loudness_filter(-23.0, -2.0, 7.5, None),
+            "loudnorm=I=-23:TP=-2:LRA=7.5"
+        );
+
+        // after the other filters, at the rate of the input
+        let mut conf = base_config();
+        conf.reverb = Some(ReverbSettings::SmallRoom);
+        conf.loudness_target = Some(-14.0);
+        conf.loudness_true_peak = -1.0;
+        conf.loudness_range = 9.0;
+        let state = State {
+            sample_rate: Some(44100),
+            ..State::default()
+        };
+        let filters = make_audio_filters(&conf, &state);
+        assert_eq!(
+            &filters[filters.len() - 2..],
+            ["loudnorm=I=-14:TP=-1:LRA=9", "aresample=44100"]
+        );
+        assert_eq!(
+            make_audio_filters(&conf, &State::default()).last().unwrap(),
+            "aresample=48000"
+        );
+    }
+
+    const LOUDNORM_LOG: &str = r#"size=N/A time=00:00:04.00 bitrate=N/A speed= 180x
+video:0kB audio:1500kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: unknown
+[Parsed_loudnorm_1 @ 0x55d5c0a3c6c0] 
+{
+    "input_i" : "-27.61",
+    "input_tp" : "-4.47",
+    "input_lra" : "18.06",
+    "input_thresh" : "-39.20",
+    "output_i" : "-16.58",
+    "output_tp" : "-1.50",
+    "output_lra" : "14.78",
+    "output_thresh" : "-27.71",
+    "normalization_type" : "dynamic",
+    "target_offset" : "0.58"
+}
+"#;
+
+    #[test]
+    fn loudness_measurement_parsing() {
+        assert_eq!(
+            parse_loudness_measurement(LOUDNORM_LOG),
+            Ok(Some(LoudnessMeasurement {
+                integrated: -27.61,
+                true_peak: -4.47,
+                range: 18.06,
+                threshold: -39.2,
+                offset: 0.58,
+            }))
+        );
+
+        // a silent selection has no loudness, nor a peak
+        let silent = LOUDNORM_LOG.replace("\"-27.61\"", "\"-inf\"");
+        assert_eq!(parse_loudness_measurement(&silent), Ok(None));
+        let silent = LOUDNORM_LOG.replace("\"-4.47\"", "\"-inf\"");
+        assert_eq!(parse_loudness_measurement(&silent), Ok(None));
+        assert!(parse_loudness_measurement("[Parsed_loudnorm_1 @ 0x55d5] \n{\n").is_err());
+        assert!(parse_loudness_measurement(&LOUDNORM_LOG.replace("-4.47", "n/a")).is_err());
+        assert!(parse_loudness_measurement("Conversion failed!").is_err());
+    }
+
+    #[test]
+    fn two_pass_loudness() {
+        let measured = LoudnessMeasurement {
+            integrated: -27.61,
+            true_peak: -4.47,
+            range: 18.06,
+            threshold: -39.2,
+            offset: 0.58,
+        };
+        assert_eq!(
+            make_loudness_filter(-16.0, -1.5, 11.0, Some(&measured)),
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.58:linear=true"
+        );
+
+        let mut conf = base_config();
+        conf.high_pass_filter = Some(100);
+        conf.loudness_target = Some(-16.0);
+        conf.loudnorm_two_pass = true;
+        let plan = plan(&conf).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(kinds, vec![StepKind::LoudnessMeasure, StepKind::Process]);
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-vn",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-filter:a",
+                "highpass=f=100,volume=0dB,loudnorm=I=-16:TP=-1.5:LRA=11:print_format=json",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+
+        // the second pass is planned once the first one measured
+        let state = State {
+            loudness_measurement: Some(measured),
+            ..State::default()
+        };
+        let filters = make_audio_filters(&conf, &state);
+        assert!(filters[2].ends_with(":offset=0.58:linear=true"));
+
+        conf.preview = true;
+        let kinds: Vec<StepKind> = make_plan(&conf, &mut State::default())
+            .unwrap()
+            .commands
+            .iter()
+            .map(|command| command.kind)
+            .collect();
+        assert_eq!(kinds, vec![StepKind::Process]);
+    }
+
+    #[test]
+    fn vocal_removal_filters() {
+        assert_eq!(
+            make_vocal_removal_filter(1.0),
+            "pan=stereo|c0=c0-1*c1|c1=c1-1*c0"
+        );
+        assert_eq!(
+            make_vocal_removal_filter(0.5),
+            "pan=stereo|c0=c0-0.5*c1|c1=c1-0.5*c0"
+        );
+        assert_eq!(
+            make_vocal_removal_filter(0.05),
+            "pan=stereo|c0=c0-0.05*c1|c1=c1-0.05*c0"
+        );
+
+        // before the channel operations
+        let mut conf = base_config();
+        conf.vocal_removal = Some(0.8);
+        conf.channel_ops = Some(ChannelOp::SwapLR);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from(
+            "pan=stereo|c0=c0-0.8*c1|c1=c1-0.8*c0,channelmap=map=FL-FR|FR-FL:channel_layout=stereo,volume=0dB"
+        )));
+    }
+
+    #[test]
+    fn channel_count_check() {
+        let mut conf = base_config();
+        let mut mono = stream("audio", "pcm_s16le");
+        mono.channels = Some(1);
+        let mut stereo = mono.clone();
+        stereo.channels = Some(2);
+        let mono = info(vec![mono]);
+        let stereo = info(vec![stereo]);
+
+        assert!(check_audio_channels(&conf, &mono).is_ok());
+        conf.channel_ops = Some(ChannelOp::SwapLR);
+        assert!(check_audio_channels(&conf, &stereo).is_ok());
+        assert_eq!(
+            check_audio_channels(&conf, &mono),
+            Err(String::from(
+                "Error: channel swap needs a stereo input but the input audio is mono."
+            ))
+        );
+        conf.channel_ops = Some(ChannelOp::ExtractRight);
+        assert!(check_audio_channels(&conf, &mono).is_err());
+        conf.channel_ops = None;
+        conf.vocal_removal = Some(1.0);
+        assert!(check_audio_channels(&conf, &stereo).is_ok());
+        assert_eq!(
+            check_audio_channels(&conf, &mono),
+            Err(String::from(
+                "Error: vocal removal needs a stereo input but the input audio is mono."
+            ))
+        );
+        // unknown channel counts are left to ffmpeg
+        assert!(check_audio_channels(&conf, &info(vec![stream("audio", "aac")])).is_ok());
+    }
+
+    #[test]
+    fn missing_output_dir() {
+        let mut root = env::temp_dir();
+        root.push(format!(
+            "media_cutter_output_dir_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        let output_dir = root.join("nested").join("dir");
+
+        let mut conf = base_config();
+        conf.output_file = output_dir.join("out.mp4").to_string_lossy().into_owned();
+
+        assert_eq!(
+            prepare_output_dir(&conf),
+            Err(format!(
+                "Output directory does not exist: {}",
+                output_dir.display()
+            ))
+        );
+        assert!(!root.exists());
+
+        conf.create_output_dirs = true;
+        assert_eq!(prepare_output_dir(&conf), Ok(()));
+        assert!(output_dir.is_dir());
+
+        // existing directory
+        conf.create_output_dirs = false;
+        assert_eq!(prepare_output_dir(&conf), Ok(()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        conf.output_file = String::from("out.mp4");
+        assert_eq!(prepare_output_dir(&conf), Ok(()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_only_output_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut output_dir = env::temp_dir();
+        output_dir.push(format!(
+            "media_cutter_read_only_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir(&output_dir).unwrap();
+
+        let mut conf = base_config();
+        conf.output_file = output_dir.join("out.mp4").to_string_lossy().into_owned();
+        assert_eq!(prepare_output_dir(&conf), Ok(()));
+        // the test file is gone
+        assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 0);
+
+        std::fs::set_permissions(&output_dir, std::fs::Permissions::from_mode(0o555)).unwrap();
+        // root writes anywhere, there is nothing to check then
+        let writable = std::fs::File::create(output_dir.join("probe")).is_ok();
+        let result = prepare_output_dir(&conf);
+        std::fs::set_permissions(&output_dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+
+        if !writable {
+            assert_eq!(result, Err(String::from(OUTPUT_DIR_PERMISSION_ERROR)));
+        }
+    }
+
+    #[test]
+    fn concurrent_write_checks() {
+        let mut output_dir = env::temp_dir();
+        output_dir.push(format!(
+            "media_cutter_concurrent_write_test_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&output_dir);
+        std::fs::create_dir(&output_dir).unwrap();
+
+        // two queued jobs writing to the same directory
+        let barrier = std::sync::Barrier::new(2);
+        for _ in 0..20 {
+            let results: Vec<Result<()>> = thread::scope(|scope| {
+                let checks: Vec<_> = (0..2)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            barrier.wait();
+                            check_output_dir_writable(&output_dir)
+                        })
+                    })
+                    .collect();
+                checks
+                    .into_iter()
+                    .map(|check| check.join().unwrap())
+                    .collect()
+            });
+            assert_eq!(results, vec![Ok(()), Ok(())]);
+        }
+        assert_eq!(std::fs::read_dir(&output_dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
+
+    #[test]
+    fn plan_noise_reduction_and_normalization() {
+        let mut conf = base_config();
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.21);
+        conf.peak_normalization = true;
+        conf.volume_change = -1.0;
+
+        let plan = plan(&conf).unwrap();
+        let sox_output_file = tmp_dir_path(&Config::default())
+            .join("in.mp4")
+            .to_string_lossy()
+            .into_owned();
+
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::NoiseProfile,
+                StepKind::NoiseReduction,
+                StepKind::PeakMeasure,
+                StepKind::Process,
+            ]
+        );
+
+        assert_eq!(plan.commands[0].program, "sox");
+        assert_eq!(plan.commands[0].args, vec!["noise.wav", "-n", "noiseprof"]);
+
+        assert_eq!(plan.commands[1].program, "sox");
+        assert_eq!(
+            plan.commands[1].args,
+            vec![
+                "in.mp4",
+                sox_output_file.as_str(),
+                "trim",
+                "0:0:1.0",
+                "0:0:4.0",
+                "noisered",
+                "-",
+                "0.21",
+            ]
+        );
+
+        // the peak is measured on the original file
+        assert_eq!(plan.commands[2].program, "ffmpeg");
+        assert_eq!(
+            plan.commands[2].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-vn",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-filter:a",
+                "volumedetect",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+
+        // sox already trimmed, the gain is filled in once measured
+        assert_eq!(plan.commands[3].program, "ffmpeg");
+        assert_eq!(
+            plan.commands[3].args,
+            vec![
+                "-nostdin",
+                "-i",
+                sox_output_file.as_str(),
+                "-af",
+                "volume=-1dB",
+                "out.mp4",
+            ]
+        );
+
+        assert_eq!(plan.temporary_files, vec![sox_output_file]);
+    }
+
+    #[test]
+    fn plan_operations() {
+        let mut conf = base_config();
+        conf.preview = true;
+        let preview = plan(&conf).unwrap();
+        assert_eq!(preview.commands.len(), 1);
+        assert_eq!(preview.commands[0].program, "ffplay");
+
+        conf.preview = false;
+        conf.operation = Operation::FixRotation { set: Some(90) };
+        let fix_rotation = plan(&conf).unwrap();
+        assert_eq!(fix_rotation.commands.len(), 1);
+        assert_eq!(
+            fix_rotation.commands[0].args,
+            make_ffmpeg_fix_rotation_args(&conf, Some(90))
+        );
+        assert!(fix_rotation.temporary_files.is_empty());
+
+        // invalid configurations are not planned
+        conf.operation = Operation::Analyze;
+        assert!(plan(&conf).is_err());
+    }
+
+    #[test]
+    fn smart_cut_plan() {
+        let mut conf = base_config();
+        conf.from_time = Duration::milliseconds(1500);
+        conf.to_time = Duration::milliseconds(7250);
+        conf.operation = Operation::SmartCut;
+        conf.allow_overidde = true;
+
+        // the keyframes are only known once the input is probed
+        assert!(plan(&conf).is_err());
+
+        let mut state = State {
+            tmp_dir: PathBuf::from("/tmp/media-cutter"),
+            smart_cut: Some(smartcut::Source {
+                encoder: EncoderSettings {
+                    encoder: "libx264",
+                    width: Some(1280),
+                    height: Some(720),
+                    bit_rate: Some(2_000_000),
+                    pix_fmt: None,
+                    profile: None,
+                    level: None,
+                    time_base: None,
+                },
+                keyframes: vec![0.0, 2.0, 4.0, 6.0, 8.0],
+            }),
+            ..State::default()
+        };
+        let plan = make_plan(&conf, &mut state).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::SmartCutPiece,
+                StepKind::SmartCutPiece,
+                StepKind::SmartCutPiece,
+                StepKind::Process,
+            ]
+        );
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-y",
+                "-nostdin",
+                "-ss",
+                "1.500000",
+                "-i",
+                "in.mp4",
+                "-t",
+                "0.500000",
+                "-map",
+                "0:v:0",
+                "-an",
+                "-sn",
+                "-dn",
+                "-c:v",
+                "libx264",
+                "-b:v",
+                "2000000",
+                "-s",
+                "1280x720",
+                "/tmp/media-cutter/smartcut_0.mkv",
+            ]
+        );
+        assert_eq!(
+            plan.commands[1].args,
+            vec![
+                "-y",
+                "-nostdin",
+                "-ss",
+                "2.000000",
+                "-i",
+                "in.mp4",
+                "-t",
+                "4.000000",
+                "-map",
+                "0:v:0",
+                "-an",
+                "-sn",
+                "-dn",
+                "-c:v",
+                "copy",
+                "/tmp/media-cutter/smartcut_1.mkv",
+            ]
+        );
+        assert_eq!(
+            &plan.commands[2].args[2..8],
+            &["-ss", "6.000000", "-i", "in.mp4", "-t", "1.250000"]
+        );
+        assert_eq!(
+            plan.commands[3].args,
+            vec![
+                "-y",
+                "-f",
+                "concat",
+                "-safe",
+                "0",
+                "-i",
+                "/tmp/media-cutter/smartcut.txt",
+                "-ss",
+                "0:0:1.500",
+                "-t",
+                "0:0:5.750",
+                "-i",
+                "in.mp4",
+                "-map",
+                "0:v",
+                "-c:v",
+                "copy",
+                "-map",
+                "1:a?",
+                "out.mp4",
+            ]
+        );
+        assert_eq!(
+            plan.temporary_files,
+            vec![
+                "/tmp/media-cutter/smartcut_0.mkv",
+                "/tmp/media-cutter/smartcut_1.mkv",
+                "/tmp/media-cutter/smartcut_2.mkv",
+                "/tmp/media-cutter/smartcut.txt",
+            ]
+        );
+        assert_eq!(
+            state.smart_cut_list,
+            Some((
+                String::from("/tmp/media-cutter/smartcut.txt"),
+                String::from(
+                    "ffconcat version 1.0\nfile '/tmp/media-cutter/smartcut_0.mkv'\nfile '/tmp/media-cutter/smartcut_1.mkv'\nfile '/tmp/media-cutter/smartcut_2.mkv'\n"
+                )
+            ))
+        );
+
+        // the audio is cut along with the video
+        assert_eq!(progress_total(&conf, None), Some(5.75));
+
+        // without audio, only the joined video is mapped
+        conf.ignore_audio = true;
+        let plan = make_plan(&conf, &mut state).unwrap();
+        assert!(!plan.commands[3].args.contains(&String::from("1:a?")));
+    }
+
+    // end to end, with the installed ffmpeg: run with `cargo test -- --ignored`
+    #[test]
+    #[ignore = "needs ffmpeg and ffprobe with libx264"]
+    fn smart_cut_output_duration() {
+        let mut dir = env::temp_dir();
+        dir.push(format!("media-cutter-smartcut-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        let input_file = dir.join("in.mp4");
+        let output_file = dir.join("out.mp4");
+
+        // 25 fps, a keyframe every two seconds
+        let generated = Command::new(FFMPEG_COMMAND)
+            .args([
+                "-y",
+                "-nostdin",
+                "-v",
+                "error",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=duration=10:size=320x240:rate=25",
+                "-c:v",
+                "libx264",
+                "-g",
+                "50",
+                "-pix_fmt",
+                "yuv420p",
+            ])
+            .arg(&input_file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        assert!(
+            matches!(generated, Ok(status) if status.success()),
+            "ffmpeg could not make the input: {:?}",
+            generated
+        );
+
+        let mut conf = base_config();
+        conf.input_file = input_file.to_string_lossy().into_owned();
+        conf.output_file = output_file.to_string_lossy().into_owned();
+        conf.from_time = Duration::milliseconds(1500);
+        conf.to_time = Duration::milliseconds(7250);
+        conf.operation = Operation::SmartCut;
+        let result = run(&conf);
+        let duration = probe::MediaInfo::probe(&conf.output_file).map(|info| info.duration);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        let duration = duration.unwrap().unwrap();
+        assert!(
+            (duration - 5.75).abs() <= 0.04,
+            "{}s long, 5.75s requested",
+            duration
+        );
+    }
+
+    #[test]
+    fn encoder_threads() {
+        let mut conf = base_config();
+        conf.threads = Some(2);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(&args[len - 3..], &["-threads", "2", "out.mp4"]);
+
+        // every output of a demux is limited
+        conf.demux_streams = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(args.iter().filter(|arg| *arg == "-threads").count(), 2);
+    }
+
+    #[test]
+    fn flac_compression_args() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.flac");
+        conf.ignore_video = true;
+        conf.flac_compression = Some(8);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(&args[len - 3..], &["-compression_level", "8", "out.flac"]);
+
+        conf.audio_codec = Some(String::from("flac"));
+        conf.output_file = String::from("out.mka");
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(
+            &args[len - 5..],
+            &["-c:a", "flac", "-compression_level", "8", "out.mka"]
+        );
+
+        // other encoders have a compression level of their own
+        conf.audio_codec = Some(String::from("libopus"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-compression_level")));
+    }
+
+    #[test]
+    fn lossless_check_plan() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.flac");
+        conf.ignore_video = true;
+        conf.verify_lossless = true;
+        let plan = plan(&conf).unwrap();
+        assert_eq!(
+            plan.commands
+                .iter()
+                .map(|command| command.kind)
+                .collect::<Vec<StepKind>>(),
+            vec![
+                StepKind::Process,
+                StepKind::LosslessCheck,
+                StepKind::LosslessCheck
+            ]
+        );
+        assert_eq!(
+            plan.commands[1].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-vn",
+                "-c:a",
+                "pcm_s32le",
+                "-f",
+                "md5",
+                "-"
+            ]
+        );
+        assert_eq!(
+            plan.commands[2].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "out.flac",
+                "-vn",
+                "-c:a",
+                "pcm_s32le",
+                "-f",
+                "md5",
+                "-"
+            ]
+        );
+
+        // trimmed on the same samples as the output
+        conf.sample_accurate = true;
+        let state = State {
+            sample_rate: Some(48_000),
+            ..State::default()
+        };
+        let args = make_ffmpeg_audio_md5_args(&conf, &state, true);
+        assert_eq!(
+            &args[3..5],
+            &["-af", "atrim=start_sample=48000:end_sample=240000"]
+        );
+    }
+
+    fn run_lossless_check(input_md5: &str, output_md5: &str) -> RunOutcome {
+        let echo = |hash: &str| PlannedCommand {
+            kind: StepKind::LosslessCheck,
+            program: String::from("sh"),
+            args: vec![String::from("-c"), format!("echo MD5={}", hash)],
+            low_priority: false,
+            nice_level: None,
+            working_dir: None,
+        };
+        let mut plan = ExecutionPlan {
+            commands: vec![echo(input_md5), echo(output_md5)],
+            temporary_files: Vec::new(),
+        };
+        let mut outcome = RunOutcome::default();
+        run_plan(
+            &mut plan,
+            &mut base_config(),
+            &mut State::default(),
+            None,
+            &CancelFlag::new(),
+            &mut |_| {},
+            &mut outcome,
+        )
+        .unwrap();
+        outcome
+    }
+
+    #[test]
+    fn lossless_check_hashes() {
+        let hash = "0123456789abcdef0123456789abcdef";
+        let outcome = run_lossless_check(hash, hash);
+        let verification = outcome.lossless_verification.unwrap();
+        assert!(verification.is_lossless());
+        assert_eq!(verification.input_md5, hash);
+        assert!(outcome.warnings.is_empty());
+
+        let other = "fedcba9876543210fedcba9876543210";
+        let outcome = run_lossless_check(hash, other);
+        assert_eq!(
+            outcome.lossless_verification,
+            Some(LosslessVerification {
+                input_md5: String::from(hash),
+                output_md5: String::from(other),
+            })
+        );
+        assert_eq!(outcome.warnings.len(), 1);
+    }
+
+    #[test]
+    fn keep_all_audio_maps() {
+        let mut conf = base_config();
+        conf.keep_all_audio = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            &args[1..7],
+            &["-i", "in.mp4", "-map", "0:v?", "-map", "0:a"]
+        );
+
+        // as would the default mapping, the subtitles are kept
+        assert_eq!(option_values(&args, "-map"), vec!["0:v?", "0:a", "0:s?"]);
+        assert_eq!(option_values(&args, "-c:s"), vec!["mov_text"]);
+        conf.output_file = String::from("out.mkv");
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-map"),
+            vec!["0:v?", "0:a", "0:s?", "0:t?"]
+        );
+        assert_eq!(option_values(&args, "-c:s"), vec!["copy"]);
+        conf.output_file = String::from("out.webm");
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-map"), vec!["0:v?", "0:a"]);
+
+        conf.output_file = String::from("out.mp4");
+        conf.ignore_video = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[1..5], &["-i", "in.mp4", "-map", "0:a"]);
+        assert!(args.contains(&String::from("-vn")));
+
+        // ffplay has no stream maps
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-map")));
     }
 
-    args
+    fn option_values(args: &[String], option: &str) -> Vec<String> {
+        args.windows(2)
+            .filter(|pair| pair[0] == option)
+            .map(|pair| pair[1].clone())
+            .collect()
+    }
+
+    #[test]
+    fn preserved_channel_count() {
+        let mut conf = base_config();
+        let mono = State {
+            audio_channels: Some(1),
+            ..State::default()
+        };
+        assert_eq!(preserved_channels(&conf, &mono), Some(1));
+        let args = make_ffmpeg_processing_args(&conf, &mono);
+        assert_eq!(option_values(&args, "-ac"), vec!["1"]);
+
+        // the same with a picture for the video
+        conf.still_image = Some(PathBuf::from("cover.jpg"));
+        let args = make_ffmpeg_processing_args(&conf, &mono);
+        assert_eq!(option_values(&args, "-ac"), vec!["1"]);
+        conf.still_image = None;
+        conf.output_file = String::from("out.mp3");
+        conf.thumbnail_from_video = Some((String::from("in.mkv"), Duration::seconds(2)));
+        let args = make_ffmpeg_processing_args(&conf, &mono);
+        assert_eq!(option_values(&args, "-ac"), vec!["1"]);
+        conf.thumbnail_from_video = None;
+        conf.output_file = String::from("out.mp4");
+
+        // unknown, or more than ffmpeg's encoders all take
+        assert_eq!(preserved_channels(&conf, &State::default()), None);
+        let surround = State {
+            audio_channels: Some(6),
+            ..State::default()
+        };
+        assert_eq!(preserved_channels(&conf, &surround), None);
+
+        // explicit channel settings win
+        conf.channel_ops = Some(ChannelOp::ExtractLeft);
+        assert_eq!(preserved_channels(&conf, &mono), None);
+        conf.channel_ops = None;
+        conf.downmix_tracks = Some(vec![(0, 1.0), (1, 1.0)]);
+        assert_eq!(preserved_channels(&conf, &mono), None);
+        conf.downmix_tracks = None;
+        conf.extra_ffmpeg_args = vec![String::from("-ac"), String::from("2")];
+        assert_eq!(preserved_channels(&conf, &mono), None);
+        assert_eq!(
+            option_values(&make_ffmpeg_processing_args(&conf, &mono), "-ac"),
+            vec!["2"]
+        );
+        conf.extra_ffmpeg_args.clear();
+
+        // nothing to pin on copied audio, nor when asked not to
+        conf.audio_codec = Some(String::from("copy"));
+        assert_eq!(preserved_channels(&conf, &mono), None);
+        conf.audio_codec = None;
+        conf.preserve_channels = false;
+        assert_eq!(preserved_channels(&conf, &mono), None);
+        assert!(!make_ffmpeg_processing_args(&conf, &mono).contains(&String::from("-ac")));
+    }
+
+    #[test]
+    fn two_audio_tracks_graph() {
+        // microphone then game sound, only the microphone filtered
+        let mut conf = base_config();
+        conf.high_pass_filter = Some(200);
+        conf.audio_tracks = vec![
+            TrackSpec {
+                stream_index: 0,
+                filters: FilterChain::Configured,
+                keep: true,
+            },
+            TrackSpec {
+                stream_index: 1,
+                filters: FilterChain::Unfiltered,
+                keep: true,
+            },
+        ];
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-filter_complex"),
+            vec!["[0:a:0]highpass=f=200,volume=0dB[s0]"]
+        );
+        assert_eq!(
+            option_values(&args, "-map"),
+            vec!["0:v?", "[s0]", "0:a:1", "0:s?"]
+        );
+        assert_eq!(option_values(&args, "-map_metadata:s:a:0"), vec!["0:s:a:0"]);
+        assert!(!args.contains(&String::from("-af")));
+        assert_eq!(args.last().unwrap(), "out.mp4");
+
+        // without filtered track, the tracks are only selected
+        conf.audio_tracks[0].filters = FilterChain::Unfiltered;
+        conf.ignore_video = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-filter_complex")));
+        assert_eq!(option_values(&args, "-map"), vec!["0:a:0", "0:a:1", "0:s?"]);
+
+        // ffplay plays the default track
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-map")));
+        assert!(args.contains(&String::from("-af")));
+    }
+
+    #[test]
+    fn three_audio_tracks_graph() {
+        let track = |stream_index, filters, keep| TrackSpec {
+            stream_index,
+            filters,
+            keep,
+        };
+        let mut conf = base_config();
+        conf.volume_change = 3.0;
+        conf.audio_tracks = vec![
+            track(2, FilterChain::Configured, true),
+            track(0, FilterChain::Unfiltered, true),
+            track(3, FilterChain::Configured, false),
+            track(1, FilterChain::Configured, true),
+        ];
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-filter_complex"),
+            vec!["[0:a:2]volume=3dB[s0];[0:a:1]volume=3dB[s1]"]
+        );
+        // in the order of the list, the dropped track left out
+        assert_eq!(
+            option_values(&args, "-map"),
+            vec!["0:v?", "[s0]", "0:a:0", "[s1]", "0:s?"]
+        );
+        assert_eq!(option_values(&args, "-map_metadata:s:a:0"), vec!["0:s:a:2"]);
+        assert_eq!(option_values(&args, "-map_metadata:s:a:2"), vec!["0:s:a:1"]);
+        assert!(option_values(&args, "-map_metadata:s:a:1").is_empty());
+    }
+
+    #[test]
+    fn two_tracks_downmix_graph() {
+        let mut conf = base_config();
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, -6.0)]);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-filter_complex"),
+            vec![
+                "[0:a:0]volume=0dB[s0];[0:a:1]volume=-6dB[s1];\
+                 [s0][s1]amix=inputs=2:normalize=0[s2];[s2]volume=0dB[s3]"
+            ]
+        );
+        assert_eq!(option_values(&args, "-map"), vec!["0:v?", "[s3]", "0:s?"]);
+        assert!(!args.contains(&String::from("-af")));
+
+        // ffplay plays the default track
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-filter_complex")));
+    }
+
+    #[test]
+    fn three_tracks_downmix_graph() {
+        let mut conf = base_config();
+        conf.ignore_video = true;
+        conf.high_pass_filter = Some(80);
+        conf.downmix_tracks = Some(vec![(2, 3.5), (0, 0.0), (1, -12.0)]);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-filter_complex"),
+            vec![
+                "[0:a:2]volume=3.5dB[s0];[0:a:0]volume=0dB[s1];[0:a:1]volume=-12dB[s2];\
+                 [s0][s1][s2]amix=inputs=3:normalize=0[s3];[s3]highpass=f=80,volume=0dB[s4]"
+            ]
+        );
+        assert_eq!(option_values(&args, "-map"), vec!["[s4]", "0:s?"]);
+    }
+
+    #[test]
+    fn audio_track_count() {
+        let game = info(vec![
+            stream("video", "h264"),
+            stream("audio", "aac"),
+            stream("audio", "aac"),
+        ]);
+        let mut conf = base_config();
+        assert!(check_audio_track_count(&conf, &game).is_ok());
+
+        conf.downmix_tracks = Some(vec![(0, 0.0), (1, 0.0)]);
+        assert!(check_audio_track_count(&conf, &game).is_ok());
+        conf.downmix_tracks = Some(vec![(0, 0.0), (2, 0.0)]);
+        assert_eq!(
+            check_audio_track_count(&conf, &game),
+            Err(String::from(
+                "Error: audio track 2 does not exist, the input file has 2 audio tracks (from 0)."
+            ))
+        );
+
+        conf.downmix_tracks = None;
+        conf.audio_tracks = vec![TrackSpec {
+            stream_index: 3,
+            filters: FilterChain::Unfiltered,
+            keep: false,
+        }];
+        assert!(check_audio_track_count(&conf, &game).is_err());
+    }
+
+    #[test]
+    fn mp3_quality_args() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.mp3");
+        conf.ignore_video = true;
+        conf.audio_codec = Some(String::from("libmp3lame"));
+        conf.mp3 = Some(crate::Mp3Settings {
+            quality: Mp3Quality::Vbr(0),
+            joint_stereo: true,
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(
+            &args[len - 5..],
+            &["-c:a", "libmp3lame", "-q:a", "0", "out.mp3"]
+        );
+
+        conf.mp3 = Some(crate::Mp3Settings {
+            quality: Mp3Quality::Cbr(192),
+            joint_stereo: false,
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(
+            &args[len - 7..],
+            &[
+                "-c:a",
+                "libmp3lame",
+                "-b:a",
+                "192k",
+                "-joint_stereo",
+                "0",
+                "out.mp3"
+            ]
+        );
+        assert!(!args.contains(&String::from("-q:a")));
+    }
+
+    #[test]
+    fn aac_options_args() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.m4a");
+        conf.ignore_video = true;
+        conf.audio_codec = Some(String::from("aac"));
+        let aac_args = |conf: &Config| {
+            let args = make_ffmpeg_processing_args(conf, &State::default());
+            let start = args.iter().position(|arg| arg == "-c:a").unwrap();
+            args[start..args.len() - 1].to_vec()
+        };
+
+        for &(encoder, profile, vbr_mode, expected) in &[
+            (
+                AacEncoder::Native,
+                AacProfile::Lc,
+                None,
+                &["-c:a", "aac", "-profile:a", "aac_low", "-b:a", "96k"][..],
+            ),
+            (
+                AacEncoder::Fdk,
+                AacProfile::Lc,
+                None,
+                &["-c:a", "libfdk_aac", "-profile:a", "aac_low", "-b:a", "96k"][..],
+            ),
+            (
+                AacEncoder::Fdk,
+                AacProfile::Lc,
+                Some(5),
+                &["-c:a", "libfdk_aac", "-profile:a", "aac_low", "-vbr", "5"][..],
+            ),
+            (
+                AacEncoder::Fdk,
+                AacProfile::HeV1,
+                None,
+                &["-c:a", "libfdk_aac", "-profile:a", "aac_he", "-b:a", "96k"][..],
+            ),
+            (
+                AacEncoder::Fdk,
+                AacProfile::HeV1,
+                Some(3),
+                &["-c:a", "libfdk_aac", "-profile:a", "aac_he", "-vbr", "3"][..],
+            ),
+            (
+                AacEncoder::Fdk,
+                AacProfile::HeV2,
+                Some(1),
+                &["-c:a", "libfdk_aac", "-profile:a", "aac_he_v2", "-vbr", "1"][..],
+            ),
+        ] {
+            conf.aac_options = Some(AacOptions {
+                encoder,
+                profile,
+                vbr_mode,
+                bitrate: 96,
+            });
+            assert!(conf.validate().is_ok());
+            assert_eq!(aac_args(&conf), expected);
+        }
+
+        // the encoder of the settings is the one checked for
+        assert_eq!(requested_encoders(&conf), vec!["libfdk_aac"]);
+        conf.aac_options = None;
+        assert_eq!(aac_args(&conf), &["-c:a", "aac"]);
+    }
+
+    #[test]
+    fn missing_fdk_aac_encoder() {
+        let mut conf = base_config();
+        conf.audio_codec = Some(String::from("aac"));
+        conf.aac_options = Some(AacOptions {
+            encoder: AacEncoder::Fdk,
+            profile: AacProfile::HeV2,
+            vbr_mode: Some(2),
+            bitrate: 48,
+        });
+
+        let with_fdk = Capabilities::from_listings(
+            "",
+            " ------\n A....D aac  AAC\n A....D libfdk_aac  Fraunhofer FDK AAC\n",
+        );
+        let mut outcome = RunOutcome::default();
+        substitute_missing_encoders(&mut conf, &with_fdk, &mut outcome);
+        assert_eq!(outcome, RunOutcome::default());
+
+        // the native encoder takes over, with what it supports
+        let without_fdk = Capabilities::from_listings("", " ------\n A....D aac  AAC\n");
+        substitute_missing_encoders(&mut conf, &without_fdk, &mut outcome);
+        assert_eq!(
+            conf.aac_options,
+            Some(AacOptions {
+                encoder: AacEncoder::Native,
+                profile: AacProfile::Lc,
+                vbr_mode: None,
+                bitrate: 48,
+            })
+        );
+        assert!(conf.validate().is_ok());
+        assert_eq!(
+            outcome.substitutions,
+            vec![CodecSubstitution {
+                requested: String::from("libfdk_aac"),
+                used: String::from("aac"),
+            }]
+        );
+    }
+
+    #[test]
+    fn low_priority_wrappers() {
+        let all = |_: &str| true;
+        assert_eq!(
+            make_low_priority_wrapper("linux", all),
+            vec!["nice", "-n", "10", "ionice", "-c", "3"]
+        );
+        // ionice is Linux only
+        assert_eq!(
+            make_low_priority_wrapper("macos", all),
+            vec!["nice", "-n", "10"]
+        );
+        // a creation flag is used instead
+        assert!(make_low_priority_wrapper("windows", all).is_empty());
+
+        // missing tools are skipped
+        assert_eq!(
+            make_low_priority_wrapper("linux", |program| program != "ionice"),
+            vec!["nice", "-n", "10"]
+        );
+        assert!(make_low_priority_wrapper("linux", |_| false).is_empty());
+    }
+
+    #[test]
+    fn nice_level_wrappers() {
+        let all = |_: &str| true;
+        assert_eq!(
+            with_nice_level(Vec::new(), "linux", 15, all),
+            vec!["nice", "-n", "15"]
+        );
+        // the niceness of the low priority wrapper is replaced, ionice kept
+        let low_priority = make_low_priority_wrapper("linux", all);
+        assert_eq!(
+            with_nice_level(low_priority, "linux", -5, all),
+            vec!["nice", "-n", "-5", "ionice", "-c", "3"]
+        );
+        let ionice_only = make_low_priority_wrapper("linux", |program| program != "nice");
+        assert_eq!(
+            with_nice_level(ionice_only.clone(), "linux", 5, |program| program != "nice"),
+            ionice_only
+        );
+        // Unix only
+        assert!(with_nice_level(Vec::new(), "windows", 15, all).is_empty());
+    }
+
+    #[test]
+    fn low_priority_plan() {
+        let mut conf = base_config();
+        let command = &plan(&conf).unwrap().commands[0];
+        assert!(!command.low_priority);
+        assert_eq!(command.command_line()[0], "ffmpeg");
+
+        // every step is wrapped
+        conf.low_priority = true;
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.21);
+        conf.peak_normalization = true;
+        for command in plan(&conf).unwrap().commands {
+            assert!(command.low_priority);
+            let command_line = command.command_line();
+            let wrapper = &LOW_PRIORITY_WRAPPER[..];
+            assert_eq!(&command_line[..wrapper.len()], wrapper);
+            assert_eq!(command_line[wrapper.len()], command.program);
+            assert_eq!(&command_line[wrapper.len() + 1..], &command.args[..]);
+        }
+    }
+
+    #[test]
+    fn working_dir_plan() {
+        let mut conf = base_config();
+        let command = &plan(&conf).unwrap().commands[0];
+        assert_eq!(command.to_process(&command.args).get_current_dir(), None);
+
+        // every program starts there, previews included
+        let working_dir = env::temp_dir();
+        conf.working_dir = Some(working_dir.clone());
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.21);
+        for preview in [false, true] {
+            conf.preview = preview;
+            for command in plan(&conf).unwrap().commands {
+                assert_eq!(
+                    command.to_process(&command.args).get_current_dir(),
+                    Some(working_dir.as_path()),
+                    "{}",
+                    command.program
+                );
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn working_dir_of_child() {
+        let working_dir = env::temp_dir().canonicalize().unwrap();
+        let command = PlannedCommand {
+            kind: StepKind::Process,
+            program: String::from("pwd"),
+            args: vec![String::from("-P")],
+            low_priority: false,
+            nice_level: None,
+            working_dir: Some(working_dir.clone()),
+        };
+        let output = run_command_and_get_output(&command).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim(),
+            working_dir.to_string_lossy()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nice_level_of_child() {
+        // `nice` alone prints the niceness it runs at
+        let niceness = |nice_level| {
+            let command = PlannedCommand {
+                kind: StepKind::Process,
+                program: String::from("nice"),
+                args: Vec::new(),
+                low_priority: false,
+                nice_level,
+                working_dir: None,
+            };
+            let output = run_command_and_get_output(&command).unwrap();
+            String::from_utf8_lossy(&output.stdout)
+                .trim()
+                .parse::<i32>()
+                .unwrap()
+        };
+        let current = niceness(None);
+        assert_eq!(niceness(Some(5)), (current + 5).min(19));
+    }
+
+    #[test]
+    fn progress_args() {
+        let conf = base_config();
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let progress_args = make_ffmpeg_progress_args(&args, "pipe:1");
+        assert_eq!(&progress_args[..3], &["-progress", "pipe:1", "-nostats"]);
+        assert_eq!(&progress_args[3..], &args[..]);
+    }
+
+    #[test]
+    fn raw_filter_complex() {
+        let mut conf = base_config();
+        conf.raw_filter_complex = Some(String::from("[0:v]hflip[v];[0:a]areverse[a]"));
+        conf.extra_ffmpeg_args = ["-map", "[v]", "-map", "[a]"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        let plan = plan(&conf).unwrap();
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-filter_complex",
+                "[0:v]hflip[v];[0:a]areverse[a]",
+                "-map",
+                "[v]",
+                "-map",
+                "[a]",
+                "out.mp4",
+            ]
+        );
+
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-filter_complex")));
+    }
+
+    #[test]
+    fn image_sequence_input() {
+        let mut conf = base_config();
+        conf.input_file = String::from("frames/frame_%05d.png");
+        conf.process_whole_file = true;
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 23.976,
+            start_number: Some(120),
+        });
+        assert_eq!(
+            plan(&conf).unwrap().commands[0].args,
+            vec![
+                "-nostdin",
+                "-f",
+                "image2",
+                "-framerate",
+                "23.976",
+                "-start_number",
+                "120",
+                "-i",
+                "frames/frame_%05d.png",
+                "-an",
+                "-af",
+                "volume=0dB",
+                "out.mp4",
+            ]
+        );
+
+        // the first pass of the stabilization reads the pictures too
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 30.0,
+            start_number: None,
+        });
+        conf.stabilize = Some(StabilizeSettings::default());
+        let args = &plan(&conf).unwrap().commands[0].args;
+        assert_eq!(
+            &args[..7],
+            [
+                "-nostdin",
+                "-f",
+                "image2",
+                "-framerate",
+                "30",
+                "-i",
+                "frames/frame_%05d.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn still_image_args() {
+        let mut conf = base_config();
+        conf.input_file = String::from("track.flac");
+        conf.output_file = String::from("track.mp4");
+        conf.still_image = Some(PathBuf::from("cover.jpg"));
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-loop",
+                "1",
+                "-i",
+                "cover.jpg",
+                "-i",
+                "track.flac",
+                "-map",
+                "0:v",
+                "-map",
+                "1:a:0",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-vf",
+                "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+                "-af",
+                "volume=0dB",
+                "-c:v",
+                "libx264",
+                "-tune",
+                "stillimage",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-shortest",
+                "track.mp4",
+            ]
+        );
+
+        // a chosen encoder has no still image tuning, chapters come third
+        conf.video_codec = Some(String::from("libvpx-vp9"));
+        conf.output_file = String::from("track.webm");
+        let state = State {
+            chapters_file: Some(String::from("chapters.txt")),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(
+            &args[5..11],
+            [
+                "-i",
+                "track.flac",
+                "-i",
+                "chapters.txt",
+                "-map_chapters",
+                "2"
+            ]
+        );
+        assert_eq!(option_values(&args, "-c:v"), vec!["libvpx-vp9"]);
+        assert!(!args.contains(&String::from("-tune")));
+        assert_eq!(&args[args.len() - 2..], ["-shortest", "track.webm"]);
+
+        // ffplay plays the audio alone
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-loop")));
+    }
+
+    #[test]
+    fn still_image_output_file() {
+        let mut conf = base_config();
+        conf.still_image = Some(PathBuf::from("cover.jpg"));
+        assert_eq!(super::still_image_output_file(&conf), "out.mp4");
+
+        conf.output_file = String::from("out.mkv");
+        assert_eq!(super::still_image_output_file(&conf), "out.mkv");
+
+        // audio containers and ogg cannot hold the default encoder
+        conf.output_file = String::from("out.m4a");
+        assert_eq!(super::still_image_output_file(&conf), "out.mp4");
+        conf.output_file = String::from("out.ogg");
+        assert_eq!(super::still_image_output_file(&conf), "out.mp4");
+
+        conf.video_codec = Some(String::from("libtheora"));
+        assert_eq!(super::still_image_output_file(&conf), "out.ogg");
+    }
+
+    #[test]
+    fn thumbnail_from_video_args() {
+        let mut conf = base_config();
+        conf.input_file = String::from("episode.wav");
+        conf.output_file = String::from("episode.mp3");
+        conf.thumbnail_from_video =
+            Some((String::from("episode.mkv"), Duration::milliseconds(95_500)));
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-i",
+                "episode.wav",
+                "-ss",
+                "0:1:35.500",
+                "-i",
+                "episode.mkv",
+                "-map",
+                "0:a:0",
+                "-map",
+                "1:v:0",
+                "-af",
+                "volume=0dB",
+                "-c:v",
+                "mjpeg",
+                "-frames:v",
+                "1",
+                "-disposition:v:0",
+                "attached_pic",
+                "episode.mp3",
+            ]
+        );
+
+        // chapters come third
+        let state = State {
+            chapters_file: Some(String::from("chapters.txt")),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(
+            &args[9..15],
+            [
+                "-i",
+                "episode.mkv",
+                "-i",
+                "chapters.txt",
+                "-map_chapters",
+                "2"
+            ]
+        );
+
+        // the video codec of the settings cannot make a cover
+        conf.video_codec = Some(String::from("libx264"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-c:v"), vec!["mjpeg"]);
+        conf.video_codec = Some(String::from("png"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-c:v"), vec!["png"]);
+        conf.video_codec = None;
+
+        // ffplay plays the audio alone
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("episode.mkv")));
+    }
+
+    #[test]
+    fn thumbnail_from_video_plan() {
+        let mut conf = base_config();
+        conf.output_file = String::from("episode.m4a");
+        conf.noise_profile_file = Some(String::from("noise.wav"));
+        conf.noise_reduction_amount = Some(0.21);
+        conf.thumbnail_from_video = Some((String::from("episode.mkv"), Duration::seconds(12)));
+
+        // the frame is taken by the process step, after the noise reduction
+        let plan = plan(&conf).unwrap();
+        let kinds: Vec<StepKind> = plan.commands.iter().map(|command| command.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::NoiseProfile,
+                StepKind::NoiseReduction,
+                StepKind::Process,
+            ]
+        );
+        let sox_output_file = tmp_dir_path(&Config::default())
+            .join("in.mp4")
+            .to_string_lossy()
+            .into_owned();
+        let process = &plan.commands[2];
+        assert_eq!(process.program, "ffmpeg");
+        assert_eq!(
+            &process.args[1..11],
+            [
+                "-i",
+                sox_output_file.as_str(),
+                "-ss",
+                "0:0:12.0",
+                "-i",
+                "episode.mkv",
+                "-map",
+                "0:a:0",
+                "-map",
+                "1:v:0"
+            ]
+        );
+        assert_eq!(option_values(&process.args, "-c:v"), vec!["mjpeg"]);
+    }
+
+    #[test]
+    fn stream_copy_args() {
+        let mut conf = base_config();
+        conf.input_file = String::from("movie.mkv");
+        conf.output_file = String::from("clip.mkv");
+        conf.stream_copy = true;
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-i",
+                "movie.mkv",
+                "-map",
+                "0",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-c",
+                "copy",
+                "-copy_unknown",
+                "clip.mkv",
+            ]
+        );
+
+        // ignored kinds are excluded along with the chosen streams
+        conf.ignore_video = true;
+        conf.stream_copy_exclude = vec![String::from("d"), String::from("s:1")];
+        let state = State {
+            chapters_file: Some(String::from("chapters.txt")),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(
+            &args[3..15],
+            [
+                "-i",
+                "chapters.txt",
+                "-map_chapters",
+                "1",
+                "-map",
+                "0",
+                "-map",
+                "-0:v",
+                "-map",
+                "-0:d",
+                "-map",
+                "-0:s:1"
+            ]
+        );
+        assert!(!args.contains(&String::from("-vn")));
+
+        // ffplay plays the selection as usual
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-copy_unknown")));
+    }
+
+    #[test]
+    fn stream_copy_chapters() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.mkv");
+        conf.stream_copy = true;
+        // as probed, timed on the input
+        conf.write_chapters = Some(vec![
+            chapters::Chapter {
+                start: Duration::zero(),
+                end: Duration::milliseconds(2500),
+                title: String::from("Opening"),
+            },
+            chapters::Chapter {
+                start: Duration::milliseconds(2500),
+                end: Duration::seconds(60),
+                title: String::from("Chapter 2"),
+            },
+        ]);
+        let mut state = State::default();
+        let plan = make_plan(&conf, &mut state).unwrap();
+        let chapters_file = state.chapters_file.clone().unwrap();
+        assert!(plan.commands[0]
+            .args
+            .windows(2)
+            .any(|pair| pair == ["-map_chapters", "1"]));
+
+        // shifted back by the 1s start of the selection, cut at its 4s length
+        let mut chapters_file_path = env::temp_dir();
+        chapters_file_path.push("media_cutter_stream_copy_chapters_test.txt");
+        let chapters_file_path = chapters_file_path.to_string_lossy().into_owned();
+        write_chapters_file(&conf, &state, &chapters_file_path).unwrap();
+        let contents = std::fs::read_to_string(&chapters_file_path).unwrap();
+        remove_file(&chapters_file_path).unwrap();
+        assert!(chapters_file.contains(CHAPTERS_FILE_PREFIX));
+        assert_eq!(
+            contents,
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=1500\ntitle=Opening\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=1500\nEND=4000\ntitle=Chapter 2\n"
+        );
+    }
+
+    #[test]
+    fn copied_stream_report() {
+        let mut streams = vec![
+            stream("video", "h264"),
+            stream("audio", "aac"),
+            stream("subtitle", "subrip"),
+            stream("subtitle", "ass"),
+            stream("attachment", "ttf"),
+            stream("data", "bin_data"),
+        ];
+        for (index, stream) in streams.iter_mut().enumerate() {
+            stream.index = index as u32;
+        }
+        streams[4].codec = None;
+        let movie = info(streams);
+
+        let mut conf = base_config();
+        conf.stream_copy = true;
+        assert_eq!(
+            copied_streams(&conf, &movie),
+            vec![
+                "#0 video (h264)",
+                "#1 audio (aac)",
+                "#2 subtitle (subrip)",
+                "#3 subtitle (ass)",
+                "#4 attachment",
+                "#5 data (bin_data)",
+            ]
+        );
+
+        // indices count the streams of their kind only
+        conf.ignore_audio = true;
+        conf.stream_copy_exclude = vec![String::from("d"), String::from("s:1")];
+        assert_eq!(
+            copied_streams(&conf, &movie),
+            vec!["#0 video (h264)", "#2 subtitle (subrip)", "#4 attachment"]
+        );
+    }
+
+    #[test]
+    fn fix_timestamps_args() {
+        let mut conf = base_config();
+        conf.fix_timestamps = true;
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-fflags",
+                "+genpts",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-af",
+                "volume=0dB",
+                "-avoid_negative_ts",
+                "make_zero",
+                "out.mp4",
+            ]
+        );
+
+        // every output is muxed from zero
+        conf.demux_streams = true;
+        conf.video_codec = Some(String::from("libx264"));
+        conf.audio_codec = Some(String::from("libopus"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-fflags"), vec!["+genpts"]);
+        assert_eq!(
+            option_values(&args, "-avoid_negative_ts"),
+            vec!["make_zero", "make_zero"]
+        );
+
+        // ffplay regenerates the timestamps it plays, there is no muxer
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-fflags"), vec!["+genpts"]);
+        assert!(!args.contains(&String::from("-avoid_negative_ts")));
+
+        conf.fix_timestamps = false;
+        conf.preview = false;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-fflags")));
+        assert!(!args.contains(&String::from("-avoid_negative_ts")));
+    }
+
+    #[test]
+    fn tolerant_input_args() {
+        let mut conf = base_config();
+        conf.tolerant_input = true;
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-err_detect",
+                "ignore_err",
+                "-analyzeduration",
+                "100M",
+                "-probesize",
+                "100M",
+                "-fflags",
+                "+genpts+discardcorrupt",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-af",
+                "volume=0dB",
+                "out.mp4",
+            ]
+        );
+
+        // a single -fflags with the fixed timestamps
+        conf.fix_timestamps = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            option_values(&args, "-fflags"),
+            vec!["+genpts+discardcorrupt"]
+        );
+        assert_eq!(
+            option_values(&args, "-avoid_negative_ts"),
+            vec!["make_zero"]
+        );
+
+        // measurements read the same damaged input
+        for args in &[
+            make_ffmpeg_detect_max_volume_args(&conf),
+            make_ffmpeg_loudness_measure_args(&conf, &State::default(), -16.0),
+            make_ffmpeg_detect_silence_args(&conf, &State::default(), -50.0, 1.0),
+        ] {
+            assert_eq!(option_values(args, "-err_detect"), vec!["ignore_err"]);
+            assert_eq!(
+                option_values(args, "-fflags"),
+                vec!["+genpts+discardcorrupt"]
+            );
+        }
+    }
+
+    #[test]
+    fn segmented_output_args() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out_%03d.mp4");
+        conf.segment_time = Some(Duration::milliseconds(2500));
+        assert_eq!(
+            make_ffmpeg_processing_args(&conf, &State::default()),
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-af",
+                "volume=0dB",
+                "-force_key_frames",
+                "expr:gte(t,n_forced*2.5)",
+                "-f",
+                "segment",
+                "-segment_time",
+                "0:0:2.500",
+                "out_%03d.mp4",
+            ]
+        );
+
+        // copied video is cut on its own keyframes, the format is the chunks'
+        conf.output_file = String::from("chunks/out_%d");
+        conf.segment_time = Some(Duration::seconds(10));
+        conf.stream_copy = true;
+        conf.output_format = Some(String::from("mpegts"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-force_key_frames")));
+        assert_eq!(option_values(&args, "-f"), vec!["segment"]);
+        assert_eq!(option_values(&args, "-segment_time"), vec!["0:0:10.0"]);
+        assert_eq!(option_values(&args, "-segment_format"), vec!["mpegts"]);
+        assert_eq!(args.last().map(String::as_str), Some("chunks/out_%d"));
+    }
+
+    #[test]
+    fn two_outputs() {
+        let mut conf = base_config();
+        conf.output_file = String::from("out.wav");
+        conf.ignore_video = true;
+        conf.additional_outputs = vec![OutputSpec {
+            audio_codec: Some(String::from("libopus")),
+            bitrate: Some(96),
+            ..OutputSpec::new("out.opus")
+        }];
+        let plan = plan(&conf).unwrap();
+        assert_eq!(plan.commands.len(), 1);
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-filter_complex",
+                "[0:a:0]volume=0dB,asplit=2[s0][s1]",
+                "-map",
+                "[s0]",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "out.wav",
+                "-map",
+                "[s1]",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-c:a",
+                "libopus",
+                "-b:a",
+                "96k",
+                "out.opus",
+            ]
+        );
+
+        // a copied audio stream is not filtered, the other output alone is
+        conf.output_file = String::from("out.m4a");
+        conf.copy_audio_when_possible = true;
+        let state = State {
+            audio_codec: Some(String::from("aac")),
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(args[4], "[0:a:0]volume=0dB[s0]");
+        assert_eq!(&args[5..7], &["-map", "0:a:0"]);
+        assert!(args.contains(&String::from("copy")));
+        let opus_output = args.iter().position(|arg| arg == "out.m4a").unwrap() + 1;
+        assert_eq!(&args[opus_output..opus_output + 2], &["-map", "[s0]"]);
+        assert_eq!(output_files(&conf), vec!["out.m4a", "out.opus"]);
+    }
+
+    #[test]
+    fn three_outputs() {
+        let mut conf = base_config();
+        conf.volume_change = 3.0;
+        conf.video_codec = Some(String::from("libx264"));
+        conf.additional_outputs = vec![
+            OutputSpec {
+                audio_codec: Some(String::from("libopus")),
+                bitrate: Some(96),
+                ..OutputSpec::new("out.opus")
+            },
+            OutputSpec {
+                format: Some(String::from("wav")),
+                audio_codec: Some(String::from("pcm_s24le")),
+                ..OutputSpec::new("out.audio")
+            },
+        ];
+        let args = &plan(&conf).unwrap().commands[0].args;
+        // decoded and filtered once for all three
+        assert_eq!(
+            &args[3..9],
+            &[
+                "-filter_complex",
+                "[0:a:0]volume=3dB,asplit=3[s0][s1][s2]",
+                "-map",
+                "0:v?",
+                "-map",
+                "[s0]",
+            ]
+        );
+        let outputs: Vec<&[String]> = args
+            .split(|arg| arg == "-map")
+            .filter(|part| part[0].starts_with("[s"))
+            .collect();
+        // the subtitles are only mapped to the main output
+        assert_eq!(outputs[0], ["[s0]"]);
+        let main_output = args
+            .split(|arg| arg == "-map")
+            .find(|part| part[0] == "0:s?")
+            .unwrap();
+        assert_eq!(main_output[1..3], ["-c:s", "mov_text"]);
+        assert_eq!(main_output[7..], ["-c:v", "libx264", "out.mp4"]);
+        assert_eq!(
+            outputs[1][5..],
+            ["-c:a", "libopus", "-b:a", "96k", "out.opus"]
+        );
+        assert_eq!(
+            outputs[2][5..],
+            ["-c:a", "pcm_s24le", "-f", "wav", "out.audio"]
+        );
+
+        // previews only play the main output
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-filter_complex")));
+        assert!(!args.contains(&String::from("out.opus")));
+    }
+
+    #[test]
+    fn visualize_waves() {
+        let mut conf = base_config();
+        conf.operation = Operation::Visualize {
+            style: VisualStyle::Waves,
+            resolution: (1280, 720),
+            colors: Some([255, 128, 0]),
+        };
+        let plan = plan(&conf).unwrap();
+        assert_eq!(plan.commands.len(), 1);
+        assert_eq!(
+            plan.commands[0].args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-filter_complex",
+                "[0:a:0]volume=0dB,asplit=2[s0][s1];[s1]showwaves=s=1280x720:mode=cline:colors=0xff8000[s2]",
+                "-map",
+                "[s2]",
+                "-map",
+                "[s0]",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn visualize_spectrums() {
+        let mut conf = base_config();
+        conf.high_pass_filter = Some(200);
+        conf.operation = Operation::Visualize {
+            style: VisualStyle::Spectrum,
+            resolution: (640, 360),
+            colors: None,
+        };
+        let args = plan(&conf).unwrap().commands.remove(0).args;
+        assert_eq!(
+            args[4],
+            "[0:a:0]highpass=f=200,volume=0dB,asplit=2[s0][s1];[s1]showspectrum=s=640x360:slide=scroll[s2]"
+        );
+        assert_eq!(&args[5..9], ["-map", "[s2]", "-map", "[s0]"]);
+
+        conf.high_pass_filter = None;
+        conf.operation = Operation::Visualize {
+            style: VisualStyle::Cqt,
+            resolution: (1920, 1080),
+            colors: Some([255, 0, 51]),
+        };
+        let args = plan(&conf).unwrap().commands.remove(0).args;
+        assert_eq!(
+            args[4],
+            "[0:a:0]volume=0dB,asplit=2[s0][s1];[s1]showcqt=s=1920x1080,hue=s=0,colorchannelmixer=rr=1.000:gg=0.000:bb=0.200[s2]"
+        );
+        assert_eq!(required_filters(&conf), vec!["showcqt"]);
+    }
+
+    #[test]
+    fn stdout_output() {
+        let mut conf = base_config();
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(args.last().unwrap(), "out.mp4");
+        assert_eq!(
+            ProgressStdio::of(&args),
+            ProgressStdio {
+                progress_pipe: "pipe:1",
+                inherit_stdout: false,
+            }
+        );
+
+        conf.output_file = String::from(STDOUT_OUTPUT);
+        conf.output_format = Some(String::from("wav"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[args.len() - 3..], &["-f", "wav", "pipe:1"]);
+        // stdout carries the output, the progress goes with the messages
+        assert_eq!(
+            ProgressStdio::of(&args),
+            ProgressStdio {
+                progress_pipe: "pipe:2",
+                inherit_stdout: true,
+            }
+        );
+        assert_eq!(
+            &make_ffmpeg_progress_args(&args, "pipe:2")[..3],
+            &["-progress", "pipe:2", "-nostats"]
+        );
+    }
+
+    #[test]
+    fn progress_total_duration() {
+        let mut conf = base_config();
+        let input_duration = Some(Duration::seconds(60));
+        assert_eq!(progress_total(&conf, input_duration), Some(4.0));
+        assert_eq!(progress_total(&conf, None), Some(4.0));
+
+        conf.process_whole_file = true;
+        assert_eq!(progress_total(&conf, input_duration), Some(60.0));
+        assert_eq!(progress_total(&conf, None), None);
+
+        conf.operation = Operation::FixRotation { set: None };
+        assert_eq!(progress_total(&conf, input_duration), Some(60.0));
+        conf.operation = Operation::Analyze;
+        assert_eq!(progress_total(&conf, input_duration), None);
+    }
+
+    #[test]
+    fn output_duration_tolerance() {
+        assert_eq!(output_duration_warning("out.mp4", 4.0, 4.0), None);
+        assert_eq!(output_duration_warning("out.mp4", 4.0, 4.023), None);
+        assert_eq!(output_duration_warning("out.mp4", 4.0, 3.5), None);
+        assert_eq!(output_duration_warning("out.mp4", 4.0, 4.5), None);
+        assert_eq!(
+            output_duration_warning("out.mp4", 4.0, 5.2),
+            Some(String::from(
+                "out.mp4 lasts 5.200s instead of 4.000s: the cut may have snapped to keyframes or the input may be truncated."
+            ))
+        );
+        // truncated input
+        assert!(output_duration_warning("out.mp4", 60.0, 12.5).is_some());
+        assert!(output_duration_warning("out.mp4", 4.0, 3.49).is_some());
+    }
+
+    #[test]
+    fn subtitle_args() {
+        let mut conf = base_config();
+        conf.output_file = String::from("/clips/out.mp4");
+        assert_eq!(
+            make_ffmpeg_subtitle_args(&conf, 2),
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-map",
+                "0:s:2",
+                "-c:s",
+                "srt",
+                "/clips/out.srt",
+            ]
+        );
+
+        conf.subtitle_format = Some(String::from("vtt"));
+        assert_eq!(
+            &make_ffmpeg_subtitle_args(&conf, 2)[9..],
+            &["-c:s", "webvtt", "/clips/out.vtt"]
+        );
+        conf.subtitle_format = Some(String::from("ass"));
+        assert_eq!(
+            &make_ffmpeg_subtitle_args(&conf, 2)[9..],
+            &["-c:s", "ass", "/clips/out.ass"]
+        );
+        conf.subtitle_format = None;
+
+        // after the output, never for a preview
+        conf.extract_subtitles = Some(0);
+        let kinds = |conf: &Config| -> Vec<StepKind> {
+            plan(conf)
+                .unwrap()
+                .commands
+                .iter()
+                .map(|command| command.kind)
+                .collect()
+        };
+        assert_eq!(
+            kinds(&conf),
+            vec![StepKind::Process, StepKind::SubtitleExtract]
+        );
+        conf.preview = true;
+        assert_eq!(kinds(&conf), vec![StepKind::Process]);
+    }
+
+    fn stream(kind: &str, codec: &str) -> probe::StreamInfo {
+        probe::StreamInfo {
+            index: 0,
+            kind: String::from(kind),
+            codec: Some(String::from(codec)),
+            width: None,
+            height: None,
+            sample_aspect_ratio: None,
+            display_aspect_ratio: None,
+            sample_rate: None,
+            channels: None,
+            language: None,
+            bit_rate: None,
+            frame_rate: None,
+            pix_fmt: None,
+            profile: None,
+            level: None,
+            time_base: None,
+        }
+    }
+
+    fn info(streams: Vec<probe::StreamInfo>) -> probe::MediaInfo {
+        probe::MediaInfo {
+            container: String::from("matroska,webm"),
+            duration: Some(60.0),
+            bit_rate: None,
+            streams,
+        }
+    }
+
+    #[test]
+    fn subtitle_stream_exists() {
+        let movie = info(vec![
+            stream("video", "h264"),
+            stream("audio", "aac"),
+            stream("subtitle", "subrip"),
+            stream("subtitle", "hdmv_pgs_subtitle"),
+            stream("subtitle", "ass"),
+        ]);
+
+        let mut conf = base_config();
+        assert!(check_subtitle_stream(&conf, &info(Vec::new())).is_ok());
+
+        // counted among subtitle streams only
+        conf.extract_subtitles = Some(0);
+        assert!(check_subtitle_stream(&conf, &movie).is_ok());
+        conf.extract_subtitles = Some(2);
+        assert!(check_subtitle_stream(&conf, &movie).is_ok());
+        conf.extract_subtitles = Some(3);
+        assert!(check_subtitle_stream(&conf, &movie)
+            .unwrap_err()
+            .contains("has 3 subtitle streams"));
+        conf.extract_subtitles = Some(1);
+        assert!(check_subtitle_stream(&conf, &movie)
+            .unwrap_err()
+            .contains("pictures"));
+
+        conf.extract_subtitles = Some(0);
+        assert!(
+            check_subtitle_stream(&conf, &info(vec![stream("audio", "flac")]))
+                .unwrap_err()
+                .contains("has none")
+        );
+        // nothing is extracted for a preview
+        conf.preview = true;
+        assert!(check_subtitle_stream(&conf, &info(Vec::new())).is_ok());
+    }
+
+    #[test]
+    fn multiple_video_streams() {
+        let single = info(vec![stream("video", "h264"), stream("audio", "aac")]);
+        let angles = info(vec![
+            stream("video", "h264"),
+            stream("video", "h264"),
+            stream("audio", "aac"),
+            stream("video", "mjpeg"),
+        ]);
+
+        let mut conf = base_config();
+        assert_eq!(check_video_streams(&conf, &single), Ok(()));
+        assert_eq!(
+            check_video_streams(&conf, &angles),
+            Err(String::from(
+                "Error: the input file has 3 video streams, choose the one to keep."
+            ))
+        );
+
+        // a chosen stream must exist
+        conf.video_stream_index = Some(2);
+        assert_eq!(check_video_streams(&conf, &angles), Ok(()));
+        conf.video_stream_index = Some(3);
+        assert_eq!(
+            check_video_streams(&conf, &angles),
+            Err(String::from(
+                "Error: video stream 3 does not exist, the input file has 3 video streams (from 0)."
+            ))
+        );
+
+        // ignored video does not matter
+        conf.video_stream_index = None;
+        conf.ignore_video = true;
+        assert_eq!(check_video_streams(&conf, &angles), Ok(()));
+        // nor copied one, they are all kept
+        conf.ignore_video = false;
+        conf.stream_copy = true;
+        assert_eq!(check_video_streams(&conf, &angles), Ok(()));
+    }
+
+    #[test]
+    fn video_stream_maps() {
+        let mut conf = base_config();
+        conf.video_stream_index = Some(1);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[3..7], &["-map", "0:v:1", "-map", "0:a:0?"]);
+
+        // once probed, the audio stream ffmpeg would have kept
+        let mut stereo = stream("audio", "aac");
+        stereo.channels = Some(2);
+        let mut surround = stream("audio", "ac3");
+        surround.channels = Some(6);
+        let angles = info(vec![
+            stream("video", "h264"),
+            stream("video", "h264"),
+            stereo,
+            surround,
+        ]);
+        let mut state = State {
+            audio_stream: default_audio_stream(&angles),
+            probed_streams: true,
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(&args[3..7], &["-map", "0:v:1", "-map", "0:a:1"]);
+        // no audio to map
+        state.audio_stream = default_audio_stream(&info(vec![stream("video", "h264")]));
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(&args[3..7], &["-map", "0:v:1", "-map", "0:s?"]);
+
+        conf.keep_all_audio = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[3..7], &["-map", "0:v:1", "-map", "0:a"]);
+        conf.keep_all_audio = false;
+
+        conf.demux_streams = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[3..5], &["-map", "0:v:1"]);
+        conf.demux_streams = false;
+
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[2..4], &["-vst", "v:1"]);
+        conf.preview = false;
+
+        conf.stabilize = Some(StabilizeSettings::default());
+        let args = make_ffmpeg_vidstab_detect_args(&conf, &State::default(), "transforms.trf");
+        assert_eq!(&args[4..6], &["-map", "0:v:1"]);
+    }
+
+    #[test]
+    fn plan_output_pattern() {
+        let mut conf = base_config();
+        conf.input_file = String::from("/media/talk.wav");
+        conf.output_pattern = Some(String::from("{parent}/{stem}_{index:02}.flac"));
+        let args = &plan(&conf).unwrap().commands[0].args;
+        assert_eq!(args.last().unwrap(), "/media/talk_01.flac");
+
+        conf.output_pattern = Some(String::from("{stem}_{take}.flac"));
+        assert!(plan(&conf).is_err());
+    }
+
+    #[test]
+    fn timecode_filter() {
+        let style = TimecodeStyle::default();
+        let zero = Duration::zero();
+        assert_eq!(
+            make_timecode_filter(&style, None, zero),
+            r"drawtext=text=%{pts\\:hms}:x=(w-tw)/2:y=h-th-20:fontsize=32:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8"
+        );
+        assert_eq!(
+            make_timecode_filter(&style, Some("/usr/share/fonts/TTF/DejaVuSans.ttf"), zero),
+            r"drawtext=fontfile=/usr/share/fonts/TTF/DejaVuSans.ttf:text=%{pts\\:hms}:x=(w-tw)/2:y=h-th-20:fontsize=32:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8"
+        );
+        assert!(
+            make_timecode_filter(&style, Some(r"C:\Windows\Fonts\arial.ttf"), zero)
+                .starts_with(r"drawtext=fontfile=C\\:\\\\Windows\\\\Fonts\\\\arial.ttf:text=")
+        );
+        let style = TimecodeStyle {
+            position: TimecodePosition::TopRight,
+            font_size: 48,
+            ..style
+        };
+        assert_eq!(
+            make_timecode_filter(&style, None, Duration::milliseconds(-90500)),
+            r"drawtext=text=%{pts\\:hms\\:-90.5}:x=w-tw-20:y=20:fontsize=48:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8"
+        );
+
+        // last of the chain, after the aspect correction
+        let mut conf = base_config();
+        conf.burn_timecode = Some(TimecodeStyle::default());
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        let filters = make_video_filters(&conf, &State::default());
+        assert_eq!(filters[..2], ["scale=trunc(iw*sar/2)*2:ih", "setsar=1"]);
+        assert!(filters[2].starts_with("drawtext="));
+        assert_eq!(required_filters(&conf), vec!["drawtext"]);
+        conf.ignore_video = true;
+        assert!(!make_ffmpeg_processing_args(&conf, &State::default())
+            .iter()
+            .any(|arg| arg.contains("drawtext")));
+    }
+
+    #[test]
+    fn timecode_sources() {
+        let mut conf = base_config();
+        let mut state = State::default();
+        let absolute = TimecodeSource::SourceAbsolute;
+        let relative = TimecodeSource::SelectionRelative;
+
+        // the output is trimmed, frames keep the times of the input
+        assert_eq!(timecode_offset(&conf, &state, absolute), Duration::zero());
+        assert_eq!(
+            timecode_offset(&conf, &state, relative),
+            Duration::seconds(-1)
+        );
+
+        // the input is seeked, frames start at 0
+        state.silence_segments = Some(vec![
+            Segment {
+                start: 0.0,
+                end: 2.0,
+                speed: 1.0,
+            },
+            Segment {
+                start: 2.0,
+                end: 4.0,
+                speed: 4.0,
+            },
+        ]);
+        assert_eq!(
+            timecode_offset(&conf, &state, absolute),
+            Duration::seconds(1)
+        );
+        assert_eq!(timecode_offset(&conf, &state, relative), Duration::zero());
+
+        conf.process_whole_file = true;
+        assert_eq!(timecode_offset(&conf, &state, absolute), Duration::zero());
+        assert_eq!(timecode_offset(&conf, &state, relative), Duration::zero());
+
+        conf.process_whole_file = false;
+        conf.burn_timecode = Some(TimecodeStyle {
+            source: relative,
+            ..TimecodeStyle::default()
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args
+            .iter()
+            .any(|arg| arg.contains(r"text=%{pts\\:hms\\:-1}")));
+    }
+
+    #[test]
+    fn escape_filter_option_values() {
+        assert_eq!(
+            escape_filter_option("/tmp/media_cutter_tmp/transforms.trf"),
+            "/tmp/media_cutter_tmp/transforms.trf"
+        );
+        assert_eq!(
+            escape_filter_option(r"C:\tmp\transforms.trf"),
+            r"C\\:\\\\tmp\\\\transforms.trf"
+        );
+        assert_eq!(escape_filter_option("it's"), r"it\\\'s");
+        assert_eq!(escape_filter_option("a,b;c[d]"), r"a\,b\;c\[d\]");
+    }
+
+    #[test]
+    fn vidstab_two_passes() {
+        let mut conf = base_config();
+        conf.stabilize = Some(StabilizeSettings {
+            shakiness: 8,
+            smoothing: 30,
+        });
+        let mut state = State::default();
+
+        let detect_args = make_ffmpeg_vidstab_detect_args(&conf, &state, "/tmp/transforms.trf");
+        assert_eq!(
+            detect_args,
+            vec![
+                "-nostdin",
+                "-i",
+                "in.mp4",
+                "-an",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-vf",
+                "vidstabdetect=shakiness=8:result=/tmp/transforms.trf",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+
+        state.vidstab_transforms_file = Some(String::from("/tmp/transforms.trf"));
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            "vidstabtransform=input=/tmp/transforms.trf:smoothing=30"
+        );
+        assert_eq!(args.last().unwrap(), "out.mp4");
+
+        // the transforms are removed along with the other intermediate files
+        let plan = plan(&conf).unwrap();
+        assert_eq!(plan.commands[0].kind, StepKind::StabilizationDetect);
+        assert_eq!(plan.temporary_files.len(), 1);
+        assert!(plan.temporary_files[0].ends_with(VIDSTAB_TRANSFORMS_FILE));
+    }
+
+    #[test]
+    fn demux_streams_dual_outputs() {
+        let mut conf = base_config();
+        conf.demux_streams = true;
+        conf.allow_overidde = true;
+        conf.video_codec = Some(String::from("libx264"));
+        conf.audio_codec = Some(String::from("libopus"));
+
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(
+            args,
+            vec![
+                "-y",
+                "-i",
+                "in.mp4",
+                "-map",
+                "0:v",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-c:v",
+                "libx264",
+                "out.mp4",
+                "-map",
+                "0:a",
+                "-ss",
+                "0:0:1.0",
+                "-t",
+                "0:0:4.0",
+                "-af",
+                "volume=0dB",
+                "-c:a",
+                "libopus",
+                "out.opus",
+            ]
+        );
+
+        conf.ignore_video = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("0:v")));
+        assert_eq!(args.last().unwrap(), "out.opus");
+    }
+
+    #[test]
+    fn demux_output_names() {
+        let mut conf = base_config();
+        conf.output_file = String::from("/videos/my.clip.mov");
+        assert_eq!(
+            demux_output_files(&conf),
+            (
+                String::from("/videos/my.clip.mkv"),
+                String::from("/videos/my.clip.mka")
+            )
+        );
+
+        conf.video_codec = Some(String::from("libvpx-vp9"));
+        conf.audio_codec = Some(String::from("pcm_s16le"));
+        assert_eq!(
+            demux_output_files(&conf),
+            (
+                String::from("/videos/my.clip.webm"),
+                String::from("/videos/my.clip.wav")
+            )
+        );
+
+        conf.audio_codec = Some(String::from("aac"));
+        assert_eq!(demux_output_files(&conf).1, "/videos/my.clip.m4a");
+    }
+
+    #[test]
+    fn output_extension_check() {
+        let mut conf = base_config();
+        conf.output_file = String::from("song.mp3");
+        conf.ignore_video = true;
+        conf.audio_codec = Some(String::from("libopus"));
+
+        let mut outcome = RunOutcome::default();
+        check_output_extension(&mut conf, &mut outcome).unwrap();
+        assert_eq!(conf.output_file, "song.opus");
+        assert_eq!(
+            outcome.warnings,
+            vec!["song.mp3 cannot hold libopus: the output was written to song.opus instead."]
+        );
+        // the plan shows the corrected name too
+        conf.output_file = String::from("song.mp3");
+        assert_eq!(
+            plan(&conf).unwrap().commands[0].args.last().unwrap(),
+            "song.opus"
+        );
+
+        conf.strict = true;
+        let mut outcome = RunOutcome::default();
+        assert_eq!(
+            check_output_extension(&mut conf, &mut outcome),
+            Err(String::from(
+                "Error: song.mp3 cannot hold libopus: use the opus extension or set the container format."
+            ))
+        );
+        assert_eq!(conf.output_file, "song.mp3");
+
+        // an explicit container is trusted
+        conf.output_format = Some(String::from("ogg"));
+        check_output_extension(&mut conf, &mut outcome).unwrap();
+        assert_eq!(conf.output_file, "song.mp3");
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[args.len() - 3..], &["-f", "ogg", "song.mp3"]);
+
+        // ignored streams do not count
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libvpx-vp9"));
+        conf.audio_codec = Some(String::from("aac"));
+        conf.ignore_video = true;
+        conf.output_file = String::from("talk.m4a");
+        check_output_extension(&mut conf, &mut outcome).unwrap();
+        assert_eq!(conf.output_file, "talk.m4a");
+        conf.ignore_video = false;
+        check_output_extension(&mut conf, &mut outcome).unwrap();
+        assert_eq!(conf.output_file, "talk.mkv");
+    }
+
+    #[test]
+    fn video_denoise_filters() {
+        let mut conf = base_config();
+        conf.video_denoise = Some(VideoDenoise::Hqdn3d {
+            luma: 4.0,
+            chroma: 3.0,
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "hqdn3d=luma_spatial=4:chroma_spatial=3");
+
+        conf.video_denoise = Some(VideoDenoise::Nlmeans { strength: 1.5 });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "nlmeans=s=1.5");
+        assert_eq!(required_filters(&conf), vec!["nlmeans"]);
+
+        conf.ignore_video = true;
+        assert!(required_filters(&conf).is_empty());
+    }
+
+    #[test]
+    fn video_denoise_before_stabilization() {
+        let mut conf = base_config();
+        conf.video_denoise = Some(VideoDenoise::Nlmeans { strength: 2.0 });
+        conf.stabilize = Some(StabilizeSettings::default());
+        let state = State {
+            vidstab_transforms_file: Some(String::from("t.trf")),
+            ..State::default()
+        };
+
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            "nlmeans=s=2,vidstabtransform=input=t.trf:smoothing=10"
+        );
+    }
+
+    #[test]
+    fn color_filter() {
+        let mut conf = base_config();
+        conf.color = Some(ColorAdjust {
+            brightness: 0.1,
+            contrast: 1.2,
+            saturation: 0.5,
+            gamma: 2.0,
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            "eq=brightness=0.1:contrast=1.2:saturation=0.5:gamma=2"
+        );
+
+        conf.color = Some(ColorAdjust {
+            saturation: 5.0,
+            ..ColorAdjust::default()
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "eq=saturation=3");
+    }
+
+    #[test]
+    fn lut_filter_windows_path() {
+        let mut conf = base_config();
+        conf.lut = Some(PathBuf::from(r"C:\Users\me\LUTs\Kodak 2383.cube"));
+        conf.color = Some(ColorAdjust {
+            gamma: 1.5,
+            ..ColorAdjust::default()
+        });
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            r"eq=gamma=1.5,lut3d=file=C\\:\\\\Users\\\\me\\\\LUTs\\\\Kodak 2383.cube"
+        );
+        assert_eq!(required_filters(&conf), vec!["lut3d"]);
+    }
+
+    #[test]
+    fn lut_filter_path_with_quotes() {
+        assert_eq!(
+            escape_filter_option(r#"/home/me/it's "graded".cube"#),
+            r#"/home/me/it\\\'s "graded".cube"#
+        );
+        assert_eq!(
+            escape_filter_option(r"\\server\luts\a'b.3dl"),
+            r"\\\\\\\\server\\\\luts\\\\a\\\'b.3dl"
+        );
+        assert_eq!(
+            escape_filter_option("/luts/[v2] warm, soft; final.cube"),
+            r"/luts/\[v2\] warm\, soft\; final.cube"
+        );
+    }
+
+    #[test]
+    fn aspect_ratio_policies() {
+        let mut conf = base_config();
+        conf.aspect_ratio = Some(AspectPolicy::Keep);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-aspect")));
+        assert!(!args.contains(&String::from("-vf")));
+
+        conf.aspect_ratio = Some(AspectPolicy::Force(4, 3));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let aspect = args.iter().position(|arg| arg == "-aspect").unwrap();
+        assert_eq!(args[aspect + 1], "4:3");
+        assert!(!args.contains(&String::from("-vf")));
+
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-aspect")));
+
+        conf.preview = false;
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "scale=trunc(iw*sar/2)*2:ih,setsar=1");
+        assert!(!args.contains(&String::from("-aspect")));
+    }
+
+    #[test]
+    fn square_pixels_scaling_comes_last() {
+        let mut conf = base_config();
+        conf.aspect_ratio = Some(AspectPolicy::SquarePixels);
+        conf.video_denoise = Some(VideoDenoise::Hqdn3d {
+            luma: 4.0,
+            chroma: 3.0,
+        });
+        conf.lut = Some(PathBuf::from("look.cube"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let vf = args.iter().position(|arg| arg == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            "hqdn3d=luma_spatial=4:chroma_spatial=3,lut3d=file=look.cube,scale=trunc(iw*sar/2)*2:ih,setsar=1"
+        );
+    }
+
+    #[test]
+    fn output_size_limit() {
+        let mut conf = base_config();
+        conf.max_output_size = Some(8 * 1024 * 1024);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(&args[len - 3..], &["-fs", "8388608", "out.mp4"]);
+
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-fs")));
+    }
+
+    #[test]
+    fn target_size_bitrate() {
+        let mut conf = base_config();
+        conf.target_size = Some(8_000_000);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        // 4 seconds, less 128 kbps of audio
+        assert_eq!(option_values(&args, "-b:v"), vec!["15072k"]);
+        assert_eq!(option_values(&args, "-maxrate"), vec!["15072k"]);
+        assert_eq!(option_values(&args, "-bufsize"), vec!["30144k"]);
+
+        conf.process_whole_file = true;
+        conf.audio_bitrate = Some(64);
+        let mut state = State::default();
+        assert!(!make_ffmpeg_processing_args(&conf, &state).contains(&String::from("-b:v")));
+        state.input_duration = Some(Duration::seconds(60));
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(option_values(&args, "-b:v"), vec!["949k"]);
+        assert_eq!(option_values(&args, "-b:a"), vec!["64k"]);
+
+        // never below a watchable rate
+        conf.target_size = Some(100_000);
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert_eq!(option_values(&args, "-b:v"), vec!["100k"]);
+    }
+
+    #[test]
+    fn max_height_filter() {
+        let mut conf = base_config();
+        conf.max_height = Some(720);
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(option_values(&args, "-vf"), vec!["scale=-2:'min(ih,720)'"]);
+    }
+
+    #[test]
+    fn fragmented_mp4() {
+        let movflags = ["-movflags", "+frag_keyframe+empty_moov+default_base_moof"];
+        let has_movflags = |args: &[String]| args.windows(2).any(|pair| pair == movflags);
+
+        let mut conf = base_config();
+        assert!(!has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+
+        conf.fragmented_mp4 = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert_eq!(&args[args.len() - 3..args.len() - 1], &movflags);
+
+        let mut outcome = RunOutcome::default();
+        push_process_warnings(&conf, &State::default(), &mut outcome);
+        assert!(outcome.warnings.is_empty());
+
+        // guarded by the output extension or format
+        conf.output_file = String::from("out.MOV");
+        assert!(has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+        conf.output_file = String::from("out.mkv");
+        assert!(!has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+        push_process_warnings(&conf, &State::default(), &mut outcome);
+        assert_eq!(outcome.warnings.len(), 1);
+        conf.output_format = Some(String::from("mp4"));
+        assert!(has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+        conf.output_format = Some(String::from("ipod"));
+        assert!(has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+        // formats are muxer names, not extensions
+        conf.output_format = Some(String::from("m4v"));
+        assert!(!has_movflags(&make_ffmpeg_processing_args(
+            &conf,
+            &State::default()
+        )));
+
+        // demuxed streams are checked one by one
+        conf.output_format = None;
+        conf.output_file = String::from("out.mp4");
+        conf.demux_streams = true;
+        conf.video_codec = Some(String::from("libx264"));
+        conf.audio_codec = Some(String::from("libopus"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let flags = args
+            .iter()
+            .filter(|arg| arg.as_str() == "-movflags")
+            .count();
+        assert_eq!(flags, 1);
+    }
+
+    #[test]
+    fn gst_audio_filter_description() {
+        let mut conf = base_config();
+        assert_eq!(make_gst_audio_filter_description(&conf), None);
+
+        conf.volume_change = 20.0;
+        assert_eq!(
+            make_gst_audio_filter_description(&conf).unwrap(),
+            "audioconvert ! volume volume=10 ! audioconvert"
+        );
+
+        conf.volume_change = 0.0;
+        conf.high_pass_filter = Some(200);
+        conf.low_pass_filter = Some(3000);
+        // normalization has no equivalent, the other filters are kept
+        conf.peak_normalization = true;
+        assert_eq!(
+            make_gst_audio_filter_description(&conf).unwrap(),
+            "audioconvert ! audiocheblimit mode=high-pass cutoff=200 ! audiocheblimit mode=low-pass cutoff=3000 ! audioconvert"
+        );
+    }
+
+    #[test]
+    fn embed_source_info() {
+        let mut conf = base_config();
+        conf.input_file = String::from("/videos/talk \"final\".mp4");
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-metadata")));
+
+        conf.embed_source_info = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let metadata = args.iter().position(|arg| arg == "-metadata").unwrap();
+        assert_eq!(
+            args[metadata + 1],
+            "comment=cut from /videos/talk \"final\".mp4 [0:0:1.0-0:0:5.0] by media-cutter"
+        );
+        assert_eq!(args.last().unwrap(), "out.mp4");
+
+        // sox output is not the source
+        let state = State {
+            sox_output_file: Some(String::from("/tmp/talk.wav")),
+            already_trimed: true,
+            ..State::default()
+        };
+        let args = make_ffmpeg_processing_args(&conf, &state);
+        assert!(args
+            .iter()
+            .any(|arg| arg.starts_with("comment=cut from /videos/talk")));
+
+        conf.process_whole_file = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(args.contains(&String::from(
+            "comment=cut from /videos/talk \"final\".mp4 by media-cutter"
+        )));
+
+        conf.preview = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-metadata")));
+    }
+
+    #[test]
+    fn machine_settings() {
+        let mut conf = base_config();
+        conf.peak_normalization = true;
+        conf.ffmpeg_path = Some(PathBuf::from("/opt/ffmpeg/bin/ffmpeg"));
+        conf.loglevel = Some(String::from("error"));
+        let commands = plan(&conf).unwrap().commands;
+        assert!(commands
+            .iter()
+            .all(|command| command.program == "/opt/ffmpeg/bin/ffmpeg"));
+        // the peak measure reads the volumedetect messages
+        assert!(!commands[0].args.contains(&String::from("-loglevel")));
+        assert_eq!(&commands[1].args[..3], &["-nostdin", "-loglevel", "error"]);
+
+        conf.preview = true;
+        assert_eq!(plan(&conf).unwrap().commands[1].program, FFPLAY_COMMAND);
+
+        conf.temp_dir = Some(PathBuf::from("/scratch"));
+        assert_eq!(
+            tmp_dir_path(&conf),
+            Path::new("/scratch").join(TMP_DIRECTORY)
+        );
+        assert_eq!(
+            tmp_dir_path(&Config::default()),
+            env::temp_dir().join(TMP_DIRECTORY)
+        );
+    }
+
+    #[test]
+    fn output_metadata() {
+        let mut conf = base_config();
+        conf.metadata = vec![
+            (String::from("title"), String::from("Song = \"2\"")),
+            (String::from("track"), String::from("2/10")),
+        ];
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let metadata = args.iter().position(|arg| arg == "-metadata").unwrap();
+        assert_eq!(
+            &args[metadata..metadata + 4],
+            &["-metadata", "title=Song = \"2\"", "-metadata", "track=2/10"]
+        );
+        assert_eq!(args.last().unwrap(), "out.mp4");
+    }
+
+    #[test]
+    fn strip_rotation_metadata() {
+        let mut conf = base_config();
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-metadata:s:v:0")));
+
+        conf.strip_rotation_metadata = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let len = args.len();
+        assert_eq!(
+            &args[len - 3..],
+            &["-metadata:s:v:0", "rotate=0", "out.mp4"]
+        );
+
+        // only the video output of a demux carries it
+        conf.demux_streams = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let video_end = args.iter().position(|arg| arg == "out.mkv").unwrap();
+        assert_eq!(
+            &args[video_end - 2..video_end],
+            &["-metadata:s:v:0", "rotate=0"]
+        );
+        assert_eq!(
+            args.iter().filter(|arg| *arg == "-metadata:s:v:0").count(),
+            1
+        );
+
+        conf.demux_streams = false;
+        conf.ignore_video = true;
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-metadata:s:v:0")));
+
+        // copied frames are not rotated, the tag must stay
+        conf.ignore_video = false;
+        conf.video_codec = Some(String::from("copy"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-metadata:s:v:0")));
+    }
+
+    #[test]
+    fn encoder_preset() {
+        let mut conf = base_config();
+        conf.video_codec = Some(String::from("libx264"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-preset")));
+
+        conf.encoder_preset = Some(String::from("veryslow"));
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        let codec = args.iter().position(|arg| arg == "-c:v").unwrap();
+        assert_eq!(
+            &args[codec..codec + 4],
+            &["-c:v", "libx264", "-preset", "veryslow"]
+        );
+
+        let mut outcome = RunOutcome::default();
+        push_process_warnings(&conf, &State::default(), &mut outcome);
+        assert!(outcome.warnings.is_empty());
+        conf.video_codec = Some(String::from("libvpx-vp9"));
+        push_process_warnings(&conf, &State::default(), &mut outcome);
+        assert_eq!(outcome.warnings.len(), 1);
+    }
+
+    #[test]
+    fn color_filter_omitted_when_default() {
+        let mut conf = base_config();
+        conf.color = Some(ColorAdjust::default());
+        let args = make_ffmpeg_processing_args(&conf, &State::default());
+        assert!(!args.contains(&String::from("-vf")));
+    }
+
+    #[test]
+    fn no_video_filters_without_stabilization() {
+        let args = make_ffmpeg_processing_args(&base_config(), &State::default());
+        assert!(!args.contains(&String::from("-vf")));
+    }
 }
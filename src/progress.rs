@@ -0,0 +1,277 @@
+use chrono::Duration;
+
+// weight of the latest speed in the smoothed one
+const SPEED_SMOOTHING: f64 = 0.3;
+// below this smoothed speed, encoding is considered stalled and no ETA is given
+const STALLED_SPEED: f64 = 0.01;
+
+/// Encoding progress read from ffmpeg `-progress` output, with an ETA smoothed
+/// so that it does not jump around with every speed change.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProgressTracker {
+    /// Expected output duration in seconds, unknown for whole files not probed.
+    total: Option<f64>,
+    /// Output time reached so far, in seconds.
+    out_time: f64,
+    /// Speed last reported by ffmpeg, as a multiple of real time.
+    speed: Option<f64>,
+    /// Exponential moving average of the reported speeds.
+    smoothed_speed: Option<f64>,
+    finished: bool,
+}
+
+impl ProgressTracker {
+    pub fn new(total: Option<f64>) -> Self {
+        Self {
+            total: total.filter(|&total| total > 0.0),
+            out_time: 0.0,
+            speed: None,
+            smoothed_speed: None,
+            finished: false,
+        }
+    }
+
+    /// Read one `key=value` line of ffmpeg `-progress` output. Returns `true`
+    /// at the end of a block, once all of its values were read.
+    pub fn feed_line(&mut self, line: &str) -> bool {
+        let (key, value) = match line.find('=') {
+            Some(equal) => (line[..equal].trim(), line[equal + 1..].trim()),
+            None => return false,
+        };
+
+        match key {
+            // out_time_ms is in microseconds too, despite its name
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(micros) = value.parse::<i64>() {
+                    // negative before the first frame is written
+                    if micros >= 0 {
+                        self.out_time = micros as f64 / 1e6;
+                    }
+                }
+                false
+            }
+            "speed" => {
+                if let Some(Ok(speed)) = value.strip_suffix('x').map(|speed| speed.trim().parse()) {
+                    self.update_speed(speed);
+                }
+                false
+            }
+            "progress" => {
+                if value == "end" {
+                    self.finished = true;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn update_speed(&mut self, speed: f64) {
+        self.speed = Some(speed);
+        self.smoothed_speed = Some(match self.smoothed_speed {
+            Some(smoothed) => smoothed + SPEED_SMOOTHING * (speed - smoothed),
+            None => speed,
+        });
+    }
+
+    /// Done part of the output, between 0 and 1, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.finished {
+            return Some(1.0);
+        }
+        self.total.map(|total| (self.out_time / total).min(1.0))
+    }
+
+    /// Speed last reported by ffmpeg, e.g. 2.0 when encoding twice as fast as
+    /// real time.
+    pub fn speed(&self) -> Option<f64> {
+        self.speed
+    }
+
+    /// Time left at the smoothed speed, `None` when unknown or stalled.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.finished {
+            return Some(Duration::zero());
+        }
+        let remaining = (self.total? - self.out_time).max(0.0);
+        let speed = self
+            .smoothed_speed
+            .filter(|&speed| speed >= STALLED_SPEED)?;
+        Some(Duration::milliseconds(
+            (remaining / speed * 1000.0).round() as i64
+        ))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Progress as displayed, e.g. `42 % · 3.4× · 1:23 left`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(fraction) = self.fraction() {
+            parts.push(format!("{:.0} %", fraction * 100.0));
+        }
+        if let Some(speed) = self.speed {
+            parts.push(format!("{:.1}×", speed));
+        }
+        match self.eta() {
+            Some(eta) if !self.finished => parts.push(format!("{} left", format_eta(eta))),
+            None if self.smoothed_speed.is_some() && self.total.is_some() => {
+                parts.push(String::from("stalled"))
+            }
+            _ => {}
+        }
+        parts.join(" · ")
+    }
+}
+
+// `m:ss` or `h:mm:ss`, seconds rounded up so that the last one shows as 0:01
+fn format_eta(eta: Duration) -> String {
+    let seconds = (eta.num_milliseconds() + 999) / 1000;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one `-progress` block, as written by ffmpeg every half second
+    fn feed_block(tracker: &mut ProgressTracker, out_time: f64, speed: &str, end: bool) -> bool {
+        let block = format!(
+            "frame=120\nfps=30.00\nout_time_us={}\nout_time=00:00:04.000000\nspeed={}\nprogress={}",
+            (out_time * 1e6) as i64,
+            speed,
+            if end { "end" } else { "continue" }
+        );
+        let mut complete = false;
+        for line in block.lines() {
+            complete = tracker.feed_line(line);
+        }
+        complete
+    }
+
+    #[test]
+    fn steady_encoding() {
+        let mut tracker = ProgressTracker::new(Some(100.0));
+        assert_eq!(tracker.fraction(), Some(0.0));
+        assert_eq!(tracker.eta(), None);
+        assert_eq!(tracker.summary(), "0 %");
+
+        assert!(feed_block(&mut tracker, 20.0, "2x", false));
+        assert_eq!(tracker.fraction(), Some(0.2));
+        assert_eq!(tracker.speed(), Some(2.0));
+        assert_eq!(tracker.eta(), Some(Duration::seconds(40)));
+        assert_eq!(tracker.summary(), "20 % · 2.0× · 0:40 left");
+
+        assert!(feed_block(&mut tracker, 40.0, " 2x", false));
+        assert_eq!(tracker.eta(), Some(Duration::seconds(30)));
+    }
+
+    #[test]
+    fn eta_is_smoothed() {
+        let mut tracker = ProgressTracker::new(Some(100.0));
+        feed_block(&mut tracker, 10.0, "1x", false);
+        assert_eq!(tracker.eta(), Some(Duration::seconds(90)));
+
+        // a sudden speedup only shortens the ETA partly
+        feed_block(&mut tracker, 20.0, "4x", false);
+        assert_eq!(tracker.speed(), Some(4.0));
+        let eta = tracker.eta().unwrap();
+        assert!(eta < Duration::seconds(80), "{}", eta);
+        assert!(eta > Duration::seconds(20), "{}", eta);
+
+        // and it converges when the speed holds
+        for out_time in 3..20 {
+            feed_block(&mut tracker, f64::from(out_time) * 5.0, "4x", false);
+        }
+        let eta = tracker.eta().unwrap();
+        assert!(
+            (eta - Duration::milliseconds(1250))
+                .num_milliseconds()
+                .abs()
+                < 50,
+            "{}",
+            eta
+        );
+    }
+
+    #[test]
+    fn stall() {
+        let mut tracker = ProgressTracker::new(Some(100.0));
+        feed_block(&mut tracker, 50.0, "1x", false);
+        let before = tracker.eta().unwrap();
+
+        // a short stall lengthens the ETA without losing it
+        feed_block(&mut tracker, 50.0, "0x", false);
+        let during = tracker.eta().unwrap();
+        assert!(during > before);
+        assert!(tracker.summary().ends_with("left"));
+
+        // a long one removes it
+        for _ in 0..20 {
+            feed_block(&mut tracker, 50.0, "0x", false);
+        }
+        assert_eq!(tracker.eta(), None);
+        assert_eq!(tracker.summary(), "50 % · 0.0× · stalled");
+
+        // until encoding resumes
+        feed_block(&mut tracker, 60.0, "2x", false);
+        assert!(tracker.eta().is_some());
+    }
+
+    #[test]
+    fn final_burst() {
+        let mut tracker = ProgressTracker::new(Some(10.0));
+        feed_block(&mut tracker, 5.0, "1x", false);
+        // ffmpeg flushes its buffered frames at once at the end
+        feed_block(&mut tracker, 12.0, "37.5x", false);
+        assert_eq!(tracker.fraction(), Some(1.0));
+        assert_eq!(tracker.eta(), Some(Duration::zero()));
+        assert!(!tracker.is_finished());
+
+        assert!(feed_block(&mut tracker, 10.0, "30x", true));
+        assert!(tracker.is_finished());
+        assert_eq!(tracker.fraction(), Some(1.0));
+        assert_eq!(tracker.eta(), Some(Duration::zero()));
+        assert_eq!(tracker.summary(), "100 % · 30.0×");
+    }
+
+    #[test]
+    fn unknown_values() {
+        let mut tracker = ProgressTracker::new(None);
+        for line in &[
+            "out_time_us=N/A",
+            "out_time_ms=-9223372036854775807",
+            "speed=N/A",
+            "",
+        ] {
+            assert!(!tracker.feed_line(line));
+        }
+        assert!(tracker.feed_line("progress=continue"));
+        assert_eq!(tracker.fraction(), None);
+        assert_eq!(tracker.speed(), None);
+        assert_eq!(tracker.summary(), "");
+
+        // speed without total
+        feed_block(&mut tracker, 3.0, "1.5x", false);
+        assert_eq!(tracker.eta(), None);
+        assert_eq!(tracker.summary(), "1.5×");
+    }
+
+    #[test]
+    fn eta_format() {
+        assert_eq!(format_eta(Duration::milliseconds(300)), "0:01");
+        assert_eq!(format_eta(Duration::seconds(83)), "1:23");
+        assert_eq!(format_eta(Duration::seconds(3723)), "1:02:03");
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{probe::MediaInfo, Config};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Format version written to project files, bumped on incompatible changes.
+pub const PROJECT_VERSION: u32 = 1;
+pub const PROJECT_EXTENSION: &str = "mcut";
+
+// the duration of a relocated source may differ by this much from the saved one
+const DURATION_TOLERANCE: f64 = 0.5;
+
+/// Named position in the input file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Marker {
+    #[serde(with = "millis")]
+    pub time: Duration,
+    pub name: String,
+}
+
+/// Range of the input file to cut as a clip of its own.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Segment {
+    #[serde(with = "millis")]
+    pub from_time: Duration,
+    #[serde(with = "millis")]
+    pub to_time: Duration,
+    pub name: String,
+}
+
+/// Editing session saved in a `.mcut` file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    /// All the settings, input and output files included.
+    pub config: Config,
+    /// Input file information when saved, to check a relocated source against.
+    pub media_info: Option<MediaInfo>,
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+}
+
+// read first so that newer files are reported as such instead of as invalid
+#[derive(Deserialize)]
+struct ProjectHeader {
+    version: u32,
+}
+
+impl Project {
+    pub fn new(config: Config) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            config,
+            media_info: None,
+            markers: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn source_exists(&self) -> bool {
+        Path::new(&self.config.input_file).is_file()
+    }
+
+    /// A file named like the missing source in the project directory, where it
+    /// usually is when both were moved together.
+    pub fn find_moved_source(&self, project_file: &Path) -> Option<PathBuf> {
+        let file_name = Path::new(&self.config.input_file).file_name()?;
+        let candidate = project_file.parent()?.join(file_name);
+        if candidate.is_file() {
+            Some(candidate)
+        } else {
+            None
+        }
+    }
+
+    /// Use `input` as the source from now on. Returns warnings when `info`,
+    /// the new source information, does not match the saved one.
+    pub fn relocate(&mut self, input: &Path, info: Option<MediaInfo>) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let (Some(saved), Some(new)) = (&self.media_info, &info) {
+            if let (Some(saved_duration), Some(new_duration)) = (saved.duration, new.duration) {
+                if (saved_duration - new_duration).abs() > DURATION_TOLERANCE {
+                    warnings.push(format!(
+                        "The new source lasts {:.1}s but the project was made for {:.1}s: markers and segments may be off.",
+                        new_duration, saved_duration
+                    ));
+                }
+            }
+            if saved.streams.len() != new.streams.len() {
+                warnings.push(format!(
+                    "The new source has {} streams instead of {}.",
+                    new.streams.len(),
+                    saved.streams.len()
+                ));
+            }
+        }
+
+        self.config.input_file = input.to_string_lossy().into_owned();
+        if info.is_some() {
+            self.media_info = info;
+        }
+        warnings
+    }
+}
+
+pub fn save(path: &Path, project: &Project) -> Result<()> {
+    let json = serde_json::to_string_pretty(project)
+        .map_err(|e| format!("Could not serialize the project.\nError: {}", e))?;
+    fs::write(path, json).map_err(|e| {
+        format!(
+            "Could not write the project file {}.\nError: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// Read a project, the source file is not checked: see `Project::source_exists`.
+pub fn load(path: &Path) -> Result<Project> {
+    let json = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Could not read the project file {}.\nError: {}",
+            path.display(),
+            e
+        )
+    })?;
+    let invalid = |e: serde_json::Error| {
+        format!(
+            "{} is not a valid project file.\nError: {}",
+            path.display(),
+            e
+        )
+    };
+
+    let header: ProjectHeader = serde_json::from_str(&json).map_err(invalid)?;
+    if header.version > PROJECT_VERSION {
+        return Err(format!(
+            "{} was saved by a newer version of Media Cutter (project version {}).",
+            path.display(),
+            header.version
+        ));
+    }
+    serde_json::from_str(&json).map_err(invalid)
+}
+
+// chrono durations are stored as whole milliseconds
+pub(crate) mod millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_milliseconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        i64::deserialize(deserializer).map(Duration::milliseconds)
+    }
+}
+
+pub(crate) mod optional_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        duration
+            .map(|duration| duration.num_milliseconds())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<i64>::deserialize(deserializer).map(|millis| millis.map(Duration::milliseconds))
+    }
+}
+
+pub(crate) mod optional_range_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        range: &Option<(Duration, Duration)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        range
+            .map(|(from, to)| (from.num_milliseconds(), to.num_milliseconds()))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(Duration, Duration)>, D::Error> {
+        Option::<(i64, i64)>::deserialize(deserializer).map(|range| {
+            range.map(|(from, to)| (Duration::milliseconds(from), Duration::milliseconds(to)))
+        })
+    }
+}
+
+pub(crate) mod optional_frame_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        frame: &Option<(String, Duration)>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        frame
+            .as_ref()
+            .map(|(file, time)| (file, time.num_milliseconds()))
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<(String, Duration)>, D::Error> {
+        Option::<(String, i64)>::deserialize(deserializer)
+            .map(|frame| frame.map(|(file, time)| (file, Duration::milliseconds(time))))
+    }
+}
+
+pub(crate) mod envelope_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        points: &[(Duration, f64)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        points
+            .iter()
+            .map(|&(time, value)| (time.num_milliseconds(), value))
+            .collect::<Vec<(i64, f64)>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(Duration, f64)>, D::Error> {
+        Vec::<(i64, f64)>::deserialize(deserializer).map(|points| {
+            points
+                .into_iter()
+                .map(|(time, value)| (Duration::milliseconds(time), value))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{probe::StreamInfo, StabilizeSettings};
+    use std::env;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("media_cutter_project_test_{}", name));
+        path
+    }
+
+    fn media_info(duration: f64, streams: u32) -> MediaInfo {
+        MediaInfo {
+            container: String::from("matroska,webm"),
+            duration: Some(duration),
+            bit_rate: None,
+            streams: (0..streams)
+                .map(|index| StreamInfo {
+                    index,
+                    kind: String::from("audio"),
+                    codec: Some(String::from("opus")),
+                    width: None,
+                    height: None,
+                    sample_aspect_ratio: None,
+                    display_aspect_ratio: None,
+                    sample_rate: Some(48_000),
+                    channels: Some(2),
+                    language: None,
+                    bit_rate: None,
+                    frame_rate: None,
+                    pix_fmt: None,
+                    profile: None,
+                    level: None,
+                    time_base: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut config = Config::new();
+        config.input_file = String::from("/recordings/talk.mkv");
+        config.output_file = String::from("/recordings/talk_cut.flac");
+        config.from_time = Duration::milliseconds(61_005);
+        config.to_time = Duration::seconds(3600);
+        config.pad_before = Some(Duration::milliseconds(250));
+        config.normalization_measure_range =
+            Some((Duration::seconds(70), Duration::milliseconds(80_500)));
+        config.stabilize = Some(StabilizeSettings::default());
+        config.output_pattern = Some(String::from("{stem}_{index:02}.flac"));
+        config.volume_envelope = vec![
+            (Duration::milliseconds(61_500), -3.5),
+            (Duration::seconds(3000), 2.0),
+        ];
+
+        let mut project = Project::new(config);
+        project.media_info = Some(media_info(7200.5, 2));
+        project.markers = vec![
+            Marker {
+                time: Duration::milliseconds(90_250),
+                name: String::from("question"),
+            },
+            Marker {
+                time: Duration::seconds(4000),
+                name: String::new(),
+            },
+        ];
+        project.segments = vec![Segment {
+            from_time: Duration::seconds(60),
+            to_time: Duration::milliseconds(1_234_567),
+            name: String::from("intro"),
+        }];
+
+        let path = temp_path("round_trip.mcut");
+        save(&path, &project).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, Ok(project));
+    }
+
+    #[test]
+    fn invalid_files() {
+        let path = temp_path("invalid.mcut");
+
+        fs::write(&path, "{\"version\": 1, \"config\": 3}").unwrap();
+        assert!(load(&path).unwrap_err().contains("not a valid project"));
+
+        fs::write(&path, "{\"version\": 2, \"something\": \"new\"}").unwrap();
+        assert!(load(&path).unwrap_err().contains("newer version"));
+
+        // older files miss the settings added since
+        fs::write(
+            &path,
+            "{\"version\": 1, \"config\": {\"input_file\": \"a.wav\"}}",
+        )
+        .unwrap();
+        let project = load(&path).unwrap();
+        assert_eq!(project.config.input_file, "a.wav");
+        assert_eq!(project.config.to_time, Config::new().to_time);
+        assert!(project.markers.is_empty());
+
+        fs::remove_file(&path).unwrap();
+        assert!(load(&path).unwrap_err().contains("Could not read"));
+    }
+
+    #[test]
+    fn missing_source() {
+        let directory = temp_path("missing_source");
+        fs::create_dir_all(&directory).unwrap();
+        let project_file = directory.join("talk.mcut");
+
+        let mut config = Config::new();
+        config.input_file = String::from("/nowhere/talk.mkv");
+        let mut project = Project::new(config);
+        project.media_info = Some(media_info(100.0, 2));
+        assert!(!project.source_exists());
+        assert_eq!(project.find_moved_source(&project_file), None);
+
+        // moved next to the project file
+        let moved = directory.join("talk.mkv");
+        fs::write(&moved, "").unwrap();
+        assert_eq!(
+            project.find_moved_source(&project_file),
+            Some(moved.clone())
+        );
+
+        assert!(project
+            .relocate(&moved, Some(media_info(100.2, 2)))
+            .is_empty());
+        assert!(project.source_exists());
+        assert_eq!(project.config.input_file, moved.to_string_lossy());
+
+        // another file entirely
+        let warnings = project.relocate(&moved, Some(media_info(30.0, 1)));
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(project.media_info, Some(media_info(30.0, 1)));
+
+        // unknown information keeps the saved one
+        assert!(project.relocate(&moved, None).is_empty());
+        assert_eq!(project.media_info, Some(media_info(30.0, 1)));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,635 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    processing::{self, CancelFlag, RunOutcome, CANCELLED_ERROR},
+    progress::ProgressTracker,
+    Config,
+};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Name of the manifest of a batch, written next to its outputs.
+pub const MANIFEST_FILE: &str = "media-cutter-batch.json";
+
+/// What happens to a job of a running queue, `usize` being its index.
+#[derive(Clone, PartialEq, Debug)]
+pub enum JobEvent {
+    Started(usize),
+    Progress(usize, ProgressTracker),
+    Finished(usize, Result<RunOutcome>),
+}
+
+/// Output written by a job of an interrupted batch.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct CompletedJob {
+    pub input_file: String,
+    pub output_file: String,
+}
+
+impl CompletedJob {
+    // `index` names the output as `run_job` does
+    fn new(index: usize, conf: &Config) -> Self {
+        Self {
+            input_file: conf.input_file.clone(),
+            output_file: conf.with_output_pattern(index + 1).output_file,
+        }
+    }
+}
+
+/// Progress of a batch, saved after each job so that an interrupted batch can
+/// be resumed.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub completed: Vec<CompletedJob>,
+}
+
+impl Manifest {
+    /// Read a manifest, a missing one being empty.
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(format!(
+                    "Could not read the batch manifest {}.\nError: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        serde_json::from_str(&json).map_err(|e| {
+            format!(
+                "{} is not a valid batch manifest.\nError: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Could not serialize the batch manifest.\nError: {}", e))?;
+        fs::write(path, json).map_err(|e| {
+            format!(
+                "Could not write the batch manifest {}.\nError: {}",
+                path.display(),
+                e
+            )
+        })
+    }
+
+    /// Mark the `index`-th job of the batch as done.
+    pub fn record(&mut self, index: usize, conf: &Config) {
+        let completed = CompletedJob::new(index, conf);
+        if !self.completed.contains(&completed) {
+            self.completed.push(completed);
+        }
+    }
+
+    /// Whether each of `jobs` is to be skipped when resuming: completed by a
+    /// previous run, of the same input, and with its output still there.
+    pub fn skipped_jobs<F>(&self, jobs: &[Config], output_exists: F) -> Vec<bool>
+    where
+        F: Fn(&str) -> bool,
+    {
+        jobs.iter()
+            .enumerate()
+            .map(|(index, conf)| {
+                let job = CompletedJob::new(index, conf);
+                self.completed.contains(&job) && output_exists(&job.output_file)
+            })
+            .collect()
+    }
+}
+
+/// Inputs processed with their own settings, up to `concurrency` at a time.
+#[derive(Clone, PartialEq, Debug)]
+pub struct JobQueue {
+    jobs: Vec<Config>,
+    concurrency: usize,
+    /// Where the progress is saved, see `Manifest`.
+    manifest_file: Option<PathBuf>,
+    /// Skip the jobs the manifest lists as completed.
+    resume: bool,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self {
+            jobs: Vec::new(),
+            concurrency: 1,
+            manifest_file: None,
+            resume: false,
+        }
+    }
+}
+
+impl JobQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of logical cores, the highest useful concurrency.
+    pub fn max_concurrency() -> usize {
+        thread::available_parallelism().map_or(1, |cores| cores.get())
+    }
+
+    pub fn push(&mut self, conf: Config) {
+        self.jobs.push(conf);
+    }
+
+    pub fn jobs(&self) -> &[Config] {
+        &self.jobs
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Jobs run at once, between 1 and `max_concurrency`. Each ffmpeg already
+    /// uses several threads: see `Config::threads` to balance both.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1).min(Self::max_concurrency());
+    }
+
+    /// Save the progress to `manifest_file` after each job. It is deleted once
+    /// every job succeeded.
+    pub fn set_manifest_file(&mut self, manifest_file: PathBuf) {
+        self.manifest_file = Some(manifest_file);
+    }
+
+    /// Skip the jobs completed by a previous run, as recorded by the manifest.
+    pub fn set_resume(&mut self, resume: bool) {
+        self.resume = resume;
+    }
+
+    /// Manifest next to the output of the first job.
+    pub fn default_manifest_file(&self) -> Option<PathBuf> {
+        let output_file = PathBuf::from(CompletedJob::new(0, self.jobs.first()?).output_file);
+        Some(output_file.parent()?.join(MANIFEST_FILE))
+    }
+
+    /// Process every job, each in a temporary directory of its own. Jobs start
+    /// in order and `on_event` is called from the calling thread. Once `cancel`
+    /// is set, running jobs are stopped and the others do not start.
+    ///
+    /// Returns the result of each job, in job order.
+    pub fn run<F>(&self, cancel: &CancelFlag, on_event: F) -> Vec<Result<RunOutcome>>
+    where
+        F: FnMut(JobEvent),
+    {
+        self.run_resumable(cancel, on_event, |index, conf, cancel, on_progress| {
+            let tmp_dir = processing::tmp_dir_path(conf).join(format!("job_{}", index + 1));
+            let result = processing::run_job(conf, index + 1, &tmp_dir, cancel, on_progress);
+            let _ = fs::remove_dir_all(&tmp_dir);
+            result
+        })
+    }
+
+    // skips the completed jobs when resuming and records the others in the manifest
+    fn run_resumable<F, R>(
+        &self,
+        cancel: &CancelFlag,
+        on_event: F,
+        run_job: R,
+    ) -> Vec<Result<RunOutcome>>
+    where
+        F: FnMut(JobEvent),
+        R: Fn(usize, &Config, &CancelFlag, &mut dyn FnMut(&ProgressTracker)) -> Result<RunOutcome>
+            + Sync,
+    {
+        let manifest_file = match self.manifest_file {
+            Some(ref manifest_file) => manifest_file,
+            None => return self.run_with(cancel, on_event, run_job),
+        };
+
+        let manifest = if self.resume {
+            match Manifest::load(manifest_file) {
+                Ok(manifest) => manifest,
+                Err(e) => return self.jobs.iter().map(|_| Err(e.clone())).collect(),
+            }
+        } else {
+            Manifest::default()
+        };
+        let skipped =
+            manifest.skipped_jobs(&self.jobs, |output_file| Path::new(output_file).exists());
+        let manifest = Mutex::new(manifest);
+
+        let results = self.run_with(cancel, on_event, |index, conf, cancel, on_progress| {
+            if skipped[index] {
+                return Ok(RunOutcome {
+                    warnings: vec![format!(
+                        "{} was completed by a previous run, skipped.",
+                        conf.with_output_pattern(index + 1).output_file
+                    )],
+                    ..RunOutcome::default()
+                });
+            }
+
+            let mut result = run_job(index, conf, cancel, on_progress);
+            if let Ok(ref mut outcome) = result {
+                let mut manifest = manifest.lock().unwrap();
+                manifest.record(index, conf);
+                if let Err(e) = manifest.save(manifest_file) {
+                    outcome.warnings.push(e);
+                }
+            }
+            result
+        });
+
+        if results.iter().all(Result::is_ok) {
+            let _ = fs::remove_file(manifest_file);
+        }
+        results
+    }
+
+    // scheduling only, `run_job` processes the job of the given index
+    fn run_with<F, R>(
+        &self,
+        cancel: &CancelFlag,
+        mut on_event: F,
+        run_job: R,
+    ) -> Vec<Result<RunOutcome>>
+    where
+        F: FnMut(JobEvent),
+        R: Fn(usize, &Config, &CancelFlag, &mut dyn FnMut(&ProgressTracker)) -> Result<RunOutcome>
+            + Sync,
+    {
+        let mut results: Vec<Result<RunOutcome>> = self
+            .jobs
+            .iter()
+            .map(|_| Err(String::from(CANCELLED_ERROR)))
+            .collect();
+        let next_job = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..self.concurrency.min(self.jobs.len()) {
+                let sender = sender.clone();
+                let (next_job, run_job) = (&next_job, &run_job);
+                scope.spawn(move || {
+                    while !cancel.is_cancelled() {
+                        let index = next_job.fetch_add(1, Ordering::SeqCst);
+                        let conf = match self.jobs.get(index) {
+                            Some(conf) => conf,
+                            None => break,
+                        };
+
+                        let _ = sender.send(JobEvent::Started(index));
+                        let result = run_job(index, conf, cancel, &mut |tracker| {
+                            let _ = sender.send(JobEvent::Progress(index, tracker.clone()));
+                        });
+                        let _ = sender.send(JobEvent::Finished(index, result));
+                    }
+                });
+            }
+            // the loop below ends once every worker is done
+            drop(sender);
+
+            for event in receiver {
+                if let JobEvent::Finished(index, ref result) = event {
+                    results[index] = result.clone();
+                }
+                on_event(event);
+            }
+        });
+
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{atomic::AtomicBool, Barrier, Condvar, Mutex};
+
+    fn queue(jobs: usize, concurrency: usize) -> JobQueue {
+        let mut queue = JobQueue::new();
+        for index in 0..jobs {
+            let mut conf = Config::new();
+            conf.input_file = format!("{}.wav", index);
+            queue.push(conf);
+        }
+        queue.concurrency = concurrency;
+        queue
+    }
+
+    fn outcome(index: usize) -> Result<RunOutcome> {
+        Ok(RunOutcome {
+            warnings: vec![index.to_string()],
+            ..RunOutcome::default()
+        })
+    }
+
+    #[test]
+    fn concurrency_setting() {
+        let mut queue = JobQueue::new();
+        assert_eq!(queue.concurrency(), 1);
+        queue.set_concurrency(0);
+        assert_eq!(queue.concurrency(), 1);
+        queue.set_concurrency(10_000);
+        assert_eq!(queue.concurrency(), JobQueue::max_concurrency());
+    }
+
+    #[test]
+    fn ordering() {
+        let queue = queue(8, 3);
+        let mut started = Vec::new();
+        let mut finished = Vec::new();
+        // the first job finishes last, once the others are done
+        let others_done = (Mutex::new(0), Condvar::new());
+        let results = queue.run_with(
+            &CancelFlag::new(),
+            |event| match event {
+                JobEvent::Started(index) => started.push(index),
+                JobEvent::Finished(index, _) => finished.push(index),
+                JobEvent::Progress(..) => {}
+            },
+            |index, conf, _, _| {
+                assert_eq!(conf.input_file, format!("{}.wav", index));
+                let (ref done, ref changed) = others_done;
+                let mut done = done.lock().unwrap();
+                if index == 0 {
+                    while *done < 7 {
+                        done = changed.wait(done).unwrap();
+                    }
+                } else {
+                    *done += 1;
+                    changed.notify_all();
+                }
+                outcome(index)
+            },
+        );
+
+        assert_eq!(finished.last(), Some(&0));
+        started.sort_unstable();
+        assert_eq!(started, (0..8).collect::<Vec<usize>>());
+        finished.sort_unstable();
+        assert_eq!(finished, started);
+        // results do not depend on the finishing order
+        assert_eq!(results, (0..8).map(outcome).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn concurrency_cap() {
+        for &concurrency in &[1, 3] {
+            let running = AtomicUsize::new(0);
+            let max_running = AtomicUsize::new(0);
+            // the jobs run `concurrency` at a time, each waits for the others
+            let all_running = Barrier::new(concurrency);
+            let results = queue(9, concurrency).run_with(
+                &CancelFlag::new(),
+                |_| {},
+                |index, _, _, _| {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    all_running.wait();
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    outcome(index)
+                },
+            );
+            assert!(results.iter().all(Result::is_ok));
+            assert_eq!(max_running.load(Ordering::SeqCst), concurrency);
+        }
+    }
+
+    // job 2 cancels the queue, the following ones wait for it and then stop
+    fn run_cancelled(concurrency: usize) -> (Vec<usize>, Vec<JobEvent>, Vec<Result<RunOutcome>>) {
+        let started = Mutex::new(Vec::new());
+        let mut events = Vec::new();
+        let results = queue(10, concurrency).run_with(
+            &CancelFlag::new(),
+            |event| events.push(event),
+            |index, _, cancel, on_progress| {
+                started.lock().unwrap().push(index);
+                on_progress(&ProgressTracker::new(None));
+                match index {
+                    0 | 1 => return outcome(index),
+                    2 => cancel.cancel(),
+                    _ => {
+                        while !cancel.is_cancelled() {
+                            thread::yield_now();
+                        }
+                    }
+                }
+                Err(String::from(CANCELLED_ERROR))
+            },
+        );
+        (started.into_inner().unwrap(), events, results)
+    }
+
+    #[test]
+    fn cancellation_drains_the_queue() {
+        let (started, events, results) = run_cancelled(1);
+        assert_eq!(started, vec![0, 1, 2]);
+        assert_eq!(&results[..2], &[outcome(0), outcome(1)]);
+        assert!(results[2..]
+            .iter()
+            .all(|result| result == &Err(String::from(CANCELLED_ERROR))));
+        assert_eq!(
+            events,
+            vec![
+                JobEvent::Started(0),
+                JobEvent::Progress(0, ProgressTracker::new(None)),
+                JobEvent::Finished(0, outcome(0)),
+                JobEvent::Started(1),
+                JobEvent::Progress(1, ProgressTracker::new(None)),
+                JobEvent::Finished(1, outcome(1)),
+                JobEvent::Started(2),
+                JobEvent::Progress(2, ProgressTracker::new(None)),
+                JobEvent::Finished(2, Err(String::from(CANCELLED_ERROR))),
+            ]
+        );
+
+        let (started, events, results) = run_cancelled(2);
+        // the job running beside the cancelling one, at most
+        assert!(started.len() <= 4, "{:?}", started);
+        assert_eq!(results.len(), 10);
+        assert!(results[4..]
+            .iter()
+            .all(|result| result == &Err(String::from(CANCELLED_ERROR))));
+        // every started job reports its end
+        let finished = events
+            .iter()
+            .filter(|event| matches!(event, JobEvent::Finished(..)))
+            .count();
+        assert_eq!(finished, started.len());
+    }
+
+    fn manifest_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("media-cutter-{}-{}", std::process::id(), name))
+    }
+
+    fn job(input_file: &str, output_file: &str) -> Config {
+        let mut conf = Config::new();
+        conf.input_file = String::from(input_file);
+        conf.output_file = String::from(output_file);
+        conf
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let path = manifest_path("round-trip.json");
+        let _ = fs::remove_file(&path);
+        assert_eq!(Manifest::load(&path), Ok(Manifest::default()));
+
+        let mut manifest = Manifest::default();
+        manifest.record(0, &job("album.flac", "01 - Intro.flac"));
+        manifest.record(1, &job("album.flac", "02 - Song.flac"));
+        // recording twice changes nothing
+        manifest.record(0, &job("album.flac", "01 - Intro.flac"));
+        assert_eq!(manifest.completed.len(), 2);
+
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path), Ok(manifest.clone()));
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(
+            json.contains("\"output_file\": \"02 - Song.flac\""),
+            "{}",
+            json
+        );
+
+        fs::write(&path, "{ \"completed\": 3 }").unwrap();
+        assert!(Manifest::load(&path)
+            .unwrap_err()
+            .contains("is not a valid batch manifest"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn skip_decision() {
+        let jobs = [
+            job("album.flac", "01.flac"),
+            job("album.flac", "02.flac"),
+            job("album.flac", "03.flac"),
+            job("other.flac", "04.flac"),
+        ];
+        let mut manifest = Manifest::default();
+        manifest.record(0, &jobs[0]);
+        manifest.record(1, &jobs[1]);
+        // same output, cut from another input
+        manifest.record(3, &job("album.flac", "04.flac"));
+
+        assert_eq!(
+            manifest.skipped_jobs(&jobs, |_| true),
+            vec![true, true, false, false]
+        );
+        // a deleted output is done again
+        assert_eq!(
+            manifest.skipped_jobs(&jobs, |output_file| output_file != "02.flac"),
+            vec![true, false, false, false]
+        );
+        assert_eq!(
+            Manifest::default().skipped_jobs(&jobs, |_| true),
+            vec![false; 4]
+        );
+
+        // outputs named by a pattern are told apart
+        let mut conf = job("take.mp4", "ignored.mp4");
+        conf.output_pattern = Some(String::from("{stem}_{index}.{ext}"));
+        let mut manifest = Manifest::default();
+        manifest.record(0, &conf);
+        assert_eq!(
+            manifest.skipped_jobs(&[conf.clone(), conf], |_| true),
+            vec![true, false]
+        );
+    }
+
+    #[test]
+    fn resuming() {
+        let path = manifest_path("resume.json");
+        let outputs: Vec<PathBuf> = (0..4)
+            .map(|index| manifest_path(&format!("resume-{}.wav", index)))
+            .collect();
+        let mut queue = JobQueue::new();
+        for output in &outputs {
+            let _ = fs::remove_file(output);
+            queue.push(job("in.wav", &output.to_string_lossy()));
+        }
+        queue.set_manifest_file(path.clone());
+        let ran = Mutex::new(Vec::new());
+        let interrupted = AtomicBool::new(true);
+        let run_job =
+            |index: usize, conf: &Config, _: &CancelFlag, _: &mut dyn FnMut(&ProgressTracker)| {
+                ran.lock().unwrap().push(index);
+                if index == 2 && interrupted.load(Ordering::SeqCst) {
+                    return Err(String::from("Error: disk full."));
+                }
+                fs::write(&conf.output_file, "").unwrap();
+                outcome(index)
+            };
+
+        // interrupted at the third job
+        let results = queue.run_resumable(&CancelFlag::new(), |_| {}, run_job);
+        assert!(results[2].is_err());
+        assert_eq!(*ran.lock().unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(Manifest::load(&path).unwrap().completed.len(), 3);
+
+        // without resuming, everything is done again
+        interrupted.store(false, Ordering::SeqCst);
+        ran.lock().unwrap().clear();
+        let results = queue.run_resumable(&CancelFlag::new(), |_| {}, run_job);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(*ran.lock().unwrap(), vec![0, 1, 2, 3]);
+        // done: the manifest goes away
+        assert!(!path.exists());
+
+        // resuming only does the missing job
+        let mut manifest = Manifest::default();
+        for index in [0, 1, 3] {
+            manifest.record(index, &queue.jobs()[index]);
+        }
+        manifest.save(&path).unwrap();
+        queue.set_resume(true);
+        ran.lock().unwrap().clear();
+        let results = queue.run_resumable(&CancelFlag::new(), |_| {}, run_job);
+        assert_eq!(*ran.lock().unwrap(), vec![2]);
+        assert_eq!(results[2], outcome(2));
+        assert_eq!(
+            results[0].as_ref().unwrap().warnings,
+            vec![format!(
+                "{} was completed by a previous run, skipped.",
+                outputs[0].display()
+            )]
+        );
+        assert!(!path.exists());
+
+        // an unreadable manifest stops the batch
+        fs::write(&path, "not json").unwrap();
+        let results = queue.run_resumable(&CancelFlag::new(), |_| {}, |_, _, _, _| unreachable!());
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(Result::is_err));
+        fs::remove_file(&path).unwrap();
+        for output in &outputs {
+            fs::remove_file(output).unwrap();
+        }
+    }
+
+    #[test]
+    fn empty_queue() {
+        let results = JobQueue::new().run_with(
+            &CancelFlag::new(),
+            |_| panic!("no event expected"),
+            |_, _, _, _| unreachable!(),
+        );
+        assert!(results.is_empty());
+    }
+}
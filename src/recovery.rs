@@ -0,0 +1,334 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::project::Project;
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Name of the autosaved session in the state directory.
+pub const RECOVERY_FILE: &str = "recovery.json";
+// written first then renamed: a crash while saving keeps the previous session
+const RECOVERY_TMP_FILE: &str = "recovery.json.tmp";
+// rewritten on each clean shutdown, older recovery files are leftovers
+const CLEAN_SHUTDOWN_FILE: &str = "clean-shutdown";
+const STATE_DIR_NAME: &str = "media-cutter";
+
+/// Quiet time after the last change before the session is autosaved.
+pub const AUTOSAVE_DELAY: Duration = Duration::from_secs(2);
+/// Longest a session keeps changing without being autosaved.
+pub const AUTOSAVE_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Editing session as autosaved, restored after a crash.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Session {
+    /// Project file the session was opened from or saved to, if any.
+    pub project_file: Option<PathBuf>,
+    /// Settings, markers and segments, saved or not.
+    pub project: Project,
+}
+
+/// `$XDG_STATE_HOME/media-cutter`, `~/.local/state/media-cutter` when the
+/// variable is unset. `None` without a home directory.
+pub fn state_dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty());
+    let state_home = match var("XDG_STATE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(var("HOME")?).join(".local").join("state"),
+    };
+    Some(state_home.join(STATE_DIR_NAME))
+}
+
+/// Whether a session autosaved at `saved` was closed normally afterwards,
+/// `clean_shutdown` being the time of the last clean shutdown if any.
+pub fn is_stale(saved: SystemTime, clean_shutdown: Option<SystemTime>) -> bool {
+    match clean_shutdown {
+        Some(clean_shutdown) => saved <= clean_shutdown,
+        None => false,
+    }
+}
+
+/// Recovery files of a state directory.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Recovery {
+    dir: PathBuf,
+}
+
+impl Recovery {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Recovery files in `state_dir`.
+    pub fn from_env() -> Option<Self> {
+        state_dir().map(Self::new)
+    }
+
+    pub fn recovery_file(&self) -> PathBuf {
+        self.dir.join(RECOVERY_FILE)
+    }
+
+    pub fn save(&self, session: &Session) -> Result<()> {
+        let json = serde_json::to_string(session)
+            .map_err(|e| format!("Could not serialize the session.\nError: {}", e))?;
+        let tmp_file = self.dir.join(RECOVERY_TMP_FILE);
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp_file, json))
+            .and_then(|_| fs::rename(&tmp_file, self.recovery_file()))
+            .map_err(|e| {
+                format!(
+                    "Could not write the recovery file {}.\nError: {}",
+                    self.recovery_file().display(),
+                    e
+                )
+            })
+    }
+
+    /// Session left by a crash, if any. Stale and unreadable recovery files
+    /// are removed: a broken one must not get in the way of each start.
+    pub fn pending(&self) -> Option<Session> {
+        let recovery_file = self.recovery_file();
+        let saved = modified(&recovery_file)?;
+        let session = if is_stale(saved, modified(&self.dir.join(CLEAN_SHUTDOWN_FILE))) {
+            None
+        } else {
+            fs::read_to_string(&recovery_file)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+        };
+        if session.is_none() {
+            self.discard();
+        }
+        session
+    }
+
+    /// Forget the autosaved session, e.g. when it is not restored.
+    pub fn discard(&self) {
+        let _ = fs::remove_file(self.recovery_file());
+    }
+
+    /// The autosaved session is not needed anymore, nor any left by another
+    /// window closed before.
+    pub fn clean_shutdown(&self) -> Result<()> {
+        self.discard();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        let clean_shutdown_file = self.dir.join(CLEAN_SHUTDOWN_FILE);
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&clean_shutdown_file, now.to_string()))
+            .map_err(|e| {
+                format!(
+                    "Could not write {}.\nError: {}",
+                    clean_shutdown_file.display(),
+                    e
+                )
+            })
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Coalesces the changes of a session into few autosaves: one once the
+/// changes stop for `delay`, or every `max_delay` while they go on, e.g.
+/// when dragging a slider.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Debouncer {
+    delay: Duration,
+    max_delay: Duration,
+    // first and last changes not saved yet
+    changes: Option<(Instant, Instant)>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            delay,
+            max_delay,
+            changes: None,
+        }
+    }
+
+    /// Record a change made at `now`.
+    pub fn touch(&mut self, now: Instant) {
+        self.changes = match self.changes {
+            Some((first, _)) => Some((first, now)),
+            None => Some((now, now)),
+        };
+    }
+
+    /// Whether the session is to be saved at `now`. True once for all the
+    /// changes recorded until then.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        match self.changes {
+            Some((first, last))
+                if now.duration_since(last) >= self.delay
+                    || now.duration_since(first) >= self.max_delay =>
+            {
+                self.changes = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+enum Message {
+    Save(Box<Session>),
+    Shutdown,
+}
+
+/// Writes the autosaved sessions on a thread of its own so that the main
+/// loop never waits for the disk.
+pub struct Autosaver {
+    sender: mpsc::Sender<Message>,
+    worker: thread::JoinHandle<()>,
+}
+
+impl Autosaver {
+    pub fn new(recovery: Recovery) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            // dropped without a shutdown: the session is kept as after a crash
+            while let Ok(message) = receiver.recv() {
+                // only the last of the queued sessions is worth writing
+                let mut session = None;
+                for message in Some(message).into_iter().chain(receiver.try_iter()) {
+                    match message {
+                        Message::Save(saved) => session = Some(saved),
+                        Message::Shutdown => {
+                            if let Err(e) = recovery.clean_shutdown() {
+                                error!("{}", e);
+                            }
+                            return;
+                        }
+                    }
+                }
+                if let Some(session) = session {
+                    if let Err(e) = recovery.save(&session) {
+                        error!("{}", e);
+                    }
+                }
+            }
+        });
+        Self { sender, worker }
+    }
+
+    /// Queue `session` to be written.
+    pub fn save(&self, session: Session) {
+        let _ = self.sender.send(Message::Save(Box::new(session)));
+    }
+
+    /// Record a clean shutdown once the queued sessions are handled.
+    pub fn shutdown(self) {
+        let _ = self.sender.send(Message::Shutdown);
+        let _ = self.worker.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("media_cutter_recovery_test_{}", name));
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    fn session(input_file: &str) -> Session {
+        let mut config = Config::new();
+        config.input_file = String::from(input_file);
+        Session {
+            project_file: Some(PathBuf::from("/recordings/talk.mcut")),
+            project: Project::new(config),
+        }
+    }
+
+    #[test]
+    fn staleness() {
+        let shutdown = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        assert!(!is_stale(shutdown, None));
+        assert!(!is_stale(
+            shutdown + Duration::from_millis(1),
+            Some(shutdown)
+        ));
+        assert!(is_stale(shutdown, Some(shutdown)));
+        assert!(is_stale(shutdown - Duration::from_secs(60), Some(shutdown)));
+    }
+
+    #[test]
+    fn debounce_coalescing() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut debouncer = Debouncer::new(Duration::from_secs(2), Duration::from_secs(30));
+        assert!(!debouncer.poll(at(0)));
+
+        // several quick edits make a single save
+        debouncer.touch(at(0));
+        debouncer.touch(at(1000));
+        debouncer.touch(at(1500));
+        assert!(!debouncer.poll(at(2000)));
+        assert!(!debouncer.poll(at(3400)));
+        assert!(debouncer.poll(at(3500)));
+        assert!(!debouncer.poll(at(10_000)));
+
+        // continuous edits are still saved from time to time
+        for second in 11..42 {
+            debouncer.touch(at(second * 1000));
+        }
+        assert!(debouncer.poll(at(41_000)));
+        assert!(!debouncer.poll(at(41_500)));
+    }
+
+    #[test]
+    fn pending_session() {
+        let recovery = Recovery::new(temp_dir("pending"));
+        assert_eq!(recovery.pending(), None);
+
+        // the last session replaces the previous one
+        let saved = session("/recordings/second.mkv");
+        recovery.save(&session("/recordings/first.mkv")).unwrap();
+        recovery.save(&saved).unwrap();
+        assert_eq!(recovery.pending(), Some(saved));
+
+        recovery.clean_shutdown().unwrap();
+        assert!(!recovery.recovery_file().exists());
+        assert_eq!(recovery.pending(), None);
+    }
+
+    #[test]
+    fn corrupt_recovery_file() {
+        let dir = temp_dir("corrupt");
+        let recovery = Recovery::new(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(recovery.recovery_file(), "{\"project_file\": null, \"proj").unwrap();
+
+        assert_eq!(recovery.pending(), None);
+        assert!(!recovery.recovery_file().exists());
+    }
+
+    #[test]
+    fn autosave_then_shutdown() {
+        let recovery = Recovery::new(temp_dir("autosaver"));
+        let autosaver = Autosaver::new(recovery.clone());
+        autosaver.save(session("/recordings/first.mkv"));
+        autosaver.save(session("/recordings/second.mkv"));
+        autosaver.shutdown();
+        assert_eq!(recovery.pending(), None);
+        assert!(recovery.dir.join(CLEAN_SHUTDOWN_FILE).exists());
+    }
+}
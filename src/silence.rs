@@ -0,0 +1,513 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::filtergraph::{FilterGraph, Label};
+
+lazy_static! {
+    static ref SILENCE_RE: Regex =
+        Regex::new(r#"silence_(?P<event>start|end):\s*(?P<time>-?[0-9\.]+)"#).unwrap();
+}
+
+// shorter parts are merged into their neighbours, trimming them would only
+// add rounding noise
+const MIN_SEGMENT_SECS: f64 = 0.001;
+
+// atempo accepts up to 100 but skips samples above 2, so faster speeds are chained
+const MAX_ATEMPO_FACTOR: f64 = 2.0;
+
+// a silence starting this close to the start, or to the end of the previous
+// one, still opens the selection
+const LEADING_SILENCE_TOLERANCE_SECS: f64 = 0.05;
+
+/// Level in dB under which the opening of a selection counts as silent, see
+/// `first_sound`.
+pub const FIRST_SOUND_NOISE_DB: f64 = -40.0;
+/// Shortest opening silence trimmed, in seconds.
+pub const FIRST_SOUND_MIN_SILENCE: f64 = 0.1;
+/// Seconds kept before the first sound, so that it does not start abruptly.
+pub const FIRST_SOUND_LEAD_IN: f64 = 0.25;
+
+/// Part of the selection played at `speed`, times in seconds from its start.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    /// 1 for speech, the speedup factor for silences.
+    pub speed: f64,
+}
+
+impl Segment {
+    pub fn is_silent(&self) -> bool {
+        self.speed != 1.0
+    }
+
+    /// Seconds of output once played at `speed`.
+    pub fn output_duration(&self) -> f64 {
+        (self.end - self.start) / self.speed
+    }
+}
+
+/// Silent intervals, `(start, end)` in seconds, reported by the `silencedetect`
+/// filter in the ffmpeg `output`. A silence still going on when the input
+/// ends lasts until `duration`.
+pub fn parse_silences(output: &str, duration: f64) -> Vec<(f64, f64)> {
+    let mut silences = Vec::new();
+    let mut start = None;
+    for caps in SILENCE_RE.captures_iter(output) {
+        let time = match caps["time"].parse::<f64>() {
+            Ok(time) => time,
+            Err(_) => continue,
+        };
+        if &caps["event"] == "start" {
+            // the start is reported a bit before zero when the input opens silent
+            start = Some(time.max(0.0));
+        } else if let Some(start) = start.take() {
+            silences.push((start, time));
+        }
+    }
+    if let Some(start) = start {
+        silences.push((start, duration));
+    }
+    silences
+}
+
+/// Segments covering `0..duration` one after the other, speech at normal
+/// speed alternating with `silences` played `factor` times faster.
+///
+/// Silences may be unsorted, overlapping or past `duration`: they are merged
+/// and clamped.
+pub fn plan_segments(silences: &[(f64, f64)], duration: f64, factor: f64) -> Vec<Segment> {
+    let mut silences = silences.to_vec();
+    silences.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut segments: Vec<Segment> = Vec::with_capacity(silences.len() * 2 + 1);
+    let mut position = 0.0;
+    for (start, end) in silences {
+        let start = start.max(position);
+        let end = end.min(duration);
+        if end - start < MIN_SEGMENT_SECS {
+            continue;
+        }
+
+        if start - position >= MIN_SEGMENT_SECS {
+            segments.push(Segment {
+                start: position,
+                end: start,
+                speed: 1.0,
+            });
+            segments.push(Segment {
+                start,
+                end,
+                speed: factor,
+            });
+        } else {
+            match segments.last_mut() {
+                // nothing heard in between: the previous silence goes on
+                Some(last) => last.end = end,
+                None => segments.push(Segment {
+                    start: position,
+                    end,
+                    speed: factor,
+                }),
+            }
+        }
+        position = end;
+    }
+
+    if duration - position >= MIN_SEGMENT_SECS {
+        segments.push(Segment {
+            start: position,
+            end: duration,
+            speed: 1.0,
+        });
+    } else if let Some(last) = segments.last_mut() {
+        last.end = duration;
+    }
+
+    segments
+}
+
+/// Seconds from the start of the selection to its first sound, given the
+/// `silences` found in it: the end of the silence it opens with, 0 when it
+/// opens with sound. `None` when it stays silent up to `duration`.
+pub fn first_sound(silences: &[(f64, f64)], duration: f64) -> Option<f64> {
+    let mut silences = silences.to_vec();
+    silences.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    // overlapping or back to back silences make a single one
+    let mut position = 0.0_f64;
+    for (start, end) in silences {
+        if start > position + LEADING_SILENCE_TOLERANCE_SECS {
+            break;
+        }
+        position = position.max(end);
+    }
+
+    if duration - position < MIN_SEGMENT_SECS {
+        None
+    } else {
+        Some(position)
+    }
+}
+
+/// Where to start the selection for it to open `FIRST_SOUND_LEAD_IN` before
+/// its `first_sound`, in seconds from its current start.
+pub fn lead_in_start(first_sound: f64) -> f64 {
+    (first_sound - FIRST_SOUND_LEAD_IN).max(0.0)
+}
+
+/// Seconds of output once the silences are sped up.
+pub fn output_duration(segments: &[Segment]) -> f64 {
+    segments.iter().map(Segment::output_duration).sum()
+}
+
+/// Position in the output of `time`, seconds of the selection, once the
+/// silences are sped up.
+pub fn output_time(segments: &[Segment], time: f64) -> f64 {
+    segments
+        .iter()
+        .map(|segment| (time.min(segment.end) - segment.start).max(0.0) / segment.speed)
+        .sum()
+}
+
+/// `atempo` filters playing the audio `speed` times faster, none at normal speed.
+pub fn atempo_filters(speed: f64) -> Vec<String> {
+    let mut filters = Vec::new();
+    let mut remaining = speed;
+    while remaining > MAX_ATEMPO_FACTOR {
+        filters.push(format!("atempo={}", MAX_ATEMPO_FACTOR));
+        remaining /= MAX_ATEMPO_FACTOR;
+    }
+    if remaining != 1.0 {
+        filters.push(format!("atempo={}", remaining));
+    }
+    filters
+}
+
+/// Graph retiming `segments`, then joining them back and applying
+/// `video_filters` and `audio_filters` to the result. Video then audio are its
+/// outputs.
+///
+/// Each segment is read from its own input, of the same index, cut to it: a
+/// single input split into the segments would buffer all of it. `video`, if
+/// any, is the stream read from each of them, e.g. `v:0`, and `audio` the
+/// audio streams mixed at their gain in dB, by index among the audio streams.
+///
+/// The pictures of a segment are shown `speed` times faster and its audio is
+/// tempo changed by the same factor, so both stay in sync.
+pub fn speedup_graph(
+    segments: &[Segment],
+    video: Option<&str>,
+    audio: &[(usize, f64)],
+    video_filters: &[String],
+    audio_filters: &[String],
+) -> FilterGraph {
+    let mut graph = FilterGraph::new();
+    let count = segments.len();
+
+    let mut parts = Vec::with_capacity(count * 2);
+    for (index, segment) in segments.iter().enumerate() {
+        if let Some(video) = video {
+            let setpts = if segment.is_silent() {
+                format!("setpts=(PTS-STARTPTS)/{}", segment.speed)
+            } else {
+                String::from("setpts=PTS-STARTPTS")
+            };
+            parts.push(graph.chain(&Label::input(index, video), &[setpts]));
+        }
+
+        let tracks: Vec<(Label, f64)> = audio
+            .iter()
+            .map(|&(stream_index, gain_db)| {
+                (Label::input(index, &format!("a:{}", stream_index)), gain_db)
+            })
+            .collect();
+        let mix = graph.mix(&tracks);
+        let mut filters = vec![String::from("asetpts=PTS-STARTPTS")];
+        filters.extend(atempo_filters(segment.speed));
+        parts.push(graph.chain(&mix, &filters));
+    }
+
+    let video_count = if video.is_some() { 1 } else { 0 };
+    let parts: Vec<&Label> = parts.iter().collect();
+    let mut joined = graph.node(
+        &parts,
+        &format!("concat=n={}:v={}:a=1", count, video_count),
+        video_count + 1,
+    );
+
+    let audio = joined.pop().unwrap();
+    if let Some(video) = joined.pop() {
+        let video = if video_filters.is_empty() {
+            video
+        } else {
+            graph.chain(&video, video_filters)
+        };
+        graph.output(video);
+    }
+    let audio = if audio_filters.is_empty() {
+        audio
+    } else {
+        graph.chain(&audio, audio_filters)
+    };
+    graph.output(audio);
+
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEED: f64 = 4.0;
+
+    fn speech(start: f64, end: f64) -> Segment {
+        Segment {
+            start,
+            end,
+            speed: 1.0,
+        }
+    }
+
+    fn silence(start: f64, end: f64) -> Segment {
+        Segment {
+            start,
+            end,
+            speed: SPEED,
+        }
+    }
+
+    // segments follow each other from 0 to `duration`, alternating
+    fn assert_covers(segments: &[Segment], duration: f64) {
+        assert_eq!(segments.first().unwrap().start, 0.0);
+        assert_eq!(segments.last().unwrap().end, duration);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_ne!(pair[0].is_silent(), pair[1].is_silent());
+        }
+        for segment in segments {
+            assert!(segment.end > segment.start);
+        }
+    }
+
+    #[test]
+    fn silences_parsing() {
+        let output = "\
+Input #0, wav, from 'in.wav':
+[silencedetect @ 0x55d5] silence_start: -0.00133333
+[silencedetect @ 0x55d5] silence_end: 1.52 | silence_duration: 1.52133
+size=N/A time=00:00:05.00 bitrate=N/A speed= 410x
+[silencedetect @ 0x55d5] silence_start: 3.25
+[silencedetect @ 0x55d5] silence_end: 4.1 | silence_duration: 0.85
+[silencedetect @ 0x55d5] silence_start: 8.5
+";
+        assert_eq!(
+            parse_silences(output, 10.0),
+            vec![(0.0, 1.52), (3.25, 4.1), (8.5, 10.0)]
+        );
+
+        assert!(parse_silences("", 10.0).is_empty());
+        assert!(parse_silences("no silence here", 10.0).is_empty());
+        // an end without start is ignored
+        assert!(parse_silences("silence_end: 2 | silence_duration: 2", 10.0).is_empty());
+    }
+
+    #[test]
+    fn no_silence() {
+        let segments = plan_segments(&[], 10.0, SPEED);
+        assert_eq!(segments, vec![speech(0.0, 10.0)]);
+        assert_eq!(output_duration(&segments), 10.0);
+
+        assert!(plan_segments(&[], 0.0, SPEED).is_empty());
+    }
+
+    #[test]
+    fn first_sound_of_parsed_silences() {
+        let output = "\
+[silencedetect @ 0x55d5] silence_start: -0.00133333
+[silencedetect @ 0x55d5] silence_end: 2.48 | silence_duration: 2.48133
+[silencedetect @ 0x55d5] silence_start: 7.5
+[silencedetect @ 0x55d5] silence_end: 8.25 | silence_duration: 0.75
+";
+        let silences = parse_silences(output, 30.0);
+        assert_eq!(first_sound(&silences, 30.0), Some(2.48));
+        assert_eq!(lead_in_start(2.48), 2.48 - FIRST_SOUND_LEAD_IN);
+
+        // opening with sound
+        assert_eq!(first_sound(&[(1.0, 3.0)], 30.0), Some(0.0));
+        assert_eq!(first_sound(&[], 30.0), Some(0.0));
+        // a sound right at the start leaves no room for the lead-in
+        assert_eq!(lead_in_start(0.1), 0.0);
+    }
+
+    #[test]
+    fn leading_silences_merge() {
+        // unsorted, overlapping, then back to back within the tolerance
+        let silences = [(4.03, 6.0), (0.0, 2.0), (1.5, 4.0), (9.0, 10.0)];
+        assert_eq!(first_sound(&silences, 12.0), Some(6.0));
+
+        // the first silence reported a bit after the start still opens it
+        assert_eq!(first_sound(&[(0.02, 1.5)], 12.0), Some(1.5));
+        assert_eq!(first_sound(&[(0.2, 1.5)], 12.0), Some(0.0));
+    }
+
+    #[test]
+    fn silent_selection() {
+        // still going on when the selection ends
+        let silences = parse_silences("silence_start: 0\n", 5.0);
+        assert_eq!(first_sound(&silences, 5.0), None);
+        assert_eq!(first_sound(&[(0.0, 2.0), (2.0, 5.0)], 5.0), None);
+        assert_eq!(first_sound(&[(0.0, 4.9995)], 5.0), None);
+    }
+
+    #[test]
+    fn alternating_segments() {
+        let silences = [(1.0, 3.0), (4.0, 8.0), (9.0, 9.5)];
+        let segments = plan_segments(&silences, 12.0, SPEED);
+        assert_eq!(
+            segments,
+            vec![
+                speech(0.0, 1.0),
+                silence(1.0, 3.0),
+                speech(3.0, 4.0),
+                silence(4.0, 8.0),
+                speech(8.0, 9.0),
+                silence(9.0, 9.5),
+                speech(9.5, 12.0),
+            ]
+        );
+        assert_covers(&segments, 12.0);
+        // 1 + 0.5 + 1 + 1 + 1 + 0.125 + 2.5
+        assert_eq!(output_duration(&segments), 7.125);
+    }
+
+    #[test]
+    fn many_segments() {
+        let silences: Vec<(f64, f64)> = (0..100)
+            .map(|index| (f64::from(index) * 2.0 + 1.0, f64::from(index) * 2.0 + 1.5))
+            .collect();
+        let segments = plan_segments(&silences, 200.0, SPEED);
+        assert_eq!(segments.len(), 201);
+        assert_covers(&segments, 200.0);
+        assert_eq!(
+            segments
+                .iter()
+                .filter(|segment| segment.is_silent())
+                .count(),
+            100
+        );
+        assert_eq!(output_duration(&segments), 150.0 + 50.0 / SPEED);
+    }
+
+    #[test]
+    fn leading_and_trailing_silences() {
+        let segments = plan_segments(&[(0.0, 2.0), (7.0, 10.0)], 10.0, SPEED);
+        assert_eq!(
+            segments,
+            vec![silence(0.0, 2.0), speech(2.0, 7.0), silence(7.0, 10.0)]
+        );
+        assert_covers(&segments, 10.0);
+
+        // nothing but silence
+        let segments = plan_segments(&[(0.0, 10.0)], 10.0, SPEED);
+        assert_eq!(segments, vec![silence(0.0, 10.0)]);
+        assert_eq!(output_duration(&segments), 2.5);
+    }
+
+    #[test]
+    fn irregular_silences() {
+        // unsorted, overlapping, touching and past the end
+        let silences = [(6.0, 12.0), (1.0, 3.0), (2.0, 4.0), (4.0, 5.0)];
+        let segments = plan_segments(&silences, 10.0, SPEED);
+        assert_eq!(
+            segments,
+            vec![
+                speech(0.0, 1.0),
+                silence(1.0, 5.0),
+                speech(5.0, 6.0),
+                silence(6.0, 10.0)
+            ]
+        );
+        assert_covers(&segments, 10.0);
+
+        // a silence inside another one, empty ones and one after the end
+        let silences = [(1.0, 5.0), (2.0, 3.0), (6.0, 6.0), (11.0, 12.0)];
+        let segments = plan_segments(&silences, 10.0, SPEED);
+        assert_eq!(
+            segments,
+            vec![speech(0.0, 1.0), silence(1.0, 5.0), speech(5.0, 10.0)]
+        );
+
+        // slivers of speech are absorbed by the silences around them
+        let silences = [(0.0005, 2.0), (2.0004, 4.0), (9.9995, 10.0)];
+        let segments = plan_segments(&silences, 10.0, SPEED);
+        assert_eq!(segments, vec![silence(0.0, 4.0), speech(4.0, 10.0)]);
+    }
+
+    #[test]
+    fn output_times() {
+        let segments = [speech(0.0, 2.0), silence(2.0, 6.0), speech(6.0, 8.0)];
+        assert_eq!(output_time(&segments, 0.0), 0.0);
+        assert_eq!(output_time(&segments, 1.5), 1.5);
+        assert_eq!(output_time(&segments, 4.0), 2.5);
+        assert_eq!(output_time(&segments, 7.0), 4.0);
+        assert_eq!(output_time(&segments, 8.0), output_duration(&segments));
+    }
+
+    #[test]
+    fn atempo_chain() {
+        assert!(atempo_filters(1.0).is_empty());
+        assert_eq!(atempo_filters(1.5), vec!["atempo=1.5"]);
+        assert_eq!(atempo_filters(2.0), vec!["atempo=2"]);
+        assert_eq!(atempo_filters(4.0), vec!["atempo=2", "atempo=2"]);
+        assert_eq!(
+            atempo_filters(5.0),
+            vec!["atempo=2", "atempo=2", "atempo=1.25"]
+        );
+    }
+
+    #[test]
+    fn graph_with_video() {
+        let segments = [speech(0.0, 1.5), silence(1.5, 3.0), speech(3.0, 5.0)];
+        let graph = speedup_graph(
+            &segments,
+            Some("v:0"),
+            &[(0, 0.0)],
+            &[String::from("hqdn3d")],
+            &[String::from("volume=0dB")],
+        );
+        assert_eq!(
+            graph.to_filter_complex(),
+            "[0:v:0]setpts=PTS-STARTPTS[s0];\
+             [0:a:0]asetpts=PTS-STARTPTS[s1];\
+             [1:v:0]setpts=(PTS-STARTPTS)/4[s2];\
+             [1:a:0]asetpts=PTS-STARTPTS,atempo=2,atempo=2[s3];\
+             [2:v:0]setpts=PTS-STARTPTS[s4];\
+             [2:a:0]asetpts=PTS-STARTPTS[s5];\
+             [s0][s1][s2][s3][s4][s5]concat=n=3:v=1:a=1[s6][s7];\
+             [s6]hqdn3d[s8];[s7]volume=0dB[s9]"
+        );
+        assert_eq!(graph.map_args(), vec!["-map", "[s8]", "-map", "[s9]"]);
+    }
+
+    #[test]
+    fn graph_audio_only() {
+        let segments = [silence(0.0, 2.0), speech(2.0, 5.0)];
+        let graph = speedup_graph(&segments, None, &[(1, 0.0)], &[], &[]);
+        assert_eq!(
+            graph.to_filter_complex(),
+            "[0:a:1]asetpts=PTS-STARTPTS,atempo=2,atempo=2[s0];\
+             [1:a:1]asetpts=PTS-STARTPTS[s1];\
+             [s0][s1]concat=n=2:v=0:a=1[s2]"
+        );
+        assert_eq!(graph.map_args(), vec!["-map", "[s2]"]);
+
+        // mixed tracks are summed in each segment
+        let graph = speedup_graph(&segments, None, &[(0, 0.0), (1, -6.0)], &[], &[]);
+        assert!(graph.to_filter_complex().starts_with(
+            "[0:a:0]volume=0dB[s0];[0:a:1]volume=-6dB[s1];\
+             [s0][s1]amix=inputs=2:normalize=0[s2];\
+             [s2]asetpts=PTS-STARTPTS,atempo=2,atempo=2[s3];"
+        ));
+    }
+}
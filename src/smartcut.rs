@@ -0,0 +1,570 @@
+//! Cuts re-encoding only the frames around the cut points.
+//!
+//! A lossless cut can only start on a keyframe. A smart cut re-encodes the
+//! head of the selection, up to its first keyframe, and the tail, from its
+//! last keyframe, copies the middle as is, then joins the pieces with the
+//! concat demuxer. The edges are encoded with settings close to the source
+//! but not identical: players may stutter or glitch at the joins.
+
+use crate::probe::MediaInfo;
+
+type Result<T> = std::result::Result<T, String>;
+
+// keyframes closer than this to a cut point are taken as the cut point, and
+// shorter pieces are dropped
+const MIN_PIECE_SECS: f64 = 0.001;
+
+/// Reported with every smart cut, see the module documentation.
+pub const DISCONTINUITY_WARNING: &str =
+    "Smart cut: the re-encoded edges may not match the copied middle exactly, playback can stutter at the joins.";
+
+// source codec, then the encoder producing it
+const ENCODERS: [(&str, &str); 6] = [
+    ("h264", "libx264"),
+    ("hevc", "libx265"),
+    ("vp9", "libvpx-vp9"),
+    ("vp8", "libvpx"),
+    ("mpeg4", "mpeg4"),
+    ("av1", "libaom-av1"),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PieceKind {
+    /// Re-encoded, it does not start on a keyframe.
+    Encode,
+    /// Copied as is, from a keyframe.
+    Copy,
+}
+
+/// Part of the selection, times in seconds of the input.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Piece {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+// profiles the encoders accept, as ffprobe names them once lowercased and
+// without spaces or colons
+const PROFILES: [(&str, &[&str]); 4] = [
+    (
+        "libx264",
+        &["baseline", "main", "high", "high10", "high422", "high444"],
+    ),
+    ("libx265", &["main", "main10", "mainstillpicture"]),
+    (
+        "libvpx-vp9",
+        &["profile0", "profile1", "profile2", "profile3"],
+    ),
+    ("libaom-av1", &["main", "high", "professional"]),
+];
+
+/// Video encoder producing frames close to those of the source.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EncoderSettings {
+    pub encoder: &'static str,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Bits per second, unknown for some containers.
+    pub bit_rate: Option<u64>,
+    pub pix_fmt: Option<String>,
+    /// Profile option of the encoder, left to it when the probed one has no
+    /// equivalent.
+    pub profile: Option<String>,
+    /// As probed: H.264 level 4.1 is 41, HEVC level 4.1 is 123.
+    pub level: Option<i32>,
+    /// Such as `1/12800`.
+    pub time_base: Option<String>,
+}
+
+impl EncoderSettings {
+    /// Settings matching the first video stream of `info`: same codec and
+    /// size, and about the same bitrate.
+    ///
+    /// Without a bitrate for the stream, the container one is used, less the
+    /// known bitrates of the other streams.
+    pub fn matching(info: &MediaInfo) -> Result<Self> {
+        let video = info
+            .streams
+            .iter()
+            .find(|stream| stream.kind == "video")
+            .ok_or_else(|| String::from("Error: input file has no video stream."))?;
+
+        let codec = video.codec.as_deref().unwrap_or("unknown");
+        let encoder = ENCODERS
+            .iter()
+            .find(|(source, _)| *source == codec)
+            .map(|(_, encoder)| *encoder)
+            .ok_or_else(|| {
+                format!(
+                    "Error: smart cut cannot re-encode {} video, it only knows {}.",
+                    codec,
+                    ENCODERS
+                        .iter()
+                        .map(|(source, _)| *source)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+
+        let bit_rate = video.bit_rate.or_else(|| {
+            let others: u64 = info
+                .streams
+                .iter()
+                .filter(|stream| stream.index != video.index)
+                .filter_map(|stream| stream.bit_rate)
+                .sum();
+            info.bit_rate
+                .and_then(|total| total.checked_sub(others))
+                .filter(|&rate| rate > 0)
+        });
+
+        Ok(Self {
+            encoder,
+            width: video.width,
+            height: video.height,
+            bit_rate,
+            pix_fmt: video.pix_fmt.clone(),
+            profile: video
+                .profile
+                .as_deref()
+                .and_then(|profile| encoder_profile(encoder, profile)),
+            level: video.level,
+            time_base: video.time_base.clone(),
+        })
+    }
+
+    /// ffmpeg output options encoding with these settings.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![String::from("-c:v"), String::from(self.encoder)];
+        if let Some(bit_rate) = self.bit_rate {
+            args.push(String::from("-b:v"));
+            args.push(bit_rate.to_string());
+        }
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            args.push(String::from("-s"));
+            args.push(format!("{}x{}", width, height));
+        }
+        if let Some(ref pix_fmt) = self.pix_fmt {
+            args.push(String::from("-pix_fmt"));
+            args.push(pix_fmt.clone());
+        }
+        if let Some(ref profile) = self.profile {
+            args.push(String::from("-profile:v"));
+            args.push(profile.clone());
+        }
+        match (self.encoder, self.level) {
+            ("libx264", Some(level)) => {
+                args.push(String::from("-level:v"));
+                args.push(format!("{}.{}", level / 10, level % 10));
+            }
+            // libx265 ignores -level
+            ("libx265", Some(level)) => {
+                args.push(String::from("-x265-params"));
+                args.push(format!("level-idc={:.1}", f64::from(level) / 30.0));
+            }
+            _ => {}
+        }
+        if let Some(ref time_base) = self.time_base {
+            args.push(String::from("-enc_time_base:v"));
+            args.push(time_base.clone());
+        }
+        args
+    }
+}
+
+// `Constrained Baseline` is `baseline`, `High 4:2:2` is `high422`, `Profile 0`
+// is `0` for libvpx-vp9
+fn encoder_profile(encoder: &str, probed: &str) -> Option<String> {
+    let name: String = probed
+        .to_lowercase()
+        .trim_start_matches("constrained ")
+        .trim_end_matches(" predictive")
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    let (_, known) = PROFILES.iter().find(|(known, _)| *known == encoder)?;
+    if !known.contains(&name.as_str()) {
+        return None;
+    }
+    Some(name.trim_start_matches("profile").to_owned())
+}
+
+/// What a smart cut needs to know of the input, probed before planning.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Source {
+    pub encoder: EncoderSettings,
+    /// Seconds, sorted.
+    pub keyframes: Vec<f64>,
+}
+
+/// Pieces covering `from..to` one after the other: the head up to the first
+/// keyframe and the tail from the last keyframe are encoded, what lies
+/// between is copied.
+///
+/// Without two keyframes in the selection nothing can be copied and the
+/// whole selection is a single encoded piece.
+pub fn plan_pieces(from: f64, to: f64, keyframes: &[f64]) -> Vec<Piece> {
+    if to - from < MIN_PIECE_SECS {
+        return Vec::new();
+    }
+
+    // probed times are rounded: keyframes a hair outside the selection count
+    let inside = |time: f64| time > from - MIN_PIECE_SECS && time < to + MIN_PIECE_SECS;
+    let first = keyframes
+        .iter()
+        .copied()
+        .filter(|&time| inside(time))
+        .reduce(f64::min);
+    let last = keyframes
+        .iter()
+        .copied()
+        .filter(|&time| inside(time))
+        .reduce(f64::max);
+
+    let (copy_start, copy_end) = match (first, last) {
+        (Some(first), Some(last)) if last - first >= MIN_PIECE_SECS => (first, last),
+        _ => {
+            return vec![Piece {
+                kind: PieceKind::Encode,
+                start: from,
+                end: to,
+            }]
+        }
+    };
+
+    let mut pieces = Vec::with_capacity(3);
+    if copy_start - from >= MIN_PIECE_SECS {
+        pieces.push(Piece {
+            kind: PieceKind::Encode,
+            start: from,
+            end: copy_start,
+        });
+    }
+    // on the keyframes themselves: seeking a bit before one lands on the previous
+    pieces.push(Piece {
+        kind: PieceKind::Copy,
+        start: copy_start,
+        end: copy_end,
+    });
+    if to - copy_end >= MIN_PIECE_SECS {
+        pieces.push(Piece {
+            kind: PieceKind::Encode,
+            start: copy_end,
+            end: to,
+        });
+    }
+    pieces
+}
+
+/// Seconds of output once the pieces are joined.
+pub fn output_duration(pieces: &[Piece]) -> f64 {
+    pieces.iter().map(Piece::duration).sum()
+}
+
+/// Concat demuxer script joining `files` in order.
+pub fn concat_list(files: &[String]) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for file in files {
+        // quotes cannot be escaped inside quotes: close, escape, reopen
+        list.push_str(&format!("file '{}'\n", file.replace('\'', r"'\''")));
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a keyframe every two seconds
+    const KEYFRAMES: [f64; 6] = [0.0, 2.0, 4.0, 6.0, 8.0, 10.0];
+    // one frame at 25 fps
+    const FRAME: f64 = 0.04;
+
+    fn encode(start: f64, end: f64) -> Piece {
+        Piece {
+            kind: PieceKind::Encode,
+            start,
+            end,
+        }
+    }
+
+    fn copy(start: f64, end: f64) -> Piece {
+        Piece {
+            kind: PieceKind::Copy,
+            start,
+            end,
+        }
+    }
+
+    // pieces follow each other from `from` to `to`, copies between keyframes
+    fn assert_covers(pieces: &[Piece], from: f64, to: f64, keyframes: &[f64]) {
+        assert!((pieces.first().unwrap().start - from).abs() < MIN_PIECE_SECS);
+        assert!((pieces.last().unwrap().end - to).abs() < MIN_PIECE_SECS);
+        for pair in pieces.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            assert_ne!(pair[0].kind, pair[1].kind);
+        }
+        for piece in pieces {
+            assert!(piece.duration() >= MIN_PIECE_SECS);
+            if piece.kind == PieceKind::Copy {
+                assert!(keyframes.contains(&piece.start));
+                assert!(keyframes.contains(&piece.end));
+            }
+        }
+        assert!((output_duration(pieces) - (to - from)).abs() < FRAME);
+    }
+
+    #[test]
+    fn cut_between_keyframes() {
+        let pieces = plan_pieces(1.5, 7.25, &KEYFRAMES);
+        assert_eq!(
+            pieces,
+            vec![encode(1.5, 2.0), copy(2.0, 6.0), encode(6.0, 7.25)]
+        );
+        assert_covers(&pieces, 1.5, 7.25, &KEYFRAMES);
+    }
+
+    #[test]
+    fn cut_on_keyframes() {
+        // nothing to encode
+        let pieces = plan_pieces(2.0, 8.0, &KEYFRAMES);
+        assert_eq!(pieces, vec![copy(2.0, 8.0)]);
+
+        // starting on a keyframe, ending between two
+        let pieces = plan_pieces(4.0, 9.0, &KEYFRAMES);
+        assert_eq!(pieces, vec![copy(4.0, 8.0), encode(8.0, 9.0)]);
+        assert_covers(&pieces, 4.0, 9.0, &KEYFRAMES);
+
+        // ending on a keyframe
+        let pieces = plan_pieces(0.5, 6.0, &KEYFRAMES);
+        assert_eq!(pieces, vec![encode(0.5, 2.0), copy(2.0, 6.0)]);
+        assert_covers(&pieces, 0.5, 6.0, &KEYFRAMES);
+    }
+
+    #[test]
+    fn keyframes_close_to_cut_points() {
+        // probed times are rounded, they snap to the cut points
+        let keyframes = [1.9996, 4.0, 6.0004];
+        let pieces = plan_pieces(2.0, 6.0, &keyframes);
+        assert_eq!(pieces, vec![copy(1.9996, 6.0004)]);
+        assert_covers(&pieces, 2.0, 6.0, &keyframes);
+
+        // further away, they do not
+        let keyframes = [2.01, 4.0, 5.99];
+        let pieces = plan_pieces(2.0, 6.0, &keyframes);
+        assert_eq!(
+            pieces,
+            vec![encode(2.0, 2.01), copy(2.01, 5.99), encode(5.99, 6.0)]
+        );
+        assert_covers(&pieces, 2.0, 6.0, &keyframes);
+    }
+
+    #[test]
+    fn too_few_keyframes() {
+        // none inside the selection
+        assert_eq!(plan_pieces(2.5, 3.5, &KEYFRAMES), vec![encode(2.5, 3.5)]);
+        // a single one: there is nothing between the head and the tail
+        assert_eq!(plan_pieces(1.0, 3.0, &KEYFRAMES), vec![encode(1.0, 3.0)]);
+        // no keyframe probed at all
+        assert_eq!(plan_pieces(1.0, 3.0, &[]), vec![encode(1.0, 3.0)]);
+        // empty selection
+        assert!(plan_pieces(3.0, 3.0, &KEYFRAMES).is_empty());
+    }
+
+    #[test]
+    fn unsorted_keyframes_outside_the_selection() {
+        let keyframes = [12.0, 6.0, 0.0, 3.0, 9.0];
+        let pieces = plan_pieces(2.0, 10.0, &keyframes);
+        assert_eq!(
+            pieces,
+            vec![encode(2.0, 3.0), copy(3.0, 9.0), encode(9.0, 10.0)]
+        );
+        assert_covers(&pieces, 2.0, 10.0, &keyframes);
+    }
+
+    #[test]
+    fn output_lasts_as_long_as_the_selection() {
+        // irregular GOPs, cut points at every frame of a second
+        let keyframes = [0.0, 0.52, 1.84, 2.0, 3.16, 4.48, 5.0];
+        for step in 0..25 {
+            let from = 0.2 + step as f64 * FRAME;
+            let to = 4.0 + step as f64 * FRAME;
+            let pieces = plan_pieces(from, to, &keyframes);
+            assert_covers(&pieces, from, to, &keyframes);
+        }
+    }
+
+    fn media_info(json: &str) -> MediaInfo {
+        MediaInfo::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn matching_encoders() {
+        let info = media_info(
+            r#"{
+    "streams": [
+        { "index": 0, "codec_type": "audio", "codec_name": "aac", "bit_rate": "128000" },
+        { "index": 1, "codec_type": "video", "codec_name": "hevc", "width": 3840, "height": 2160, "bit_rate": "20000000",
+          "pix_fmt": "yuv420p10le", "profile": "Main 10", "level": 153, "time_base": "1/90000" }
+    ],
+    "format": { "format_name": "mov,mp4", "bit_rate": "20200000" }
+}"#,
+        );
+        let settings = EncoderSettings::matching(&info).unwrap();
+        assert_eq!(
+            settings,
+            EncoderSettings {
+                encoder: "libx265",
+                width: Some(3840),
+                height: Some(2160),
+                bit_rate: Some(20_000_000),
+                pix_fmt: Some(String::from("yuv420p10le")),
+                profile: Some(String::from("main10")),
+                level: Some(153),
+                time_base: Some(String::from("1/90000")),
+            }
+        );
+        assert_eq!(
+            settings.args(),
+            vec![
+                "-c:v",
+                "libx265",
+                "-b:v",
+                "20000000",
+                "-s",
+                "3840x2160",
+                "-pix_fmt",
+                "yuv420p10le",
+                "-profile:v",
+                "main10",
+                "-x265-params",
+                "level-idc=5.1",
+                "-enc_time_base:v",
+                "1/90000",
+            ]
+        );
+
+        for (codec, encoder) in ENCODERS.iter() {
+            let info = media_info(&format!(
+                r#"{{ "streams": [{{ "index": 0, "codec_type": "video", "codec_name": "{}" }}], "format": {{ "format_name": "matroska" }} }}"#,
+                codec
+            ));
+            let settings = EncoderSettings::matching(&info).unwrap();
+            assert_eq!(settings.encoder, *encoder);
+            assert_eq!(settings.args(), vec!["-c:v", *encoder]);
+        }
+    }
+
+    #[test]
+    fn matching_profiles() {
+        let info = media_info(
+            r#"{
+    "streams": [
+        { "index": 0, "codec_type": "video", "codec_name": "h264", "pix_fmt": "yuv420p",
+          "profile": "Constrained Baseline", "level": 31, "time_base": "1/12800" }
+    ],
+    "format": { "format_name": "mov,mp4" }
+}"#,
+        );
+        assert_eq!(
+            EncoderSettings::matching(&info).unwrap().args(),
+            vec![
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-profile:v",
+                "baseline",
+                "-level:v",
+                "3.1",
+                "-enc_time_base:v",
+                "1/12800",
+            ]
+        );
+
+        assert_eq!(
+            encoder_profile("libx264", "High 4:4:4 Predictive").as_deref(),
+            Some("high444")
+        );
+        assert_eq!(
+            encoder_profile("libx264", "High 4:2:2").as_deref(),
+            Some("high422")
+        );
+        assert_eq!(
+            encoder_profile("libvpx-vp9", "Profile 2").as_deref(),
+            Some("2")
+        );
+        // no equivalent, left to the encoder
+        assert_eq!(encoder_profile("libx265", "Rext"), None);
+        assert_eq!(encoder_profile("mpeg4", "Simple Profile"), None);
+    }
+
+    #[test]
+    fn bit_rate_from_the_container() {
+        // matroska rarely stores stream bitrates: the audio ones are taken off
+        let info = media_info(
+            r#"{
+    "streams": [
+        { "index": 0, "codec_type": "video", "codec_name": "h264", "width": 1280, "height": 720 },
+        { "index": 1, "codec_type": "audio", "codec_name": "aac", "bit_rate": "192000" },
+        { "index": 2, "codec_type": "audio", "codec_name": "opus" }
+    ],
+    "format": { "format_name": "matroska,webm", "bit_rate": "2192000" }
+}"#,
+        );
+        let settings = EncoderSettings::matching(&info).unwrap();
+        assert_eq!(settings.bit_rate, Some(2_000_000));
+
+        // nonsense totals leave the bitrate to the encoder
+        let info = media_info(
+            r#"{
+    "streams": [
+        { "index": 0, "codec_type": "video", "codec_name": "h264" },
+        { "index": 1, "codec_type": "audio", "codec_name": "aac", "bit_rate": "192000" }
+    ],
+    "format": { "format_name": "matroska,webm", "bit_rate": "128000" }
+}"#,
+        );
+        assert_eq!(EncoderSettings::matching(&info).unwrap().bit_rate, None);
+    }
+
+    #[test]
+    fn unmatched_sources() {
+        let info = media_info(
+            r#"{ "streams": [{ "index": 0, "codec_type": "video", "codec_name": "prores" }], "format": { "format_name": "mov" } }"#,
+        );
+        assert_eq!(
+            EncoderSettings::matching(&info),
+            Err(String::from(
+                "Error: smart cut cannot re-encode prores video, it only knows h264, hevc, vp9, vp8, mpeg4, av1."
+            ))
+        );
+
+        let info = media_info(
+            r#"{ "streams": [{ "index": 0, "codec_type": "audio", "codec_name": "flac" }], "format": { "format_name": "flac" } }"#,
+        );
+        assert_eq!(
+            EncoderSettings::matching(&info),
+            Err(String::from("Error: input file has no video stream."))
+        );
+    }
+
+    #[test]
+    fn concat_script() {
+        let files = [
+            String::from("/tmp/media-cutter/smartcut_0.mkv"),
+            String::from("/tmp/it's here/smartcut_1.mkv"),
+        ];
+        assert_eq!(
+            concat_list(&files),
+            "ffconcat version 1.0\nfile '/tmp/media-cutter/smartcut_0.mkv'\nfile '/tmp/it'\\''s here/smartcut_1.mkv'\n"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::path::Path;
+
+use crate::{image_sequence, is_url, Config};
+
+// rule of `Config::validate` about a single field
+type FieldCheck = fn(&Config) -> Result<(), String>;
+
+/// Part of the settings an error is attached to, so that it can be shown
+/// next to the widget editing it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Field {
+    InputFile,
+    OutputFile,
+    /// Start and end times.
+    Times,
+    HighPass,
+    LowPass,
+    NoiseProfile,
+    Lut,
+    /// Errors spanning several settings, from `Config::validate`.
+    Other,
+}
+
+/// Every problem of a configuration at once, unlike `Config::validate` which
+/// stops at the first one.
+#[derive(Default, PartialEq, Debug)]
+pub struct ValidationState {
+    errors: Vec<(Field, String)>,
+}
+
+impl ValidationState {
+    pub fn new(conf: &Config) -> Self {
+        let mut state = Self::default();
+
+        if conf.input_file.is_empty() {
+            state.push(Field::InputFile, "No input file specified.");
+        } else if is_url(&conf.input_file) {
+            // network inputs are only known to be missing once ffmpeg tries them
+            if let Err(e) = conf.validate_input_url() {
+                state.errors.push((Field::InputFile, e));
+            }
+        } else if conf.image_sequence.is_some() {
+            if image_sequence::first_number(&conf.input_file).is_none() {
+                state.push(
+                    Field::InputFile,
+                    "No picture matches the image sequence pattern.",
+                );
+            }
+        } else if !Path::new(&conf.input_file).is_file() {
+            state.push(Field::InputFile, "Input file not found.");
+        }
+
+        if conf.output_pattern.is_none() {
+            if conf.output_file.is_empty() {
+                state.push(Field::OutputFile, "No output file specified.");
+            } else if conf.writes_to_stdout() {
+                state.push(
+                    Field::OutputFile,
+                    "Writing to the standard output only works from the command line: choose an output file.",
+                );
+            }
+        }
+        if let Err(e) = conf.validate_output_file() {
+            state.errors.push((Field::OutputFile, e));
+        }
+
+        let field_checks: [(Field, FieldCheck); 5] = [
+            (Field::Times, Config::validate_times),
+            (Field::HighPass, Config::validate_high_pass),
+            (Field::LowPass, Config::validate_low_pass),
+            (Field::NoiseProfile, Config::validate_noise_profile),
+            (Field::Lut, Config::validate_lut),
+        ];
+        for (field, check) in &field_checks {
+            if let Err(e) = check(conf) {
+                state.errors.push((*field, e));
+            }
+        }
+        if let Some(ref noise_profile_file) = conf.noise_profile_file {
+            if !noise_profile_file.is_empty() && !Path::new(noise_profile_file).is_file() {
+                state.push(Field::NoiseProfile, "Noise profile file not found.");
+            }
+        }
+
+        match conf.validate_settings() {
+            // the standard output is already rejected above
+            Err(_) if conf.validate_stdout_output().is_err() => {}
+            Err(e) => state.errors.push((Field::Other, e)),
+            Ok(()) => {}
+        }
+
+        state.errors.sort_by_key(|&(field, _)| field);
+        state
+    }
+
+    fn push(&mut self, field: Field, message: &str) {
+        self.errors.push((field, String::from(message)));
+    }
+
+    /// First error of `field`, if any.
+    pub fn error(&self, field: Field) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|&&(error_field, _)| error_field == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    pub fn can_process(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Previews are not written anywhere: output errors do not matter.
+    pub fn can_preview(&self) -> bool {
+        self.errors
+            .iter()
+            .all(|&(field, _)| field == Field::OutputFile)
+    }
+
+    /// All the errors, one per line.
+    pub fn summary(&self) -> String {
+        self.errors
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ImageSequence;
+    use chrono::Duration;
+    use std::path::PathBuf;
+
+    fn valid_config() -> Config {
+        let mut conf = Config::new();
+        // any existing file will do
+        conf.input_file = String::from(file!());
+        conf.output_file = String::from("out.mp4");
+        conf.from_time = Duration::seconds(1);
+        conf.to_time = Duration::seconds(5);
+        conf
+    }
+
+    #[test]
+    fn valid() {
+        let state = ValidationState::new(&valid_config());
+        assert!(state.can_process());
+        assert!(state.can_preview());
+        assert_eq!(state.summary(), "");
+    }
+
+    #[test]
+    fn missing_files() {
+        let mut conf = valid_config();
+        conf.output_file = String::new();
+        let state = ValidationState::new(&conf);
+        assert!(!state.can_process());
+        // nothing is written by a preview
+        assert!(state.can_preview());
+        assert_eq!(
+            state.error(Field::OutputFile),
+            Some("No output file specified.")
+        );
+
+        // a pattern replaces the output file
+        conf.output_pattern = Some(String::from("{stem}_cut.{ext}"));
+        assert!(ValidationState::new(&conf).can_process());
+
+        conf.input_file = String::new();
+        let state = ValidationState::new(&conf);
+        assert!(!state.can_process());
+        assert!(!state.can_preview());
+        assert_eq!(
+            state.error(Field::InputFile),
+            Some("No input file specified.")
+        );
+
+        conf.input_file = String::from("/nowhere/in.mp4");
+        assert_eq!(
+            ValidationState::new(&conf).error(Field::InputFile),
+            Some("Input file not found.")
+        );
+        conf.input_file = String::from("https://example.com/in.mp4");
+        assert!(ValidationState::new(&conf).can_process());
+
+        conf.input_file = String::from("/nowhere/frame_%05d.png");
+        conf.image_sequence = Some(ImageSequence {
+            framerate: 25.0,
+            start_number: None,
+        });
+        assert_eq!(
+            ValidationState::new(&conf).error(Field::InputFile),
+            Some("No picture matches the image sequence pattern.")
+        );
+    }
+
+    #[test]
+    fn network_inputs() {
+        let mut conf = valid_config();
+        conf.input_file = String::from("rtmp://live.example.com/app/key");
+        assert!(ValidationState::new(&conf).can_process());
+
+        conf.input_file = String::from("gopher://example.com/in.mp4");
+        let state = ValidationState::new(&conf);
+        assert!(!state.can_preview());
+        assert!(state
+            .error(Field::InputFile)
+            .unwrap()
+            .starts_with("Error: unsupported input protocol gopher://"));
+        assert_eq!(state.error(Field::Other), None);
+
+        conf.input_file = String::from("https://example.com/in.mp4");
+        conf.noise_profile_file = Some(String::from(file!()));
+        conf.noise_reduction_amount = Some(0.2);
+        let state = ValidationState::new(&conf);
+        assert_eq!(
+            state.error(Field::InputFile),
+            Some("Error: noise reduction runs sox, which cannot read network inputs: download the input first.")
+        );
+        assert_eq!(state.summary().lines().count(), 1);
+    }
+
+    #[test]
+    fn stdout_output() {
+        let mut conf = valid_config();
+        conf.output_file = String::from(crate::STDOUT_OUTPUT);
+        let state = ValidationState::new(&conf);
+        assert!(!state.can_process());
+        assert!(state.can_preview());
+        assert_eq!(
+            state.error(Field::OutputFile),
+            Some("Writing to the standard output only works from the command line: choose an output file.")
+        );
+        // the missing output format is not reported on top of it
+        assert_eq!(state.summary().lines().count(), 1);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let mut conf = valid_config();
+        conf.output_file = conf.input_file.clone();
+        conf.to_time = conf.from_time;
+        conf.high_pass_filter = Some(5000);
+        conf.low_pass_filter = Some(200);
+        conf.noise_profile_file = Some(String::new());
+        conf.lut = Some(PathBuf::from("look.png"));
+        conf.ignore_audio = true;
+        conf.ignore_video = true;
+
+        let state = ValidationState::new(&conf);
+        assert!(!state.can_process());
+        assert!(!state.can_preview());
+        for &field in &[
+            Field::OutputFile,
+            Field::Times,
+            Field::HighPass,
+            Field::NoiseProfile,
+            Field::Lut,
+            Field::Other,
+        ] {
+            assert!(state.error(field).is_some(), "{:?}", field);
+        }
+        assert_eq!(state.error(Field::InputFile), None);
+        assert_eq!(state.error(Field::LowPass), None);
+        assert_eq!(state.summary().lines().count(), 6);
+    }
+
+    #[test]
+    fn config_errors_are_not_duplicated() {
+        let mut conf = valid_config();
+        conf.lut = Some(PathBuf::from("look.png"));
+        let state = ValidationState::new(&conf);
+        assert!(state.error(Field::Lut).is_some());
+        assert_eq!(state.error(Field::Other), None);
+
+        // whole file processing ignores the times
+        let mut conf = valid_config();
+        conf.to_time = Duration::zero();
+        assert!(!ValidationState::new(&conf).can_process());
+        conf.process_whole_file = true;
+        assert!(ValidationState::new(&conf).can_process());
+    }
+}
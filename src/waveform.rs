@@ -0,0 +1,323 @@
+use std::{
+    io::Read,
+    process::{Command, Stdio},
+};
+
+use crate::{logging::LogLaunch, probe::MediaInfo, processing, Config};
+
+type Result<T> = std::result::Result<T, String>;
+
+/// Rate the audio is resampled to before computing peaks, plenty for display.
+pub const PEAK_SAMPLE_RATE: u32 = 8000;
+
+// bytes of s16le samples read from ffmpeg at once
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Minimum and maximum amplitude, in `-1.0..=1.0`, of `buckets` consecutive
+/// slices of the audio mixed down to mono, decoded by the ffmpeg `conf` runs.
+pub fn extract_peaks(conf: &Config, path: &str, buckets: usize) -> Result<Vec<(f32, f32)>> {
+    extract_peaks_with_progress(conf, path, buckets, |_| {})
+}
+
+/// Same as `extract_peaks`, `on_progress` is called with the done fraction.
+pub fn extract_peaks_with_progress<F>(
+    conf: &Config,
+    path: &str,
+    buckets: usize,
+    mut on_progress: F,
+) -> Result<Vec<(f32, f32)>>
+where
+    F: FnMut(f64),
+{
+    let duration = MediaInfo::probe(path)?
+        .duration
+        .ok_or_else(|| String::from("Error: input file duration is unknown."))?;
+    let total_samples = (duration * f64::from(PEAK_SAMPLE_RATE)).ceil() as usize;
+
+    let ffmpeg = processing::ffmpeg_program(conf);
+    let mut child = Command::new(&ffmpeg)
+        .args(make_ffmpeg_pcm_args(path))
+        .logged()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}.\nError: {}", ffmpeg, e))?;
+
+    let mut stdout = child.stdout.take().unwrap();
+    let mut peaks = PeakBuckets::new(total_samples, buckets);
+    let mut buffer = vec![0; READ_CHUNK_SIZE];
+    // a sample may be split across two reads
+    let mut pending_byte = None;
+    let mut read_samples = 0;
+
+    loop {
+        let read = stdout
+            .read(&mut buffer)
+            .map_err(|e| format!("Could not read decoded audio.\nError: {}", e))?;
+        if read == 0 {
+            break;
+        }
+
+        let mut bytes = buffer[..read].iter().copied();
+        if let Some(low) = pending_byte.take() {
+            if let Some(high) = bytes.next() {
+                peaks.push(sample_from_bytes(low, high));
+                read_samples += 1;
+            }
+        }
+        while let Some(low) = bytes.next() {
+            match bytes.next() {
+                Some(high) => {
+                    peaks.push(sample_from_bytes(low, high));
+                    read_samples += 1;
+                }
+                None => pending_byte = Some(low),
+            }
+        }
+
+        if total_samples > 0 {
+            on_progress((read_samples as f64 / total_samples as f64).min(1.0));
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for {}.\nError: {}", ffmpeg, e))?;
+    if !status.success() {
+        return Err(format!("Could not decode the audio of {}.", path));
+    }
+
+    on_progress(1.0);
+    Ok(peaks.finish())
+}
+
+fn make_ffmpeg_pcm_args(path: &str) -> Vec<String> {
+    vec![
+        String::from("-v"),
+        String::from("error"),
+        String::from("-i"),
+        String::from(path),
+        String::from("-vn"),
+        String::from("-ac"),
+        String::from("1"),
+        String::from("-ar"),
+        PEAK_SAMPLE_RATE.to_string(),
+        String::from("-f"),
+        String::from("s16le"),
+        String::from("-"),
+    ]
+}
+
+fn sample_from_bytes(low: u8, high: u8) -> f32 {
+    f32::from(i16::from_le_bytes([low, high])) / 32768.0
+}
+
+/// Downsample `samples` into at most `buckets` min/max pairs.
+pub fn downsample(samples: &[f32], buckets: usize) -> Vec<(f32, f32)> {
+    let mut peaks = PeakBuckets::new(samples.len(), buckets);
+    for &sample in samples {
+        peaks.push(sample);
+    }
+    peaks.finish()
+}
+
+// streaming min/max downsampling: samples are not kept in memory
+struct PeakBuckets {
+    samples_per_bucket: usize,
+    max_buckets: usize,
+    current: Option<(f32, f32)>,
+    count: usize,
+    peaks: Vec<(f32, f32)>,
+}
+
+impl PeakBuckets {
+    fn new(total_samples: usize, buckets: usize) -> Self {
+        let buckets = buckets.max(1);
+        Self {
+            // round up so that the estimated samples fit in `buckets`
+            samples_per_bucket: ((total_samples + buckets - 1) / buckets).max(1),
+            max_buckets: buckets,
+            current: None,
+            count: 0,
+            peaks: Vec::with_capacity(buckets),
+        }
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.current = Some(match self.current {
+            Some((min, max)) => (min.min(sample), max.max(sample)),
+            None => (sample, sample),
+        });
+        self.count += 1;
+
+        // samples beyond the estimate end up in the last bucket
+        if self.count == self.samples_per_bucket && self.peaks.len() + 1 < self.max_buckets {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(peak) = self.current.take() {
+            self.peaks.push(peak);
+        }
+        self.count = 0;
+    }
+
+    fn finish(mut self) -> Vec<(f32, f32)> {
+        self.flush();
+        self.peaks
+    }
+}
+
+/// Visible part of a waveform: maps pixels to times, in seconds, and back.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    /// Length of the whole media.
+    pub duration: f64,
+    /// Width of the widget in pixels.
+    pub width: f64,
+    /// 1.0 shows the whole media, 2.0 half of it…
+    pub zoom: f64,
+    /// Time at the left edge.
+    pub offset: f64,
+}
+
+impl Viewport {
+    pub const MAX_ZOOM: f64 = 100_000.0;
+
+    pub fn new(duration: f64, width: f64) -> Self {
+        Self {
+            duration,
+            width,
+            zoom: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    pub fn visible_duration(&self) -> f64 {
+        self.duration / self.zoom
+    }
+
+    pub fn time_at(&self, x: f64) -> f64 {
+        if self.width <= 0.0 {
+            return self.offset;
+        }
+        self.offset + x / self.width * self.visible_duration()
+    }
+
+    pub fn x_at(&self, time: f64) -> f64 {
+        let visible_duration = self.visible_duration();
+        if visible_duration <= 0.0 {
+            return 0.0;
+        }
+        (time - self.offset) / visible_duration * self.width
+    }
+
+    /// Multiply the zoom by `factor`, keeping the time under `anchor_x` in place.
+    pub fn zoom_by(&mut self, factor: f64, anchor_x: f64) {
+        let anchor_time = self.time_at(anchor_x);
+        self.zoom = (self.zoom * factor).clamp(1.0, Self::MAX_ZOOM);
+        self.offset = anchor_time - anchor_x / self.width.max(1.0) * self.visible_duration();
+        self.clamp_offset();
+    }
+
+    /// Move the visible part by `dx` pixels, positive goes forward.
+    pub fn scroll_by(&mut self, dx: f64) {
+        self.offset += dx / self.width.max(1.0) * self.visible_duration();
+        self.clamp_offset();
+    }
+
+    /// Round `time` to the closest sample once a sample is at least a pixel wide.
+    pub fn snap(&self, time: f64, sample_rate: u32) -> f64 {
+        let sample_rate = f64::from(sample_rate);
+        if sample_rate <= 0.0 || self.x_at(self.offset + 1.0 / sample_rate) < 1.0 {
+            return time;
+        }
+        (time * sample_rate).round() / sample_rate
+    }
+
+    fn clamp_offset(&mut self) {
+        let max_offset = (self.duration - self.visible_duration()).max(0.0);
+        self.offset = self.offset.clamp(0.0, max_offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downsample_buckets() {
+        let samples = [0.1, -0.5, 0.3, 0.9, -0.2, 0.0, 0.4, -0.8];
+        assert_eq!(
+            downsample(&samples, 4),
+            vec![(-0.5, 0.1), (0.3, 0.9), (-0.2, 0.0), (-0.8, 0.4)]
+        );
+        assert_eq!(downsample(&samples, 1), vec![(-0.8, 0.9)]);
+
+        // uneven split: 3 samples per bucket, the last one is partial
+        assert_eq!(
+            downsample(&samples, 3),
+            vec![(-0.5, 0.3), (-0.2, 0.9), (-0.8, 0.4)]
+        );
+
+        // more buckets than samples
+        assert_eq!(downsample(&samples[..2], 4), vec![(0.1, 0.1), (-0.5, -0.5)]);
+        assert!(downsample(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn samples_beyond_estimate_go_to_last_bucket() {
+        let mut peaks = PeakBuckets::new(4, 2);
+        for &sample in &[0.1, 0.2, 0.3, 0.4, -1.0, 1.0] {
+            peaks.push(sample);
+        }
+        assert_eq!(peaks.finish(), vec![(0.1, 0.2), (-1.0, 1.0)]);
+    }
+
+    #[test]
+    fn s16le_samples() {
+        assert_eq!(sample_from_bytes(0x00, 0x00), 0.0);
+        assert_eq!(sample_from_bytes(0x00, 0x80), -1.0);
+        assert_eq!(sample_from_bytes(0x00, 0x40), 0.5);
+    }
+
+    #[test]
+    fn pixel_time_mapping() {
+        let mut viewport = Viewport::new(100.0, 1000.0);
+        assert_eq!(viewport.time_at(0.0), 0.0);
+        assert_eq!(viewport.time_at(500.0), 50.0);
+        assert_eq!(viewport.x_at(25.0), 250.0);
+
+        viewport.zoom_by(4.0, 500.0);
+        assert_eq!(viewport.visible_duration(), 25.0);
+        // the anchor keeps its time
+        assert_eq!(viewport.time_at(500.0), 50.0);
+        assert_eq!(viewport.offset, 37.5);
+        assert_eq!(viewport.x_at(37.5), 0.0);
+        assert_eq!(viewport.x_at(62.5), 1000.0);
+
+        viewport.scroll_by(400.0);
+        assert_eq!(viewport.offset, 47.5);
+        // cannot scroll past either end
+        viewport.scroll_by(1e6);
+        assert_eq!(viewport.offset, 75.0);
+        viewport.scroll_by(-1e6);
+        assert_eq!(viewport.offset, 0.0);
+
+        viewport.zoom_by(0.01, 0.0);
+        assert_eq!(viewport, Viewport::new(100.0, 1000.0));
+    }
+
+    #[test]
+    fn snapping_to_samples() {
+        let mut viewport = Viewport::new(10.0, 1000.0);
+        // a sample is far less than a pixel wide: no snapping
+        assert_eq!(viewport.snap(1.23456, 48_000), 1.23456);
+
+        viewport.zoom_by(Viewport::MAX_ZOOM, 0.0);
+        let snapped = viewport.snap(0.000_03, 48_000);
+        assert!((snapped - 1.0 / 48_000.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use media_cutter::waveform::Viewport;
+
+// distance in pixels under which a click grabs a selection edge
+const EDGE_GRAB_DISTANCE: f64 = 6.0;
+const ZOOM_STEP: f64 = 1.25;
+const SCROLL_STEP: f64 = 50.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Edge {
+    Start,
+    End,
+}
+
+struct ViewState {
+    peaks: Vec<(f32, f32)>,
+    viewport: Viewport,
+    sample_rate: Option<u32>,
+    /// Start and end of the selection, in seconds.
+    selection: (f64, f64),
+    dragging: Option<Edge>,
+    /// Fraction of the peaks extracted, `None` when not loading.
+    progress: Option<f64>,
+    /// Incremented on each load so that results of a previous file are dropped.
+    load_id: u64,
+    on_selection_changed: Option<Box<dyn Fn(f64, f64)>>,
+}
+
+/// Waveform of the input file with draggable selection edges.
+/// Ctrl+scroll zooms around the pointer, scroll moves along the file.
+#[derive(Clone)]
+pub struct WaveformView {
+    area: gtk::DrawingArea,
+    state: Rc<RefCell<ViewState>>,
+}
+
+impl WaveformView {
+    pub fn new() -> Self {
+        let area = gtk::DrawingArea::new();
+        area.set_size_request(-1, 100);
+        area.add_events(
+            (gdk::EventMask::BUTTON_PRESS_MASK
+                | gdk::EventMask::BUTTON_RELEASE_MASK
+                | gdk::EventMask::POINTER_MOTION_MASK
+                | gdk::EventMask::SCROLL_MASK)
+                .bits() as i32,
+        );
+
+        let view = Self {
+            area,
+            state: Rc::new(RefCell::new(ViewState {
+                peaks: Vec::new(),
+                viewport: Viewport::new(0.0, 0.0),
+                sample_rate: None,
+                selection: (0.0, 0.0),
+                dragging: None,
+                progress: None,
+                load_id: 0,
+                on_selection_changed: None,
+            })),
+        };
+        view.connect_events();
+        view
+    }
+
+    pub fn widget(&self) -> &gtk::DrawingArea {
+        &self.area
+    }
+
+    /// Start showing a new file, returns the id to pass to `is_current_load`.
+    pub fn begin_load(&self) -> u64 {
+        self.state.borrow_mut().load_id += 1;
+        self.set_progress(0.0);
+        self.state.borrow().load_id
+    }
+
+    pub fn is_current_load(&self, load_id: u64) -> bool {
+        self.state.borrow().load_id == load_id
+    }
+
+    /// Clear the waveform and show the extraction progress instead.
+    pub fn set_progress(&self, progress: f64) {
+        let mut state = self.state.borrow_mut();
+        state.peaks.clear();
+        state.progress = Some(progress);
+        drop(state);
+        self.area.queue_draw();
+    }
+
+    pub fn set_peaks(&self, peaks: Vec<(f32, f32)>, duration: f64, sample_rate: Option<u32>) {
+        let mut state = self.state.borrow_mut();
+        state.peaks = peaks;
+        state.viewport = Viewport::new(duration, state.viewport.width);
+        state.sample_rate = sample_rate;
+        state.progress = None;
+        drop(state);
+        self.area.queue_draw();
+    }
+
+    pub fn clear(&self) {
+        self.state.borrow_mut().load_id += 1;
+        self.set_peaks(Vec::new(), 0.0, None);
+    }
+
+    /// Move the selection edges, without notifying `connect_selection_changed`.
+    pub fn set_selection(&self, start: f64, end: f64) {
+        self.state.borrow_mut().selection = (start, end);
+        self.area.queue_draw();
+    }
+
+    pub fn connect_selection_changed<F: Fn(f64, f64) + 'static>(&self, f: F) {
+        self.state.borrow_mut().on_selection_changed = Some(Box::new(f));
+    }
+
+    fn connect_events(&self) {
+        let state = self.state.clone();
+        self.area.connect_draw(move |area, cr| {
+            let mut state = state.borrow_mut();
+            let width = f64::from(area.get_allocated_width());
+            let height = f64::from(area.get_allocated_height());
+            state.viewport.width = width;
+
+            cr.set_source_rgb(0.12, 0.12, 0.12);
+            cr.rectangle(0.0, 0.0, width, height);
+            cr.fill();
+
+            if let Some(progress) = state.progress {
+                cr.set_source_rgb(0.3, 0.5, 0.8);
+                cr.rectangle(0.0, height / 2.0 - 2.0, width * progress, 4.0);
+                cr.fill();
+                return Inhibit(false);
+            }
+
+            if state.peaks.is_empty() || state.viewport.duration <= 0.0 {
+                return Inhibit(false);
+            }
+
+            // selection background
+            let viewport = state.viewport;
+            let (start, end) = state.selection;
+            let start_x = viewport.x_at(start);
+            let end_x = viewport.x_at(end);
+            cr.set_source_rgba(0.3, 0.5, 0.8, 0.35);
+            cr.rectangle(start_x, 0.0, end_x - start_x, height);
+            cr.fill();
+
+            // one vertical line per pixel, from the min to the max of the buckets it covers
+            let bucket_duration = viewport.duration / state.peaks.len() as f64;
+            let middle = height / 2.0;
+            cr.set_source_rgb(0.55, 0.8, 0.55);
+            cr.set_line_width(1.0);
+            for x in 0..width as usize {
+                let x = x as f64;
+                let first = (viewport.time_at(x) / bucket_duration) as usize;
+                let last = ((viewport.time_at(x + 1.0) / bucket_duration) as usize)
+                    .max(first + 1)
+                    .min(state.peaks.len());
+                if first >= last {
+                    break;
+                }
+                let (min, max) = state.peaks[first..last]
+                    .iter()
+                    .fold((0.0f32, 0.0f32), |(min, max), &(low, high)| {
+                        (min.min(low), max.max(high))
+                    });
+                cr.move_to(x + 0.5, middle - f64::from(max) * middle);
+                cr.line_to(x + 0.5, middle - f64::from(min) * middle);
+            }
+            cr.stroke();
+
+            // selection edges
+            cr.set_source_rgb(0.9, 0.9, 0.3);
+            for &x in &[start_x, end_x] {
+                cr.move_to(x, 0.0);
+                cr.line_to(x, height);
+            }
+            cr.stroke();
+
+            Inhibit(false)
+        });
+
+        let state = self.state.clone();
+        self.area.connect_button_press_event(move |_, event| {
+            let mut state = state.borrow_mut();
+            if event.get_button() != 1 || state.peaks.is_empty() {
+                return Inhibit(false);
+            }
+
+            let (x, _) = event.get_position();
+            let start_distance = (state.viewport.x_at(state.selection.0) - x).abs();
+            let end_distance = (state.viewport.x_at(state.selection.1) - x).abs();
+            state.dragging = if start_distance.min(end_distance) > EDGE_GRAB_DISTANCE {
+                None
+            } else if start_distance <= end_distance {
+                Some(Edge::Start)
+            } else {
+                Some(Edge::End)
+            };
+            Inhibit(state.dragging.is_some())
+        });
+
+        let state = self.state.clone();
+        self.area.connect_button_release_event(move |_, _| {
+            state.borrow_mut().dragging = None;
+            Inhibit(false)
+        });
+
+        let state = self.state.clone();
+        self.area.connect_motion_notify_event(move |area, event| {
+            let selection = {
+                let mut state = state.borrow_mut();
+                let edge = match state.dragging {
+                    Some(edge) => edge,
+                    None => return Inhibit(false),
+                };
+
+                let (x, _) = event.get_position();
+                let mut time = state
+                    .viewport
+                    .time_at(x)
+                    .clamp(0.0, state.viewport.duration);
+                if let Some(sample_rate) = state.sample_rate {
+                    time = state.viewport.snap(time, sample_rate);
+                }
+
+                // edges cannot cross each other
+                match edge {
+                    Edge::Start => state.selection.0 = time.min(state.selection.1),
+                    Edge::End => state.selection.1 = time.max(state.selection.0),
+                }
+                state.selection
+            };
+
+            area.queue_draw();
+            // the callback may call back into the view: state must not be borrowed
+            let callback = state.borrow_mut().on_selection_changed.take();
+            if let Some(ref callback) = callback {
+                callback(selection.0, selection.1);
+            }
+            state.borrow_mut().on_selection_changed = callback;
+            Inhibit(true)
+        });
+
+        let state = self.state.clone();
+        self.area.connect_scroll_event(move |area, event| {
+            let mut state = state.borrow_mut();
+            let (x, _) = event.get_position();
+            let zoom = event.get_state().contains(gdk::ModifierType::CONTROL_MASK);
+            match (event.get_direction(), zoom) {
+                (gdk::ScrollDirection::Up, true) => state.viewport.zoom_by(ZOOM_STEP, x),
+                (gdk::ScrollDirection::Down, true) => state.viewport.zoom_by(1.0 / ZOOM_STEP, x),
+                (gdk::ScrollDirection::Up, false) | (gdk::ScrollDirection::Left, _) => {
+                    state.viewport.scroll_by(-SCROLL_STEP)
+                }
+                (gdk::ScrollDirection::Down, false) | (gdk::ScrollDirection::Right, _) => {
+                    state.viewport.scroll_by(SCROLL_STEP)
+                }
+                _ => return Inhibit(false),
+            }
+            area.queue_draw();
+            Inhibit(true)
+        });
+    }
+}